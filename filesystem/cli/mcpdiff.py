@@ -4,19 +4,44 @@
 import sys
 import argparse
 import os
+import json
+import shlex
 import shutil
+import subprocess
+import tempfile
+import time
+from dataclasses import asdict, dataclass, field
 from pathlib import Path
-from typing import List, Dict, Any, Optional, Tuple
+from typing import List, Dict, Any, Optional, Set, Tuple
 
 # Import from local utility and history modules
 import mcpdiff_utils as utils
 import mcpdiff_history as history
+import mcpdiff_acl as acl
+import mcpdiff_append as append
+import mcpdiff_audit as audit
+import mcpdiff_api as api
+import mcpdiff_daemon as daemon
+import mcpdiff_migration as migration
+import mcpdiff_config as config
+import mcpdiff_git as git
+import mcpdiff_hooks as hooks
+import mcpdiff_patch as patch
+import mcpdiff_ignore as ignore
+import mcpdiff_metrics as metrics
+import mcpdiff_replay as replay
+import mcpdiff_schema as schema
+import mcpdiff_snapshot as snapshots
+import mcpdiff_split as split
+import mcpdiff_symbols as symbols
 from mcpdiff_utils import (
     log,
     HistoryError,
     AmbiguousIDError,
+    CorruptLogError,
+    PermissionDeniedError,
+    WorkspaceNotFoundError,
     LOCK_TIMEOUT,
-    HISTORY_DIR_NAME,
     CHECKPOINTS_DIR,
 )
 
@@ -24,6 +49,49 @@ from mcpdiff_utils import (
 # --- Command Handlers ---
 
 
+def _status_filters(args: argparse.Namespace) -> Dict[str, Any]:
+    """The iter_filtered_entries() keyword arguments of status's filter options."""
+    return {
+        "conv_id": args.conv,
+        "file_path": args.file,
+        "status": args.status,
+        "time_filter": args.time,
+        "op_type": args.op,
+        "since_edit_id": args.since_edit_id,
+        "until_edit_id": args.until_edit_id,
+        "session_id": args.session,
+        "before": _parse_time_option(args.before, "--before"),
+        "after": _parse_time_option(args.after, "--after"),
+    }
+
+
+def _print_status_count(
+    args: argparse.Namespace, workspace_root: Path, history_root: Path
+) -> None:
+    """status --count: the number of matching entries, without listing them."""
+    filters = _status_filters(args)
+    count = daemon.fetch_count(workspace_root, filters, args.group_by, args.no_ignore)
+    if count is None:
+        entries = history.find_all_entries(history_root, lock_timeout=args.timeout)
+        if not args.no_ignore:
+            ignored = ignore.load_ignore_matcher(workspace_root)
+            entries = [e for e in entries if not ignored.matches_entry(e)]
+        count = history.count_filtered_entries(entries, args.group_by, **filters)
+    else:
+        log.debug("Got the count from the daemon")
+
+    if args.json:
+        if args.group_by:
+            print(json.dumps({"count": sum(count.values()), "groups": count}))
+        else:
+            print(json.dumps({"count": count}))
+    elif args.group_by:
+        for key, n in count.items():
+            print(f"{n:>6}  {key}")
+    else:
+        print(count)
+
+
 def handle_status(
     args: argparse.Namespace,
     workspace_root: Path,
@@ -32,52 +100,106 @@ def handle_status(
 ) -> None:
     """Handle the status command."""
     log.debug("Processing status command")
+    if args.group_by and not args.count:
+        raise HistoryError("--group-by needs --count.")
+    if args.count:
+        _print_status_count(args, workspace_root, history_root)
+        return
+    # Read by the handler: a running daemon answers without reading the logs
+    all_entries = daemon.fetch_entries(workspace_root)
+    if all_entries is None:
+        all_entries = history.find_all_entries(history_root, lock_timeout=args.timeout)
+    else:
+        log.debug(f"Got {len(all_entries)} entries from the daemon")
+    fields = history.parse_status_fields(args.fields) if args.fields else None
 
+    if not all_entries and args.json:
+        print("[]")
+        return
     if not all_entries:
         print(f"{utils.COLOR_YELLOW}No edit history entries found.{utils.COLOR_RESET}")
         return
 
+    # Edits of paths listed in .mcp/ignore are left out unless --no-ignore
+    visible_entries = all_entries
+    if not args.no_ignore:
+        ignored = ignore.load_ignore_matcher(workspace_root)
+        visible_entries = [e for e in all_entries if not ignored.matches_entry(e)]
+    hidden = len(all_entries) - len(visible_entries)
+
     # Apply filters - Use limit=0 to show all if limit not specified or <= 0
     display_limit = args.limit if args.limit > 0 else 0
-    # Filter returns newest first if limited
+    # Newest first, also when all are shown
     filtered_entries = history.filter_entries(
-        all_entries,
-        conv_id=args.conv,
-        file_path=args.file,
-        status=args.status,
-        time_filter=args.time,
-        op_type=args.op,
-        limit=display_limit
-        or None,  # Pass None if limit is 0 to get all (sorted newest first)
+        visible_entries, limit=display_limit, **_status_filters(args)
     )
 
+    if args.json:
+        # Always the complete entries; --fields only shapes the table
+        print(
+            json.dumps(
+                [
+                    {
+                        k: v
+                        for k, v in utils.encode_log_paths(e).items()
+                        if k != "log_file_source"
+                    }
+                    for e in filtered_entries
+                ],
+                indent=2,
+            )
+        )
+        return
+
     if not filtered_entries:
         print(
             f"{utils.COLOR_YELLOW}No entries match the specified filters.{utils.COLOR_RESET}"
         )
         # Optionally print filter criteria here if useful
+        if hidden:
+            print(_hidden_by_ignore_note(hidden))
         return
 
     # Print header and entries
-    history.print_entry_list_header()
-    for entry in (
-        filtered_entries
-    ):  # Already sorted newest first by filter_entries if limit used
-        print(history.format_entry_summary(entry))
+    if fields:
+        history.print_entry_fields(filtered_entries, fields, history_root)
+    else:
+        tags = history.get_tags_by_conversation(history.load_tags(history_root))
+        history.print_entry_list_header(tags)
+        children = history.count_children(all_entries)
+        for entry in filtered_entries:  # Already sorted newest first
+            print(
+                history.format_entry_summary(
+                    entry, children.get(entry.get("edit_id"), 0), tags
+                )
+            )
 
     # Print summary
     total_shown = len(filtered_entries)
     total_available = len(
-        all_entries
+        visible_entries
     )  # Or count after non-limit filters? Let's use total.
     print(f"\nShowing {total_shown} of {total_available} total entries.")
     if display_limit > 0 and total_shown == display_limit:
         print(f"(Limited to {display_limit}, use -n 0 to show all matching)")
+    if hidden:
+        print(_hidden_by_ignore_note(hidden))
 
     # Show filter info if any were applied
-    filters_applied = args.conv or args.file or args.status or args.time or args.op
+    filters_applied = (
+        args.conv
+        or args.file
+        or args.status
+        or args.time
+        or args.op
+        or args.session
+        or args.before
+        or args.after
+    )
     if filters_applied:
         print(f"\n{utils.COLOR_CYAN}Applied filters:{utils.COLOR_RESET}")
+        if args.session:
+            print(f"  Session: {args.session}")
         if args.conv:
             print(f"  Conversation ID: {args.conv}")
         if args.file:
@@ -86,8 +208,48 @@ def handle_status(
             print(f"  Status: {args.status}")
         if args.time:
             print(f"  Time filter: {args.time}")
+        if args.after:
+            print(f"  After: {args.after}")
+        if args.before:
+            print(f"  Before: {args.before}")
         if args.op:
             print(f"  Operation type: {args.op}")
+        if args.since_edit_id:
+            print(f"  After edit: {args.since_edit_id}")
+        if args.until_edit_id:
+            print(f"  Before edit: {args.until_edit_id}")
+
+
+def _parse_time_option(value: Optional[str], option: str) -> Optional[float]:
+    """The epoch time given to a --before/--after/--since option, if any."""
+    if not value:
+        return None
+    when = utils.parse_time_point(value)
+    if when is None:
+        raise HistoryError(
+            f"Invalid {option} value: {value} (e.g. 2h, 7d, 2025-01-31 or 2025-01-31T12:30)"
+        )
+    return when
+
+
+def _add_time_range_arguments(parser: argparse.ArgumentParser, what: str) -> None:
+    """--before/--after, the time range status, accept and reject select by."""
+    parser.add_argument(
+        "--before",
+        metavar="TIME",
+        help=f"Only {what} logged before TIME: an ISO date or time (UTC unless it has an offset) or a duration ago, e.g. 2h.",
+    )
+    parser.add_argument(
+        "--after",
+        metavar="TIME",
+        help=f"Only {what} logged at or after TIME (same syntax as --before).",
+    )
+
+
+def _hidden_by_ignore_note(hidden: int) -> str:
+    return (
+        f"({hidden} entries of paths in .mcp/ignore hidden, use --no-ignore to show)"
+    )
 
 
 def handle_show(
@@ -97,32 +259,81 @@ def handle_show(
     all_entries: List[Dict[str, Any]],
 ) -> None:
     """Handle the show command."""
+    if args.list_languages:
+        _print_syntax_languages()
+        return
     identifier = args.identifier
+    if not identifier:
+        raise HistoryError("show needs an edit or conversation ID.")
     log.debug(f"Processing show command for identifier: {identifier}")
+    if args.language:
+        utils.check_syntax_language(args.language)
+
+    raw = args.format == "raw"
+    color = not (raw or args.no_color or not sys.stdout.isatty())
+    colors = utils.get_colors(color)
+    theme = config.get_config_value(workspace_root, "syntax_theme")
+    tags = history.get_tags_by_conversation(history.load_tags(history_root))
+
+    def print_diff(entry: Dict[str, Any]) -> None:
+        diff_content = history.get_diff_for_entry(entry, history_root)
+        if diff_content and args.symbols and symbols.supports(entry.get("file_path")):
+            try:
+                before = replay.content_before(entry, all_entries, history_root)
+                diff_content = symbols.annotate_hunk_headers(
+                    diff_content, before, entry["file_path"]
+                )
+            except HistoryError as e:
+                log.debug(f"No symbols for {entry.get('edit_id')}: {e}")
+        if diff_content and args.ignore_whitespace:
+            # Display only: the stored diff is left as it is
+            diff_content = patch.omit_whitespace_changes(diff_content)
+        if diff_content and entry.get("file_encoding") == patch.BASE64_ENCODING:
+            # On stderr with --format raw, so the output stays a diff
+            file_path = utils.display_path(entry.get("file_path"))
+            print(
+                f"{colors.yellow}Warning: the diff of {file_path} is stored "
+                "base64-encoded, as the file is not valid UTF-8; its undecodable "
+                f"bytes are shown as replacement characters.{colors.reset}",
+                file=sys.stderr if raw else sys.stdout,
+            )
+        if raw:
+            if diff_content:
+                print(diff_content, end="" if diff_content.endswith("\n") else "\n")
+        else:
+            # --language is display only: the entry keeps its file_path
+            utils.print_diff_with_color(
+                diff_content, entry.get("file_path"), theme, args.language, color
+            )
 
     if not all_entries:
-        print(f"{utils.COLOR_YELLOW}No edit history entries found.{utils.COLOR_RESET}")
+        print(f"{colors.yellow}No edit history entries found.{colors.reset}")
         return
 
     # Try finding a single entry by edit ID prefix
     try:
         entry = history.find_entry_by_id(all_entries, identifier)
+        if entry and raw:
+            print_diff(entry)
+            return
         if entry:
             print(
-                f"\n{utils.COLOR_CYAN}Details for Edit: {entry.get('edit_id', 'N/A')}{utils.COLOR_RESET}"
+                f"\n{colors.cyan}Details for Edit: {entry.get('edit_id', 'N/A')}{colors.reset}"
             )
-            history.print_entry_list_header()
-            print(history.format_entry_summary(entry))
+            history.print_entry_list_header(tags, color)
+            print(history.format_entry_summary(entry, tags=tags, color=color))
             print("-" * 100)
-            diff_content = history.get_diff_for_entry(entry, history_root)
-            utils.print_diff_with_color(diff_content)
+            if args.show_details:
+                history.print_entry_details(entry, color)
+            print_diff(entry)
+            history.print_review_notes(entry, color)
             return
         # If find_entry_by_id returned None (not found), proceed to check conversation ID
     except AmbiguousIDError as e:
         # Ambiguous ID error already printed message, just exit handler
         log.warning(f"Ambiguous ID provided: {e}")
         # Let it fall through to conversation search? Or exit? Let's exit.
-        # print(f"{utils.COLOR_RED}Could not show details due to ambiguous ID.{utils.COLOR_RESET}")
+        # print(f"{colors.red}Could not show details due to ambiguous ID.{colors.reset}")
         raise e  # Re-raise for main handler to catch and report
     except KeyboardInterrupt:
         print("\nOperation cancelled during ambiguous ID selection.")
@@ -133,32 +344,136 @@ def handle_show(
 
     if not conv_entries:
         print(
-            f"{utils.COLOR_RED}No edit or conversation found matching identifier: {identifier}{utils.COLOR_RESET}"
+            f"{colors.red}No edit or conversation found matching identifier: {identifier}{colors.reset}"
         )
         return
 
+    if raw:
+        for entry in conv_entries:
+            print_diff(entry)
+        return
+
     # Found conversation entries
     conv_id = conv_entries[0].get(
         "conversation_id", identifier
     )  # Use ID from first entry
     print(
-        f"\n{utils.COLOR_CYAN}Showing {len(conv_entries)} edits for Conversation: {conv_id}{utils.COLOR_RESET}"
+        f"\n{colors.cyan}Showing {len(conv_entries)} edits for Conversation: {conv_id}{colors.reset}"
     )
 
     for i, entry in enumerate(conv_entries):  # Already sorted chronologically
         print("\n" + "=" * 80)
         print(
-            f"{utils.COLOR_BLUE}Edit {i + 1}/{len(conv_entries)} - ID: {entry.get('edit_id', 'N/A')}{utils.COLOR_RESET}"
+            f"{colors.blue}Edit {i + 1}/{len(conv_entries)} - ID: {entry.get('edit_id', 'N/A')}{colors.reset}"
         )
-        history.print_entry_list_header()
-        print(history.format_entry_summary(entry))
+        history.print_entry_list_header(tags, color)
+        print(history.format_entry_summary(entry, tags=tags, color=color))
         print("-" * 100)
-        diff_content = history.get_diff_for_entry(entry, history_root)
-        utils.print_diff_with_color(diff_content)
+        if args.show_details:
+            history.print_entry_details(entry, color)
+        print_diff(entry)
+        history.print_review_notes(entry, color)
 
     print("\n" + "=" * 80)
 
 
+def _print_syntax_languages() -> None:
+    """Prints the languages show --language takes: `Name: alias, ...` each."""
+    languages = utils.list_syntax_languages()
+    if languages is None:
+        raise HistoryError(
+            "Pygments is not installed, so show does no syntax highlighting. Install it with: pip install pygments"
+        )
+    for name, aliases in languages:
+        print(f"{name}: {', '.join(aliases)}")
+
+
+def _run_pre_hook(workspace_root: Path, action: str, entry: Dict[str, Any]) -> bool:
+    """Runs the pre-<action> hook for an edit. Returns False if it vetoed the edit."""
+    exit_code = hooks.run_hook(workspace_root, f"pre-{action}", entry)
+    if exit_code != 0:
+        print(
+            f"{utils.COLOR_RED}pre-{action} hook exited with code {exit_code}; not {action}ing edit {entry.get('edit_id', 'N/A')}.{utils.COLOR_RESET}"
+        )
+        return False
+    return True
+
+
+def _run_post_hook(workspace_root: Path, action: str, entry: Dict[str, Any]) -> None:
+    """Runs the post-<action> hook for an edit. Failures are reported only."""
+    exit_code = hooks.run_hook(workspace_root, f"post-{action}", entry)
+    if exit_code != 0:
+        print(
+            f"{utils.COLOR_YELLOW}post-{action} hook exited with code {exit_code} for edit {entry.get('edit_id', 'N/A')}.{utils.COLOR_RESET}"
+        )
+
+
+def _check_acl(
+    action: str,
+    args: argparse.Namespace,
+    workspace_root: Path,
+    all_entries: List[Dict[str, Any]],
+) -> None:
+    """
+    Raises PermissionDeniedError if .mcp/acl.toml does not let the current user
+    accept or reject the edits the command names: those of the conversation or
+    the edit's conversation, and for a reject also the edits in other
+    conversations that are rejected with them.
+    """
+    rules = acl.load_acl(workspace_root)
+    if rules is None:
+        return
+    if args.edit_id:
+        entry = history.find_entry_by_id(all_entries, args.edit_id)
+        entries = [entry] if entry else []
+    else:
+        entries = history.find_entries_by_conversation(all_entries, args.conv)
+    if action == "reject":
+        entries += [e for e, _ in _unrejected_dependents(entries, all_entries)]
+    acl.check_permission(
+        rules,
+        action,
+        [e.get("conversation_id") for e in entries],
+        acl.current_user(),
+    )
+
+
+@dataclass
+class ActionResult:
+    """
+    What accepting or rejecting edits did: how many actions succeeded and
+    failed, the typed errors they failed with (the command exits with the code
+    of the last one), the edits whose checkpoint a re-apply started from did
+    not hash to their hash_before, and the files of the accepted edits, for
+    `accept --git-stage`.
+    """
+
+    successful: int = 0
+    failed: int = 0
+    errors: List[HistoryError] = field(default_factory=list)
+    checkpoint_mismatches: List[str] = field(default_factory=list)
+    accepted_files: List[str] = field(default_factory=list)
+
+    def add_error(self, error: Exception) -> None:
+        if not isinstance(error, HistoryError):
+            error = HistoryError(str(error))
+        self.errors.append(error)
+
+    def add_accepted(self, entry: Dict[str, Any]) -> None:
+        """Keeps the files of an accepted edit: the one edited, and a move's source."""
+        self.accepted_files.extend(
+            entry[key] for key in ("source_path", "file_path") if entry.get(key)
+        )
+
+    def add_reapply(self, recon_result: Dict[str, Any]) -> None:
+        """Keeps the error and checkpoint mismatch of a re-apply's result."""
+        mismatch = recon_result.get("checkpoint_mismatch")
+        if mismatch is not None and mismatch not in self.checkpoint_mismatches:
+            self.checkpoint_mismatches.append(mismatch)
+        if recon_result.get("error"):
+            self.add_error(recon_result["exception"])
+
+
 def _accept_or_reject_single(
     edit_id_prefix: str,
     action: str,  # 'accept' or 'reject'
@@ -166,17 +481,26 @@ def _accept_or_reject_single(
     history_root: Path,
     all_entries: List[Dict[str, Any]],
     lock_timeout: Optional[float] = None,
-) -> Tuple[int, int]:
-    """Helper to accept or reject a single edit."""
-    successful = 0
-    failed = 0
+    force: bool = False,
+    ignored: Optional[ignore.IgnoreMatcher] = None,
+    options: Optional[history.ReplayOptions] = None,
+    invocation: Optional[audit.Invocation] = None,
+) -> ActionResult:
+    """
+    Helper to accept or reject a single edit. Rejecting an edit also rejects the
+    edits that depend on it; accepting one whose parent was rejected needs `force`.
+    Edits of paths matched by `ignored` are not rejected. Files are re-applied
+    with `options`.
+    """
+    result = ActionResult()
+    locks: List[utils.ConversationLock] = []
     try:
         entry = history.find_entry_by_id(all_entries, edit_id_prefix)
         if not entry:
             print(
                 f"{utils.COLOR_RED}No entry found with ID prefix: {edit_id_prefix}{utils.COLOR_RESET}"
             )
-            return 0, 1  # 0 success, 1 failure
+            return ActionResult(failed=1)
 
         edit_id = entry.get("edit_id", "N/A")
         current_status = entry.get("status", "unknown").lower()
@@ -188,29 +512,69 @@ def _accept_or_reject_single(
             print(
                 f"{utils.COLOR_RED}Missing file path in entry {edit_id}. Cannot {action}.{utils.COLOR_RESET}"
             )
-            return 0, 1
+            return ActionResult(failed=1)
         if not log_file_name:
             print(
                 f"{utils.COLOR_RED}Missing log file source in entry {edit_id}. Cannot update status.{utils.COLOR_RESET}"
             )
-            return 0, 1
+            return ActionResult(failed=1)
 
         file_path_abs = workspace_root / file_path_rel
         log_file_path = history_root / utils.LOGS_DIR / log_file_name
 
         # --- Pre-Action Checks ---
+        target_status = "accepted" if action == "accept" else "rejected"
+        if entry.get("archived") and current_status != target_status:
+            print(
+                f"{utils.COLOR_RED}Edit {edit_id} is archived: its diff and checkpoint were pruned. Cannot {action}.{utils.COLOR_RESET}"
+            )
+            return ActionResult(failed=1)
         if action == "accept":
             if current_status == "accepted":
+                # Nothing to write: running accept again is harmless
+                print(f"Already accepted: {edit_id}")
+                return ActionResult(successful=1)  # Already done, count as success
+            parent = history.find_parent_entry(entry, all_entries)
+            if parent and parent.get("status") == "rejected" and not force:
                 print(
-                    f"{utils.COLOR_YELLOW}Edit {edit_id} is already accepted.{utils.COLOR_RESET}"
+                    f"{utils.COLOR_RED}Edit {edit_id} depends on rejected edit {parent.get('edit_id')}. Use --force to accept it anyway.{utils.COLOR_RESET}"
                 )
-                return 1, 0  # Already done, count as success
+                return ActionResult(failed=1)
         elif action == "reject":
             if current_status == "rejected":
+                print(f"Already rejected: {edit_id}")
+                return ActionResult(successful=1)  # Already done, count as success
+            if ignored and ignored.matches_entry(entry):
                 print(
-                    f"{utils.COLOR_YELLOW}Edit {edit_id} is already rejected.{utils.COLOR_RESET}"
+                    f"{utils.COLOR_YELLOW}Skipping edit {edit_id}: {utils.display_path(file_path_rel)} is listed in .mcp/ignore. Use --no-ignore to reject it.{utils.COLOR_RESET}"
+                )
+                return ActionResult()
+            dependents = _unrejected_dependents([entry], all_entries)
+            if dependents:
+                return _reject_with_dependents(
+                    entry,
+                    dependents,
+                    workspace_root,
+                    history_root,
+                    all_entries,
+                    lock_timeout,
+                    options,
+                    invocation=invocation,
                 )
-                return 1, 0  # Already done, count as success
+
+        if not _run_pre_hook(workspace_root, action, entry):
+            return ActionResult(failed=1)
+
+        # Keep the server from extending the conversations whose edits are
+        # replayed until we are done
+        conv_ids = _replayed_conversations([conv_id], [entry], all_entries)
+        locks = _acquire_conversation_locks(conv_ids, history_root, lock_timeout)
+        new_files = _find_new_files(conv_ids, all_entries, history_root, lock_timeout)
+        if file_path_rel in new_files:
+            print(
+                f"{utils.COLOR_RED}Conversation still active: {new_files[file_path_rel]} new edit(s) to {file_path_rel} since the history was read. Not {action}ing {edit_id}.{utils.COLOR_RESET}"
+            )
+            return ActionResult(failed=1)
 
         # --- Hash Verification (Check for external modifications) ---
         # Find the last *applied* edit before this one to get expected hash
@@ -221,13 +585,14 @@ def _accept_or_reject_single(
             last_applied_edit.get("hash_after") if last_applied_edit else None
         )
 
-        if not history.verify_and_prompt_if_modified(
+        modified = history.verify_and_prompt_if_modified(
             file_path_abs, expected_hash, history_root, workspace_root
-        ):
+        )
+        if modified is not None:
             print(
                 f"{utils.COLOR_YELLOW}Operation aborted by user due to external modifications.{utils.COLOR_RESET}"
             )
-            return 0, 1
+            return ActionResult(failed=1, errors=[modified])
 
         # --- Perform Action ---
         if action == "accept":
@@ -241,13 +606,17 @@ def _accept_or_reject_single(
                 workspace_root,
                 history_root,
                 apply_only_accepted=False,
+                options=options,
+                invocation=invocation,
             )
+            result.add_reapply(recon_result)
             if recon_result["error"]:
                 print(
                     f"{utils.COLOR_RED}Failed to reconstruct file state: {recon_result['error']}{utils.COLOR_RESET}"
                 )
                 # Don't accept if reconstruction failed
-                return 0, 1
+                result.failed += 1
+                return result
 
             final_hash = recon_result["hash"]
             # Update the entry's hash_after field BEFORE updating status
@@ -255,18 +624,24 @@ def _accept_or_reject_single(
 
             # Now update status in the log file
             if history.update_entry_status(
-                entry, "accepted", history_root, lock_timeout=lock_timeout
+                entry,
+                "accepted",
+                history_root,
+                lock_timeout=lock_timeout,
+                invocation=invocation,
             ):
                 print(
                     f"{utils.COLOR_GREEN}Successfully accepted edit: {edit_id}{utils.COLOR_RESET}"
                 )
-                successful += 1
+                result.successful += 1
+                result.add_accepted(entry)
+                _run_post_hook(workspace_root, action, entry)
             else:
                 print(
                     f"{utils.COLOR_RED}Failed to update status for accepted edit: {edit_id}{utils.COLOR_RESET}"
                 )
                 # Should we revert the reconstruction? Risky. Log failure.
-                failed += 1
+                result.failed += 1
 
         elif action == "reject":
             # 1. Take snapshot of current state *before* rejecting
@@ -277,7 +652,10 @@ def _accept_or_reject_single(
             )
             checkpoint_dir = history_root / CHECKPOINTS_DIR / conv_id
             checkpoint_dir.mkdir(parents=True, exist_ok=True)
-            sanitized_path = file_path_rel.replace("/", "_").replace("\\", "_")
+            # Checkpoint names must be valid UTF-8 so that the log can reference them
+            sanitized_path = (
+                utils.display_path(file_path_rel).replace("/", "_").replace("\\", "_")
+            )
             # Use edit ID in checkpoint name for easier association
             chkpt_filename = (
                 f"{sanitized_path}_{edit_id}_{utils.generate_hex_timestamp()}.chkpt"
@@ -313,17 +691,21 @@ def _accept_or_reject_single(
                     f"{utils.COLOR_RED}Failed to create or log snapshot before rejecting: {snap_err}{utils.COLOR_RESET}"
                 )
                 # Proceed with rejection? Risky without snapshot. Let's abort.
-                return 0, 1
+                return ActionResult(failed=1)
 
             # 2. Mark the edit as rejected *first*
             if not history.update_entry_status(
-                entry, "rejected", history_root, lock_timeout=lock_timeout
+                entry,
+                "rejected",
+                history_root,
+                lock_timeout=lock_timeout,
+                invocation=invocation,
             ):
                 print(
                     f"{utils.COLOR_RED}Failed to mark edit {edit_id} as rejected. Aborting rejection process.{utils.COLOR_RESET}"
                 )
                 # Consider deleting the snapshot? Maybe not, it records state *before* failure.
-                return 0, 1
+                return ActionResult(failed=1)
             print(
                 f"{utils.COLOR_YELLOW}Marked edit {edit_id} as rejected.{utils.COLOR_RESET}"
             )
@@ -334,16 +716,31 @@ def _accept_or_reject_single(
                     all_entries[idx]["status"] = "rejected"
                     break
 
-            # 3. Reconstruct file state, applying only 'accepted' edits
+            # 3. Reconstruct file state, applying the accepted and pending edits
             print(f"Reconstructing file {file_path_rel} state (skipping rejected)...")
             recon_result = history.reconstruct_file_from_history(
                 file_path_rel,
                 all_entries,
                 workspace_root,
                 history_root,
-                apply_only_accepted=True,
+                apply_only_accepted=False,
+                options=options,
+                invocation=invocation,
             )
 
+            result.add_reapply(recon_result)
+            if recon_result["error"] is None:
+                for move_result in _reconstruct_move_sources(
+                    [entry],
+                    all_entries,
+                    workspace_root,
+                    history_root,
+                    options,
+                    invocation=invocation,
+                ):
+                    result.add_reapply(move_result)
+                    recon_result["error"] = move_result["error"]
+
             # 4. Log the revert operation attempt
             revert_status = "done" if recon_result["error"] is None else "failed"
             final_hash = recon_result.get("hash")
@@ -388,7 +785,11 @@ def _accept_or_reject_single(
                         )
                     # Should we revert the status back to pending? Yes, failed rejection.
                     if history.update_entry_status(
-                        entry, current_status, history_root, lock_timeout=lock_timeout
+                        entry,
+                        current_status,
+                        history_root,
+                        lock_timeout=lock_timeout,
+                        invocation=invocation,
                     ):
                         print(
                             f"{utils.COLOR_YELLOW}Reset status of edit {edit_id} back to {current_status}.{utils.COLOR_RESET}"
@@ -397,23 +798,37 @@ def _accept_or_reject_single(
                         print(
                             f"{utils.COLOR_RED}Failed to reset status for edit {edit_id} after failed rejection.{utils.COLOR_RESET}"
                         )
-                    failed += 1
+                    result.failed += 1
 
                 except Exception as restore_err:
                     print(
                         f"{utils.COLOR_RED}Failed to restore file from snapshot: {restore_err}{utils.COLOR_RESET}"
                     )
-                    failed += 1
+                    result.failed += 1
             else:
                 print(
                     f"{utils.COLOR_GREEN}Successfully rejected edit {edit_id} and reconstructed file.{utils.COLOR_RESET}"
                 )
-                successful += 1
+                result.successful += 1
+                _run_post_hook(workspace_root, action, entry)
+            _enforce_history_depth(
+                file_path_rel,
+                conv_id,
+                workspace_root,
+                history_root,
+                lock_timeout,
+                invocation=invocation,
+            )
 
     except AmbiguousIDError as e:
         # Error message already printed by find_entry_by_id
         log.warning(f"Cannot {action} due to ambiguous ID: {e}")
-        failed += 1
+        result.add_error(e)
+        result.failed += 1
+    except TimeoutError as e:
+        print(f"{utils.COLOR_RED}{e}{utils.COLOR_RESET}")
+        result.add_error(e)
+        result.failed += 1
     except KeyboardInterrupt:
         print(f"\n{action.capitalize()} operation cancelled by user.")
         # Don't count as failure, just cancelled
@@ -422,9 +837,438 @@ def _accept_or_reject_single(
             f"Unexpected error during {action} of single edit {edit_id_prefix}: {e}"
         )
         print(f"{utils.COLOR_RED}An unexpected error occurred: {e}{utils.COLOR_RESET}")
-        failed += 1
+        result.failed += 1
+    finally:
+        for lock in reversed(locks):
+            lock.release()
+
+    return result
+
+
+def _unrejected_dependents(
+    entries: List[Dict[str, Any]], all_entries: List[Dict[str, Any]]
+) -> List[Tuple[Dict[str, Any], int]]:
+    """Transitive children of `entries` that are not rejected yet, with depth."""
+    dependents = history.find_dependent_entries(
+        [e.get("edit_id") for e in entries], all_entries
+    )
+    return [(e, depth) for e, depth in dependents if e.get("status") != "rejected"]
+
+
+def _print_dependency_chain(
+    root: Dict[str, Any], dependents: List[Tuple[Dict[str, Any], int]]
+) -> None:
+    """Prints an edit and its dependent edits as a tree."""
+    print(
+        f"  {root.get('edit_id', 'N/A')[:8]}  {utils.display_path(root.get('file_path')) or 'N/A'}"
+    )
+    for entry, depth in dependents:
+        indent = "   " * (depth - 1)
+        print(
+            f"  {indent}└─ {entry.get('edit_id', 'N/A')[:8]}  {utils.display_path(entry.get('file_path')) or 'N/A'}  ({entry.get('status', 'unknown')})"
+        )
+
+
+def _acquire_conversation_locks(
+    conv_ids: List[str], history_root: Path, lock_timeout: Optional[float] = None
+) -> List[utils.ConversationLock]:
+    """Locks several conversations, in sorted order to avoid lock-order deadlocks."""
+    locks: List[utils.ConversationLock] = []
+    try:
+        for conv_id in sorted(set(conv_ids)):
+            locks.append(api.acquire_lock_sync(history_root, conv_id, lock_timeout))
+    except BaseException:
+        for lock in reversed(locks):
+            lock.release()
+        raise
+    return locks
+
+
+def _replayed_conversations(
+    conv_ids: List[str],
+    edits: List[Dict[str, Any]],
+    all_entries: List[Dict[str, Any]],
+) -> List[str]:
+    """
+    `conv_ids` and the other conversations with edits to the files of `edits`,
+    whose edits are replayed with them when those files are re-applied.
+    """
+    paths = [
+        path
+        for e in edits
+        for path in (e.get("file_path"), e.get("source_path"))
+        if path
+    ]
+    return sorted(
+        set(conv_ids) | set(history.find_conversations_for_files(all_entries, paths))
+    )
+
+
+def _find_new_files(
+    conv_ids: List[str],
+    all_entries: List[Dict[str, Any]],
+    history_root: Path,
+    lock_timeout: Optional[float] = None,
+) -> Dict[str, int]:
+    """find_new_conversation_files() summed over several conversations."""
+    new_files: Dict[str, int] = {}
+    for conv_id in sorted(set(conv_ids)):
+        conv_files = history.find_new_conversation_files(
+            conv_id, all_entries, history_root, lock_timeout
+        )
+        for path, count in conv_files.items():
+            new_files[path] = new_files.get(path, 0) + count
+    return new_files
 
-    return successful, failed
+
+def _reject_with_dependents(
+    entry: Dict[str, Any],
+    dependents: List[Tuple[Dict[str, Any], int]],
+    workspace_root: Path,
+    history_root: Path,
+    all_entries: List[Dict[str, Any]],
+    lock_timeout: Optional[float] = None,
+    options: Optional[history.ReplayOptions] = None,
+    invocation: Optional[audit.Invocation] = None,
+) -> ActionResult:
+    """
+    Rejects an edit together with the edits that depend on it, file by file, with
+    every conversation involved locked.
+    """
+    print(
+        f"{utils.COLOR_YELLOW}Edit {entry.get('edit_id')} has dependent edits, which are rejected with it:{utils.COLOR_RESET}"
+    )
+    _print_dependency_chain(entry, dependents)
+    chain = [entry] + [e for e, _ in dependents]
+    conv_ids = _replayed_conversations(
+        [e["conversation_id"] for e in chain if e.get("conversation_id")],
+        chain,
+        all_entries,
+    )
+    locks = _acquire_conversation_locks(conv_ids, history_root, lock_timeout)
+    try:
+        active_files = _find_new_files(
+            conv_ids, all_entries, history_root, lock_timeout
+        )
+        return _accept_or_reject_conversation_files(
+            entry.get("conversation_id", "unknown_conv"),
+            chain,
+            active_files,
+            "reject",
+            workspace_root,
+            history_root,
+            all_entries,
+            lock_timeout,
+            scope="Dependency chain",
+            options=options,
+            invocation=invocation,
+        )
+    finally:
+        for lock in reversed(locks):
+            lock.release()
+
+
+def _reconstruct_move_sources(
+    edits: List[Dict[str, Any]],
+    all_entries: List[Dict[str, Any]],
+    workspace_root: Path,
+    history_root: Path,
+    options: Optional[history.ReplayOptions] = None,
+    invocation: Optional[audit.Invocation] = None,
+) -> List[Dict[str, Any]]:
+    """
+    Re-applies the source paths of rejected moves, so the content goes back
+    where it was moved from. Returns the results of the re-applies, up to the
+    first that failed.
+    """
+    results: List[Dict[str, Any]] = []
+    for entry in edits:
+        source_rel = entry.get("source_path")
+        if entry.get("operation") not in utils.MOVE_OPERATIONS or not source_rel:
+            continue
+        print(f"Reconstructing move source {utils.display_path(source_rel)}...")
+        recon_result = history.reconstruct_file_from_history(
+            source_rel,
+            all_entries,
+            workspace_root,
+            history_root,
+            apply_only_accepted=False,
+            options=options,
+            invocation=invocation,
+        )
+        results.append(recon_result)
+        if recon_result["error"]:
+            break
+    return results
+
+
+def _enforce_history_depth(
+    file_path_rel: str,
+    conv_id: str,
+    workspace_root: Path,
+    history_root: Path,
+    lock_timeout: Optional[float] = None,
+    invocation: Optional[audit.Invocation] = None,
+) -> None:
+    """
+    Prunes the file's checkpoints in the conversation down to the history_depth
+    setting, if one is set, once a reject has written its snapshot. A failure
+    only warns: the reject itself is done.
+    """
+    depth = config.get_config_value(workspace_root, "history_depth")
+    if depth is None:
+        return
+    try:
+        result = history.enforce_history_depth(
+            history_root,
+            file_path_rel,
+            conv_id,
+            depth,
+            lock_timeout,
+            invocation=invocation,
+        )
+    except (HistoryError, OSError) as e:
+        print(
+            f"{utils.COLOR_YELLOW}Warning: could not prune checkpoints of {file_path_rel}: {e}{utils.COLOR_RESET}"
+        )
+        return
+    if result["promoted"]:
+        print(f"Promoted a checkpoint for edit {result['promoted'][:8]}.")
+    if result["pruned"]:
+        print(
+            f"Pruned {len(result['pruned'])} checkpoint(s) of {file_path_rel} beyond history_depth {depth}."
+        )
+    if result["kept"]:
+        print(
+            f"{utils.COLOR_YELLOW}Kept {len(result['kept'])} older checkpoint(s) of {file_path_rel} that re-applies still need.{utils.COLOR_RESET}"
+        )
+
+
+def _reject_file_edits(
+    file_path_rel: str,
+    file_edits: List[Dict[str, Any]],
+    conv_id: str,
+    log_file_name: str,
+    workspace_root: Path,
+    history_root: Path,
+    all_entries: List[Dict[str, Any]],
+    lock_timeout: Optional[float] = None,
+    options: Optional[history.ReplayOptions] = None,
+    invocation: Optional[audit.Invocation] = None,
+) -> Tuple[Optional[str], List[Dict[str, Any]]]:
+    """
+    Rejects all given edits of one file as a unit.
+    The file is re-applied without the edits first; the 'rejected' status is only
+    persisted once that succeeded. On failure the file is restored from a snapshot
+    and the log is left untouched.
+    Returns None on success, otherwise the error for this file, and the results
+    of the re-applies.
+    """
+    file_path_abs = workspace_root / file_path_rel
+    reject_ids = {e.get("edit_id") for e in file_edits}
+
+    # 1. Snapshot current state so a failed re-apply can be rolled back
+    current_hash = (
+        utils.calculate_hash(str(file_path_abs)) if file_path_abs.exists() else None
+    )
+    checkpoint_dir = history_root / CHECKPOINTS_DIR / conv_id
+    checkpoint_dir.mkdir(parents=True, exist_ok=True)
+    sanitized_path = (
+        utils.display_path(file_path_rel).replace("/", "_").replace("\\", "_")
+    )
+    # A namespaced conversation's checkpoints are already in checkpoints/<ns>/
+    conv_name = conv_id.rpartition("/")[2]
+    chkpt_filename = (
+        f"{sanitized_path}_{conv_name}_{utils.generate_hex_timestamp()}.chkpt"
+    )
+    checkpoint_path_abs = checkpoint_dir / chkpt_filename
+    checkpoint_rel_path = history.get_relative_path(checkpoint_path_abs, history_root)
+    try:
+        if file_path_abs.exists():
+            shutil.copy2(file_path_abs, checkpoint_path_abs)
+        else:
+            checkpoint_path_abs.touch()
+        history.add_snapshot_log_entry(
+            file_path_rel,
+            current_hash,
+            checkpoint_rel_path,
+            conv_id,
+            log_file_name,
+            history_root,
+            lock_timeout,
+        )
+    except Exception as snap_err:
+        print(
+            f"{utils.COLOR_RED}Failed snapshot for {file_path_rel}: {snap_err}{utils.COLOR_RESET}"
+        )
+        return f"snapshot failed: {snap_err}", []
+
+    # 2. Re-apply against a view of the history where the edits are rejected.
+    #    Nothing is written to the log yet.
+    staged_entries = [
+        {**e, "status": "rejected"} if e.get("edit_id") in reject_ids else e
+        for e in all_entries
+    ]
+    print(f"Reconstructing file {file_path_rel} (skipping rejected)...")
+    recon_result = history.reconstruct_file_from_history(
+        file_path_rel,
+        staged_entries,
+        workspace_root,
+        history_root,
+        apply_only_accepted=False,
+        options=options,
+        invocation=invocation,
+    )
+    reapplies = [recon_result]
+    if recon_result["error"] is None:
+        reapplies += _reconstruct_move_sources(
+            file_edits,
+            staged_entries,
+            workspace_root,
+            history_root,
+            options,
+            invocation=invocation,
+        )
+    error = reapplies[-1]["error"]
+
+    # 3. Persist the rejected status only if the re-apply succeeded
+    previous_status = {e.get("edit_id"): e.get("status", "pending") for e in file_edits}
+    persisted: List[Dict[str, Any]] = []
+    if error is None:
+        update = history.update_entries_status(
+            file_edits,
+            "rejected",
+            history_root,
+            lock_timeout=lock_timeout,
+            invocation=invocation,
+        )
+        persisted = update["updated"]
+        if update["failed"]:
+            failed_entry, reason = update["failed"][0]
+            error = f"failed to persist rejected status for {failed_entry.get('edit_id')}: {reason}"
+
+    # 4. Log the revert attempt (use last rejected edit ID for context)
+    try:
+        history.add_revert_log_entry(
+            file_edits[-1].get("edit_id", "multi-reject"),
+            file_path_rel,
+            current_hash,
+            recon_result.get("hash") if error is None else current_hash,
+            "done" if error is None else "failed",
+            conv_id,
+            log_file_name,
+            history_root,
+            lock_timeout,
+        )
+    except Exception as revert_log_err:
+        print(
+            f"{utils.COLOR_RED}Failed to log revert op for {file_path_rel}: {revert_log_err}{utils.COLOR_RESET}"
+        )
+
+    if error is None:
+        for e in all_entries:
+            if e.get("edit_id") in reject_ids:
+                e["status"] = "rejected"
+        print(
+            f"{utils.COLOR_GREEN}Rejected {len(file_edits)} edits and reconstructed {file_path_rel}.{utils.COLOR_RESET}"
+        )
+        return None, reapplies
+
+    # 5. Roll back: restore the file and any statuses already written
+    print(
+        f"{utils.COLOR_RED}Failed to reject edits for {file_path_rel}: {error}{utils.COLOR_RESET}"
+    )
+    try:
+        if current_hash is None:
+            if file_path_abs.exists():
+                file_path_abs.unlink()
+        elif utils.calculate_hash(str(file_path_abs)) != current_hash:
+            shutil.copy2(checkpoint_path_abs, file_path_abs)
+            print(
+                f"{utils.COLOR_YELLOW}Restored {file_path_rel} from snapshot.{utils.COLOR_RESET}"
+            )
+    except Exception as restore_err:
+        print(
+            f"{utils.COLOR_RED}Snapshot restore failed for {file_path_rel}: {restore_err}{utils.COLOR_RESET}"
+        )
+        error = f"{error}; snapshot restore failed: {restore_err}"
+    for entry in persisted:
+        if not history.update_entry_status(
+            entry,
+            previous_status[entry.get("edit_id")],
+            history_root,
+            lock_timeout=lock_timeout,
+            invocation=invocation,
+        ):
+            error = f"{error}; could not roll back status of {entry.get('edit_id')}"
+    return error, reapplies
+
+
+def _print_reject_summary(
+    rejected_files: List[Tuple[str, int]],
+    left_pending_files: List[Tuple[str, int, str]],
+) -> None:
+    """Prints which files reject --conv reverted and which it left untouched."""
+    if rejected_files:
+        print(f"{utils.COLOR_GREEN}Rejected and reverted:{utils.COLOR_RESET}")
+        for file_path_rel, count in rejected_files:
+            print(f"  {file_path_rel} ({count} edits)")
+    if left_pending_files:
+        print(
+            f"{utils.COLOR_YELLOW}Left pending (log unchanged):{utils.COLOR_RESET}"
+        )
+        for file_path_rel, count, error in left_pending_files:
+            first_line = error.strip().splitlines()[0] if error.strip() else error
+            print(f"  {file_path_rel} ({count} edits): {first_line}")
+
+
+# Accepting or rejecting more edits than this by time range asks first
+BULK_CONFIRM_THRESHOLD = 20
+
+
+def _select_by_time(
+    conv_entries: List[Dict[str, Any]],
+    action: str,  # 'accept' or 'reject'
+    before: Optional[float],
+    after: Optional[float],
+    assume_yes: bool,
+) -> List[Dict[str, Any]]:
+    """
+    The entries of a conversation logged in a time range, selected as `status
+    --before/--after` selects them. Prints the edits the action will change,
+    and asks before going on with more than BULK_CONFIRM_THRESHOLD of them
+    unless `assume_yes`. Returns no entries if there is nothing to do or the
+    user declined.
+    """
+    selected = history.filter_entries(
+        conv_entries, before=before, after=after, limit=None
+    )
+    allowed_from = ["pending"] if action == "accept" else ["pending", "accepted"]
+    matched = [
+        e
+        for e in selected
+        if e.get("tool_name") != "mcpdiff" and e.get("status") in allowed_from
+    ]
+    conv_id = conv_entries[0].get("conversation_id", "unknown_conv")
+    if not matched:
+        print(
+            f"{utils.COLOR_YELLOW}No {'/'.join(allowed_from)} edits of conversation {conv_id} were logged in that time range.{utils.COLOR_RESET}"
+        )
+        return []
+    print(f"{len(matched)} edit(s) of conversation {conv_id} match the time range:")
+    for entry in matched:
+        print(
+            f"  {entry.get('edit_id', 'N/A')}  {utils.format_timestamp_absolute(entry.get('timestamp'), display_friendly=True)}  {utils.display_path(entry.get('file_path')) or 'N/A'}"
+        )
+    if len(matched) > BULK_CONFIRM_THRESHOLD and not assume_yes:
+        try:
+            choice = input(f"{action.capitalize()} all {len(matched)}? (y/n): ")
+        except (EOFError, KeyboardInterrupt):
+            choice = "n"
+        if choice.lower().strip() not in ["y", "yes"]:
+            print("Aborted.")
+            return []
+    return selected
 
 
 def _accept_or_reject_conversation(
@@ -434,54 +1278,181 @@ def _accept_or_reject_conversation(
     history_root: Path,
     all_entries: List[Dict[str, Any]],
     lock_timeout: Optional[float] = None,
-) -> Tuple[int, int]:
-    """Helper to accept or reject all relevant edits for a conversation."""
+    force: bool = False,
+    ignored: Optional[ignore.IgnoreMatcher] = None,
+    before: Optional[float] = None,
+    after: Optional[float] = None,
+    assume_yes: bool = False,
+    options: Optional[history.ReplayOptions] = None,
+    invocation: Optional[audit.Invocation] = None,
+) -> ActionResult:
+    """
+    Helper to accept or reject all relevant edits for a conversation. Rejecting
+    also rejects edits in other conversations that depend on its edits, but not
+    edits of paths matched by `ignored`. With `before`/`after`, only the edits
+    logged in that time range (see _select_by_time()). Files are re-applied
+    with `options`.
+    """
     conv_entries = history.find_entries_by_conversation(all_entries, conv_id_prefix)
     if not conv_entries:
         print(
             f"{utils.COLOR_RED}No entries found for conversation matching ID: {conv_id_prefix}{utils.COLOR_RESET}"
         )
-        return 0, 0  # No entries, no failures
+        return ActionResult()  # No entries, no failures
+    if before is not None or after is not None:
+        conv_entries = _select_by_time(
+            conv_entries, action, before, after, assume_yes
+        )
+        if not conv_entries:
+            return ActionResult()
 
     conv_id = conv_entries[0].get("conversation_id", "unknown_conv")
     log.info(
         f"Processing {action} for conversation {conv_id} ({len(conv_entries)} entries total)"
     )
 
-    # Filter entries relevant for the action
-    if action == "accept":
-        relevant_entries = [e for e in conv_entries if e.get("status") == "pending"]
-        if not relevant_entries:
-            print(
-                f"{utils.COLOR_YELLOW}No pending edits found to accept for conversation {conv_id}.{utils.COLOR_RESET}"
-            )
-            return 0, 0
-        print(f"Found {len(relevant_entries)} pending edits to accept.")
-    elif action == "reject":
-        relevant_entries = [
-            e for e in conv_entries if e.get("status") in ["pending", "accepted"]
-        ]
-        if not relevant_entries:
+    dependents = (
+        _unrejected_dependents(conv_entries, all_entries) if action == "reject" else []
+    )
+    if dependents:
+        print(
+            f"{utils.COLOR_YELLOW}Edits in other conversations depend on this one and are rejected with it:{utils.COLOR_RESET}"
+        )
+        for entry, _ in dependents:
+            parent_id = (entry.get("parent_edit_id") or "N/A")[:8]
             print(
-                f"{utils.COLOR_YELLOW}No pending or accepted edits found to reject for conversation {conv_id}.{utils.COLOR_RESET}"
+                f"  {entry.get('edit_id', 'N/A')[:8]}  {utils.display_path(entry.get('file_path')) or 'N/A'}  (child of {parent_id})"
             )
-            return 0, 0
-        print(f"Found {len(relevant_entries)} pending/accepted edits to reject.")
-    else:
-        return 0, 0  # Should not happen
-
-    # Group by file path for processing
-    entries_by_file: Dict[str, List[Dict[str, Any]]] = {}
-    for entry in relevant_entries:
-        file_path = entry.get("file_path")
-        if file_path:
-            entries_by_file.setdefault(file_path, []).append(entry)
-
-    total_successful = 0
-    total_failed = 0
+    edits = conv_entries + [e for e, _ in dependents]
+    conv_ids = _replayed_conversations(
+        [conv_id]
+        + [e["conversation_id"] for e, _ in dependents if e.get("conversation_id")],
+        edits,
+        all_entries,
+    )
 
-    # Process file by file
-    for file_path_rel, file_edits in entries_by_file.items():
+    # Keep the server from extending the conversations whose edits are replayed
+    # until we are done, and find files it logged new edits for since the
+    # history was read
+    locks = _acquire_conversation_locks(conv_ids, history_root, lock_timeout)
+    try:
+        active_files = _find_new_files(
+            conv_ids, all_entries, history_root, lock_timeout
+        )
+        return _accept_or_reject_conversation_files(
+            conv_id,
+            edits,
+            active_files,
+            action,
+            workspace_root,
+            history_root,
+            all_entries,
+            lock_timeout,
+            force=force,
+            ignored=ignored,
+            options=options,
+            invocation=invocation,
+        )
+    finally:
+        for lock in reversed(locks):
+            lock.release()
+
+
+def _accept_or_reject_conversation_files(
+    conv_id: str,
+    conv_entries: List[Dict[str, Any]],
+    active_files: Dict[str, int],
+    action: str,  # 'accept' or 'reject'
+    workspace_root: Path,
+    history_root: Path,
+    all_entries: List[Dict[str, Any]],
+    lock_timeout: Optional[float] = None,
+    force: bool = False,
+    scope: str = "Conversation",
+    ignored: Optional[ignore.IgnoreMatcher] = None,
+    options: Optional[history.ReplayOptions] = None,
+    invocation: Optional[audit.Invocation] = None,
+) -> ActionResult:
+    """
+    Accepts or rejects a conversation's edits file by file, under the conversation
+    lock. Files in `active_files` got new edits since planning and are skipped.
+    Edits whose parent was rejected are only accepted with `force`, and edits of
+    paths matched by `ignored` are not rejected.
+    """
+    # Filter entries relevant for the action; the rest only count in the summary
+    target_status = "accepted" if action == "accept" else "rejected"
+    allowed_from = ["pending"] if action == "accept" else ["pending", "accepted"]
+    status_result: Dict[str, Any] = {
+        "updated": [],
+        "already": [],
+        "skipped": [],
+        "failed": [],
+    }
+    relevant_entries = []
+    for e in conv_entries:
+        status = e.get("status", "unknown")
+        if e.get("tool_name") == "mcpdiff":
+            continue  # Snapshots and reverts written by mcpdiff itself
+        parent = history.find_parent_entry(e, all_entries)
+        if status == target_status:
+            status_result["already"].append(e)
+        elif e.get("archived"):
+            status_result["skipped"].append((e, "archived"))
+        elif action == "reject" and ignored and ignored.matches_entry(e):
+            status_result["skipped"].append((e, "ignored"))
+        elif status not in allowed_from:
+            status_result["skipped"].append((e, status))
+        elif (
+            action == "accept"
+            and not force
+            and parent
+            and parent.get("status") == "rejected"
+        ):
+            status_result["skipped"].append((e, "parent rejected"))
+        else:
+            relevant_entries.append(e)
+
+    # Left alone, so that scripts can accept a conversation after every turn
+    for e in status_result["already"]:
+        print(f"Already {target_status}: {e.get('edit_id', 'N/A')}")
+
+    ignored_files = sorted(
+        {
+            utils.display_path(e.get("file_path"))
+            for e, reason in status_result["skipped"]
+            if reason == "ignored"
+        }
+    )
+    if ignored_files:
+        print(
+            f"{utils.COLOR_YELLOW}Skipping files listed in .mcp/ignore (use --no-ignore to reject them): {', '.join(ignored_files)}{utils.COLOR_RESET}"
+        )
+    if not relevant_entries:
+        print(
+            f"{utils.COLOR_YELLOW}No {'/'.join(allowed_from)} edits found to {action} for conversation {conv_id}.{utils.COLOR_RESET}"
+        )
+        print(
+            f"Edits: {history.format_status_update_summary(status_result, target_status)}."
+        )
+        return ActionResult()
+    print(f"Found {len(relevant_entries)} {'/'.join(allowed_from)} edits to {action}.")
+
+    # Group by file path for processing
+    entries_by_file: Dict[str, List[Dict[str, Any]]] = {}
+    for entry in relevant_entries:
+        file_path = entry.get("file_path")
+        if file_path:
+            entries_by_file.setdefault(file_path, []).append(entry)
+
+    result = ActionResult()
+    total_successful = 0
+    total_failed = 0
+    # Per-file outcome for the reject summary
+    rejected_files: List[Tuple[str, int]] = []
+    left_pending_files: List[Tuple[str, int, str]] = []
+
+    # Process file by file
+    for file_path_rel, file_edits in entries_by_file.items():
         print(f"\n--- Processing file: {file_path_rel} ({len(file_edits)} edits) ---")
         file_path_abs = workspace_root / file_path_rel
         log_file_name = file_edits[0].get(
@@ -492,6 +1463,18 @@ def _accept_or_reject_conversation(
                 f"{utils.COLOR_RED}Cannot process file {file_path_rel}: missing log file source.{utils.COLOR_RESET}"
             )
             total_failed += len(file_edits)
+            left_pending_files.append(
+                (file_path_rel, len(file_edits), "missing log file source")
+            )
+            continue
+        if file_path_rel in active_files:
+            print(
+                f"{utils.COLOR_RED}Skipping {file_path_rel}: conversation still active ({active_files[file_path_rel]} new edit(s) since the history was read).{utils.COLOR_RESET}"
+            )
+            total_failed += len(file_edits)
+            left_pending_files.append(
+                (file_path_rel, len(file_edits), "conversation still active")
+            )
             continue
 
         # --- Hash Verification ---
@@ -501,14 +1484,23 @@ def _accept_or_reject_conversation(
         expected_hash = (
             last_applied_edit.get("hash_after") if last_applied_edit else None
         )
-        if not history.verify_and_prompt_if_modified(
+        modified = history.verify_and_prompt_if_modified(
             file_path_abs, expected_hash, history_root, workspace_root
-        ):
+        )
+        if modified is not None:
+            result.errors.append(modified)
             print(
                 f"{utils.COLOR_YELLOW}Skipping file {file_path_rel} due to user cancellation.{utils.COLOR_RESET}"
             )
             # How many failures? Count all relevant edits for this file as failed.
             total_failed += len(file_edits)
+            left_pending_files.append(
+                (
+                    file_path_rel,
+                    len(file_edits),
+                    "skipped due to external modifications",
+                )
+            )
             continue
 
         # --- Perform Action for File ---
@@ -516,6 +1508,14 @@ def _accept_or_reject_conversation(
         file_failed = 0
 
         if action == "accept":
+            # Edits vetoed by the pre-accept hook stay pending
+            allowed_edits = [
+                e for e in file_edits if _run_pre_hook(workspace_root, action, e)
+            ]
+            file_failed += len(file_edits) - len(allowed_edits)
+            file_edits = allowed_edits
+
+        if action == "accept" and file_edits:
             # Reconstruct applies pending anyway
             print(f"Ensuring file state for {file_path_rel} before accepting...")
             recon_result = history.reconstruct_file_from_history(
@@ -524,7 +1524,10 @@ def _accept_or_reject_conversation(
                 workspace_root,
                 history_root,
                 apply_only_accepted=False,
+                options=options,
+                invocation=invocation,
             )
+            result.add_reapply(recon_result)
             if recon_result["error"]:
                 print(
                     f"{utils.COLOR_RED}Failed to reconstruct file state for {file_path_rel}: {recon_result['error']}{utils.COLOR_RESET}"
@@ -532,19 +1535,25 @@ def _accept_or_reject_conversation(
                 file_failed += len(file_edits)
             else:
                 final_hash = recon_result["hash"]
-                # Update status for all relevant edits for this file
                 for entry in file_edits:
                     entry["hash_after"] = final_hash  # Update in-memory hash
-                    if history.update_entry_status(
-                        entry, "accepted", history_root, lock_timeout=lock_timeout
-                    ):
-                        log.debug(f"Marked edit {entry.get('edit_id')} as accepted.")
-                        file_success += 1
-                    else:
-                        log.error(
-                            f"Failed to mark edit {entry.get('edit_id')} as accepted."
-                        )
-                        file_failed += 1
+                # Update status for all relevant edits for this file at once
+                update = history.update_entries_status(
+                    file_edits,
+                    "accepted",
+                    history_root,
+                    lock_timeout=lock_timeout,
+                    allowed_from=allowed_from,
+                    invocation=invocation,
+                )
+                status_result["updated"].extend(update["updated"])
+                file_success += len(update["updated"]) + len(update["already"])
+                file_failed += len(update["failed"]) + len(update["skipped"])
+                for entry, reason in update["failed"]:
+                    log.error(f"Failed to mark edit {entry.get('edit_id')}: {reason}")
+                for entry in update["updated"]:
+                    result.add_accepted(entry)
+                    _run_post_hook(workspace_root, action, entry)
                 if file_failed == 0:
                     print(
                         f"{utils.COLOR_GREEN}Accepted {file_success} edits for {file_path_rel}.{utils.COLOR_RESET}"
@@ -555,163 +1564,66 @@ def _accept_or_reject_conversation(
                     )
 
         elif action == "reject":
-            # 1. Snapshot
-            current_hash = (
-                utils.calculate_hash(str(file_path_abs))
-                if file_path_abs.exists()
-                else None
-            )
-            checkpoint_dir = history_root / CHECKPOINTS_DIR / conv_id
-            checkpoint_dir.mkdir(parents=True, exist_ok=True)
-            sanitized_path = file_path_rel.replace("/", "_").replace("\\", "_")
-            # Use conv ID and file name for checkpoint
-            chkpt_filename = (
-                f"{sanitized_path}_{conv_id}_{utils.generate_hex_timestamp()}.chkpt"
+            # The file is rejected as a unit, so any vetoed edit keeps all pending
+            if not all(_run_pre_hook(workspace_root, action, e) for e in file_edits):
+                file_failed += len(file_edits)
+                left_pending_files.append(
+                    (file_path_rel, len(file_edits), "pre-reject hook failed")
+                )
+                total_failed += file_failed
+                continue
+            error, reapplies = _reject_file_edits(
+                file_path_rel,
+                file_edits,
+                conv_id,
+                log_file_name,
+                workspace_root,
+                history_root,
+                all_entries,
+                lock_timeout,
+                options,
+                invocation=invocation,
             )
-            checkpoint_path_abs = checkpoint_dir / chkpt_filename
-            checkpoint_rel_path = history.get_relative_path(
-                checkpoint_path_abs, history_root
+            for recon_result in reapplies:
+                result.add_reapply(recon_result)
+            _enforce_history_depth(
+                file_path_rel,
+                conv_id,
+                workspace_root,
+                history_root,
+                lock_timeout,
+                invocation=invocation,
             )
-
-            snapshot_failed = False
-            snapshot_edit_id = "N/A"
-            try:
-                if file_path_abs.exists():
-                    shutil.copy2(file_path_abs, checkpoint_path_abs)
-                else:
-                    checkpoint_path_abs.touch()
-                snapshot_edit_id = history.add_snapshot_log_entry(
-                    file_path_rel,
-                    current_hash,
-                    checkpoint_rel_path,
-                    conv_id,
-                    log_file_name,
-                    history_root,
-                    lock_timeout,
-                )
-            except Exception as snap_err:
-                print(
-                    f"{utils.COLOR_RED}Failed snapshot for {file_path_rel}: {snap_err}{utils.COLOR_RESET}"
-                )
-                snapshot_failed = True
-                file_failed += len(file_edits)
-
-            if not snapshot_failed:
-                # 2. Mark all as rejected
-                reject_update_failed = False
-                original_statuses = {}  # Store original status in case we need to revert
+            if error is None:
+                file_success += len(file_edits)
+                status_result["updated"].extend(file_edits)
+                rejected_files.append((file_path_rel, len(file_edits)))
                 for entry in file_edits:
-                    edit_id = entry.get("edit_id")
-                    original_statuses[edit_id] = entry.get("status", "unknown")
-                    if not history.update_entry_status(
-                        entry, "rejected", history_root, lock_timeout=lock_timeout
-                    ):
-                        log.error(f"Failed to mark edit {edit_id} as rejected.")
-                        reject_update_failed = True
-                    else:
-                        # Update local all_entries list
-                        for idx, e in enumerate(all_entries):
-                            if e.get("edit_id") == edit_id:
-                                all_entries[idx]["status"] = "rejected"
-                                break
-
-                if reject_update_failed:
-                    print(
-                        f"{utils.COLOR_RED}Failed to mark one or more edits as rejected for {file_path_rel}. Aborting rejection for this file.{utils.COLOR_RESET}"
-                    )
-                    # TODO: Revert statuses? Complex. For now, just count as failure.
-                    file_failed += (
-                        len(file_edits) - file_success
-                    )  # Count successes so far
-                else:
-                    print(
-                        f"{utils.COLOR_YELLOW}Marked {len(file_edits)} edits for {file_path_rel} as rejected.{utils.COLOR_RESET}"
-                    )
-
-                    # 3. Reconstruct (applying only accepted)
-                    print(f"Reconstructing file {file_path_rel} (skipping rejected)...")
-                    recon_result = history.reconstruct_file_from_history(
-                        file_path_rel,
-                        all_entries,
-                        workspace_root,
-                        history_root,
-                        apply_only_accepted=True,
-                    )
-
-                    # 4. Log revert attempt (use last rejected edit ID for context?)
-                    last_rejected_id = file_edits[-1].get("edit_id", "multi-reject")
-                    revert_status = (
-                        "done" if recon_result["error"] is None else "failed"
-                    )
-                    final_hash = recon_result.get("hash")
-                    try:
-                        history.add_revert_log_entry(
-                            last_rejected_id,
-                            file_path_rel,
-                            current_hash,
-                            final_hash,
-                            revert_status,
-                            conv_id,
-                            log_file_name,
-                            history_root,
-                            lock_timeout,
-                        )
-                    except Exception as revert_log_err:
-                        print(
-                            f"{utils.COLOR_RED}Failed to log revert op for {file_path_rel}: {revert_log_err}{utils.COLOR_RESET}"
-                        )
-
-                    # 5. Handle result
-                    if recon_result["error"]:
-                        print(
-                            f"{utils.COLOR_RED}Failed reconstruction for {file_path_rel}: {recon_result['error']}{utils.COLOR_RESET}"
-                        )
-                        print(
-                            f"{utils.COLOR_YELLOW}Attempting restore from snapshot: {checkpoint_path_abs}{utils.COLOR_RESET}"
-                        )
-                        try:
-                            if checkpoint_path_abs.exists():
-                                shutil.copy2(checkpoint_path_abs, file_path_abs)
-                            # Revert statuses back
-                            print(
-                                f"{utils.COLOR_YELLOW}Reverting statuses for {len(file_edits)} edits...{utils.COLOR_RESET}"
-                            )
-                            revert_status_ok = True
-                            for entry in file_edits:
-                                eid = entry.get("edit_id")
-                                if not history.update_entry_status(
-                                    entry,
-                                    original_statuses.get(eid, "pending"),
-                                    history_root,
-                                    lock_timeout=lock_timeout,
-                                ):
-                                    revert_status_ok = False
-                            if not revert_status_ok:
-                                print(
-                                    f"{utils.COLOR_RED}Failed to revert all statuses.{utils.COLOR_RESET}"
-                                )
-
-                        except Exception as restore_err:
-                            print(
-                                f"{utils.COLOR_RED}Snapshot restore failed: {restore_err}{utils.COLOR_RESET}"
-                            )
-                        file_failed += len(file_edits)
-
-                    else:
-                        print(
-                            f"{utils.COLOR_GREEN}Successfully rejected edits and reconstructed {file_path_rel}.{utils.COLOR_RESET}"
-                        )
-                        file_success += len(file_edits)
+                    _run_post_hook(workspace_root, action, entry)
+            else:
+                file_failed += len(file_edits)
+                left_pending_files.append((file_path_rel, len(file_edits), error))
 
         total_successful += file_success
         total_failed += file_failed
         # End loop for file_path_rel
 
+    updated_ids = {e.get("edit_id") for e in status_result["updated"]}
+    status_result["failed"] = [
+        (e, "not updated")
+        for e in relevant_entries
+        if e.get("edit_id") not in updated_ids
+    ]
     print("\n" + "-" * 30)
     print(
-        f"Conversation {action} summary: {total_successful} successful actions, {total_failed} failed actions."
+        f"{scope} {action} summary: {total_successful} successful actions, {total_failed} failed actions."
     )
-    return total_successful, total_failed
+    print(f"Edits: {history.format_status_update_summary(status_result, target_status)}.")
+    if action == "reject":
+        _print_reject_summary(rejected_files, left_pending_files)
+    result.successful = total_successful
+    result.failed = total_failed
+    return result
 
 
 def handle_accept(
@@ -723,24 +1635,48 @@ def handle_accept(
     """Handle the accept command."""
     log.debug("Processing accept command")
     lock_timeout = args.timeout  # Pass timeout argument
+    options = history.ReplayOptions(verify=not args.no_verify)
+    before, after = _accept_reject_time_range(args)
 
     if not all_entries:
         print(f"{utils.COLOR_YELLOW}No edit history entries found.{utils.COLOR_RESET}")
         return
+    _check_acl("accept", args, workspace_root, all_entries)
 
     if args.edit_id:
-        _accept_or_reject_single(
+        result = _accept_or_reject_single(
             args.edit_id,
             "accept",
             workspace_root,
             history_root,
             all_entries,
             lock_timeout,
+            force=args.force,
+            options=options,
+            invocation=args.invocation,
         )
-    elif args.conv:
-        _accept_or_reject_conversation(
-            args.conv, "accept", workspace_root, history_root, all_entries, lock_timeout
+    else:
+        result = _accept_or_reject_conversation(
+            args.conv,
+            "accept",
+            workspace_root,
+            history_root,
+            all_entries,
+            lock_timeout,
+            force=args.force,
+            before=before,
+            after=after,
+            assume_yes=args.yes,
+            options=options,
+            invocation=args.invocation,
         )
+    metrics.METRICS.increment("accepts_total", result.successful)
+    if args.git_stage or config.get_config_value(workspace_root, "auto_git_stage"):
+        staged = git.stage_files(workspace_root, result.accepted_files)
+        if staged:
+            print(f"Staged {len(staged)} file(s) with git add.")
+    _print_checkpoint_mismatches(result.checkpoint_mismatches)
+    _raise_if_failed("accept", result)
 
 
 def handle_reject(
@@ -752,24 +1688,91 @@ def handle_reject(
     """Handle the reject command."""
     log.debug("Processing reject command")
     lock_timeout = args.timeout  # Pass timeout argument
+    if args.fuzz < 0:
+        raise HistoryError("--fuzz must not be negative.")
+    before, after = _accept_reject_time_range(args)
+    options = history.ReplayOptions(
+        args.fuzz,
+        args.ignore_whitespace,
+        verify=not args.no_verify,
+        show_reapplied=args.show_reapplied,
+    )
+    ignored = None if args.no_ignore else ignore.load_ignore_matcher(workspace_root)
 
     if not all_entries:
         print(f"{utils.COLOR_YELLOW}No edit history entries found.{utils.COLOR_RESET}")
         return
+    _check_acl("reject", args, workspace_root, all_entries)
 
     if args.edit_id:
-        _accept_or_reject_single(
+        result = _accept_or_reject_single(
             args.edit_id,
             "reject",
             workspace_root,
             history_root,
             all_entries,
             lock_timeout,
+            ignored=ignored,
+            options=options,
+            invocation=args.invocation,
+        )
+    else:
+        result = _accept_or_reject_conversation(
+            args.conv,
+            "reject",
+            workspace_root,
+            history_root,
+            all_entries,
+            lock_timeout,
+            ignored=ignored,
+            before=before,
+            after=after,
+            assume_yes=args.yes,
+            options=options,
+            invocation=args.invocation,
         )
-    elif args.conv:
-        _accept_or_reject_conversation(
-            args.conv, "reject", workspace_root, history_root, all_entries, lock_timeout
+    metrics.METRICS.increment("rejects_total", result.successful)
+    _print_checkpoint_mismatches(result.checkpoint_mismatches)
+    _raise_if_failed("reject", result)
+
+
+def _accept_reject_time_range(
+    args: argparse.Namespace,
+) -> Tuple[Optional[float], Optional[float]]:
+    """The --before/--after range of accept or reject, which needs --conv."""
+    before = _parse_time_option(args.before, "--before")
+    after = _parse_time_option(args.after, "--after")
+    if (before is not None or after is not None) and not args.conv:
+        raise HistoryError(
+            "--before and --after select edits of a conversation (--conv)."
         )
+    return before, after
+
+
+def _print_checkpoint_mismatches(mismatches: List[str]) -> None:
+    """
+    Ends an accept or reject with the checkpoints its re-applies distrusted,
+    by the edit IDs they belong to.
+    """
+    if not mismatches:
+        return
+    print(
+        f"{utils.COLOR_YELLOW}{len(mismatches)} checkpoint(s) did not match the hash_before of their edit, so the files re-applied from them may be wrong. Once such a file is right, make it the checkpoint with:{utils.COLOR_RESET}"
+    )
+    for edit_id in mismatches:
+        print(f"  mcpdiff rebaseline --edit-id {edit_id}")
+
+
+def _raise_if_failed(action: str, result: ActionResult) -> None:
+    """
+    Makes a command that failed to accept or reject some edits exit non-zero,
+    with the kind of the last error they failed with.
+    """
+    if not result.failed:
+        return
+    if result.errors:
+        raise result.errors[-1]
+    raise HistoryError(f"{result.failed} {action} action(s) failed.")
 
 
 def handle_review(
@@ -815,6 +1818,8 @@ def handle_review(
     accepted_count = 0
     rejected_count = 0
     skipped_count = 0
+    rules = acl.load_acl(workspace_root)
+    tags = history.get_tags_by_conversation(history.load_tags(history_root))
 
     try:
         for i, entry in enumerate(pending_entries):
@@ -827,8 +1832,8 @@ def handle_review(
                 f"{utils.COLOR_CYAN}Reviewing Edit {i + 1}/{len(pending_entries)} - ID: {edit_id}{utils.COLOR_RESET}"
             )
             print(f"Conversation: {conv_id}, File: {file_path_rel}")
-            history.print_entry_list_header()
-            print(history.format_entry_summary(entry))
+            history.print_entry_list_header(tags)
+            print(history.format_entry_summary(entry, tags=tags))
             print("-" * 100)
 
             diff_content = history.get_diff_for_entry(entry, history_root)
@@ -843,19 +1848,38 @@ def handle_review(
                 )
                 choice = input(prompt).lower().strip()
 
+                if choice in ["a", "accept", "r", "reject"]:
+                    action = "accept" if choice.startswith("a") else "reject"
+                    affected = [entry]
+                    if action == "reject":
+                        affected += [
+                            e for e, _ in _unrejected_dependents([entry], all_entries)
+                        ]
+                    try:
+                        acl.check_permission(
+                            rules,
+                            action,
+                            [e.get("conversation_id") for e in affected],
+                            acl.current_user(),
+                        )
+                    except PermissionDeniedError as e:
+                        print(f"{utils.COLOR_RED}{e}{utils.COLOR_RESET}")
+                        continue
+
                 if choice in ["a", "accept"]:
                     print("Accepting...")
                     # Use the single accept function - it handles reconstruction & status update
                     # Pass the full all_entries list for context
-                    a_ok, a_fail = _accept_or_reject_single(
+                    accepted = _accept_or_reject_single(
                         edit_id,
                         "accept",
                         workspace_root,
                         history_root,
                         all_entries,
                         lock_timeout,
+                        invocation=args.invocation,
                     )
-                    if a_ok > 0:
+                    if accepted.successful > 0:
                         accepted_count += 1
                     # Update all_entries list with accepted status for subsequent reviews in this session
                     for idx, e in enumerate(all_entries):
@@ -866,15 +1890,16 @@ def handle_review(
                 elif choice in ["r", "reject"]:
                     print("Rejecting...")
                     # Use the single reject function
-                    r_ok, r_fail = _accept_or_reject_single(
+                    rejected = _accept_or_reject_single(
                         edit_id,
                         "reject",
                         workspace_root,
                         history_root,
                         all_entries,
                         lock_timeout,
+                        invocation=args.invocation,
                     )
-                    if r_ok > 0:
+                    if rejected.successful > 0:
                         rejected_count += 1
                     # Status already updated in all_entries by the helper on success
                     break  # Next entry
@@ -912,6 +1937,171 @@ def handle_review(
         print("=" * 80)
 
 
+def handle_crash_recover(
+    args: argparse.Namespace,
+    workspace_root: Path,
+    history_root: Path,
+    all_entries: List[Dict[str, Any]],
+) -> None:
+    """
+    Handle the crash-recover command: deal with the WALs of log writes a crash
+    interrupted, replaying those that did not reach their log.
+    """
+    results = [
+        {
+            "wal_file": wal_path.name,
+            "outcome": utils.recover_wal(wal_path, args.timeout),
+        }
+        for wal_path in utils.find_wal_files(history_root)
+    ]
+    if args.json:
+        print(json.dumps(results, indent=2))
+        return
+    if not results:
+        print("No WAL files found: every log write completed.")
+        return
+    descriptions = {
+        "replayed": f"{utils.COLOR_GREEN}replayed into its log{utils.COLOR_RESET}",
+        "applied": "already in its log, removed",
+        "discarded": f"{utils.COLOR_YELLOW}incomplete, discarded (its log was not changed){utils.COLOR_RESET}",
+        "stale": f"{utils.COLOR_YELLOW}its log was written after it, discarded{utils.COLOR_RESET}",
+    }
+    for result in results:
+        print(f"  {result['wal_file']}: {descriptions[result['outcome']]}")
+    replayed = sum(result["outcome"] == "replayed" for result in results)
+    print(f"Recovered {len(results)} WAL file(s), {replayed} replayed.")
+
+
+def _print_conflict_blocks(file_path_abs: Path, blocks: List[Dict[str, int]]) -> None:
+    """Prints the lines of the conflict blocks of a file, numbered."""
+    lines = patch.split_lines(
+        file_path_abs.read_bytes().decode("utf-8", errors="surrogateescape")
+    )
+    for block in blocks:
+        print("-" * 100)
+        for number in range(block["start_line"], block["end_line"] + 1):
+            line = lines[number - 1].rstrip("\r\n")
+            color = utils.COLOR_RED if line[:7] in patch.CONFLICT_MARKERS else ""
+            reset = utils.COLOR_RESET if color else ""
+            print(f"{number:6d}  {color}{line}{reset}")
+    print("-" * 100)
+
+
+def handle_resolve_conflicts(
+    args: argparse.Namespace,
+    workspace_root: Path,
+    history_root: Path,
+    all_entries: List[Dict[str, Any]],
+) -> None:
+    """
+    Handle the resolve-conflicts command: list the files edits left merge
+    conflict markers in, and walk through resolving them one by one.
+    """
+    conflicted = history.find_conflicted_files(all_entries, workspace_root, args.conv)
+    if args.json:
+        print(json.dumps(conflicted, indent=2))
+        return
+    if not conflicted:
+        print(
+            f"{utils.COLOR_GREEN}No edits left merge conflict markers{f' in conversation {args.conv}' if args.conv else ''}.{utils.COLOR_RESET}"
+        )
+        return
+
+    state_colors = {
+        "unresolved": utils.COLOR_RED,
+        "resolved": utils.COLOR_GREEN,
+        "missing": utils.COLOR_YELLOW,
+    }
+    print(
+        f"{utils.COLOR_CYAN}{'State':<10}  {'Edit':8}  {'Conflicts':<20}  File Path{utils.COLOR_RESET}"
+    )
+    print("-" * 100)
+    for item in conflicted:
+        state = item["state"]
+        ranges = history.format_line_ranges(item["live"] or item["recorded"])
+        print(
+            f"{state_colors[state]}{state:<10}{utils.COLOR_RESET}  {item['edit_id'][:8]}  {ranges:<20}  {item['file_path']}"
+        )
+    unresolved = [item for item in conflicted if item["state"] == "unresolved"]
+    if args.list or not unresolved:
+        print(f"\n{len(unresolved)} of {len(conflicted)} files still have conflicts.")
+        return
+
+    editor = os.environ.get("VISUAL") or os.environ.get("EDITOR") or "vi"
+    resolved_count = 0
+    skipped_count = 0
+    try:
+        for i, item in enumerate(unresolved):
+            file_path_abs = workspace_root / item["file_path"]
+            blocks = item["live"]
+            print("\n" + "=" * 80)
+            print(
+                f"{utils.COLOR_CYAN}Resolving {i + 1}/{len(unresolved)} - {item['file_path']} (edit {item['edit_id'][:8]}){utils.COLOR_RESET}"
+            )
+            while blocks:
+                _print_conflict_blocks(file_path_abs, blocks)
+                prompt = (
+                    f"\nAction? ({utils.COLOR_BLUE}[e]{utils.COLOR_RESET}dit, "
+                    f"{utils.COLOR_GREEN}[o]{utils.COLOR_RESET}urs, "
+                    f"{utils.COLOR_GREEN}[t]{utils.COLOR_RESET}heirs, "
+                    f"{utils.COLOR_BLUE}[s]{utils.COLOR_RESET}kip, "
+                    f"{utils.COLOR_YELLOW}[q]{utils.COLOR_RESET}uit): "
+                )
+                choice = input(prompt).lower().strip()
+                if choice in ["e", "edit"]:
+                    command = shlex.split(editor)
+                    command += [f"+{blocks[0]['start_line']}", str(file_path_abs)]
+                    try:
+                        subprocess.run(command, check=False)
+                    except OSError as e:
+                        print(
+                            f"{utils.COLOR_RED}Cannot run {editor}: {e}{utils.COLOR_RESET}"
+                        )
+                        continue
+                elif choice in ["o", "ours", "t", "theirs"]:
+                    side = "ours" if choice.startswith("o") else "theirs"
+                    content = file_path_abs.read_bytes().decode(
+                        "utf-8", errors="surrogateescape"
+                    )
+                    resolved = patch.resolve_conflict_markers(content, side)
+                    file_path_abs.write_bytes(
+                        resolved.encode("utf-8", errors="surrogateescape")
+                    )
+                elif choice in ["s", "skip"]:
+                    print(f"{utils.COLOR_YELLOW}File skipped.{utils.COLOR_RESET}")
+                    skipped_count += 1
+                    break
+                elif choice in ["q", "quit"]:
+                    raise KeyboardInterrupt("Resolving quit by user")
+                else:
+                    print(
+                        f"{utils.COLOR_RED}Invalid choice. Please try again.{utils.COLOR_RESET}"
+                    )
+                    continue
+                content = file_path_abs.read_bytes().decode(
+                    "utf-8", errors="surrogateescape"
+                )
+                blocks = patch.find_conflict_markers(patch.split_lines(content))
+                if not blocks:
+                    print(f"{utils.COLOR_GREEN}No conflicts left.{utils.COLOR_RESET}")
+                    resolved_count += 1
+    except (KeyboardInterrupt, EOFError):
+        pass
+    finally:
+        print("\n" + "=" * 80)
+        print("Resolve Summary:")
+        print(f"  {utils.COLOR_GREEN}Resolved: {resolved_count}{utils.COLOR_RESET}")
+        print(f"  {utils.COLOR_BLUE}Skipped:  {skipped_count}{utils.COLOR_RESET}")
+        remaining = len(unresolved) - resolved_count - skipped_count
+        if remaining > 0:
+            print(f"  {utils.COLOR_YELLOW}Remaining: {remaining}{utils.COLOR_RESET}")
+        print("=" * 80)
+        if resolved_count:
+            print(
+                "Resolved files no longer match their last edit; 'mcpdiff diagnose' shows them as modified."
+            )
+
+
 def handle_cleanup(
     args: argparse.Namespace,
     workspace_root: Path,
@@ -928,29 +2118,2143 @@ def handle_cleanup(
     else:
         print("No stale locks found to clean up.")
 
+    # Older servers locked files with <file>.lock next to them
+    strays = history.find_stray_lock_files(workspace_root, all_entries)
+    if not strays:
+        return
+    print(f"Found {len(strays)} lock file(s) left next to tracked files:")
+    for lock_file in strays:
+        print(f"  {history.get_relative_path(lock_file, workspace_root)}")
+    if not args.force:
+        try:
+            choice = input("Remove them? (y/n): ").lower().strip()
+        except (EOFError, KeyboardInterrupt):
+            choice = "n"
+        if choice not in ["y", "yes"]:
+            print("Left them in place.")
+            return
+    for lock_file in strays:
+        lock_file.unlink(missing_ok=True)
+    print(
+        f"{utils.COLOR_GREEN}Removed {len(strays)} stray lock file(s).{utils.COLOR_RESET}"
+    )
+
 
-# --- Main Execution ---
+def handle_annotate(
+    args: argparse.Namespace,
+    workspace_root: Path,
+    history_root: Path,
+    all_entries: List[Dict[str, Any]],
+) -> None:
+    """Handle the annotate command: set or clear the review notes of an edit."""
+    entry = history.find_entry_by_id(all_entries, args.edit_id)
+    if not entry:
+        raise HistoryError(f"No entry found with ID prefix: {args.edit_id}")
+    notes = args.note.strip()
+    history.set_review_notes(entry, notes, history_root, lock_timeout=args.timeout)
+    edit_id = entry["edit_id"]
+    if notes:
+        print(
+            f"{utils.COLOR_GREEN}Saved review notes for edit {edit_id}.{utils.COLOR_RESET}"
+        )
+    else:
+        print(f"Removed the review notes of edit {edit_id}.")
 
 
-def main():
-    parser = argparse.ArgumentParser(
-        description="MCP Diff Tool: Review and manage LLM file edits.",
-        formatter_class=argparse.RawDescriptionHelpFormatter,
-        epilog="""
-Examples:
-  mcpdiff status                     # Show recent history status (newest first limited)
-  mcpdiff st -n 0                    # Show all history status (newest first)
-  mcpdiff status --conv 17... --file src/main.py --status pending
-  mcpdiff show <edit_id_prefix>      # Show diff for a specific edit
-  mcpdiff show <conv_id_prefix>      # Show all diffs for a conversation
-  mcpdiff accept -e <edit_id_prefix> # Accept a specific edit (reconstructs file)
-  mcpdiff accept -c <conv_id_prefix> # Accept all pending edits for a conversation
-  mcpdiff reject -e <edit_id_prefix> # Reject an edit (snapshots, rejects, reconstructs)
-  mcpdiff reject -c <conv_id_prefix> # Reject all pending/accepted edits for a conversation
-  mcpdiff review                     # Interactively review pending edits (oldest first)
-  mcpdiff review -c <conv_id>        # Review pending edits for a specific conversation
-  mcpdiff cleanup                    # Clean up stale locks
-""",
+def handle_split(
+    args: argparse.Namespace,
+    workspace_root: Path,
+    history_root: Path,
+    all_entries: List[Dict[str, Any]],
+) -> None:
+    """Handle the split command: divide a Rust edit into one edit per item."""
+    entry = history.find_entry_by_id(all_entries, args.edit_id)
+    if not entry:
+        raise HistoryError(f"No entry found with ID prefix: {args.edit_id}")
+    children = split.split_entry(
+        entry,
+        all_entries,
+        history_root,
+        lock_timeout=args.timeout,
+        invocation=args.invocation,
+    )
+    print(
+        f"{utils.COLOR_GREEN}Split edit {entry['edit_id']} into {len(children)} edits:{utils.COLOR_RESET}"
+    )
+    for child in children:
+        print(f"  {child['edit_id']}  {child['label']}")
+
+
+def handle_rebaseline(
+    args: argparse.Namespace,
+    workspace_root: Path,
+    history_root: Path,
+    all_entries: List[Dict[str, Any]],
+) -> None:
+    """Handle the rebaseline command: re-checkpoint an edit from its live file."""
+    entry = history.find_entry_by_id(all_entries, args.edit_id)
+    if not entry:
+        raise HistoryError(f"No entry found with ID prefix: {args.edit_id}")
+    edit_id = entry["edit_id"]
+    file_path_rel = entry.get("file_path")
+    checkpoint_hash = history.checkpoint_mismatch(entry, history_root)
+    print(f"Edit:        {edit_id}")
+    print(f"File:        {utils.display_path(file_path_rel)}")
+    print(f"Checkpoint:  {entry.get('checkpoint_file') or 'none'}")
+    print(f"hash_before: {entry.get('hash_before') or 'N/A'}")
+    if checkpoint_hash:
+        print(
+            f"{utils.COLOR_YELLOW}The checkpoint hashes to {checkpoint_hash}.{utils.COLOR_RESET}"
+        )
+    if not args.yes:
+        try:
+            choice = input(
+                f"Replace the checkpoint with the current content of {utils.display_path(file_path_rel)}? (y/n): "
+            )
+        except (EOFError, KeyboardInterrupt):
+            choice = "n"
+        if choice.lower().strip() not in ["y", "yes"]:
+            print("Aborted.")
+            return
+    result = history.rebaseline_entry(
+        entry,
+        workspace_root,
+        history_root,
+        lock_timeout=args.timeout,
+        invocation=args.invocation,
+    )
+    print(
+        f"{utils.COLOR_GREEN}Edit {edit_id} now starts from {result['checkpoint_file']} (hash_before {result['hash_before'][:8]}).{utils.COLOR_RESET}"
+    )
+
+
+def handle_mark_pending(
+    args: argparse.Namespace,
+    workspace_root: Path,
+    history_root: Path,
+    all_entries: List[Dict[str, Any]],
+) -> None:
+    """Handle the mark-pending command: reset an accepted/rejected edit to pending."""
+    entry = history.find_entry_by_id(all_entries, args.edit_id)
+    if not entry:
+        raise HistoryError(f"No entry found with ID prefix: {args.edit_id}")
+
+    edit_id = entry.get("edit_id", "N/A")
+    current_status = entry.get("status", "unknown").lower()
+    file_path_rel = entry.get("file_path", "N/A")
+
+    if current_status == "pending" and not args.force:
+        raise HistoryError(
+            f"Edit {edit_id} is already pending. Use --force to rewrite it anyway."
+        )
+
+    if current_status == "rejected":
+        # Rejecting reverted the change on disk; setting pending does not re-apply it
+        print(
+            f"{utils.COLOR_YELLOW}Warning: Edit {edit_id} was rejected and its change was reverted from {file_path_rel}.{utils.COLOR_RESET}"
+        )
+        print(
+            f"{utils.COLOR_YELLOW}The working tree may not match the recorded state after this. Run 'mcpdiff diagnose -f {file_path_rel}' to check.{utils.COLOR_RESET}"
+        )
+        if not args.force:
+            try:
+                choice = input("Proceed? (y/n): ").lower().strip()
+            except (EOFError, KeyboardInterrupt):
+                choice = "n"
+            if choice not in ["y", "yes"]:
+                print("Aborted.")
+                return
+
+    conv_id = entry.get("conversation_id", "unknown_conv")
+    conv_lock = utils.ConversationLock(history_root, conv_id)
+    conv_lock.acquire(args.timeout)
+    try:
+        if not history.update_entry_status(
+            entry,
+            "pending",
+            history_root,
+            lock_timeout=args.timeout,
+            invocation=args.invocation,
+        ):
+            raise HistoryError(f"Failed to update status for edit {edit_id}")
+    finally:
+        conv_lock.release()
+    print(
+        f"{utils.COLOR_GREEN}Marked edit {edit_id} as pending (was {current_status}).{utils.COLOR_RESET}"
+    )
+
+
+def handle_diagnose(
+    args: argparse.Namespace,
+    workspace_root: Path,
+    history_root: Path,
+    all_entries: List[Dict[str, Any]],
+) -> None:
+    """Handle the diagnose command: compare tracked files with their recorded state."""
+    file_paths = sorted(
+        {
+            e["file_path"]
+            for e in all_entries
+            if e.get("file_path")
+            and e.get("operation") not in ["snapshot", "revert"]
+            and (not args.file or utils.path_matches_filter(e["file_path"], args.file))
+        }
+    )
+    if not file_paths:
+        print(f"{utils.COLOR_YELLOW}No tracked files found.{utils.COLOR_RESET}")
+        return
+
+    state_colors = {
+        "ok": utils.COLOR_GREEN,
+        "modified": utils.COLOR_RED,
+        "missing": utils.COLOR_RED,
+        "unexpected": utils.COLOR_RED,
+        "unknown": utils.COLOR_YELLOW,
+    }
+    problems = 0
+    conflicted = 0
+    hashes = utils.WorkspaceRootHashes(workspace_root)
+    print(
+        f"{utils.COLOR_CYAN}{'State':<10}  {'Last Edit':8}  File Path{utils.COLOR_RESET}"
+    )
+    print("-" * 100)
+    for file_path_rel in file_paths:
+        result = history.diagnose_file(
+            file_path_rel, all_entries, workspace_root, hashes
+        )
+        state = result["state"]
+        if state not in ["ok", "unknown"]:
+            problems += 1
+        edit_short = (result["edit_id"] or "-")[:8]
+        structure_note = (
+            f"  {utils.COLOR_YELLOW}(workspace structure changed){utils.COLOR_RESET}"
+            if result["structure_changed"]
+            else ""
+        )
+        if result["conflict_markers"]:
+            conflicted += 1
+            structure_note += f"  {utils.COLOR_RED}(conflict markers at {history.format_line_ranges(result['conflict_markers'])}){utils.COLOR_RESET}"
+        print(
+            f"{state_colors[state]}{state:<10}{utils.COLOR_RESET}  {edit_short:8}  {file_path_rel}{structure_note}"
+        )
+
+    if problems:
+        print(
+            f"\n{utils.COLOR_RED}{problems} of {len(file_paths)} files do not match their recorded state.{utils.COLOR_RESET}"
+        )
+    else:
+        print(
+            f"\n{utils.COLOR_GREEN}All {len(file_paths)} files match their recorded state.{utils.COLOR_RESET}"
+        )
+    if conflicted:
+        print(
+            f"{utils.COLOR_RED}{conflicted} files have merge conflict markers left by their last edit. Run 'mcpdiff resolve-conflicts' to resolve them.{utils.COLOR_RESET}"
+        )
+
+    # Edits recorded in a workspace with other file names may not re-apply cleanly
+    fingerprinted = [
+        e
+        for e in all_entries
+        if e.get("workspace_root_hash")
+        and (not args.file or utils.path_matches_filter(e["file_path"], args.file))
+    ]
+    changed = [
+        e for e in fingerprinted if history.workspace_structure_changed(e, hashes)
+    ]
+    if changed:
+        latest = changed[-1]
+        print(
+            f"{utils.COLOR_YELLOW}Warning: the workspace's files were added, removed or renamed since {len(changed)} of {len(fingerprinted)} edits were recorded (latest: {latest['edit_id'][:8]} at {latest.get('timestamp', '?')}). Re-applying them may not find the paths they expect.{utils.COLOR_RESET}"
+        )
+
+
+def handle_tag(
+    args: argparse.Namespace,
+    workspace_root: Path,
+    history_root: Path,
+    all_entries: List[Dict[str, Any]],
+) -> None:
+    """Handle the tag command: name, list, or remove conversation tags."""
+    tags = history.load_tags(history_root)
+
+    if args.list:
+        if not tags:
+            print(f"{utils.COLOR_YELLOW}No conversation tags defined.{utils.COLOR_RESET}")
+            return
+        print(f"{utils.COLOR_CYAN}{'Tag':<24}  Conversation ID{utils.COLOR_RESET}")
+        print("-" * 100)
+        for tag, conv_id in sorted(tags.items()):
+            print(f"{tag:<24}  {conv_id}")
+        return
+
+    if args.remove:
+        if args.remove not in tags:
+            raise HistoryError(f"No such tag: {args.remove}")
+        conv_id = tags.pop(args.remove)
+        history.save_tags(history_root, tags)
+        print(f"Removed tag '{args.remove}' from conversation {conv_id}.")
+        return
+
+    if not args.conv or not args.name:
+        raise HistoryError("Tagging requires both --conv and --name.")
+    name = args.name.strip()
+    if not name:
+        raise HistoryError("Tag name must not be empty.")
+
+    conv_ids = sorted(
+        {
+            e["conversation_id"]
+            for e in history.find_entries_by_conversation(all_entries, args.conv)
+            if e.get("conversation_id")
+        }
+    )
+    if not conv_ids:
+        raise HistoryError(f"No conversation found matching ID: {args.conv}")
+    if len(conv_ids) > 1:
+        raise AmbiguousIDError(
+            f"Conversation ID '{args.conv}' matches multiple conversations: {', '.join(conv_ids)}"
+        )
+    conv_id = conv_ids[0]
+
+    existing = tags.get(name)
+    if existing and existing != conv_id:
+        raise HistoryError(
+            f"Tag '{name}' is already used by conversation {existing}. Remove it first."
+        )
+    # One tag per conversation: renaming replaces the previous tag
+    previous = [tag for tag, cid in tags.items() if cid == conv_id and tag != name]
+    for tag in previous:
+        del tags[tag]
+    tags[name] = conv_id
+    history.save_tags(history_root, tags)
+    replaced = f" (replaces '{previous[0]}')" if previous else ""
+    print(
+        f"{utils.COLOR_GREEN}Tagged conversation {conv_id} as '{name}'{replaced}.{utils.COLOR_RESET}"
+    )
+
+
+def handle_du(
+    args: argparse.Namespace,
+    workspace_root: Path,
+    history_root: Path,
+    all_entries: List[Dict[str, Any]],
+) -> None:
+    """Handle the du command: disk usage of the history directory."""
+    usage = history.compute_disk_usage(history_root, all_entries)
+    conversations = usage["conversations"]
+    if args.top and args.top > 0:
+        conversations = conversations[: args.top]
+
+    if args.json:
+        print(
+            json.dumps(
+                {
+                    "totals": usage["totals"],
+                    "conversations": conversations,
+                    "orphans": usage["orphans"],
+                },
+                indent=2,
+            )
+        )
+        return
+
+    fmt = utils.format_size
+    print(
+        f"{utils.COLOR_CYAN}{'Conversation':<32}  {'Logs':>10}  {'Diffs':>10}  {'Checkpoints':>11}  {'Total':>10}{utils.COLOR_RESET}"
+    )
+    print("-" * 83)
+    for conv in conversations:
+        print(
+            f"{conv['conversation_id'][:32]:<32}  {fmt(conv['logs']):>10}  {fmt(conv['diffs']):>10}  {fmt(conv['checkpoints']):>11}  {fmt(conv['total']):>10}"
+        )
+    print("-" * 83)
+    totals = usage["totals"]
+    print(
+        f"{'Total':<32}  {fmt(totals['logs']):>10}  {fmt(totals['diffs']):>10}  {fmt(totals['checkpoints']):>11}  {fmt(totals['total']):>10}"
+    )
+    if totals["binary_diffs"]:
+        print(
+            f"Diffs include {fmt(totals['binary_diffs'])} of bsdiff patches of binary files."
+        )
+    hidden = len(usage["conversations"]) - len(conversations)
+    if hidden > 0:
+        print(f"({hidden} smaller conversations not shown, use --top 0 to show all)")
+
+    orphans = usage["orphans"]
+    if orphans:
+        orphan_bytes = sum(o["bytes"] for o in orphans)
+        print(
+            f"\n{utils.COLOR_YELLOW}Orphaned artifacts (not referenced by any log entry): {len(orphans)} files, {fmt(orphan_bytes)}{utils.COLOR_RESET}"
+        )
+        for orphan in orphans:
+            print(f"  {orphan['kind']:<10}  {fmt(orphan['bytes']):>10}  {orphan['path']}")
+    else:
+        print("\nNo orphaned artifacts found.")
+
+
+def handle_info(
+    args: argparse.Namespace,
+    workspace_root: Path,
+    history_root: Path,
+    all_entries: List[Dict[str, Any]],
+) -> None:
+    """Handle the info command: where the workspace is, its settings and size."""
+    current = config.load_config(workspace_root)
+    config_path = config.get_config_path(workspace_root)
+    settings = {
+        key: current.get(key, spec["default"])
+        for key, spec in sorted(config.CONFIG_KEYS.items())
+    }
+    stats = history.summarize_history(history_root, all_entries)
+    # Known statuses first, in their usual order
+    order = [status.value for status in api.Status]
+    by_status = dict(
+        sorted(
+            stats["entries_by_status"].items(),
+            key=lambda item: (
+                order.index(item[0]) if item[0] in order else len(order),
+                item[0],
+            ),
+        )
+    )
+
+    if args.json:
+        info = {
+            "workspace_root": str(workspace_root),
+            "history_root": str(history_root),
+            "config_file": str(config_path) if config_path.is_file() else None,
+            "config": settings,
+            **stats,
+            "entries_by_status": by_status,
+        }
+        print(json.dumps(info, indent=2))
+        return
+
+    fmt = utils.format_size
+    print(f"Workspace:    {workspace_root}")
+    print(f"History:      {history_root}")
+    if config_path.is_file():
+        print(f"Config:       {config_path}")
+    else:
+        print("Config:       none, all defaults")
+    for key, value in settings.items():
+        marker = "" if key in current else " (default)"
+        print(f"  {key:<22}  {value}{marker}")
+    print(f"Log files:    {stats['log_files']}")
+    counts = ", ".join(f"{count} {status}" for status, count in by_status.items())
+    print(f"Entries:      {stats['entries']}" + (f" ({counts})" if counts else ""))
+    print(f"Diffs:        {fmt(stats['diffs_bytes'])}")
+    print(f"Checkpoints:  {fmt(stats['checkpoints_bytes'])}")
+    latest = stats["latest_timestamp"]
+    print(
+        f"Last entry:   {utils.format_timestamp_absolute(latest, True) if latest else 'none'}"
+    )
+
+
+def handle_conversations(
+    args: argparse.Namespace,
+    workspace_root: Path,
+    history_root: Path,
+    all_entries: List[Dict[str, Any]],
+) -> None:
+    """Handle the conversations command: one line per conversation."""
+    conversations = api.summarize_conversations(history_root, all_entries)
+
+    if args.session:
+        session_convs = history.conversations_in_session(all_entries, args.session)
+        conversations = [
+            c for c in conversations if c.conversation_id in session_convs
+        ]
+    if args.namespace:
+        conversations = [c for c in conversations if c.namespace == args.namespace]
+    if args.active:
+        conversations = [c for c in conversations if c.pending > 0]
+    if args.idle:
+        seconds = utils.parse_time_filter(args.idle)
+        if seconds is None:
+            raise HistoryError(
+                f"Invalid --idle duration: {args.idle} (e.g. 30m, 2h, 1d)"
+            )
+        cutoff = time.time() - seconds
+        conversations = [c for c in conversations if (c.last_activity or 0) < cutoff]
+
+    # (depth, summary): forks under the conversation they were forked from
+    rows = [(0, c) for c in conversations]
+    if args.tree:
+        by_id = {c.conversation_id: c for c in conversations}
+        rows = [
+            (depth, by_id[summary["conversation_id"]])
+            for depth, summary in history.conversation_tree(
+                [asdict(c) for c in conversations]
+            )
+        ]
+
+    if args.json:
+        if args.tree:
+            print(json.dumps([dict(asdict(c), depth=d) for d, c in rows], indent=2))
+        else:
+            print(json.dumps([asdict(c) for c in conversations], indent=2))
+        return
+
+    if not conversations:
+        print(
+            f"{utils.COLOR_YELLOW}No conversations match the specified filters.{utils.COLOR_RESET}"
+        )
+        return
+
+    tags = history.get_tags_by_conversation(history.load_tags(history_root))
+    print(
+        f"{utils.COLOR_CYAN}{'Last Activity':<19}  {'Conversation':<36}  {'Edits':>5}  {'Pending':>7}  {'Accepted':>8}  {'Rejected':>8}  {'Files':>5}{utils.COLOR_RESET}"
+    )
+    print("-" * 100)
+    for depth, conv in rows:
+        last = utils.format_timestamp_absolute(conv.last_activity or 0, True)
+        conv_id = conv.conversation_id
+        label = f"{conv_id[:8]} [{tags[conv_id]}]" if conv_id in tags else conv_id
+        if depth:
+            label = "   " * (depth - 1) + "└─ " + label
+        pending = f"{conv.pending:>7}"
+        if conv.pending:
+            pending = f"{utils.COLOR_YELLOW}{pending}{utils.COLOR_RESET}"
+        print(
+            f"{last:<19}  {label[:36]:<36}  {conv.edits:>5}  {pending}  {conv.accepted:>8}  {conv.rejected:>8}  {conv.files:>5}"
+        )
+    count = len(conversations)
+    print(f"\n{count} conversation{'' if count == 1 else 's'}.")
+
+
+def _print_ranking(
+    args: argparse.Namespace,
+    all_entries: List[Dict[str, Any]],
+    key: str,
+    heading: str,
+    labels: Optional[Dict[str, str]] = None,
+    conv_id: Optional[str] = None,
+) -> None:
+    """
+    The top-files and top-conversations table: the values of key with the most
+    edits, or with --by bytes the most byte growth.
+    """
+    entries = history.filter_entries(
+        all_entries,
+        conv_id=conv_id,
+        limit=None,
+        before=_parse_time_option(args.until, "--until"),
+        after=_parse_time_option(args.since, "--since"),
+    )
+    rows = history.rank_by_edits(entries, key, by=args.by)
+    if args.limit > 0:
+        rows = rows[: args.limit]
+
+    if args.json:
+        print(json.dumps(rows, indent=2))
+        return
+    if not rows:
+        print(f"{utils.COLOR_YELLOW}No edits found.{utils.COLOR_RESET}")
+        return
+
+    print(
+        f"{utils.COLOR_CYAN}{'Rank':>4}  {heading:<50}  {'Edits':>5}  {'Pending':>7}  {'Accepted':>8}  {'Rejected':>8}  {'Growth':>11}{utils.COLOR_RESET}"
+    )
+    print("-" * 105)
+    for rank, row in enumerate(rows, 1):
+        label = (labels or {}).get(row[key], row[key])
+        if len(label) > 50:
+            label = "..." + label[-47:]
+        pending = f"{row['pending']:>7}"
+        if row["pending"]:
+            pending = f"{utils.COLOR_YELLOW}{pending}{utils.COLOR_RESET}"
+        growth = row["byte_growth"]
+        growth = ("-" if growth < 0 else "+") + utils.format_size(abs(growth))
+        print(
+            f"{rank:>4}  {label:<50}  {row['total_edits']:>5}  {pending}  {row['accepted']:>8}  {row['rejected']:>8}  {growth:>11}"
+        )
+
+
+def handle_top_files(
+    args: argparse.Namespace,
+    workspace_root: Path,
+    history_root: Path,
+    all_entries: List[Dict[str, Any]],
+) -> None:
+    """Handle the top-files command: the most edited or most grown files."""
+    _print_ranking(args, all_entries, "file_path", "File", conv_id=args.conv)
+
+
+def handle_top_conversations(
+    args: argparse.Namespace,
+    workspace_root: Path,
+    history_root: Path,
+    all_entries: List[Dict[str, Any]],
+) -> None:
+    """
+    Handle the top-conversations command: the conversations with most edits or
+    most byte growth.
+    """
+    tags = history.get_tags_by_conversation(history.load_tags(history_root))
+    labels = {conv_id: f"{conv_id[:8]} [{tag}]" for conv_id, tag in tags.items()}
+    _print_ranking(args, all_entries, "conversation_id", "Conversation", labels)
+
+
+def handle_compare_convs(
+    args: argparse.Namespace,
+    workspace_root: Path,
+    history_root: Path,
+    all_entries: List[Dict[str, Any]],
+) -> None:
+    """Handle the compare-convs command: the files two conversations changed."""
+    tags = history.load_tags(history_root)
+    namespace = config.get_config_value(workspace_root, "namespace")
+    [conv_a] = _logged_conversation_ids(
+        history_root, history.resolve_conversation_tag(args.conv_a, tags, namespace)
+    )
+    [conv_b] = _logged_conversation_ids(
+        history_root, history.resolve_conversation_tag(args.conv_b, tags, namespace)
+    )
+    comparison = history.compare_conversations(all_entries, conv_a, conv_b)
+    if args.json:
+        print(json.dumps(comparison, indent=2))
+        return
+
+    print(f"{utils.COLOR_CYAN}A: {conv_a}\nB: {conv_b}{utils.COLOR_RESET}")
+    for key, heading in (("only_a", "Only in A"), ("only_b", "Only in B")):
+        print(f"\n{heading} ({len(comparison[key])}):")
+        for path in comparison[key]:
+            print(f"  {utils.display_path(path)}")
+    print(f"\nIn both ({len(comparison['both'])}):")
+    for item in comparison["both"]:
+        if item["identical"]:
+            state = f"{utils.COLOR_GREEN}same final content{utils.COLOR_RESET}"
+        else:
+            state = f"{utils.COLOR_YELLOW}different final content{utils.COLOR_RESET}"
+        print(f"  {utils.display_path(item['file_path'])}  ({state})")
+
+
+def _resolve_tracked_file(
+    file_arg: str, workspace_root: Path, all_entries: List[Dict[str, Any]]
+) -> str:
+    """
+    The logged path of the file `file_arg` refers to: a path to an existing
+    file, a workspace-relative path, or a substring matching one tracked file.
+    """
+    tracked = sorted({e["file_path"] for e in all_entries if e.get("file_path")})
+    candidate = Path(file_arg)
+    if candidate.exists():
+        relative = history.get_relative_path(candidate, workspace_root)
+    else:
+        relative = utils.normalize_log_path(file_arg, workspace_root)
+    if relative in tracked:
+        return relative
+    matches = [path for path in tracked if utils.path_matches_filter(path, file_arg)]
+    if len(matches) == 1:
+        return matches[0]
+    if not matches:
+        raise HistoryError(f"No history found for file: {file_arg}")
+    raise AmbiguousIDError(
+        f"File '{file_arg}' matches multiple tracked files: {', '.join(matches)}"
+    )
+
+
+def _parse_line_range(lines_arg: Optional[str]) -> Tuple[int, Optional[int]]:
+    """Parses --lines START-END, START- or N (1-based, inclusive)."""
+    if not lines_arg:
+        return 1, None
+    start_str, sep, end_str = lines_arg.partition("-")
+    try:
+        start = int(start_str)
+        end = (int(end_str) if end_str else None) if sep else start
+    except ValueError:
+        raise HistoryError(f"Invalid --lines range: {lines_arg} (e.g. 120-160)")
+    if start < 1 or (end is not None and end < start):
+        raise HistoryError(f"Invalid --lines range: {lines_arg} (e.g. 120-160)")
+    return start, end
+
+
+def handle_blame(
+    args: argparse.Namespace,
+    workspace_root: Path,
+    history_root: Path,
+    all_entries: List[Dict[str, Any]],
+) -> None:
+    """Handle the blame command: which edit last touched each line of a file."""
+    file_path_rel = _resolve_tracked_file(args.file, workspace_root, all_entries)
+    start, end = _parse_line_range(args.lines)
+    lines = replay.replay_file(
+        file_path_rel, all_entries, history_root, apply_only_accepted=args.accepted
+    )
+    if start > len(lines):
+        raise HistoryError(
+            f"{utils.display_path(file_path_rel)} has {len(lines)} lines after replay."
+        )
+    end = min(end or len(lines), len(lines))
+
+    number_width = len(str(end))
+    for number in range(start, end + 1):
+        line = lines[number - 1]
+        text = utils.display_path(line.text.rstrip("\r\n"))
+        if line.origin is None:
+            label = f"{replay.BASELINE:<8}  {'':<8}  {'':<19}"
+            color = ""
+        else:
+            timestamp = utils.format_timestamp_absolute(
+                line.origin.get("timestamp", 0), True
+            )
+            conv_id = line.origin.get("conversation_id", "N/A")
+            label = f"{line.edit_id[:8]:<8}  {conv_id[:8]:<8}  {timestamp:<19}"
+            color = utils.COLOR_YELLOW
+        print(
+            f"{color}{label}{utils.COLOR_RESET if color else ''}  {number:>{number_width}}) {text}"
+        )
+
+
+def handle_plan(
+    args: argparse.Namespace,
+    workspace_root: Path,
+    history_root: Path,
+    all_entries: List[Dict[str, Any]],
+) -> None:
+    """Handle the plan command: how accept and reject would re-apply files."""
+    conv_ref = history.resolve_conversation_tag(
+        args.conv, history.load_tags(history_root)
+    )
+    conv_entries = history.find_entries_by_conversation(all_entries, conv_ref)
+    if not conv_entries:
+        raise HistoryError(f"No entries found for conversation matching ID: {args.conv}")
+    if args.file:
+        files = [_resolve_tracked_file(args.file, workspace_root, all_entries)]
+    else:
+        files = sorted(
+            {
+                e["file_path"]
+                for e in conv_entries
+                if e.get("tool_name") != "mcpdiff" and e.get("file_path")
+            }
+        )
+    plans = [
+        history.plan_file_replay(
+            file_rel, all_entries, history_root, apply_only_accepted=args.accepted
+        )
+        for file_rel in files
+    ]
+
+    if args.json:
+        print(json.dumps([plan.to_dict(history_root) for plan in plans], indent=2))
+        return
+    for i, plan in enumerate(plans):
+        if i:
+            print()
+        _print_replay_plan(plan, history_root)
+
+
+def _print_replay_plan(plan: history.ReplayPlan, history_root: Path) -> None:
+    """Prints a replay plan as its starting point and a table of steps."""
+    edits = (
+        "accepted edits" if plan.apply_only_accepted else "accepted and pending edits"
+    )
+    print(
+        f"{utils.COLOR_CYAN}Re-applying {utils.display_path(plan.file_path)} ({edits}){utils.COLOR_RESET}"
+    )
+    start_id = (plan.start_edit_id or "N/A")[:8]
+    if plan.start == history.PLAN_START_CHECKPOINT:
+        checkpoint = history.get_relative_path(plan.checkpoint_path, history_root)
+        print(f"Start: checkpoint {checkpoint} (taken before edit {start_id})")
+    elif plan.start == history.PLAN_START_EMPTY:
+        print(f"Start: empty file (created by edit {start_id})")
+    elif plan.start == history.PLAN_START_MISSING_CHECKPOINT:
+        print(
+            f"{utils.COLOR_RED}Start: checkpoint {plan.missing_checkpoint} is missing; the re-apply will fail.{utils.COLOR_RESET}"
+        )
+    elif plan.start == history.PLAN_START_UNKNOWN:
+        print(
+            f"{utils.COLOR_YELLOW}Start: empty file, as no checkpoint or create was found; the result may be wrong.{utils.COLOR_RESET}"
+        )
+    else:
+        print("No history: the file is left as it is.")
+        return
+
+    action_colors = {
+        "apply": utils.COLOR_GREEN,
+        "skip": utils.COLOR_RED,
+        "clear": utils.COLOR_YELLOW,
+        "split": utils.COLOR_CYAN,
+    }
+    print(
+        f"{'#':>3}  {'Action':<6}  {'Edit':<8}  {'Conv':<8}  {'Operation':<9}  {'Status':<8}  {'Hash after':<10}  Diff"
+    )
+    print("-" * 100)
+    for number, step in enumerate(plan.steps, 1):
+        data = step.to_dict(history_root)
+        if data["diff_missing"]:
+            diff = f"{utils.COLOR_RED}missing{utils.COLOR_RESET}"
+        else:
+            diff = data["diff_file"] or "-"
+            if data["file_encoding"] != "text":
+                diff += f" ({data['file_encoding']})"
+        color = action_colors.get(step.action, "")
+        print(
+            f"{number:>3}  {color}{step.action:<6}{utils.COLOR_RESET}  {step.edit_id[:8]:<8}  {(data['conversation_id'] or 'N/A')[:8]:<8}  {step.operation:<9}  {step.status:<8}  {(data['hash_after'] or '-')[:8]:<10}  {diff}"
+        )
+        if step.rejected_parent:
+            outcome = (
+                "applied anyway, as it was accepted with --force"
+                if step.status == "accepted"
+                else "the re-apply will fail"
+            )
+            print(
+                f"{utils.COLOR_YELLOW}     depends on rejected edit {step.rejected_parent[:8]}: {outcome}{utils.COLOR_RESET}"
+            )
+
+    verified = plan.verified_step()
+    if verified:
+        expected = (verified.entry.get("hash_after") or "-")[:8]
+        print(f"The result must hash to {expected}, as recorded by edit {verified.edit_id[:8]}.")
+    elif any(step.action == "apply" for step in plan.steps):
+        print(
+            "The result is not verified: an edit before the last one applied is skipped."
+        )
+
+
+# Directory under .mcp that preview stages re-applied files in
+PREVIEW_DIR_NAME = "preview"
+
+
+def handle_preview(
+    args: argparse.Namespace,
+    workspace_root: Path,
+    history_root: Path,
+    all_entries: List[Dict[str, Any]],
+) -> None:
+    """
+    Handle the preview command: what rejecting a conversation would make of
+    its files. They are re-applied as reject would, but in a staging
+    directory under .mcp/preview, and compared with the working tree.
+    """
+    conv_ref = history.resolve_conversation_tag(
+        args.conv, history.load_tags(history_root)
+    )
+    conv_entries = history.find_entries_by_conversation(all_entries, conv_ref)
+    if not conv_entries:
+        raise HistoryError(f"No entries found for conversation matching ID: {args.conv}")
+    conv_id = conv_entries[0].get("conversation_id", "unknown_conv")
+    ignored = None if args.no_ignore else ignore.load_ignore_matcher(workspace_root)
+
+    # The edits reject would reject, with the edits that depend on them
+    edits = conv_entries + [
+        e for e, _ in _unrejected_dependents(conv_entries, all_entries)
+    ]
+    rejected = [
+        e
+        for e in edits
+        if e.get("tool_name") != "mcpdiff"
+        and not e.get("archived")
+        and e.get("status") in ("pending", "accepted")
+        and not (ignored and ignored.matches_entry(e))
+        and e.get("edit_id")
+    ]
+    rejected_ids = {e["edit_id"] for e in rejected}
+    preview_entries = [
+        dict(e, status="rejected") if e.get("edit_id") in rejected_ids else e
+        for e in all_entries
+    ]
+    # Rejected moves also re-apply the path they moved the content from
+    files = sorted(
+        {e["file_path"] for e in rejected if e.get("file_path")}
+        | {
+            e["source_path"]
+            for e in rejected
+            if e.get("operation") in utils.MOVE_OPERATIONS and e.get("source_path")
+        }
+    )
+    if args.file:
+        file_rel = _resolve_tracked_file(args.file, workspace_root, all_entries)
+        files = [f for f in files if f == file_rel]
+    if not files:
+        print(
+            f"{utils.COLOR_YELLOW}No pending/accepted edits found to reject for conversation {conv_id}.{utils.COLOR_RESET}"
+        )
+        return
+
+    preview_root = workspace_root / ".mcp" / PREVIEW_DIR_NAME
+    preview_root.mkdir(parents=True, exist_ok=True)
+    staging_root = Path(tempfile.mkdtemp(prefix=f"{conv_id[:8]}_", dir=preview_root))
+    try:
+        previews = [
+            _stage_rejected_file(
+                file_rel, preview_entries, workspace_root, history_root, staging_root
+            )
+            for file_rel in files
+        ]
+    finally:
+        if not args.keep_staged:
+            shutil.rmtree(staging_root, ignore_errors=True)
+            try:
+                preview_root.rmdir()  # Only if no other preview is kept
+            except OSError:
+                pass
+
+    if args.json:
+        print(
+            json.dumps(
+                [
+                    {
+                        "file_path": p["file_path"],
+                        "state": p["state"],
+                        "diff": p["diff"],
+                        "error": p["error"],
+                        "staged_path": str(staging_root / p["file_path"])
+                        if args.keep_staged and p["state"] != "error"
+                        else None,
+                    }
+                    for p in previews
+                ],
+                indent=2,
+            )
+        )
+    else:
+        _print_previews(conv_id, previews)
+        if args.keep_staged:
+            print(f"Staged files kept in {staging_root}")
+
+    errors = [p["exception"] for p in previews if p["state"] == "error"]
+    if errors:
+        error = errors[-1]
+        raise error if isinstance(error, HistoryError) else HistoryError(str(error))
+
+
+def _stage_rejected_file(
+    file_rel: str,
+    preview_entries: List[Dict[str, Any]],
+    workspace_root: Path,
+    history_root: Path,
+    staging_root: Path,
+) -> Dict[str, Any]:
+    """
+    Re-applies a file into `staging_root` from entries with the edits to reject
+    marked rejected, and compares the result with the working-tree file. The
+    state is "modified", "deleted", "created", "unchanged" or "error"; the diff
+    runs from the working tree to the staged file.
+    """
+    preview: Dict[str, Any] = {
+        "file_path": file_rel,
+        "state": "error",
+        "diff": None,
+        "error": None,
+        "exception": None,
+        "warnings": [],
+    }
+    plan = history.plan_file_replay(file_rel, preview_entries, history_root)
+    result = history.execute_replay_plan(plan, staging_root)
+    if result.get("error"):
+        preview["error"] = result["error"]
+        preview["exception"] = result.get("exception") or HistoryError(result["error"])
+        return preview
+    preview["warnings"] = result.get("warnings", [])
+
+    staged_path = staging_root / file_rel
+    current_path = workspace_root / file_rel
+    staged = staged_path.read_bytes() if staged_path.is_file() else None
+    current = current_path.read_bytes() if current_path.is_file() else None
+    if staged == current:
+        preview["state"] = "unchanged"
+        return preview
+    if staged is None:
+        preview["state"] = "deleted"
+    elif current is None:
+        preview["state"] = "created"
+    else:
+        preview["state"] = "modified"
+    preview["diff"] = patch.make_unified_diff(
+        (current or b"").decode("utf-8", errors="replace"),
+        (staged or b"").decode("utf-8", errors="replace"),
+        file_rel,
+    )
+    return preview
+
+
+def _print_previews(conv_id: str, previews: List[Dict[str, Any]]) -> None:
+    """Prints what rejecting a conversation would change, file by file."""
+    outcomes = {
+        "modified": "would change",
+        "deleted": "would be deleted",
+        "created": "would be recreated",
+        "unchanged": "would not change",
+    }
+    for preview in previews:
+        file_path = utils.display_path(preview["file_path"])
+        if preview["state"] == "error":
+            print(
+                f"{utils.COLOR_RED}--- {file_path}: cannot be re-applied: {preview['error']}{utils.COLOR_RESET}"
+            )
+            continue
+        print(
+            f"{utils.COLOR_CYAN}--- {file_path} {outcomes[preview['state']]} ---{utils.COLOR_RESET}"
+        )
+        for warning in preview["warnings"]:
+            print(f"{utils.COLOR_YELLOW}Warning: {warning}{utils.COLOR_RESET}")
+        if preview["diff"]:
+            utils.print_diff_with_color(preview["diff"], preview["file_path"])
+    changed = sum(p["state"] not in ("unchanged", "error") for p in previews)
+    print(
+        f"\nRejecting conversation {conv_id} would change {changed} of {len(previews)} file(s)."
+    )
+
+
+def handle_diff_since(
+    args: argparse.Namespace,
+    workspace_root: Path,
+    history_root: Path,
+    all_entries: List[Dict[str, Any]],
+) -> None:
+    """
+    Handle the diff-since command: one diff per file edited after a time or a
+    snapshot, from the file as it was then to the file with the edits
+    accepted since applied in sequence. Both are re-applied from the history
+    in a temporary directory; a snapshot's checkpoints are the first instead.
+    """
+    snapshot = None
+    if args.since_snapshot:
+        snapshot = snapshots.find_snapshot(history_root, args.since_snapshot)
+        since = utils.parse_timestamp(snapshot["timestamp"])
+    else:
+        since = _parse_time_option(args.since, "--since")
+    conv_id = None
+    if args.conv:
+        conv_ref = history.resolve_conversation_tag(
+            args.conv, history.load_tags(history_root)
+        )
+        conv_entries = history.find_entries_by_conversation(all_entries, conv_ref)
+        if not conv_entries:
+            raise HistoryError(
+                f"No entries found for conversation matching ID: {args.conv}"
+            )
+        conv_id = conv_entries[0].get("conversation_id")
+
+    later = [
+        e
+        for e in all_entries
+        if e.get("tool_name") != "mcpdiff"
+        and e.get("edit_id")
+        and utils.parse_timestamp(e.get("timestamp", 0)) > since
+    ]
+    selected = [e for e in later if conv_id is None or e["conversation_id"] == conv_id]
+    # Moves also change the path they moved the content from
+    files = sorted(
+        {e["file_path"] for e in selected if e.get("file_path")}
+        | {
+            e["source_path"]
+            for e in selected
+            if e.get("operation") in utils.MOVE_OPERATIONS and e.get("source_path")
+        }
+    )
+    if args.file:
+        file_rel = _resolve_tracked_file(args.file, workspace_root, all_entries)
+        files = [f for f in files if f == file_rel]
+    if not files:
+        print(f"{utils.COLOR_YELLOW}No edits found since then.{utils.COLOR_RESET}")
+        return
+
+    # The files as they were then, pending edits included, and with only the
+    # accepted edits since added (of the conversation, with --conv)
+    later_ids = {e["edit_id"] for e in later}
+    accepted_ids = {e["edit_id"] for e in selected if e.get("status") == "accepted"}
+    before_entries = [
+        dict(e, status="rejected") if e.get("edit_id") in later_ids else e
+        for e in all_entries
+    ]
+    after_entries = [
+        dict(e, status="rejected")
+        if e.get("edit_id") in later_ids - accepted_ids
+        else e
+        for e in all_entries
+    ]
+    snapshotted = {f["file_path"]: f for f in snapshot["files"]} if snapshot else {}
+    with tempfile.TemporaryDirectory(prefix="mcpdiff_since_") as staging:
+        diffs = []
+        for file_rel in files:
+            diff = _diff_since_file(
+                file_rel,
+                before_entries,
+                after_entries,
+                history_root,
+                Path(staging),
+                snapshotted.get(file_rel),
+            )
+            diff["edits"] = sum(
+                file_rel in (e.get("file_path"), e.get("source_path"))
+                for e in selected
+            )
+            diffs.append(diff)
+
+    if args.json:
+        print(
+            json.dumps(
+                [{k: v for k, v in d.items() if k != "exception"} for d in diffs],
+                indent=2,
+            )
+        )
+    else:
+        for diff in diffs:
+            file_path = utils.display_path(diff["file_path"])
+            if diff["error"]:
+                print(
+                    f"{utils.COLOR_RED}--- {file_path}: cannot be re-applied: {diff['error']}{utils.COLOR_RESET}"
+                )
+            elif diff["diff"]:
+                print(
+                    f"{utils.COLOR_CYAN}--- {file_path} ({diff['edits']} edit(s)) ---{utils.COLOR_RESET}"
+                )
+                utils.print_diff_with_color(diff["diff"], diff["file_path"])
+        changed = sum(bool(d["diff"]) for d in diffs)
+        print(f"\n{changed} of {len(diffs)} file(s) edited since then changed.")
+
+    errors = [d["exception"] for d in diffs if d["error"]]
+    if errors:
+        error = errors[-1]
+        raise error if isinstance(error, HistoryError) else HistoryError(str(error))
+
+
+def _diff_since_file(
+    file_rel: str,
+    before_entries: List[Dict[str, Any]],
+    after_entries: List[Dict[str, Any]],
+    history_root: Path,
+    staging_root: Path,
+    snapshotted: Optional[Dict[str, Any]] = None,
+) -> Dict[str, Any]:
+    """
+    Re-applies a file from both lists of entries into subdirectories of
+    `staging_root` and diffs the results. A snapshot's entry for the file, if
+    given, is the earlier state instead.
+    """
+    result: Dict[str, Any] = {
+        "file_path": file_rel,
+        "diff": None,
+        "error": None,
+        "exception": None,
+    }
+    contents = []
+    for name, entries in (("before", before_entries), ("after", after_entries)):
+        if name == "before" and snapshotted is not None:
+            checkpoint = snapshotted["checkpoint_file"]
+            contents.append(
+                (history_root / checkpoint).read_bytes() if checkpoint else None
+            )
+            continue
+        root = staging_root / name
+        plan = history.plan_file_replay(file_rel, entries, history_root)
+        replayed = history.execute_replay_plan(plan, root)
+        if replayed.get("error"):
+            result["error"] = replayed["error"]
+            result["exception"] = replayed.get("exception") or HistoryError(
+                replayed["error"]
+            )
+            return result
+        path = root / file_rel
+        contents.append(path.read_bytes() if path.is_file() else None)
+    before, after = contents
+    if before != after:
+        result["diff"] = patch.make_unified_diff(
+            (before or b"").decode("utf-8", errors="replace"),
+            (after or b"").decode("utf-8", errors="replace"),
+            file_rel,
+        )
+    return result
+
+
+def handle_checkpoint_info(
+    args: argparse.Namespace,
+    workspace_root: Path,
+    history_root: Path,
+    all_entries: List[Dict[str, Any]],
+) -> None:
+    """Handle the checkpoint-info command: size and age of checkpoint files."""
+    checkpoints = history.collect_checkpoint_info(
+        history_root, all_entries, conv_id=args.conv, file_path=args.file
+    )
+    if not checkpoints:
+        print(f"{utils.COLOR_YELLOW}No checkpoint files found.{utils.COLOR_RESET}")
+        return
+
+    if args.sort_by == "size":
+        checkpoints.sort(key=lambda c: c["bytes"], reverse=True)
+    elif args.sort_by == "age":
+        # Oldest first
+        checkpoints.sort(key=lambda c: utils.parse_timestamp(c["written"] or 0))
+    else:
+        checkpoints.sort(key=lambda c: c["edit_id"] or "")
+
+    fmt = utils.format_size
+    print(
+        f"{utils.COLOR_CYAN}{'Size':>10}  {'Written':<19}  {'Edit ID':<8}  {'Refs':>4}  {'Checkpoint'}{utils.COLOR_RESET}"
+    )
+    print("-" * 100)
+    for chkpt in checkpoints:
+        written = utils.format_timestamp_absolute(
+            chkpt["written"] or 0, display_friendly=True
+        )
+        edit_id = (chkpt["edit_id"] or "-")[:8]
+        refs = chkpt["references"]
+        color = utils.COLOR_YELLOW if refs == 0 else ""
+        reset = utils.COLOR_RESET if color else ""
+        note = ""
+        if not chkpt["exists"]:
+            note = f" {utils.COLOR_RED}(missing){utils.COLOR_RESET}"
+        elif chkpt["file_path"]:
+            note = f" ({chkpt['file_path']})"
+        print(
+            f"{color}{fmt(chkpt['bytes']):>10}  {written:<19}  {edit_id:<8}  {refs:>4}  {chkpt['path']}{note}{reset}"
+        )
+    print("-" * 100)
+
+    total_bytes = sum(c["bytes"] for c in checkpoints)
+    orphaned = [c for c in checkpoints if c["references"] == 0 and c["exists"]]
+    print(f"Total: {len(checkpoints)} checkpoints, {fmt(total_bytes)}")
+    if orphaned:
+        orphan_bytes = sum(c["bytes"] for c in orphaned)
+        print(
+            f"{utils.COLOR_YELLOW}Orphaned (no non-rejected references): {len(orphaned)} checkpoints, {fmt(orphan_bytes)}{utils.COLOR_RESET}"
+        )
+    else:
+        print("Orphaned (no non-rejected references): 0")
+
+
+def handle_prune_checkpoints(
+    args: argparse.Namespace,
+    workspace_root: Path,
+    history_root: Path,
+    all_entries: List[Dict[str, Any]],
+) -> None:
+    """Handle the prune-checkpoints command: keep the N newest checkpoints per file."""
+    plan = history.plan_checkpoint_pruning(
+        history_root,
+        all_entries,
+        args.keep_n,
+        conv_id=args.conv,
+        file_path=args.file,
+    )
+    if not plan:
+        print(
+            f"{utils.COLOR_GREEN}No file has more than {args.keep_n} checkpoints.{utils.COLOR_RESET}"
+        )
+        return
+
+    fmt = utils.format_size
+    print(
+        f"{utils.COLOR_CYAN}{'Size':>10}  {'Refs':>4}  {'Checkpoint'}{utils.COLOR_RESET}"
+    )
+    print("-" * 100)
+    for item in plan:
+        print(
+            f"{fmt(item['bytes']):>10}  {len(item['edit_ids']):>4}  {item['path']} ({utils.display_path(item['file_path'])})"
+        )
+    print("-" * 100)
+    total = sum(item["bytes"] for item in plan)
+    if args.dry_run:
+        print(f"Would prune {len(plan)} checkpoints, {fmt(total)}.")
+        return
+
+    conv_ids = [
+        e["conversation_id"]
+        for item in plan
+        for e in all_entries
+        if e.get("checkpoint_file") == item["path"] and e.get("conversation_id")
+    ]
+    locks = _acquire_conversation_locks(conv_ids, history_root, args.timeout)
+    try:
+        freed = history.prune_checkpoints(
+            history_root, all_entries, plan, lock_timeout=args.timeout
+        )
+    finally:
+        for lock in reversed(locks):
+            lock.release()
+    print(
+        f"{utils.COLOR_GREEN}Pruned {len(plan)} checkpoints, freed {fmt(freed)}.{utils.COLOR_RESET}"
+    )
+
+
+def handle_prune(
+    args: argparse.Namespace,
+    workspace_root: Path,
+    history_root: Path,
+    all_entries: List[Dict[str, Any]],
+) -> None:
+    """Handle the prune command: drop older conversations' diffs and checkpoints."""
+    plan = history.plan_conversation_pruning(
+        history_root,
+        all_entries,
+        args.keep_last,
+        keep_artifacts_for_pending=args.keep_artifacts_for_pending,
+    )
+    if not plan:
+        print(
+            f"{utils.COLOR_GREEN}No older conversation has diffs or checkpoints to prune.{utils.COLOR_RESET}"
+        )
+        return
+
+    fmt = utils.format_size
+    print(
+        f"{utils.COLOR_CYAN}{'Last Activity':<19}  {'Conversation':<36}  {'Edits':>5}  {'Files':>5}  {'Size':>10}{utils.COLOR_RESET}"
+    )
+    print("-" * 100)
+    for item in plan:
+        last = utils.format_timestamp_absolute(item["last_activity"] or 0, True)
+        print(
+            f"{last:<19}  {item['conversation_id'][:36]:<36}  {item['edits']:>5}  {len(item['files']):>5}  {fmt(item['bytes']):>10}"
+        )
+    print("-" * 100)
+    total = sum(item["bytes"] for item in plan)
+    count = f"{len(plan)} conversation{'' if len(plan) == 1 else 's'}"
+    if args.dry_run:
+        print(f"Would prune the diffs and checkpoints of {count}, {fmt(total)}.")
+        return
+
+    conv_ids = [item["conversation_id"] for item in plan]
+    locks = _acquire_conversation_locks(conv_ids, history_root, args.timeout)
+    try:
+        freed = history.prune_conversations(
+            history_root, all_entries, plan, lock_timeout=args.timeout
+        )
+    finally:
+        for lock in reversed(locks):
+            lock.release()
+    print(
+        f"{utils.COLOR_GREEN}Pruned the diffs and checkpoints of {count}, freed {fmt(freed)}. Their logs are kept.{utils.COLOR_RESET}"
+    )
+
+
+def handle_squash_rejected(
+    args: argparse.Namespace,
+    workspace_root: Path,
+    history_root: Path,
+    all_entries: List[Dict[str, Any]],
+) -> None:
+    """Handle the squash-rejected command: drop a conversation's rejected edits."""
+    [conv_id] = _logged_conversation_ids(history_root, args.conv)
+    plan = history.plan_rejected_squash(history_root, all_entries, conv_id)
+    for entry, reason in plan["skipped"]:
+        print(
+            f"{utils.COLOR_YELLOW}Warning: keeping edit {entry['edit_id'][:8]} ({utils.display_path(entry['file_path'])}): {reason}.{utils.COLOR_RESET}"
+        )
+    if not plan["remove"]:
+        print(
+            f"{utils.COLOR_GREEN}No rejected edits to remove in conversation {conv_id}.{utils.COLOR_RESET}"
+        )
+        return
+
+    fmt = utils.format_size
+    print(
+        f"{utils.COLOR_CYAN}{'Edit ID':<8}  {'Files':>5}  {'Size':>10}  {'File'}{utils.COLOR_RESET}"
+    )
+    print("-" * 100)
+    for item in plan["remove"]:
+        entry = item["entry"]
+        print(
+            f"{entry['edit_id'][:8]:<8}  {len(item['files']):>5}  {fmt(item['bytes']):>10}  {utils.display_path(entry['file_path'])}"
+        )
+    print("-" * 100)
+    total = sum(item["bytes"] for item in plan["remove"])
+    count = len(plan["remove"])
+    if not args.confirm:
+        print(
+            f"Would remove {count} rejected edit(s) and free {fmt(total)}. Run again with --confirm to remove them."
+        )
+        return
+
+    locks = _acquire_conversation_locks([conv_id], history_root, args.timeout)
+    try:
+        freed = history.squash_rejected(
+            history_root,
+            all_entries,
+            plan,
+            lock_timeout=args.timeout,
+            invocation=args.invocation,
+        )
+    finally:
+        for lock in reversed(locks):
+            lock.release()
+    print(
+        f"{utils.COLOR_GREEN}Removed {count} rejected edit(s) from conversation {conv_id}, freed {fmt(freed)}.{utils.COLOR_RESET}"
+    )
+
+
+def handle_snapshot(
+    args: argparse.Namespace,
+    workspace_root: Path,
+    history_root: Path,
+    all_entries: List[Dict[str, Any]],
+) -> None:
+    """Handle the snapshot command: checkpoint all tracked files, or list snapshots."""
+    if args.list:
+        listed = snapshots.read_snapshots(history_root)
+        if args.json:
+            print(json.dumps(listed, indent=2))
+            return
+        if not listed:
+            print(f"{utils.COLOR_YELLOW}No snapshots.{utils.COLOR_RESET}")
+            return
+        print(
+            f"{utils.COLOR_CYAN}{'Time':<19}  {'Snapshot ID':<36}  {'Files':>5}  Message{utils.COLOR_RESET}"
+        )
+        print("-" * 100)
+        for entry in reversed(listed):
+            time_str = utils.format_timestamp_absolute(
+                utils.parse_timestamp(entry["timestamp"]), True
+            )
+            print(
+                f"{time_str:<19}  {entry['edit_id']:<36}  {len(entry['files']):>5}  {entry.get('message') or ''}"
+            )
+        return
+
+    entry = snapshots.create_snapshot(
+        workspace_root,
+        history_root,
+        all_entries,
+        message=args.message,
+        lock_timeout=args.timeout,
+        invocation=args.invocation,
+    )
+    if args.json:
+        print(json.dumps(entry, indent=2))
+        return
+    missing = sum(1 for f in entry["files"] if f["checkpoint_file"] is None)
+    print(
+        f"{utils.COLOR_GREEN}Snapshot {entry['edit_id']} of {len(entry['files'])} files ({missing} not present).{utils.COLOR_RESET}"
+    )
+
+
+def handle_restore_snapshot(
+    args: argparse.Namespace,
+    workspace_root: Path,
+    history_root: Path,
+    all_entries: List[Dict[str, Any]],
+) -> None:
+    """Handle the restore-snapshot command: put all files back as snapshotted."""
+    snapshot = snapshots.find_snapshot(history_root, args.snapshot_id)
+    changes = snapshots.plan_restore(
+        workspace_root, history_root, snapshot, all_entries
+    )
+    if not changes:
+        print(
+            f"{utils.COLOR_GREEN}All files already match snapshot {snapshot['edit_id']}.{utils.COLOR_RESET}"
+        )
+        return
+    for change in changes:
+        print(f"  {change['action']:<6}  {utils.display_path(change['file_path'])}")
+    if not args.yes:
+        try:
+            choice = input(
+                f"Restore {len(changes)} file(s) to snapshot {snapshot['edit_id'][:8]}? (y/n): "
+            )
+        except (EOFError, KeyboardInterrupt):
+            choice = "n"
+        if choice.lower().strip() not in ["y", "yes"]:
+            print("Aborted.")
+            return
+    result = snapshots.restore_snapshot(
+        workspace_root,
+        history_root,
+        snapshot["edit_id"],
+        all_entries,
+        lock_timeout=args.timeout,
+        invocation=args.invocation,
+    )
+    print(
+        f"{utils.COLOR_GREEN}Restored snapshot {result['snapshot_id']}: {len(result['written'])} written, {len(result['deleted'])} deleted.{utils.COLOR_RESET}"
+    )
+
+
+def handle_expire_old(
+    args: argparse.Namespace,
+    workspace_root: Path,
+    history_root: Path,
+    all_entries: List[Dict[str, Any]],
+) -> None:
+    """Handle the expire-old command: reject pending edits older than a duration."""
+    older_than = utils.parse_time_filter(args.older_than)
+    if not older_than:
+        raise HistoryError(
+            f"Invalid --older-than duration: {args.older_than} (e.g. 12h, 7d)"
+        )
+    expired = history.find_expired_entries(all_entries, older_than)
+    if not expired:
+        print(
+            f"{utils.COLOR_GREEN}No pending edits older than {args.older_than}.{utils.COLOR_RESET}"
+        )
+        return
+
+    if args.dry_run:
+        outcomes = [(e, "would expire") for e in expired]
+    else:
+        report = history.expire_entries(
+            expired,
+            all_entries,
+            workspace_root,
+            history_root,
+            args.timeout,
+            invocation=args.invocation,
+        )
+        outcomes = [(e, "expired") for e in report["expired"]]
+        outcomes += [(e, f"skipped: {reason}") for e, reason in report["skipped"]]
+        outcomes.sort(key=lambda o: utils.parse_timestamp(o[0].get("timestamp", 0)))
+
+    print(
+        f"{utils.COLOR_CYAN}{'Time':<19}  {'Edit ID':<8}  {'Conv ID':<8}  {'File Path':<40}  Result{utils.COLOR_RESET}"
+    )
+    print("-" * 100)
+    for entry, outcome in outcomes:
+        timestamp = utils.format_timestamp_absolute(entry.get("timestamp", 0), True)
+        path = utils.display_path(entry.get("file_path")) or "N/A"
+        print(
+            f"{timestamp:<19}  {entry.get('edit_id', 'N/A')[:8]:<8}  {entry.get('conversation_id', 'N/A')[:8]:<8}  {path:<40}  {outcome}"
+        )
+    print("-" * 100)
+    if args.dry_run:
+        print(f"Would expire {len(expired)} pending edits older than {args.older_than}.")
+        return
+    skipped = len(report["skipped"])
+    print(
+        f"{utils.COLOR_GREEN}Expired {len(report['expired'])} pending edits older than {args.older_than}.{utils.COLOR_RESET}"
+    )
+    if skipped:
+        raise HistoryError(f"{skipped} expired edit(s) were left pending.")
+
+
+def handle_reattach(
+    args: argparse.Namespace,
+    workspace_root: Path,
+    history_root: Path,
+    all_entries: List[Dict[str, Any]],
+) -> None:
+    """Handle the reattach command: move edits to another conversation."""
+    tags = history.load_tags(history_root)
+    namespace = config.get_config_value(workspace_root, "namespace")
+    [from_conv] = _logged_conversation_ids(
+        history_root, history.resolve_conversation_tag(args.from_conv, tags, namespace)
+    )
+    [to_conv] = _logged_conversation_ids(
+        history_root, history.resolve_conversation_tag(args.to_conv, tags, namespace)
+    )
+    edit_ids = None
+    if args.edit_id:
+        source_entries = [
+            e for e in all_entries if e.get("conversation_id") == from_conv
+        ]
+        entry = history.find_entry_by_id(source_entries, args.edit_id)
+        if not entry:
+            raise HistoryError(
+                f"No edit matching '{args.edit_id}' in conversation {from_conv}"
+            )
+        edit_ids = [entry["edit_id"]]
+
+    locks = _acquire_conversation_locks(
+        [from_conv, to_conv], history_root, args.timeout
+    )
+    try:
+        moved = history.reattach_entries(
+            history_root,
+            from_conv,
+            to_conv,
+            edit_ids,
+            lock_timeout=args.timeout,
+            invocation=args.invocation,
+        )
+    finally:
+        for lock in reversed(locks):
+            lock.release()
+    first, last = moved[0]["tool_call_index"], moved[-1]["tool_call_index"]
+    print(
+        f"{utils.COLOR_GREEN}Reattached {len(moved)} entries from {from_conv} to {to_conv} (tool_call_index {first}-{last}).{utils.COLOR_RESET}"
+    )
+
+
+def _logged_conversation_ids(history_root: Path, conv_ref: Optional[str]) -> List[str]:
+    """Conversations with log files, optionally narrowed to one by ID prefix/suffix."""
+    logs_dir = history_root / utils.LOGS_DIR
+    conv_ids = sorted(
+        {utils.get_log_conversation_id(p) for p in utils.find_log_files(logs_dir)}
+    )
+    if not conv_ref:
+        return conv_ids
+    ref = conv_ref.lower()
+    matches = [c for c in conv_ids if c.lower().startswith(ref)] or [
+        c for c in conv_ids if c.lower().endswith(ref)
+    ]
+    if not matches:
+        raise HistoryError(f"No conversation found matching '{conv_ref}'")
+    if len(matches) > 1:
+        raise AmbiguousIDError(
+            f"Conversation '{conv_ref}' is ambiguous: {', '.join(matches)}"
+        )
+    return matches
+
+
+def handle_format_log(
+    args: argparse.Namespace,
+    workspace_root: Path,
+    history_root: Path,
+    all_entries: List[Dict[str, Any]],
+) -> None:
+    """Handle the format-log command: pretty-print a conversation's log entries."""
+    conv_ref = history.resolve_conversation_tag(
+        args.conv, history.load_tags(history_root)
+    )
+    conv_id = _logged_conversation_ids(history_root, conv_ref)[0]
+    log_files = utils.get_conversation_log_files(history_root, conv_id)
+    if args.output != "-" and Path(args.output).resolve() in {
+        p.resolve() for p in log_files
+    }:
+        raise HistoryError(f"Refusing to overwrite the log {args.output}.")
+
+    text, problems = append.format_log_files(history_root, conv_id)
+    if args.output == "-":
+        sys.stdout.write(text)
+    else:
+        Path(args.output).write_text(text, encoding="utf-8")
+        print(f"Wrote {len(log_files)} log file(s) of {conv_id} to {args.output}")
+
+    for log_name, line_number, message in problems:
+        print(
+            f"{utils.COLOR_RED}{log_name}:{line_number}: {message}{utils.COLOR_RESET}",
+            file=sys.stderr,
+        )
+    if problems:
+        raise CorruptLogError(
+            f"{len(problems)} invalid entr{'y' if len(problems) == 1 else 'ies'} in the logs of {conv_id}",
+            line=problems[0][1],
+        )
+
+
+def handle_schema(
+    args: argparse.Namespace,
+    workspace_root: Optional[Path],
+    history_root: Optional[Path],
+    all_entries: List[Dict[str, Any]],
+) -> None:
+    """Handle the schema command: print the JSON Schema of a log format type."""
+    print(schema.dump_schema(args.type))
+
+
+def handle_workspace(
+    args: argparse.Namespace,
+    workspace_root: Optional[Path],
+    history_root: Optional[Path],
+    all_entries: List[Dict[str, Any]],
+) -> None:
+    """
+    Handle the workspace command: list the workspaces under a directory, or
+    set up a directory as one.
+    """
+    root = Path(args.root or os.getcwd()).resolve()
+    if not root.is_dir():
+        raise HistoryError(f"Not a directory: {root}")
+    if args.workspace_action == "init":
+        _init_workspace(root)
+        return
+    roots = history.find_workspace_roots(root)
+
+    if not args.json:
+        for workspace in roots:
+            print(workspace)
+        return
+    summaries = []
+    for workspace in roots:
+        try:
+            summaries.append(history.summarize_workspace(workspace, args.timeout))
+        except (HistoryError, TimeoutError) as e:
+            # One unreadable workspace should not hide the others
+            log.warning(f"Could not read the history of {workspace}: {e}")
+            summaries.append(
+                {
+                    "path": str(workspace),
+                    "conversation_count": None,
+                    "pending_count": None,
+                }
+            )
+    print(json.dumps(summaries, indent=2))
+
+
+def _init_workspace(root: Path) -> None:
+    """Creates the edit history of a workspace and a permissive ACL file."""
+    history_root = root / ".mcp" / utils.HISTORY_DIR_NAME
+    existed = history_root.is_dir()
+    for directory in (utils.LOGS_DIR, utils.DIFFS_DIR, utils.CHECKPOINTS_DIR):
+        (history_root / directory).mkdir(parents=True, exist_ok=True)
+    if existed:
+        print(f"Edit history already exists: {history_root}")
+    else:
+        print(
+            f"{utils.COLOR_GREEN}Created edit history: {history_root}{utils.COLOR_RESET}"
+        )
+    acl_path = acl.get_acl_path(root)
+    if acl.write_default_acl(root):
+        print(
+            f"Created {acl_path}, which lets every user accept and reject; edit it to restrict conversations to their users."
+        )
+    else:
+        print(f"Kept existing {acl_path}")
+
+
+def handle_verify(
+    args: argparse.Namespace,
+    workspace_root: Path,
+    history_root: Path,
+    all_entries: List[Dict[str, Any]],
+) -> None:
+    """Handle the verify command: check Merkle roots and checkpoint hashes."""
+    if args.merkle_root and not args.conv:
+        raise HistoryError("--merkle-root needs a single conversation (--conv).")
+    conv_ids = _logged_conversation_ids(history_root, args.conv)
+    if not conv_ids:
+        print(f"{utils.COLOR_YELLOW}No conversation logs found.{utils.COLOR_RESET}")
+        return
+
+    failures = []
+    for conv_id in conv_ids:
+        sidecar = utils.read_merkle_sidecar(history_root, conv_id)
+        algorithm = sidecar["algorithm"] if sidecar else None
+        try:
+            actual = utils.compute_merkle_root(history_root, conv_id, algorithm)
+        except ValueError as e:
+            raise HistoryError(f"Cannot verify {conv_id}: {e}")
+        label = f"{conv_id}  {actual['leaves']} entries  {actual['root'][:16]}"
+
+        if args.merkle_root:
+            if actual["root"] != args.merkle_root.lower():
+                failures.append(conv_id)
+                print(
+                    f"{utils.COLOR_RED}MISMATCH   {label} (trusted root {args.merkle_root[:16]}){utils.COLOR_RESET}"
+                )
+                continue
+        if sidecar is None:
+            if not args.merkle_root:
+                print(
+                    f"{utils.COLOR_YELLOW}NO ROOT    {label} (no {utils.merkle_sidecar_path(history_root, conv_id).name}){utils.COLOR_RESET}"
+                )
+                continue
+        elif sidecar["root"] != actual["root"]:
+            failures.append(conv_id)
+            print(
+                f"{utils.COLOR_RED}MISMATCH   {label} (recorded {sidecar['root'][:16]}, {sidecar.get('leaves', '?')} entries){utils.COLOR_RESET}"
+            )
+            continue
+        print(f"{utils.COLOR_GREEN}OK         {label}{utils.COLOR_RESET}")
+
+    # Checkpoints must hold the content their entry's hash_before describes
+    bad_checkpoints = []
+    for entry in all_entries:
+        if entry.get("conversation_id") not in conv_ids:
+            continue
+        actual_hash = history.checkpoint_mismatch(entry, history_root)
+        if actual_hash:
+            bad_checkpoints.append(entry["edit_id"])
+            print(
+                f"{utils.COLOR_RED}CHECKPOINT {history.checkpoint_mismatch_message(entry, actual_hash)}{utils.COLOR_RESET}"
+            )
+
+    if failures:
+        raise HistoryError(
+            f"Merkle root mismatch in {len(failures)} conversation{'' if len(failures) == 1 else 's'}: {', '.join(failures)}"
+        )
+    if bad_checkpoints:
+        raise HistoryError(
+            f"{len(bad_checkpoints)} checkpoint(s) do not match the hash_before of their edit: {', '.join(bad_checkpoints)}"
+        )
+
+
+def handle_audit(
+    args: argparse.Namespace,
+    workspace_root: Path,
+    history_root: Path,
+    all_entries: List[Dict[str, Any]],
+) -> None:
+    """Handle the audit command: who changed which statuses, and when."""
+    since = _parse_time_option(args.since, "--since")
+    records = audit.read_audit_log(history_root, since)
+
+    if args.json:
+        print(json.dumps(records, indent=2))
+        return
+    if not records:
+        print(f"{utils.COLOR_YELLOW}No audit records found.{utils.COLOR_RESET}")
+        return
+
+    print(
+        f"{utils.COLOR_CYAN}{'Time':<19}  {'User':<12}  {'Action':<9}  {'Outcome':<8}  {'Edits':>5}  {'Command / File'}{utils.COLOR_RESET}"
+    )
+    print("-" * 100)
+    for record in records:
+        when = utils.format_timestamp_absolute(record.get("timestamp", 0), True)
+        outcome = record.get("outcome", "?")
+        color = {"success": "", "partial": utils.COLOR_YELLOW}.get(
+            outcome, utils.COLOR_RED
+        )
+        edit_ids = record.get("edit_ids") or []
+        target = record.get("file_path") or " ".join(
+            ["mcpdiff"] + record.get("argv", [])
+        )
+        print(
+            f"{color}{when:<19}  {str(record.get('user'))[:12]:<12}  {record.get('action', '?'):<9}  {outcome:<8}  {len(edit_ids):>5}  {target}{utils.COLOR_RESET if color else ''}"
+        )
+        if args.ids and edit_ids:
+            print(f"{'':<19}  {', '.join(e[:8] for e in edit_ids)}")
+    count = len(records)
+    print(f"\n{count} record{'' if count == 1 else 's'}.")
+
+
+def handle_watch(
+    args: argparse.Namespace,
+    workspace_root: Path,
+    history_root: Path,
+    all_entries: List[Dict[str, Any]],
+) -> None:
+    """Handle the watch command: print log entries as they are appended."""
+    logs_dir = history_root / utils.LOGS_DIR
+    conv_filter = args.conv.lower() if args.conv else None
+
+    def conv_matches(conv_id: str) -> bool:
+        conv_id = conv_id.lower()
+        return conv_id.startswith(conv_filter) or conv_id.endswith(conv_filter)
+
+    def watched_log_files() -> List[Path]:
+        return [
+            p
+            for p in utils.find_log_files(logs_dir)
+            if not conv_filter or conv_matches(utils.get_log_conversation_id(p))
+        ]
+
+    # Conversations seen with an edit of the --session shell session
+    session_convs: Set[str] = set()
+    tags = history.get_tags_by_conversation(history.load_tags(history_root))
+
+    state = history.load_watch_state(history_root)
+    # Logs without a saved position start at their current end: only new entries
+    for log_file in watched_log_files():
+        source = utils.get_log_file_source(log_file)
+        if source not in state:
+            _, state[source] = history.read_new_log_entries(log_file, None)
+    history.save_watch_state(history_root, state)
+
+    if not args.json:
+        target = f"conversation {args.conv}" if args.conv else "all conversations"
+        if args.session:
+            target += f" of session {args.session}"
+        print(f"Watching {target} for new edits (Ctrl-C to stop)...")
+        history.print_entry_list_header(tags)
+    sys.stdout.flush()
+
+    try:
+        while True:
+            changed = False
+            for log_file in watched_log_files():
+                source = utils.get_log_file_source(log_file)
+                try:
+                    entries, position = history.read_new_log_entries(
+                        log_file, state.get(source)
+                    )
+                except FileNotFoundError:
+                    continue  # Replaced between glob and read; retry next poll
+                except (OSError, UnicodeDecodeError) as e:
+                    log.warning(f"Could not read {source}: {e}")
+                    continue
+                if position != state.get(source):
+                    state[source] = position
+                    changed = True
+                for entry in entries:
+                    if conv_filter and not conv_matches(
+                        entry.get("conversation_id", "")
+                    ):
+                        continue
+                    if args.session:
+                        if entry.get("session_id") == args.session:
+                            session_convs.add(entry.get("conversation_id"))
+                        if entry.get("conversation_id") not in session_convs:
+                            continue
+                    if args.json:
+                        print(
+                            json.dumps(utils.encode_log_paths(entry)), flush=True
+                        )
+                    else:
+                        print(
+                            history.format_entry_summary(entry, tags=tags),
+                            flush=True,
+                        )
+            if changed:
+                history.save_watch_state(history_root, state)
+            time.sleep(args.interval)
+    except KeyboardInterrupt:
+        history.save_watch_state(history_root, state)
+        if not args.json:
+            print("\nStopped watching.")
+
+
+def handle_daemon(
+    args: argparse.Namespace,
+    workspace_root: Path,
+    history_root: Path,
+    all_entries: List[Dict[str, Any]],
+) -> None:
+    """Handle the daemon command: start, stop or query the background index."""
+    if not daemon.is_supported():
+        raise HistoryError("The daemon needs fork() and Unix domain sockets.")
+    socket_path = daemon.get_socket_path(workspace_root)
+
+    if args.action == "start":
+        metrics_server = None
+        if args.metrics is not None:
+            metrics_server = daemon.bind_metrics_socket(args.metrics)
+        server = daemon.bind_socket(workspace_root)
+        pid = daemon.run_in_background(
+            server, workspace_root, history_root, args.interval, metrics_server
+        )
+        deadline = time.monotonic() + args.timeout
+        while daemon.query(workspace_root, {"op": "ping"}) is None:
+            if time.monotonic() > deadline or not utils.pid_exists(pid):
+                log_path = workspace_root / ".mcp" / daemon.DAEMON_LOG_NAME
+                raise HistoryError(f"The daemon did not start; see {log_path}")
+            time.sleep(0.05)
+        print(
+            f"{utils.COLOR_GREEN}Daemon started (pid {pid}), listening on {socket_path}.{utils.COLOR_RESET}"
+        )
+    elif args.action == "stop":
+        pid = daemon.stop(workspace_root, args.timeout)
+        if pid is None:
+            print(f"{utils.COLOR_YELLOW}No daemon is running.{utils.COLOR_RESET}")
+        else:
+            print(f"{utils.COLOR_GREEN}Daemon {pid} stopped.{utils.COLOR_RESET}")
+    else:
+        info = daemon.query(workspace_root, {"op": "ping"})
+        if info is None:
+            print(f"{utils.COLOR_YELLOW}No daemon is running.{utils.COLOR_RESET}")
+            return
+        print(f"Daemon {info['pid']} listening on {socket_path}")
+        started = utils.format_timestamp_absolute(info["started"])
+        print(f"  Running since: {started}")
+        print(f"  Change watcher: {info['watcher']}")
+        print(f"  Indexed: {info['entries']} entries in {info['log_files']} log files")
+        if info.get("metrics_port"):
+            metrics_url = daemon.get_metrics_url(info["metrics_port"])
+            print(f"  Metrics: {metrics_url}")
+
+
+def handle_upgrade_schema(
+    args: argparse.Namespace,
+    workspace_root: Path,
+    history_root: Path,
+    all_entries: List[Dict[str, Any]],
+) -> None:
+    """Handle the upgrade-schema command: rewrite logs at the current schema version."""
+    lock_timeout = args.timeout
+    logs_dir = history_root / utils.LOGS_DIR
+    log_files = utils.find_log_files(logs_dir)
+    if not log_files:
+        print(f"{utils.COLOR_YELLOW}No log files found.{utils.COLOR_RESET}")
+        return
+
+    migrator = migration.default_migrator
+    total_upgraded = 0
+    files_upgraded = 0
+    for log_file in log_files:
+        # Like every log writer, hold the conversation's lock: the server
+        # resolves WALs it finds under it
+        conv_lock = utils.ConversationLock(
+            history_root, utils.get_log_conversation_id(log_file)
+        )
+        conv_lock.acquire(lock_timeout)
+        try:
+            # Rewriting a log would drop lines that do not parse
+            raw_entries = utils.read_log_file(
+                log_file, lock_timeout=lock_timeout, migrate=False, strict=True
+            )
+            outdated = [e for e in raw_entries if migrator.needs_upgrade(e)]
+            if outdated and not args.dry_run:
+                upgraded_entries = [migrator.upgrade(e) for e in raw_entries]
+                utils.write_log_file(
+                    log_file, upgraded_entries, lock_timeout=lock_timeout
+                )
+        finally:
+            conv_lock.release()
+        if not outdated:
+            log.debug(f"Log file {log_file.name} is already at the current schema.")
+            continue
+
+        if args.dry_run:
+            print(f"Would upgrade {len(outdated)} entries in {log_file.name}")
+        else:
+            print(f"Upgraded {len(outdated)} entries in {log_file.name}")
+        total_upgraded += len(outdated)
+        files_upgraded += 1
+
+    if total_upgraded == 0:
+        print(
+            f"{utils.COLOR_GREEN}All {len(log_files)} log file(s) are already at schema version {migrator.current_version}.{utils.COLOR_RESET}"
+        )
+    elif args.dry_run:
+        print(
+            f"\n{total_upgraded} entries in {files_upgraded} log file(s) would be upgraded to schema version {migrator.current_version}."
+        )
+    else:
+        print(
+            f"\n{utils.COLOR_GREEN}Upgraded {total_upgraded} entries in {files_upgraded} log file(s) to schema version {migrator.current_version}.{utils.COLOR_RESET}"
+        )
+
+
+def handle_log_append(
+    args: argparse.Namespace,
+    workspace_root: Path,
+    history_root: Path,
+    all_entries: List[Dict[str, Any]],
+) -> None:
+    """
+    Handle the log-append plumbing command: validate log entries read from stdin
+    and append them to a conversation's log.
+    """
+    entries = append.parse_entries(sys.stdin.read())
+    if not entries:
+        raise HistoryError("No log entries on stdin.")
+    written = append.append_log_entries(
+        history_root, args.conv, entries, lock_timeout=args.timeout
+    )
+    if args.json:
+        print(
+            json.dumps(
+                [
+                    {
+                        "edit_id": e["edit_id"],
+                        "tool_call_index": e["tool_call_index"],
+                        "log_file": e["log_file_source"],
+                    }
+                    for e in written
+                ]
+            )
+        )
+        return
+    for entry in written:
+        print(f"{entry['edit_id']}  {entry['tool_call_index']}")
+    print(
+        f"{utils.COLOR_GREEN}Appended {len(written)} entries to {written[0]['log_file_source']}.{utils.COLOR_RESET}",
+        file=sys.stderr,
+    )
+
+
+def handle_config(
+    args: argparse.Namespace,
+    workspace_root: Path,
+    history_root: Path,
+    all_entries: List[Dict[str, Any]],
+) -> None:
+    """Handle the config command (get/set/unset/list workspace settings)."""
+    if args.config_action == "set":
+        value = config.set_config_value(workspace_root, args.key, args.value)
+        print(f"{utils.COLOR_GREEN}Set {args.key} = {value}{utils.COLOR_RESET}")
+    elif args.config_action == "get":
+        print(config.get_config_value(workspace_root, args.key))
+    elif args.config_action == "unset":
+        if config.unset_config_value(workspace_root, args.key) is None:
+            print(f"{utils.COLOR_YELLOW}{args.key} was not set.{utils.COLOR_RESET}")
+        else:
+            default = config.CONFIG_KEYS[args.key]["default"]
+            print(f"Unset {args.key} (default: {default})")
+    else:  # list
+        current = config.load_config(workspace_root)
+        print(f"{utils.COLOR_CYAN}{'Key':<24}  {'Value':<20}  Description{utils.COLOR_RESET}")
+        print("-" * 100)
+        for key, spec in sorted(config.CONFIG_KEYS.items()):
+            value = current.get(key, spec["default"])
+            marker = "" if key in current else " (default)"
+            print(f"{key:<24}  {str(value) + marker:<20}  {spec['help']}")
+
+
+def handle_ignore(
+    args: argparse.Namespace,
+    workspace_root: Path,
+    history_root: Path,
+    all_entries: List[Dict[str, Any]],
+) -> None:
+    """Handle the ignore command (add a pattern to .mcp/ignore)."""
+    pattern = args.pattern.strip()
+    if not pattern or pattern.startswith("#"):
+        raise HistoryError(f"Not an ignore pattern: {args.pattern!r}")
+    ignore_path = ignore.get_ignore_path(workspace_root)
+    if ignore.add_ignore_pattern(workspace_root, pattern):
+        print(
+            f"{utils.COLOR_GREEN}Added {pattern} to {ignore_path}{utils.COLOR_RESET}"
+        )
+    else:
+        print(
+            f"{utils.COLOR_YELLOW}{pattern} is already in {ignore_path}{utils.COLOR_RESET}"
+        )
+
+
+# --- Main Execution ---
+
+
+def main():
+    parser = argparse.ArgumentParser(
+        description="MCP Diff Tool: Review and manage LLM file edits.",
+        formatter_class=argparse.RawDescriptionHelpFormatter,
+        epilog="""
+Examples:
+  mcpdiff status                     # Show recent history status (newest first limited)
+  mcpdiff st -n 0                    # Show all history status (newest first)
+  mcpdiff status --conv 17... --file src/main.py --status pending
+  mcpdiff st --fields edit_id,status,delta,file_path
+  mcpdiff st -c 17... --since-edit-id ab12 --until-edit-id cd34  # Edits between two edits
+  mcpdiff st --session $MCP_SESSION_ID  # Edits of conversations run from this shell
+  mcpdiff st --status pending --count --group-by file  # Pending edits per file
+  mcpdiff show <edit_id_prefix>      # Show diff for a specific edit
+  mcpdiff show <conv_id_prefix>      # Show all diffs for a conversation
+  mcpdiff show <id> --ignore-whitespace  # Hide indentation-only changes
+  mcpdiff show <conv_id> --symbols   # Name the Rust fn/struct/impl of each hunk
+  mcpdiff accept -e <edit_id_prefix> # Accept a specific edit (reconstructs file)
+  mcpdiff accept -c <conv_id_prefix> # Accept all pending edits for a conversation
+  mcpdiff reject -e <edit_id_prefix> # Reject an edit (snapshots, rejects, reconstructs)
+  mcpdiff reject -c <conv_id_prefix> # Reject all pending/accepted edits for a conversation
+  mcpdiff reject -e <id> --fuzz 1    # Reject even if later diffs have slightly stale context
+  mcpdiff st --no-ignore             # Include edits of paths listed in .mcp/ignore
+  mcpdiff mark-pending -e <edit_id>  # Reset an accepted/rejected edit to pending
+  mcpdiff annotate -e <id> --note "..." # Attach review notes, shown by show
+  mcpdiff split -e <edit_id>         # One edit per fn/struct/impl a Rust edit changes
+  mcpdiff diagnose                   # Check files on disk against recorded hashes
+  mcpdiff review                     # Interactively review pending edits (oldest first)
+  mcpdiff review -c <conv_id>        # Review pending edits for a specific conversation
+  mcpdiff cleanup                    # Clean up stale locks
+  mcpdiff crash-recover              # Replay log writes a crash interrupted
+  mcpdiff resolve-conflicts          # Resolve conflict markers edits left, file by file
+  mcpdiff tag -c <conv_id> --name X  # Name a conversation; use -c X afterwards
+  mcpdiff watch -c <conv_id>         # Print new edits of a conversation as they land
+  mcpdiff du --top 10                # Disk usage of the 10 largest conversations
+  mcpdiff info                       # Workspace paths, settings and history size
+  mcpdiff conversations --active     # Conversations with pending edits
+  mcpdiff convs --tree               # Forked conversations under their parent
+  mcpdiff top-files --since 7d       # The 10 files edited most in the last week
+  mcpdiff top-files -c a1b2 --by bytes  # The files conversation a1b2 grew most
+  mcpdiff blame -f src/x.py -L 10-20 # Which edit last touched lines 10-20
+  mcpdiff plan -c <conv_id>          # What rejecting the conversation would re-apply
+  mcpdiff preview reject -c <conv_id> # What rejecting it would change in the files
+  mcpdiff diff-since --since 2d      # What edits accepted in 2 days changed
+  mcpdiff checkpoint-info            # Size and age of checkpoint files
+  mcpdiff prune-checkpoints --keep-n 3 # Keep the 3 newest checkpoints per file
+  mcpdiff prune --keep-last 20 --dry-run # Artifacts only for the 20 newest conversations
+  mcpdiff squash-rejected -c <conv_id> --confirm # Drop its rejected edits and their files
+  mcpdiff snapshot -m "before refactor" # Checkpoint all tracked files
+  mcpdiff restore-snapshot -s 3fa8   # Put them all back
+  mcpdiff expire-old --older-than 7d # Reject edits pending for more than a week
+  mcpdiff reattach --from-conv new --to-conv old # Move a restarted session's edits
+  mcpdiff compare-convs --conv-a 1f3e --conv-b 9a0c  # Files both sessions changed
+  mcpdiff verify -c <conv_id>        # Check a conversation log against its Merkle root
+  mcpdiff rebaseline -e <edit_id>    # Make the file as it is now the edit's checkpoint
+  mcpdiff format-log -c <conv_id> | less # Read a conversation log, one field per line
+  mcpdiff schema > log-entry.schema.json # JSON Schema of log entries, for other tools
+  mcpdiff workspace detect ~/monorepo --json  # Workspaces below a directory
+  mcpdiff workspace init             # Set up a history here, with a permissive .mcp/acl.toml
+  mcpdiff audit --since 7d           # Who accepted or rejected what in the last week
+  mcpdiff accept -c <conv_id> --before 2025-01-31T12:00 # Accept the morning's edits
+  mcpdiff daemon start               # Keep the history in memory for fast status
+  mcpdiff upgrade-schema --dry-run   # Report log entries needing a schema upgrade
+  mcpdiff show <id> --format raw     # Print the stored diff without decoration
+  mcpdiff config set syntax_theme X  # Set the Pygments theme used by show
+  mcpdiff ignore add 'target/'       # Keep no checkpoints or diffs of build output
+""",
     )
     parser.add_argument(
         "-w",
@@ -996,11 +4300,53 @@ Examples:
         help="Limit entries shown (0 for all matching, default: 50).",
     )
     parser_status.add_argument(
-        "--time", help="Filter by time relative to now (e.g., '30s', '5m', '1h', '2d')."
+        "--time", help="Filter by time relative to now (e.g., '30s', '5m', '1h', '2d')."
+    )
+    parser_status.add_argument(
+        "--no-ignore",
+        action="store_true",
+        help="Also show edits of paths listed in .mcp/ignore.",
+    )
+    parser_status.add_argument(
+        "--session",
+        metavar="ID",
+        help="Only conversations with edits made in this shell session "
+        "(MCP_SESSION_ID).",
+    )
+    parser_status.add_argument(
+        "--op",
+        help="Filter by operation type (e.g., edit, create, delete, move, rename, replace).",
+    )
+    _add_time_range_arguments(parser_status, "edits")
+    parser_status.add_argument(
+        "--since-edit-id",
+        metavar="ID",
+        help="Only edits made after this edit in its conversation (needs --conv).",
+    )
+    parser_status.add_argument(
+        "--until-edit-id",
+        metavar="ID",
+        help="Only edits made before this edit in its conversation (needs --conv).",
+    )
+    parser_status.add_argument(
+        "--fields",
+        help="Comma-separated columns to show, e.g. 'edit_id,status,delta,file_path'. "
+        f"Valid fields: {', '.join(history.STATUS_FIELDS)}.",
+    )
+    parser_status.add_argument(
+        "--json",
+        action="store_true",
+        help="Print matching entries as a JSON array with all fields.",
     )
     parser_status.add_argument(
-        "--op",
-        help="Filter by operation type (e.g., edit, create, delete, move, replace).",
+        "--count",
+        action="store_true",
+        help="Only print the number of matching entries (ignores -n).",
+    )
+    parser_status.add_argument(
+        "--group-by",
+        choices=list(history.COUNT_GROUP_FIELDS),
+        help="With --count, print a count per conversation, file, status or operation.",
     )
     parser_status.set_defaults(func=handle_status)
 
@@ -1010,8 +4356,46 @@ Examples:
     )
     parser_show.add_argument(
         "identifier",
+        nargs="?",
         help="The edit_id prefix or conversation_id prefix/suffix to show.",
     )
+    parser_show.add_argument(
+        "--verbose",
+        dest="show_details",
+        action="store_true",
+        help="Also show tool, hashes, and IDE context recorded with each edit.",
+    )
+    parser_show.add_argument(
+        "--no-color",
+        action="store_true",
+        help="Disable colors and syntax highlighting (off anyway if not a TTY).",
+    )
+    parser_show.add_argument(
+        "--format",
+        choices=["pretty", "raw"],
+        default="pretty",
+        help="'raw' prints the diff exactly as stored, without headers or colors.",
+    )
+    parser_show.add_argument(
+        "--ignore-whitespace",
+        action="store_true",
+        help="Show changes that only touch indentation or trailing whitespace as unchanged lines; hunk headers keep their counts.",
+    )
+    parser_show.add_argument(
+        "--symbols",
+        action="store_true",
+        help="Name the function, struct or impl each hunk changes in its header (Rust files).",
+    )
+    parser_show.add_argument(
+        "--language",
+        metavar="LANG",
+        help="Highlight the diff as LANG instead of by file extension: a Pygments language alias such as python, html+mako, jinja or rust (see --list-languages).",
+    )
+    parser_show.add_argument(
+        "--list-languages",
+        action="store_true",
+        help="List the languages --language takes, with their aliases, and exit.",
+    )
     parser_show.set_defaults(func=handle_show)
 
     # accept
@@ -1029,13 +4413,35 @@ Examples:
         "--conv",
         help="Accept all pending edits for a conversation_id prefix/suffix.",
     )
+    parser_accept.add_argument(
+        "--force",
+        action="store_true",
+        help="Also accept edits whose parent edit was rejected.",
+    )
+    parser_accept.add_argument(
+        "--no-verify",
+        action="store_true",
+        help="Write re-applied files even if they do not match the hash recorded by the last edit.",
+    )
+    _add_time_range_arguments(parser_accept, "the conversation's edits")
+    parser_accept.add_argument(
+        "--yes",
+        "-y",
+        action="store_true",
+        help=f"Do not ask before accepting more than {BULK_CONFIRM_THRESHOLD} edits selected by --before/--after.",
+    )
+    parser_accept.add_argument(
+        "--git-stage",
+        action="store_true",
+        help="Run `git add` on each accepted file git tracks (config key auto_git_stage makes this the default).",
+    )
     parser_accept.set_defaults(func=handle_accept)
 
     # reject
     parser_reject = subparsers.add_parser(
         "reject",
         aliases=["r"],
-        help="Reject edits (snapshots current state, marks rejected, reconstructs file).",
+        help="Reject edits and the edits that depend on them (snapshots current state, marks rejected, reconstructs file).",
     )
     group_reject = parser_reject.add_mutually_exclusive_group(required=True)
     group_reject.add_argument(
@@ -1046,8 +4452,103 @@ Examples:
         "--conv",
         help="Reject all pending/accepted edits for a conversation_id prefix/suffix.",
     )
+    parser_reject.add_argument(
+        "--fuzz",
+        type=int,
+        default=0,
+        metavar="N",
+        help="Let diffs apply with up to N lines of their context not matching (default: 0). Each such hunk is reported.",
+    )
+    parser_reject.add_argument(
+        "--ignore-whitespace",
+        action="store_true",
+        help="Match diff context ignoring differences in whitespace.",
+    )
+    parser_reject.add_argument(
+        "--no-ignore",
+        action="store_true",
+        help="Also reject edits of paths listed in .mcp/ignore.",
+    )
+    parser_reject.add_argument(
+        "--no-verify",
+        action="store_true",
+        help="Write re-applied files even if they do not match the hash recorded by the last edit.",
+    )
+    parser_reject.add_argument(
+        "--verbose",
+        dest="show_reapplied",
+        action="store_true",
+        help="Print each edit re-applied, with its operation and file, and whether it applied.",
+    )
+    _add_time_range_arguments(parser_reject, "the conversation's edits")
+    parser_reject.add_argument(
+        "--yes",
+        "-y",
+        action="store_true",
+        help=f"Do not ask before rejecting more than {BULK_CONFIRM_THRESHOLD} edits selected by --before/--after.",
+    )
     parser_reject.set_defaults(func=handle_reject)
 
+    # annotate
+    parser_annotate = subparsers.add_parser(
+        "annotate", help="Attach review notes to an edit (shown by `show`)."
+    )
+    parser_annotate.add_argument(
+        "-e", "--edit-id", required=True, help="Edit ID prefix to annotate."
+    )
+    parser_annotate.add_argument(
+        "--note",
+        required=True,
+        help="The notes, replacing any earlier ones; an empty string removes them.",
+    )
+    parser_annotate.set_defaults(func=handle_annotate)
+
+    # split
+    parser_split = subparsers.add_parser(
+        "split",
+        help="Divide a pending edit of a Rust file into one edit per item it changes.",
+    )
+    parser_split.add_argument(
+        "-e", "--edit-id", required=True, help="Edit ID prefix to split."
+    )
+    parser_split.set_defaults(func=handle_split)
+
+    # rebaseline
+    parser_rebaseline = subparsers.add_parser(
+        "rebaseline",
+        help="Make a file's current content the checkpoint of an edit, and its hash the edit's hash_before.",
+    )
+    parser_rebaseline.add_argument(
+        "-e", "--edit-id", required=True, help="Edit ID prefix to rebaseline."
+    )
+    parser_rebaseline.add_argument(
+        "--yes", "-y", action="store_true", help="Do not ask for confirmation."
+    )
+    parser_rebaseline.set_defaults(func=handle_rebaseline)
+
+    # mark-pending
+    parser_mark_pending = subparsers.add_parser(
+        "mark-pending",
+        help="Reset an accepted or rejected edit back to pending (log only).",
+    )
+    parser_mark_pending.add_argument(
+        "-e", "--edit-id", required=True, help="Edit ID prefix to reset."
+    )
+    parser_mark_pending.add_argument(
+        "--force",
+        action="store_true",
+        help="Rewrite already-pending edits and skip the confirmation prompt.",
+    )
+    parser_mark_pending.set_defaults(func=handle_mark_pending)
+
+    # diagnose
+    parser_diagnose = subparsers.add_parser(
+        "diagnose",
+        help="Check whether tracked files on disk match their recorded state.",
+    )
+    parser_diagnose.add_argument("--file", "-f", help="Filter by file path substring.")
+    parser_diagnose.set_defaults(func=handle_diagnose)
+
     # review
     parser_review = subparsers.add_parser(
         "review",
@@ -1063,10 +4564,637 @@ Examples:
 
     # cleanup
     parser_cleanup = subparsers.add_parser(
-        "cleanup", aliases=["clean"], help="Clean up stale locks."
+        "cleanup",
+        aliases=["clean", "clean-locks"],
+        help="Clean up stale locks, and lock files older versions left next to tracked files.",
+    )
+    parser_cleanup.add_argument(
+        "--force",
+        action="store_true",
+        help="Remove stray lock files next to tracked files without asking.",
     )
     parser_cleanup.set_defaults(func=handle_cleanup)
 
+    # crash-recover
+    parser_recover = subparsers.add_parser(
+        "crash-recover",
+        help="Replay the write-ahead logs (logs/*.wal) of log writes a crash interrupted.",
+    )
+    parser_recover.add_argument(
+        "--json", action="store_true", help="Output what was done as JSON."
+    )
+    parser_recover.set_defaults(func=handle_crash_recover)
+
+    # resolve-conflicts
+    parser_conflicts = subparsers.add_parser(
+        "resolve-conflicts",
+        help="List the files edits left merge conflict markers in and resolve them one by one.",
+    )
+    parser_conflicts.add_argument(
+        "--conv", "-c", help="Only edits of this conversation ID prefix/suffix."
+    )
+    group_conflicts = parser_conflicts.add_mutually_exclusive_group()
+    group_conflicts.add_argument(
+        "--list", action="store_true", help="Only list the files, do not prompt."
+    )
+    group_conflicts.add_argument(
+        "--json", action="store_true", help="List the files as JSON."
+    )
+    parser_conflicts.set_defaults(func=handle_resolve_conflicts)
+
+    # tag
+    parser_tag = subparsers.add_parser(
+        "tag", help="Name conversations so --conv can refer to them by tag."
+    )
+    parser_tag.add_argument("--conv", "-c", help="Conversation ID prefix/suffix.")
+    parser_tag.add_argument("--name", help="Tag name to assign.")
+    group_tag = parser_tag.add_mutually_exclusive_group()
+    group_tag.add_argument("--list", action="store_true", help="List all tags.")
+    group_tag.add_argument("--remove", metavar="NAME", help="Remove a tag.")
+    parser_tag.set_defaults(func=handle_tag)
+
+    # du
+    parser_du = subparsers.add_parser(
+        "du",
+        help="Show disk usage of logs, diffs and checkpoints per conversation.",
+    )
+    parser_du.add_argument(
+        "--top",
+        type=int,
+        default=0,
+        help="Only show the N largest conversations (default: all).",
+    )
+    parser_du.add_argument(
+        "--json", action="store_true", help="Output the report as JSON."
+    )
+    parser_du.set_defaults(func=handle_du)
+
+    # info
+    parser_info = subparsers.add_parser(
+        "info",
+        help="Show the workspace and history paths, settings, and history statistics.",
+    )
+    parser_info.add_argument(
+        "--json", action="store_true", help="Output the information as JSON."
+    )
+    parser_info.set_defaults(func=handle_info)
+
+    # conversations
+    parser_conversations = subparsers.add_parser(
+        "conversations",
+        aliases=["convs"],
+        help="List conversations with their edit counts and last activity.",
+    )
+    parser_conversations.add_argument(
+        "--active",
+        action="store_true",
+        help="Only conversations with at least one pending edit.",
+    )
+    parser_conversations.add_argument(
+        "--idle",
+        metavar="DURATION",
+        help="Only conversations with no new edits for DURATION (e.g. 30m, 2h).",
+    )
+    parser_conversations.add_argument(
+        "--session",
+        metavar="ID",
+        help="Only conversations with edits made in this shell session "
+        "(MCP_SESSION_ID).",
+    )
+    parser_conversations.add_argument(
+        "--namespace",
+        metavar="NAME",
+        help="Only conversations of this namespace (config key `namespace`); "
+        "those without one are in 'default'.",
+    )
+    parser_conversations.add_argument(
+        "--tree",
+        action="store_true",
+        help="Show forked conversations indented under the one they were "
+        "forked from.",
+    )
+    parser_conversations.add_argument(
+        "--json", action="store_true", help="Output the list as JSON."
+    )
+    parser_conversations.set_defaults(func=handle_conversations)
+
+    # top-files, top-conversations
+    for name, func, what in (
+        ("top-files", handle_top_files, "files"),
+        ("top-conversations", handle_top_conversations, "conversations"),
+    ):
+        parser_top = subparsers.add_parser(
+            name,
+            help=f"Rank {what} by number of edits or bytes added, with counts per status.",
+        )
+        parser_top.add_argument(
+            "--by",
+            choices=["edits", "bytes"],
+            default="edits",
+            help="Rank by number of edits (default) or by byte growth: the sum of byte_size_after - byte_size_before over the edits.",
+        )
+        parser_top.add_argument(
+            "--limit",
+            type=int,
+            default=10,
+            help=f"Number of {what} to show (default: 10, 0 for all).",
+        )
+        if name == "top-files":
+            parser_top.add_argument(
+                "-c",
+                "--conv",
+                help="Only count the edits of this conversation (ID prefix/suffix or tag).",
+            )
+        parser_top.add_argument(
+            "--since",
+            metavar="TIME",
+            help="Only count edits logged at or after TIME: an ISO date or time (UTC unless it has an offset) or a duration ago, e.g. 7d.",
+        )
+        parser_top.add_argument(
+            "--until",
+            metavar="TIME",
+            help="Only count edits logged before TIME (same syntax as --since).",
+        )
+        parser_top.add_argument(
+            "--json", action="store_true", help="Output the ranking as JSON."
+        )
+        parser_top.set_defaults(func=func)
+
+    # compare-convs
+    parser_compare = subparsers.add_parser(
+        "compare-convs",
+        help="List the files changed by only one of two conversations, or by both.",
+    )
+    parser_compare.add_argument(
+        "--conv-a", required=True, help="First conversation ID prefix/suffix or tag."
+    )
+    parser_compare.add_argument(
+        "--conv-b", required=True, help="Second conversation ID prefix/suffix or tag."
+    )
+    parser_compare.add_argument(
+        "--json", action="store_true", help="Output the comparison as JSON."
+    )
+    parser_compare.set_defaults(func=handle_compare_convs)
+
+    # blame
+    parser_blame = subparsers.add_parser(
+        "blame", help="Show which edit last added or changed each line of a file."
+    )
+    parser_blame.add_argument(
+        "--file",
+        "-f",
+        required=True,
+        help="File to annotate (path or unique substring).",
+    )
+    parser_blame.add_argument(
+        "--lines", "-L", metavar="START-END", help="Only show these lines (1-based)."
+    )
+    parser_blame.add_argument(
+        "--accepted",
+        action="store_true",
+        help="Replay accepted edits only (default: accepted and pending).",
+    )
+    parser_blame.set_defaults(func=handle_blame)
+
+    # plan
+    parser_plan = subparsers.add_parser(
+        "plan",
+        help="Show how accept and reject would re-apply a conversation's files, without changing anything.",
+    )
+    parser_plan.add_argument(
+        "--conv", "-c", required=True, help="Conversation ID (prefix or suffix) or tag."
+    )
+    parser_plan.add_argument(
+        "--file",
+        "-f",
+        help="Only plan this file (path or unique substring; default: all files the conversation edited).",
+    )
+    parser_plan.add_argument(
+        "--accepted",
+        action="store_true",
+        help="Plan applying accepted edits only (default: accepted and pending).",
+    )
+    parser_plan.add_argument(
+        "--json", action="store_true", help="Output the plans as JSON."
+    )
+    parser_plan.set_defaults(func=handle_plan)
+
+    # preview
+    parser_preview = subparsers.add_parser(
+        "preview",
+        help="Show what an action would change in the working tree, without changing it.",
+    )
+    preview_subparsers = parser_preview.add_subparsers(
+        dest="preview_action", required=True
+    )
+    parser_preview_reject = preview_subparsers.add_parser(
+        "reject",
+        help="Re-apply a conversation's files as rejecting it would, in a staging directory under .mcp/preview, and diff them against the working tree.",
+    )
+    parser_preview_reject.add_argument(
+        "--conv", "-c", required=True, help="Conversation ID (prefix or suffix) or tag."
+    )
+    parser_preview_reject.add_argument(
+        "--file",
+        "-f",
+        help="Only preview this file (path or unique substring; default: all files the reject would re-apply).",
+    )
+    parser_preview_reject.add_argument(
+        "--keep-staged",
+        action="store_true",
+        help="Keep the staging directory and print its path.",
+    )
+    parser_preview_reject.add_argument(
+        "--no-ignore",
+        action="store_true",
+        help="Also preview rejecting edits of paths listed in .mcp/ignore.",
+    )
+    parser_preview_reject.add_argument(
+        "--json", action="store_true", help="Output the result per file as JSON."
+    )
+    parser_preview.set_defaults(func=handle_preview)
+
+    # diff-since
+    parser_diff_since = subparsers.add_parser(
+        "diff-since",
+        help="Show one diff per file of what the edits accepted since a time or snapshot changed.",
+    )
+    group_since = parser_diff_since.add_mutually_exclusive_group(required=True)
+    group_since.add_argument(
+        "--since",
+        metavar="TIME",
+        help="Diff from the files as they were at TIME: an RFC 3339 / ISO time (UTC unless it has an offset) or a duration ago, e.g. 2d.",
+    )
+    group_since.add_argument(
+        "--since-snapshot",
+        metavar="SNAPSHOT_ID",
+        help="Diff from the files as snapshot SNAPSHOT_ID (prefix) recorded them.",
+    )
+    parser_diff_since.add_argument(
+        "--conv",
+        "-c",
+        help="Only the edits of this conversation (ID prefix or suffix, or tag).",
+    )
+    parser_diff_since.add_argument(
+        "--file",
+        "-f",
+        help="Only diff this file (path or unique substring).",
+    )
+    parser_diff_since.add_argument(
+        "--json", action="store_true", help="Output the diff per file as JSON."
+    )
+    parser_diff_since.set_defaults(func=handle_diff_since)
+
+    # checkpoint-info
+    parser_checkpoint_info = subparsers.add_parser(
+        "checkpoint-info",
+        help="List checkpoint files with their size, age and references.",
+    )
+    parser_checkpoint_info.add_argument(
+        "--conv", "-c", help="Filter by conversation ID prefix or suffix."
+    )
+    parser_checkpoint_info.add_argument(
+        "--file", "-f", help="Filter by file path substring."
+    )
+    parser_checkpoint_info.add_argument(
+        "--sort-by",
+        choices=["size", "age", "edit_id"],
+        default="size",
+        help="Sort order: largest, oldest, or by edit ID (default: size).",
+    )
+    parser_checkpoint_info.set_defaults(func=handle_checkpoint_info)
+
+    # prune-checkpoints
+    parser_prune = subparsers.add_parser(
+        "prune-checkpoints",
+        help="Delete all but the N most recent checkpoints of each file.",
+    )
+    parser_prune.add_argument(
+        "--keep-n",
+        type=int,
+        required=True,
+        metavar="N",
+        help="Checkpoints to keep per file (at least 1).",
+    )
+    parser_prune.add_argument(
+        "--conv", "-c", help="Only checkpoints of this conversation (prefix/suffix)."
+    )
+    parser_prune.add_argument("--file", "-f", help="Only files matching this path.")
+    parser_prune.add_argument(
+        "--dry-run",
+        action="store_true",
+        help="Only list the checkpoints that would be pruned.",
+    )
+    parser_prune.set_defaults(func=handle_prune_checkpoints)
+
+    # prune
+    parser_prune_convs = subparsers.add_parser(
+        "prune",
+        help="Drop diffs and checkpoints of all but the N most recent conversations, keeping their logs.",
+    )
+    parser_prune_convs.add_argument(
+        "--keep-last",
+        type=int,
+        required=True,
+        metavar="N",
+        help="Conversations (by last activity) that keep their diffs and checkpoints.",
+    )
+    parser_prune_convs.add_argument(
+        "--keep-artifacts-for-pending",
+        action="store_true",
+        help="Also keep them for older conversations that still have pending edits.",
+    )
+    parser_prune_convs.add_argument(
+        "--dry-run",
+        action="store_true",
+        help="Only list the conversations that would be pruned.",
+    )
+    parser_prune_convs.set_defaults(func=handle_prune)
+
+    # squash-rejected
+    parser_squash = subparsers.add_parser(
+        "squash-rejected",
+        help="Remove a conversation's rejected edits from its log, with their diffs and checkpoints.",
+    )
+    parser_squash.add_argument(
+        "--conv",
+        "-c",
+        required=True,
+        help="Conversation ID prefix/suffix or tag.",
+    )
+    parser_squash.add_argument(
+        "--confirm",
+        action="store_true",
+        help="Remove them; without it, only list what would be removed.",
+    )
+    parser_squash.set_defaults(func=handle_squash_rejected)
+
+    # snapshot, restore-snapshot
+    parser_snapshot = subparsers.add_parser(
+        "snapshot",
+        help="Checkpoint every tracked file at once, outside any conversation.",
+    )
+    parser_snapshot.add_argument(
+        "--message", "-m", help="Description stored with the snapshot."
+    )
+    parser_snapshot.add_argument(
+        "--list", action="store_true", help="List the snapshots, newest first."
+    )
+    parser_snapshot.add_argument(
+        "--json", action="store_true", help="Output the snapshot(s) as JSON."
+    )
+    parser_snapshot.set_defaults(func=handle_snapshot)
+    parser_restore = subparsers.add_parser(
+        "restore-snapshot",
+        help="Restore all files of a snapshot to their snapshotted content.",
+    )
+    parser_restore.add_argument(
+        "--snapshot-id", "-s", required=True, help="Snapshot ID prefix."
+    )
+    parser_restore.add_argument(
+        "--yes", "-y", action="store_true", help="Do not ask for confirmation."
+    )
+    parser_restore.set_defaults(func=handle_restore_snapshot)
+
+    # expire-old
+    parser_expire = subparsers.add_parser(
+        "expire-old",
+        help="Reject pending edits older than a duration, in all conversations.",
+    )
+    parser_expire.add_argument(
+        "--older-than",
+        required=True,
+        metavar="DURATION",
+        help="Age of the edits to reject (e.g. 12h, 7d).",
+    )
+    parser_expire.add_argument(
+        "--dry-run",
+        action="store_true",
+        help="Only list the edits that would be rejected.",
+    )
+    parser_expire.set_defaults(func=handle_expire_old)
+
+    # reattach
+    parser_reattach = subparsers.add_parser(
+        "reattach",
+        help="Move edits to another conversation, e.g. after a session restarted.",
+    )
+    parser_reattach.add_argument(
+        "--from-conv",
+        required=True,
+        metavar="CONV",
+        help="Conversation to move the edits out of (ID prefix/suffix or tag).",
+    )
+    parser_reattach.add_argument(
+        "--to-conv",
+        required=True,
+        metavar="CONV",
+        help="Conversation to move them into (ID prefix/suffix or tag).",
+    )
+    parser_reattach.add_argument(
+        "--edit-id", "-e", help="Only move this edit (default: all of them)."
+    )
+    parser_reattach.set_defaults(func=handle_reattach)
+
+    # verify
+    parser_verify = subparsers.add_parser(
+        "verify",
+        help="Check conversation logs against their recorded Merkle roots, and checkpoints against the hash_before of their edits.",
+    )
+    parser_verify.add_argument(
+        "--conv", "-c", help="Conversation ID prefix/suffix (default: all)."
+    )
+    parser_verify.add_argument(
+        "--merkle-root",
+        metavar="HEX",
+        help="Trusted root to check the conversation against instead of its sidecar.",
+    )
+    parser_verify.set_defaults(func=handle_verify)
+
+    # format-log
+    parser_format_log = subparsers.add_parser(
+        "format-log",
+        help="Pretty-print a conversation's log entries and report invalid ones.",
+    )
+    parser_format_log.add_argument(
+        "--conv", "-c", required=True, help="Conversation ID (prefix or suffix) or tag."
+    )
+    parser_format_log.add_argument(
+        "--output",
+        "-o",
+        default="-",
+        help="File to write to, or - for stdout (default). For reading only: it is not a log file.",
+    )
+    parser_format_log.set_defaults(func=handle_format_log)
+
+    # schema
+    parser_schema = subparsers.add_parser(
+        "schema",
+        help="Print the JSON Schema (draft-07) of log entries or their enums.",
+    )
+    parser_schema.add_argument(
+        "--type",
+        choices=schema.SCHEMA_TYPES,
+        default="LogEntry",
+        help="Type to describe (default: LogEntry).",
+    )
+    parser_schema.set_defaults(func=handle_schema)
+
+    # audit
+    parser_audit = subparsers.add_parser(
+        "audit", help="Show the audit log of status changes and re-applies."
+    )
+    parser_audit.add_argument(
+        "--since",
+        metavar="WHEN",
+        help="Only records newer than a duration (e.g. 2h, 7d) or since a date.",
+    )
+    parser_audit.add_argument(
+        "--ids",
+        action="store_true",
+        help="List the edit IDs of each record.",
+    )
+    parser_audit.add_argument(
+        "--json", action="store_true", help="Output the records as JSON."
+    )
+    parser_audit.set_defaults(func=handle_audit)
+
+    # watch
+    parser_watch = subparsers.add_parser(
+        "watch", help="Print new edits as they are logged, until Ctrl-C."
+    )
+    parser_watch.add_argument("--conv", "-c", help="Conversation ID prefix/suffix.")
+    parser_watch.add_argument(
+        "--session",
+        metavar="ID",
+        help="Only conversations with edits made in this shell session "
+        "(MCP_SESSION_ID).",
+    )
+    parser_watch.add_argument(
+        "--json", action="store_true", help="Print one JSON object per entry."
+    )
+    parser_watch.add_argument(
+        "--interval",
+        type=float,
+        default=1.0,
+        help="Seconds between polls of the logs directory (default: 1.0).",
+    )
+    parser_watch.set_defaults(func=handle_watch)
+
+    # daemon
+    parser_daemon = subparsers.add_parser(
+        "daemon",
+        help="Run a background process that serves status from an in-memory index.",
+    )
+    parser_daemon.add_argument(
+        "action",
+        choices=["start", "stop", "status"],
+        help="Start the daemon, stop it, or show whether it is running.",
+    )
+    parser_daemon.add_argument(
+        "--interval",
+        type=float,
+        default=daemon.DEFAULT_INTERVAL,
+        help="Seconds between rescans of the logs when no change event arrives "
+        f"(default: {daemon.DEFAULT_INTERVAL}).",
+    )
+    parser_daemon.add_argument(
+        "--metrics",
+        type=int,
+        metavar="PORT",
+        help="With start: serve operation counts at http://127.0.0.1:PORT/metrics "
+        "in the Prometheus text format, without authentication (0: any free port).",
+    )
+    parser_daemon.set_defaults(func=handle_daemon)
+
+    # workspace
+    parser_workspace = subparsers.add_parser(
+        "workspace", help="Find the workspaces in a directory tree, or set one up."
+    )
+    workspace_subparsers = parser_workspace.add_subparsers(
+        dest="workspace_action", required=True
+    )
+    parser_workspace_detect = workspace_subparsers.add_parser(
+        "detect",
+        help="List the directories at or below ROOT that contain .mcp/edit_history.",
+    )
+    parser_workspace_detect.add_argument(
+        "root", nargs="?", help="Directory to search (default: the current directory)."
+    )
+    parser_workspace_detect.add_argument(
+        "--json",
+        action="store_true",
+        help="Print path, conversation_count and pending_count of each workspace.",
+    )
+    parser_workspace_init = workspace_subparsers.add_parser(
+        "init",
+        help="Create .mcp/edit_history in ROOT, and a .mcp/acl.toml that allows everything.",
+    )
+    parser_workspace_init.add_argument(
+        "root", nargs="?", help="Directory to set up (default: the current directory)."
+    )
+    parser_workspace.set_defaults(func=handle_workspace)
+
+    # upgrade-schema
+    parser_upgrade = subparsers.add_parser(
+        "upgrade-schema",
+        help="Rewrite log files in place at the current log entry schema version.",
+    )
+    parser_upgrade.add_argument(
+        "--dry-run",
+        action="store_true",
+        help="Only report which log files would be upgraded.",
+    )
+    parser_upgrade.set_defaults(func=handle_upgrade_schema)
+
+    # log-append (plumbing for the server and tests, not listed in the help)
+    parser_log_append = subparsers.add_parser("log-append")
+    parser_log_append.add_argument(
+        "--conv",
+        required=True,
+        help="Conversation ID (exact, not a prefix or tag), in the configured "
+        "namespace unless it names one.",
+    )
+    parser_log_append.add_argument(
+        "--json",
+        action="store_true",
+        help="Print the edit ID, tool call index and log file of each entry as JSON.",
+    )
+    parser_log_append.set_defaults(func=handle_log_append)
+
+    # config
+    parser_config = subparsers.add_parser(
+        "config", help="Get or set workspace settings (.mcp/config.json)."
+    )
+    config_subparsers = parser_config.add_subparsers(
+        dest="config_action", required=True
+    )
+    parser_config_set = config_subparsers.add_parser("set", help="Set a value.")
+    parser_config_set.add_argument("key", choices=sorted(config.CONFIG_KEYS))
+    parser_config_set.add_argument("value")
+    parser_config_get = config_subparsers.add_parser("get", help="Print a value.")
+    parser_config_get.add_argument("key", choices=sorted(config.CONFIG_KEYS))
+    parser_config_unset = config_subparsers.add_parser(
+        "unset", help="Reset a value to its default."
+    )
+    parser_config_unset.add_argument("key", choices=sorted(config.CONFIG_KEYS))
+    config_subparsers.add_parser("list", help="List all settings.")
+    parser_config.set_defaults(func=handle_config)
+
+    # ignore
+    parser_ignore = subparsers.add_parser(
+        "ignore", help="Manage the gitignore-style patterns in .mcp/ignore."
+    )
+    ignore_subparsers = parser_ignore.add_subparsers(
+        dest="ignore_action", required=True
+    )
+    parser_ignore_add = ignore_subparsers.add_parser(
+        "add",
+        help="Append a pattern; the server keeps no checkpoints or diffs of matching files.",
+    )
+    parser_ignore_add.add_argument("pattern", help="A gitignore-style pattern.")
+    parser_ignore.set_defaults(func=handle_ignore)
+
     # help
     parser_help = subparsers.add_parser(
         "help", aliases=["h"], help="Show help information."
@@ -1077,6 +5205,13 @@ Examples:
     # --- Parse Args and Setup ---
     args = parser.parse_args()
 
+    args.invocation = audit.Invocation(args.command, sys.argv[1:])
+
+    # Logged paths may contain undecodable bytes; never fail printing them
+    for stream in (sys.stdout, sys.stderr):
+        if hasattr(stream, "reconfigure"):
+            stream.reconfigure(errors="replace")
+
     # Setup logging level
     if args.verbose:
         utils.log.setLevel(utils.logging.DEBUG)
@@ -1094,36 +5229,53 @@ Examples:
     lock_timeout = args.timeout
     log.debug(f"Using lock timeout: {lock_timeout}s")
 
+    # The schema describes the format, workspace detect looks for workspaces
+    # and show --list-languages lists what Pygments knows: none works on the
+    # history of one workspace
+    if args.func in (handle_schema, handle_workspace) or getattr(
+        args, "list_languages", False
+    ):
+        try:
+            args.func(args, None, None, [])
+        except HistoryError as e:
+            print(f"{utils.COLOR_RED}Error: {e}{utils.COLOR_RESET}", file=sys.stderr)
+            if getattr(args, "json", False):
+                print(json.dumps({"error": e.to_dict()}))
+            sys.exit(e.exit_code)
+        return
+
     # --- Find Workspace ---
     try:
-        workspace_root = history.find_workspace_root(args.workspace)
-        if not workspace_root:
-            print(
-                f"{utils.COLOR_RED}Error: Could not find workspace root (.mcp/{HISTORY_DIR_NAME}) from '{args.workspace or os.getcwd()}'.{utils.COLOR_RESET}",
-                file=sys.stderr,
-            )
-            sys.exit(1)
+        try:
+            roots = history.find_workspace_and_history_roots(args.workspace)
+            workspace_root, history_root = roots
+        except WorkspaceNotFoundError as e:
+            print(f"{utils.COLOR_RED}Error: {e}{utils.COLOR_RESET}", file=sys.stderr)
+            if args.func is handle_info:
+                # Likely run in the wrong directory: say where to look instead
+                print(
+                    "The MCP server creates the history at its first edit in a workspace. "
+                    "To find the workspaces below this directory, run `mcpdiff workspace detect`.",
+                    file=sys.stderr,
+                )
+                if args.json:
+                    print(json.dumps({"error": e.to_dict()}))
+            sys.exit(e.exit_code)
 
         # Security check deferred to history functions where paths are used
 
-        history_root = workspace_root / ".mcp" / HISTORY_DIR_NAME
         log.debug(f"Using workspace root: {workspace_root}")
         log.debug(f"Using history root: {history_root}")
 
-        # Force cleanup if requested or running cleanup command
-        if args.force_cleanup or args.command == "cleanup":
-            if (
-                args.command != "cleanup"
-            ):  # Only log if forced, not if it's the main command
-                log.debug(
-                    "Performing pre-command cleanup of stale locks (--force-cleanup)."
-                )
+        # Force cleanup if requested; the cleanup command (under any of its
+        # names) does it itself
+        if args.force_cleanup and args.func is not handle_cleanup:
+            log.debug(
+                "Performing pre-command cleanup of stale locks (--force-cleanup)."
+            )
             cleaned = history.cleanup_stale_locks(history_root)
-            if cleaned > 0 and args.command != "cleanup":
+            if cleaned > 0:
                 log.info(f"Cleaned up {cleaned} stale lock(s) before main operation.")
-            if args.command == "cleanup":
-                # The handler will print the message, avoid double printing
-                pass
 
     except Exception as e:
         print(
@@ -1138,14 +5290,37 @@ Examples:
     exit_code = 0
     try:
         # Read all entries once, pass to handlers. Pass lock_timeout here.
-        # Skip reading if only doing cleanup or help.
-        if args.command not in ["cleanup", "help"]:
+        # Skip reading if only doing help.
+        if args.command not in [
+            "help",
+            "upgrade-schema",
+            "config",
+            "ignore",
+            "watch",
+            "audit",
+            "status",  # Read by handle_status, possibly from the daemon
+            "st",
+            "daemon",
+            "log-append",
+            "crash-recover",
+        ]:
             log.info("Reading edit history...")
             all_entries = history.find_all_entries(
                 history_root, lock_timeout=lock_timeout
             )
             log.info(f"Found {len(all_entries)} total history entries.")
 
+        # Conversation tags: label listings, and let --conv name a tag
+        tags = history.load_tags(history_root)
+        # Conversation IDs without a namespace are looked for in the configured one
+        if getattr(args, "conv", None):
+            if args.command in ("tag", "log-append"):
+                args.conv = utils.qualify_conversation_ref(args.conv, roots.namespace)
+            else:
+                args.conv = history.resolve_conversation_tag(
+                    args.conv, tags, roots.namespace
+                )
+
         # --- Execute Command ---
         # Pass workspace, history root, and the pre-read entries to the handler
         args.func(args, workspace_root, history_root, all_entries)
@@ -1153,9 +5328,13 @@ Examples:
     except KeyboardInterrupt:
         print("\nOperation cancelled by user.", file=sys.stderr)
         exit_code = 130
-    except (HistoryError, TimeoutError, AmbiguousIDError) as e:
+    except (HistoryError, TimeoutError) as e:
         print(f"{utils.COLOR_RED}Error: {e}{utils.COLOR_RESET}", file=sys.stderr)
-        exit_code = 1
+        if not isinstance(e, HistoryError):
+            e = HistoryError(str(e))
+        if getattr(args, "json", False):
+            print(json.dumps({"error": e.to_dict()}))
+        exit_code = e.exit_code
     except Exception as e:
         print(
             f"{utils.COLOR_RED}An unexpected error occurred. Use --verbose for detailed logs.{utils.COLOR_RESET}",
@@ -1167,6 +5346,9 @@ Examples:
         log.exception("Unexpected error during command execution:")
         exit_code = 2
 
+    # What this run counted goes to the daemon, which sums it up for /metrics
+    if not metrics.METRICS.is_empty():
+        daemon.report_metrics(workspace_root, metrics.METRICS)
     sys.exit(exit_code)
 
 
@@ -0,0 +1,150 @@
+# mcpdiff_acl.py
+
+import os
+import tomllib
+from pathlib import Path
+from typing import Any, Dict, Iterable, List, Optional
+
+from mcpdiff_utils import HistoryError, PermissionDeniedError, log
+
+# --- Access Control ---
+# <workspace>/.mcp/acl.toml says who may accept and reject the edits of each
+# conversation, for workspaces several users share (over NFS, in a container):
+#
+#   [default]
+#   can_accept = ["*"]
+#   can_reject = ["*"]
+#
+#   [conversation."<conv_id>"]
+#   owner = "alice"
+#   can_accept = ["alice", "bob"]
+#   can_reject = ["alice"]
+#
+# A conversation's own table decides for it, [default] for the others. "*"
+# allows every user, and a list that is left out allows everyone too; the owner
+# may always accept and reject. Without the file nothing is restricted.
+ACL_FILE_NAME = "acl.toml"
+ACL_ACTIONS = ("accept", "reject")
+# The user is named by the first of these that is set
+USER_ENV_VARS = ("MCP_USER", "USER")
+
+DEFAULT_ACL = """# Who may accept and reject the edits of each conversation; see the
+# "Access control" section of mcpdiff-reference.md.
+#
+# [conversation."<conv_id>"]
+# owner = "alice"
+# can_accept = ["alice", "bob"]
+# can_reject = ["alice"]
+
+[default]
+can_accept = ["*"]
+can_reject = ["*"]
+"""
+
+
+def get_acl_path(workspace_root: Path) -> Path:
+    return workspace_root / ".mcp" / ACL_FILE_NAME
+
+
+def current_user() -> Optional[str]:
+    """The user mcpdiff acts for: $MCP_USER, else $USER; None if neither is set."""
+    for name in USER_ENV_VARS:
+        if os.environ.get(name):
+            return os.environ[name]
+    return None
+
+
+def _check_rules(rules: Any, where: str) -> None:
+    """Raises HistoryError if a [default] or conversation table is malformed."""
+    if not isinstance(rules, dict):
+        raise HistoryError(f"{where} must be a table")
+    owner = rules.get("owner")
+    if owner is not None and not isinstance(owner, str):
+        raise HistoryError(f"{where}: owner must be a string")
+    for action in ACL_ACTIONS:
+        users = rules.get(f"can_{action}")
+        if users is not None and not (
+            isinstance(users, list) and all(isinstance(u, str) for u in users)
+        ):
+            raise HistoryError(f"{where}: can_{action} must be a list of user names")
+
+
+def load_acl(workspace_root: Path) -> Optional[Dict[str, Any]]:
+    """
+    The parsed ACL file of the workspace, or None if there is none. Raises
+    HistoryError if it is not valid TOML or its tables are malformed, so that a
+    typo never lifts the restrictions.
+    """
+    path = get_acl_path(workspace_root)
+    if not path.is_file():
+        return None
+    try:
+        with open(path, "rb") as f:
+            acl = tomllib.load(f)
+    except (OSError, tomllib.TOMLDecodeError) as e:
+        raise HistoryError(f"Cannot read {path}: {e}")
+    try:
+        if "default" in acl:
+            _check_rules(acl["default"], "[default]")
+        conversations = acl.get("conversation", {})
+        if not isinstance(conversations, dict):
+            raise HistoryError("[conversation] must be a table")
+        for conv_id, rules in conversations.items():
+            _check_rules(rules, f'[conversation."{conv_id}"]')
+    except HistoryError as e:
+        raise HistoryError(f"Invalid {path}: {e}")
+    log.debug(f"Loaded ACL with {len(conversations)} conversation(s) from {path}")
+    return acl
+
+
+def is_allowed(
+    acl: Dict[str, Any], action: str, conv_id: str, user: Optional[str]
+) -> bool:
+    """Whether `user` may `action` ('accept' or 'reject') the conversation's edits."""
+    rules = acl.get("conversation", {}).get(conv_id, acl.get("default", {}))
+    if user is not None and rules.get("owner") == user:
+        return True
+    users: Optional[List[str]] = rules.get(f"can_{action}")
+    if users is None or "*" in users:
+        return True
+    return user is not None and user in users
+
+
+def check_permission(
+    acl: Optional[Dict[str, Any]],
+    action: str,
+    conv_ids: Iterable[Optional[str]],
+    user: Optional[str],
+) -> None:
+    """
+    Raises PermissionDeniedError for the first conversation whose edits `user`
+    may not `action`; nothing is checked without an ACL.
+    """
+    if acl is None:
+        return
+    for conv_id in sorted({conv_id for conv_id in conv_ids if conv_id}):
+        if not is_allowed(acl, action, conv_id, user):
+            who = (
+                f"User {user}"
+                if user
+                else "An unnamed user ($MCP_USER and $USER are unset)"
+            )
+            raise PermissionDeniedError(
+                f"Permission denied: {who} may not {action} edits of conversation {conv_id} (see .mcp/{ACL_FILE_NAME}).",
+                user=user,
+                action=action,
+                conversation_id=conv_id,
+            )
+
+
+def write_default_acl(workspace_root: Path) -> bool:
+    """
+    Writes a permissive ACL file unless the workspace has one. Returns whether
+    it was written.
+    """
+    path = get_acl_path(workspace_root)
+    if path.exists():
+        return False
+    path.parent.mkdir(parents=True, exist_ok=True)
+    path.write_text(DEFAULT_ACL, encoding="utf-8")
+    return True
@@ -0,0 +1,482 @@
+# mcpdiff_api.py
+
+"""
+Public API for tools that read or change the edit history without going
+through the mcpdiff command line (IDE integrations, a TUI, scripts):
+
+    sys.path.insert(0, "<repo>/filesystem/cli")
+    import mcpdiff_api as api
+
+    workspace_root, history_root = api.find_workspace_and_history_roots()
+    for entry in api.read_conversation(history_root, conv_id):
+        print(entry.edit_id, entry.status, entry.file_path)
+
+Everything else in the mcpdiff_* modules is an implementation detail of the
+CLI and may change without notice. The functions here keep their signatures
+and behave exactly like the corresponding mcpdiff commands.
+"""
+
+import contextlib
+from dataclasses import dataclass, field
+from enum import Enum
+from pathlib import Path
+from typing import Any, Dict, Iterable, List, Optional, Union
+
+import mcpdiff_history as history
+import mcpdiff_patch as patch
+import mcpdiff_utils as utils
+from mcpdiff_history import WorkspaceRoots
+from mcpdiff_patch import ConflictRegion
+from mcpdiff_utils import (
+    AmbiguousIDError,
+    ConversationLock,
+    CorruptLogError,
+    ExternalModificationError,
+    FileLock,
+    HistoryError,
+    InvalidLogEntryError,
+    LockTimeoutError,
+    MissingCheckpointError,
+    MissingDiffError,
+    PatchConflictError,
+    PermissionDeniedError,
+    VerificationError,
+    WorkspaceNotFoundError,
+)
+
+__all__ = [
+    "AmbiguousIDError",
+    "ConflictRegion",
+    "ConversationLock",
+    "ConversationSummary",
+    "CorruptLogError",
+    "ExternalModificationError",
+    "FileLock",
+    "HistoryError",
+    "InvalidLogEntryError",
+    "LockTimeoutError",
+    "LogEntry",
+    "MissingCheckpointError",
+    "MissingDiffError",
+    "Operation",
+    "PatchConflictError",
+    "PermissionDeniedError",
+    "Status",
+    "VerificationError",
+    "WorkspaceNotFoundError",
+    "WorkspaceRoots",
+    "acquire_lock",
+    "acquire_lock_sync",
+    "calculate_hash",
+    "detect_conflicts",
+    "find_history_root",
+    "find_workspace_and_history_roots",
+    "read_all_entries",
+    "read_conversation",
+    "read_log_file",
+    "reapply_conversation_state",
+    "reapply_file_state",
+    "summarize_conversations",
+    "write_log_file",
+]
+
+
+class Status(str, Enum):
+    """Review status of a log entry. Entries written by mcpdiff use DONE/FAILED."""
+
+    PENDING = "pending"
+    ACCEPTED = "accepted"
+    REJECTED = "rejected"
+    DONE = "done"
+    FAILED = "failed"
+
+
+class Operation(str, Enum):
+    """Filesystem change recorded by a log entry (SNAPSHOT/REVERT by mcpdiff)."""
+
+    CREATE = "create"
+    REPLACE = "replace"
+    EDIT = "edit"
+    APPEND = "append"
+    PARTIAL = "partial"
+    DELETE = "delete"
+    MOVE = "move"
+    RENAME = "rename"
+    SNAPSHOT = "snapshot"
+    REVERT = "revert"
+
+
+def _enum_or_raw(enum_type, value: Any) -> Any:
+    """The enum member for `value`, or `value` itself if it is not a known one."""
+    try:
+        return enum_type(value)
+    except ValueError:
+        return value
+
+
+@dataclass
+class LogEntry:
+    """
+    One log entry (see DIFFSPEC.md §4), upgraded to the current schema and with
+    workspace-relative paths. `status` and `operation` are Status/Operation
+    members, or the raw string for values this version does not know. Fields
+    not listed here, such as `ide_context` or `log_file_source`, are kept in
+    `extra` so that to_dict() returns everything that was read.
+    """
+
+    edit_id: str
+    conversation_id: str
+    tool_call_index: int
+    timestamp: str
+    operation: Union[Operation, str]
+    file_path: Optional[str]
+    status: Union[Status, str]
+    source_path: Optional[str] = None
+    tool_name: Optional[str] = None
+    parent_edit_id: Optional[str] = None
+    diff_file: Optional[str] = None
+    checkpoint_file: Optional[str] = None
+    hash_before: Optional[str] = None
+    hash_after: Optional[str] = None
+    schema_version: Optional[int] = None
+    workspace_root_hash: Optional[str] = None
+    session_id: Optional[str] = None
+    parent_conversation_id: Optional[str] = None
+    forked_at: Optional[str] = None
+    byte_range_start: Optional[int] = None
+    byte_range_end: Optional[int] = None
+    byte_size_before: Optional[int] = None
+    byte_size_after: Optional[int] = None
+    conflict_markers: Optional[List[Dict[str, int]]] = None
+    ignored: Optional[bool] = None
+    review_notes: Optional[str] = None
+    file_encoding: Optional[str] = None
+    extra: Dict[str, Any] = field(default_factory=dict)
+
+    _REQUIRED_FIELDS = (
+        "edit_id",
+        "conversation_id",
+        "tool_call_index",
+        "timestamp",
+        "operation",
+        "file_path",
+        "status",
+    )
+    _OPTIONAL_FIELDS = (
+        "source_path",
+        "tool_name",
+        "parent_edit_id",
+        "diff_file",
+        "checkpoint_file",
+        "hash_before",
+        "hash_after",
+        "schema_version",
+        "workspace_root_hash",
+        "session_id",
+        "parent_conversation_id",
+        "forked_at",
+        "byte_range_start",
+        "byte_range_end",
+        "byte_size_before",
+        "byte_size_after",
+        "conflict_markers",
+        "ignored",
+        "review_notes",
+        "file_encoding",
+    )
+    _FIELDS = _REQUIRED_FIELDS + _OPTIONAL_FIELDS
+
+    @classmethod
+    def from_dict(cls, entry: Dict[str, Any]) -> "LogEntry":
+        """Builds a LogEntry from an entry dict as read from a log."""
+        values = {name: entry.get(name) for name in cls._FIELDS}
+        values["operation"] = _enum_or_raw(Operation, values["operation"] or "")
+        values["status"] = _enum_or_raw(Status, values["status"] or "")
+        extra = {k: v for k, v in entry.items() if k not in cls._FIELDS}
+        return cls(**values, extra=extra)
+
+    def to_dict(self) -> Dict[str, Any]:
+        """
+        The entry as a dict, with enum members as their string values. Optional
+        fields that are None are left out, as older log entries do.
+        """
+        result = {name: getattr(self, name) for name in self._REQUIRED_FIELDS}
+        for name in self._OPTIONAL_FIELDS:
+            if getattr(self, name) is not None:
+                result[name] = getattr(self, name)
+        for name in ("operation", "status"):
+            if isinstance(result[name], Enum):
+                result[name] = result[name].value
+        result.update(self.extra)
+        return result
+
+    @property
+    def is_edit(self) -> bool:
+        """False for the snapshot and revert entries mcpdiff writes itself."""
+        return self.tool_name != "mcpdiff"
+
+
+@dataclass
+class ConversationSummary:
+    """Edit counts and activity of one conversation, as listed by `conversations`."""
+
+    conversation_id: str
+    edits: int
+    pending: int
+    accepted: int
+    rejected: int
+    files: int
+    first_activity: Optional[float]  # Epoch seconds
+    last_activity: Optional[float]
+    parent_conversation_id: Optional[str] = None  # Set for forked conversations
+    forked_at: Optional[str] = None
+    # The <namespace>/ of conversation_id, "default" if it has none
+    namespace: str = utils.DEFAULT_NAMESPACE
+
+
+def find_workspace_and_history_roots(
+    start_path: Optional[str] = None,
+) -> WorkspaceRoots:
+    """
+    The root of the workspace containing `start_path` (default: the current
+    directory) and its .mcp/edit_history directory, found as mcpdiff finds
+    them. Raises WorkspaceNotFoundError if no parent directory has a history.
+    The result unpacks as (workspace_root, history_root); its `namespace` is
+    the configured conversation namespace, or "default".
+    """
+    return history.find_workspace_and_history_roots(start_path)
+
+
+def find_history_root(start_path: Optional[str] = None) -> Optional[Path]:
+    """
+    The .mcp/edit_history directory of the workspace containing `start_path`
+    (default: the current directory), or None if there is none.
+    """
+    try:
+        return find_workspace_and_history_roots(start_path)[1]
+    except WorkspaceNotFoundError:
+        return None
+
+
+def read_log_file(
+    log_file_path: Path, lock_timeout: Optional[float] = None
+) -> List[LogEntry]:
+    """
+    The entries of one log file (one conversation shard) in file order, under
+    the log's file lock. Paths are as logged, not made workspace-relative. A
+    missing file has no entries; raises CorruptLogError for a line that is not
+    valid JSON.
+    """
+    entries = utils.read_log_file(log_file_path, lock_timeout=lock_timeout, strict=True)
+    return [LogEntry.from_dict(e) for e in entries]
+
+
+def write_log_file(
+    log_file_path: Path,
+    entries: Iterable[Union[LogEntry, Dict[str, Any]]],
+    lock_timeout: Optional[float] = None,
+) -> None:
+    """
+    Replaces a log file with `entries`, sorted by timestamp and
+    tool_call_index, under the log's file lock, and updates its Merkle root.
+    The file is written to a temporary file first and renamed, so readers
+    never see a partial log. Hold the conversation's lock (acquire_lock_sync())
+    around reading, changing and writing back a log the server may append to.
+    """
+    dicts = [e.to_dict() if isinstance(e, LogEntry) else dict(e) for e in entries]
+    for entry in dicts:
+        # Set when reading, to know the shard; not part of the entry
+        entry.pop("log_file_source", None)
+    utils.write_log_file(log_file_path, dicts, lock_timeout=lock_timeout)
+
+
+def read_all_entries(
+    history_root: Path, lock_timeout: Optional[float] = None
+) -> List[LogEntry]:
+    """All entries of all conversations, oldest first."""
+    return [
+        LogEntry.from_dict(e)
+        for e in history.find_all_entries(history_root, lock_timeout=lock_timeout)
+    ]
+
+
+def read_conversation(
+    history_root: Path, conv_id: str, lock_timeout: Optional[float] = None
+) -> List[LogEntry]:
+    """
+    The entries of one conversation, from all of its log shards, oldest first.
+    `conv_id` must be the full ID; raises HistoryError if there is no log.
+    """
+    if not utils.get_conversation_log_files(history_root, conv_id):
+        raise HistoryError(f"No log found for conversation {conv_id}")
+    entries = utils.read_all_shards(history_root, conv_id, lock_timeout=lock_timeout)
+    history.normalize_entry_paths(entries, history_root.parent.parent)
+    entries.sort(
+        key=lambda e: (
+            utils.parse_timestamp(e.get("timestamp", 0)),
+            e.get("tool_call_index", float("inf")),
+        )
+    )
+    return [LogEntry.from_dict(e) for e in entries]
+
+
+def summarize_conversations(
+    history_root: Path, entries: Optional[List[Dict[str, Any]]] = None
+) -> List[ConversationSummary]:
+    """
+    One summary per conversation, most recently active first. `entries` are
+    entry dicts already read with the CLI's find_all_entries; by default all
+    logs under `history_root` are read.
+    """
+    if entries is None:
+        entries = history.find_all_entries(history_root)
+    return [
+        ConversationSummary(**summary)
+        for summary in history.summarize_conversations(entries)
+    ]
+
+
+def reapply_conversation_state(
+    workspace_root: Path,
+    history_root: Path,
+    conv_id: str,
+    file_path: Optional[str] = None,
+    apply_only_accepted: bool = False,
+    lineage: bool = False,
+    verify: bool = True,
+) -> Dict[str, Dict[str, Any]]:
+    """
+    Rewrites each file the conversation edited (or only `file_path`) with
+    reapply_file_state(), replaying the edits of all conversations, as `accept`
+    and `reject` do. With `lineage`, only the conversation and the ones it was
+    forked from are replayed, each ancestor up to its fork point: the state the
+    fork's edits (and checkpoints) were made on. Returns {file_path: {"hash",
+    "error"}}; for a file that failed, "exception" holds the error, e.g. a
+    PatchConflictError. Before each diff is applied to a file that no longer
+    hashes to the edit's hash_before, detect_conflicts() looks for the hunks
+    that will not apply; the error then names their lines, and its
+    `conflicts` holds them as ConflictRegion.to_dict() dicts. A file whose
+    oldest usable checkpoint or a diff after it is missing (checkpoints pruned
+    to the history_depth setting, say) is left alone with a
+    MissingCheckpointError or MissingDiffError. Files with edits logged while
+    they were listed in .mcp/ignore, which the server keeps no checkpoints or
+    diffs of, are left as they are too, without checking their hashes: their
+    result is the hash they have now, with "ignored" set (see
+    execute_replay_plan()). `verify` is as for reapply_file_state().
+    """
+    all_entries = history.find_all_entries(history_root)
+    files = sorted(
+        {
+            e["file_path"]
+            for e in all_entries
+            if e.get("conversation_id") == conv_id
+            and e.get("tool_name") != "mcpdiff"
+            and e.get("file_path")
+        }
+    )
+    if file_path is not None:
+        files = [f for f in files if f == file_path]
+    replayed = history.lineage_entries(all_entries, conv_id) if lineage else all_entries
+    results: Dict[str, Dict[str, Any]] = {}
+    for file_rel in files:
+        plan = history.plan_file_replay(
+            file_rel, replayed, history_root, apply_only_accepted
+        )
+        error = history.replay_plan_error(plan)
+        if error is not None:
+            results[file_rel] = {"hash": None, "error": str(error), "exception": error}
+            continue
+        results[file_rel] = reapply_file_state(
+            workspace_root,
+            history_root,
+            file_rel,
+            apply_only_accepted=apply_only_accepted,
+            lineage_of=conv_id if lineage else None,
+            verify=verify,
+        )
+    return results
+
+
+def reapply_file_state(
+    workspace_root: Path,
+    history_root: Path,
+    file_path: str,
+    conv_ids: Optional[Iterable[str]] = None,
+    apply_only_accepted: bool = False,
+    lineage_of: Optional[str] = None,
+    verify: bool = True,
+) -> Dict[str, Any]:
+    """
+    Rewrites one file from the entries for it in the logs of `conv_ids` (by
+    default all conversations), merged in timestamp and tool_call_index order:
+    from the newest checkpoint no skipped edit precedes, the edits that are
+    accepted, or accepted and pending, are applied. Leaving out a conversation
+    that edited the file leaves out its edits. `lineage_of` replays instead a
+    conversation and the ancestors it was forked from, each up to its fork
+    point, following the parent chain. Holds the locks of the conversations
+    replayed meanwhile. A file re-applied exactly must hash to the hash_after of
+    its last edit unless `verify` is off, as with --no-verify. Returns {"hash",
+    "error"}, plus "exception" on failure.
+    """
+    wanted = set(conv_ids) if conv_ids is not None else None
+
+    def read_entries() -> List[Dict[str, Any]]:
+        entries = history.find_all_entries(history_root)
+        if lineage_of is not None:
+            return history.lineage_entries(entries, lineage_of)
+        if wanted is None:
+            return entries
+        return [e for e in entries if e.get("conversation_id") in wanted]
+
+    touching = history.find_conversations_for_files(read_entries(), [file_path])
+    with contextlib.ExitStack() as locks:
+        for touching_conv in touching:
+            locks.enter_context(acquire_lock_sync(history_root, touching_conv))
+        return history.reconstruct_file_from_history(
+            file_path,
+            read_entries(),
+            workspace_root,
+            history_root,
+            apply_only_accepted=apply_only_accepted,
+            options=history.ReplayOptions(verify=verify),
+        )
+
+
+def detect_conflicts(
+    content: str, diff_content: str, reverse: bool = False
+) -> List[ConflictRegion]:
+    """
+    The hunks of a unified diff (as stored for an edit) that would not apply to
+    `content`, with the lines where each expected its context and the first
+    line that differs. Returns [] if the diff applies, also at an offset.
+    """
+    return patch.detect_conflicts(content, diff_content, reverse=reverse)
+
+
+async def acquire_lock(
+    history_root: Path, conv_id: str, timeout: Optional[float] = None
+) -> ConversationLock:
+    """
+    Takes the conversation lock the server holds while it logs an edit, and
+    returns it held, without blocking the event loop while it waits. Release it
+    with release(), or use it in a with statement. Raises LockTimeoutError (a
+    TimeoutError) if it is not free within `timeout` seconds; it can also be
+    bounded with asyncio.wait_for(), and is not held if that cancels it.
+    """
+    lock = ConversationLock(history_root, conv_id)
+    await lock.acquire_async(timeout)
+    return lock
+
+
+def acquire_lock_sync(
+    history_root: Path, conv_id: str, timeout: Optional[float] = None
+) -> ConversationLock:
+    """acquire_lock() for code that is not async: blocks while it waits."""
+    lock = ConversationLock(history_root, conv_id)
+    lock.acquire(timeout)
+    return lock
+
+
+def calculate_hash(file_path: Union[str, Path]) -> Optional[str]:
+    """SHA-256 of a file's content as recorded in hash_before/hash_after, or None."""
+    return utils.calculate_hash(str(file_path))
@@ -0,0 +1,317 @@
+# mcpdiff_append.py
+
+import json
+import re
+from datetime import datetime, timezone
+from pathlib import Path
+from typing import Any, Dict, List, Optional, Tuple
+
+import mcpdiff_api as api
+import mcpdiff_config as config
+import mcpdiff_utils as utils
+from mcpdiff_migration import CURRENT_SCHEMA_VERSION, default_migrator
+from mcpdiff_patch import BASE64_ENCODING, BSDIFF_ENCODING
+from mcpdiff_utils import LOGS_DIR, HistoryError, InvalidLogEntryError, log
+
+# --- Appending Log Entries (mcpdiff log-append) ---
+# The one writer that checks entries against DIFFSPEC.md §4 before they reach a
+# log, so that processes other than the server (and tests) cannot drift from it.
+
+# Types each known field may have; None means the field may be null
+_FIELD_TYPES: Dict[str, tuple] = {
+    "edit_id": (str,),
+    "conversation_id": (str,),
+    "tool_call_index": (int,),
+    "timestamp": (str,),
+    "operation": (str,),
+    "file_path": (str,),
+    "status": (str,),
+    "source_path": (str, None),
+    "tool_name": (str, None),
+    "parent_edit_id": (str, None),
+    "diff_file": (str, None),
+    "checkpoint_file": (str, None),
+    "hash_before": (str, None),
+    "hash_after": (str, None),
+    "schema_version": (int,),
+    "workspace_root_hash": (str, None),
+    "session_id": (str, None),
+    "parent_conversation_id": (str, None),
+    "forked_at": (str, None),
+    "byte_range_start": (int, None),
+    "byte_range_end": (int, None),
+    "byte_size_before": (int, None),
+    "byte_size_after": (int, None),
+    "conflict_markers": (list, None),
+    "ignored": (bool,),
+    "file_path_bytes": (str,),
+    "source_path_bytes": (str,),
+    "ide_context": (dict,),
+    "archived": (bool,),
+    "review_notes": (str,),
+    "file_encoding": (str,),
+    "split_into": (list,),
+}
+_REQUIRED_FIELDS = ("edit_id", "timestamp", "operation", "file_path", "status")
+# Written as null when missing, as the server writes every one of them
+_NULLABLE_FIELDS = [k for k, types in _FIELD_TYPES.items() if None in types]
+
+OPERATIONS = [op.value for op in api.Operation]
+STATUSES = [status.value for status in api.Status]
+# How the diff file is stored: as is (the default), base64-encoded or a bsdiff patch
+FILE_ENCODINGS = ["text", BASE64_ENCODING, BSDIFF_ENCODING]
+
+# The server's "%Y-%m-%dT%H%M%S.%fZ" and ISO 8601 with colons
+_TIMESTAMP_RE = re.compile(
+    r"^\d{4}-\d{2}-\d{2}T\d{2}:?\d{2}:?\d{2}(\.\d+)?(Z|[+-]\d{2}:?\d{2})$"
+)
+
+
+def parse_entries(text: str) -> List[Any]:
+    """
+    Reads the values given to log-append: JSON objects one per line (or simply
+    one after the other), or a JSON array of them.
+    """
+    decoder = json.JSONDecoder()
+    values: List[Any] = []
+    pos = 0
+    while True:
+        while pos < len(text) and text[pos].isspace():
+            pos += 1
+        if pos == len(text):
+            return values
+        try:
+            value, pos = decoder.raw_decode(text, pos)
+        except json.JSONDecodeError as e:
+            raise InvalidLogEntryError(
+                f"Input is not valid JSON: {e.msg} (line {e.lineno}, column {e.colno})",
+                line=e.lineno,
+            ) from e
+        values.extend(value if isinstance(value, list) else [value])
+
+
+def _invalid(
+    index: int, field: Optional[str], message: str
+) -> InvalidLogEntryError:
+    pointer = f"/{index}/{field}" if field else f"/{index}"
+    return InvalidLogEntryError(
+        f"Invalid log entry at {pointer}: {message}",
+        pointer=pointer,
+        entry=index,
+        field=field,
+    )
+
+
+def _type_names(types: tuple) -> str:
+    names = {str: "string", int: "integer", dict: "object", bool: "boolean"}
+    return " or ".join("null" if t is None else names[t] for t in types)
+
+
+def validate_entry(entry: Any, index: int, conv_id: str) -> Dict[str, Any]:
+    """
+    Checks one entry for log-append and returns it completed: conversation_id
+    and schema_version filled in, entries of older schemas upgraded, missing
+    nullable fields set to null and a missing file_encoding set to "text".
+    `tool_call_index` may still be missing.
+    Raises InvalidLogEntryError naming the first offending field.
+    """
+    if not isinstance(entry, dict):
+        raise _invalid(index, None, "expected a JSON object")
+    entry = dict(entry)
+    entry.setdefault("conversation_id", conv_id)
+    version = entry.setdefault("schema_version", CURRENT_SCHEMA_VERSION)
+    if isinstance(version, int) and not isinstance(version, bool):
+        if version > CURRENT_SCHEMA_VERSION:
+            raise _invalid(
+                index,
+                "schema_version",
+                f"{version} is newer than the supported version {CURRENT_SCHEMA_VERSION}",
+            )
+        entry = default_migrator.upgrade(entry)
+
+    for field in _REQUIRED_FIELDS:
+        if field not in entry:
+            raise _invalid(index, field, "required field is missing")
+    for field, types in _FIELD_TYPES.items():
+        if field not in entry:
+            continue
+        value = entry[field]
+        if value is None:
+            valid = None in types
+        else:
+            # bool is an int to Python, but not to JSON Schema
+            valid = isinstance(value, tuple(t for t in types if t is not None)) and (
+                not isinstance(value, bool) or bool in types
+            )
+        if not valid:
+            raise _invalid(
+                index, field, f"expected {_type_names(types)}, got {json.dumps(value)}"
+            )
+
+    if entry["conversation_id"] != conv_id:
+        raise _invalid(
+            index,
+            "conversation_id",
+            f"'{entry['conversation_id']}' does not match --conv '{conv_id}'",
+        )
+    if not entry["edit_id"]:
+        raise _invalid(index, "edit_id", "must not be empty")
+    if entry["operation"] not in OPERATIONS:
+        raise _invalid(
+            index,
+            "operation",
+            f"'{entry['operation']}' is not one of {', '.join(OPERATIONS)}",
+        )
+    if entry["status"] not in STATUSES:
+        raise _invalid(
+            index, "status", f"'{entry['status']}' is not one of {', '.join(STATUSES)}"
+        )
+    if entry.get("file_encoding", FILE_ENCODINGS[0]) not in FILE_ENCODINGS:
+        raise _invalid(
+            index,
+            "file_encoding",
+            f"'{entry['file_encoding']}' is not one of {', '.join(FILE_ENCODINGS)}",
+        )
+    if entry["operation"] in utils.MOVE_OPERATIONS and not entry.get("source_path"):
+        raise _invalid(index, "source_path", f"required for a {entry['operation']}")
+    if entry["operation"] == "partial":
+        for field in ("byte_range_start", "byte_range_end"):
+            if entry.get(field) is None:
+                raise _invalid(index, field, "required for a partial edit")
+        if not 0 <= entry["byte_range_start"] <= entry["byte_range_end"]:
+            raise _invalid(
+                index, "byte_range_end", "must not be before byte_range_start"
+            )
+    for block in entry.get("conflict_markers") or []:
+        start = block.get("start_line") if isinstance(block, dict) else None
+        end = block.get("end_line") if isinstance(block, dict) else None
+        if not (type(start) is int and type(end) is int and 1 <= start <= end):
+            raise _invalid(index, "conflict_markers", f"{block!r} is not a line range")
+    if not _TIMESTAMP_RE.match(entry["timestamp"]):
+        raise _invalid(
+            index, "timestamp", f"'{entry['timestamp']}' is not an ISO 8601 UTC time"
+        )
+    for field in ("diff_file", "checkpoint_file"):
+        path = entry.get(field)
+        if path and (Path(path).is_absolute() or ".." in Path(path).parts):
+            raise _invalid(
+                index, field, f"'{path}' is not a path relative to the history root"
+            )
+    for field in _NULLABLE_FIELDS:
+        entry.setdefault(field, None)
+    entry.setdefault("file_encoding", FILE_ENCODINGS[0])
+    return entry
+
+
+def get_log_file_path(
+    history_root: Path, conv_id: str, now: Optional[datetime] = None
+) -> Path:
+    """The log new entries of a conversation go to, as in the server."""
+    workspace_root = history_root.parent.parent
+    interval = config.get_config_value(workspace_root, "log_shard_interval")
+    suffix = utils.history_format.get_log_shard_suffix(
+        interval, now or datetime.now(timezone.utc)
+    )
+    name = f"{conv_id}_{suffix}.log" if suffix else f"{conv_id}.log"
+    return history_root / LOGS_DIR / name
+
+
+def append_log_entries(
+    history_root: Path,
+    conv_id: str,
+    entries: List[Any],
+    lock_timeout: Optional[float] = None,
+) -> List[Dict[str, Any]]:
+    """
+    Validates `entries` and appends them to the conversation's current log as
+    one write, under the conversation lock the server also takes. Entries
+    without a tool_call_index get the next ones after the conversation's
+    highest. Nothing is written if any entry is invalid. Returns the entries as
+    written.
+    """
+    if not utils.is_valid_conversation_id(conv_id):
+        raise HistoryError(f"Invalid conversation ID: '{conv_id}'")
+    validated = [validate_entry(e, i, conv_id) for i, e in enumerate(entries)]
+    if not validated:
+        return []
+
+    log_file_path = get_log_file_path(history_root, conv_id)
+    log_file_path.parent.mkdir(parents=True, exist_ok=True)
+    conv_lock = utils.ConversationLock(history_root, conv_id)
+    conv_lock.acquire(lock_timeout)
+    with conv_lock:
+        existing = utils.read_all_shards(history_root, conv_id, lock_timeout)
+        seen_ids = {e.get("edit_id") for e in existing}
+        next_index = 1 + max(
+            (
+                e["tool_call_index"]
+                for e in existing
+                if isinstance(e.get("tool_call_index"), int)
+            ),
+            default=-1,
+        )
+        next_index = max(next_index, 0)
+        for index, entry in enumerate(validated):
+            if entry["edit_id"] in seen_ids:
+                raise _invalid(
+                    index,
+                    "edit_id",
+                    f"'{entry['edit_id']}' is already in conversation {conv_id}",
+                )
+            seen_ids.add(entry["edit_id"])
+            if "tool_call_index" not in entry:
+                entry["tool_call_index"] = next_index
+            if entry["tool_call_index"] >= next_index:
+                next_index = entry["tool_call_index"] + 1
+        # Known fields in the order of DIFFSPEC.md, as the server writes them
+        validated = [
+            dict({k: e[k] for k in _FIELD_TYPES if k in e}, **e) for e in validated
+        ]
+
+        log_entries = utils.read_log_file(
+            log_file_path, lock_timeout=lock_timeout, migrate=False
+        )
+        log_entries.extend(validated)
+        utils.write_log_file(log_file_path, log_entries, lock_timeout=lock_timeout)
+    log.debug(f"Appended {len(validated)} entries to {log_file_path.name}")
+    for entry in validated:
+        entry["log_file_source"] = utils.get_log_file_source(log_file_path)
+    return validated
+
+
+# --- Formatting Log Files (mcpdiff format-log) ---
+
+
+def format_log_files(
+    history_root: Path, conv_id: str
+) -> Tuple[str, List[Tuple[str, int, str]]]:
+    """
+    Pretty-prints the logs of a conversation for reading: each entry as indented
+    JSON, as stored, followed by a newline. Entries are checked as log-append
+    checks them; returns the text and the problems found as (log file name,
+    1-based line, message). Lines that are not JSON are left out of the text.
+    The text is not a log: read_log_file() expects one entry per line.
+    """
+    blocks: List[str] = []
+    problems: List[Tuple[str, int, str]] = []
+    for log_file_path in utils.get_conversation_log_files(history_root, conv_id):
+        with open(log_file_path, "r", encoding="utf-8") as f:
+            lines = f.readlines()
+        for line_number, line in enumerate(lines, 1):
+            line = line.strip()
+            if not line:
+                continue
+            try:
+                entry = json.loads(line)
+            except json.JSONDecodeError as e:
+                problems.append((log_file_path.name, line_number, f"not JSON: {e}"))
+                continue
+            blocks.append(json.dumps(entry, indent=2, ensure_ascii=False) + "\n")
+            try:
+                validate_entry(entry, line_number - 1, conv_id)
+            except InvalidLogEntryError as e:
+                message = str(e).split(": ", 1)[-1]
+                if getattr(e, "field", None):
+                    message = f"{e.field}: {message}"
+                problems.append((log_file_path.name, line_number, message))
+    return "\n".join(blocks), problems
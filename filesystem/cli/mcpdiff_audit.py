@@ -0,0 +1,116 @@
+# mcpdiff_audit.py
+
+import getpass
+import json
+import os
+import shutil
+from dataclasses import dataclass, field
+from datetime import datetime, timezone
+from pathlib import Path
+from typing import Any, Dict, List, Optional
+
+from mcpdiff_utils import AdvisoryLock, log, parse_timestamp
+
+# --- Audit Log ---
+# One JSON object per line in <history_root>/audit.log for every status change,
+# file re-apply and reattach made by mcpdiff, so that it can be established
+# later who accepted or rejected what. Lines are only ever appended.
+AUDIT_LOG_NAME = "audit.log"
+
+
+@dataclass(frozen=True)
+class Invocation:
+    """The mcpdiff command, and its arguments, that an audit record is made for."""
+
+    command: Optional[str] = None
+    argv: List[str] = field(default_factory=list)
+
+
+def get_audit_log_path(history_root: Path) -> Path:
+    return history_root / AUDIT_LOG_NAME
+
+
+def _current_user() -> str:
+    try:
+        return getpass.getuser()
+    except (KeyError, OSError):  # No passwd entry or login name (containers)
+        return str(os.getuid()) if hasattr(os, "getuid") else "unknown"
+
+
+def record(
+    history_root: Path,
+    action: str,
+    edit_ids: List[str],
+    outcome: str,
+    invocation: Optional[Invocation] = None,
+    lock_timeout: Optional[float] = None,
+    **details: Any,
+) -> Optional[Dict[str, Any]]:
+    """
+    Appends an audit record and returns it. `action` is the new status,
+    "reapply", "reattach", "rebaseline", "promote", "split", "squash",
+    "snapshot" or "restore-snapshot"; `outcome` is "success", "partial" or
+    "failure". `invocation` is stored as the record's command and argv, which
+    are empty for changes not made from the command line (the daemon, the
+    API). Extra keyword arguments (file_path, error, ...) are stored as
+    given. A failure to write only warns, since the action itself has already
+    happened.
+    """
+    invocation = invocation or Invocation()
+    entry = {
+        "timestamp": datetime.now(timezone.utc)
+        .isoformat(timespec="milliseconds")
+        .replace("+00:00", "Z"),
+        "user": _current_user(),
+        "command": invocation.command,
+        "argv": list(invocation.argv),
+        "action": action,
+        "edit_ids": edit_ids,
+        "outcome": outcome,
+    }
+    entry.update(details)
+
+    audit_path = get_audit_log_path(history_root)
+    temp_path = audit_path.with_suffix(audit_path.suffix + ".tmp" + str(os.getpid()))
+    try:
+        # Same discipline as the conversation logs: copy, append, rename, locked
+        with AdvisoryLock(audit_path.with_name(AUDIT_LOG_NAME + ".lock")):
+            if audit_path.is_file():
+                shutil.copyfile(audit_path, temp_path)
+            with open(temp_path, "a", encoding="utf-8") as f:
+                json.dump(entry, f, separators=(",", ":"))
+                f.write("\n")
+            os.replace(temp_path, audit_path)
+        return entry
+    except (IOError, TimeoutError) as e:
+        log.warning(f"Could not write audit record to {audit_path}: {e}")
+        if temp_path.exists():
+            try:
+                os.remove(temp_path)
+            except OSError:
+                pass
+        return None
+
+
+def read_audit_log(
+    history_root: Path, since: Optional[float] = None
+) -> List[Dict[str, Any]]:
+    """Audit records, oldest first; only those at or after `since` (epoch) if given."""
+    audit_path = get_audit_log_path(history_root)
+    if not audit_path.is_file():
+        return []
+    records = []
+    with AdvisoryLock(audit_path.with_name(AUDIT_LOG_NAME + ".lock")):
+        with open(audit_path, "r", encoding="utf-8") as f:
+            for i, line in enumerate(f):
+                if not line.strip():
+                    continue
+                try:
+                    records.append(json.loads(line))
+                except json.JSONDecodeError as e:
+                    log.warning(f"Invalid JSON on line {i + 1} in {audit_path}: {e}")
+    if since is not None:
+        records = [
+            r for r in records if parse_timestamp(r.get("timestamp", 0)) >= since
+        ]
+    return records
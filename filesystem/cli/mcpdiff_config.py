@@ -0,0 +1,205 @@
+# mcpdiff_config.py
+
+import os
+import json
+from pathlib import Path
+from typing import Any, Callable, Dict, Optional
+
+from mcpdiff_utils import (
+    log,
+    history_format,
+    HistoryError,
+    DEFAULT_NAMESPACE,
+    NAMESPACE_PATTERN,
+)
+
+# --- Configuration File ---
+# Workspace-level settings live next to the edit history: <workspace>/.mcp/config.json
+# It is JSON, also where `namespace` and `info` speak of .mcp/config.toml; the
+# server reads the same file
+CONFIG_FILE_NAME = "config.json"
+
+
+def _parse_syntax_theme(value: str) -> str:
+    """Validates a syntax theme name against the installed Pygments styles."""
+    try:
+        from pygments.styles import get_all_styles
+    except ImportError:
+        # Can't validate without Pygments; store as-is, it is only used if installed
+        return value
+    styles = sorted(get_all_styles())
+    if value not in styles:
+        raise ValueError(f"Unknown theme '{value}'. Available: {', '.join(styles)}")
+    return value
+
+
+def _parse_log_shard_interval(value: str) -> Optional[str]:
+    """Validates a log shard interval; "none" disables sharding."""
+    value = value.lower()
+    if value == "none":
+        return None
+    if value not in history_format.LOG_SHARD_INTERVALS:
+        intervals = ", ".join(history_format.LOG_SHARD_INTERVALS)
+        raise ValueError(f"Expected one of: {intervals}, none")
+    return value
+
+
+def _parse_auto_expire_days(value: str) -> Optional[int]:
+    """Validates a number of days; 0 or "none" turns expiry off."""
+    if value.lower() == "none":
+        return None
+    try:
+        days = int(value)
+    except ValueError:
+        days = -1
+    if days < 0:
+        raise ValueError("Expected a whole number of days, or none")
+    return days or None
+
+
+def _parse_history_depth(value: str) -> Optional[int]:
+    """Validates a number of checkpoints to keep; "none" keeps them all."""
+    if value.lower() == "none":
+        return None
+    try:
+        depth = int(value)
+    except ValueError:
+        depth = 0
+    if depth < 1:
+        raise ValueError("Expected a whole number of checkpoints (at least 1), or none")
+    return depth
+
+
+def _parse_bool(value: str) -> bool:
+    """Validates an on/off setting."""
+    value = value.lower()
+    if value in ("true", "yes", "on", "1"):
+        return True
+    if value in ("false", "no", "off", "0"):
+        return False
+    raise ValueError("Expected true or false")
+
+
+def _parse_namespace(value: str) -> Optional[str]:
+    """Validates a conversation namespace; "none" goes back to the default one."""
+    if value.lower() == "none":
+        return None
+    if value == DEFAULT_NAMESPACE:
+        raise ValueError(
+            f"'{DEFAULT_NAMESPACE}' is the namespace of conversations without one"
+        )
+    if not NAMESPACE_PATTERN.match(value):
+        raise ValueError(
+            "Expected a letter or digit, then letters, digits, '.', '_' or '-', "
+            "or none"
+        )
+    return value
+
+
+# Known keys: parser (validates/converts the CLI string), default, description
+CONFIG_KEYS: Dict[str, Dict[str, Any]] = {
+    "syntax_theme": {
+        "parse": _parse_syntax_theme,
+        "default": "monokai",
+        "help": "Pygments style used to highlight file content in `show`.",
+    },
+    "log_shard_interval": {
+        "parse": _parse_log_shard_interval,
+        "default": None,
+        "help": "Start a new log file per conversation every day, week or month.",
+    },
+    "auto_expire_days": {
+        "parse": _parse_auto_expire_days,
+        "default": None,
+        "help": "Have the daemon reject pending edits older than this many days.",
+    },
+    "history_depth": {
+        "parse": _parse_history_depth,
+        "default": None,
+        "help": "Keep only this many checkpoints per file in each conversation.",
+    },
+    "namespace": {
+        "parse": _parse_namespace,
+        "default": None,
+        "help": "Prefix conversation IDs with <namespace>/ (for a shared .mcp/ root).",
+    },
+    "auto_git_stage": {
+        "parse": _parse_bool,
+        "default": False,
+        "help": "Have `accept` run `git add` on the files it accepted, as --git-stage.",
+    },
+}
+
+
+def get_config_path(workspace_root: Path) -> Path:
+    return workspace_root / ".mcp" / CONFIG_FILE_NAME
+
+
+def load_config(workspace_root: Path) -> Dict[str, Any]:
+    """Loads the workspace config. A missing or invalid file yields an empty config."""
+    config_path = get_config_path(workspace_root)
+    if not config_path.is_file():
+        return {}
+    try:
+        with open(config_path, "r", encoding="utf-8") as f:
+            config = json.load(f)
+        if not isinstance(config, dict):
+            log.warning(f"Ignoring config file {config_path}: not a JSON object.")
+            return {}
+        return config
+    except (IOError, json.JSONDecodeError) as e:
+        log.warning(f"Ignoring unreadable config file {config_path}: {e}")
+        return {}
+
+
+def save_config(workspace_root: Path, config: Dict[str, Any]) -> None:
+    """Writes the workspace config atomically."""
+    config_path = get_config_path(workspace_root)
+    temp_path = config_path.with_suffix(config_path.suffix + ".tmp" + str(os.getpid()))
+    try:
+        config_path.parent.mkdir(parents=True, exist_ok=True)
+        with open(temp_path, "w", encoding="utf-8") as f:
+            json.dump(config, f, indent=2, sort_keys=True)
+            f.write("\n")
+        os.replace(temp_path, config_path)
+    except IOError as e:
+        if temp_path.exists():
+            try:
+                os.remove(temp_path)
+            except OSError:
+                pass
+        raise HistoryError(f"Could not write config file: {config_path}") from e
+
+
+def get_config_value(workspace_root: Path, key: str) -> Any:
+    """Returns the configured value for a known key, or its default."""
+    if key not in CONFIG_KEYS:
+        raise HistoryError(f"Unknown config key: {key}")
+    return load_config(workspace_root).get(key, CONFIG_KEYS[key]["default"])
+
+
+def set_config_value(workspace_root: Path, key: str, raw_value: str) -> Any:
+    """Validates and stores a value for a known key. Returns the stored value."""
+    if key not in CONFIG_KEYS:
+        raise HistoryError(
+            f"Unknown config key: {key}. Known keys: {', '.join(sorted(CONFIG_KEYS))}"
+        )
+    parse: Callable[[str], Any] = CONFIG_KEYS[key]["parse"]
+    try:
+        value = parse(raw_value)
+    except ValueError as e:
+        raise HistoryError(f"Invalid value for {key}: {e}") from e
+    config = load_config(workspace_root)
+    config[key] = value
+    save_config(workspace_root, config)
+    log.debug(f"Set config {key} = {value!r}")
+    return value
+
+
+def unset_config_value(workspace_root: Path, key: str) -> Optional[Any]:
+    """Removes a key from the config, returning its previous value if any."""
+    config = load_config(workspace_root)
+    previous = config.pop(key, None)
+    if previous is not None:
+        save_config(workspace_root, config)
+    return previous
@@ -0,0 +1,595 @@
+# mcpdiff_daemon.py
+
+import ctypes
+import ctypes.util
+import json
+import os
+import select
+import selectors
+import signal
+import socket
+import sys
+import time
+from pathlib import Path
+from typing import Any, Dict, List, Optional, Tuple
+
+import mcpdiff_config as config
+import mcpdiff_history as history
+import mcpdiff_ignore as ignore
+import mcpdiff_metrics as metrics
+import mcpdiff_utils as utils
+from mcpdiff_utils import HistoryError, LOGS_DIR, log
+
+# --- Daemon ---
+# A background process per workspace that keeps every log entry in memory and
+# answers queries over a Unix domain socket, so that `status` does not have to
+# read all log files on slow (NFS) mounts. It re-reads a log file only when its
+# inode, size or mtime changed: on an inotify/kqueue event for the logs
+# directory, and at least every `interval` seconds for filesystems that do not
+# deliver events for changes made on other hosts. Started with --metrics, it
+# also serves the operation counts mcpdiff runs report to it over HTTP.
+DAEMON_SOCKET_NAME = "daemon.sock"
+DAEMON_PID_NAME = "daemon.pid"
+DAEMON_LOG_NAME = "daemon.log"
+DEFAULT_INTERVAL = 5.0  # Seconds between rescans without an event
+CLIENT_TIMEOUT = 5.0  # Seconds a client waits for the daemon before falling back
+EXPIRE_INTERVAL = 3600.0  # Seconds between runs of the auto_expire_days setting
+MAX_REQUEST_BYTES = 64 * 1024
+METRICS_HOST = "127.0.0.1"
+METRICS_PATH = "/metrics"
+METRICS_CONTENT_TYPE = "text/plain; version=0.0.4; charset=utf-8"
+
+# inotify(7) events on the logs directory; log files are replaced by rename
+_IN_MODIFY = 0x00000002
+_IN_CLOSE_WRITE = 0x00000008
+_IN_MOVED_FROM = 0x00000040
+_IN_MOVED_TO = 0x00000080
+_IN_CREATE = 0x00000100
+_IN_DELETE = 0x00000200
+_IN_WATCH_MASK = (
+    _IN_MODIFY
+    | _IN_CLOSE_WRITE
+    | _IN_MOVED_FROM
+    | _IN_MOVED_TO
+    | _IN_CREATE
+    | _IN_DELETE
+)
+
+
+def get_socket_path(workspace_root: Path) -> Path:
+    return workspace_root / ".mcp" / DAEMON_SOCKET_NAME
+
+
+def get_pid_path(workspace_root: Path) -> Path:
+    return workspace_root / ".mcp" / DAEMON_PID_NAME
+
+
+def is_supported() -> bool:
+    """The daemon needs fork() and Unix domain sockets (not on Windows)."""
+    return hasattr(os, "fork") and hasattr(socket, "AF_UNIX")
+
+
+class LogIndex:
+    """All log entries of a history root, re-read per log file when it changes."""
+
+    def __init__(self, history_root: Path):
+        self.history_root = history_root
+        self.logs_dir = history_root / LOGS_DIR
+        # Log file name -> (inode, size, mtime_ns) and its entries
+        self.signatures: Dict[str, Tuple[int, int, int]] = {}
+        self.entries_by_file: Dict[str, List[Dict[str, Any]]] = {}
+        self.entries: List[Dict[str, Any]] = []
+        self.by_edit_id: Dict[str, Dict[str, Any]] = {}
+        self.by_conversation: Dict[str, List[Dict[str, Any]]] = {}
+
+    def refresh(self) -> bool:
+        """Re-reads new and changed log files. Returns True if anything changed."""
+        current: Dict[str, Tuple[int, int, int]] = {}
+        for log_file in utils.find_log_files(self.logs_dir):
+            try:
+                st = log_file.stat()
+            except OSError:
+                continue  # Replaced or removed since the glob
+            source = utils.get_log_file_source(log_file)
+            current[source] = (st.st_ino, st.st_size, st.st_mtime_ns)
+
+        changed = False
+        for name in set(self.entries_by_file) - set(current):
+            del self.entries_by_file[name]
+            changed = True
+        for name, signature in current.items():
+            if self.signatures.get(name) == signature:
+                continue
+            try:
+                # Unlocked, so that the daemon never holds up a writer
+                entries = history.read_log_entries(
+                    self.logs_dir / name, self.history_root, locked=False
+                )
+            except (HistoryError, TimeoutError) as e:
+                log.warning(f"Could not read log file {name}: {e}")
+                current.pop(name)  # Retried on the next refresh
+                continue
+            self.entries_by_file[name] = entries
+            changed = True
+        self.signatures = {
+            name: sig for name, sig in current.items() if name in self.entries_by_file
+        }
+
+        if changed:
+            self.entries = [
+                e for entries in self.entries_by_file.values() for e in entries
+            ]
+            history.sort_entries(self.entries)
+            self.by_edit_id = {
+                e["edit_id"]: e for e in self.entries if "edit_id" in e
+            }
+            self.by_conversation = {}
+            for entry in self.entries:
+                conv_id = entry.get("conversation_id")
+                self.by_conversation.setdefault(conv_id, []).append(entry)
+        return changed
+
+
+class _InotifyWatcher:
+    """Readable whenever something in the watched directory changes (Linux)."""
+
+    name = "inotify"
+
+    def __init__(self, directory: Path):
+        libc = ctypes.CDLL(ctypes.util.find_library("c"), use_errno=True)
+        self.fd = libc.inotify_init1(os.O_NONBLOCK | os.O_CLOEXEC)
+        if self.fd < 0:
+            raise OSError(ctypes.get_errno(), "inotify_init1 failed")
+        watch = libc.inotify_add_watch(
+            self.fd, os.fsencode(directory), _IN_WATCH_MASK
+        )
+        if watch < 0:
+            errno = ctypes.get_errno()
+            os.close(self.fd)
+            raise OSError(errno, f"inotify_add_watch failed for {directory}")
+
+    def fileno(self) -> int:
+        return self.fd
+
+    def drain(self) -> None:
+        try:
+            while os.read(self.fd, 64 * 1024):
+                pass
+        except BlockingIOError:
+            pass
+
+    def close(self) -> None:
+        os.close(self.fd)
+
+
+class _KqueueWatcher:
+    """Readable whenever an entry of the watched directory changes (BSD/macOS)."""
+
+    name = "kqueue"
+
+    def __init__(self, directory: Path):
+        self.dir_fd = os.open(directory, os.O_RDONLY)
+        self.kq = select.kqueue()
+        event = select.kevent(
+            self.dir_fd,
+            filter=select.KQ_FILTER_VNODE,
+            flags=select.KQ_EV_ADD | select.KQ_EV_CLEAR,
+            fflags=select.KQ_NOTE_WRITE | select.KQ_NOTE_DELETE,
+        )
+        self.kq.control([event], 0, 0)
+
+    def fileno(self) -> int:
+        return self.kq.fileno()
+
+    def drain(self) -> None:
+        self.kq.control(None, 16, 0)
+
+    def close(self) -> None:
+        self.kq.close()
+        os.close(self.dir_fd)
+
+
+def open_watcher(directory: Path):
+    """An inotify or kqueue watcher for `directory`, or None to only poll."""
+    try:
+        if sys.platform.startswith("linux"):
+            return _InotifyWatcher(directory)
+        if hasattr(select, "kqueue"):
+            return _KqueueWatcher(directory)
+    except (OSError, AttributeError) as e:
+        log.warning(f"Cannot watch {directory} for changes, polling instead: {e}")
+    return None
+
+
+def _handle_request(
+    index: LogIndex, request: Dict[str, Any], info: Dict[str, Any]
+) -> Dict[str, Any]:
+    op = request.get("op")
+    if op == "ping":
+        return {
+            "ok": True,
+            "pid": os.getpid(),
+            **info,
+            "history_root": str(index.history_root),
+            "log_files": len(index.entries_by_file),
+            "entries": len(index.entries),
+        }
+    if op == "entries":
+        conv_id = request.get("conversation_id")
+        entries = index.by_conversation.get(conv_id, []) if conv_id else index.entries
+        return {"ok": True, "entries": [utils.encode_log_paths(e) for e in entries]}
+    if op == "entry":
+        entry = index.by_edit_id.get(request.get("edit_id"))
+        return {"ok": True, "entry": entry and utils.encode_log_paths(entry)}
+    if op == "count":
+        filters = request.get("filters") or {}
+        unknown = set(filters) - set(history.ENTRY_FILTERS)
+        if unknown:
+            return {"ok": False, "error": f"Unknown filters: {sorted(unknown)}"}
+        group_by = request.get("group_by")
+        if group_by and group_by not in history.COUNT_GROUP_FIELDS:
+            return {"ok": False, "error": f"Unknown group: {group_by!r}"}
+        entries = index.entries
+        if not request.get("no_ignore"):
+            # The workspace's .mcp/ignore, read per request as it may change
+            ignored = ignore.load_ignore_matcher(index.history_root.parent.parent)
+            entries = [e for e in entries if not ignored.matches_entry(e)]
+        try:
+            count = history.count_filtered_entries(entries, group_by, **filters)
+        except HistoryError as e:
+            return {"ok": False, "error": str(e)}
+        return {"ok": True, "count": count}
+    if op == "metrics":
+        try:
+            metrics.METRICS.merge(request.get("metrics") or {})
+        except ValueError as e:
+            return {"ok": False, "error": str(e)}
+        return {"ok": True}
+    return {"ok": False, "error": f"Unknown request: {op!r}"}
+
+
+def _serve_connection(
+    conn: socket.socket, index: LogIndex, info: Dict[str, Any]
+) -> None:
+    with conn:
+        conn.settimeout(CLIENT_TIMEOUT)
+        try:
+            data = b""
+            while b"\n" not in data and len(data) < MAX_REQUEST_BYTES:
+                chunk = conn.recv(4096)
+                if not chunk:
+                    break
+                data += chunk
+            try:
+                request = json.loads(data.split(b"\n", 1)[0] or b"null")
+                if not isinstance(request, dict):
+                    raise ValueError("request must be a JSON object")
+                response = _handle_request(index, request, info)
+            except ValueError as e:
+                response = {"ok": False, "error": f"Invalid request: {e}"}
+            conn.sendall(json.dumps(response).encode("utf-8") + b"\n")
+        except OSError as e:
+            log.debug(f"Daemon client connection failed: {e}")
+
+
+def _serve_metrics(conn: socket.socket) -> None:
+    """Answers one HTTP request: the metrics for GET /metrics, else an error."""
+    with conn:
+        conn.settimeout(CLIENT_TIMEOUT)
+        try:
+            data = b""
+            while b"\r\n\r\n" not in data and len(data) < MAX_REQUEST_BYTES:
+                chunk = conn.recv(4096)
+                if not chunk:
+                    break
+                data += chunk
+            request_line = data.split(b"\r\n", 1)[0].decode("latin-1")
+            method, _, rest = request_line.partition(" ")
+            path = rest.split(" ", 1)[0].split("?", 1)[0]
+            if method != "GET":
+                status, body = "405 Method Not Allowed", "Only GET is supported.\n"
+            elif path != METRICS_PATH:
+                status, body = "404 Not Found", f"Metrics are at {METRICS_PATH}.\n"
+            else:
+                status, body = "200 OK", metrics.METRICS.render()
+            payload = body.encode("utf-8")
+            content_type = METRICS_CONTENT_TYPE if status == "200 OK" else "text/plain"
+            head = (
+                f"HTTP/1.0 {status}\r\n"
+                f"Content-Type: {content_type}\r\n"
+                f"Content-Length: {len(payload)}\r\n"
+                "Connection: close\r\n\r\n"
+            )
+            conn.sendall(head.encode("latin-1") + payload)
+        except OSError as e:
+            log.debug(f"Metrics client connection failed: {e}")
+
+
+def expire_pending_edits(history_root: Path) -> int:
+    """
+    Rejects pending edits older than the workspace's auto_expire_days setting,
+    if it is set, as `mcpdiff expire-old` does. Returns the number expired.
+    """
+    workspace_root = history_root.parent.parent
+    days = config.get_config_value(workspace_root, "auto_expire_days")
+    if not days:
+        return 0
+    all_entries = history.find_all_entries(history_root)
+    expired = history.find_expired_entries(all_entries, days * 86400)
+    if not expired:
+        return 0
+    report = history.expire_entries(expired, all_entries, workspace_root, history_root)
+    for entry in report["expired"]:
+        log.info(
+            f"Expired edit {entry.get('edit_id')} ({entry.get('file_path')}), "
+            f"pending since {entry.get('timestamp')}"
+        )
+    for entry, reason in report["skipped"]:
+        log.warning(f"Did not expire edit {entry.get('edit_id')}: {reason}")
+    return len(report["expired"])
+
+
+def serve(
+    server: socket.socket,
+    history_root: Path,
+    interval: float = DEFAULT_INTERVAL,
+    should_stop=None,
+    expire_interval: float = EXPIRE_INTERVAL,
+    metrics_server: Optional[socket.socket] = None,
+) -> None:
+    """
+    Answers queries on the listening socket `server` until `should_stop()`
+    returns True (checked at least every `interval` seconds) or the process
+    is terminated. Every `expire_interval` seconds, starting right away, it
+    also expires old pending edits if auto_expire_days is configured. With a
+    `metrics_server` (see bind_metrics_socket()), it answers HTTP requests for
+    the metrics on that one too.
+    """
+    index = LogIndex(history_root)
+    index.logs_dir.mkdir(parents=True, exist_ok=True)
+    watcher = open_watcher(index.logs_dir)
+    info = {
+        "started": time.time(),
+        "watcher": watcher.name if watcher else "polling",
+        "metrics_port": metrics_server.getsockname()[1] if metrics_server else None,
+    }
+    index.refresh()
+    last_refresh = time.monotonic()
+    last_expire: Optional[float] = None
+
+    selector = selectors.DefaultSelector()
+    server.setblocking(False)
+    selector.register(server, selectors.EVENT_READ, "client")
+    if watcher:
+        selector.register(watcher.fileno(), selectors.EVENT_READ, "watch")
+    if metrics_server:
+        metrics_server.setblocking(False)
+        selector.register(metrics_server, selectors.EVENT_READ, "metrics")
+    try:
+        while not (should_stop and should_stop()):
+            now = time.monotonic()
+            if last_expire is None or now - last_expire >= expire_interval:
+                last_expire = now
+                try:
+                    expire_pending_edits(history_root)
+                except (HistoryError, TimeoutError) as e:
+                    log.warning(f"Could not expire old pending edits: {e}")
+            events = selector.select(timeout=interval)
+            ready = {key.data for key, _ in events}
+            # Take in changes before answering, so a query made right after an
+            # accept or reject already sees its result
+            if "watch" in ready:
+                watcher.drain()
+            if "watch" in ready or time.monotonic() - last_refresh >= interval:
+                index.refresh()
+                last_refresh = time.monotonic()
+            if "client" in ready:
+                try:
+                    conn, _ = server.accept()
+                except BlockingIOError:
+                    continue
+                conn.setblocking(True)
+                _serve_connection(conn, index, info)
+            if "metrics" in ready:
+                try:
+                    conn, _ = metrics_server.accept()
+                except BlockingIOError:
+                    continue
+                conn.setblocking(True)
+                _serve_metrics(conn)
+    finally:
+        selector.close()
+        if watcher:
+            watcher.close()
+
+
+def bind_socket(workspace_root: Path) -> socket.socket:
+    """A listening socket at .mcp/daemon.sock, replacing a stale socket file."""
+    socket_path = get_socket_path(workspace_root)
+    if query(workspace_root, {"op": "ping"}) is not None:
+        raise HistoryError(f"A daemon is already running for {workspace_root}")
+    if socket_path.exists() or socket_path.is_symlink():
+        socket_path.unlink()
+    server = socket.socket(socket.AF_UNIX, socket.SOCK_STREAM)
+    try:
+        server.bind(str(socket_path))
+        server.listen(16)
+    except OSError as e:
+        server.close()
+        raise HistoryError(f"Cannot listen on {socket_path}: {e}") from e
+    return server
+
+
+def bind_metrics_socket(port: int) -> socket.socket:
+    """A listening TCP socket for /metrics on 127.0.0.1:`port` (0: any free one)."""
+    try:
+        return socket.create_server((METRICS_HOST, port))
+    except OSError as e:
+        raise HistoryError(f"Cannot serve metrics on port {port}: {e}") from e
+
+
+def get_metrics_url(port: int) -> str:
+    return f"http://{METRICS_HOST}:{port}{METRICS_PATH}"
+
+
+def run_in_background(
+    server: socket.socket,
+    workspace_root: Path,
+    history_root: Path,
+    interval: float,
+    metrics_server: Optional[socket.socket] = None,
+) -> int:
+    """
+    Forks a detached process (double fork, new session) serving on `server`,
+    and on `metrics_server` if given, and returns its PID. The daemon logs to
+    .mcp/daemon.log and removes its socket and PID file when it gets SIGTERM.
+    """
+    read_fd, write_fd = os.pipe()
+    child = os.fork()
+    if child:
+        os.close(write_fd)
+        with os.fdopen(read_fd) as pipe:
+            daemon_pid = pipe.read().strip()
+        os.waitpid(child, 0)
+        server.close()
+        if metrics_server:
+            metrics_server.close()
+        if not daemon_pid:
+            raise HistoryError("The daemon process exited during startup")
+        return int(daemon_pid)
+
+    # First child: detach from the terminal, then let a grandchild serve
+    os.close(read_fd)
+    exit_code = 1
+    try:
+        os.setsid()
+        if os.fork():
+            os._exit(0)
+        socket_path = get_socket_path(workspace_root)
+        pid_path = get_pid_path(workspace_root)
+        pid_path.write_text(f"{os.getpid()}\n")
+        devnull = os.open(os.devnull, os.O_RDWR)
+        log_fd = os.open(
+            workspace_root / ".mcp" / DAEMON_LOG_NAME,
+            os.O_WRONLY | os.O_CREAT | os.O_APPEND,
+            0o644,
+        )
+        os.dup2(devnull, 0)
+        os.dup2(log_fd, 1)
+        os.dup2(log_fd, 2)
+        os.write(write_fd, f"{os.getpid()}\n".encode())
+        os.close(write_fd)
+        signal.signal(signal.SIGTERM, lambda signum, frame: sys.exit(0))
+        signal.signal(signal.SIGINT, signal.SIG_IGN)
+        log.info(f"Daemon {os.getpid()} serving {history_root} on {socket_path}")
+        try:
+            serve(server, history_root, interval, metrics_server=metrics_server)
+        finally:
+            for path in (socket_path, pid_path):
+                try:
+                    path.unlink()
+                except OSError:
+                    pass
+        exit_code = 0
+    except SystemExit:  # SIGTERM
+        exit_code = 0
+    except BaseException as e:
+        log.exception(f"Daemon failed: {e}")
+    finally:
+        os._exit(exit_code)
+
+
+def query(
+    workspace_root: Path, request: Dict[str, Any], timeout: float = CLIENT_TIMEOUT
+) -> Optional[Dict[str, Any]]:
+    """
+    Sends one request to the workspace's daemon. Returns the response, or None
+    if no daemon is running or it did not answer properly.
+    """
+    socket_path = get_socket_path(workspace_root)
+    if not is_supported() or not socket_path.exists():
+        return None
+    try:
+        with socket.socket(socket.AF_UNIX, socket.SOCK_STREAM) as client:
+            client.settimeout(timeout)
+            client.connect(str(socket_path))
+            client.sendall(json.dumps(request).encode("utf-8") + b"\n")
+            chunks = []
+            while True:
+                chunk = client.recv(64 * 1024)
+                if not chunk:
+                    break
+                chunks.append(chunk)
+        response = json.loads(b"".join(chunks))
+    except (OSError, ValueError) as e:
+        log.debug(f"Daemon at {socket_path} did not answer: {e}")
+        return None
+    if not isinstance(response, dict) or not response.get("ok"):
+        log.debug(f"Daemon at {socket_path} refused {request}: {response}")
+        return None
+    return response
+
+
+def fetch_entries(workspace_root: Path) -> Optional[List[Dict[str, Any]]]:
+    """All log entries as find_all_entries() returns them, or None without a daemon."""
+    response = query(workspace_root, {"op": "entries"})
+    if response is None:
+        return None
+    return [utils.decode_log_paths(e) for e in response["entries"]]
+
+
+def fetch_count(
+    workspace_root: Path,
+    filters: Dict[str, Any],
+    group_by: Optional[str] = None,
+    no_ignore: bool = False,
+) -> Optional[Any]:
+    """
+    history.count_filtered_entries() of the entries `status` shows, counted by
+    the daemon without sending them, or None without a daemon (or if it
+    refused the filters: the caller then counts itself, and reports errors).
+    """
+    request = {
+        "op": "count",
+        "filters": filters,
+        "group_by": group_by,
+        "no_ignore": no_ignore,
+    }
+    response = query(workspace_root, request)
+    return None if response is None else response["count"]
+
+
+def report_metrics(workspace_root: Path, counted: metrics.Metrics) -> bool:
+    """Adds what a run counted to the daemon's metrics. False without a daemon."""
+    request = {"op": "metrics", "metrics": counted.to_dict()}
+    return query(workspace_root, request) is not None
+
+
+def stop(workspace_root: Path, timeout: float) -> Optional[int]:
+    """
+    Sends SIGTERM to the daemon and removes its socket and PID file. Returns
+    the PID that was stopped, or None if no daemon was running.
+    """
+    socket_path = get_socket_path(workspace_root)
+    pid_path = get_pid_path(workspace_root)
+    response = query(workspace_root, {"op": "ping"})
+    pid = response["pid"] if response else None
+    if pid is None and pid_path.is_file():
+        try:
+            pid = int(pid_path.read_text().strip())
+        except ValueError:
+            pid = None
+        if pid is not None and not utils.pid_exists(pid):
+            pid = None
+
+    if pid is not None:
+        os.kill(pid, signal.SIGTERM)
+        deadline = time.monotonic() + timeout
+        while utils.pid_exists(pid) and time.monotonic() < deadline:
+            time.sleep(0.05)
+        if utils.pid_exists(pid):
+            raise TimeoutError(f"Daemon {pid} did not exit within {timeout}s")
+    for path in (socket_path, pid_path):
+        if path.exists() or path.is_symlink():
+            path.unlink()
+    return pid
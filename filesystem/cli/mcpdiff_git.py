@@ -0,0 +1,50 @@
+# mcpdiff_git.py
+
+import subprocess
+from pathlib import Path
+from typing import Iterable, List
+
+from mcpdiff_utils import log
+
+# --- Git Staging ---
+# `accept --git-stage`, or the config key auto_git_stage, runs `git add` in the
+# workspace root for each file whose edit was accepted. Files git does not track,
+# and workspaces outside a git repository, are skipped without a word; a failing
+# `git add` is only warned about, the accept stands.
+
+
+def _git(workspace_root: Path, *args: str) -> subprocess.CompletedProcess:
+    return subprocess.run(
+        ["git", *args], cwd=workspace_root, capture_output=True, text=True
+    )
+
+
+def is_tracked(workspace_root: Path, file_path: str) -> bool:
+    """
+    Whether git tracks the workspace-relative path, deleted or not. False
+    outside a git repository or without git installed.
+    """
+    try:
+        result = _git(workspace_root, "ls-files", "--error-unmatch", "--", file_path)
+    except OSError:
+        return False
+    return result.returncode == 0
+
+
+def stage_files(workspace_root: Path, file_paths: Iterable[str]) -> List[str]:
+    """Runs `git add` for each path git tracks. Returns the paths staged."""
+    staged = []
+    for file_path in dict.fromkeys(file_paths):
+        if not is_tracked(workspace_root, file_path):
+            log.debug(f"Not staging {file_path}: not tracked by git.")
+            continue
+        try:
+            result = _git(workspace_root, "add", "--", file_path)
+        except OSError as e:
+            log.warning(f"Could not stage {file_path}: {e}")
+            continue
+        if result.returncode != 0:
+            log.warning(f"Could not stage {file_path}: {result.stderr.strip()}")
+            continue
+        staged.append(file_path)
+    return staged
@@ -1,25 +1,52 @@
 # mcpdiff_history.py
 
+import difflib
 import os
-import subprocess
+import json
 import shutil
 import tempfile
+import time
 import uuid
+from dataclasses import dataclass, field, replace
 from pathlib import Path
 from datetime import datetime, timezone
-from typing import List, Dict, Any, Optional, Tuple
+from typing import List, Dict, Any, Iterator, NamedTuple, Optional, Set, Tuple, Callable
 
 # Import from utils module
+import mcpdiff_audit as audit
+import mcpdiff_config as config
+import mcpdiff_metrics as metrics
 import mcpdiff_utils as utils
 from mcpdiff_utils import (
     log,
     HistoryError,
     AmbiguousIDError,
+    ExternalModificationError,
+    MissingCheckpointError,
+    MissingDiffError,
+    PatchConflictError,
+    VerificationError,
+    WorkspaceNotFoundError,
     HISTORY_DIR_NAME,
     LOGS_DIR,
     DIFFS_DIR,
     CHECKPOINTS_DIR,
 )
+from mcpdiff_migration import SchemaVersionError, default_migrator
+from mcpdiff_patch import (
+    BSDIFF_ENCODING,
+    BSDIFF_MAGIC,
+    ConflictRegion,
+    PatchError,
+    apply_bsdiff_to_file,
+    apply_diff_to_file,
+    apply_partial_to_file,
+    detect_conflicts,
+    find_conflict_markers,
+    is_bsdiff_patch,
+    read_diff_file,
+    split_lines,
+)
 
 
 # --- Workspace Root Finding ---
@@ -28,6 +55,7 @@ def find_workspace_root(start_path: Optional[str] = None) -> Optional[Path]:
     Find the workspace root (directory containing .mcp/edit_history) by walking up.
     """
     current = Path(start_path if start_path else os.getcwd()).resolve()
+    current = Path(utils.strip_long_path_prefix(str(current)))
     log.debug(f"Starting workspace search from: {current}")
     while True:
         check_dir = current / ".mcp" / HISTORY_DIR_NAME
@@ -42,6 +70,74 @@ def find_workspace_root(start_path: Optional[str] = None) -> Optional[Path]:
     # return None
 
 
+class WorkspaceRoots(NamedTuple):
+    """
+    A workspace and its history root; unpacks as (workspace_root, history_root).
+    """
+
+    workspace_root: Path
+    history_root: Path
+
+    @property
+    def namespace(self) -> str:
+        """
+        The namespace new conversation IDs get as their <namespace>/ prefix
+        (config key `namespace`), or "default" if none is configured.
+        """
+        namespace = config.get_config_value(self.workspace_root, "namespace")
+        return namespace or utils.DEFAULT_NAMESPACE
+
+
+def find_workspace_and_history_roots(
+    start_path: Optional[str] = None,
+) -> WorkspaceRoots:
+    """
+    The workspace root and its history root (.mcp/edit_history), found by
+    walking up from `start_path` (default: the current directory). Raises
+    WorkspaceNotFoundError if there is none.
+    """
+    workspace_root = find_workspace_root(start_path)
+    if workspace_root is None:
+        start = start_path or os.getcwd()
+        raise WorkspaceNotFoundError(
+            f"Could not find workspace root (.mcp/{HISTORY_DIR_NAME}) from '{start}'.",
+            start_path=str(start),
+        )
+    return WorkspaceRoots(workspace_root, workspace_root / ".mcp" / HISTORY_DIR_NAME)
+
+
+# Not searched by find_workspace_roots(): no workspace lives inside these
+DETECT_SKIP_DIRS = {".mcp", ".git", "node_modules"}
+
+
+def find_workspace_roots(root: Path) -> List[Path]:
+    """
+    All workspace roots (directories containing .mcp/edit_history) at or below
+    `root`, sorted. Symlinked directories are not followed.
+    """
+    roots = []
+    for current, dirs, _files in os.walk(root):
+        if (Path(current) / ".mcp" / HISTORY_DIR_NAME).is_dir():
+            roots.append(Path(current))
+        dirs[:] = [d for d in dirs if d not in DETECT_SKIP_DIRS]
+    log.debug(f"Found {len(roots)} workspace(s) under {root}")
+    return sorted(roots)
+
+
+def summarize_workspace(
+    workspace_root: Path, lock_timeout: Optional[float] = None
+) -> Dict[str, Any]:
+    """The path of a workspace, with its number of conversations and pending edits."""
+    entries = find_all_entries(
+        workspace_root / ".mcp" / HISTORY_DIR_NAME, lock_timeout=lock_timeout
+    )
+    return {
+        "path": str(workspace_root),
+        "conversation_count": len({e.get("conversation_id") for e in entries}),
+        "pending_count": sum(1 for e in entries if e.get("status") == "pending"),
+    }
+
+
 def get_workspace_path(relative_path: str, workspace_root: Path) -> Path:
     """Convert a path relative to workspace root to an absolute path."""
     return (workspace_root / relative_path).resolve()
@@ -51,8 +147,9 @@ def get_relative_path(absolute_path: Path, workspace_root: Path) -> str:
     """Convert an absolute path to a path relative to workspace root."""
     try:
         # Ensure absolute_path is absolute and resolved
-        abs_resolved = absolute_path.resolve()
-        return str(abs_resolved.relative_to(workspace_root.resolve()))
+        abs_resolved = utils.strip_long_path_prefix(str(absolute_path.resolve()))
+        root_resolved = utils.strip_long_path_prefix(str(workspace_root.resolve()))
+        return str(Path(abs_resolved).relative_to(root_resolved))
     except ValueError:
         log.debug(
             f"Path {absolute_path} not relative to workspace {workspace_root}. Using absolute path string."
@@ -65,9 +162,215 @@ def get_relative_path(absolute_path: Path, workspace_root: Path) -> str:
         return str(absolute_path)  # Fallback
 
 
+# --- Conversation Tags ---
+# Human-readable names for conversations, stored as {"tag": "conversation_id"}
+TAGS_FILE = "tags.json"
+
+
+def load_tags(history_root: Path) -> Dict[str, str]:
+    """Loads the tag -> conversation_id mapping. Missing or invalid files yield {}."""
+    tags_path = history_root / TAGS_FILE
+    if not tags_path.is_file():
+        return {}
+    try:
+        with open(tags_path, "r", encoding="utf-8") as f:
+            tags = json.load(f)
+        if not isinstance(tags, dict):
+            log.warning(f"Ignoring tags file {tags_path}: not a JSON object.")
+            return {}
+        return {str(k): str(v) for k, v in tags.items()}
+    except (IOError, json.JSONDecodeError) as e:
+        log.warning(f"Ignoring unreadable tags file {tags_path}: {e}")
+        return {}
+
+
+def save_tags(history_root: Path, tags: Dict[str, str]) -> None:
+    """Writes the tag mapping atomically under the tags file lock."""
+    tags_path = history_root / TAGS_FILE
+    temp_path = tags_path.with_suffix(tags_path.suffix + ".tmp" + str(os.getpid()))
+    try:
+        with utils.FileLock(str(tags_path)):
+            with open(temp_path, "w", encoding="utf-8") as f:
+                json.dump(tags, f, indent=2, sort_keys=True)
+                f.write("\n")
+            os.replace(temp_path, tags_path)
+    except (IOError, TimeoutError) as e:
+        if temp_path.exists():
+            try:
+                os.remove(temp_path)
+            except OSError:
+                pass
+        raise HistoryError(f"Could not write tags file: {tags_path}") from e
+
+
+def get_tags_by_conversation(tags: Dict[str, str]) -> Dict[str, str]:
+    """Inverts the tag mapping to conversation_id -> tag."""
+    return {conv_id: tag for tag, conv_id in tags.items()}
+
+
+def resolve_conversation_tag(
+    conv_ref: str, tags: Dict[str, str], namespace: Optional[str] = None
+) -> str:
+    """
+    Resolves a --conv argument that may be a tag name: exact tag match first,
+    then a unique tag prefix. Anything else is returned unchanged so it can be
+    matched as a conversation ID prefix/suffix, within `namespace` if given
+    (see utils.qualify_conversation_ref()).
+    """
+    if not conv_ref:
+        return conv_ref
+    if not tags:
+        return utils.qualify_conversation_ref(conv_ref, namespace)
+    if conv_ref in tags:
+        log.debug(f"Resolved tag '{conv_ref}' to conversation {tags[conv_ref]}")
+        return tags[conv_ref]
+    prefix_matches = [tag for tag in tags if tag.startswith(conv_ref)]
+    if len(prefix_matches) == 1:
+        tag = prefix_matches[0]
+        log.debug(f"Resolved tag prefix '{conv_ref}' to '{tag}' ({tags[tag]})")
+        return tags[tag]
+    return utils.qualify_conversation_ref(conv_ref, namespace)
+
+
+# --- Watch State ---
+# Per log file read positions of `mcpdiff watch`, so a restart resumes where the
+# previous run stopped: {"<log_file_source>": {"inode", "offset", "lines"}}
+WATCH_STATE_FILE = "watch_state.json"
+
+
+def load_watch_state(history_root: Path) -> Dict[str, Dict[str, int]]:
+    """Loads saved watch positions. Missing or invalid files yield {}."""
+    state_path = history_root / WATCH_STATE_FILE
+    if not state_path.is_file():
+        return {}
+    try:
+        with open(state_path, "r", encoding="utf-8") as f:
+            state = json.load(f)
+        return state if isinstance(state, dict) else {}
+    except (IOError, json.JSONDecodeError) as e:
+        log.warning(f"Ignoring unreadable watch state {state_path}: {e}")
+        return {}
+
+
+def save_watch_state(history_root: Path, state: Dict[str, Dict[str, int]]) -> None:
+    """Writes watch positions atomically. Failures are logged, not raised."""
+    state_path = history_root / WATCH_STATE_FILE
+    temp_path = state_path.with_suffix(state_path.suffix + ".tmp" + str(os.getpid()))
+    try:
+        with open(temp_path, "w", encoding="utf-8") as f:
+            json.dump(state, f, indent=2, sort_keys=True)
+            f.write("\n")
+        os.replace(temp_path, state_path)
+    except IOError as e:
+        log.warning(f"Could not save watch state {state_path}: {e}")
+        if temp_path.exists():
+            try:
+                os.remove(temp_path)
+            except OSError:
+                pass
+
+
+def read_new_log_entries(
+    log_file: Path, position: Optional[Dict[str, int]]
+) -> Tuple[List[Dict[str, Any]], Dict[str, int]]:
+    """
+    Returns the entries appended to a log file since `position` and the new
+    position. Logs are rewritten via rename, so when the inode changed or the
+    file shrank, the file is re-read and the first `lines` entries are skipped.
+    Only complete lines are consumed; a partially written last line is left
+    for the next call.
+    """
+    stat = log_file.stat()
+    position = position or {"inode": stat.st_ino, "offset": 0, "lines": 0}
+    same_file = position.get("inode") == stat.st_ino
+    if same_file and stat.st_size == position.get("offset"):
+        return [], position
+
+    resume = same_file and stat.st_size > position.get("offset", 0)
+    with open(log_file, "rb") as f:
+        if resume:
+            f.seek(position["offset"])
+            data = f.read()
+            skip = 0
+            offset = position["offset"]
+        else:
+            data = f.read()
+            skip = position.get("lines", 0)
+            offset = 0
+
+    end = data.rfind(b"\n") + 1  # Ignore a trailing partial line
+    lines = [line for line in data[:end].decode("utf-8").splitlines() if line]
+    entries = []
+    for line in lines[skip:]:
+        try:
+            entry = utils.decode_log_paths(json.loads(line))
+            entries.append(default_migrator.upgrade(entry))
+        except json.JSONDecodeError as e:
+            log.warning(f"Skipping invalid JSON line in {log_file.name}: {e}")
+        except SchemaVersionError as e:
+            log.warning(f"Skipping line in {log_file.name}: {e}")
+    source = utils.get_log_file_source(log_file)
+    for entry in entries:
+        entry["log_file_source"] = source
+    # Logs live in <workspace>/.mcp/<history>/logs/
+    normalize_entry_paths(entries, utils.get_log_history_root(log_file).parent.parent)
+
+    lines_total = (position.get("lines", 0) if resume else 0) + len(lines)
+    new_position = {
+        "inode": stat.st_ino,
+        "offset": offset + end,
+        "lines": lines_total,
+    }
+    return entries, new_position
+
+
 # --- History Entry Management ---
 
 
+def normalize_entry_paths(
+    entries: List[Dict[str, Any]], workspace_root: Path
+) -> List[Dict[str, Any]]:
+    """
+    Rewrites `file_path` and `source_path` of each entry in place to the
+    workspace-relative form, so that entries logged with absolute and relative
+    paths for the same file compare equal. The log files are not modified.
+    """
+    for entry in entries:
+        for key in ("file_path", "source_path"):
+            if entry.get(key):
+                entry[key] = utils.normalize_log_path(entry[key], workspace_root)
+    return entries
+
+
+def read_log_entries(
+    log_file: Path,
+    history_root: Path,
+    lock_timeout: Optional[float] = None,
+    locked: bool = True,
+) -> List[Dict[str, Any]]:
+    """Entries of one log file, tagged with their source and workspace-relative."""
+    entries = utils.read_log_file(log_file, lock_timeout=lock_timeout, locked=locked)
+    # Add log file source to each entry for later updates
+    source = utils.get_log_file_source(log_file)
+    for entry in entries:
+        entry["log_file_source"] = source
+    normalize_entry_paths(entries, history_root.parent.parent)
+    return entries
+
+
+def sort_entries(entries: List[Dict[str, Any]]) -> None:
+    """Sorts entries chronologically (timestamp then index), in place."""
+    try:
+        entries.sort(
+            key=lambda e: (
+                utils.parse_timestamp(e.get("timestamp", 0)),
+                e.get("tool_call_index", float("inf")),
+            )
+        )
+    except Exception as e:
+        log.warning(f"Error sorting entries: {e}. Entries might be out of order.")
+
+
 def find_all_entries(
     history_root: Path, lock_timeout: Optional[float] = None
 ) -> List[Dict[str, Any]]:
@@ -77,7 +380,7 @@ def find_all_entries(
     if not logs_dir.is_dir():
         return []
 
-    log_files = list(logs_dir.glob("*.log"))
+    log_files = utils.find_log_files(logs_dir)
     log.debug(f"Found {len(log_files)} log files in {logs_dir}")
 
     # Consider parallelizing read if many log files and performance is critical
@@ -85,10 +388,7 @@ def find_all_entries(
         try:
             log.debug(f"Reading log file: {log_file}")
             # Pass the actual lock timeout value
-            entries = utils.read_log_file(log_file, lock_timeout=lock_timeout)
-            # Add log file source to each entry for later updates
-            for entry in entries:
-                entry["log_file_source"] = log_file.name
+            entries = read_log_entries(log_file, history_root, lock_timeout)
             all_entries.extend(entries)
             log.debug(f"Found {len(entries)} entries in {log_file}")
         except HistoryError as e:
@@ -96,17 +396,7 @@ def find_all_entries(
         except Exception as e:
             log.warning(f"Unexpected error reading log file {log_file}: {e}")
 
-    # Sort entries chronologically (timestamp then index)
-    try:
-        all_entries.sort(
-            key=lambda e: (
-                utils.parse_timestamp(e.get("timestamp", 0)),
-                e.get("tool_call_index", float("inf")),
-            )
-        )
-    except Exception as e:
-        log.warning(f"Error sorting entries: {e}. Entries might be out of order.")
-
+    sort_entries(all_entries)
     log.debug(f"Total entries found and sorted: {len(all_entries)}")
     return all_entries
 
@@ -119,63 +409,344 @@ def filter_entries(
     time_filter: Optional[str] = None,
     op_type: Optional[str] = None,
     limit: Optional[int] = 50,  # Allow None for no limit internally
+    since_edit_id: Optional[str] = None,
+    until_edit_id: Optional[str] = None,
+    session_id: Optional[str] = None,
+    before: Optional[float] = None,
+    after: Optional[float] = None,
 ) -> List[Dict[str, Any]]:
-    """Filter entries based on criteria."""
-    filtered = entries  # Start with all entries
+    """
+    Filter entries based on criteria (see iter_filtered_entries()). With a
+    positive `limit`, the newest `limit` matches are returned, newest first;
+    with 0 all matches, newest first; with None all of them, oldest first.
+    """
+    filtered = list(
+        iter_filtered_entries(
+            entries,
+            conv_id=conv_id,
+            file_path=file_path,
+            status=status,
+            time_filter=time_filter,
+            op_type=op_type,
+            since_edit_id=since_edit_id,
+            until_edit_id=until_edit_id,
+            session_id=session_id,
+            before=before,
+            after=after,
+        )
+    )
 
-    if conv_id:
-        conv_id_lower = conv_id.lower()
-        filtered = [
-            e
-            for e in filtered
-            if (cid := e.get("conversation_id"))
-            and (
-                cid.lower().startswith(conv_id_lower)
-                or cid.lower().endswith(conv_id_lower)
+    # find_all_entries sorts oldest first; displays want the newest first
+    if limit is not None and limit > 0:
+        return filtered[-limit:][::-1]
+    elif limit == 0:  # Allow showing all with limit=0
+        return filtered[::-1]
+    else:  # limit is None or negative (no limit requested internally)
+        return filtered  # Return as is (oldest first)
+
+
+def iter_filtered_entries(
+    entries: List[Dict[str, Any]],
+    conv_id: Optional[str] = None,
+    file_path: Optional[str] = None,
+    status: Optional[str] = None,
+    time_filter: Optional[str] = None,
+    op_type: Optional[str] = None,
+    since_edit_id: Optional[str] = None,
+    until_edit_id: Optional[str] = None,
+    session_id: Optional[str] = None,
+    before: Optional[float] = None,
+    after: Optional[float] = None,
+) -> Iterator[Dict[str, Any]]:
+    """
+    The entries matching every given filter, in their order, checked one at a
+    time so that counting them builds no lists. `since_edit_id`/`until_edit_id`
+    keep the entries after/before the named edit in its conversation, by
+    tool_call_index, and require `conv_id`. `session_id` keeps the
+    conversations of that shell session (see conversations_in_session()).
+    `before`/`after` (epoch seconds, see utils.parse_time_point()) keep entries
+    logged before `before` and at or after `after`.
+    """
+    session_convs = (
+        conversations_in_session(entries, session_id) if session_id else None
+    )
+    conv_id_lower = conv_id.lower() if conv_id else None
+
+    def conv_matches(entry: Dict[str, Any]) -> bool:
+        cid = (entry.get("conversation_id") or "").lower()
+        return bool(cid) and (
+            cid.startswith(conv_id_lower) or cid.endswith(conv_id_lower)
+        )
+
+    in_edit_range: Optional[Set[int]] = None
+    if since_edit_id or until_edit_id:
+        if not conv_id:
+            raise HistoryError("--since-edit-id and --until-edit-id require --conv.")
+        conv_entries = [e for e in entries if conv_matches(e)]
+        in_edit_range = {
+            id(e)
+            for e in filter_edit_range(
+                conv_entries, conv_id, since_edit_id, until_edit_id
             )
-        ]
+        }
+
+    status_lower = status.lower() if status else None
+    op_type_lower = op_type.lower() if op_type else None
+    seconds = utils.parse_time_filter(time_filter) if time_filter else None
+    if seconds is not None:
+        after = max(after or 0.0, time.time() - seconds)
+
+    for e in entries:
+        if session_convs is not None and e.get("conversation_id") not in session_convs:
+            continue
+        if conv_id_lower and not conv_matches(e):
+            continue
+        if in_edit_range is not None and id(e) not in in_edit_range:
+            continue
+        if file_path and not (
+            # Separator- and (on Windows) case-insensitive substring match
+            (fp := e.get("file_path"))
+            and utils.path_matches_filter(fp, file_path)
+        ):
+            continue
+        if status_lower and e.get("status", "").lower() != status_lower:
+            continue
+        if op_type_lower and e.get("operation", "").lower() != op_type_lower:
+            continue
+        if before is not None or after is not None:
+            timestamp = utils.parse_timestamp(e.get("timestamp", 0))
+            if before is not None and timestamp >= before:
+                continue
+            if after is not None and timestamp < after:
+                continue
+        yield e
+
+
+# The keyword arguments of iter_filtered_entries(), which the daemon accepts
+ENTRY_FILTERS = (
+    "conv_id",
+    "file_path",
+    "status",
+    "time_filter",
+    "op_type",
+    "since_edit_id",
+    "until_edit_id",
+    "session_id",
+    "before",
+    "after",
+)
 
-    if file_path:
-        # Normalize path separators for comparison
-        norm_filter_path = file_path.replace("\\", "/")
-        filtered = [
-            e
-            for e in filtered
-            if (fp := e.get("file_path")) and norm_filter_path in fp.replace("\\", "/")
-        ]
+# What `status --count --group-by` counts per: option value -> entry field
+COUNT_GROUP_FIELDS = {
+    "conversation": "conversation_id",
+    "file": "file_path",
+    "status": "status",
+    "operation": "operation",
+}
 
-    if status:
-        status_lower = status.lower()
-        filtered = [e for e in filtered if e.get("status", "").lower() == status_lower]
 
-    if op_type:
-        op_type_lower = op_type.lower()
-        filtered = [
-            e for e in filtered if e.get("operation", "").lower() == op_type_lower
-        ]
+def count_filtered_entries(
+    entries: List[Dict[str, Any]], group_by: Optional[str] = None, **filters: Any
+) -> Any:
+    """
+    The number of entries iter_filtered_entries(entries, **filters) yields, or
+    with `group_by` (a COUNT_GROUP_FIELDS key) a dict of counts per value of
+    that field, largest first.
+    """
+    matches = iter_filtered_entries(entries, **filters)
+    if not group_by:
+        return sum(1 for _ in matches)
+    field_name = COUNT_GROUP_FIELDS[group_by]
+    counts: Dict[str, int] = {}
+    for entry in matches:
+        key = str(entry.get(field_name))
+        counts[key] = counts.get(key, 0) + 1
+    return dict(sorted(counts.items(), key=lambda item: (-item[1], item[0])))
+
+
+def conversations_in_session(
+    entries: List[Dict[str, Any]], session_id: str
+) -> Set[str]:
+    """
+    IDs of the conversations with an edit logged in the shell session
+    `session_id` (the server's MCP_SESSION_ID), matched exactly. Entries mcpdiff
+    writes itself have no session, so conversations are matched by their edits.
+    """
+    return {
+        e["conversation_id"]
+        for e in entries
+        if e.get("session_id") == session_id and e.get("conversation_id")
+    }
 
-    if time_filter:
-        seconds = utils.parse_time_filter(time_filter)
-        if seconds is not None:
-            cutoff_timestamp = time.time() - seconds
-            filtered = [
-                e
-                for e in filtered
-                if utils.parse_timestamp(e.get("timestamp", 0)) >= cutoff_timestamp
+
+def conversation_forks(
+    entries: List[Dict[str, Any]],
+) -> Dict[str, Tuple[str, Optional[str]]]:
+    """
+    (parent_conversation_id, forked_at) of each forked conversation, taken from
+    the first of its entries that records a parent. A conversation naming
+    itself as its parent is not forked.
+    """
+    forks: Dict[str, Tuple[str, Optional[str]]] = {}
+    for entry in entries:
+        conv_id = entry.get("conversation_id")
+        parent = entry.get("parent_conversation_id")
+        if conv_id and parent and parent != conv_id and conv_id not in forks:
+            forks[conv_id] = (parent, entry.get("forked_at"))
+    return forks
+
+
+def conversation_lineage(
+    entries: List[Dict[str, Any]], conv_id: str
+) -> List[Tuple[str, Optional[str]]]:
+    """
+    The ancestors of a conversation, nearest first, as (conversation_id,
+    forked_at) with forked_at the edit of that ancestor its child was forked
+    at. A cycle of parents ends the chain at the first repeated conversation.
+    """
+    forks = conversation_forks(entries)
+    lineage: List[Tuple[str, Optional[str]]] = []
+    seen = {conv_id}
+    while conv_id in forks:
+        parent, forked_at = forks[conv_id]
+        if parent in seen:
+            break
+        lineage.append((parent, forked_at))
+        seen.add(parent)
+        conv_id = parent
+    return lineage
+
+
+def lineage_entries(
+    entries: List[Dict[str, Any]], conv_id: str
+) -> List[Dict[str, Any]]:
+    """
+    The entries of a conversation plus those of its ancestors it was forked
+    from: of each parent, its entries up to and including the forked_at edit
+    (by tool_call_index), or without forked_at those logged before the child's
+    first entry. A forked_at unknown to the parent keeps all of its entries.
+    Entries keep their order in `entries`.
+    """
+    keep: Dict[str, Callable[[Dict[str, Any]], bool]] = {conv_id: lambda e: True}
+    child = conv_id
+    for parent, forked_at in conversation_lineage(entries, conv_id):
+        parent_entries = [e for e in entries if e.get("conversation_id") == parent]
+        fork_entry = next(
+            (e for e in parent_entries if forked_at and e.get("edit_id") == forked_at),
+            None,
+        )
+        if fork_entry is not None:
+            index = fork_entry.get("tool_call_index", 0)
+            keep[parent] = (
+                lambda e, index=index: e.get("tool_call_index", 0) <= index
+            )
+        elif forked_at:
+            keep[parent] = lambda e: True
+        else:
+            child_times = [
+                utils.parse_timestamp(e.get("timestamp", 0))
+                for e in entries
+                if e.get("conversation_id") == child
+                and e.get("tool_name") != "mcpdiff"
             ]
+            start = min(child_times, default=float("inf"))
+            keep[parent] = (
+                lambda e, start=start: utils.parse_timestamp(e.get("timestamp", 0))
+                < start
+            )
+        child = parent
+    return [
+        e
+        for e in entries
+        if e.get("conversation_id") in keep and keep[e["conversation_id"]](e)
+    ]
 
-    # Apply limit *after* all filtering, return latest first for display if limited
-    # Note: find_all_entries sorts oldest first. For display, often newest is desired.
-    # Let's reverse *after* filtering if a limit is applied.
-    if limit is not None and limit > 0:
-        # Return the *most recent* 'limit' entries that match
-        return filtered[-limit:][
-            ::-1
-        ]  # Get last 'limit', then reverse to show newest first
-    elif limit == 0:  # Allow showing all with limit=0
-        return filtered[::-1]  # Reverse all to show newest first
-    else:  # limit is None or negative (no limit requested internally)
-        return filtered  # Return as is (oldest first)
+
+def conversation_tree(
+    summaries: List[Dict[str, Any]],
+) -> List[Tuple[int, Dict[str, Any]]]:
+    """
+    Orders conversation summaries as a tree of forks: each conversation is
+    followed by the ones forked from it, one level deeper, keeping the order of
+    `summaries` among siblings. Returns (depth, summary) pairs; a fork whose
+    parent is not among the summaries is shown at depth 0.
+    """
+    by_id = {s["conversation_id"]: s for s in summaries}
+    children: Dict[str, List[Dict[str, Any]]] = {}
+    roots = []
+    for summary in summaries:
+        parent = summary.get("parent_conversation_id")
+        if parent in by_id and parent != summary["conversation_id"]:
+            children.setdefault(parent, []).append(summary)
+        else:
+            roots.append(summary)
+
+    result: List[Tuple[int, Dict[str, Any]]] = []
+    placed: Set[str] = set()
+
+    def place(summary: Dict[str, Any], depth: int) -> None:
+        if summary["conversation_id"] in placed:
+            return
+        placed.add(summary["conversation_id"])
+        result.append((depth, summary))
+        for child in children.get(summary["conversation_id"], []):
+            place(child, depth + 1)
+
+    for root in roots:
+        place(root, 0)
+    # Conversations in a cycle of parents have no root; list them flat
+    for summary in summaries:
+        place(summary, 0)
+    return result
+
+
+def filter_edit_range(
+    conv_entries: List[Dict[str, Any]],
+    conv_id: str,
+    since_edit_id: Optional[str] = None,
+    until_edit_id: Optional[str] = None,
+) -> List[Dict[str, Any]]:
+    """
+    The entries of `conv_entries` (already filtered by `conv_id`) strictly
+    between the two edits, by tool_call_index, which only increases within a
+    conversation. Both edits must be in the same conversation. mcpdiff's own
+    entries (snapshots, reverts) have negative indexes and are left out.
+    """
+    bounds: Dict[str, Tuple[str, int]] = {}
+    for option, id_prefix in (
+        ("--since-edit-id", since_edit_id),
+        ("--until-edit-id", until_edit_id),
+    ):
+        if not id_prefix:
+            continue
+        entry = find_entry_by_id(conv_entries, id_prefix)
+        if not entry:
+            raise HistoryError(
+                f"{option}: no edit '{id_prefix}' in conversation '{conv_id}'."
+            )
+        index = entry.get("tool_call_index")
+        if not isinstance(index, int) or index < 0:
+            raise HistoryError(
+                f"{option}: {entry.get('edit_id')} is not a tool call and has no usable tool_call_index."
+            )
+        bounds[option] = (entry.get("conversation_id"), index)
+
+    conv_ids = {cid for cid, _ in bounds.values()}
+    if len(conv_ids) > 1:
+        raise HistoryError(
+            "--since-edit-id and --until-edit-id name edits in different conversations."
+        )
+    (range_conv_id,) = conv_ids
+    since = bounds.get("--since-edit-id", (None, -1))[1]
+    until = bounds.get("--until-edit-id", (None, None))[1]
+    return [
+        e
+        for e in conv_entries
+        if e.get("conversation_id") == range_conv_id
+        and isinstance(call_index := e.get("tool_call_index"), int)
+        and call_index > since
+        and (until is None or call_index < until)
+    ]
 
 
 def find_entry_by_id(
@@ -276,109 +847,466 @@ def find_entries_by_conversation(
     return matching
 
 
+def find_conversations_for_files(
+    entries: List[Dict[str, Any]], file_paths: List[str]
+) -> List[str]:
+    """
+    Conversations with edits to any of `file_paths` (either end of a move).
+    Re-applying one of these files replays the edits of all of them.
+    """
+    paths = set(file_paths)
+    return sorted(
+        {
+            e["conversation_id"]
+            for e in entries
+            if e.get("conversation_id")
+            and (e.get("file_path") in paths or e.get("source_path") in paths)
+        }
+    )
+
+
+# --- Dependent Edits ---
+def find_parent_entry(
+    entry: Dict[str, Any], all_entries: List[Dict[str, Any]]
+) -> Optional[Dict[str, Any]]:
+    """The entry named by `entry`'s parent_edit_id, or None if unset or unknown."""
+    parent_id = entry.get("parent_edit_id")
+    if not parent_id:
+        return None
+    for candidate in all_entries:
+        if candidate.get("edit_id") == parent_id:
+            return candidate
+    log.debug(f"Parent {parent_id} of edit {entry.get('edit_id')} not found.")
+    return None
+
+
+def count_children(all_entries: List[Dict[str, Any]]) -> Dict[str, int]:
+    """Number of entries naming each edit ID as their parent_edit_id."""
+    counts: Dict[str, int] = {}
+    for entry in all_entries:
+        parent_id = entry.get("parent_edit_id")
+        if parent_id:
+            counts[parent_id] = counts.get(parent_id, 0) + 1
+    return counts
+
+
+def find_dependent_entries(
+    edit_ids: List[str], all_entries: List[Dict[str, Any]]
+) -> List[Tuple[Dict[str, Any], int]]:
+    """
+    All transitive children of the given edits, as (entry, depth) in depth-first
+    order so that every parent comes before its children. Depth 1 is a direct
+    child. Edits in `edit_ids` themselves are not included.
+    """
+    children: Dict[str, List[Dict[str, Any]]] = {}
+    for entry in all_entries:
+        parent_id = entry.get("parent_edit_id")
+        if parent_id:
+            children.setdefault(parent_id, []).append(entry)
+
+    seen = set(edit_ids)
+    dependents: List[Tuple[Dict[str, Any], int]] = []
+
+    def visit(edit_id: str, depth: int) -> None:
+        for child in children.get(edit_id, []):
+            child_id = child.get("edit_id")
+            if child_id in seen:
+                continue  # Already listed, or a cycle
+            seen.add(child_id)
+            dependents.append((child, depth))
+            visit(child_id, depth + 1)
+
+    for edit_id in edit_ids:
+        visit(edit_id, 1)
+    return dependents
+
+
+def find_new_conversation_files(
+    conv_id: str,
+    all_entries: List[Dict[str, Any]],
+    history_root: Path,
+    lock_timeout: Optional[float] = None,
+) -> Dict[str, int]:
+    """
+    Re-reads a conversation's logs and returns {file_path: count} of edits the
+    server logged since `all_entries` was read. System entries (snapshots and
+    reverts written by mcpdiff itself) are ignored. Source paths of moves count
+    as touched files too.
+    """
+    known_ids = {e.get("edit_id") for e in all_entries}
+    new_files: Dict[str, int] = {}
+    entries = utils.read_all_shards(history_root, conv_id, lock_timeout)
+    for entry in normalize_entry_paths(entries, history_root.parent.parent):
+        if entry.get("edit_id") in known_ids or entry.get("tool_name") == "mcpdiff":
+            continue
+        for path in (entry.get("file_path"), entry.get("source_path")):
+            if path:
+                new_files[path] = new_files.get(path, 0) + 1
+    if new_files:
+        log.debug(f"Conversation {conv_id} has new edits since planning: {new_files}")
+    return new_files
+
+
 def update_entry_status(
     entry_to_update: Dict[str, Any],
     new_status: str,
     history_root: Path,
     lock_timeout: Optional[float] = None,
+    invocation: Optional[audit.Invocation] = None,
 ) -> bool:
-    """Update the status of a specific entry in its log file."""
-    edit_id = entry_to_update.get("edit_id")
-    log_file_name = entry_to_update.get(
-        "log_file_source"
-    )  # Use the source log file name
-
-    if not edit_id or not log_file_name:
-        log.error(
-            f"Cannot update entry: missing edit_id or log_file_source. Entry: {entry_to_update}"
-        )
-        return False
+    """
+    Update the status of a specific entry in its log file.
+    Returns True if the entry now has `new_status` (including if it already had it).
+    """
+    result = update_entries_status(
+        [entry_to_update],
+        new_status,
+        history_root,
+        lock_timeout=lock_timeout,
+        invocation=invocation,
+    )
+    return not result["failed"]
 
-    log_file_path = history_root / LOGS_DIR / log_file_name
-    if not log_file_path.is_file():
-        log.error(
-            f"Log file '{log_file_path}' not found for updating entry '{edit_id}'."
-        )
-        return False
 
-    try:
-        # Read all entries from the specific log file
-        # Pass the specific timeout value
-        entries = utils.read_log_file(log_file_path, lock_timeout=lock_timeout)
+def update_entries_status(
+    entries: List[Dict[str, Any]],
+    new_status: str,
+    history_root: Path,
+    lock_timeout: Optional[float] = None,
+    allowed_from: Optional[List[str]] = None,
+    invocation: Optional[audit.Invocation] = None,
+) -> Dict[str, Any]:
+    """
+    Sets the status of several entries, possibly spread over several log files.
+    All targets are collected and validated first; entries already in
+    `new_status` are left alone, and entries whose current status is not in
+    `allowed_from` (if given) are skipped. The affected logs are then written
+    with write_log_files(), and the in-memory entries are updated only for
+    logs that were written; the targets of the others fail.
+
+    Returns {'updated': [...], 'already': [...], 'skipped': [(entry, reason)],
+    'failed': [(entry, error)], 'changed': [(conversation_id, file_path)]},
+    where 'changed' lists the distinct pairs whose status actually changed.
+    """
+    result: Dict[str, Any] = {
+        "updated": [],
+        "already": [],
+        "skipped": [],
+        "failed": [],
+        "changed": [],
+    }
 
-        updated = False
-        for i, entry in enumerate(entries):
-            if entry.get("edit_id") == edit_id:
-                if entry.get("status") == new_status:
-                    log.debug(
-                        f"Entry {edit_id} already has status {new_status}. No update needed."
-                    )
-                    return True  # Considered success
+    # 1. Collect and validate targets, grouped by log file
+    targets_by_log: Dict[str, List[Dict[str, Any]]] = {}
+    for entry in entries:
+        edit_id = entry.get("edit_id")
+        status = (entry.get("status") or "unknown").lower()
+        if status == new_status:
+            log.debug(f"Entry {edit_id} already has status {new_status}.")
+            result["already"].append(entry)
+        elif allowed_from is not None and status not in allowed_from:
+            result["skipped"].append((entry, status))
+        elif not edit_id or not entry.get("log_file_source"):
+            log.error(f"Cannot update entry without edit_id/log_file_source: {entry}")
+            result["failed"].append((entry, "missing edit_id or log file source"))
+        else:
+            targets_by_log.setdefault(entry["log_file_source"], []).append(entry)
 
-                log.debug(
-                    f"Updating entry {edit_id} in {log_file_name}: status -> {new_status}"
-                )
-                entries[i]["status"] = new_status
-                # Use consistent ISO 8601 format with Z
-                entries[i]["updated_at"] = (
-                    datetime.now(timezone.utc)
-                    .isoformat(timespec="milliseconds")
-                    .replace("+00:00", "Z")
-                )
-                updated = True
-                break  # Assumes edit_id is unique within a log file
+    # 2. Apply the new status to fresh copies of the affected logs
+    updates: Dict[Path, List[Dict[str, Any]]] = {}
+    targets_by_path: Dict[Path, List[Dict[str, Any]]] = {}
+    timestamp = (
+        datetime.now(timezone.utc)
+        .isoformat(timespec="milliseconds")
+        .replace("+00:00", "Z")
+    )
+    for log_file_name, targets in targets_by_log.items():
+        log_file_path = history_root / LOGS_DIR / log_file_name
+        try:
+            if not log_file_path.is_file():
+                raise HistoryError(f"log file {log_file_name} not found")
+            log_entries = utils.read_log_file(log_file_path, lock_timeout=lock_timeout)
+        except HistoryError as e:
+            result["failed"].extend((t, str(e)) for t in targets)
+            continue
+        by_id = {e.get("edit_id"): e for e in log_entries}
+        found = []
+        for target in targets:
+            log_entry = by_id.get(target["edit_id"])
+            if log_entry is None:
+                log.error(f"Entry {target['edit_id']} not found in {log_file_path}.")
+                result["failed"].append((target, f"not found in {log_file_name}"))
+                continue
+            log_entry["status"] = new_status
+            log_entry["updated_at"] = timestamp
+            found.append(target)
+        if found:
+            updates[log_file_path] = log_entries
+            targets_by_path[log_file_path] = found
+
+    # 3. Write the affected logs; the targets of logs not written fail
+    committed, error = utils.write_log_files(updates, lock_timeout=lock_timeout)
+    for log_file_path, targets in targets_by_path.items():
+        if log_file_path not in committed:
+            result["failed"].extend((t, error or "not written") for t in targets)
+            continue
+        for target in targets:
+            target["status"] = new_status
+            target["updated_at"] = timestamp
+            result["updated"].append(target)
+            pair = (target.get("conversation_id"), target.get("file_path"))
+            if pair not in result["changed"]:
+                result["changed"].append(pair)
+    if result["updated"]:
+        log.info(f"Updated status of {len(result['updated'])} entries to {new_status}")
+    if result["updated"] or result["failed"]:
+        if not result["failed"]:
+            outcome = "success"
+        else:
+            outcome = "partial" if result["updated"] else "failure"
+        audit.record(
+            history_root,
+            new_status,
+            [e["edit_id"] for e in result["updated"]],
+            outcome,
+            invocation=invocation,
+            lock_timeout=lock_timeout,
+            failed=[
+                {"edit_id": e.get("edit_id"), "error": error}
+                for e, error in result["failed"]
+            ],
+        )
+    return result
 
-        if not updated:
-            log.error(
-                f"Entry {edit_id} not found in log file {log_file_path}. Cannot update status."
-            )
-            return False
 
-        # Write back all entries to the same log file
-        utils.write_log_file(log_file_path, entries, lock_timeout=lock_timeout)
-        log.info(
-            f"Successfully updated status of entry {edit_id} to {new_status} in {log_file_name}"
-        )
-        return True
+def format_status_update_summary(result: Dict[str, Any], new_status: str) -> str:
+    """
+    One-line summary of an update_entries_status() result, e.g.
+    "3 updated, 2 already accepted, 1 skipped: rejected".
+    """
+    parts = [f"{len(result['updated'])} updated"]
+    if result["already"]:
+        parts.append(f"{len(result['already'])} already {new_status}")
+    skip_counts: Dict[str, int] = {}
+    for _, reason in result["skipped"]:
+        skip_counts[reason] = skip_counts.get(reason, 0) + 1
+    for reason, count in sorted(skip_counts.items()):
+        parts.append(f"{count} skipped: {reason}")
+    if result["failed"]:
+        parts.append(f"{len(result['failed'])} failed")
+    return ", ".join(parts)
+
+
+# --- Review Notes ---
+def set_review_notes(
+    entry: Dict[str, Any],
+    notes: Optional[str],
+    history_root: Path,
+    lock_timeout: Optional[float] = None,
+) -> None:
+    """
+    Sets the `review_notes` of an entry in its log file, or removes them if
+    `notes` is empty. An entry older than schema version 9 is upgraded to the
+    current version along the way; nothing else in it changes, so notes never
+    affect statuses or hashes.
+    """
+    edit_id = entry.get("edit_id")
+    if not edit_id or not entry.get("log_file_source"):
+        raise HistoryError(f"Cannot annotate entry without edit_id/log file: {edit_id}")
+
+    def update(target: Dict[str, Any]) -> None:
+        if notes:
+            # An entry older than the field moves up to the version that has it
+            target.update(default_migrator.upgrade(target))
+            target["review_notes"] = notes
+        else:
+            target.pop("review_notes", None)
 
-    except HistoryError as e:
-        log.error(f"HistoryError updating status for {edit_id} in {log_file_path}: {e}")
-        return False
-    except Exception as e:
-        log.exception(
-            f"Unexpected error updating status for {edit_id} in {log_file_path}: {e}"
-        )
-        return False
+    _update_logged_entry(entry, history_root, update, lock_timeout)
+    update(entry)
 
 
-def get_diff_for_entry(entry: Dict[str, Any], history_root: Path) -> Optional[str]:
-    """Get the diff content for an entry, trying multiple locations."""
+def _update_logged_entry(
+    entry: Dict[str, Any],
+    history_root: Path,
+    update: Callable[[Dict[str, Any]], None],
+    lock_timeout: Optional[float] = None,
+) -> None:
+    """
+    Applies `update` to the stored copy of `entry` in its log file, under the
+    conversation lock. `entry` itself is left as it is.
+    """
     edit_id = entry.get("edit_id")
-    conv_id = entry.get("conversation_id")
-    diff_file_rel_path = entry.get("diff_file")  # Relative path stored in log
+    log_file_name = entry.get("log_file_source")
+    if not edit_id or not log_file_name:
+        raise HistoryError(f"Cannot update entry without edit_id/log file: {edit_id}")
+    log_file_path = history_root / LOGS_DIR / log_file_name
+    conv_lock = utils.ConversationLock(history_root, entry.get("conversation_id", ""))
+    conv_lock.acquire(lock_timeout)
+    with conv_lock:
+        log_entries = utils.read_log_file(
+            log_file_path, lock_timeout=lock_timeout, migrate=False, strict=True
+        )
+        target = next((e for e in log_entries if e.get("edit_id") == edit_id), None)
+        if target is None:
+            raise HistoryError(f"Edit {edit_id} not found in {log_file_name}")
+        update(target)
+        utils.write_log_file(log_file_path, log_entries, lock_timeout=lock_timeout)
 
-    if not edit_id:
-        log.debug(f"Cannot get diff, missing edit_id in entry: {entry}")
+
+def checkpoint_mismatch(entry: Dict[str, Any], history_root: Path) -> Optional[str]:
+    """
+    The hash of an entry's checkpoint if it is not the entry's hash_before,
+    the content the checkpoint was taken of, or None. A mismatch means the
+    file changed between checkpointing and logging (say, the server crashed
+    in between). Entries without a hash_before or a checkpoint on disk are not
+    checked.
+    """
+    checkpoint_rel = entry.get("checkpoint_file")
+    expected_hash = entry.get("hash_before")
+    if not checkpoint_rel or not expected_hash:
+        return None
+    checkpoint_path = history_root / checkpoint_rel
+    if not checkpoint_path.is_file():
         return None
+    actual_hash = utils.calculate_hash(str(checkpoint_path))
+    return actual_hash if actual_hash != expected_hash else None
 
-    # Handle operations without diffs explicitly
-    operation = entry.get("operation", "").lower()
-    if operation == "move":
-        source = entry.get("source_path", "unknown_source")
-        dest = entry.get("file_path", "unknown_dest")
-        return f"OPERATION: MOVE\nSource: {source}\nDestination: {dest}"
-    if (
-        operation in ["create", "delete", "snapshot", "revert"]
-        and not diff_file_rel_path
-    ):
-        # These might legitimately not have diffs sometimes (e.g., snapshot, revert, initial create)
-        log.debug(f"Operation '{operation}' for {edit_id} may not have a diff file.")
-        # Return specific info if possible
-        if operation == "delete":
-            return f"OPERATION: DELETE\nFile: {entry.get('file_path')}"
-        if operation == "create":
-            return f"OPERATION: CREATE\nFile: {entry.get('file_path')}"
-        return f"OPERATION: {operation.upper()}\n(No diff file associated)"
+
+def checkpoint_mismatch_message(entry: Dict[str, Any], actual_hash: str) -> str:
+    return (
+        f"Checkpoint {entry.get('checkpoint_file')} of edit {entry.get('edit_id')} "
+        f"hashes to {actual_hash[:8]}, not its hash_before "
+        f"{(entry.get('hash_before') or '')[:8]}; see mcpdiff rebaseline"
+    )
+
+
+def rebaseline_entry(
+    entry: Dict[str, Any],
+    workspace_root: Path,
+    history_root: Path,
+    lock_timeout: Optional[float] = None,
+    invocation: Optional[audit.Invocation] = None,
+) -> Dict[str, Any]:
+    """
+    Makes the current content of an entry's file its checkpoint: copies the
+    file over the entry's checkpoint (or a new one, if the entry has none) and
+    sets its hash_before to the file's hash, in the log and in `entry`.
+    Records the change in the audit log. Returns {checkpoint_file,
+    old_hash_before, hash_before}.
+    """
+    edit_id = entry.get("edit_id")
+    file_path_rel = entry.get("file_path")
+    live_path = workspace_root / file_path_rel if file_path_rel else None
+    if not live_path or not live_path.is_file():
+        raise HistoryError(
+            f"Cannot rebaseline edit {edit_id}: {utils.display_path(file_path_rel)} does not exist."
+        )
+    checkpoint_rel = entry.get("checkpoint_file") or str(
+        Path(CHECKPOINTS_DIR) / entry.get("conversation_id", "") / f"{edit_id}.chkpt"
+    )
+    checkpoint_path = history_root / checkpoint_rel
+    checkpoint_path.parent.mkdir(parents=True, exist_ok=True)
+    temp_path = checkpoint_path.with_name(checkpoint_path.name + ".tmp")
+    shutil.copy2(live_path, temp_path)
+    new_hash = utils.calculate_hash(str(temp_path))
+    old_hash = entry.get("hash_before")
+
+    def update(target: Dict[str, Any]) -> None:
+        target["checkpoint_file"] = checkpoint_rel
+        target["hash_before"] = new_hash
+
+    try:
+        _update_logged_entry(entry, history_root, update, lock_timeout)
+    except Exception:
+        temp_path.unlink(missing_ok=True)
+        raise
+    os.replace(temp_path, checkpoint_path)
+    update(entry)
+    details = utils.encode_log_paths({"file_path": file_path_rel})
+    audit.record(
+        history_root,
+        "rebaseline",
+        [edit_id],
+        "success",
+        invocation=invocation,
+        checkpoint_file=checkpoint_rel,
+        old_hash_before=old_hash,
+        hash_before=new_hash,
+        **details,
+    )
+    return {
+        "checkpoint_file": checkpoint_rel,
+        "old_hash_before": old_hash,
+        "hash_before": new_hash,
+    }
+
+
+def print_review_notes(entry: Dict[str, Any], color: bool = True) -> None:
+    """Prints the review notes of an entry, if it has any, as `show` does."""
+    notes = entry.get("review_notes")
+    if not notes:
+        return
+    colors = utils.get_colors(color)
+    print(f"{colors.cyan}Review notes:{colors.reset}")
+    for line in notes.splitlines():
+        print(f"  {line}")
+
+
+def format_partial_edit(entry: Dict[str, Any], written: bytes) -> str:
+    """
+    A partial edit for display: its byte range, then the bytes written there as
+    added lines, or as a hex dump if they are not text.
+    """
+    start, end = entry.get("byte_range_start"), entry.get("byte_range_end")
+    header = (
+        f"OPERATION: PARTIAL\nFile: {utils.display_path(entry.get('file_path'))}\n"
+        f"Bytes {start}-{end} replaced with {len(written)} bytes\n"
+    )
+    text: Optional[str] = None
+    if b"\x00" not in written:
+        try:
+            text = written.decode("utf-8")
+        except UnicodeDecodeError:
+            pass
+    if text is None:
+        return header + utils.hex_dump(written, offset=start or 0)
+    added_lines = "".join(f"+{line}" for line in text.splitlines(keepends=True))
+    if added_lines and not added_lines.endswith("\n"):
+        added_lines += "\n\\ No newline at end of range\n"
+    return header + added_lines
+
+
+def get_diff_for_entry(entry: Dict[str, Any], history_root: Path) -> Optional[str]:
+    """Get the diff content for an entry, trying multiple locations."""
+    edit_id = entry.get("edit_id")
+    conv_id = entry.get("conversation_id")
+    diff_file_rel_path = entry.get("diff_file")  # Relative path stored in log
+
+    if not edit_id:
+        log.debug(f"Cannot get diff, missing edit_id in entry: {entry}")
+        return None
+
+    # Handle operations without diffs explicitly
+    operation = entry.get("operation", "").lower()
+    if entry.get("archived") and operation not in utils.MOVE_OPERATIONS:
+        return f"OPERATION: {operation.upper()}\n(Archived: the diff was pruned by `mcpdiff prune`)"
+    if operation in utils.MOVE_OPERATIONS:
+        source = utils.display_path(entry.get("source_path")) or "unknown_source"
+        dest = utils.display_path(entry.get("file_path")) or "unknown_dest"
+        return f"OPERATION: {operation.upper()}\nSource: {source}\nDestination: {dest}"
+    if (
+        operation in ["create", "delete", "snapshot", "revert"]
+        and not diff_file_rel_path
+    ):
+        # These might legitimately not have diffs sometimes (e.g., snapshot, revert, initial create)
+        log.debug(f"Operation '{operation}' for {edit_id} may not have a diff file.")
+        # Return specific info if possible
+        if operation == "delete":
+            return f"OPERATION: DELETE\nFile: {utils.display_path(entry.get('file_path'))}"
+        if operation == "create":
+            return f"OPERATION: CREATE\nFile: {utils.display_path(entry.get('file_path'))}"
+        return f"OPERATION: {operation.upper()}\n(No diff file associated)"
 
     # Potential diff paths to check
     potential_paths: List[Path] = []
@@ -415,7 +1343,31 @@ def get_diff_for_entry(entry: Dict[str, Any], history_root: Path) -> Optional[st
         if abs_path.is_file():
             log.debug(f"Found diff for {edit_id} at: {abs_path}")
             try:
-                content = abs_path.read_text(encoding="utf-8")
+                if entry.get("file_encoding") == BSDIFF_ENCODING:
+                    size = utils.format_size(abs_path.stat().st_size)
+                    return (
+                        f"OPERATION: {operation.upper()}\nFile: {utils.display_path(entry.get('file_path'))}\n"
+                        f"Binary file changed (bsdiff patch, {size})\n"
+                    )
+                if operation == "partial":
+                    return format_partial_edit(entry, abs_path.read_bytes())
+                if operation == "append":
+                    # Append diffs are the raw appended bytes; show them as added lines
+                    appended = abs_path.read_text(encoding="utf-8", errors="replace")
+                    added_lines = "".join(
+                        f"+{line}" for line in appended.splitlines(keepends=True)
+                    )
+                    if added_lines and not added_lines.endswith("\n"):
+                        added_lines += "\n\\ No newline at end of file\n"
+                    return (
+                        f"OPERATION: APPEND\nFile: {utils.display_path(entry.get('file_path'))}\n"
+                        f"{added_lines}"
+                    )
+                # Undecodable bytes (e.g. in header paths) are only displayed
+                content = read_diff_file(abs_path, entry.get("file_encoding"))
+                content = content.encode("utf-8", "surrogateescape").decode(
+                    "utf-8", errors="replace"
+                )
                 # Simple check for diff format (optional, but helpful)
                 if (
                     "--- a/" in content
@@ -439,63 +1391,237 @@ def get_diff_for_entry(entry: Dict[str, Any], history_root: Path) -> Optional[st
     return None  # Diff not found
 
 
-def format_entry_summary(entry: Dict[str, Any]) -> str:
-    """Format a single entry for display in summaries."""
+def _status_color(status: str, colors: utils.Colors = utils.Colors()) -> str:
+    """Color used to display an entry status."""
+    if status == "pending":
+        return colors.yellow
+    if status in ["accepted", "done"]:
+        return colors.green
+    if status in ["rejected", "failed"]:
+        return colors.red
+    return colors.reset
+
+
+def _operation_color(op: str, colors: utils.Colors = utils.Colors()) -> str:
+    """Color used to display an operation type."""
+    return {
+        "edit": colors.blue,
+        "create": colors.green,
+        "replace": colors.yellow,
+        "append": colors.green,
+        "partial": colors.yellow,
+        "snapshot": colors.magenta,
+        "delete": colors.red,
+        "move": colors.cyan,
+        "rename": colors.cyan,
+    }.get(op, colors.blue)
+
+
+def _conv_display(entry: Dict[str, Any], tags: Optional[Dict[str, str]]) -> str:
+    """Short conversation ID, followed by its tag in `tags` if it has one."""
+    conv_id_short = entry.get("conversation_id", "N/A")[:8]
+    tag = (tags or {}).get(entry.get("conversation_id", ""))
+    conv_display = f"{conv_id_short} [{tag}]" if tag else conv_id_short
+    return conv_display[: _conv_column_width(tags)]
+
+
+def format_line_ranges(ranges: List[Dict[str, int]]) -> str:
+    """`conflict_markers` ranges as "lines 3-9, 20-24"."""
+    return "lines " + ", ".join(f"{r['start_line']}-{r['end_line']}" for r in ranges)
+
+
+def format_entry_summary(
+    entry: Dict[str, Any],
+    children: int = 0,
+    tags: Optional[Dict[str, str]] = None,
+    color: bool = True,
+) -> str:
+    """
+    Format a single entry for display in summaries. `children` is the number of
+    edits naming this one as their parent, shown as `[+N children]`. `tags`,
+    conversation_id -> tag as from get_tags_by_conversation(), labels the
+    conversation; pass the same to print_entry_list_header(). Nothing is
+    colored if `color` is off.
+    """
     if not entry:
         return "[Invalid Entry Data]"
+    colors = utils.get_colors(color)
 
     edit_id_short = entry.get("edit_id", "no_id")[:8]
-    conv_id_short = entry.get("conversation_id", "N/A")[:8]
+    conv_width = _conv_column_width(tags)
+    conv_display = _conv_display(entry, tags)
     op = entry.get("operation", "UNK").lower()
     status = entry.get("status", "UNK").lower()
-    file_path = entry.get("file_path", "N/A")
+    file_path = utils.display_path(entry.get("file_path")) or "N/A"
     timestamp_val = entry.get("timestamp", 0)
 
     time_str = utils.format_timestamp_absolute(timestamp_val, True)
 
-    # Status Color
-    status_color = (
-        utils.COLOR_YELLOW
-        if status == "pending"
-        else utils.COLOR_GREEN
-        if status == "accepted" or status == "done"
-        else utils.COLOR_RED
-        if status == "rejected" or status == "failed"
-        else utils.COLOR_RESET
-    )  # Default color
-
-    # Operation Color & Detail
-    op_color = utils.COLOR_BLUE  # Default
-    op_details = op
-    if op == "edit":
-        op_color = utils.COLOR_BLUE
-    elif op == "create":
-        op_color = utils.COLOR_GREEN
-    elif op == "replace":
-        op_color = utils.COLOR_YELLOW
-    elif op == "snapshot":
-        op_color = utils.COLOR_MAGENTA
-    elif op == "delete":
-        op_color = utils.COLOR_RED
-    elif op in ["move", "rename"]:
-        op_color = utils.COLOR_CYAN
-        source = entry.get("source_path", "?")
+    status_color = _status_color(status, colors)
+    op_color = _operation_color(op, colors)
+    if op in utils.MOVE_OPERATIONS:
+        source = utils.display_path(entry.get("source_path")) or "?"
         file_path = f"{source} -> {file_path}"  # Combine paths for display
+    if children:
+        file_path += f" [+{children} {'child' if children == 1 else 'children'}]"
+    if entry.get("conflict_markers"):
+        file_path += f" {colors.red}[CONFLICT]{colors.reset}"
 
-    op_colored = f"{op_color}{op:<9}{utils.COLOR_RESET}"  # Pad to 9 chars
-    status_colored = f"{status_color}{status:<8}{utils.COLOR_RESET}"  # Pad to 8 chars
+    op_colored = f"{op_color}{op:<9}{colors.reset}"  # Pad to 9 chars
+    status_colored = f"{status_color}{status:<8}{colors.reset}"  # Pad to 8 chars
 
     # Ensure consistent spacing
     # Time: 19, Edit ID: 8, Conv ID: 8, Op: 9, Status: 8, File Path: Rest
-    return f"{time_str:<19}  {edit_id_short:8}  {conv_id_short:8}  {op_colored}  {status_colored}  {file_path}"
+    return f"{time_str:<19}  {edit_id_short:8}  {conv_display:{conv_width}}  {op_colored}  {status_colored}  {file_path}"
+
+
+def print_entry_details(entry: Dict[str, Any], color: bool = True) -> None:
+    """Prints the extra per-entry fields shown by `show --verbose`."""
+    colors = utils.get_colors(color)
+    print(f"{colors.cyan}Details:{colors.reset}")
+    print(f"  Tool:        {entry.get('tool_name') or 'N/A'}")
+    if entry.get("parent_edit_id"):
+        print(f"  Parent edit: {entry['parent_edit_id']}")
+    if entry.get("parent_conversation_id"):
+        forked_at = entry.get("forked_at")
+        at = f" at {forked_at}" if forked_at else ""
+        print(f"  Forked from: {entry['parent_conversation_id']}{at}")
+    print(f"  Hash before: {entry.get('hash_before') or 'N/A'}")
+    print(f"  Hash after:  {entry.get('hash_after') or 'N/A'}")
+    if entry.get("conflict_markers"):
+        print(f"  Conflicts:   {format_line_ranges(entry['conflict_markers'])}")
+    if entry.get("checkpoint_file"):
+        print(f"  Checkpoint:  {entry['checkpoint_file']}")
+    ide_context = entry.get("ide_context")
+    if ide_context:
+        print("  IDE context:")
+        for line in json.dumps(ide_context, indent=2, sort_keys=True).splitlines():
+            print(f"    {line}")
+    print("-" * 100)
 
 
-def print_entry_list_header():
-    """Prints the header row for lists of entries."""
+def _conv_column_width(tags: Optional[Dict[str, str]]) -> int:
+    """Width of the conversation column: widened to fit tags when any exist."""
+    if not tags:
+        return 8
+    longest_tag = max(len(tag) for tag in tags.values())
+    return min(8 + 3 + longest_tag, 32)  # "<conv> [<tag>]"
+
+
+def print_entry_list_header(
+    tags: Optional[Dict[str, str]] = None, color: bool = True
+):
+    """Prints the header row for lists of entries labelled with `tags`."""
+    colors = utils.get_colors(color)
     print(
-        f"{utils.COLOR_CYAN}{'Time':<19}  {'Edit ID':8}  {'Conv ID':8}  {'Operation':<9}  {'Status':<8}  {'File Path'}{utils.COLOR_RESET}"
+        f"{colors.cyan}{'Time':<19}  {'Edit ID':8}  {'Conv ID':{_conv_column_width(tags)}}  {'Operation':<9}  {'Status':<8}  {'File Path'}{colors.reset}"
+    )
+    print("-" * 100)
+
+
+# --- Status Fields ---
+# Columns selectable with `status --fields`: log entry fields plus the computed
+# "delta" (lines added/removed by the entry's diff).
+STATUS_FIELDS = [
+    "timestamp",
+    "edit_id",
+    "conversation_id",
+    "tool_call_index",
+    "operation",
+    "status",
+    "file_path",
+    "source_path",
+    "tool_name",
+    "parent_edit_id",
+    "diff_file",
+    "checkpoint_file",
+    "hash_before",
+    "hash_after",
+    "schema_version",
+    "delta",
+]
+
+
+def parse_status_fields(fields_arg: str) -> List[str]:
+    """Parses a comma-separated --fields value (case-insensitive)."""
+    fields = [f.strip().lower() for f in fields_arg.split(",") if f.strip()]
+    unknown = [f for f in fields if f not in STATUS_FIELDS]
+    if unknown or not fields:
+        raise HistoryError(
+            f"Unknown field(s): {', '.join(unknown) or '(none given)'}. Valid fields: {', '.join(STATUS_FIELDS)}"
+        )
+    return fields
+
+
+def count_diff_lines(diff_content: Optional[str]) -> Tuple[int, int]:
+    """Returns (added, removed) line counts of a unified diff."""
+    added = removed = 0
+    for line in (diff_content or "").splitlines():
+        if line.startswith("+") and not line.startswith("+++"):
+            added += 1
+        elif line.startswith("-") and not line.startswith("---"):
+            removed += 1
+    return added, removed
+
+
+def build_status_field_renderers(
+    history_root: Path,
+) -> Dict[str, Callable[[Dict[str, Any]], str]]:
+    """Returns a renderer per status field, turning an entry into cell text."""
+
+    def render_delta(entry: Dict[str, Any]) -> str:
+        if not entry.get("diff_file"):
+            return "-"
+        added, removed = count_diff_lines(get_diff_for_entry(entry, history_root))
+        return f"+{added} -{removed}"
+
+    def render_raw(field: str) -> Callable[[Dict[str, Any]], str]:
+        return lambda entry: "" if entry.get(field) is None else str(entry[field])
+
+    renderers = {field: render_raw(field) for field in STATUS_FIELDS}
+    renderers.update(
+        {
+            "timestamp": lambda e: utils.format_timestamp_absolute(
+                e.get("timestamp", 0), True
+            ),
+            "edit_id": lambda e: e.get("edit_id", "no_id")[:8],
+            "conversation_id": _conv_display,
+            "operation": lambda e: e.get("operation", "UNK").lower(),
+            "status": lambda e: e.get("status", "UNK").lower(),
+            "file_path": lambda e: utils.display_path(e.get("file_path")) or "",
+            "source_path": lambda e: utils.display_path(e.get("source_path")) or "",
+            "delta": render_delta,
+        }
     )
+    return renderers
+
+
+def print_entry_fields(
+    entries: List[Dict[str, Any]], fields: List[str], history_root: Path
+) -> None:
+    """Prints entries as a table of the selected fields, sized to fit the cells."""
+    renderers = build_status_field_renderers(history_root)
+    rows = [[renderers[field](entry) for field in fields] for entry in entries]
+    headers = [field.upper() for field in fields]
+    widths = [
+        max([len(headers[i])] + [len(row[i]) for row in rows])
+        for i in range(len(fields))
+    ]
+    colors = {"status": _status_color, "operation": _operation_color}
+
+    def join(cells: List[str], colorize: bool) -> str:
+        parts = []
+        for i, (field, cell) in enumerate(zip(fields, cells)):
+            padded = cell if i == len(fields) - 1 else f"{cell:<{widths[i]}}"
+            if colorize and field in colors:
+                padded = f"{colors[field](cell)}{padded}{utils.COLOR_RESET}"
+            parts.append(padded)
+        return "  ".join(parts)
+
+    print(f"{utils.COLOR_CYAN}{join(headers, False)}{utils.COLOR_RESET}")
     print("-" * 100)
+    for row in rows:
+        print(join(row, True))
 
 
 def apply_or_revert_edit(
@@ -543,20 +1669,7 @@ def apply_or_revert_edit(
     # --- Get Absolute Paths for History Artifacts ---
     diff_path: Optional[Path] = None
     if diff_file_rel:
-        # Assume diff_file path is relative to DIFFS_DIR
-        # Allow for diff_file potentially having conv_id dir already
-        potential_diff_path = history_root / DIFFS_DIR / diff_file_rel
-        if potential_diff_path.is_file():
-            diff_path = potential_diff_path
-        else:
-            # Fallback: Maybe it's just the filename under conv_id dir?
-            conv_id = entry.get("conversation_id")
-            if conv_id:
-                potential_diff_path_alt = (
-                    history_root / DIFFS_DIR / conv_id / diff_file_rel
-                )
-                if potential_diff_path_alt.is_file():
-                    diff_path = potential_diff_path_alt
+        diff_path = find_diff_file(entry, history_root)
         if not diff_path:
             log.warning(
                 f"Diff file '{diff_file_rel}' specified in entry {edit_id} not found."
@@ -591,30 +1704,13 @@ def apply_or_revert_edit(
                 # Ensure parent dir exists
                 target_path.parent.mkdir(parents=True, exist_ok=True)
                 if diff_path:
-                    # Apply patch to create content
-                    # We need an empty file to patch against
-                    with open(target_path, "w") as f:
-                        pass  # Create empty file
-                    cmd = [
-                        "git",
-                        "apply",
-                        "--verbose",
-                        "--unsafe-paths",
-                        str(diff_path),
-                    ]
-                    log.debug(f"Running apply for create: {' '.join(cmd)}")
-                    result = subprocess.run(
-                        cmd,
-                        cwd=workspace_root,
-                        capture_output=True,
-                        text=True,
-                        check=False,
-                    )
-                    if result.returncode != 0:
-                        log.error(
-                            f"Failed to apply diff for create {edit_id}: {result.stderr}\n{result.stdout}"
-                        )
-                        # Clean up potentially partially created file? Or leave it? Let's leave it.
+                    # Apply patch to create content, starting from an empty file
+                    target_path.write_bytes(b"")
+                    try:
+                        apply_entry_diff(entry, target_path, diff_path)
+                    except PatchError as e:
+                        log.error(f"Failed to apply diff for create {edit_id}: {e}")
+                        # Leave the empty file in place
                         return False
                     log.debug(f"Applied diff for create {edit_id} successfully.")
                     return True
@@ -657,89 +1753,93 @@ def apply_or_revert_edit(
                     log.debug(f"Applying delete: file {target_path} already removed.")
                 return True
 
-        elif operation == "move":
+        elif operation in utils.MOVE_OPERATIONS:
             if not source_path_rel:
-                log.error(f"Cannot process move {edit_id}: missing 'source_path'.")
+                log.error(
+                    f"Cannot process {operation} {edit_id}: missing 'source_path'."
+                )
                 return False
             source_path = workspace_root / source_path_rel
+            # Revert = move the target back to the source
+            from_path, to_path = (
+                (target_path, source_path) if is_revert else (source_path, target_path)
+            )
+            action = "revert" if is_revert else "apply"
+            log.debug(f"{action.capitalize()} {operation}: {from_path} -> {to_path}")
+            if not from_path.exists():
+                if is_revert:
+                    log.error(
+                        f"Cannot revert {operation} {edit_id}: destination {target_path} does not exist."
+                    )
+                    return False
+                # E.g. when applying edits out of order or after manual changes
+                log.warning(
+                    f"Cannot apply {operation} {edit_id}: source {source_path} does not exist. Assuming it was already moved."
+                )
+                # Check if target exists, if so, consider it successful.
+                return target_path.exists()
+            if operation == "rename":
+                # Within one directory, which exists as the file is in it
+                os.rename(from_path, to_path)
+            else:
+                to_path.parent.mkdir(parents=True, exist_ok=True)
+                shutil.move(str(from_path), str(to_path))
+            return True
 
+        elif operation == "append":
+            # The diff file holds the raw appended bytes, not a patch
+            if not diff_path:
+                log.error(
+                    f"Cannot {'revert' if is_revert else 'apply'} append {edit_id}: diff file not found."
+                )
+                return False
+            appended = diff_path.read_bytes()
             if is_revert:
-                # Revert move = move target back to source
-                log.debug(f"Reverting move: moving {target_path} back to {source_path}")
-                if not target_path.exists():
+                # Revert append = truncate the appended bytes, if still at the end
+                current = target_path.read_bytes() if target_path.exists() else b""
+                if not current.endswith(appended):
                     log.error(
-                        f"Cannot revert move {edit_id}: destination {target_path} does not exist."
+                        f"Cannot revert append {edit_id}: {target_path.name} no longer ends with the appended content."
                     )
                     return False
-                source_path.parent.mkdir(parents=True, exist_ok=True)
-                shutil.move(str(target_path), str(source_path))
-                return True
+                with open(target_path, "r+b") as f:
+                    f.truncate(len(current) - len(appended))
             else:
-                # Apply move = move source to target
-                log.debug(f"Applying move: moving {source_path} to {target_path}")
-                if not source_path.exists():
-                    # This might happen if applying edits out of order or after manual changes
-                    log.warning(
-                        f"Cannot apply move {edit_id}: source {source_path} does not exist. Assuming it was already moved."
-                    )
-                    # Check if target exists, if so, consider it successful.
-                    return target_path.exists()
                 target_path.parent.mkdir(parents=True, exist_ok=True)
-                shutil.move(str(source_path), str(target_path))
-                return True
+                with open(target_path, "ab") as f:
+                    f.write(appended)
+            log.debug(
+                f"Successfully {'reverted' if is_revert else 'applied'} append {edit_id} ({len(appended)} bytes)."
+            )
+            return True
 
-        elif operation in ["edit", "replace"]:
-            # Both need a diff file to apply/revert meaningfully
+        elif operation in ["edit", "replace", "partial"]:
+            # All need a diff file to apply/revert meaningfully
             if not diff_path:
                 log.error(
                     f"Cannot {'revert' if is_revert else 'apply'} {operation} {edit_id}: diff file not found."
                 )
                 return False
 
-            # Check if target file exists before applying/reverting patch
-            # git apply might create the file if the diff adds it entirely,
-            # but it's safer if the file exists for standard edits/replaces.
-            # Exception: A 'replace' operation's diff might represent creating the file anew.
-            # Let git apply handle file existence issues.
-            # if not target_path.exists() and operation == "edit":
-            #      log.error(f"Cannot {'revert' if is_revert else 'apply'} edit {edit_id}: target file {target_path} does not exist.")
-            #      return False
-
-            # Use git apply
-            cmd = ["git", "apply", "--verbose", "--unsafe-paths"]
-            if is_revert:
-                cmd.append("-R")  # Apply reverse patch
-            # Apply relative to workspace root
-            cmd.extend(["--directory", str(workspace_root), str(diff_path)])
-
-            log.debug(f"Running: {' '.join(cmd)}")
-            # Run from workspace root context
-            result = subprocess.run(
-                cmd, cwd=workspace_root, capture_output=True, text=True, check=False
+            log.debug(
+                f"{'Reverting' if is_revert else 'Applying'} {diff_path} to {target_path}"
             )
-
-            if result.returncode != 0:
-                # Attempt with --reject flag to see if it applies partially? No, keep it simple.
+            try:
+                apply_entry_diff(entry, target_path, diff_path, reverse=is_revert)
+            except PatchError as e:
                 log.error(
-                    f"Failed to {'revert' if is_revert else 'apply'} {operation} {edit_id} using git apply."
+                    f"Failed to {'revert' if is_revert else 'apply'} {operation} {edit_id}: {e}"
                 )
-                log.error(f"Command: {' '.join(cmd)}")
-                log.error(f"Stderr: {result.stderr}")
-                log.error(f"Stdout: {result.stdout}")
-                # Check common errors
-                if (
-                    "does not exist in index" in result.stderr
-                    and not target_path.exists()
-                ):
+                if not target_path.exists():
                     log.error(f"Hint: Target file {target_path.name} might be missing.")
-                elif "patch does not apply" in result.stderr:
+                else:
                     log.error(
                         "Hint: File content may have changed since the diff was created."
                     )
                 return False
 
             log.debug(
-                f"Successfully {'reverted' if is_revert else 'applied'} {operation} {edit_id} using git apply."
+                f"Successfully {'reverted' if is_revert else 'applied'} {operation} {edit_id}."
             )
             return True
 
@@ -779,22 +1879,42 @@ def get_relevant_history_for_file(
 
 
 def find_closest_checkpoint(
-    target_entry_index: int, file_entries: List[Dict[str, Any]], history_root: Path
+    target_entry_index: int,
+    file_entries: List[Dict[str, Any]],
+    history_root: Path,
+    is_applied: Optional[Callable[[Dict[str, Any]], bool]] = None,
+    exists: Callable[[Path], bool] = Path.is_file,
 ) -> Tuple[Optional[Path], int]:
     """
     Find the most recent valid checkpoint file at or before target_entry_index.
     Returns the checkpoint path and the index of the entry it corresponds to.
+    `exists` tells whether a checkpoint file is there.
+
+    `file_entries` holds the file's entries of all conversations, so a
+    checkpoint may contain edits of other conversations made before it. With
+    `is_applied`, checkpoints that come after an edit it says is not replayed
+    (a rejected one, say) are passed over, since they contain its change.
     """
     closest_chkpt_path: Optional[Path] = None
     closest_chkpt_entry_index: int = -1
+    # Checkpoints at or before this index contain no skipped edit
+    usable_until = target_entry_index
+    if is_applied is not None:
+        for i in range(target_entry_index + 1):
+            entry = file_entries[i]
+            if entry.get("tool_name") != "mcpdiff" and not is_applied(entry):
+                usable_until = i
+                break
 
-    for i in range(target_entry_index, -1, -1):
+    for i in range(usable_until, -1, -1):
         entry = file_entries[i]
+        if entry.get("tool_name") == "mcpdiff":
+            continue  # Snapshot backups may contain edits rejected since
         chkpt_rel = entry.get("checkpoint_file")
         # Checkpoints are relative to history_root
         if chkpt_rel:
             potential_path = (history_root / chkpt_rel).resolve()
-            if potential_path.is_file():
+            if exists(potential_path):
                 log.debug(
                     f"Found potential checkpoint {potential_path} at index {i} for entry {entry.get('edit_id')}"
                 )
@@ -846,27 +1966,499 @@ def find_closest_checkpoint(
     return closest_chkpt_path, closest_chkpt_entry_index
 
 
+def find_diff_file(
+    entry: Dict[str, Any],
+    history_root: Path,
+    exists: Callable[[Path], bool] = Path.is_file,
+) -> Optional[Path]:
+    """
+    The stored diff file of an entry, if it exists (as told by `exists`).
+    `diff_file` is relative to the history root (diffs/<conv_id>/<edit_id>.diff,
+    as the server writes it); older entries have it relative to the diffs
+    directory, with or without the conversation directory.
+    """
+    diff_file_rel = entry.get("diff_file")
+    if not diff_file_rel:
+        return None
+    candidates = [
+        history_root / diff_file_rel,
+        history_root / DIFFS_DIR / diff_file_rel,
+    ]
+    conv_id = entry.get("conversation_id")
+    if conv_id:
+        candidates.append(history_root / DIFFS_DIR / conv_id / diff_file_rel)
+    for candidate in candidates:
+        if exists(candidate):
+            return candidate
+    return None
+
+
+@dataclass
+class ReplayOptions:
+    """
+    How re-applies match and check diffs. `fuzz` and `ignore_whitespace`, from
+    `reject --fuzz N` and `--ignore-whitespace`, loosen the context matching of
+    unified diffs: fuzz N lets a hunk apply with up to N of its leading and
+    trailing context lines ignored, as with `patch -F N`. `verify` makes a
+    re-applied file hash to the hash_after of the last edit applied, when every
+    edit since its starting point was applied exactly; --no-verify turns it off.
+    `show_reapplied`, from `reject --verbose`, prints each edit applied, with a
+    ✓ or ✗ for whether it applied.
+    """
+
+    fuzz: int = 0
+    ignore_whitespace: bool = False
+    verify: bool = True
+    show_reapplied: bool = False
+
+
+def apply_entry_diff(
+    entry: Dict[str, Any],
+    target_path: Path,
+    diff_path: Path,
+    reverse: bool = False,
+    options: Optional[ReplayOptions] = None,
+) -> List[str]:
+    """
+    Applies an entry's stored diff to `target_path` in place: a unified diff,
+    possibly base64-encoded (`file_encoding` "base64", files that are not valid
+    UTF-8), a bsdiff patch if its `file_encoding` is "bsdiff" (binary files), or
+    the bytes of a partial edit's range. The last two only apply to the content
+    recorded in hash_before and cannot be reversed. A unified diff's context
+    matches as loosely as `options` allow.
+    Returns the loosely applied hunks; raises PatchError if it does not apply.
+    """
+    if (entry.get("operation") or "").lower() == "partial":
+        if reverse:
+            raise PatchError("Partial edits cannot be reversed.")
+        apply_partial_to_file(
+            target_path,
+            diff_path,
+            entry.get("byte_range_start") or 0,
+            entry.get("byte_range_end") or 0,
+            entry.get("hash_before"),
+        )
+        return []
+    if entry.get("file_encoding") == BSDIFF_ENCODING:
+        if reverse:
+            raise PatchError("Binary (bsdiff) patches cannot be reversed.")
+        apply_bsdiff_to_file(target_path, diff_path, entry.get("hash_before"))
+        return []
+    options = options or ReplayOptions()
+    return apply_diff_to_file(
+        target_path,
+        diff_path,
+        reverse=reverse,
+        file_encoding=entry.get("file_encoding"),
+        fuzz=options.fuzz,
+        ignore_whitespace=options.ignore_whitespace,
+    )
+
+
+def find_entry_conflicts(
+    entry: Dict[str, Any],
+    target_path: Path,
+    diff_path: Path,
+    options: Optional[ReplayOptions] = None,
+) -> List[ConflictRegion]:
+    """
+    The regions of `target_path` where an entry's unified diff will not apply
+    with `options`, checked before applying it: none if the file still hashes
+    to the entry's hash_before, otherwise those detect_conflicts() finds
+    comparing the diff's hunks with the file. bsdiff patches and partial edits
+    are not checked.
+    """
+    if entry.get("file_encoding") == BSDIFF_ENCODING:
+        return []
+    if (entry.get("operation") or "").lower() == "partial":
+        return []
+    content = ""
+    if target_path.exists():
+        if entry.get("hash_before") == utils.calculate_hash(str(target_path)):
+            return []
+        content = target_path.read_bytes().decode("utf-8", errors="surrogateescape")
+    diff_content = read_diff_file(diff_path, entry.get("file_encoding"))
+    options = options or ReplayOptions()
+    return detect_conflicts(
+        content,
+        diff_content,
+        fuzz=options.fuzz,
+        ignore_whitespace=options.ignore_whitespace,
+    )
+
+
+def conflict_error(
+    description: str,
+    conflicts: List[ConflictRegion],
+    file_path_rel: str,
+    edit_id: Optional[str],
+) -> PatchConflictError:
+    """
+    The PatchConflictError for a diff with conflicting regions, naming each
+    with its lines; `conflicts` holds them as dicts.
+    """
+    regions = "; ".join(conflict.describe() for conflict in conflicts)
+    return PatchConflictError(
+        f"{description} does not apply: {regions}",
+        file=file_path_rel,
+        hunk=conflicts[0].hunk,
+        edit_id=edit_id,
+        conflicts=[conflict.to_dict() for conflict in conflicts],
+    )
+
+
+# --- Replay Plans ---
+# What re-applying a file will do, worked out from the logs alone: where the
+# replay starts, and for each entry from there whether it is applied. The
+# executor, execute_replay_plan(), only carries a plan out, so `mcpdiff plan`
+# shows exactly what accept and reject will do.
+
+# How a replay starts: from a checkpoint, from empty for a file created in the
+# history, from empty because nothing better is known, or not at all
+PLAN_START_CHECKPOINT = "checkpoint"
+PLAN_START_EMPTY = "empty"
+PLAN_START_UNKNOWN = "unknown"
+PLAN_START_MISSING_CHECKPOINT = "missing_checkpoint"
+PLAN_START_NO_HISTORY = "no_history"
+
+
+@dataclass
+class ReplayStep:
+    """
+    One entry of a replay plan. `action` is "apply", "skip", "split" for an
+    edit applied as its children instead (see mcpdiff_split), or "clear" for a
+    skipped move to the file, whose content then never arrived. `diff_path` is
+    the stored diff found for an applied entry, if any; `rejected_parent` the
+    ID of a rejected edit it depends on.
+    """
+
+    entry: Dict[str, Any]
+    action: str
+    diff_path: Optional[Path] = None
+    rejected_parent: Optional[str] = None
+
+    @property
+    def edit_id(self) -> str:
+        return self.entry.get("edit_id", "unknown_id")
+
+    @property
+    def operation(self) -> str:
+        return (self.entry.get("operation") or "unknown").lower()
+
+    @property
+    def status(self) -> str:
+        return (self.entry.get("status") or "unknown").lower()
+
+    @property
+    def needs_diff(self) -> bool:
+        return self.operation in ("edit", "replace", "append", "partial")
+
+    def to_dict(self, history_root: Optional[Path] = None) -> Dict[str, Any]:
+        """The step as JSON, with the diff path relative to `history_root`."""
+        return {
+            "edit_id": self.edit_id,
+            "conversation_id": self.entry.get("conversation_id"),
+            "operation": self.operation,
+            "status": self.status,
+            "action": self.action,
+            "file_path": self.entry.get("file_path"),
+            "source_path": self.entry.get("source_path"),
+            "diff_file": _plan_path(self.diff_path, history_root),
+            "diff_missing": self.action == "apply"
+            and self.diff_path is None
+            and (self.needs_diff or bool(self.entry.get("diff_file"))),
+            "file_encoding": self.entry.get("file_encoding") or "text",
+            "hash_before": self.entry.get("hash_before"),
+            "hash_after": self.entry.get("hash_after"),
+            "rejected_parent": self.rejected_parent,
+        }
+
+
+@dataclass
+class ReplayPlan:
+    """
+    How to re-apply one file: the starting state (`start`, one of the
+    PLAN_START_* values, with the checkpoint and the entry it belongs to) and
+    the steps from there to the file's latest entry.
+    """
+
+    file_path: str
+    apply_only_accepted: bool
+    start: str = PLAN_START_NO_HISTORY
+    checkpoint_path: Optional[Path] = None
+    start_edit_id: Optional[str] = None
+    # The entry the starting checkpoint belongs to
+    start_entry: Optional[Dict[str, Any]] = None
+    missing_checkpoint: Optional[str] = None
+    steps: List[ReplayStep] = field(default_factory=list)
+
+    def verified_step(self) -> Optional[ReplayStep]:
+        """
+        The last step applied, if no edit before it is skipped: the result
+        should then hash to its hash_after (see _verify_reconstruction()).
+        """
+        last: Optional[ReplayStep] = None
+        skipped = False
+        for step in self.steps:
+            if step.action == "apply":
+                last = None if skipped else step
+            elif step.action != "split":
+                skipped = True
+        return last
+
+    @property
+    def ignored(self) -> bool:
+        """
+        Whether an edit of the plan was logged while its file was listed in
+        .mcp/ignore: with no checkpoint and an empty diff, it cannot be
+        replayed, so the file is left as it is.
+        """
+        return any(step.entry.get("ignored") for step in self.steps)
+
+    def to_dict(self, history_root: Optional[Path] = None) -> Dict[str, Any]:
+        """The plan as JSON, with paths relative to `history_root`."""
+        verified = self.verified_step()
+        return {
+            "file_path": self.file_path,
+            "apply_only_accepted": self.apply_only_accepted,
+            "start": self.start,
+            "ignored": self.ignored,
+            "checkpoint_file": _plan_path(self.checkpoint_path, history_root),
+            "start_edit_id": self.start_edit_id,
+            "missing_checkpoint": self.missing_checkpoint,
+            "expected_hash": verified.entry.get("hash_after") if verified else None,
+            "steps": [step.to_dict(history_root) for step in self.steps],
+        }
+
+
+def _plan_path(path: Optional[Path], history_root: Optional[Path]) -> Optional[str]:
+    if path is None:
+        return None
+    return get_relative_path(path, history_root) if history_root else str(path)
+
+
+def plan_file_replay(
+    file_path_rel: str,
+    all_entries: List[Dict[str, Any]],
+    history_root: Path,
+    apply_only_accepted: bool = False,
+    exists: Callable[[Path], bool] = Path.is_file,
+) -> ReplayPlan:
+    """
+    Plans re-applying `file_path_rel` from the entries of all conversations
+    that touched it: from the newest checkpoint no skipped edit precedes, the
+    edits that are accepted (and pending, unless `apply_only_accepted`). Only
+    `exists` looks at the filesystem, to find checkpoints and diffs.
+    """
+    plan = ReplayPlan(file_path_rel, apply_only_accepted)
+    file_entries = get_relevant_history_for_file(file_path_rel, all_entries)
+    if not file_entries:
+        return plan
+
+    latest_entry_index = len(file_entries) - 1
+    applied = ("accepted",) if apply_only_accepted else ("accepted", "pending")
+    checkpoint_path, start_entry_index = find_closest_checkpoint(
+        latest_entry_index,
+        file_entries,
+        history_root,
+        is_applied=lambda e: (e.get("status") or "").lower() in applied,
+        exists=exists,
+    )
+    if checkpoint_path:
+        plan.start = PLAN_START_CHECKPOINT
+        plan.checkpoint_path = checkpoint_path
+    elif (
+        start_entry_index != -1
+        and file_entries[start_entry_index].get("operation", "").lower() == "create"
+    ):
+        plan.start = PLAN_START_EMPTY
+    else:
+        plan.missing_checkpoint = next(
+            (
+                e["checkpoint_file"]
+                for e in file_entries
+                if e.get("checkpoint_file") and e.get("tool_name") != "mcpdiff"
+            ),
+            None,
+        )
+        plan.start = (
+            PLAN_START_MISSING_CHECKPOINT
+            if plan.missing_checkpoint
+            else PLAN_START_UNKNOWN
+        )
+    if start_entry_index != -1:
+        plan.start_entry = file_entries[start_entry_index]
+        plan.start_edit_id = plan.start_entry.get("edit_id")
+
+    # A checkpoint holds the content before its entry, and a create starts from
+    # empty, so the starting entry itself is applied too. Entries mcpdiff
+    # logged (snapshots, reverts) never change the file, and split edits are
+    # applied as their children, which follow them.
+    for entry in file_entries[max(start_entry_index, 0) :]:
+        if entry.get("tool_name") == "mcpdiff":
+            continue
+        if entry.get("split_into"):
+            plan.steps.append(ReplayStep(entry, "split"))
+            continue
+        status = (entry.get("status") or "").lower()
+        operation = (entry.get("operation") or "").lower()
+        if status not in applied:
+            # Rejected edits are never applied
+            moved_here = (
+                operation in utils.MOVE_OPERATIONS
+                and entry.get("source_path") != file_path_rel
+            )
+            plan.steps.append(ReplayStep(entry, "clear" if moved_here else "skip"))
+            continue
+        parent = find_parent_entry(entry, all_entries)
+        plan.steps.append(
+            ReplayStep(
+                entry,
+                "apply",
+                diff_path=find_diff_file(entry, history_root, exists=exists),
+                rejected_parent=parent.get("edit_id", "unknown_id")
+                if parent and (parent.get("status") or "").lower() == "rejected"
+                else None,
+            )
+        )
+    return plan
+
+
+def replay_plan_error(plan: ReplayPlan) -> Optional[HistoryError]:
+    """
+    Why a replay plan cannot reach the file's current state, checked before
+    anything is applied: its starting checkpoint is missing, or an edit it
+    applies has no diff. None if the checkpoint and diffs are all there, or
+    the plan is ignored and leaves the file alone.
+    """
+    if plan.ignored:
+        return None
+    if plan.start == PLAN_START_MISSING_CHECKPOINT:
+        return MissingCheckpointError(
+            f"Checkpoint {plan.missing_checkpoint} for {utils.display_path(plan.file_path)} is missing, so its initial state is unknown.",
+            file=plan.file_path,
+            checkpoint_file=plan.missing_checkpoint,
+        )
+    for step in plan.steps:
+        if step.action == "apply" and step.needs_diff and step.diff_path is None:
+            return MissingDiffError(
+                f"{step.operation} op {step.edit_id} missing diff file",
+                file=plan.file_path,
+                edit_id=step.edit_id,
+            )
+    return None
+
+
 def reconstruct_file_from_history(
     file_path_rel: str,
     all_entries: List[Dict[str, Any]],
     workspace_root: Path,
     history_root: Path,
     apply_only_accepted: bool = False,  # If True, only apply 'accepted' edits, otherwise apply 'accepted' and 'pending'
+    options: Optional[ReplayOptions] = None,
+    invocation: Optional[audit.Invocation] = None,
 ) -> Dict[str, Any]:
     """
     Reconstructs the state of a file by finding the latest checkpoint
     and applying subsequent relevant edits ('accepted' and optionally 'pending').
-
-    Returns: Dict containing {'hash': final_hash or None, 'error': error_message or None}
+    The re-apply is recorded in the audit log.
+
+    Hunks that applied only as loosely as `options` allow are printed as
+    warnings. If every edit since the starting checkpoint was applied, exactly,
+    the result must hash to the hash_after of the last one; otherwise the file
+    is left as it was and a VerificationError is returned, unless `options`
+    turn verification off.
+
+    Returns: Dict containing {'hash': final_hash or None, 'error': error_message or None,
+    'warnings': messages about loosely applied hunks (on success)}, plus on
+    failure 'exception', the error (a PatchConflictError, say), and
+    'checkpoint_mismatch' if the starting checkpoint was distrusted (see
+    execute_replay_plan()).
     """
-    target_file_abs = workspace_root / file_path_rel
+    result = _reconstruct_file(
+        file_path_rel,
+        all_entries,
+        workspace_root,
+        history_root,
+        apply_only_accepted,
+        options,
+    )
+    for warning in result.get("warnings", []):
+        print(f"{utils.COLOR_YELLOW}Warning: {warning}{utils.COLOR_RESET}")
+    applied = ("accepted",) if apply_only_accepted else ("accepted", "pending")
+    edit_ids = [
+        e["edit_id"]
+        for e in get_relevant_history_for_file(file_path_rel, all_entries)
+        if e.get("tool_name") != "mcpdiff"
+        and (e.get("status") or "").lower() in applied
+        and e.get("edit_id")
+    ]
+    # file_path, plus file_path_bytes if it is not valid UTF-8
+    details = utils.encode_log_paths({"file_path": file_path_rel})
+    if result.get("error"):
+        details["error"] = result["error"]
+        result.setdefault("exception", HistoryError(result["error"]))
+    if result.get("warnings"):
+        details["warnings"] = result["warnings"]
+    outcome = "failure" if result.get("error") else "success"
+    audit.record(
+        history_root, "reapply", edit_ids, outcome, invocation=invocation, **details
+    )
+    metrics.METRICS.increment(
+        "reapply_failures_total" if result.get("error") else "reapply_successes_total"
+    )
+    return result
+
+
+def _reconstruct_file(
+    file_path_rel: str,
+    all_entries: List[Dict[str, Any]],
+    workspace_root: Path,
+    history_root: Path,
+    apply_only_accepted: bool,
+    options: Optional[ReplayOptions],
+) -> Dict[str, Any]:
     log.info(
         f"Reconstructing file '{file_path_rel}' (apply_only_accepted={apply_only_accepted})"
     )
+    plan = plan_file_replay(
+        file_path_rel, all_entries, history_root, apply_only_accepted
+    )
+    return execute_replay_plan(plan, workspace_root, options)
 
-    file_entries = get_relevant_history_for_file(file_path_rel, all_entries)
 
-    if not file_entries:
+def execute_replay_plan(
+    plan: ReplayPlan, workspace_root: Path, options: Optional[ReplayOptions] = None
+) -> Dict[str, Any]:
+    """
+    Carries out a replay plan in a temporary directory, with `options`, and if
+    it succeeds replaces the workspace file with the result. Returns the result
+    dict of reconstruct_file_from_history(). A starting checkpoint that does
+    not hash to its entry's hash_before is used anyway, but reported in the
+    warnings, and the result's 'checkpoint_mismatch' is the entry's edit ID.
+    An ignored plan (see ReplayPlan.ignored) leaves the file as it is: the
+    result is the hash it has now, with 'ignored' set.
+    """
+    mismatch = None
+    if plan.start == PLAN_START_CHECKPOINT and plan.start_entry and not plan.ignored:
+        expected_hash = plan.start_entry.get("hash_before")
+        actual_hash = utils.calculate_hash(str(plan.checkpoint_path))
+        if expected_hash and actual_hash and actual_hash != expected_hash:
+            mismatch = checkpoint_mismatch_message(plan.start_entry, actual_hash)
+    result = _execute_replay_plan(plan, workspace_root, options or ReplayOptions())
+    if mismatch:
+        result["warnings"] = [mismatch] + result.get("warnings", [])
+        result["checkpoint_mismatch"] = plan.start_edit_id or ""
+    return result
+
+
+def _execute_replay_plan(
+    plan: ReplayPlan, workspace_root: Path, options: ReplayOptions
+) -> Dict[str, Any]:
+    file_path_rel = plan.file_path
+    target_file_abs = workspace_root / file_path_rel
+
+    if plan.start == PLAN_START_NO_HISTORY:
         # If file exists but has no history, return its current hash.
         if target_file_abs.exists():
             current_hash = utils.calculate_hash(str(target_file_abs))
@@ -881,13 +2473,13 @@ def reconstruct_file_from_history(
                 "error": None,
             }  # File doesn't exist and never did according to history
 
-    # Find the latest entry index (which represents the desired state)
-    latest_entry_index = len(file_entries) - 1
-
-    # Find the most recent checkpoint at or before the latest entry
-    checkpoint_path, start_entry_index = find_closest_checkpoint(
-        latest_entry_index, file_entries, history_root
-    )
+    if plan.ignored:
+        log.info(f"Leaving {file_path_rel} as it is: it has edits logged as ignored.")
+        return {
+            "hash": utils.calculate_hash(str(target_file_abs)),
+            "error": None,
+            "ignored": True,
+        }
 
     temp_dir = None
     try:
@@ -897,17 +2489,22 @@ def reconstruct_file_from_history(
         log.debug(f"Using temporary directory for reconstruction: {temp_dir}")
 
         # 1. Initialize temp file state from checkpoint or empty
-        if checkpoint_path:
-            log.debug(f"Initializing reconstruction from checkpoint: {checkpoint_path}")
-            shutil.copy2(checkpoint_path, temp_file_path)
-        elif (
-            start_entry_index != -1
-            and file_entries[start_entry_index].get("operation", "").lower() == "create"
-        ):
+        if plan.start == PLAN_START_CHECKPOINT:
+            log.debug(
+                f"Initializing reconstruction from checkpoint: {plan.checkpoint_path}"
+            )
+            shutil.copy2(plan.checkpoint_path, temp_file_path)
+        elif plan.start == PLAN_START_EMPTY:
             log.debug(
-                f"Initializing reconstruction with empty file (from create at index {start_entry_index})"
+                f"Initializing reconstruction with empty file (from create {plan.start_edit_id})"
             )
             temp_file_path.touch()  # Create empty file
+        elif plan.start == PLAN_START_MISSING_CHECKPOINT:
+            raise MissingCheckpointError(
+                f"Checkpoint {plan.missing_checkpoint} for {utils.display_path(file_path_rel)} is missing, so its initial state is unknown.",
+                file=file_path_rel,
+                checkpoint_file=plan.missing_checkpoint,
+            )
         else:
             # No checkpoint and not starting with 'create'. What state was it in?
             # This might happen if history is incomplete or the first recorded action wasn't create/checkpointed.
@@ -918,66 +2515,50 @@ def reconstruct_file_from_history(
             )
             temp_file_path.touch()
 
-        # 2. Apply edits sequentially from start_entry_index + 1 up to latest_entry_index
+        # 2. Apply the planned edits sequentially
         current_temp_file_path = temp_file_path  # Track potential renames
-
-        for i in range(start_entry_index + 1, latest_entry_index + 1):
-            entry = file_entries[i]
-            status = entry.get("status", "unknown").lower()
-            operation = entry.get("operation", "unknown").lower()
-            entry_id = entry.get("edit_id", "unknown_id")
-
-            # Determine if this edit should be applied
-            should_apply = status == "accepted" or (
-                status == "pending" and not apply_only_accepted
-            )
-            # Rejected edits are *never* applied during reconstruction
-            if status == "rejected":
-                log.debug(f"Skipping rejected edit {entry_id} at index {i}")
-                continue
-
-            if not should_apply:
+        # Hunks that applied only with fuzz or ignoring whitespace
+        warnings: List[str] = []
+        # Whether the result should be exactly what the last edit applied
+        # recorded: not if a hunk applied loosely
+        exact = True
+
+        for step in plan.steps:
+            entry = step.entry
+            status = step.status
+            operation = step.operation
+            entry_id = step.edit_id
+
+            if step.action != "apply":
                 log.debug(
-                    f"Skipping edit {entry_id} (status: {status}, apply_only_accepted: {apply_only_accepted})"
+                    f"Skipping edit {entry_id} (status: {status}, apply_only_accepted: {plan.apply_only_accepted})"
                 )
+                if step.action == "clear" and current_temp_file_path.exists():
+                    # The content starting from this move's checkpoint never
+                    # arrived here
+                    current_temp_file_path.unlink()
                 continue
 
-            log.debug(
-                f"Applying {status} edit {entry_id} (op: {operation}) at index {i}"
-            )
+            # A child edit is only valid on top of its parent
+            if step.rejected_parent:
+                if status != "accepted":
+                    _print_reapplied_edit(entry, False, options)
+                    return {
+                        "hash": None,
+                        "error": f"Edit {entry_id} depends on rejected edit {step.rejected_parent}",
+                    }
+                # Only possible through accept --force
+                log.warning(
+                    f"Applying accepted edit {entry_id} although its parent {step.rejected_parent} was rejected."
+                )
 
-            # We need to apply relative to the temp dir containing the file
-            temp_workspace_root = Path(temp_dir)
-
-            # --- Create a dummy entry for apply_or_revert_edit ---
-            # It needs paths relative to the *actual* workspace, but will operate
-            # within the temp dir context provided via git apply --directory or shutil.
-            # Construct a temporary entry dict for the apply function.
-            # The function needs workspace_root for path resolution internal to it.
-            # Crucially, git apply needs the diff path correctly.
-            temp_entry_for_apply = entry.copy()
-
-            # Get the correct diff path relative to the *real* history root
-            diff_file_rel = entry.get("diff_file")
-            actual_diff_path: Optional[Path] = None
-            if diff_file_rel:
-                # Assume relative to DIFFS_DIR
-                p1 = history_root / DIFFS_DIR / diff_file_rel
-                p2 = None
-                conv_id = entry.get("conversation_id")
-                if conv_id:
-                    p2 = history_root / DIFFS_DIR / conv_id / diff_file_rel
-
-                if p1.is_file():
-                    actual_diff_path = p1
-                elif p2 and p2.is_file():
-                    actual_diff_path = p2
-
-            # --- Apply using a simplified logic within the temp context ---
-            # We can't directly call apply_or_revert_edit as it modifies the actual workspace.
-            # Re-implement the core apply logic here for the temp file.
+            log.debug(f"Applying {status} edit {entry_id} (op: {operation})")
 
+            # Apply within the temp dir: the diff is the one found in the real
+            # history root, the file the one being reconstructed
+            actual_diff_path = step.diff_path
             target_path_in_temp = current_temp_file_path  # The file we are modifying
+            loose_hunks: List[str] = []
 
             try:
                 if operation == "create":
@@ -985,100 +2566,126 @@ def reconstruct_file_from_history(
                     if not target_path_in_temp.exists():
                         target_path_in_temp.touch()
                     if actual_diff_path:
-                        cmd = [
-                            "git",
-                            "apply",
-                            "--verbose",
-                            "--unsafe-paths",
-                            str(actual_diff_path),
-                        ]
-                        # Apply relative to the temp dir
-                        result = subprocess.run(
-                            cmd,
-                            cwd=temp_workspace_root,
-                            capture_output=True,
-                            text=True,
-                            check=False,
+                        conflicts = find_entry_conflicts(
+                            entry, target_path_in_temp, actual_diff_path, options
                         )
-                        if result.returncode != 0:
-                            raise HistoryError(
-                                f"git apply failed for create {entry_id}: {result.stderr}"
+                        if conflicts:
+                            raise conflict_error(
+                                f"Diff for create {entry_id}",
+                                conflicts,
+                                file_path_rel,
+                                entry_id,
+                            )
+                        try:
+                            loose_hunks = apply_entry_diff(
+                                entry,
+                                target_path_in_temp,
+                                actual_diff_path,
+                                options=options,
+                            )
+                        except PatchError as e:
+                            raise PatchConflictError(
+                                f"Diff does not apply for create {entry_id}: {e}",
+                                file=file_path_rel,
+                                hunk=e.hunk,
+                                edit_id=entry_id,
                             )
 
                 elif operation == "delete":
                     if target_path_in_temp.exists():
                         target_path_in_temp.unlink()
 
-                elif operation == "move":
+                elif operation == "append":
+                    if not actual_diff_path:
+                        raise MissingDiffError(
+                            f"append op {entry_id} missing diff file",
+                            file=file_path_rel,
+                            edit_id=entry_id,
+                        )
+                    with open(target_path_in_temp, "ab") as f:
+                        f.write(actual_diff_path.read_bytes())
+                    # hash_after covers the original content plus the appended bytes
+                    expected_hash = entry.get("hash_after")
+                    actual_hash = utils.calculate_hash(str(target_path_in_temp))
+                    if expected_hash and actual_hash != expected_hash:
+                        log.warning(
+                            f"Hash mismatch after append {entry_id}: expected {expected_hash[:8]}, got {(actual_hash or '')[:8]}"
+                        )
+
+                elif operation in utils.MOVE_OPERATIONS:
                     source_rel = entry.get("source_path")
                     dest_rel = entry.get("file_path")
                     if not source_rel or not dest_rel:
-                        raise HistoryError(f"Move op {entry_id} missing paths")
-                    # The source *should* match the current temp file's expected relative path
-                    # We just need to update where the next operation looks
-                    new_temp_name = Path(dest_rel).name
-                    new_temp_path = temp_workspace_root / new_temp_name
-                    if target_path_in_temp.exists():
-                        log.debug(
-                            f"Simulating move: renaming {target_path_in_temp} to {new_temp_path}"
-                        )
-                        target_path_in_temp.rename(new_temp_path)
-                        current_temp_file_path = new_temp_path  # Update tracked path
-                    else:
+                        raise HistoryError(f"{operation} op {entry_id} missing paths")
+                    if source_rel == file_path_rel and dest_rel != file_path_rel:
+                        # Moved away: nothing is left at this path
+                        if target_path_in_temp.exists():
+                            target_path_in_temp.unlink()
+                    elif not target_path_in_temp.exists():
                         log.warning(
-                            f"Skipping move {entry_id}: source {target_path_in_temp.name} doesn't exist in temp dir."
+                            f"Skipping move {entry_id}: source {utils.display_path(source_rel)} has no reconstructed content."
                         )
+                    # Moved here: the content is already in the temp file
 
-                elif operation in ["edit", "replace"]:
+                elif operation in ["edit", "replace", "partial"]:
                     if not actual_diff_path:
-                        raise HistoryError(
-                            f"{operation} op {entry_id} missing diff file"
+                        raise MissingDiffError(
+                            f"{operation} op {entry_id} missing diff file",
+                            file=file_path_rel,
+                            edit_id=entry_id,
                         )
-                    if not target_path_in_temp.exists():
-                        # If file doesn't exist, maybe the diff creates it (e.g., replace)
-                        log.debug(
-                            f"Target {target_path_in_temp.name} doesn't exist in temp, git apply might create it."
-                        )
-                        # Create empty file for git apply to patch against if needed?
-                        # Let's assume git apply handles it.
-
-                    cmd = ["git", "apply", "--verbose", "--unsafe-paths"]
-                    # Apply relative to the temp dir
-                    # We need to tell git apply the *target filename* within the temp dir
-                    # Use --index and potentially --cached? No, simpler: apply directly
-                    # We need cwd to be temp_dir, and diff path absolute
-                    cmd.append(str(actual_diff_path))
                     log.debug(
-                        f"Running apply for {operation}: {' '.join(cmd)} in {temp_workspace_root}"
+                        f"Applying {operation} {entry_id} to {target_path_in_temp}"
                     )
-                    result = subprocess.run(
-                        cmd,
-                        cwd=temp_workspace_root,
-                        capture_output=True,
-                        text=True,
-                        check=False,
+                    # Checked first, to name the lines that differ
+                    conflicts = find_entry_conflicts(
+                        entry, target_path_in_temp, actual_diff_path, options
                     )
-                    if result.returncode != 0:
-                        # Check if target file exists now, maybe apply created it
-                        if not target_path_in_temp.exists():
-                            raise HistoryError(
-                                f"git apply failed for {operation} {entry_id} and target file doesn't exist: {result.stderr}"
-                            )
-                        else:
-                            # File exists, maybe patch didn't apply cleanly?
-                            raise HistoryError(
-                                f"git apply failed for {operation} {entry_id} (file exists): {result.stderr}"
-                            )
+                    if conflicts:
+                        raise conflict_error(
+                            f"Diff for {operation} {entry_id}",
+                            conflicts,
+                            file_path_rel,
+                            entry_id,
+                        )
+                    try:
+                        # A missing file is patched as empty (e.g. replace)
+                        loose_hunks = apply_entry_diff(
+                            entry,
+                            target_path_in_temp,
+                            actual_diff_path,
+                            options=options,
+                        )
+                    except PatchError as e:
+                        raise PatchConflictError(
+                            f"Diff does not apply for {operation} {entry_id}: {e}",
+                            file=file_path_rel,
+                            hunk=e.hunk,
+                            edit_id=entry_id,
+                        )
 
             except Exception as apply_err:
                 log.error(
                     f"Failed to apply {status} edit {entry_id} (op: {operation}) during reconstruction: {apply_err}"
                 )
+                _print_reapplied_edit(entry, False, options)
                 # Stop reconstruction here? Or continue? Let's stop for safety.
                 return {
                     "hash": None,
                     "error": f"Failed applying edit {entry_id}: {apply_err}",
+                    "exception": apply_err,
                 }
+            _print_reapplied_edit(entry, True, options)
+
+            if loose_hunks:
+                exact = False
+                warnings.extend(f"Edit {entry_id}: {m}" for m in loose_hunks)
+                expected_hash = entry.get("hash_after")
+                actual_hash = utils.calculate_hash(str(target_path_in_temp))
+                if expected_hash and actual_hash != expected_hash:
+                    warnings.append(
+                        f"Edit {entry_id}: result differs from the recorded hash_after, as it did not apply exactly"
+                    )
 
         # 3. Final state is in current_temp_file_path
         final_hash = (
@@ -1086,6 +2693,15 @@ def reconstruct_file_from_history(
             if current_temp_file_path.exists()
             else None
         )
+        verified = plan.verified_step()
+        if exact and options.verify and verified:
+            error = _verify_reconstruction(file_path_rel, verified.entry, final_hash)
+            if error:
+                log.error(str(error))
+                _print_reapplied_edit(
+                    verified.entry, False, options, "result does not verify"
+                )
+                return {"hash": None, "error": str(error), "exception": error}
 
         # 4. Replace the actual file with the reconstructed one
         log.info(f"Reconstruction successful. Updating {target_file_abs}")
@@ -1107,11 +2723,11 @@ def reconstruct_file_from_history(
         log.info(
             f"Reconstruction complete for {file_path_rel}. Final hash: {final_hash}"
         )
-        return {"hash": final_hash, "error": None}
+        return {"hash": final_hash, "error": None, "warnings": warnings}
 
     except Exception as e:
         log.exception(f"Error during reconstruction of {file_path_rel}: {e}")
-        return {"hash": None, "error": str(e)}
+        return {"hash": None, "error": str(e), "exception": e}
     finally:
         # Clean up temporary directory
         if temp_dir and Path(temp_dir).exists():
@@ -1124,6 +2740,56 @@ def reconstruct_file_from_history(
                 )
 
 
+def _print_reapplied_edit(
+    entry: Dict[str, Any],
+    applied: bool,
+    options: ReplayOptions,
+    note: Optional[str] = None,
+) -> None:
+    """Prints an edit a re-apply processed, if `options` ask for it."""
+    if not options.show_reapplied:
+        return
+    mark = (
+        f"{utils.COLOR_GREEN}✓{utils.COLOR_RESET}"
+        if applied
+        else f"{utils.COLOR_RED}✗{utils.COLOR_RESET}"
+    )
+    path = utils.display_path(entry.get("file_path") or "N/A")
+    source = entry.get("source_path")
+    if source and source != entry.get("file_path"):
+        path = f"{utils.display_path(source)} -> {path}"
+    edit_id = entry.get("edit_id", "N/A")
+    line = f"  {mark} {edit_id} {entry.get('operation', 'N/A')} {path}"
+    print(f"{line} ({note})" if note else line)
+
+
+def _verify_reconstruction(
+    file_path_rel: str, last_applied: Dict[str, Any], final_hash: Optional[str]
+) -> Optional[VerificationError]:
+    """
+    The VerificationError for a file re-applied up to and including
+    `last_applied` that does not hash to its recorded hash_after, or None.
+    Deletes and moves away leave nothing to compare, nor do entries without a
+    hash_after.
+    """
+    expected_hash = last_applied.get("hash_after")
+    if (
+        not expected_hash
+        or last_applied.get("file_path") != file_path_rel
+        or (last_applied.get("operation") or "").lower() == "delete"
+        or final_hash == expected_hash
+    ):
+        return None
+    edit_id = last_applied.get("edit_id", "unknown_id")
+    return VerificationError(
+        f"Re-applied {utils.display_path(file_path_rel)} does not match the state recorded by edit {edit_id}: expected {expected_hash[:8]}, got {(final_hash or 'no file')[:8]}. The file was left unchanged; use --no-verify to write it anyway.",
+        file=file_path_rel,
+        edit_id=edit_id,
+        expected_hash=expected_hash,
+        actual_hash=final_hash,
+    )
+
+
 def verify_file_hash(file_path: Path, expected_hash: Optional[str]) -> bool:
     """Verify if the file's current hash matches the expected hash."""
     if not expected_hash:
@@ -1145,16 +2811,144 @@ def verify_file_hash(file_path: Path, expected_hash: Optional[str]) -> bool:
 def get_last_applied_edit_for_file(
     file_path_rel: str, all_entries: List[Dict[str, Any]]
 ) -> Optional[Dict[str, Any]]:
-    """Get the last 'accepted' or 'pending' edit entry for a specific file path."""
+    """
+    Get the last 'accepted' or 'pending' edit entry for a specific file path, or
+    the last completed revert if the file was re-applied after that edit.
+    """
     file_entries = get_relevant_history_for_file(file_path_rel, all_entries)
     # Iterate backwards to find the most recent accepted/pending
     for entry in reversed(file_entries):
         status = entry.get("status", "").lower()
         if status in ["accepted", "pending"]:
             return entry
+        if entry.get("operation") == "revert" and status == "done":
+            # Its hash_after is the file as re-applied without the rejected edits
+            return entry
     return None
 
 
+def workspace_structure_changed(
+    entry: Dict[str, Any], hashes: utils.WorkspaceRootHashes
+) -> Optional[bool]:
+    """
+    Whether the workspace's file names differ from when `entry` was recorded,
+    by its workspace_root_hash and the workspace's current one in `hashes`.
+    None if the entry has no such hash or it was computed with a hash
+    algorithm that is not available here.
+    """
+    recorded = entry.get("workspace_root_hash")
+    if not recorded or ":" not in recorded:
+        return None
+    algorithm = recorded.split(":", 1)[0]
+    try:
+        return hashes.get(algorithm) != recorded
+    except ValueError:
+        return None
+
+
+def diagnose_file(
+    file_path_rel: str,
+    all_entries: List[Dict[str, Any]],
+    workspace_root: Path,
+    hashes: Optional[utils.WorkspaceRootHashes] = None,
+) -> Dict[str, Any]:
+    """
+    Compares a tracked file on disk with the state recorded by its last applied edit.
+    Returns {'file_path', 'edit_id', 'expected_hash', 'current_hash', 'state',
+    'structure_changed', 'conflict_markers'} where state is 'ok', 'modified',
+    'missing', 'unexpected' (exists but should not) or 'unknown' (no hash
+    recorded), structure_changed is workspace_structure_changed() of that edit,
+    and conflict_markers the conflict blocks it recorded if the file still has
+    its content (so they are live without reading the file). Pass `hashes` when
+    diagnosing several files, so the workspace is walked once.
+    """
+    file_path_abs = workspace_root / file_path_rel
+    last_applied = get_last_applied_edit_for_file(file_path_rel, all_entries)
+    current_hash = (
+        utils.calculate_hash(str(file_path_abs)) if file_path_abs.exists() else None
+    )
+    result = {
+        "file_path": file_path_rel,
+        "edit_id": last_applied.get("edit_id") if last_applied else None,
+        "expected_hash": None,
+        "current_hash": current_hash,
+        "state": "unknown",
+        "structure_changed": None,
+        "conflict_markers": None,
+    }
+    if not last_applied:
+        return result
+
+    result["structure_changed"] = workspace_structure_changed(
+        last_applied, hashes or utils.WorkspaceRootHashes(workspace_root)
+    )
+
+    expected_hash = last_applied.get("hash_after")
+    result["expected_hash"] = expected_hash
+    operation = last_applied.get("operation", "").lower()
+    if operation == "delete" or (
+        operation in utils.MOVE_OPERATIONS
+        and last_applied.get("source_path") == file_path_rel
+    ):
+        result["state"] = "ok" if current_hash is None else "unexpected"
+    elif not expected_hash:
+        result["state"] = "unknown"
+    elif current_hash is None:
+        result["state"] = "missing"
+    else:
+        result["state"] = "ok" if current_hash == expected_hash else "modified"
+    if result["state"] == "ok" and last_applied.get("file_path") == file_path_rel:
+        result["conflict_markers"] = last_applied.get("conflict_markers") or None
+    return result
+
+
+def find_conflicted_files(
+    all_entries: List[Dict[str, Any]],
+    workspace_root: Path,
+    conv_id: Optional[str] = None,
+) -> List[Dict[str, Any]]:
+    """
+    The files whose last applied edit (of conversation `conv_id`, if given)
+    recorded conflict markers, by path. Returns {'file_path', 'edit_id',
+    'conversation_id', 'recorded', 'live', 'state'}: the recorded and the
+    current conflict blocks, and state 'unresolved', 'resolved' (the file has
+    none left) or 'missing'.
+    """
+    results = []
+    file_paths = sorted({e["file_path"] for e in all_entries if e.get("file_path")})
+    for file_path_rel in file_paths:
+        entry = get_last_applied_edit_for_file(file_path_rel, all_entries)
+        if (
+            not entry
+            or not entry.get("conflict_markers")
+            or entry.get("file_path") != file_path_rel
+        ):
+            continue
+        if conv_id and not find_entries_by_conversation([entry], conv_id):
+            continue
+        file_path_abs = workspace_root / file_path_rel
+        live: List[Dict[str, int]] = []
+        if not file_path_abs.is_file():
+            state = "missing"
+        else:
+            content = file_path_abs.read_bytes().decode(
+                "utf-8", errors="surrogateescape"
+            )
+            live = find_conflict_markers(split_lines(content))
+            state = "unresolved" if live else "resolved"
+        results.append(
+            {
+                "file_path": file_path_rel,
+                "edit_id": entry["edit_id"],
+                "conversation_id": entry.get("conversation_id"),
+                "recorded": entry["conflict_markers"],
+                "live": live,
+                "state": state,
+            }
+        )
+    return results
+
+
 def generate_diff_from_checkpoint(
     current_file_path: Path,
     checkpoint_file_path: Path,
@@ -1165,39 +2959,20 @@ def generate_diff_from_checkpoint(
         log.error("Cannot generate diff: one or both files missing.")
         return None
     try:
-        # Use git diff --no-index for comparing arbitrary files
-        cmd = [
-            "git",
-            "diff",
-            "--no-index",
-            "--",
-            str(checkpoint_file_path),
-            str(current_file_path),
-        ]
-        # Rename paths in diff output for clarity
-        # Git diff will use the full paths, need to adjust header lines
-        log.debug(f"Generating diff: {' '.join(cmd)}")
-        result = subprocess.run(
-            cmd, capture_output=True, text=True, check=False
-        )  # Allow non-zero exit code for diffs
-
-        if result.returncode > 1:  # 0=no diff, 1=diff found, >1=error
-            log.error(f"Error generating diff: {result.stderr}")
-            return None
-
-        diff_content = result.stdout
-        # Replace the absolute paths in header with the display name
-        # --- a/<abs_checkpoint_path>
-        # +++ b/<abs_current_path>
-        # becomes
-        # --- a/relative/path/to/file
-        # +++ b/relative/path/to/file
-        lines = diff_content.splitlines()
-        if len(lines) >= 2:
-            # Be careful with paths containing spaces
-            lines[0] = f"--- a/{file_display_name}"
-            lines[1] = f"+++ b/{file_display_name}"
-        return "\n".join(lines)
+        old_lines = checkpoint_file_path.read_text(
+            encoding="utf-8", errors="replace"
+        ).splitlines(keepends=True)
+        new_lines = current_file_path.read_text(
+            encoding="utf-8", errors="replace"
+        ).splitlines(keepends=True)
+        return "".join(
+            difflib.unified_diff(
+                old_lines,
+                new_lines,
+                f"a/{file_display_name}",
+                f"b/{file_display_name}",
+            )
+        )
 
     except Exception as e:
         log.exception(f"Error generating diff between checkpoint and current file: {e}")
@@ -1209,16 +2984,17 @@ def verify_and_prompt_if_modified(
     expected_hash: Optional[str],
     history_root: Path,
     workspace_root: Path,
-) -> bool:
+) -> Optional[ExternalModificationError]:
     """
     Checks if a file matches expected hash. If not, shows diff vs last checkpoint
     and prompts user whether to proceed (overwriting changes).
 
-    Returns True if verification passes OR user confirms overwrite.
-    Returns False if verification fails AND user chooses not to proceed.
+    Returns None if verification passes OR user confirms overwrite.
+    Returns an ExternalModificationError, for the action to fail with, if
+    verification fails AND user chooses not to proceed.
     """
     if verify_file_hash(file_path_abs, expected_hash):
-        return True  # Hash matches, proceed
+        return None  # Hash matches, proceed
 
     # Hash mismatch or file missing when expected
     log.warning(
@@ -1289,27 +3065,1275 @@ def verify_and_prompt_if_modified(
                 log.warning(
                     f"User chose to proceed, overwriting external changes to {file_path_abs}"
                 )
-                return True
+                return None
             elif choice in ["n", "no"]:
                 log.info(
                     f"User chose not to proceed due to external changes to {file_path_abs}"
                 )
-                return False
+                break
             print(
                 f"{utils.COLOR_RED}Invalid choice. Please enter 'y' or 'n'.{utils.COLOR_RESET}"
             )
         except (EOFError, KeyboardInterrupt):
             print("\nOperation cancelled by user.")
-            return False
+            break
+    file_rel_path = get_relative_path(file_path_abs, workspace_root)
+    return ExternalModificationError(
+        f"{utils.display_path(file_rel_path)} was modified outside of the edit history.",
+        file=file_rel_path,
+    )
+
+
+# --- Disk Usage & Orphaned Artifacts ---
+
+
+def get_referenced_artifacts(
+    history_root: Path, all_entries: List[Dict[str, Any]]
+) -> Tuple[set, set]:
+    """
+    Returns the resolved paths of all diff and checkpoint files referenced by log entries.
+    Diffs are matched both by their stored path and by the diffs/<conv>/<edit_id>.diff
+    convention, mirroring the lookup in get_diff_for_entry.
+    """
+    diffs: set = set()
+    checkpoints: set = set()
+    for entry in all_entries:
+        conv_id = entry.get("conversation_id")
+        edit_id = entry.get("edit_id")
+        if conv_id and edit_id:
+            conventional = history_root / DIFFS_DIR / conv_id / f"{edit_id}.diff"
+            diffs.add(conventional.resolve())
+        diff_file = entry.get("diff_file")
+        if diff_file:
+            diffs.add((history_root / DIFFS_DIR / diff_file).resolve())
+            diffs.add((history_root / diff_file).resolve())
+        checkpoint_file = entry.get("checkpoint_file")
+        if checkpoint_file:
+            checkpoints.add((history_root / checkpoint_file).resolve())
+    return diffs, checkpoints
+
+
+def _iter_artifact_files(base_dir: Path):
+    """Yields regular files under an artifact directory, skipping lock directories."""
+    if not base_dir.is_dir():
+        return
+    for path in base_dir.rglob("*"):
+        if path.is_file() and not any(p.endswith(".lockdir") for p in path.parts):
+            yield path
+
+
+def find_orphaned_artifacts(
+    history_root: Path, all_entries: List[Dict[str, Any]]
+) -> List[Dict[str, Any]]:
+    """Finds diff and checkpoint files that no log entry references."""
+    ref_diffs, ref_checkpoints = get_referenced_artifacts(history_root, all_entries)
+    orphans = []
+    for kind, base_dir, referenced in (
+        ("diff", history_root / DIFFS_DIR, ref_diffs),
+        ("checkpoint", history_root / CHECKPOINTS_DIR, ref_checkpoints),
+    ):
+        for path in _iter_artifact_files(base_dir):
+            if path.resolve() not in referenced:
+                orphans.append(
+                    {
+                        "kind": kind,
+                        "path": get_relative_path(path, history_root),
+                        "bytes": path.stat().st_size,
+                    }
+                )
+    return orphans
+
+
+def compute_disk_usage(
+    history_root: Path, all_entries: List[Dict[str, Any]]
+) -> Dict[str, Any]:
+    """
+    Computes sizes of logs, diffs and checkpoints per conversation.
+    Returns {'totals': {...}, 'conversations': [{conversation_id, logs, diffs,
+    checkpoints, total}, ...] sorted by total descending, 'orphans': [...]}.
+    totals['binary_diffs'] is the part of the diffs that are bsdiff patches of
+    binary files rather than unified diffs.
+    """
+    per_conv: Dict[str, Dict[str, int]] = {}
+    binary_diffs = 0
+
+    def add(conv_id: str, kind: str, size: int) -> None:
+        usage = per_conv.setdefault(
+            conv_id, {"logs": 0, "diffs": 0, "checkpoints": 0}
+        )
+        usage[kind] += size
+
+    for log_file in utils.find_log_files(history_root / LOGS_DIR):
+        conv_id = utils.get_log_conversation_id(log_file)
+        add(conv_id, "logs", log_file.stat().st_size)
+    for kind, dir_name in (("diffs", DIFFS_DIR), ("checkpoints", CHECKPOINTS_DIR)):
+        base_dir = history_root / dir_name
+        for path in _iter_artifact_files(base_dir):
+            # Artifacts are stored as <kind>/[<namespace>/]<conv_id>/<file>
+            rel_parts = path.relative_to(base_dir).parts
+            conv_id = "/".join(rel_parts[:-1]) if len(rel_parts) > 1 else "(none)"
+            size = path.stat().st_size
+            add(conv_id, kind, size)
+            if kind == "diffs":
+                with open(path, "rb") as f:
+                    if is_bsdiff_patch(f.read(len(BSDIFF_MAGIC))):
+                        binary_diffs += size
+
+    conversations = []
+    for conv_id, usage in per_conv.items():
+        conversations.append(
+            {"conversation_id": conv_id, **usage, "total": sum(usage.values())}
+        )
+    conversations.sort(key=lambda c: c["total"], reverse=True)
+
+    totals = {"logs": 0, "diffs": 0, "checkpoints": 0}
+    for conv in conversations:
+        for kind in totals:
+            totals[kind] += conv[kind]
+    totals["total"] = sum(totals.values())
+    totals["binary_diffs"] = binary_diffs
+
+    return {
+        "totals": totals,
+        "conversations": conversations,
+        "orphans": find_orphaned_artifacts(history_root, all_entries),
+    }
+
+
+def summarize_history(
+    history_root: Path, all_entries: List[Dict[str, Any]]
+) -> Dict[str, Any]:
+    """
+    Overall statistics of a history, for `mcpdiff info`: {log_files, entries,
+    entries_by_status, diffs_bytes, checkpoints_bytes, latest_timestamp}. The
+    latest timestamp is the newest entry's, as logged, or None without entries.
+    """
+    logs_dir = history_root / LOGS_DIR
+    by_status: Dict[str, int] = {}
+    for entry in all_entries:
+        status = (entry.get("status") or "unknown").lower()
+        by_status[status] = by_status.get(status, 0) + 1
+    latest = max(
+        all_entries,
+        key=lambda e: utils.parse_timestamp(e.get("timestamp", 0)),
+        default=None,
+    )
+    totals = compute_disk_usage(history_root, all_entries)["totals"]
+    return {
+        "log_files": len(utils.find_log_files(logs_dir)),
+        "entries": len(all_entries),
+        "entries_by_status": by_status,
+        "diffs_bytes": totals["diffs"],
+        "checkpoints_bytes": totals["checkpoints"],
+        "latest_timestamp": latest.get("timestamp") if latest else None,
+    }
+
+
+def summarize_conversations(
+    all_entries: List[Dict[str, Any]],
+) -> List[Dict[str, Any]]:
+    """
+    Summarizes each conversation's edits: counts per status, files touched, the
+    time of its first and last logged edit and the conversation it was forked
+    from. Entries written by mcpdiff itself (snapshots, reverts) are not edits
+    and are left out. Returns a list of {conversation_id, edits, pending,
+    accepted, rejected, files, first_activity, last_activity,
+    parent_conversation_id, forked_at, namespace} sorted by last activity,
+    newest first.
+    """
+    forks = conversation_forks(all_entries)
+    summaries: Dict[str, Dict[str, Any]] = {}
+    for entry in all_entries:
+        conv_id = entry.get("conversation_id")
+        if not conv_id or entry.get("tool_name") == "mcpdiff":
+            continue
+        summary = summaries.setdefault(
+            conv_id,
+            {
+                "conversation_id": conv_id,
+                "edits": 0,
+                "pending": 0,
+                "accepted": 0,
+                "rejected": 0,
+                "files": set(),
+                "first_activity": None,
+                "last_activity": None,
+                "parent_conversation_id": forks.get(conv_id, (None, None))[0],
+                "forked_at": forks.get(conv_id, (None, None))[1],
+                "namespace": utils.get_conversation_namespace(conv_id),
+            },
+        )
+        summary["edits"] += 1
+        status = entry.get("status", "").lower()
+        if status in ("pending", "accepted", "rejected"):
+            summary[status] += 1
+        if entry.get("file_path"):
+            summary["files"].add(entry["file_path"])
+        timestamp = utils.parse_timestamp(entry.get("timestamp", 0))
+        if summary["first_activity"] is None or timestamp < summary["first_activity"]:
+            summary["first_activity"] = timestamp
+        if summary["last_activity"] is None or timestamp > summary["last_activity"]:
+            summary["last_activity"] = timestamp
+
+    result = []
+    for summary in summaries.values():
+        summary["files"] = len(summary["files"])
+        result.append(summary)
+    result.sort(key=lambda s: s["last_activity"] or 0, reverse=True)
+    return result
+
+
+def rank_by_edits(
+    all_entries: List[Dict[str, Any]], key: str, by: str = "edits"
+) -> List[Dict[str, Any]]:
+    """
+    Counts edits per value of `key` ("file_path" or "conversation_id"), in
+    total and per status, and sums their byte_growth (byte_size_after -
+    byte_size_before, a missing size counting as 0), leaving out entries written
+    by mcpdiff itself. Returns a list of {<key>, total_edits, pending, accepted,
+    rejected, byte_growth}, most edited first, or with by="bytes" most grown
+    first (ties by name).
+    """
+    counts: Dict[str, Dict[str, Any]] = {}
+    for entry in all_entries:
+        value = entry.get(key)
+        if not value or entry.get("tool_name") == "mcpdiff":
+            continue
+        row = counts.setdefault(
+            value,
+            {
+                key: value,
+                "total_edits": 0,
+                "pending": 0,
+                "accepted": 0,
+                "rejected": 0,
+                "byte_growth": 0,
+            },
+        )
+        row["total_edits"] += 1
+        status = entry.get("status", "").lower()
+        if status in ("pending", "accepted", "rejected"):
+            row[status] += 1
+        row["byte_growth"] += (entry.get("byte_size_after") or 0) - (
+            entry.get("byte_size_before") or 0
+        )
+    column = "byte_growth" if by == "bytes" else "total_edits"
+    return sorted(counts.values(), key=lambda row: (-row[column], row[key]))
+
+
+def final_file_states(
+    all_entries: List[Dict[str, Any]], conv_id: str
+) -> Dict[str, Optional[str]]:
+    """
+    The files a conversation changed in its non-rejected edits (both ends of a
+    move), each with its hash after the conversation's last such edit: None if
+    that deleted the file or moved it away.
+    """
+    states: Dict[str, Optional[str]] = {}
+    for entry in all_entries:
+        if (
+            entry.get("conversation_id") != conv_id
+            or entry.get("tool_name") == "mcpdiff"
+            or entry.get("status", "").lower() == "rejected"
+        ):
+            continue
+        if entry.get("source_path"):
+            states[entry["source_path"]] = None
+        if entry.get("file_path"):
+            states[entry["file_path"]] = entry.get("hash_after")
+    return states
+
+
+def compare_conversations(
+    all_entries: List[Dict[str, Any]], conv_a: str, conv_b: str
+) -> Dict[str, Any]:
+    """
+    The files changed by conversation A only, B only, and both, as `only_a`,
+    `only_b` and `both` (sorted). Each item of `both` is {file_path, hash_a,
+    hash_b, identical}: whether the two leave the file with the same content.
+    """
+    states_a = final_file_states(all_entries, conv_a)
+    states_b = final_file_states(all_entries, conv_b)
+    return {
+        "conversation_a": conv_a,
+        "conversation_b": conv_b,
+        "only_a": sorted(set(states_a) - set(states_b)),
+        "only_b": sorted(set(states_b) - set(states_a)),
+        "both": [
+            {
+                "file_path": path,
+                "hash_a": states_a[path],
+                "hash_b": states_b[path],
+                "identical": states_a[path] == states_b[path],
+            }
+            for path in sorted(set(states_a) & set(states_b))
+        ],
+    }
+
+
+def collect_checkpoint_info(
+    history_root: Path,
+    all_entries: List[Dict[str, Any]],
+    conv_id: Optional[str] = None,
+    file_path: Optional[str] = None,
+) -> List[Dict[str, Any]]:
+    """
+    Lists checkpoint files with their size, the entry that wrote them and how
+    many non-rejected entries still reference them. A checkpoint is attributed
+    to the earliest entry that references it, and its `written` time is that
+    entry's timestamp. Checkpoint files on disk that no entry references at
+    all are included with their modification time, unless `file_path` is
+    given. Each item is {path, bytes, written, edit_id, conversation_id,
+    file_path, references, exists}.
+    """
+    entries = filter_entries(
+        all_entries, conv_id=conv_id, file_path=file_path, limit=None
+    )
+    checkpoints: Dict[Path, Dict[str, Any]] = {}
+    for entry in sorted(
+        entries, key=lambda e: utils.parse_timestamp(e.get("timestamp", 0))
+    ):
+        checkpoint_rel = entry.get("checkpoint_file")
+        if not checkpoint_rel:
+            continue
+        checkpoint_path = (history_root / checkpoint_rel).resolve()
+        info = checkpoints.get(checkpoint_path)
+        if info is None:
+            exists = checkpoint_path.is_file()
+            info = checkpoints[checkpoint_path] = {
+                "path": checkpoint_rel,
+                "bytes": checkpoint_path.stat().st_size if exists else 0,
+                "written": entry.get("timestamp"),
+                "edit_id": entry.get("edit_id"),
+                "conversation_id": entry.get("conversation_id"),
+                "file_path": entry.get("file_path"),
+                "references": 0,
+                "exists": exists,
+            }
+        if entry.get("status", "").lower() != "rejected":
+            info["references"] += 1
+
+    if not file_path:
+        _, referenced = get_referenced_artifacts(history_root, all_entries)
+        base_dir = history_root / CHECKPOINTS_DIR
+        conv_lower = conv_id.lower() if conv_id else None
+        for path in _iter_artifact_files(base_dir):
+            if path.resolve() in referenced:
+                continue
+            # Checkpoints are stored as checkpoints/<conv_id>/<file>
+            rel_parts = path.relative_to(base_dir).parts
+            path_conv = rel_parts[0] if len(rel_parts) > 1 else None
+            if conv_lower and not (
+                path_conv
+                and (
+                    path_conv.lower().startswith(conv_lower)
+                    or path_conv.lower().endswith(conv_lower)
+                )
+            ):
+                continue
+            stat = path.stat()
+            checkpoints[path.resolve()] = {
+                "path": get_relative_path(path, history_root),
+                "bytes": stat.st_size,
+                "written": stat.st_mtime,
+                "edit_id": None,
+                "conversation_id": path_conv,
+                "file_path": None,
+                "references": 0,
+                "exists": True,
+            }
+    return list(checkpoints.values())
+
+
+def plan_checkpoint_pruning(
+    history_root: Path,
+    all_entries: List[Dict[str, Any]],
+    keep_n: int,
+    conv_id: Optional[str] = None,
+    file_path: Optional[str] = None,
+) -> List[Dict[str, Any]]:
+    """
+    Picks the checkpoints to prune so that at most `keep_n` (>= 1) checkpoints
+    per file remain among the entries matching the filters. Checkpoints are
+    ordered by the first entry of the file's history that references them, and
+    only existing checkpoint files count. Snapshots mcpdiff takes before a
+    reject are backups, not checkpoints, and are left alone. Returns one item
+    per checkpoint to prune: {path, file_path, bytes, edit_ids}, where edit_ids
+    are all entries referencing it.
+
+    Raises HistoryError unless every affected file can still be reconstructed
+    after pruning: the oldest kept checkpoint of the file and the diffs of all
+    applicable edits after it must exist.
+    """
+    if keep_n < 1:
+        raise HistoryError("--keep-n must be at least 1.")
+    in_scope = {
+        id(e)
+        for e in filter_entries(
+            all_entries, conv_id=conv_id, file_path=file_path, limit=None
+        )
+    }
+    files = sorted(
+        {
+            e["file_path"]
+            for e in all_entries
+            if id(e) in in_scope and e.get("file_path")
+        }
+    )
+
+    plan: List[Dict[str, Any]] = []
+    problems: List[str] = []
+    for file_rel in files:
+        file_entries = get_relevant_history_for_file(file_rel, all_entries)
+        # Checkpoint paths in order of first reference, with that index
+        first_index: Dict[str, int] = {}
+        for index, entry in enumerate(file_entries):
+            checkpoint_rel = entry.get("checkpoint_file")
+            if (
+                id(entry) in in_scope
+                and entry.get("file_path") == file_rel
+                and entry.get("tool_name") != "mcpdiff"
+                and checkpoint_rel
+                and checkpoint_rel not in first_index
+                and (history_root / checkpoint_rel).is_file()
+            ):
+                first_index[checkpoint_rel] = index
+        ordered = sorted(first_index, key=lambda c: first_index[c])
+        if len(ordered) <= keep_n:
+            continue
+        pruned, kept = ordered[:-keep_n], ordered[-keep_n:]
+
+        # The oldest kept checkpoint must be able to replace the pruned ones
+        start = first_index[kept[0]]
+        for entry in file_entries[start:]:
+            status = entry.get("status", "").lower()
+            operation = entry.get("operation", "").lower()
+            if entry.get("tool_name") == "mcpdiff" or status == "rejected":
+                continue
+            needs_diff = operation in ("edit", "replace", "append", "partial")
+            if needs_diff and not find_diff_file(entry, history_root):
+                problems.append(
+                    f"{utils.display_path(file_rel)}: diff of {entry.get('edit_id')} after the oldest kept checkpoint is missing"
+                )
+                break
+
+        for checkpoint_rel in pruned:
+            plan.append(
+                {
+                    "path": checkpoint_rel,
+                    "file_path": file_rel,
+                    "bytes": (history_root / checkpoint_rel).stat().st_size,
+                    "edit_ids": [
+                        e["edit_id"]
+                        for e in all_entries
+                        if e.get("checkpoint_file") == checkpoint_rel
+                        and e.get("edit_id")
+                    ],
+                }
+            )
+
+    if problems:
+        raise HistoryError(
+            "Not pruning, files could not be reconstructed afterwards:\n  "
+            + "\n  ".join(problems)
+        )
+    return plan
+
+
+def prune_checkpoints(
+    history_root: Path,
+    all_entries: List[Dict[str, Any]],
+    plan: List[Dict[str, Any]],
+    lock_timeout: Optional[float] = None,
+) -> int:
+    """
+    Carries out a plan from plan_checkpoint_pruning(): sets checkpoint_file to
+    null on the referencing entries, then deletes the checkpoint files once
+    every affected log was written. Returns the number of bytes freed.
+    """
+    pruned = {item["path"] for item in plan}
+    log_names = {
+        e["log_file_source"]
+        for e in all_entries
+        if e.get("checkpoint_file") in pruned and e.get("log_file_source")
+    }
+    updates: Dict[Path, List[Dict[str, Any]]] = {}
+    for log_file_name in log_names:
+        log_file_path = history_root / LOGS_DIR / log_file_name
+        log_entries = utils.read_log_file(log_file_path, lock_timeout=lock_timeout)
+        for entry in log_entries:
+            if entry.get("checkpoint_file") in pruned:
+                entry["checkpoint_file"] = None
+        updates[log_file_path] = log_entries
+    committed, error = utils.write_log_files(updates, lock_timeout=lock_timeout)
+    if error:
+        # Logs that were written no longer reference their checkpoints, but the
+        # files are still there, so nothing is lost
+        raise HistoryError(f"Could not update logs, no checkpoints deleted: {error}")
+
+    for entry in all_entries:
+        if entry.get("checkpoint_file") in pruned:
+            entry["checkpoint_file"] = None
+    freed = 0
+    for item in plan:
+        checkpoint_path = history_root / item["path"]
+        try:
+            freed += checkpoint_path.stat().st_size
+            checkpoint_path.unlink()
+        except OSError as e:
+            log.warning(f"Could not delete checkpoint {checkpoint_path}: {e}")
+    return freed
+
+
+def enforce_history_depth(
+    history_root: Path,
+    file_path: str,
+    conv_id: str,
+    depth: int,
+    lock_timeout: Optional[float] = None,
+    invocation: Optional[audit.Invocation] = None,
+) -> Dict[str, Any]:
+    """
+    Keeps only the `depth` (>= 1) newest checkpoints of a file in one
+    conversation (the history_depth setting), once a new one was written.
+    Checkpoints, snapshots included, are ordered by the first entry referencing
+    them, and the older ones are pruned with prune_checkpoints().
+
+    The file's replay must still reach its current state afterwards. If it
+    would start from a pruned checkpoint, a later one is promoted first (see
+    _promote_checkpoint()); if none can be, the checkpoint the replay starts
+    from is kept beyond the depth. Returns {pruned, kept: checkpoint paths,
+    promoted: the edit ID whose checkpoint was written, or None}.
+    """
+    if depth < 1:
+        raise HistoryError("history_depth must be at least 1.")
+    all_entries = find_all_entries(history_root, lock_timeout)
+    file_entries = get_relevant_history_for_file(file_path, all_entries)
+    first_index: Dict[str, int] = {}
+    for index, entry in enumerate(file_entries):
+        checkpoint_rel = entry.get("checkpoint_file")
+        if (
+            entry.get("conversation_id") == conv_id
+            and entry.get("file_path") == file_path
+            and checkpoint_rel
+            and checkpoint_rel not in first_index
+            and (history_root / checkpoint_rel).is_file()
+        ):
+            first_index[checkpoint_rel] = index
+    ordered = sorted(first_index, key=lambda c: first_index[c])
+    result: Dict[str, Any] = {"pruned": [], "kept": [], "promoted": None}
+    if len(ordered) <= depth:
+        return result
+    pruned = ordered[:-depth]
+    pruned_paths = {(history_root / c).resolve() for c in pruned}
+
+    def exists_after(path: Path) -> bool:
+        return path.resolve() not in pruned_paths and path.is_file()
+
+    plan = plan_file_replay(file_path, all_entries, history_root)
+    if replay_plan_error(plan) is not None:
+        # Already broken: only snapshots, which replays never use, go
+        result["kept"] = [
+            c
+            for c in pruned
+            if any(
+                e.get("checkpoint_file") == c and e.get("tool_name") != "mcpdiff"
+                for e in file_entries
+            )
+        ]
+    elif replay_plan_error(
+        plan_file_replay(file_path, all_entries, history_root, exists=exists_after)
+    ):
+        result["promoted"] = _promote_checkpoint(
+            plan, conv_id, history_root, lock_timeout, invocation
+        )
+        if result["promoted"] is None or replay_plan_error(
+            plan_file_replay(file_path, all_entries, history_root, exists=exists_after)
+        ):
+            result["kept"] = [
+                c
+                for c in pruned
+                if (history_root / c).resolve() == plan.checkpoint_path
+            ]
+    result["pruned"] = [c for c in pruned if c not in result["kept"]]
+    if result["pruned"]:
+        prune_checkpoints(
+            history_root,
+            all_entries,
+            [{"path": c, "file_path": file_path} for c in result["pruned"]],
+            lock_timeout,
+        )
+    return result
+
+
+def _promote_checkpoint(
+    plan: ReplayPlan,
+    conv_id: str,
+    history_root: Path,
+    lock_timeout: Optional[float] = None,
+    invocation: Optional[audit.Invocation] = None,
+) -> Optional[str]:
+    """
+    Writes the state before the oldest edit of `conv_id` that `plan` applies
+    after its starting entry as that edit's checkpoint, replayed from the
+    plan's start. Every edit before it must be accepted, so that no reject can
+    need the older state again, and the result must hash to the edit's
+    hash_before. Updates the log and the entry in `plan`, and records the
+    promotion in the audit log. Returns the edit ID, or None if there is no
+    such edit.
+    """
+    if plan.start not in (PLAN_START_CHECKPOINT, PLAN_START_EMPTY):
+        return None
+    for index, step in enumerate(plan.steps):
+        entry = step.entry
+        if (
+            index > 0
+            and entry.get("conversation_id") == conv_id
+            and entry.get("file_path") == plan.file_path
+            and step.needs_diff
+            and entry.get("hash_before")
+        ):
+            break
+        if step.action == "split":
+            continue  # Applied as its children
+        if step.action != "apply" or step.status != "accepted":
+            return None
+    else:
+        return None
+
+    temp_dir = Path(tempfile.mkdtemp(prefix="mcp_promote_"))
+    try:
+        before = replace(plan, steps=plan.steps[:index])
+        replayed = _execute_replay_plan(before, temp_dir, ReplayOptions())
+        state_path = temp_dir / plan.file_path
+        if (
+            replayed["error"]
+            or not state_path.is_file()
+            or utils.calculate_hash(str(state_path)) != entry["hash_before"]
+        ):
+            return None
+        edit_id = step.edit_id
+        checkpoint_rel = str(Path(CHECKPOINTS_DIR) / conv_id / f"{edit_id}.chkpt")
+        checkpoint_path = history_root / checkpoint_rel
+        checkpoint_path.parent.mkdir(parents=True, exist_ok=True)
+        shutil.copy2(state_path, checkpoint_path)
+    finally:
+        shutil.rmtree(temp_dir, ignore_errors=True)
+
+    # The caller holds the conversation lock, as when a snapshot is logged
+    log_file_path = history_root / LOGS_DIR / entry["log_file_source"]
+    log_entries = utils.read_log_file(
+        log_file_path, lock_timeout=lock_timeout, migrate=False, strict=True
+    )
+    for logged in log_entries:
+        if logged.get("edit_id") == edit_id:
+            logged["checkpoint_file"] = checkpoint_rel
+    utils.write_log_file(log_file_path, log_entries, lock_timeout=lock_timeout)
+    entry["checkpoint_file"] = checkpoint_rel
+    details = utils.encode_log_paths({"file_path": plan.file_path})
+    audit.record(
+        history_root,
+        "promote",
+        [edit_id],
+        "success",
+        invocation=invocation,
+        checkpoint_file=checkpoint_rel,
+        **details,
+    )
+    return edit_id
+
+
+def plan_conversation_pruning(
+    history_root: Path,
+    all_entries: List[Dict[str, Any]],
+    keep_last: int,
+    keep_artifacts_for_pending: bool = False,
+) -> List[Dict[str, Any]]:
+    """
+    Picks the conversations whose diffs and checkpoints to drop so that only the
+    `keep_last` conversations with the most recent activity keep theirs. With
+    `keep_artifacts_for_pending`, conversations with pending edits are kept
+    too, so those edits can still be accepted or rejected. Conversations with
+    no artifacts left are not listed. Returns one item per conversation:
+    {conversation_id, last_activity, edits, files (the artifact paths relative
+    to history_root), bytes}, oldest last activity first.
+    """
+    if keep_last < 0:
+        raise HistoryError("--keep-last must not be negative.")
+    summaries = summarize_conversations(all_entries)
+    plan: List[Dict[str, Any]] = []
+    for summary in summaries[keep_last:]:
+        if keep_artifacts_for_pending and summary["pending"]:
+            continue
+        conv_id = summary["conversation_id"]
+        files = [
+            path
+            for dir_name in (DIFFS_DIR, CHECKPOINTS_DIR)
+            for path in _iter_artifact_files(history_root / dir_name / conv_id)
+        ]
+        if not files:
+            continue
+        plan.append(
+            {
+                "conversation_id": conv_id,
+                "last_activity": summary["last_activity"],
+                "edits": summary["edits"],
+                "files": [str(path.relative_to(history_root)) for path in files],
+                "bytes": sum(path.stat().st_size for path in files),
+            }
+        )
+    plan.reverse()
+    return plan
+
+
+def prune_conversations(
+    history_root: Path,
+    all_entries: List[Dict[str, Any]],
+    plan: List[Dict[str, Any]],
+    lock_timeout: Optional[float] = None,
+) -> int:
+    """
+    Carries out a plan from plan_conversation_pruning(): marks every entry of
+    the conversations `archived` and sets its diff_file and checkpoint_file to
+    null, then deletes the artifact files once every affected log was written.
+    The logs themselves are kept as the audit trail. Returns the number of
+    bytes freed.
+    """
+    conv_ids = {item["conversation_id"] for item in plan}
+    log_names = {
+        e["log_file_source"]
+        for e in all_entries
+        if e.get("conversation_id") in conv_ids and e.get("log_file_source")
+    }
+    updates: Dict[Path, List[Dict[str, Any]]] = {}
+    for log_file_name in log_names:
+        log_file_path = history_root / LOGS_DIR / log_file_name
+        log_entries = utils.read_log_file(log_file_path, lock_timeout=lock_timeout)
+        for entry in log_entries:
+            if entry.get("conversation_id") in conv_ids:
+                _archive_entry(entry)
+        updates[log_file_path] = log_entries
+    committed, error = utils.write_log_files(updates, lock_timeout=lock_timeout)
+    if error:
+        # Logs that were written no longer reference their artifacts, but the
+        # files are still there, so nothing is lost
+        raise HistoryError(f"Could not update logs, no artifacts deleted: {error}")
+
+    for entry in all_entries:
+        if entry.get("conversation_id") in conv_ids:
+            _archive_entry(entry)
+    freed = 0
+    for item in plan:
+        for path_rel in item["files"]:
+            path = history_root / path_rel
+            try:
+                freed += path.stat().st_size
+                path.unlink()
+            except OSError as e:
+                log.warning(f"Could not delete {path}: {e}")
+        for dir_name in (DIFFS_DIR, CHECKPOINTS_DIR):
+            try:
+                (history_root / dir_name / item["conversation_id"]).rmdir()
+            except OSError:
+                pass  # Not empty (lock directories) or already gone
+    return freed
+
+
+def _archive_entry(entry: Dict[str, Any]) -> None:
+    entry["diff_file"] = None
+    entry["checkpoint_file"] = None
+    entry["archived"] = True
+
+
+def plan_rejected_squash(
+    history_root: Path, all_entries: List[Dict[str, Any]], conv_id: str
+) -> Dict[str, Any]:
+    """
+    Picks the rejected edits of a conversation to remove from its log, with
+    their diffs and checkpoints. An edit is kept, with the reason, while a
+    non-rejected edit names it as its parent_edit_id, or while replays could
+    go wrong without it: a later checkpoint of the file may contain its change,
+    which replays only know to pass over while the edit is logged, and its
+    checkpoint can only go to the conversation's next edit of the file if no
+    other edit comes in between.
+
+    Returns {remove: one item per edit, {entry, files (artifact paths relative
+    to history_root to delete), bytes}; skipped: [(entry, reason)]; moved:
+    {checkpoint path: the edit ID that references it instead}}.
+    """
+    rejected = [
+        e
+        for e in all_entries
+        if e.get("conversation_id") == conv_id
+        and e.get("status") == "rejected"
+        and e.get("tool_name") != "mcpdiff"
+        and e.get("edit_id")
+    ]
+    removed = {e["edit_id"] for e in rejected}
+    children = {
+        e.get("parent_edit_id")
+        for e in all_entries
+        if e.get("parent_edit_id") and e.get("status") != "rejected"
+    }
+    reasons: Dict[str, str] = {}
+    moved: Dict[str, str] = {}
+    # Keeping one edit can make its checkpoint block another, so repeat
+    changed = True
+    while changed:
+        changed = False
+        moved = {}
+        for entry in rejected:
+            edit_id = entry["edit_id"]
+            if edit_id not in removed:
+                continue
+            reason, target = _squash_blocker(
+                entry, all_entries, removed, children, history_root
+            )
+            if reason:
+                removed.discard(edit_id)
+                reasons[edit_id] = reason
+                changed = True
+            elif target is not None:
+                moved[entry["checkpoint_file"]] = target["edit_id"]
+
+    kept = [e for e in all_entries if e.get("edit_id") not in removed]
+    referenced = {
+        str(path.relative_to(history_root))
+        for e in kept
+        for path in (
+            find_diff_file(e, history_root),
+            history_root / e["checkpoint_file"] if e.get("checkpoint_file") else None,
+        )
+        if path is not None
+    }
+    remove: List[Dict[str, Any]] = []
+    for entry in rejected:
+        if entry["edit_id"] not in removed:
+            continue
+        paths = []
+        diff_path = find_diff_file(entry, history_root)
+        if diff_path is not None:
+            paths.append(diff_path)
+        checkpoint_rel = entry.get("checkpoint_file")
+        if checkpoint_rel and checkpoint_rel not in moved:
+            checkpoint_path = history_root / checkpoint_rel
+            if checkpoint_path.is_file():
+                paths.append(checkpoint_path)
+        files = [
+            str(path.relative_to(history_root))
+            for path in paths
+            if str(path.relative_to(history_root)) not in referenced
+        ]
+        remove.append(
+            {
+                "entry": entry,
+                "files": files,
+                "bytes": sum((history_root / f).stat().st_size for f in files),
+            }
+        )
+    skipped = [(e, reasons[e["edit_id"]]) for e in rejected if e["edit_id"] in reasons]
+    return {"remove": remove, "skipped": skipped, "moved": moved}
+
+
+def _squash_blocker(
+    entry: Dict[str, Any],
+    all_entries: List[Dict[str, Any]],
+    removed: Set[str],
+    children: Set[str],
+    history_root: Path,
+) -> Tuple[Optional[str], Optional[Dict[str, Any]]]:
+    """
+    Why a rejected edit must stay logged, or None, and the edit its checkpoint
+    would move to, for plan_rejected_squash().
+    """
+    edit_id = entry["edit_id"]
+    if edit_id in children:
+        return "a non-rejected edit names it as its parent", None
+
+    def is_kept(e: Dict[str, Any]) -> bool:
+        return e.get("tool_name") != "mcpdiff" and e.get("edit_id") not in removed
+
+    file_entries = get_relevant_history_for_file(entry["file_path"], all_entries)
+    index = next(i for i, e in enumerate(file_entries) if e is entry)
+    later = file_entries[index + 1 :]
+    target = None
+    checkpoint_rel = entry.get("checkpoint_file")
+    if checkpoint_rel and (history_root / checkpoint_rel).is_file():
+        target = next(
+            (
+                e
+                for e in later
+                if is_kept(e)
+                and e.get("conversation_id") == entry.get("conversation_id")
+                and e.get("file_path") == entry["file_path"]
+            ),
+            None,
+        )
+    # An edit with a checkpoint of its own (a promoted one) needs none
+    if target is not None and target.get("checkpoint_file"):
+        target = None
+    if target is not None:
+        between = later[: later.index(target)]
+        if any(is_kept(e) and e.get("status") != "rejected" for e in between):
+            return "later edits of the file replay from its checkpoint", None
+
+    paths = [entry["file_path"]]
+    if entry.get("source_path"):
+        paths.append(entry["source_path"])
+    for path in paths:
+        file_entries = get_relevant_history_for_file(path, all_entries)
+        index = next(i for i, e in enumerate(file_entries) if e is entry)
+        for e in file_entries[index + 1 :]:
+            # Its own checkpoint, which a failed squash already moved, predates it
+            if (
+                is_kept(e)
+                and e.get("checkpoint_file")
+                and e["checkpoint_file"] != checkpoint_rel
+                and (history_root / e["checkpoint_file"]).is_file()
+            ):
+                return f"a later checkpoint of {path} may contain its change", None
+    return None, target
+
+
+def squash_rejected(
+    history_root: Path,
+    all_entries: List[Dict[str, Any]],
+    plan: Dict[str, Any],
+    lock_timeout: Optional[float] = None,
+    invocation: Optional[audit.Invocation] = None,
+) -> int:
+    """
+    Carries out a plan from plan_rejected_squash(): removes the edits from
+    their logs and moves the checkpoints, then deletes the artifact files.
+    Returns the number of bytes freed.
+
+    A checkpoint moves to a later edit, which is in the same shard or a later
+    one, so the shards are written newest first: when a write fails, every
+    checkpoint is still referenced by its old or its new edit.
+    """
+    removed = {item["entry"]["edit_id"] for item in plan["remove"]}
+    moved = {edit_id: path for path, edit_id in plan["moved"].items()}
+    log_names = {
+        e["log_file_source"]
+        for e in all_entries
+        if (e.get("edit_id") in removed or e.get("edit_id") in moved)
+        and e.get("log_file_source")
+    }
+    conv_ids = {item["entry"].get("conversation_id") for item in plan["remove"]}
+    log_paths = [
+        log_file_path
+        for conv_id in sorted(conv_ids)
+        for log_file_path in reversed(
+            utils.get_conversation_log_files(history_root, conv_id)
+        )
+        if utils.get_log_file_source(log_file_path) in log_names
+    ]
+    updates: Dict[Path, List[Dict[str, Any]]] = {}
+    for log_file_path in log_paths:
+        log_entries = utils.read_log_file(log_file_path, lock_timeout=lock_timeout)
+        kept = [e for e in log_entries if e.get("edit_id") not in removed]
+        for entry in kept:
+            if entry.get("edit_id") in moved:
+                entry["checkpoint_file"] = moved[entry["edit_id"]]
+        updates[log_file_path] = kept
+    committed, error = utils.write_log_files(updates, lock_timeout=lock_timeout)
+    if error:
+        # Logs that were written no longer have the edits, but the files are
+        # still there, so nothing is lost
+        raise HistoryError(f"Could not update logs, no artifacts deleted: {error}")
+
+    all_entries[:] = [e for e in all_entries if e.get("edit_id") not in removed]
+    for entry in all_entries:
+        if entry.get("edit_id") in moved:
+            entry["checkpoint_file"] = moved[entry["edit_id"]]
+    freed = 0
+    for item in plan["remove"]:
+        for path_rel in item["files"]:
+            path = history_root / path_rel
+            try:
+                freed += path.stat().st_size
+                path.unlink()
+            except OSError as e:
+                log.warning(f"Could not delete {path}: {e}")
+    if removed:
+        audit.record(
+            history_root,
+            "squash",
+            sorted(removed),
+            "success",
+            invocation=invocation,
+            lock_timeout=lock_timeout,
+            freed_bytes=freed,
+        )
+    return freed
+
+
+# --- Expiring Old Pending Edits ---
+
+
+def find_expired_entries(
+    all_entries: List[Dict[str, Any]],
+    older_than: float,
+    now: Optional[float] = None,
+) -> List[Dict[str, Any]]:
+    """Pending edits made more than `older_than` seconds before `now`, oldest first."""
+    cutoff = (time.time() if now is None else now) - older_than
+    expired = [
+        e
+        for e in all_entries
+        if e.get("status") == "pending"
+        and e.get("tool_name") != "mcpdiff"
+        and not e.get("archived")
+        and utils.parse_timestamp(e.get("timestamp", 0)) < cutoff
+    ]
+    expired.sort(key=lambda e: utils.parse_timestamp(e.get("timestamp", 0)))
+    return expired
+
+
+def expire_entries(
+    expired: List[Dict[str, Any]],
+    all_entries: List[Dict[str, Any]],
+    workspace_root: Path,
+    history_root: Path,
+    lock_timeout: Optional[float] = None,
+    invocation: Optional[audit.Invocation] = None,
+) -> Dict[str, Any]:
+    """
+    Rejects the edits found by find_expired_entries() without prompting, for
+    `expire-old` and the daemon: file by file, under the conversation lock, it
+    marks the edits rejected and re-applies the file from its history. Files
+    changed outside the history, or logged to since `all_entries` was read, are
+    left alone, as are files whose re-apply fails (their edits go back to
+    pending).
+
+    Returns {'expired': [entries], 'skipped': [(entry, reason)]}.
+    """
+    report: Dict[str, Any] = {"expired": [], "skipped": []}
+    by_conversation: Dict[str, Dict[str, List[Dict[str, Any]]]] = {}
+    for entry in expired:
+        files = by_conversation.setdefault(entry.get("conversation_id", ""), {})
+        files.setdefault(entry.get("file_path", ""), []).append(entry)
+
+    for conv_id, files in by_conversation.items():
+        conv_lock = utils.ConversationLock(history_root, conv_id)
+        conv_lock.acquire(lock_timeout)
+        with conv_lock:
+            active_files = find_new_conversation_files(
+                conv_id, all_entries, history_root, lock_timeout
+            )
+            for file_path_rel, file_edits in files.items():
+                reason = _expire_file_edits(
+                    file_path_rel,
+                    file_edits,
+                    active_files,
+                    all_entries,
+                    workspace_root,
+                    history_root,
+                    lock_timeout,
+                    invocation,
+                )
+                if reason:
+                    report["skipped"].extend((e, reason) for e in file_edits)
+                else:
+                    report["expired"].extend(file_edits)
+    return report
+
+
+def _expire_file_edits(
+    file_path_rel: str,
+    file_edits: List[Dict[str, Any]],
+    active_files: Dict[str, int],
+    all_entries: List[Dict[str, Any]],
+    workspace_root: Path,
+    history_root: Path,
+    lock_timeout: Optional[float],
+    invocation: Optional[audit.Invocation],
+) -> Optional[str]:
+    """Rejects one file's expired edits. Returns why it did not, or None."""
+    paths = {file_path_rel}
+    paths.update(e["source_path"] for e in file_edits if e.get("source_path"))
+    if any(path in active_files for path in paths):
+        return "conversation still active"
+    last_applied = get_last_applied_edit_for_file(file_path_rel, all_entries)
+    expected_hash = last_applied.get("hash_after") if last_applied else None
+    if expected_hash and not verify_file_hash(
+        workspace_root / file_path_rel, expected_hash
+    ):
+        return "modified outside the history"
+
+    update = update_entries_status(
+        file_edits,
+        "rejected",
+        history_root,
+        lock_timeout=lock_timeout,
+        allowed_from=["pending"],
+        invocation=invocation,
+    )
+    if update["failed"] or update["skipped"]:
+        return "status not updated"
+
+    for path in sorted(paths):
+        result = reconstruct_file_from_history(
+            path, all_entries, workspace_root, history_root, invocation=invocation
+        )
+        if result["error"]:
+            update_entries_status(
+                file_edits,
+                "pending",
+                history_root,
+                lock_timeout=lock_timeout,
+                invocation=invocation,
+            )
+            return f"re-apply failed: {result['error']}"
+    return None
+
+
+# --- Reattaching Edits ---
+def reattach_entries(
+    history_root: Path,
+    from_conv: str,
+    to_conv: str,
+    edit_ids: Optional[List[str]] = None,
+    lock_timeout: Optional[float] = None,
+    invocation: Optional[audit.Invocation] = None,
+) -> List[Dict[str, Any]]:
+    """
+    Moves entries of conversation `from_conv` (those in `edit_ids`, or all of
+    them) to the end of `to_conv`'s latest log, for a session that restarted
+    under a new conversation ID. Moved entries get `to_conv` as their
+    conversation_id and tool_call_index values continuing from its highest, in
+    their original order. The destination log is written before the entries
+    are removed from the source logs, so a failed write never loses them: if
+    removing them fails, they are in both conversations until reattach is run
+    again, which then only removes them from the source. Diffs and checkpoints
+    stay where they are, since `diff_file` and `checkpoint_file` still point at
+    them. The caller holds both conversation locks.
+    Returns the moved entries as written.
+    """
+    if from_conv == to_conv:
+        raise HistoryError("Cannot reattach a conversation to itself.")
+    to_logs = utils.get_conversation_log_files(history_root, to_conv)
+    if not to_logs:
+        raise HistoryError(f"No log found for conversation {to_conv}")
+
+    source_updates: Dict[Path, List[Dict[str, Any]]] = {}
+    moved: List[Dict[str, Any]] = []
+    for log_file_path in utils.get_conversation_log_files(history_root, from_conv):
+        log_entries = utils.read_log_file(
+            log_file_path, lock_timeout=lock_timeout, migrate=False, strict=True
+        )
+        kept = []
+        for entry in log_entries:
+            if edit_ids is None or entry.get("edit_id") in edit_ids:
+                moved.append(entry)
+            else:
+                kept.append(entry)
+        if len(kept) != len(log_entries):
+            source_updates[log_file_path] = kept
+    if edit_ids is not None:
+        missing = set(edit_ids) - {e.get("edit_id") for e in moved}
+        if missing:
+            raise HistoryError(
+                f"Not in conversation {from_conv}: {', '.join(sorted(missing))}"
+            )
+    if not moved:
+        raise HistoryError(f"No entries to reattach in conversation {from_conv}")
+
+    to_entries = [
+        e
+        for log_file_path in to_logs
+        for e in utils.read_log_file(log_file_path, lock_timeout=lock_timeout)
+    ]
+    next_index = 1 + max(
+        (
+            e["tool_call_index"]
+            for e in to_entries
+            if isinstance(e.get("tool_call_index"), int)
+        ),
+        default=-1,
+    )
+    moved.sort(
+        key=lambda e: (
+            utils.parse_timestamp(e.get("timestamp", 0)),
+            e.get("tool_call_index", float("inf")),
+        )
+    )
+    # Entries an interrupted reattach already wrote to the destination are only
+    # removed from the source
+    already_there = {e.get("edit_id"): e for e in to_entries}
+    appended = [e for e in moved if e.get("edit_id") not in already_there]
+    for offset, entry in enumerate(appended):
+        entry["conversation_id"] = to_conv
+        entry["tool_call_index"] = next_index + offset
+
+    # The destination goes first: write_log_files() commits in order and stops
+    # at the first failure
+    to_log = to_logs[-1]
+    updates: Dict[Path, List[Dict[str, Any]]] = {}
+    if appended:
+        to_log_entries = utils.read_log_file(
+            to_log, lock_timeout=lock_timeout, migrate=False, strict=True
+        )
+        updates[to_log] = to_log_entries + appended
+    updates.update(source_updates)
+    committed, error = utils.write_log_files(updates, lock_timeout=lock_timeout)
+    if error and appended and to_log in committed:
+        raise HistoryError(
+            f"Reattached entries were written to {to_conv} but are still in "
+            f"{from_conv}: {error}. Run reattach again to remove them from {from_conv}."
+        )
+    if error:
+        raise HistoryError(f"Could not reattach entries: {error}")
+    moved = [already_there.get(e.get("edit_id"), e) for e in moved]
+
+    audit.record(
+        history_root,
+        "reattach",
+        [e["edit_id"] for e in moved],
+        "success",
+        invocation=invocation,
+        lock_timeout=lock_timeout,
+        from_conversation=from_conv,
+        to_conversation=to_conv,
+    )
+    for entry in moved:
+        entry["log_file_source"] = utils.get_log_file_source(to_log)
+    log.info(f"Reattached {len(moved)} entries from {from_conv} to {to_conv}")
+    return moved
+
+
+def _is_lock_file_free(path: Path) -> bool:
+    """True if no process holds the server's lock (a `filelock` file) on `path`."""
+    try:
+        with open(path, "a") as handle:
+            utils.lock_file_handle(handle)
+            utils.unlock_file_handle(handle)
+        return True
+    except OSError:
+        return False
 
 
 def cleanup_stale_locks(history_root: Path) -> int:
-    """Clean up any stale lock directories under the history directory."""
+    """
+    Clean up any stale lock directories under the history directory, and the
+    server's file locks in locks/ that no process holds.
+    """
     cleaned_count = 0
     if not history_root.is_dir():
         log.debug("History root does not exist, no locks to clean.")
         return 0
 
+    locks_dir = history_root / utils.LOCKS_DIR
+    if locks_dir.is_dir():
+        for lock_file in locks_dir.glob("*.lock"):
+            if lock_file.is_file() and _is_lock_file_free(lock_file):
+                try:
+                    lock_file.unlink()
+                    log.info(f"Cleaned up stale lock: {lock_file}")
+                    cleaned_count += 1
+                except OSError as e:
+                    log.warning(f"Error cleaning lock {lock_file}: {e}")
+
     # Use rglob to find all .lockdir directories recursively
     for lockdir in history_root.rglob("*.lockdir"):
         if lockdir.is_dir():  # Ensure it's actually a directory
@@ -1328,6 +4352,30 @@ def cleanup_stale_locks(history_root: Path) -> int:
     return cleaned_count
 
 
+def find_stray_lock_files(
+    workspace_root: Path, all_entries: List[Dict[str, Any]]
+) -> List[Path]:
+    """
+    Lock files older servers left next to tracked files (`<file>.lock`): empty,
+    held by no process, and named after a file in the history, so that lock
+    files of other tools (Cargo.lock, say) are never taken for them.
+    """
+    tracked = {
+        path
+        for entry in all_entries
+        for path in (entry.get("file_path"), entry.get("source_path"))
+        if path
+    }
+    strays = []
+    for path in sorted(tracked):
+        lock_file = workspace_root / f"{path}.lock"
+        if f"{path}.lock" in tracked or not lock_file.is_file():
+            continue
+        if lock_file.stat().st_size == 0 and _is_lock_file_free(lock_file):
+            strays.append(lock_file)
+    return strays
+
+
 def add_snapshot_log_entry(
     file_path_rel: str,
     current_hash: Optional[str],
@@ -0,0 +1,55 @@
+# mcpdiff_hooks.py
+
+import os
+import subprocess
+from pathlib import Path
+from typing import Any, Dict, Optional
+
+from mcpdiff_utils import log
+
+# --- Hooks ---
+# Executable scripts in <workspace>/.mcp/hooks/ named after the event, e.g.
+# .mcp/hooks/pre-accept. A non-zero exit from a pre-* hook aborts the action;
+# the exit code of a post-* hook is only reported.
+HOOKS_DIR_NAME = "hooks"
+HOOK_EVENTS = ("pre-accept", "post-accept", "pre-reject", "post-reject")
+
+
+def get_hook_path(workspace_root: Path, event: str) -> Optional[Path]:
+    """Returns the hook script for an event if it exists and is executable."""
+    hook_path = workspace_root / ".mcp" / HOOKS_DIR_NAME / event
+    if hook_path.is_file() and os.access(hook_path, os.X_OK):
+        return hook_path
+    if hook_path.exists():
+        log.debug(f"Ignoring hook {hook_path}: not an executable file.")
+    return None
+
+
+def run_hook(workspace_root: Path, event: str, entry: Dict[str, Any]) -> int:
+    """
+    Runs the hook for `event` with the edit described in the environment
+    (EDIT_ID, CONVERSATION_ID, FILE_PATH, OPERATION). The hook runs in the
+    workspace root and shares the terminal. Returns its exit code, or 0 if no
+    hook is installed. A hook that cannot be started counts as exit code 127.
+    """
+    hook_path = get_hook_path(workspace_root, event)
+    if hook_path is None:
+        return 0
+
+    env = dict(os.environ)
+    env.update(
+        {
+            "EDIT_ID": entry.get("edit_id") or "",
+            "CONVERSATION_ID": entry.get("conversation_id") or "",
+            "FILE_PATH": entry.get("file_path") or "",
+            "OPERATION": entry.get("operation") or "",
+        }
+    )
+    try:
+        result = subprocess.run([str(hook_path)], cwd=workspace_root, env=env)
+        exit_code = result.returncode
+    except OSError as e:
+        log.debug(f"Hook {event} could not be started: {e}")
+        exit_code = 127
+    log.debug(f"Hook {event} for edit {env['EDIT_ID']} exited with code {exit_code}")
+    return exit_code
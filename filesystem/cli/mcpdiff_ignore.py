@@ -0,0 +1,32 @@
+# mcpdiff_ignore.py
+
+from pathlib import Path
+
+from mcpdiff_utils import history_format
+
+# --- Ignore File ---
+# <workspace>/.mcp/ignore lists paths, with gitignore-style patterns, whose edits
+# `status` hides and `reject` leaves alone (generated files, lockfiles, ...).
+# The server reads it too, so the rules live in mcp_history_format.
+IgnoreMatcher = history_format.IgnoreMatcher
+get_ignore_path = history_format.get_ignore_path
+load_ignore_matcher = history_format.load_ignore_matcher
+
+
+def add_ignore_pattern(workspace_root: Path, pattern: str) -> bool:
+    """
+    Appends `pattern` as a line of the ignore file, creating it if needed.
+    Returns False, leaving the file as it is, if it already has that line.
+    """
+    ignore_path = get_ignore_path(workspace_root)
+    try:
+        text = ignore_path.read_text(encoding="utf-8")
+    except FileNotFoundError:
+        text = ""
+    if pattern in text.splitlines():
+        return False
+    if text and not text.endswith("\n"):
+        text += "\n"
+    ignore_path.parent.mkdir(parents=True, exist_ok=True)
+    ignore_path.write_text(text + pattern + "\n", encoding="utf-8")
+    return True
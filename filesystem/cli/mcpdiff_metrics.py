@@ -0,0 +1,119 @@
+# mcpdiff_metrics.py
+
+import contextlib
+import threading
+import time
+from typing import Any, Dict, Iterator, List
+
+# --- Metrics ---
+# Counts of the operations of a process, in the module-level METRICS. An mcpdiff
+# run hands what it counted to the workspace's daemon when it exits; the daemon
+# adds it to its own METRICS and, started with --metrics PORT, serves the sums
+# at http://127.0.0.1:PORT/metrics in the Prometheus text format.
+METRIC_PREFIX = "mcpdiff_"
+COUNTERS = {
+    "accepts_total": "Edits accepted by accept.",
+    "rejects_total": "Edits rejected by reject.",
+    "reapply_successes_total": "Files re-applied from their history.",
+    "reapply_failures_total": "Files whose re-apply failed.",
+    "lock_contentions_total": "Lock acquisitions that had to wait for a holder.",
+}
+LOG_WRITE_HISTOGRAM = "log_write_duration_seconds"
+# Upper bounds of the log write duration buckets, in seconds; +Inf follows
+LOG_WRITE_BUCKETS = (0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5)
+
+
+class Metrics:
+    """Counters and a histogram of log write durations, shared between threads."""
+
+    def __init__(self):
+        self._lock = threading.Lock()
+        self.counters: Dict[str, int] = dict.fromkeys(COUNTERS, 0)
+        # Observations per bucket, not cumulative; the last one is +Inf
+        self.log_write_buckets: List[int] = [0] * (len(LOG_WRITE_BUCKETS) + 1)
+        self.log_write_sum = 0.0
+
+    def increment(self, name: str, amount: int = 1) -> None:
+        if name not in self.counters:
+            raise KeyError(f"Unknown counter: {name}")
+        with self._lock:
+            self.counters[name] += amount
+
+    def observe_log_write(self, seconds: float) -> None:
+        bucket = next(
+            (i for i, bound in enumerate(LOG_WRITE_BUCKETS) if seconds <= bound),
+            len(LOG_WRITE_BUCKETS),
+        )
+        with self._lock:
+            self.log_write_buckets[bucket] += 1
+            self.log_write_sum += seconds
+
+    @contextlib.contextmanager
+    def time_log_write(self) -> Iterator[None]:
+        """Observes how long the block takes, also if it raises."""
+        start = time.perf_counter()
+        try:
+            yield
+        finally:
+            self.observe_log_write(time.perf_counter() - start)
+
+    def is_empty(self) -> bool:
+        return not any(self.counters.values()) and not any(self.log_write_buckets)
+
+    def to_dict(self) -> Dict[str, Any]:
+        with self._lock:
+            return {
+                "counters": dict(self.counters),
+                LOG_WRITE_HISTOGRAM: {
+                    "buckets": list(self.log_write_buckets),
+                    "sum": self.log_write_sum,
+                },
+            }
+
+    def merge(self, data: Dict[str, Any]) -> None:
+        """Adds the counts of another process's to_dict(). ValueError if malformed."""
+        try:
+            counters = {
+                name: int(value) for name, value in data.get("counters", {}).items()
+            }
+            histogram = data.get(LOG_WRITE_HISTOGRAM, {})
+            buckets = [int(count) for count in histogram.get("buckets", [])]
+            total = float(histogram.get("sum", 0.0))
+        except (AttributeError, TypeError, ValueError) as e:
+            raise ValueError(f"malformed metrics: {e}") from e
+        unknown = set(counters) - set(self.counters)
+        if unknown:
+            raise ValueError(f"unknown counters: {sorted(unknown)}")
+        if buckets and len(buckets) != len(self.log_write_buckets):
+            raise ValueError(f"expected {len(self.log_write_buckets)} buckets")
+        with self._lock:
+            for name, value in counters.items():
+                self.counters[name] += value
+            for i, count in enumerate(buckets):
+                self.log_write_buckets[i] += count
+            self.log_write_sum += total
+
+    def render(self) -> str:
+        """The metrics in the Prometheus text exposition format (version 0.0.4)."""
+        data = self.to_dict()
+        lines = []
+        for name, value in data["counters"].items():
+            metric = METRIC_PREFIX + name
+            lines.append(f"# HELP {metric} {COUNTERS[name]}")
+            lines.append(f"# TYPE {metric} counter")
+            lines.append(f"{metric} {value}")
+        metric = METRIC_PREFIX + LOG_WRITE_HISTOGRAM
+        histogram = data[LOG_WRITE_HISTOGRAM]
+        lines.append(f"# HELP {metric} Time taken to write a log file.")
+        lines.append(f"# TYPE {metric} histogram")
+        cumulative = 0
+        bounds = [str(bound) for bound in LOG_WRITE_BUCKETS] + ["+Inf"]
+        for bound, count in zip(bounds, histogram["buckets"]):
+            cumulative += count
+            lines.append(f'{metric}_bucket{{le="{bound}"}} {cumulative}')
+        lines.append(f"{metric}_sum {histogram['sum']}")
+        lines.append(f"{metric}_count {cumulative}")
+        return "\n".join(lines) + "\n"
+
+
+METRICS = Metrics()
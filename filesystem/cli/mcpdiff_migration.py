@@ -0,0 +1,263 @@
+# mcpdiff_migration.py
+
+import logging
+from typing import Callable, Dict, Any, List, Optional
+
+log = logging.getLogger("mcpdiff")
+
+# --- Schema Versioning ---
+# Entries written before versioning was introduced carry no "schema_version"
+# field and are treated as version 0.
+SCHEMA_VERSION_KEY = "schema_version"
+
+# Fields every current entry is expected to carry (value is the default used
+# when upgrading an older entry that lacks the field).
+_V1_DEFAULTS: Dict[str, Any] = {
+    "source_path": None,
+    "tool_name": None,
+    "status": "pending",
+    "diff_file": None,
+    "checkpoint_file": None,
+    "hash_before": None,
+    "hash_after": None,
+}
+
+
+# Added in v2: the edit this one depends on (see `parent_edit_id` in DIFFSPEC.md)
+_V2_DEFAULTS: Dict[str, Any] = {
+    "parent_edit_id": None,
+}
+
+
+# Added in v3: structural fingerprint of the workspace at edit time
+# (see `workspace_root_hash` in DIFFSPEC.md)
+_V3_DEFAULTS: Dict[str, Any] = {
+    "workspace_root_hash": None,
+}
+
+
+# Added in v4: the shell session the server ran in (see `session_id` in DIFFSPEC.md)
+_V4_DEFAULTS: Dict[str, Any] = {
+    "session_id": None,
+}
+
+
+# Added in v5: the conversation a forked conversation branched off, and the edit
+# it branched at (see `parent_conversation_id` in DIFFSPEC.md)
+_V5_DEFAULTS: Dict[str, Any] = {
+    "parent_conversation_id": None,
+    "forked_at": None,
+}
+
+
+# Added in v6: the bytes a partial edit replaced (see `byte_range_start` in
+# DIFFSPEC.md)
+_V6_DEFAULTS: Dict[str, Any] = {
+    "byte_range_start": None,
+    "byte_range_end": None,
+}
+
+
+# Added in v7: the size of the file before and after the edit (see
+# `byte_size_before` in DIFFSPEC.md)
+_V7_DEFAULTS: Dict[str, Any] = {
+    "byte_size_before": None,
+    "byte_size_after": None,
+}
+
+
+# Added in v8: the merge conflict blocks left in the file (see `conflict_markers`
+# in DIFFSPEC.md)
+_V8_DEFAULTS: Dict[str, Any] = {
+    "conflict_markers": None,
+}
+
+
+# Added in v9: `review_notes`, set by `mcpdiff annotate`. It is left out rather
+# than null when an edit has no notes, so older entries need no default.
+
+
+# Added in v10: `file_encoding`, how diff_file is stored: "text", "base64" or
+# "bsdiff". It was asked for as `encoding` with "utf8" and "base64", and renamed
+# because it describes the diff file, not the edited file, and a "text" diff
+# (the bytes an append added, say) need not be UTF-8. Every older entry has a
+# text diff.
+_V10_DEFAULTS: Dict[str, Any] = {
+    "file_encoding": "text",
+}
+
+
+# Added in v11: whether the file was listed in .mcp/ignore when the edit was
+# logged, so it has no checkpoint and an empty diff (see `ignored` in DIFFSPEC.md)
+_V11_DEFAULTS: Dict[str, Any] = {
+    "ignored": False,
+}
+
+
+# --- Migrations ---
+# Each migration takes an entry at version N and returns it at version N + 1.
+# Migrations must be pure with respect to unrelated fields: unknown keys are
+# carried over untouched so newer tools can round-trip older logs.
+
+
+def _migrate_v0_to_v1(entry: Dict[str, Any]) -> Dict[str, Any]:
+    """Fill in optional fields that early server versions omitted."""
+    for key, default in _V1_DEFAULTS.items():
+        entry.setdefault(key, default)
+    if isinstance(entry.get("status"), str):
+        entry["status"] = entry["status"].lower()
+    if isinstance(entry.get("operation"), str):
+        entry["operation"] = entry["operation"].lower()
+    return entry
+
+
+def _migrate_v1_to_v2(entry: Dict[str, Any]) -> Dict[str, Any]:
+    """Entries before v2 have no parent edit."""
+    for key, default in _V2_DEFAULTS.items():
+        entry.setdefault(key, default)
+    return entry
+
+
+def _migrate_v2_to_v3(entry: Dict[str, Any]) -> Dict[str, Any]:
+    """Entries before v3 have no record of the workspace structure."""
+    for key, default in _V3_DEFAULTS.items():
+        entry.setdefault(key, default)
+    return entry
+
+
+def _migrate_v3_to_v4(entry: Dict[str, Any]) -> Dict[str, Any]:
+    """Entries before v4 belong to no session."""
+    for key, default in _V4_DEFAULTS.items():
+        entry.setdefault(key, default)
+    return entry
+
+
+def _migrate_v4_to_v5(entry: Dict[str, Any]) -> Dict[str, Any]:
+    """Entries before v5 belong to conversations that were not forked."""
+    for key, default in _V5_DEFAULTS.items():
+        entry.setdefault(key, default)
+    return entry
+
+
+def _migrate_v5_to_v6(entry: Dict[str, Any]) -> Dict[str, Any]:
+    """Entries before v6 are no partial edits."""
+    for key, default in _V6_DEFAULTS.items():
+        entry.setdefault(key, default)
+    return entry
+
+
+def _migrate_v6_to_v7(entry: Dict[str, Any]) -> Dict[str, Any]:
+    """Entries before v7 did not record file sizes."""
+    for key, default in _V7_DEFAULTS.items():
+        entry.setdefault(key, default)
+    return entry
+
+
+def _migrate_v7_to_v8(entry: Dict[str, Any]) -> Dict[str, Any]:
+    """Entries before v8 did not look for conflict markers."""
+    for key, default in _V8_DEFAULTS.items():
+        entry.setdefault(key, default)
+    return entry
+
+
+def _migrate_v8_to_v9(entry: Dict[str, Any]) -> Dict[str, Any]:
+    """Entries before v9 have no review notes, and go without the field."""
+    return entry
+
+
+def _migrate_v9_to_v10(entry: Dict[str, Any]) -> Dict[str, Any]:
+    """Entries before v10 have text diffs."""
+    for key, default in _V10_DEFAULTS.items():
+        entry.setdefault(key, default)
+    return entry
+
+
+def _migrate_v10_to_v11(entry: Dict[str, Any]) -> Dict[str, Any]:
+    """Entries before v11 did not record whether their file was ignored."""
+    for key, default in _V11_DEFAULTS.items():
+        entry.setdefault(key, default)
+    return entry
+
+
+MIGRATIONS: List[Callable[[Dict[str, Any]], Dict[str, Any]]] = [
+    _migrate_v0_to_v1,
+    _migrate_v1_to_v2,
+    _migrate_v2_to_v3,
+    _migrate_v3_to_v4,
+    _migrate_v4_to_v5,
+    _migrate_v5_to_v6,
+    _migrate_v6_to_v7,
+    _migrate_v7_to_v8,
+    _migrate_v8_to_v9,
+    _migrate_v9_to_v10,
+    _migrate_v10_to_v11,
+]
+
+CURRENT_SCHEMA_VERSION = len(MIGRATIONS)
+
+
+class SchemaVersionError(ValueError):
+    """An entry's schema_version is not a version at all."""
+
+
+class Migrator:
+    """Upgrades raw log entries to the current schema version."""
+
+    def __init__(
+        self,
+        migrations: Optional[List[Callable[[Dict[str, Any]], Dict[str, Any]]]] = None,
+    ):
+        self.migrations = migrations if migrations is not None else MIGRATIONS
+
+    @property
+    def current_version(self) -> int:
+        return len(self.migrations)
+
+    def get_version(self, entry: Dict[str, Any]) -> int:
+        """
+        Returns the schema version of an entry (0 if unversioned or invalid).
+        A boolean raises SchemaVersionError: Python would take true for version
+        1, so a corrupted entry would be migrated as if it were one.
+        """
+        version = entry.get(SCHEMA_VERSION_KEY, 0)
+        if isinstance(version, bool):
+            raise SchemaVersionError(
+                f"Invalid schema_version {version!r} in entry {entry.get('edit_id', 'unknown')}."
+            )
+        if not isinstance(version, int) or version < 0:
+            log.warning(
+                f"Invalid schema_version '{version}' in entry {entry.get('edit_id', 'unknown')}. Treating as 0."
+            )
+            return 0
+        return version
+
+    def needs_upgrade(self, entry: Dict[str, Any]) -> bool:
+        return self.get_version(entry) < self.current_version
+
+    def upgrade(self, entry: Dict[str, Any]) -> Dict[str, Any]:
+        """
+        Applies all pending migrations to a single entry in order.
+        Entries from a newer schema than this tool knows about are returned unchanged.
+        """
+        version = self.get_version(entry)
+        if version > self.current_version:
+            log.warning(
+                f"Entry {entry.get('edit_id', 'unknown')} has schema_version {version}, "
+                f"newer than supported version {self.current_version}. Leaving as-is."
+            )
+            return entry
+        if version == self.current_version:
+            return entry
+
+        upgraded = dict(entry)
+        for target_version in range(version, self.current_version):
+            upgraded = self.migrations[target_version](upgraded)
+            upgraded[SCHEMA_VERSION_KEY] = target_version + 1
+        log.debug(
+            f"Upgraded entry {upgraded.get('edit_id', 'unknown')} from schema v{version} to v{self.current_version}"
+        )
+        return upgraded
+
+
+# Shared instance used by log readers
+default_migrator = Migrator()
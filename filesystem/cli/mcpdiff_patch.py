@@ -0,0 +1,751 @@
+# mcpdiff_patch.py
+
+import base64
+import bz2
+import difflib
+import hashlib
+import re
+from dataclasses import dataclass, field
+from pathlib import Path
+from typing import Any, Dict, List, Optional, Tuple
+
+from mcpdiff_utils import log
+
+# --- Unified Diff Application ---
+# A pure-Python replacement for `git apply`/`patch`, so that mcpdiff works
+# where neither binary is available (notably Windows). Lines are compared
+# without their line endings, and lines the patch adds are written with the
+# ending style the file already uses.
+
+HUNK_HEADER_PATTERN = re.compile(r"^@@ -(\d+)(?:,(\d+))? \+(\d+)(?:,(\d+))? @@")
+
+# (tag, text, has_eol) where tag is " ", "-" or "+" and text has no line ending
+HunkLine = Tuple[str, str, bool]
+
+
+class PatchError(Exception):
+    """
+    Raised when a diff cannot be parsed or does not apply. `hunk` is the 1-based
+    number of the hunk that did not apply, if any.
+    """
+
+    def __init__(self, message: str, hunk: Optional[int] = None):
+        super().__init__(message)
+        self.hunk = hunk
+
+
+class Hunk:
+    """One `@@` section of a unified diff."""
+
+    def __init__(self, old_start: int, old_len: int, new_start: int, new_len: int):
+        self.old_start = old_start
+        self.old_len = old_len
+        self.new_start = new_start
+        self.new_len = new_len
+        self.lines: List[HunkLine] = []
+
+    def reversed(self) -> "Hunk":
+        swap = {"-": "+", "+": "-", " ": " "}
+        hunk = Hunk(self.new_start, self.new_len, self.old_start, self.old_len)
+        hunk.lines = [(swap[tag], text, eol) for tag, text, eol in self.lines]
+        return hunk
+
+    def old_lines(self) -> List[HunkLine]:
+        return [line for line in self.lines if line[0] in (" ", "-")]
+
+
+def parse_unified_diff(diff_content: str) -> List[Hunk]:
+    """Parses the hunks of a single-file unified diff, ignoring its headers."""
+    hunks: List[Hunk] = []
+    current: Optional[Hunk] = None
+    old_left = new_left = 0
+    for raw_line in diff_content.split("\n"):
+        line = raw_line[:-1] if raw_line.endswith("\r") else raw_line
+        match = HUNK_HEADER_PATTERN.match(line)
+        if match:
+            old_start, old_len, new_start, new_len = match.groups()
+            current = Hunk(
+                int(old_start),
+                int(old_len) if old_len is not None else 1,
+                int(new_start),
+                int(new_len) if new_len is not None else 1,
+            )
+            hunks.append(current)
+            old_left, new_left = current.old_len, current.new_len
+            continue
+        if current is None:
+            continue  # File headers before the first hunk
+        if line.startswith("\\"):
+            # "\ No newline at end of file" applies to the line before it
+            if current.lines:
+                tag, text, _ = current.lines[-1]
+                current.lines[-1] = (tag, text, False)
+            continue
+        if old_left <= 0 and new_left <= 0:
+            continue  # Trailing text after the hunk
+        tag, text = (line[0], line[1:]) if line else (" ", "")
+        if tag not in (" ", "-", "+"):
+            raise PatchError(f"Malformed hunk line: {line[:80]!r}")
+        if tag in (" ", "-"):
+            old_left -= 1
+        if tag in (" ", "+"):
+            new_left -= 1
+        current.lines.append((tag, text, True))
+    if not hunks and diff_content.strip():
+        raise PatchError("No hunks found in diff.")
+    return hunks
+
+
+def format_hunks(hunks: List[Hunk]) -> str:
+    """Unified diff text of hunks, without file headers."""
+    parts = []
+    for hunk in hunks:
+        parts.append(
+            f"@@ -{hunk.old_start},{hunk.old_len} +{hunk.new_start},{hunk.new_len} @@\n"
+        )
+        for tag, text, has_eol in hunk.lines:
+            parts.append(f"{tag}{text}\n")
+            if not has_eol:
+                parts.append("\\ No newline at end of file\n")
+    return "".join(parts)
+
+
+def make_unified_diff(before: str, after: str, path: str) -> str:
+    """
+    Unified diff from `before` to `after` with a/ and b/ headers, as the server
+    writes them, marking a last line without an ending.
+    """
+    parts = []
+    for line in difflib.unified_diff(
+        split_lines(before), split_lines(after), f"a/{path}", f"b/{path}"
+    ):
+        if not line.endswith("\n"):
+            line += "\n\\ No newline at end of file\n"
+        parts.append(line)
+    return "".join(parts)
+
+
+def detect_line_ending(content: str) -> str:
+    """The line ending style of `content`: CRLF if its first line ends with it."""
+    first_eol = content.find("\n")
+    if first_eol > 0 and content[first_eol - 1] == "\r":
+        return "\r\n"
+    return "\n"
+
+
+def split_lines(content: str) -> List[str]:
+    """Splits on "\n" only, keeping endings (str.splitlines also splits on "\r")."""
+    parts = content.split("\n")
+    lines = [part + "\n" for part in parts[:-1]]
+    if parts[-1]:
+        lines.append(parts[-1])
+    return lines
+
+
+def _strip_eol(line: str) -> str:
+    if line.endswith("\r\n"):
+        return line[:-2]
+    if line.endswith("\n"):
+        return line[:-1]
+    return line
+
+
+def _squeeze_whitespace(text: str) -> str:
+    return " ".join(text.split())
+
+
+def find_hunk(
+    file_lines: List[str],
+    old_texts: List[str],
+    expected: int,
+    ignore_whitespace: bool = False,
+) -> Optional[int]:
+    """Index where `old_texts` matches, searching outward from `expected`."""
+    last_start = len(file_lines) - len(old_texts)
+    if last_start < 0:
+        return None
+    normalize = _squeeze_whitespace if ignore_whitespace else (lambda text: text)
+    old_texts = [normalize(text) for text in old_texts]
+    expected = min(max(expected, 0), last_start)
+    for distance in range(0, max(expected, last_start - expected) + 1):
+        for start in (expected - distance, expected + distance):
+            if 0 <= start <= last_start and all(
+                normalize(_strip_eol(file_lines[start + i])) == text
+                for i, text in enumerate(old_texts)
+            ):
+                return start
+            if distance == 0:
+                break
+    return None
+
+
+def _context_run(lines: List[HunkLine]) -> int:
+    """Number of context lines at the start of `lines`."""
+    count = 0
+    for tag, _, _ in lines:
+        if tag != " ":
+            break
+        count += 1
+    return count
+
+
+def _locate_hunk(
+    file_lines: List[str], hunk: Hunk, expected: int, fuzz: int, ignore_whitespace: bool
+) -> Optional[Tuple[int, List[HunkLine], int, int, bool]]:
+    """
+    Finds where `hunk` applies, first exactly, then ignoring whitespace, then
+    with 1..fuzz context lines dropped at either end. Returns (start, the hunk
+    lines to apply there, offset from `expected`, fuzz used, whether
+    whitespace was ignored).
+    """
+    leading = _context_run(hunk.lines)
+    trailing = _context_run(hunk.lines[::-1])
+    for used_fuzz in range(fuzz + 1):
+        if used_fuzz > max(leading, trailing):
+            break  # No context left to drop
+        head, tail = min(used_fuzz, leading), min(used_fuzz, trailing)
+        lines = hunk.lines[head : len(hunk.lines) - tail]
+        old_texts = [text for tag, text, _ in lines if tag in (" ", "-")]
+        for loose in (False, True) if ignore_whitespace else (False,):
+            start = find_hunk(file_lines, old_texts, expected + head, loose)
+            if start is not None:
+                return start, lines, start - head - expected, used_fuzz, loose
+    return None
+
+
+@dataclass
+class ConflictRegion:
+    """
+    A hunk of a diff whose old lines are not in the file, so it does not apply.
+    Lines are 1-based lines of the file: start_line and end_line (inclusive)
+    are where the hunk expected its old lines, allowing for the lines the
+    hunks before it add or remove, and first_difference the first of them that
+    differs from the diff (past the end of the file if the file is too short).
+    expected_lines are the hunk's old lines and actual_lines the file's lines
+    in that range, without endings.
+    """
+
+    hunk: int
+    start_line: int
+    end_line: int
+    first_difference: int
+    expected_lines: List[str] = field(default_factory=list)
+    actual_lines: List[str] = field(default_factory=list)
+
+    def describe(self) -> str:
+        """One-line summary, e.g. "hunk #1 at lines 40-44: line 42 is `x`, ..."."""
+        lines = (
+            f"line {self.start_line}"
+            if self.end_line <= self.start_line
+            else f"lines {self.start_line}-{self.end_line}"
+        )
+        index = self.first_difference - self.start_line
+        expected = actual = None
+        if index < len(self.expected_lines):
+            expected = self.expected_lines[index]
+        if index < len(self.actual_lines):
+            actual = self.actual_lines[index]
+        if actual is None:
+            found = f"the file ends before line {self.first_difference}"
+        else:
+            found = f"line {self.first_difference} is `{actual.strip()[:60]}`"
+        if expected is not None:
+            found += f", the diff expects `{expected.strip()[:60]}`"
+        return f"hunk #{self.hunk} at {lines}: {found}"
+
+    def to_dict(self) -> Dict[str, Any]:
+        return {
+            "hunk": self.hunk,
+            "start_line": self.start_line,
+            "end_line": self.end_line,
+            "first_difference": self.first_difference,
+            "expected_lines": self.expected_lines,
+            "actual_lines": self.actual_lines,
+        }
+
+
+def detect_conflicts(
+    content: str,
+    diff_content: str,
+    reverse: bool = False,
+    fuzz: int = 0,
+    ignore_whitespace: bool = False,
+) -> List[ConflictRegion]:
+    """
+    The hunks of a unified diff that would not apply to `content`, located as
+    apply_unified_diff_reporting() locates them (with the same fuzz and
+    whitespace settings), without applying anything. Hunks that apply shift
+    the expected position of the later ones as they would when applied; a hunk
+    that does not is left out of the content. Returns [] if the diff applies.
+    """
+    hunks = parse_unified_diff(diff_content)
+    if reverse:
+        hunks = [hunk.reversed() for hunk in hunks]
+    eol_style = detect_line_ending(content)
+    file_lines = split_lines(content)
+    normalize = _squeeze_whitespace if ignore_whitespace else (lambda text: text)
+    offset = 0
+    conflicts: List[ConflictRegion] = []
+    for number, hunk in enumerate(hunks, 1):
+        expected = hunk.old_start if hunk.old_len == 0 else hunk.old_start - 1
+        expected += offset
+        located = _locate_hunk(file_lines, hunk, expected, fuzz, ignore_whitespace)
+        if located is not None:
+            start, hunk_lines = located[:2]
+            offset += _apply_hunk(file_lines, start, hunk_lines, eol_style)
+            continue
+        old_texts = [text for _, text, _ in hunk.old_lines()]
+        start = min(max(expected, 0), len(file_lines))
+        actual = file_lines[start : start + len(old_texts)]
+        actual = [_strip_eol(line) for line in actual]
+        first = next(
+            (
+                i
+                for i, text in enumerate(old_texts)
+                if i >= len(actual) or normalize(actual[i]) != normalize(text)
+            ),
+            len(old_texts),
+        )
+        # In lines of `content`, before the hunks applied so far
+        start -= offset
+        conflicts.append(
+            ConflictRegion(
+                hunk=number,
+                start_line=start + 1,
+                end_line=start + max(len(old_texts), 1),
+                first_difference=start + 1 + first,
+                expected_lines=old_texts,
+                actual_lines=actual,
+            )
+        )
+    return conflicts
+
+
+def _apply_hunk(
+    file_lines: List[str], start: int, hunk_lines: List[HunkLine], eol_style: str
+) -> int:
+    """
+    Applies located hunk lines to `file_lines` at `start`, in place. Returns
+    how many lines longer the file got.
+    """
+    new_lines: List[str] = []
+    file_index = start
+    for tag, text, has_eol in hunk_lines:
+        if tag == " ":
+            # Keep the file's own line, including its line ending
+            new_lines.append(file_lines[file_index])
+            file_index += 1
+        elif tag == "-":
+            file_index += 1
+        else:
+            new_lines.append(text + (eol_style if has_eol else ""))
+    file_lines[start:file_index] = new_lines
+    return len(new_lines) - (file_index - start)
+
+
+def apply_unified_diff(content: str, diff_content: str, reverse: bool = False) -> str:
+    """
+    Applies a unified diff to `content` and returns the result. Hunks may be
+    offset from their recorded position. Raises PatchError if a hunk's context
+    cannot be found.
+    """
+    return apply_unified_diff_reporting(content, diff_content, reverse)[0]
+
+
+def apply_unified_diff_reporting(
+    content: str,
+    diff_content: str,
+    reverse: bool = False,
+    fuzz: int = 0,
+    ignore_whitespace: bool = False,
+) -> Tuple[str, List[str]]:
+    """
+    apply_unified_diff() with context matching as loose as `fuzz` and
+    `ignore_whitespace` allow: fuzz N lets a hunk apply with up to N of its
+    leading and trailing context lines ignored, as with `patch -F N`. Returns
+    the result and a message for each hunk that only applied loosely, such as
+    "Hunk #2 applied with fuzz 1 at offset +3".
+    """
+    hunks = parse_unified_diff(diff_content)
+    if reverse:
+        hunks = [hunk.reversed() for hunk in hunks]
+    eol_style = detect_line_ending(content)
+    file_lines = split_lines(content)
+    offset = 0
+    loose_hunks: List[str] = []
+    for number, hunk in enumerate(hunks, 1):
+        # With no old lines, old_start is the line *after* which to insert
+        expected = hunk.old_start if hunk.old_len == 0 else hunk.old_start - 1
+        expected += offset
+        located = _locate_hunk(file_lines, hunk, expected, fuzz, ignore_whitespace)
+        if located is None:
+            raise PatchError(
+                f"Hunk #{number} (@@ -{hunk.old_start},{hunk.old_len} @@) does not apply.",
+                hunk=number,
+            )
+        start, hunk_lines, hunk_offset, used_fuzz, loose = located
+        if used_fuzz or loose:
+            how = f"with fuzz {used_fuzz}" if used_fuzz else "ignoring whitespace"
+            if used_fuzz and loose:
+                how += ", ignoring whitespace,"
+            message = f"Hunk #{number} applied {how} at offset {hunk_offset:+d}"
+            log.info(message)
+            loose_hunks.append(message)
+        elif hunk_offset:
+            log.debug(f"Hunk #{number} applied with offset {hunk_offset}.")
+        offset += _apply_hunk(file_lines, start, hunk_lines, eol_style)
+    # Only the last line may lack an ending, e.g. if a hunk added lines after
+    # a final line that had none
+    for i in range(len(file_lines) - 1):
+        if not file_lines[i].endswith("\n"):
+            file_lines[i] += eol_style
+    return "".join(file_lines), loose_hunks
+
+
+# Unified diffs of files that are not valid UTF-8 carry their undecodable bytes,
+# so they are stored base64-encoded; such entries have `file_encoding` set to
+# BASE64_ENCODING.
+BASE64_ENCODING = "base64"
+
+
+def read_diff_file(diff_path: Path, file_encoding: Optional[str] = None) -> str:
+    """
+    The unified diff stored at `diff_path`, decoded from base64 if its entry's
+    `file_encoding` is BASE64_ENCODING. Bytes that are not valid UTF-8 are kept
+    as surrogate escapes. Raises PatchError if the base64 is malformed.
+    """
+    data = diff_path.read_bytes()
+    if file_encoding == BASE64_ENCODING:
+        try:
+            data = base64.b64decode(data, validate=True)
+        except ValueError as e:
+            raise PatchError(f"Malformed base64 diff {diff_path.name}: {e}")
+    return data.decode("utf-8", errors="surrogateescape")
+
+
+def encode_diff(diff_content: str, file_encoding: Optional[str] = None) -> bytes:
+    """The bytes to store for a unified diff, as read_diff_file() reads them."""
+    data = diff_content.encode("utf-8", errors="surrogateescape")
+    if file_encoding == BASE64_ENCODING:
+        return base64.b64encode(data)
+    return data
+
+
+def apply_diff_to_file(
+    target_path: Path,
+    diff_path: Path,
+    reverse: bool = False,
+    file_encoding: Optional[str] = None,
+    fuzz: int = 0,
+    ignore_whitespace: bool = False,
+) -> List[str]:
+    """
+    Applies the diff stored at `diff_path` to `target_path` in place (see
+    read_diff_file() for `file_encoding`). A missing target is treated as empty.
+    Bytes that are not valid UTF-8 are preserved. Returns the messages of hunks
+    that applied only with `fuzz` or `ignore_whitespace` (see
+    apply_unified_diff_reporting()). Raises PatchError if the diff does not
+    apply; the target is left unchanged.
+    """
+    diff_content = read_diff_file(diff_path, file_encoding)
+    content = ""
+    if target_path.exists():
+        content = target_path.read_bytes().decode("utf-8", errors="surrogateescape")
+    patched, loose_hunks = apply_unified_diff_reporting(
+        content, diff_content, reverse, fuzz, ignore_whitespace
+    )
+    target_path.parent.mkdir(parents=True, exist_ok=True)
+    target_path.write_bytes(patched.encode("utf-8", errors="surrogateescape"))
+    return loose_hunks
+
+
+# --- Binary Patches (bsdiff) ---
+# Edits of binary files are stored as BSDIFF40 patches (the format of Colin
+# Percival's bsdiff and of the bsdiff4 package): a header with the compressed
+# lengths of the control and diff blocks and the new size, then the three
+# bz2-compressed blocks. A control triple (add, copy, seek) adds `add` diff bytes
+# to old bytes, copies `copy` extra bytes, then moves the old position by `seek`.
+# Entries with such a diff have `file_encoding` set to BSDIFF_ENCODING. The
+# server makes them with src/mcp_bsdiff.py, which the tests also use.
+
+BSDIFF_ENCODING = "bsdiff"
+BSDIFF_MAGIC = b"BSDIFF40"
+_BSDIFF_HEADER_SIZE = 32
+
+
+def is_bsdiff_patch(data: bytes) -> bool:
+    return data[: len(BSDIFF_MAGIC)] == BSDIFF_MAGIC
+
+
+def _offtin(data: bytes) -> int:
+    value = int.from_bytes(data[:7] + bytes([data[7] & 0x7F]), "little")
+    return -value if data[7] & 0x80 else value
+
+
+def apply_bsdiff_patch(old: bytes, patch: bytes) -> bytes:
+    """Applies a BSDIFF40 patch to `old`. Raises PatchError if it is malformed."""
+    if len(patch) < _BSDIFF_HEADER_SIZE or not is_bsdiff_patch(patch):
+        raise PatchError("Not a bsdiff patch.")
+    control_size = _offtin(patch[8:16])
+    diff_size = _offtin(patch[16:24])
+    new_size = _offtin(patch[24:32])
+    if min(control_size, diff_size, new_size) < 0:
+        raise PatchError("Corrupt bsdiff header.")
+    diff_start = _BSDIFF_HEADER_SIZE + control_size
+    extra_start = diff_start + diff_size
+    try:
+        control = bz2.decompress(patch[_BSDIFF_HEADER_SIZE:diff_start])
+        diff = bz2.decompress(patch[diff_start:extra_start])
+        extra = bz2.decompress(patch[extra_start:])
+    except (OSError, ValueError) as e:
+        raise PatchError(f"Corrupt bsdiff patch: {e}")
+
+    new = bytearray()
+    old_pos = diff_pos = extra_pos = 0
+    for offset in range(0, len(control) - 23, 24):
+        add, copy, seek = (
+            _offtin(control[offset + j : offset + j + 8]) for j in (0, 8, 16)
+        )
+        if (
+            add < 0
+            or copy < 0
+            or len(new) + add + copy > new_size
+            or diff_pos + add > len(diff)
+            or extra_pos + copy > len(extra)
+        ):
+            raise PatchError("Corrupt bsdiff patch: control data out of range.")
+        new.extend(
+            (diff[diff_pos + i] + (old[old_pos + i] if old_pos + i < len(old) else 0))
+            & 0xFF
+            for i in range(add)
+        )
+        diff_pos += add
+        old_pos += add
+        new.extend(extra[extra_pos : extra_pos + copy])
+        extra_pos += copy
+        old_pos += seek
+    if len(new) != new_size:
+        raise PatchError("Corrupt bsdiff patch: output is truncated.")
+    return bytes(new)
+
+
+def apply_bsdiff_to_file(
+    target_path: Path, patch_path: Path, expected_hash: Optional[str] = None
+) -> None:
+    """
+    Applies the bsdiff patch stored at `patch_path` to `target_path` in place; a
+    missing target is treated as empty. A binary patch has no context to check,
+    so if `expected_hash` (the entry's hash_before) is given, the target must
+    match it. Raises PatchError otherwise, leaving the target unchanged.
+    """
+    old = target_path.read_bytes() if target_path.exists() else b""
+    if expected_hash and hashlib.sha256(old).hexdigest() != expected_hash:
+        raise PatchError(
+            "Binary file differs from the content the patch was made for.", hunk=1
+        )
+    new = apply_bsdiff_patch(old, patch_path.read_bytes())
+    target_path.parent.mkdir(parents=True, exist_ok=True)
+    target_path.write_bytes(new)
+
+
+# --- Partial Edits ---
+# Edits of large files that change a single byte range store only the bytes
+# written in place of the range, without any header. Such entries have the
+# operation "partial", and the range of the content before the edit in
+# `byte_range_start` (inclusive) and `byte_range_end` (exclusive).
+
+
+def splice_bytes(data: bytes, start: int, end: int, replacement: bytes) -> bytes:
+    """`data` with its bytes start..end replaced. Raises PatchError if out of range."""
+    if not 0 <= start <= end <= len(data):
+        raise PatchError(
+            f"Byte range {start}-{end} is outside the file ({len(data)} bytes).",
+            hunk=1,
+        )
+    return data[:start] + replacement + data[end:]
+
+
+def apply_partial_to_file(
+    target_path: Path,
+    diff_path: Path,
+    start: int,
+    end: int,
+    expected_hash: Optional[str] = None,
+) -> None:
+    """
+    Splices the bytes stored at `diff_path` into `target_path` in place of its
+    bytes start..end. Offsets only fit the content the edit was made on, so if
+    `expected_hash` (the entry's hash_before) is given, the target must match
+    it. Raises PatchError otherwise, leaving the target unchanged.
+    """
+    old = target_path.read_bytes() if target_path.exists() else b""
+    if expected_hash and hashlib.sha256(old).hexdigest() != expected_hash:
+        raise PatchError(
+            "File differs from the content the partial edit was made for.", hunk=1
+        )
+    new = splice_bytes(old, start, end, diff_path.read_bytes())
+    target_path.parent.mkdir(parents=True, exist_ok=True)
+    target_path.write_bytes(new)
+
+
+# --- Merge Conflict Markers ---
+# Blocks a merge left unresolved in a file:
+#
+#   <<<<<<< ours
+#   ...
+#   ||||||| base      (diff3 style only)
+#   ...
+#   =======
+#   ...
+#   >>>>>>> theirs
+#
+# The server records the lines of the blocks a file has after an edit in the
+# entry's `conflict_markers`, for `diagnose`, `status` and `resolve-conflicts`.
+
+CONFLICT_MARKERS = ("<<<<<<<", "|||||||", "=======", ">>>>>>>")
+_CONFLICT_MARKER_PATTERN = re.compile(r"^(<{7}|\|{7}|={7}|>{7})(?:[ \t]|\r?\n?$)")
+
+
+def find_conflict_markers(lines: List[str]) -> List[Dict[str, int]]:
+    """
+    The conflict blocks among `lines` (with or without line endings), as
+    {"start_line", "end_line"}, 1-based and inclusive, from the `<<<<<<<` line
+    to the `>>>>>>>` one. Only blocks with all three markers count, so a lone
+    `=======` (a Markdown heading underline) is no conflict.
+    """
+    blocks: List[Dict[str, int]] = []
+    start: Optional[int] = None
+    separated = False
+    for number, line in enumerate(lines, 1):
+        match = _CONFLICT_MARKER_PATTERN.match(line)
+        if not match:
+            continue
+        marker = match.group(1)[0]
+        if marker == "<":
+            start, separated = number, False
+        elif marker == "=" and start is not None:
+            separated = True
+        elif marker == ">" and start is not None and separated:
+            blocks.append({"start_line": start, "end_line": number})
+            start = None
+    return blocks
+
+
+def resolve_conflict_markers(content: str, side: str) -> str:
+    """
+    `content` with each conflict block replaced by the lines of one side:
+    "ours", before the `|||||||` or `=======`, or "theirs", after the `=======`.
+    """
+    lines = split_lines(content)
+    blocks = find_conflict_markers(lines)
+    result: List[str] = []
+    position = 0
+    for block in blocks:
+        start, end = block["start_line"] - 1, block["end_line"] - 1
+        result.extend(lines[position:start])
+        kept: List[str] = []
+        section = "ours"
+        for line in lines[start + 1 : end]:
+            match = _CONFLICT_MARKER_PATTERN.match(line)
+            if match and match.group(1)[0] == "|":
+                section = "base"
+            elif match and match.group(1)[0] == "=" and section != "theirs":
+                section = "theirs"
+            elif section == side:
+                kept.append(line)
+        result.extend(kept)
+        position = end + 1
+    result.extend(lines[position:])
+    return "".join(result)
+
+
+# --- Display Filters ---
+
+WHITESPACE_OMITTED_NOTE = "(whitespace changes omitted)"
+
+
+def _omit_whitespace_in_run(run: List[str]) -> Tuple[List[str], int]:
+    """
+    Turns the removed/added line pairs of a run of changed lines that differ
+    only in indentation or trailing whitespace into context lines (with the
+    added text). Returns the new lines and the number of pairs turned.
+    """
+    removed = [line for line in run if line.startswith("-")]
+    added = [line for line in run if line.startswith("+")]
+    matcher = difflib.SequenceMatcher(
+        None,
+        [line[1:].strip() for line in removed],
+        [line[1:].strip() for line in added],
+        autojunk=False,
+    )
+    lines: List[str] = []
+    omitted = 0
+    for tag, i1, i2, j1, j2 in matcher.get_opcodes():
+        if tag == "equal":
+            lines.extend(" " + line[1:] for line in added[j1:j2])
+            omitted += i2 - i1
+        else:
+            lines.extend(removed[i1:i2] + added[j1:j2])
+    return lines, omitted
+
+
+def omit_whitespace_changes(diff_content: str) -> str:
+    """
+    Display form of a unified diff without changes that only touch indentation
+    or trailing whitespace: they are shown as context lines. Hunk headers keep
+    their line counts and are marked WHITESPACE_OMITTED_NOTE where lines were
+    omitted; a hunk with no other changes is reduced to its header. Text that is
+    not a unified diff is returned unchanged.
+    """
+    output: List[str] = []
+    header: Optional[str] = None
+    body: List[str] = []
+
+    def flush_hunk() -> None:
+        lines: List[str] = []
+        omitted = 0
+        run: List[str] = []
+        for line in body + [""]:
+            if line[:1] in ("-", "+"):
+                run.append(line)
+                continue
+            if run:
+                kept, count = _omit_whitespace_in_run(run)
+                lines.extend(kept)
+                omitted += count
+                run = []
+            lines.append(line)
+        lines.pop()  # The sentinel
+        if not omitted:
+            output.extend([header] + body)
+        elif any(line[:1] in ("-", "+") for line in lines):
+            output.extend([f"{header} {WHITESPACE_OMITTED_NOTE}"] + lines)
+        else:
+            output.append(f"{header} {WHITESPACE_OMITTED_NOTE}")
+
+    old_left = new_left = 0
+    for line in diff_content.splitlines():
+        match = HUNK_HEADER_PATTERN.match(line)
+        if match:
+            if header is not None:
+                flush_hunk()
+            header, body = line, []
+            old_left = int(match.group(2) or "1")
+            new_left = int(match.group(4) or "1")
+        elif header is not None and (
+            old_left > 0 or new_left > 0 or line.startswith("\\")
+        ):
+            body.append(line)
+            tag = line[:1] or " "
+            if tag in (" ", "-"):
+                old_left -= 1
+            if tag in (" ", "+"):
+                new_left -= 1
+        else:
+            if header is not None:
+                flush_hunk()
+                header = None
+            output.append(line)
+    if header is not None:
+        flush_hunk()
+    return "\n".join(output) + ("\n" if diff_content.endswith("\n") else "")
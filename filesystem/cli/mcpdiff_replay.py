@@ -0,0 +1,295 @@
+# mcpdiff_replay.py
+
+import hashlib
+from pathlib import Path
+from typing import Any, Dict, List, Optional
+
+import mcpdiff_history as history
+from mcpdiff_patch import (
+    BSDIFF_ENCODING,
+    PatchError,
+    find_hunk,
+    parse_unified_diff,
+    read_diff_file,
+    split_lines,
+)
+from mcpdiff_utils import MOVE_OPERATIONS, HistoryError, log
+
+# --- Line-Level Replay ---
+# Replays a file's history like reconstruct_file_from_history, but keeps the
+# file as a list of lines that each remember the entry that last added or
+# changed them. Lines that were already in the original checkpoint have no
+# entry and are reported as BASELINE.
+
+BASELINE = "baseline"
+
+
+class BlameLine:
+    """One line of a replayed file (with its line ending, if any) and its origin."""
+
+    def __init__(self, text: str, origin: Optional[Dict[str, Any]] = None):
+        self.text = text
+        self.origin = origin
+
+    @property
+    def edit_id(self) -> str:
+        if self.origin is None:
+            return BASELINE
+        return self.origin.get("edit_id", "unknown")
+
+    def __repr__(self) -> str:
+        return f"BlameLine({self.text!r}, {self.edit_id})"
+
+
+def lines_from_content(
+    content: str, origin: Optional[Dict[str, Any]] = None
+) -> List[BlameLine]:
+    """Splits file content into lines attributed to `origin` (baseline if None)."""
+    return [BlameLine(line, origin) for line in split_lines(content)]
+
+
+def render(lines: List[BlameLine]) -> str:
+    """The file content the lines represent."""
+    return "".join(line.text for line in lines)
+
+
+def apply_diff_with_blame(
+    lines: List[BlameLine],
+    diff_content: str,
+    origin: Optional[Dict[str, Any]],
+    reverse: bool = False,
+) -> List[BlameLine]:
+    """
+    Applies a unified diff to `lines` and returns the new lines. Context lines
+    keep their origin, added lines get `origin`. Hunks may be offset from their
+    recorded position, as in apply_unified_diff. Raises PatchError if a hunk's
+    context cannot be found.
+    """
+    hunks = parse_unified_diff(diff_content)
+    if reverse:
+        hunks = [hunk.reversed() for hunk in hunks]
+    result = list(lines)
+    file_texts = [line.text for line in result]
+    offset = 0
+    for number, hunk in enumerate(hunks, 1):
+        old_texts = [text for _, text, _ in hunk.old_lines()]
+        expected = hunk.old_start if hunk.old_len == 0 else hunk.old_start - 1
+        expected += offset
+        start = find_hunk(file_texts, old_texts, expected)
+        if start is None:
+            raise PatchError(
+                f"Hunk #{number} (@@ -{hunk.old_start},{hunk.old_len} @@) does not apply."
+            )
+
+        new_lines: List[BlameLine] = []
+        index = start
+        for tag, text, has_eol in hunk.lines:
+            if tag == " ":
+                new_lines.append(result[index])
+                index += 1
+            elif tag == "-":
+                index += 1
+            else:
+                new_lines.append(BlameLine(text + ("\n" if has_eol else ""), origin))
+        result[start:index] = new_lines
+        file_texts[start:index] = [line.text for line in new_lines]
+        offset += len(new_lines) - (index - start)
+
+    # A line that gained text after it (no newline at the end before) needs one
+    for i, line in enumerate(result[:-1]):
+        if not line.text.endswith("\n"):
+            result[i] = BlameLine(line.text + "\n", line.origin)
+    return result
+
+
+def append_with_blame(
+    lines: List[BlameLine], appended: str, origin: Optional[Dict[str, Any]]
+) -> List[BlameLine]:
+    """
+    Appends text to `lines`. If the last line had no line ending, the first
+    appended fragment completes it, so that line is attributed to `origin`.
+    """
+    result = list(lines)
+    new_lines = split_lines(appended)
+    if result and new_lines and not result[-1].text.endswith("\n"):
+        result[-1] = BlameLine(result[-1].text + new_lines.pop(0), origin)
+    result.extend(BlameLine(text, origin) for text in new_lines)
+    return result
+
+
+def splice_with_blame(
+    lines: List[BlameLine],
+    start: int,
+    end: int,
+    written: bytes,
+    origin: Optional[Dict[str, Any]],
+) -> List[BlameLine]:
+    """
+    Replaces the bytes start..end of the content with `written`, as a partial
+    edit does. The lines the range touches become the new bytes' lines, which
+    are attributed to `origin`; the others keep theirs. Raises PatchError if the
+    range is outside the content.
+    """
+    data = [line.text.encode("utf-8", "surrogateescape") for line in lines]
+    total = sum(len(chunk) for chunk in data)
+    if not 0 <= start <= end <= total:
+        raise PatchError(
+            f"Byte range {start}-{end} is outside the file ({total} bytes)."
+        )
+    # Whole lines before and after the range stay as they are
+    head, head_end = 0, 0
+    while (
+        head < len(data)
+        and head_end + len(data[head]) <= start
+        and data[head].endswith(b"\n")
+    ):
+        head_end += len(data[head])
+        head += 1
+    tail, tail_start = len(data), total
+    while tail > head and tail_start - len(data[tail - 1]) >= end:
+        tail_start -= len(data[tail - 1])
+        tail -= 1
+    middle = b"".join(data[head:tail])
+    middle = middle[: start - head_end] + written + middle[end - head_end :]
+    # New bytes without a line ending run into the next line
+    while middle and not middle.endswith(b"\n") and tail < len(data):
+        middle += data[tail]
+        tail += 1
+    new_lines = lines_from_content(middle.decode("utf-8", "surrogateescape"), origin)
+    return lines[:head] + new_lines + lines[tail:]
+
+
+def _content_hash(lines: List[BlameLine]) -> str:
+    return hashlib.sha256(
+        render(lines).encode("utf-8", errors="surrogateescape")
+    ).hexdigest()
+
+
+def _find_original_checkpoint(
+    file_entries: List[Dict[str, Any]], history_root: Path
+) -> Optional[int]:
+    """Index of the earliest entry whose checkpoint file still exists."""
+    for index, entry in enumerate(file_entries):
+        checkpoint_rel = entry.get("checkpoint_file")
+        if checkpoint_rel and (history_root / checkpoint_rel).is_file():
+            return index
+    return None
+
+
+def replay_file(
+    file_path_rel: str,
+    all_entries: List[Dict[str, Any]],
+    history_root: Path,
+    apply_only_accepted: bool = False,
+    stop_before: Optional[str] = None,
+) -> List[BlameLine]:
+    """
+    Replays a file's history from its original checkpoint and returns its lines,
+    each attributed to the last entry that added or changed it. Rejected edits
+    are skipped, and pending ones too if `apply_only_accepted`. Entries written
+    by mcpdiff itself (snapshots, reverts) do not change the content. With
+    `stop_before` (an edit ID), the lines just before that edit are returned.
+    Raises HistoryError if the file has no history or an edit does not apply.
+    """
+    file_entries = history.get_relevant_history_for_file(file_path_rel, all_entries)
+    if not file_entries:
+        raise HistoryError(f"No history found for file: {file_path_rel}")
+
+    # The checkpoint holds the content before its entry, so that entry is replayed
+    start = _find_original_checkpoint(file_entries, history_root)
+    if start is not None:
+        checkpoint_path = history_root / file_entries[start]["checkpoint_file"]
+        content = checkpoint_path.read_bytes().decode("utf-8", "surrogateescape")
+        lines = lines_from_content(content)
+    else:
+        if file_entries[0].get("operation", "").lower() != "create":
+            log.warning(
+                f"No checkpoint found for {file_path_rel}. Replaying from an empty file."
+            )
+        start = 0
+        lines = []
+
+    for entry in file_entries[start:]:
+        edit_id = entry.get("edit_id", "unknown_id")
+        if edit_id == stop_before:
+            return lines
+        status = entry.get("status", "").lower()
+        operation = entry.get("operation", "").lower()
+        if entry.get("tool_name") == "mcpdiff" or status == "rejected":
+            continue
+        if entry.get("split_into"):
+            continue  # Replayed as its children
+        if status != "accepted" and (apply_only_accepted or status != "pending"):
+            continue
+
+        if entry.get("file_encoding") == BSDIFF_ENCODING:
+            raise HistoryError(
+                f"{file_path_rel} has binary edits ({edit_id}); blame only covers text files."
+            )
+        diff_path = history.find_diff_file(entry, history_root)
+        if operation in ("edit", "replace", "create"):
+            if diff_path:
+                try:
+                    diff_content = read_diff_file(diff_path, entry.get("file_encoding"))
+                    lines = apply_diff_with_blame(lines, diff_content, entry)
+                except PatchError as e:
+                    raise HistoryError(f"Diff does not apply for {edit_id}: {e}")
+            elif operation != "create":
+                raise HistoryError(f"{operation} op {edit_id} missing diff file")
+        elif operation == "append":
+            if not diff_path:
+                raise HistoryError(f"append op {edit_id} missing diff file")
+            appended = diff_path.read_bytes().decode("utf-8", "surrogateescape")
+            lines = append_with_blame(lines, appended, entry)
+        elif operation == "partial":
+            if not diff_path:
+                raise HistoryError(f"partial op {edit_id} missing diff file")
+            try:
+                lines = splice_with_blame(
+                    lines,
+                    entry.get("byte_range_start") or 0,
+                    entry.get("byte_range_end") or 0,
+                    diff_path.read_bytes(),
+                    entry,
+                )
+            except PatchError as e:
+                raise HistoryError(f"Partial edit {edit_id} does not apply: {e}")
+        elif operation == "delete":
+            lines = []
+        elif operation in MOVE_OPERATIONS:
+            if entry.get("source_path") == file_path_rel:
+                lines = []  # Moved away; a later entry may recreate it
+            # Moved here: content carries over from the source unchanged
+        else:
+            log.debug(f"Skipping {edit_id}: operation '{operation}' not replayed")
+            continue
+
+        expected_hash = entry.get("hash_after")
+        if expected_hash and operation != "delete":
+            actual_hash = _content_hash(lines)
+            if actual_hash != expected_hash:
+                log.warning(
+                    f"Hash mismatch after replaying {edit_id}: expected {expected_hash[:8]}, got {actual_hash[:8]}"
+                )
+    if stop_before is not None:
+        raise HistoryError(f"Edit {stop_before} is not replayed for {file_path_rel}")
+    return lines
+
+
+def content_before(
+    entry: Dict[str, Any], all_entries: List[Dict[str, Any]], history_root: Path
+) -> str:
+    """
+    The content of an edit's file just before the edit, replayed as replay_file()
+    does. Raises HistoryError if it cannot be replayed, or does not hash to the
+    entry's hash_before (an edit before it was rejected since, for example).
+    """
+    lines = replay_file(
+        entry["file_path"], all_entries, history_root, stop_before=entry["edit_id"]
+    )
+    expected_hash = entry.get("hash_before")
+    if expected_hash and _content_hash(lines) != expected_hash:
+        raise HistoryError(
+            f"Replayed content before {entry['edit_id']} does not match its hash_before"
+        )
+    return render(lines)
@@ -0,0 +1,166 @@
+# mcpdiff_schema.py
+
+"""
+JSON Schema (draft-07) documents for the log format of DIFFSPEC.md §4, printed
+by `mcpdiff schema`. They are built from the definitions mcpdiff itself reads
+and checks entries with (LogEntry, the Status and Operation enums and the
+field types of log-append), so they cannot drift from them.
+"""
+
+import json
+from typing import Any, Dict, List
+
+import mcpdiff_api as api
+import mcpdiff_append as append
+
+DRAFT_07 = "http://json-schema.org/draft-07/schema#"
+
+# What each field holds, as documented in DIFFSPEC.md §4
+FIELD_DESCRIPTIONS: Dict[str, str] = {
+    "edit_id": "Unique ID (UUID) of this edit operation.",
+    "conversation_id": "ID of the conversation whose tool call made the edit.",
+    "tool_call_index": "Order of the edit within its conversation (0, 1, 2...). Entries mcpdiff writes itself have negative indexes.",
+    "timestamp": "UTC time the edit was recorded, ISO 8601 (the server writes %Y-%m-%dT%H%M%S.%fZ).",
+    "operation": "Kind of filesystem change.",
+    "file_path": "Workspace-relative path of the file changed (the destination of a move).",
+    "source_path": "Workspace-relative source path of a move, otherwise null.",
+    "tool_name": "MCP tool that made the edit; 'mcpdiff' for snapshots and reverts.",
+    "parent_edit_id": "Edit this one depends on (from the tool request's _meta), possibly in another conversation, or null.",
+    "status": "Review status.",
+    "diff_file": "Path of the diff relative to the history root, or null.",
+    "checkpoint_file": "Path of the content before the conversation's first edit of the file, relative to the history root, or null.",
+    "hash_before": "SHA-256 of the file before the edit (null if it did not exist).",
+    "hash_after": "SHA-256 of the file after the edit (null if it was deleted).",
+    "schema_version": "Version of this format the entry was written in (missing means 0).",
+    "workspace_root_hash": "<algorithm>:<hex> fingerprint of the workspace's file names after the edit, or null.",
+    "session_id": "MCP_SESSION_ID of the server process, or null.",
+    "parent_conversation_id": "Conversation this one was forked from (from the tool request's _meta), or null.",
+    "forked_at": "Edit of the parent conversation at which this one was forked, or null for the parent's latest state.",
+    "byte_range_start": "For partial edits, the offset of the first byte replaced, or null.",
+    "byte_range_end": "For partial edits, the offset after the last byte replaced, or null.",
+    "byte_size_before": "Size of the file in bytes before the edit (null if it did not exist).",
+    "byte_size_after": "Size of the file in bytes after the edit (null if it was deleted).",
+    "conflict_markers": "Merge conflict blocks left in the file after the edit, as [{start_line, end_line}] (1-based, inclusive), or null if there are none.",
+    "ignored": "Whether the file was listed in .mcp/ignore when the edit was logged, so it has no checkpoint and an empty diff, and re-applies leave it as it is.",
+    "file_path_bytes": "Base64 of the exact bytes of file_path, present when they are not valid UTF-8.",
+    "source_path_bytes": "Base64 of the exact bytes of source_path, present when they are not valid UTF-8.",
+    "ide_context": "Editor state sent by the client in the tool request's _meta, stored as given.",
+    "archived": "Set by mcpdiff prune when the diff and checkpoint were deleted; the entry is then a record only.",
+    "review_notes": "A reviewer's notes on the edit, set by mcpdiff annotate.",
+    "file_encoding": "How diff_file is stored: a unified diff ('text', the default), a base64-encoded unified diff of a file that is not valid UTF-8 ('base64'), or a BSDIFF40 patch ('bsdiff').",
+    "split_into": "Set by mcpdiff split: the IDs of the edits this one was split into, in order. It is then applied as them.",
+}
+
+STATUS_DESCRIPTIONS: Dict[str, str] = {
+    api.Status.PENDING.value: "Not reviewed yet; the change is on disk.",
+    api.Status.ACCEPTED.value: "Kept by the reviewer.",
+    api.Status.REJECTED.value: "Undone by the reviewer; re-applies skip it.",
+    api.Status.DONE.value: "A snapshot or revert entry mcpdiff wrote.",
+    api.Status.FAILED.value: "A snapshot or revert entry mcpdiff could not complete.",
+}
+
+OPERATION_DESCRIPTIONS: Dict[str, str] = {
+    api.Operation.CREATE.value: "A new file was written.",
+    api.Operation.REPLACE.value: "An existing file was overwritten.",
+    api.Operation.EDIT.value: "Part of a file was changed.",
+    api.Operation.APPEND.value: "Content was added at the end of a file.",
+    api.Operation.PARTIAL.value: "One byte range of a large file was rewritten; diff_file holds the new bytes.",
+    api.Operation.DELETE.value: "The file was deleted.",
+    api.Operation.MOVE.value: "The file was moved from source_path to file_path in another directory.",
+    api.Operation.RENAME.value: "The file was renamed from source_path to file_path in the same directory.",
+    api.Operation.SNAPSHOT.value: "mcpdiff saved the file before a reject.",
+    api.Operation.REVERT.value: "mcpdiff re-applied the file after a reject.",
+}
+
+SCHEMA_TYPES = ["LogEntry", "Status", "Operation"]
+
+_JSON_TYPES = {
+    str: "string",
+    int: "integer",
+    dict: "object",
+    bool: "boolean",
+    list: "array",
+}
+
+
+def _enum_schema(
+    title: str, doc: str, descriptions: Dict[str, str]
+) -> Dict[str, Any]:
+    values: List[str] = list(descriptions)
+    return {
+        "title": title,
+        "description": doc,
+        "type": "string",
+        "enum": values,
+        # Not a draft-07 keyword; validators ignore it
+        "enumDescriptions": [descriptions[value] for value in values],
+    }
+
+
+def _status_schema() -> Dict[str, Any]:
+    return _enum_schema(
+        "Status",
+        "Review status of a log entry. Entries written by mcpdiff use done/failed.",
+        STATUS_DESCRIPTIONS,
+    )
+
+
+def _operation_schema() -> Dict[str, Any]:
+    return _enum_schema(
+        "Operation",
+        "Filesystem change recorded by a log entry (snapshot/revert by mcpdiff).",
+        OPERATION_DESCRIPTIONS,
+    )
+
+
+def _log_entry_schema() -> Dict[str, Any]:
+    properties: Dict[str, Any] = {}
+    for name, types in append._FIELD_TYPES.items():
+        if name in ("status", "operation"):
+            prop: Dict[str, Any] = {"$ref": f"#/definitions/{name.capitalize()}"}
+        else:
+            json_types = ["null" if t is None else _JSON_TYPES[t] for t in types]
+            prop = {"type": json_types[0] if len(json_types) == 1 else json_types}
+        prop["description"] = FIELD_DESCRIPTIONS[name]
+        properties[name] = prop
+    properties["timestamp"]["pattern"] = append._TIMESTAMP_RE.pattern
+    properties["file_encoding"]["enum"] = append.FILE_ENCODINGS
+    properties["file_encoding"]["default"] = append.FILE_ENCODINGS[0]
+    properties["archived"]["default"] = False
+    properties["ignored"]["default"] = False
+    properties["split_into"]["items"] = {"type": "string"}
+    properties["conflict_markers"]["items"] = {
+        "type": "object",
+        "required": ["start_line", "end_line"],
+        "properties": {
+            "start_line": {"type": "integer", "minimum": 1},
+            "end_line": {"type": "integer", "minimum": 1},
+        },
+    }
+    properties["schema_version"]["minimum"] = 0
+    return {
+        "$schema": DRAFT_07,
+        "title": "LogEntry",
+        "description": "One line of a conversation log (logs/<conv_id>.log, JSON Lines), as described in DIFFSPEC.md section 4. Readers should ignore fields they do not know.",
+        "type": "object",
+        "required": list(api.LogEntry._REQUIRED_FIELDS),
+        "properties": properties,
+        "additionalProperties": True,
+        "definitions": {"Status": _status_schema(), "Operation": _operation_schema()},
+    }
+
+
+def get_schema(type_name: str = "LogEntry") -> Dict[str, Any]:
+    """The JSON Schema of one of SCHEMA_TYPES."""
+    if type_name == "LogEntry":
+        return _log_entry_schema()
+    if type_name == "Status":
+        return {"$schema": DRAFT_07, **_status_schema()}
+    if type_name == "Operation":
+        return {"$schema": DRAFT_07, **_operation_schema()}
+    raise ValueError(f"Unknown schema type: {type_name}")
+
+
+def dump_schema(type_name: str = "LogEntry") -> str:
+    """get_schema() as indented JSON."""
+    return json.dumps(get_schema(type_name), indent=2)
@@ -0,0 +1,332 @@
+# mcpdiff_snapshot.py
+
+import os
+import shutil
+import uuid
+from datetime import datetime, timezone
+from pathlib import Path
+from typing import Any, Dict, List, Optional, Tuple
+
+import mcpdiff_audit as audit
+import mcpdiff_history as history
+import mcpdiff_ignore as ignore
+import mcpdiff_utils as utils
+from mcpdiff_migration import CURRENT_SCHEMA_VERSION
+from mcpdiff_utils import (
+    AdvisoryLock,
+    HistoryError,
+    MissingCheckpointError,
+    log,
+)
+
+# --- Workspace Snapshots ---
+# `mcpdiff snapshot` checkpoints every tracked file (every path with an entry in
+# any conversation) at once, outside the edit flow, e.g. before a refactor.
+# The files are copied like the server's checkpoints to
+# <history_root>/snapshots/<snapshot_id>/, and one entry with operation
+# "snapshot" is appended to snapshots/snapshots.log. Its `files` list each path
+# with its hash and checkpoint, both null for a file that did not exist. The
+# log is not in logs/, so a snapshot is no conversation and no edit: reviews,
+# re-applies and pruning never see it. `restore-snapshot` writes all files back
+# as they were, or leaves all of them alone.
+SNAPSHOTS_DIR = "snapshots"
+SNAPSHOTS_LOG_NAME = "snapshots.log"
+# The conversation_id of snapshot entries
+SNAPSHOT_CONVERSATION = "snapshots"
+
+
+def get_snapshots_log_path(history_root: Path) -> Path:
+    return history_root / SNAPSHOTS_DIR / SNAPSHOTS_LOG_NAME
+
+
+def _snapshots_lock(history_root: Path) -> AdvisoryLock:
+    return AdvisoryLock(get_snapshots_log_path(history_root).with_suffix(".lock"))
+
+
+def read_snapshots(history_root: Path) -> List[Dict[str, Any]]:
+    """The snapshot entries, oldest first."""
+    log_path = get_snapshots_log_path(history_root)
+    if not log_path.is_file():
+        return []
+    with _snapshots_lock(history_root):
+        entries = utils.read_log_file(log_path, migrate=False, locked=False)
+    for entry in entries:
+        entry["files"] = [utils.decode_log_paths(f) for f in entry.get("files", [])]
+    return entries
+
+
+def find_snapshot(history_root: Path, snapshot_id: str) -> Dict[str, Any]:
+    """The snapshot with this ID or ID prefix. Raises HistoryError if none."""
+    snapshot = history.find_entry_by_id(read_snapshots(history_root), snapshot_id)
+    if not snapshot:
+        raise HistoryError(f"No snapshot found with ID prefix: {snapshot_id}")
+    return snapshot
+
+
+def tracked_files(workspace_root: Path, all_entries: List[Dict[str, Any]]) -> List[str]:
+    """
+    The workspace-relative paths with an entry in any conversation, as the
+    file edited or the source of a move, without those in .mcp/ignore.
+    """
+    matcher = ignore.load_ignore_matcher(workspace_root)
+    paths = set()
+    for entry in all_entries:
+        for key in ("file_path", "source_path"):
+            path = entry.get(key)
+            # Paths outside the workspace stay absolute when normalized
+            if path and not os.path.isabs(path) and not path.startswith("../"):
+                paths.add(path)
+    return sorted(p for p in paths if not matcher.matches(p))
+
+
+def create_snapshot(
+    workspace_root: Path,
+    history_root: Path,
+    all_entries: List[Dict[str, Any]],
+    message: Optional[str] = None,
+    lock_timeout: Optional[float] = None,
+    invocation: Optional[audit.Invocation] = None,
+) -> Dict[str, Any]:
+    """
+    Checkpoints every tracked file and logs the snapshot. Returns its entry.
+    """
+    snapshot_id = str(uuid.uuid4())
+    snapshot_dir = history_root / SNAPSHOTS_DIR / snapshot_id
+    snapshot_dir.mkdir(parents=True)
+    files = []
+    try:
+        for file_path in tracked_files(workspace_root, all_entries):
+            live_path = workspace_root / file_path
+            if not live_path.is_file():
+                files.append(
+                    {"file_path": file_path, "hash": None, "checkpoint_file": None}
+                )
+                continue
+            # Hashed as copied; the hash keeps names of paths like a/b and a_b apart
+            temp_path = snapshot_dir / f"{len(files)}.tmp"
+            shutil.copy2(live_path, temp_path)
+            file_hash = utils.calculate_hash(str(temp_path))
+            sanitized_path = (
+                utils.display_path(file_path).replace("/", "_").replace("\\", "_")
+            )
+            checkpoint_path = snapshot_dir / f"{sanitized_path}_{file_hash[:16]}.chkpt"
+            os.replace(temp_path, checkpoint_path)
+            files.append(
+                {
+                    "file_path": file_path,
+                    "hash": file_hash,
+                    "checkpoint_file": checkpoint_path.relative_to(
+                        history_root
+                    ).as_posix(),
+                }
+            )
+    except OSError as e:
+        shutil.rmtree(snapshot_dir, ignore_errors=True)
+        raise HistoryError(f"Could not snapshot the workspace: {e}") from e
+
+    log_path = get_snapshots_log_path(history_root)
+    with _snapshots_lock(history_root):
+        entries = utils.read_log_file(log_path, migrate=False, locked=False)
+        entry = {
+            "schema_version": CURRENT_SCHEMA_VERSION,
+            "edit_id": snapshot_id,
+            "conversation_id": SNAPSHOT_CONVERSATION,
+            "tool_call_index": len(entries),
+            "timestamp": datetime.now(timezone.utc)
+            .isoformat(timespec="milliseconds")
+            .replace("+00:00", "Z"),
+            "operation": "snapshot",
+            "file_path": ".",
+            "tool_name": "mcpdiff",
+            "status": "done",
+            "message": message,
+            "files": [utils.encode_log_paths(f) for f in files],
+        }
+        entries.append(entry)
+        temp_path = log_path.with_suffix(log_path.suffix + ".tmp" + str(os.getpid()))
+        try:
+            with open(temp_path, "wb") as f:
+                f.write(utils.serialize_log_entries(entries))
+            os.replace(temp_path, log_path)
+        except OSError as e:
+            temp_path.unlink(missing_ok=True)
+            shutil.rmtree(snapshot_dir, ignore_errors=True)
+            raise HistoryError(f"Could not write {log_path}: {e}") from e
+    entry["files"] = files
+    audit.record(
+        history_root,
+        "snapshot",
+        [],
+        "success",
+        invocation=invocation,
+        lock_timeout=lock_timeout,
+        snapshot_id=snapshot_id,
+        files=len(files),
+    )
+    log.info(f"Snapshot {snapshot_id} of {len(files)} files")
+    return entry
+
+
+def _created_since(
+    workspace_root: Path, snapshot: Dict[str, Any], all_entries: List[Dict[str, Any]]
+) -> List[str]:
+    """
+    Tracked files the snapshot does not list because their first entry, made
+    after it, created them (or moved them there).
+    """
+    listed = {item["file_path"] for item in snapshot["files"]}
+    unlisted = set(tracked_files(workspace_root, all_entries)) - listed
+    taken = utils.parse_timestamp(snapshot["timestamp"])
+    first_entries: Dict[str, Dict[str, Any]] = {}
+    for entry in all_entries:  # Oldest first
+        if entry.get("file_path") in unlisted:
+            first_entries.setdefault(entry["file_path"], entry)
+    return sorted(
+        path
+        for path, entry in first_entries.items()
+        if entry.get("operation") in ("create",) + utils.MOVE_OPERATIONS
+        and utils.parse_timestamp(entry.get("timestamp", 0)) > taken
+    )
+
+
+def plan_restore(
+    workspace_root: Path,
+    history_root: Path,
+    snapshot: Dict[str, Any],
+    all_entries: List[Dict[str, Any]],
+) -> List[Dict[str, Any]]:
+    """
+    What restoring `snapshot` changes: {file_path, action, checkpoint_path}
+    per file that differs from its snapshotted state, where action is "write"
+    or "delete". Files created after the snapshot are deleted too. Raises
+    MissingCheckpointError if a checkpoint is missing or no longer matches its
+    hash.
+    """
+    absent = [
+        {"file_path": path, "checkpoint_file": None}
+        for path in _created_since(workspace_root, snapshot, all_entries)
+    ]
+    changes = []
+    for item in snapshot["files"] + absent:
+        file_path = item["file_path"]
+        live_path = workspace_root / file_path
+        if item["checkpoint_file"] is None:
+            if live_path.exists():
+                changes.append(
+                    {
+                        "file_path": file_path,
+                        "action": "delete",
+                        "checkpoint_path": None,
+                    }
+                )
+            continue
+        checkpoint_path = history_root / item["checkpoint_file"]
+        if utils.calculate_hash(str(checkpoint_path)) != item["hash"]:
+            raise MissingCheckpointError(
+                f"Checkpoint of {utils.display_path(file_path)} in snapshot "
+                f"{snapshot['edit_id']} is missing or changed: "
+                f"{item['checkpoint_file']}"
+            )
+        if utils.calculate_hash(str(live_path)) != item["hash"]:
+            changes.append(
+                {
+                    "file_path": file_path,
+                    "action": "write",
+                    "checkpoint_path": checkpoint_path,
+                }
+            )
+    return changes
+
+
+def restore_snapshot(
+    workspace_root: Path,
+    history_root: Path,
+    snapshot_id: str,
+    all_entries: List[Dict[str, Any]],
+    lock_timeout: Optional[float] = None,
+    invocation: Optional[audit.Invocation] = None,
+) -> Dict[str, Any]:
+    """
+    Writes every file of a snapshot back to its snapshotted content, and
+    deletes those that did not exist then, under the locks of all
+    conversations so that the server does not edit them meanwhile. The new
+    contents are staged next to the files first; if staging or any of the
+    renames fails, the files already replaced are put back and nothing is
+    changed. Returns {snapshot_id, written, deleted}.
+    """
+    snapshot = find_snapshot(history_root, snapshot_id)
+    conv_ids = {e["conversation_id"] for e in all_entries if e.get("conversation_id")}
+    locks: List[utils.ConversationLock] = []
+    try:
+        for conv_id in sorted(conv_ids):
+            lock = utils.ConversationLock(history_root, conv_id)
+            lock.acquire(lock_timeout)
+            locks.append(lock)
+        changes = plan_restore(workspace_root, history_root, snapshot, all_entries)
+        _apply_changes(workspace_root, changes)
+    finally:
+        for lock in reversed(locks):
+            lock.release()
+
+    written = [c["file_path"] for c in changes if c["action"] == "write"]
+    deleted = [c["file_path"] for c in changes if c["action"] == "delete"]
+    audit.record(
+        history_root,
+        "restore-snapshot",
+        [],
+        "success",
+        invocation=invocation,
+        lock_timeout=lock_timeout,
+        snapshot_id=snapshot["edit_id"],
+        written=[utils.display_path(p) for p in written],
+        deleted=[utils.display_path(p) for p in deleted],
+    )
+    return {"snapshot_id": snapshot["edit_id"], "written": written, "deleted": deleted}
+
+
+def _apply_changes(workspace_root: Path, changes: List[Dict[str, Any]]) -> None:
+    """Stages, then renames all changes into place, undoing them on a failure."""
+    suffix = f".mcpdiff-restore-{os.getpid()}"
+    staged: List[Optional[Path]] = []
+    # (file, its previous content moved aside or None) in the order replaced
+    replaced: List[Tuple[Path, Optional[Path]]] = []
+    try:
+        for change in changes:
+            live_path = workspace_root / change["file_path"]
+            if change["action"] == "write":
+                live_path.parent.mkdir(parents=True, exist_ok=True)
+                stage_path = live_path.with_name(live_path.name + suffix)
+                shutil.copy2(change["checkpoint_path"], stage_path)
+                staged.append(stage_path)
+            else:
+                staged.append(None)
+        for change, stage_path in zip(changes, staged):
+            live_path = workspace_root / change["file_path"]
+            backup_path = None
+            if live_path.exists():
+                backup_path = live_path.with_name(live_path.name + suffix + ".bak")
+                os.replace(live_path, backup_path)
+            replaced.append((live_path, backup_path))
+            if stage_path is not None:
+                os.replace(stage_path, live_path)
+    except OSError as e:
+        for live_path, backup_path in reversed(replaced):
+            try:
+                if backup_path is not None:
+                    os.replace(backup_path, live_path)
+                else:
+                    live_path.unlink(missing_ok=True)
+            except OSError as undo_error:
+                # Its previous content stays in the backup file
+                log.error(f"Could not put back {live_path}: {undo_error}")
+                continue
+        raise HistoryError(
+            f"Could not restore the snapshot, nothing changed: {e}"
+        ) from e
+    finally:
+        for stage_path in staged:
+            if stage_path is not None:
+                stage_path.unlink(missing_ok=True)
+    for _live_path, backup_path in replaced:
+        if backup_path is not None:
+            backup_path.unlink(missing_ok=True)
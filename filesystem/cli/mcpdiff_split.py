@@ -0,0 +1,208 @@
+# mcpdiff_split.py
+
+import hashlib
+import uuid
+from pathlib import Path
+from typing import Any, Dict, List, Optional, Tuple
+
+import mcpdiff_audit as audit
+import mcpdiff_history as history
+import mcpdiff_replay as replay
+import mcpdiff_symbols as symbols
+import mcpdiff_utils as utils
+from mcpdiff_patch import (
+    BSDIFF_ENCODING,
+    Hunk,
+    PatchError,
+    apply_unified_diff,
+    encode_diff,
+    format_hunks,
+    make_unified_diff,
+    parse_unified_diff,
+    read_diff_file,
+)
+from mcpdiff_utils import LOGS_DIR, HistoryError, log
+
+# --- Splitting Edits by Symbol ---
+# `mcpdiff split` turns a pending edit of a Rust file into child edits, one per
+# top-level item (fn, struct, impl, ...) its hunks change, plus one for the
+# hunks outside any item. The children are logged right after the edit with
+# its timestamp and tool_call_index and with it as their parent_edit_id; each
+# has its own diff, on top of the children before it. The edit itself gets
+# `split_into`, the children's IDs in order: re-applies skip it and apply the
+# children in its place, so they can be accepted and rejected one by one.
+
+# The label of the hunks outside any item
+OTHER_HUNKS = "(other)"
+
+
+def _content_hash(content: str) -> str:
+    return hashlib.sha256(content.encode("utf-8", "surrogateescape")).hexdigest()
+
+
+def group_hunks(
+    hunks: List[Hunk], content: str, file_path: str
+) -> List[Tuple[str, List[Hunk]]]:
+    """
+    The hunks of a diff of `content` grouped by the top-level item around the
+    first line each changes, as (label, hunks) in the order of their first hunk.
+    """
+    elements = symbols.parse_elements(content, file_path) or []
+    groups: Dict[str, List[Hunk]] = {}
+    for hunk in hunks:
+        element = symbols.enclosing_symbol(elements, symbols.first_changed_line(hunk))
+        while element is not None and element.parent is not None:
+            element = element.parent
+        label = symbols.symbol_name(element) if element is not None else OTHER_HUNKS
+        groups.setdefault(label, []).append(hunk)
+    return list(groups.items())
+
+
+def _check_splittable(entry: Dict[str, Any]) -> None:
+    edit_id = entry.get("edit_id")
+    operation = (entry.get("operation") or "").lower()
+    status = (entry.get("status") or "").lower()
+    if entry.get("tool_name") == "mcpdiff":
+        raise HistoryError(f"Entry {edit_id} was written by mcpdiff; it is no edit.")
+    if entry.get("split_into"):
+        raise HistoryError(f"Edit {edit_id} is already split.")
+    if status != "pending":
+        raise HistoryError(f"Only pending edits can be split; {edit_id} is {status}.")
+    if operation not in ("edit", "replace"):
+        raise HistoryError(f"Only edits can be split; {edit_id} is a {operation}.")
+    if entry.get("archived") or entry.get("file_encoding") == BSDIFF_ENCODING:
+        raise HistoryError(f"Edit {edit_id} has no text diff to split.")
+    if not symbols.supports(entry.get("file_path")):
+        extensions = ", ".join(sorted(symbols.SYMBOL_EXTENSIONS))
+        raise HistoryError(
+            f"Cannot split {edit_id}: items are only known in {extensions} files."
+        )
+
+
+def plan_split(
+    entry: Dict[str, Any], all_entries: List[Dict[str, Any]], history_root: Path
+) -> List[Dict[str, Any]]:
+    """
+    The child edits `entry` would be split into, in order, each as {label,
+    diff, hash_before, hash_after}. Raises HistoryError if the edit cannot be
+    split, or changes a single item.
+    """
+    _check_splittable(entry)
+    edit_id = entry["edit_id"]
+    file_path = entry["file_path"]
+    diff_path = history.find_diff_file(entry, history_root)
+    if diff_path is None:
+        raise HistoryError(f"Diff file of {edit_id} is missing.")
+    try:
+        diff_content = read_diff_file(diff_path, entry.get("file_encoding"))
+        hunks = parse_unified_diff(diff_content)
+    except PatchError as e:
+        raise HistoryError(f"Cannot parse the diff of {edit_id}: {e}")
+    # The content the diff's line numbers refer to
+    before = replay.content_before(entry, all_entries, history_root)
+    groups = group_hunks(hunks, before, file_path)
+    if len(groups) < 2:
+        changed = groups[0][0] if groups else "nothing"
+        raise HistoryError(f"Edit {edit_id} only changes {changed}; nothing to split.")
+
+    children = []
+    applied: List[Hunk] = []
+    content = before
+    for label, group in groups:
+        # Each child is the edit's hunks so far, at their recorded positions
+        applied = sorted(applied + group, key=lambda hunk: hunk.old_start)
+        try:
+            after = apply_unified_diff(before, format_hunks(applied))
+        except PatchError as e:
+            raise HistoryError(f"Diff of {edit_id} does not apply: {e}")
+        children.append(
+            {
+                "label": label,
+                "diff": make_unified_diff(content, after, file_path),
+                "hash_before": _content_hash(content),
+                "hash_after": _content_hash(after),
+            }
+        )
+        content = after
+    expected_hash = entry.get("hash_after")
+    if expected_hash and children[-1]["hash_after"] != expected_hash:
+        raise HistoryError(f"The hunks of {edit_id} do not add up to its hash_after.")
+    return children
+
+
+def split_entry(
+    entry: Dict[str, Any],
+    all_entries: List[Dict[str, Any]],
+    history_root: Path,
+    lock_timeout: Optional[float] = None,
+    invocation: Optional[audit.Invocation] = None,
+) -> List[Dict[str, Any]]:
+    """
+    Splits a pending edit into the child edits of plan_split(): writes their
+    diffs, then logs them after the edit and sets its `split_into`, under the
+    conversation lock. Returns the children as logged, each with its `label`
+    added (not stored).
+    """
+    edit_id = entry["edit_id"]
+    planned = plan_split(entry, all_entries, history_root)
+    conv_id = entry.get("conversation_id", "")
+    log_file_path = history_root / LOGS_DIR / entry["log_file_source"]
+    conv_lock = utils.ConversationLock(history_root, conv_id)
+    conv_lock.acquire(lock_timeout)
+    written: List[Path] = []
+    with conv_lock:
+        log_entries = utils.read_log_file(
+            log_file_path, lock_timeout=lock_timeout, migrate=False, strict=True
+        )
+        position = next(
+            (i for i, e in enumerate(log_entries) if e.get("edit_id") == edit_id),
+            None,
+        )
+        if position is None:
+            raise HistoryError(f"Edit {edit_id} not found in {log_file_path.name}")
+        stored = log_entries[position]
+        # Another mcpdiff may have reviewed or split it since it was read
+        _check_splittable(stored)
+
+        diff_dir = Path(stored.get("diff_file") or f"diffs/{conv_id}/x").parent
+        children = []
+        for number, child in enumerate(planned):
+            child_id = str(uuid.uuid4())
+            diff_rel = (diff_dir / f"{child_id}.diff").as_posix()
+            diff_abs = history_root / diff_rel
+            diff_abs.parent.mkdir(parents=True, exist_ok=True)
+            diff_data = encode_diff(child["diff"], stored.get("file_encoding"))
+            diff_abs.write_bytes(diff_data)
+            written.append(diff_abs)
+            # Notes on the edit need not hold for each part of it
+            logged = {k: v for k, v in stored.items() if k != "review_notes"}
+            logged.update(
+                edit_id=child_id,
+                parent_edit_id=edit_id,
+                diff_file=diff_rel,
+                # The checkpoint holds the content before the first child
+                checkpoint_file=stored.get("checkpoint_file") if number == 0 else None,
+                hash_before=child["hash_before"],
+                hash_after=child["hash_after"],
+            )
+            children.append(logged)
+        stored["split_into"] = [child["edit_id"] for child in children]
+        log_entries[position + 1 : position + 1] = children
+        try:
+            utils.write_log_file(log_file_path, log_entries, lock_timeout=lock_timeout)
+        except Exception:
+            for path in written:
+                path.unlink(missing_ok=True)
+            raise
+
+    log.debug(f"Split {edit_id} into {len(children)} edits")
+    audit.record(
+        history_root,
+        "split",
+        [edit_id],
+        "success",
+        invocation=invocation,
+        lock_timeout=lock_timeout,
+        children=stored["split_into"],
+    )
+    return [dict(logged, label=c["label"]) for logged, c in zip(children, planned)]
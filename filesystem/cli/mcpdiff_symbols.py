@@ -0,0 +1,118 @@
+# mcpdiff_symbols.py
+
+"""
+Names the code a diff hunk changes, for `show --symbols`: the content before
+the edit is parsed with the server's grammar parsers (src/grammar), and each
+hunk header gets the innermost symbol around its first changed line, as in
+`@@ -12,6 +12,7 @@ fn Parser::parse (src/parser.rs)`. Anything that cannot be
+parsed keeps its plain header.
+"""
+
+import sys
+from pathlib import Path
+from typing import Any, List, Optional
+
+from mcpdiff_patch import HUNK_HEADER_PATTERN, PatchError, parse_unified_diff
+from mcpdiff_utils import log
+
+# The grammar package is not installed with the CLI; it is found next to it
+_SRC_DIR = Path(__file__).resolve().parent.parent / "src"
+
+# Languages whose symbols are shown, by file extension
+SYMBOL_EXTENSIONS = {".rs"}
+
+# How each kind of element is introduced in the header, as in Rust source
+_KEYWORDS = {
+    "function": "fn",
+    "method": "fn",
+    "struct": "struct",
+    "enum": "enum",
+    "trait": "trait",
+    "impl": "impl",
+    "module": "mod",
+    "constant": "const",
+    "variable": "static",
+    "type_definition": "type",
+}
+
+
+def supports(file_path: Optional[str]) -> bool:
+    """Whether hunks of the file can be annotated with symbols."""
+    return bool(file_path) and Path(file_path).suffix.lower() in SYMBOL_EXTENSIONS
+
+
+def parse_elements(content: str, file_path: str) -> Optional[List[Any]]:
+    """The grammar's code elements of the content, or None if it cannot parse it."""
+    if str(_SRC_DIR) not in sys.path:
+        sys.path.insert(0, str(_SRC_DIR))
+    try:
+        from grammar.regex_parser import get_parser_for_file
+
+        return get_parser_for_file(file_path).parse(content)
+    except Exception as e:  # The parsers are heuristic; any failure is a fallback
+        log.debug(f"Could not parse {file_path} for symbols: {e}")
+        return None
+
+
+def symbol_name(element: Any) -> str:
+    """An element with its kind and the path of the elements it is nested in."""
+    names = [element.name]
+    parent = element.parent
+    while parent is not None:
+        names.append(parent.name)
+        parent = parent.parent
+    keyword = _KEYWORDS.get(element.element_type.value, element.element_type.value)
+    return f"{keyword} {'::'.join(reversed(names))}"
+
+
+def enclosing_symbol(elements: List[Any], line: int) -> Optional[Any]:
+    """The innermost (shortest) element containing the 1-based line, if any."""
+    containing = [e for e in elements if e.start_line <= line <= e.end_line]
+    if not containing:
+        return None
+    return min(containing, key=lambda e: e.end_line - e.start_line)
+
+
+def first_changed_line(hunk: Any) -> int:
+    """
+    The line of the old content the hunk first changes; for an insertion, the
+    line it follows (or the first line, at the start of the file).
+    """
+    line = hunk.old_start
+    for tag, _text, _eol in hunk.lines:
+        if tag == "-":
+            return line
+        if tag == "+":
+            return max(line - 1, 1)
+        line += 1
+    return hunk.old_start
+
+
+def annotate_hunk_headers(diff_content: str, content: str, file_path: str) -> str:
+    """
+    The diff with the symbol each hunk changes in `content` (the file before
+    the diff) appended to its `@@` header. Returned unchanged if the file type is
+    not supported or cannot be parsed.
+    """
+    if not supports(file_path):
+        return diff_content
+    try:
+        hunks = parse_unified_diff(diff_content)
+    except PatchError as e:
+        log.debug(f"Could not parse the diff of {file_path}: {e}")
+        return diff_content
+    elements = parse_elements(content, file_path)
+    if not elements:
+        return diff_content
+
+    lines = diff_content.split("\n")
+    hunk_index = 0
+    for i, line in enumerate(lines):
+        match = HUNK_HEADER_PATTERN.match(line)
+        if not match or hunk_index >= len(hunks):
+            continue
+        element = enclosing_symbol(elements, first_changed_line(hunks[hunk_index]))
+        hunk_index += 1
+        if element is not None:
+            lines[i] = f"{match.group(0)} {symbol_name(element)} ({file_path})"
+    return "\n".join(lines)
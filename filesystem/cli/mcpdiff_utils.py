@@ -1,15 +1,43 @@
 # mcpdiff_utils.py
 
+import asyncio
+import base64
+import contextlib
 import os
-import fcntl
 import time
 import hashlib
 import json
 import logging
 import re
+import sys
+from dataclasses import dataclass
 from pathlib import Path
 from datetime import datetime, timezone
-from typing import List, Dict, Any, Optional, Union
+from typing import List, Dict, Any, Iterator, Optional, Union, Callable, Tuple
+
+import mcpdiff_metrics as metrics
+from mcpdiff_migration import SchemaVersionError, default_migrator
+
+# The history format shared with the server lives next to it, in src/
+_SRC_DIR = Path(__file__).resolve().parent.parent / "src"
+if str(_SRC_DIR) not in sys.path:
+    sys.path.append(str(_SRC_DIR))
+import mcp_history_format as history_format  # noqa: E402
+from mcp_history_format import (  # noqa: E402
+    WAL_SUFFIX,
+    compute_workspace_root_hash,
+    fsync_directory,
+    get_conversation_log_files,
+    get_log_conversation_id,
+    write_synced,
+    write_wal,
+)
+
+try:
+    import fcntl
+except ImportError:  # Windows
+    fcntl = None
+    import msvcrt
 
 # --- Configuration Constants ---
 # These might be better placed in history if purely history-related,
@@ -18,7 +46,22 @@ HISTORY_DIR_NAME = "edit_history"
 LOGS_DIR = "logs"
 DIFFS_DIR = "diffs"
 CHECKPOINTS_DIR = "checkpoints"
+# The server's locks on workspace files (see get_target_lock_path() there)
+LOCKS_DIR = "locks"
 LOCK_TIMEOUT = 10  # seconds for file locks
+# Waits between attempts on a conversation lock: doubling from the first to the
+# last, in seconds, for both AdvisoryLock.acquire() and acquire_async()
+LOCK_BACKOFF_INITIAL = 0.01
+LOCK_BACKOFF_MAX = 0.1
+IS_WINDOWS = os.name == "nt"
+# With `namespace` configured, conversation IDs are <namespace>/<conv_id> and
+# their logs, diffs and checkpoints live one directory further down
+NAMESPACE_PATTERN = re.compile(r"^[A-Za-z0-9][A-Za-z0-9._-]*$")
+# The namespace of conversation IDs without a <namespace>/ prefix
+DEFAULT_NAMESPACE = "default"
+# Operations that take a file from source_path to file_path: "rename" within
+# one directory, "move" to another one (also in logs from before "rename")
+MOVE_OPERATIONS = ("move", "rename")
 
 # --- Logging Setup ---
 # Initialize logger basic config - level will be set in main() of mcpdiff.py
@@ -38,23 +81,126 @@ COLOR_CYAN = "\033[96m"
 COLOR_MAGENTA = "\033[95m"
 
 
+@dataclass(frozen=True)
+class Colors:
+    """
+    The color codes a printer uses: the COLOR_* constants, or none at all for
+    show --no-color, --format raw and output that is not a terminal.
+    """
+
+    reset: str = COLOR_RESET
+    red: str = COLOR_RED
+    green: str = COLOR_GREEN
+    yellow: str = COLOR_YELLOW
+    blue: str = COLOR_BLUE
+    cyan: str = COLOR_CYAN
+    magenta: str = COLOR_MAGENTA
+
+
+NO_COLORS = Colors("", "", "", "", "", "", "")
+
+
+def get_colors(color: bool) -> Colors:
+    """The color codes to print with, or empty ones if `color` is off."""
+    return Colors() if color else NO_COLORS
+
+
 # --- Custom Exceptions ---
 class HistoryError(Exception):
-    """Custom exception for history-related errors."""
+    """
+    Custom exception for history-related errors. Subclasses name the kind of
+    failure, so callers (and scripts reading `--json` output or the exit code)
+    can tell them apart; keyword details such as `file` are kept as attributes.
+    """
+
+    kind = "history_error"
+    exit_code = 1
+
+    def __init__(self, message: str = "", **details: Any):
+        super().__init__(message)
+        self.details = {k: v for k, v in details.items() if v is not None}
+        for key, value in self.details.items():
+            setattr(self, key, value)
+
+    def to_dict(self) -> Dict[str, Any]:
+        """Machine-readable form: the kind, the message and the details."""
+        return {"kind": self.kind, "message": str(self), **self.details}
 
-    pass
+
+class LockTimeoutError(HistoryError, TimeoutError):
+    """A conversation lock was held by another process for too long."""
+
+    kind = "lock_timeout"
+    exit_code = 3
+
+
+class MissingCheckpointError(HistoryError):
+    """A checkpoint an entry refers to is missing, so a file has no base state."""
+
+    kind = "missing_checkpoint"
+    exit_code = 4
+
+
+class MissingDiffError(HistoryError):
+    """An entry that needs a diff file to be re-applied has none."""
+
+    kind = "missing_diff"
+    exit_code = 5
+
+
+class PatchConflictError(HistoryError):
+    """A diff did not apply to a file; `hunk` is the 1-based failing hunk."""
+
+    kind = "patch_conflict"
+    exit_code = 6
 
 
 class ExternalModificationError(HistoryError):
     """Indicates a file was modified outside the expected history sequence."""
 
-    pass
+    kind = "external_modification"
+    exit_code = 7
+
+
+class CorruptLogError(HistoryError):
+    """A log file line is not a valid entry; `line` is 1-based."""
+
+    kind = "corrupt_log"
+    exit_code = 8
 
 
 class AmbiguousIDError(HistoryError):
     """Indicates a partial ID matched multiple entries."""
 
-    pass
+    kind = "ambiguous_id"
+    exit_code = 9
+
+
+class InvalidLogEntryError(HistoryError):
+    """An entry given to `log-append` breaks the schema; `pointer` names the field."""
+
+    kind = "invalid_entry"
+    exit_code = 10
+
+
+class VerificationError(HistoryError):
+    """A re-applied file does not hash to what the last edit applied recorded."""
+
+    kind = "verification_failed"
+    exit_code = 11
+
+
+class PermissionDeniedError(HistoryError):
+    """The .mcp/acl.toml of the workspace does not let `user` do `action`."""
+
+    kind = "permission_denied"
+    exit_code = 12
+
+
+class WorkspaceNotFoundError(HistoryError):
+    """No directory at or above `start_path` has a .mcp/edit_history."""
+
+    kind = "workspace_not_found"
 
 
 # --- Path Normalization and Expansion ---
@@ -73,15 +219,137 @@ def expand_home(filepath: str) -> str:
     return filepath
 
 
+def strip_long_path_prefix(path: str) -> str:
+    r"""
+    Removes the `\\?\` prefix that Path.resolve() can add on Windows, turning
+    `\\?\UNC\server\share` back into `\\server\share`.
+    """
+    if path.startswith("\\\\?\\UNC\\"):
+        return "\\\\" + path[8:]
+    if path.startswith("\\\\?\\"):
+        return path[4:]
+    return path
+
+
+def normalize_path_for_compare(
+    path: Union[str, Path], case_insensitive: Optional[bool] = None
+) -> str:
+    """
+    Normalizes a path for comparison only: strips the long path prefix, uses
+    `/` as separator, drops leading `./` and trailing `/`, and case-folds if
+    `case_insensitive` (default: on Windows).
+    """
+    if case_insensitive is None:
+        case_insensitive = IS_WINDOWS
+    normalized = strip_long_path_prefix(str(path)).replace("\\", "/")
+    while normalized.startswith("./"):
+        normalized = normalized[2:]
+    if len(normalized) > 1:
+        normalized = normalized.rstrip("/")
+    return normalized.casefold() if case_insensitive else normalized
+
+
+def path_matches_filter(
+    file_path: str, filter_path: str, case_insensitive: Optional[bool] = None
+) -> bool:
+    """True if `filter_path` (as given to --file) occurs in a logged file path."""
+    return normalize_path_for_compare(
+        filter_path, case_insensitive
+    ) in normalize_path_for_compare(file_path, case_insensitive)
+
+
+# Log entry fields holding paths. A path whose OS bytes are not valid UTF-8 is
+# stored with a lossy `<field>` for display and the exact bytes, base64
+# encoded, in `<field>_bytes`. In memory such paths are kept as str with
+# surrogate escapes (os.fsdecode), which round-trips to the original bytes.
+LOG_PATH_FIELDS = ("file_path", "source_path")
+
+
+def display_path(path: Optional[str]) -> Optional[str]:
+    """Printable form of a path: undecodable bytes become U+FFFD."""
+    if path is None:
+        return None
+    return path.encode("utf-8", "surrogateescape").decode("utf-8", "replace")
+
+
+def encode_log_paths(entry: Dict[str, Any]) -> Dict[str, Any]:
+    """Returns a copy of `entry` with non-UTF-8 paths in the `_bytes` form."""
+    encoded = dict(entry)
+    for field in LOG_PATH_FIELDS:
+        path = encoded.get(field)
+        encoded.pop(f"{field}_bytes", None)
+        if not isinstance(path, str):
+            continue
+        try:
+            path.encode("utf-8")
+        except UnicodeEncodeError:
+            encoded[field] = display_path(path)
+            encoded[f"{field}_bytes"] = base64.b64encode(os.fsencode(path)).decode(
+                "ascii"
+            )
+    return encoded
+
+
+def decode_log_paths(entry: Dict[str, Any]) -> Dict[str, Any]:
+    """Restores exact paths from `_bytes` fields in place and returns `entry`."""
+    for field in LOG_PATH_FIELDS:
+        raw = entry.pop(f"{field}_bytes", None)
+        if raw:
+            try:
+                entry[field] = os.fsdecode(base64.b64decode(raw))
+            except (ValueError, TypeError) as e:
+                log.warning(
+                    f"Invalid {field}_bytes in entry {entry.get('edit_id')}: {e}"
+                )
+    return entry
+
+
+def normalize_log_path(path: Optional[str], workspace_root: Path) -> Optional[str]:
+    """
+    Canonical form of a `file_path`/`source_path` read from a log: relative to
+    the workspace root with `/` separators and no `.`/`..` components. Server
+    versions differ in whether they log absolute or relative paths, so both
+    must be normalized before paths are compared. Absolute paths outside the
+    workspace are returned normalized but absolute.
+    """
+    if not path:
+        return path
+    stripped = strip_long_path_prefix(path)
+    if not os.path.isabs(stripped) and not re.match(r"^[A-Za-z]:[\\/]", stripped):
+        normalized = os.path.normpath(stripped.replace("\\", "/")).replace("\\", "/")
+        return "" if normalized == "." else normalized
+
+    # Absolute: compare lexically first, then with symlinks resolved
+    root_str = normalize_path_for_compare(workspace_root, case_insensitive=False)
+    path_str = normalize_path_for_compare(
+        os.path.normpath(stripped), case_insensitive=False
+    ).replace("\\", "/")
+    # Compare with a trailing separator so that /ws2 is not inside /ws
+    prefix = root_str if root_str.endswith("/") else root_str + "/"
+    path_cmp = normalize_path_for_compare(path_str)
+    prefix_cmp = normalize_path_for_compare(root_str).rstrip("/") + "/"
+    if path_cmp + "/" == prefix_cmp:
+        return ""
+    if path_cmp.startswith(prefix_cmp):
+        return path_str[len(prefix) :]
+    try:
+        relative = Path(stripped).resolve().relative_to(workspace_root.resolve())
+        return relative.as_posix()
+    except (ValueError, OSError):
+        log.debug(f"Logged path {path} is outside workspace {workspace_root}")
+        return path_str
+
+
 def is_path_within_directory(path: Path, directory: Path) -> bool:
     """
     Check if a path is within a directory (or is the directory itself).
     Both paths must be absolute and resolved.
     """
     try:
-        path = path.resolve()
-        directory = directory.resolve()
-        return str(path).startswith(str(directory))
+        path_str = normalize_path_for_compare(path.resolve())
+        directory_str = normalize_path_for_compare(directory.resolve())
+        prefix = directory_str if directory_str.endswith("/") else directory_str + "/"
+        return path_str == directory_str or path_str.startswith(prefix)
     except Exception:
         return False
 
@@ -137,9 +405,68 @@ def calculate_hash(file_path: str) -> Optional[str]:
         return None
 
 
-# --- Locking Mechanism (fcntl-based) ---
+class WorkspaceRootHashes:
+    """
+    compute_workspace_root_hash() of one workspace, by algorithm, for a command
+    that compares it with many entries: the workspace is walked once for each
+    algorithm, however many entries are checked.
+    """
+
+    def __init__(self, workspace_root: Path):
+        self.workspace_root = workspace_root
+        self._hashes: Dict[str, str] = {}
+
+    def get(self, algorithm: str) -> str:
+        """The workspace's hash with `algorithm`; ValueError if it is unavailable."""
+        if algorithm not in self._hashes:
+            self._hashes[algorithm] = compute_workspace_root_hash(
+                self.workspace_root, algorithm
+            )
+        return self._hashes[algorithm]
+
+
+# --- Locking Mechanism (fcntl, or msvcrt on Windows) ---
+def lock_file_handle(handle) -> None:
+    """Takes a non-blocking exclusive lock on an open file. Raises OSError if held."""
+    if fcntl is not None:
+        fcntl.flock(handle.fileno(), fcntl.LOCK_EX | fcntl.LOCK_NB)
+    else:
+        # msvcrt locks a byte range from the current position
+        handle.seek(0)
+        msvcrt.locking(handle.fileno(), msvcrt.LK_NBLCK, 1)
+
+
+def unlock_file_handle(handle) -> None:
+    """Releases a lock taken with lock_file_handle()."""
+    if fcntl is not None:
+        fcntl.flock(handle.fileno(), fcntl.LOCK_UN)
+    else:
+        handle.seek(0)
+        msvcrt.locking(handle.fileno(), msvcrt.LK_UNLCK, 1)
+
+
+def pid_exists(pid: int) -> bool:
+    """True if a process with this PID is running."""
+    if IS_WINDOWS:
+        # os.kill(pid, 0) would terminate the process on Windows
+        import ctypes
+
+        PROCESS_QUERY_LIMITED_INFORMATION = 0x1000
+        kernel32 = ctypes.windll.kernel32
+        process = kernel32.OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, False, pid)
+        if not process:
+            return False
+        kernel32.CloseHandle(process)
+        return True
+    try:
+        os.kill(pid, 0)
+        return True
+    except OSError:
+        return False
+
+
 class FileLock:
-    """A simple file locking mechanism using fcntl (Unix-like)."""
+    """A simple file locking mechanism using an exclusive lock on a PID file."""
 
     def __init__(self, path: str):
         self.lock_dir = Path(f"{path}.lockdir")
@@ -172,16 +499,12 @@ class FileLock:
                 log.debug(f"Invalid PID in lock file: {pid}")
                 self._force_cleanup()
                 return True
-            try:
-                os.kill(pid, 0)
+            if pid_exists(pid):
                 log.debug(f"Process with PID {pid} exists, lock may be valid")
                 return False
-            except OSError:
-                log.debug(
-                    f"Process with PID {pid} does not exist, cleaning up stale lock"
-                )
-                self._force_cleanup()
-                return True
+            log.debug(f"Process with PID {pid} does not exist, cleaning up stale lock")
+            self._force_cleanup()
+            return True
         except (ValueError, IOError, OSError) as e:
             log.debug(f"Error checking stale lock: {e}, cleaning up")
             try:
@@ -224,12 +547,11 @@ class FileLock:
 
         self.lock_dir.mkdir(parents=True, exist_ok=True)
 
+        contended = False
         while True:
             try:
                 self.lock_file_handle = open(self.lock_file_path, "w")
-                fcntl.flock(
-                    self.lock_file_handle.fileno(), fcntl.LOCK_EX | fcntl.LOCK_NB
-                )
+                lock_file_handle(self.lock_file_handle)
                 self.lock_file_handle.write(str(os.getpid()))
                 self.lock_file_handle.flush()
                 os.fsync(self.lock_file_handle.fileno())
@@ -240,6 +562,9 @@ class FileLock:
                 if self.lock_file_handle:
                     self.lock_file_handle.close()
                     self.lock_file_handle = None
+                if not contended:
+                    contended = True
+                    metrics.METRICS.increment("lock_contentions_total")
 
                 if time.time() - start_time >= effective_timeout:
                     # Final stale check before timeout
@@ -247,10 +572,7 @@ class FileLock:
                         # Retry one last time immediately after cleanup
                         try:
                             self.lock_file_handle = open(self.lock_file_path, "w")
-                            fcntl.flock(
-                                self.lock_file_handle.fileno(),
-                                fcntl.LOCK_EX | fcntl.LOCK_NB,
-                            )
+                            lock_file_handle(self.lock_file_handle)
                             self.lock_file_handle.write(str(os.getpid()))
                             self.lock_file_handle.flush()
                             os.fsync(self.lock_file_handle.fileno())
@@ -277,8 +599,9 @@ class FileLock:
                     log.error(
                         f"Timeout acquiring lock {self.lock_dir} after {effective_timeout}s. Locked by PID: {locker_pid}."
                     )
-                    raise TimeoutError(
-                        f"Could not acquire lock for {self.lock_dir} (locked by PID {locker_pid})"
+                    raise LockTimeoutError(
+                        f"Could not acquire lock for {self.lock_dir} (locked by PID {locker_pid})",
+                        lock_path=str(self.lock_dir),
                     ) from e
                 time.sleep(0.1)
             except Exception as e:
@@ -291,7 +614,7 @@ class FileLock:
         """Release the lock and cleanup."""
         if self.is_locked and self.lock_file_handle:
             try:
-                fcntl.flock(self.lock_file_handle.fileno(), fcntl.LOCK_UN)
+                unlock_file_handle(self.lock_file_handle)
                 self.is_locked = False
                 log.debug(f"Released lock: {self.lock_dir}")
             except Exception as e:
@@ -315,16 +638,135 @@ class FileLock:
         self.release()
 
 
+class AdvisoryLock:
+    """
+    Exclusive lock on a lock file that stays in place between holders. Unlike
+    FileLock nothing is removed on release, so two processes can never both
+    hold a lock on different incarnations of the file.
+    """
+
+    def __init__(self, lock_file_path: Path):
+        self.lock_file_path = lock_file_path
+        self.lock_file_handle = None
+
+    def _timeout_message(self) -> str:
+        return f"Could not acquire lock {self.lock_file_path}"
+
+    def try_acquire(self) -> bool:
+        """One attempt at the lock, without waiting. Returns whether it is held."""
+        self.lock_file_path.parent.mkdir(parents=True, exist_ok=True)
+        handle = open(self.lock_file_path, "a")
+        try:
+            lock_file_handle(handle)
+            # The server deletes its lock files on release; make sure we did
+            # not lock a file that was unlinked while we were waiting.
+            if os.fstat(handle.fileno()).st_ino == os.stat(
+                self.lock_file_path
+            ).st_ino:
+                self.lock_file_handle = handle
+                log.debug(f"Acquired lock: {self.lock_file_path}")
+                return True
+        except (IOError, OSError):
+            pass
+        handle.close()
+        return False
+
+    def _backoff(self, timeout: Optional[float]) -> Iterator[float]:
+        """
+        The waits before each retry, LOCK_BACKOFF_INITIAL doubling up to
+        LOCK_BACKOFF_MAX; raises LockTimeoutError once `timeout` seconds passed.
+        """
+        effective_timeout = timeout if timeout is not None else LOCK_TIMEOUT
+        start_time = time.time()
+        delay = LOCK_BACKOFF_INITIAL
+        while time.time() - start_time < effective_timeout:
+            yield delay
+            delay = min(delay * 2, LOCK_BACKOFF_MAX)
+        raise LockTimeoutError(
+            self._timeout_message(), lock_path=str(self.lock_file_path)
+        )
+
+    def acquire(self, timeout: Optional[float] = None):
+        """Acquire the lock, polling until `timeout` seconds have passed."""
+        if self.try_acquire():
+            return
+        metrics.METRICS.increment("lock_contentions_total")
+        for delay in self._backoff(timeout):
+            time.sleep(delay)
+            if self.try_acquire():
+                return
+
+    async def acquire_async(self, timeout: Optional[float] = None):
+        """
+        acquire() for coroutines: awaits asyncio.sleep() between attempts, so
+        the event loop keeps running. Cancelling it, as asyncio.wait_for() does,
+        leaves the lock not held.
+        """
+        if self.try_acquire():
+            return
+        metrics.METRICS.increment("lock_contentions_total")
+        for delay in self._backoff(timeout):
+            await asyncio.sleep(delay)
+            if self.try_acquire():
+                return
+
+    def release(self):
+        """Release the lock. The lock file is left in place for other holders."""
+        if self.lock_file_handle:
+            try:
+                unlock_file_handle(self.lock_file_handle)
+                log.debug(f"Released lock: {self.lock_file_path}")
+            finally:
+                self.lock_file_handle.close()
+                self.lock_file_handle = None
+
+    def __enter__(self):
+        if self.lock_file_handle is None:  # May already be held (acquire_lock API)
+            self.acquire()
+        return self
+
+    def __exit__(self, exc_type, exc_val, exc_tb):
+        self.release()
+
+
+class ConversationLock(AdvisoryLock):
+    """
+    Advisory lock on a conversation, shared with the MCP server: a lock on
+    logs/<conv_id>.log.lock, the file the server's acquire_lock() uses while it
+    appends to the conversation's log. Held by mcpdiff while it changes statuses
+    so that it never works from a snapshot the server is still extending.
+    """
+
+    def __init__(self, history_root: Path, conv_id: str):
+        super().__init__(history_root / LOGS_DIR / f"{conv_id}.log.lock")
+        self.conv_id = conv_id
+
+    def _timeout_message(self) -> str:
+        return f"Conversation {self.conv_id} is locked, the MCP server may still be writing to it ({self.lock_file_path})"
+
+
 # --- Log File Handling ---
 def read_log_file(
-    log_file_path: Path, lock_timeout: Optional[float] = None
+    log_file_path: Path,
+    lock_timeout: Optional[float] = None,
+    migrate: bool = True,
+    locked: bool = True,
+    strict: bool = False,
 ) -> List[Dict[str, Any]]:
-    """Reads a JSON Lines log file safely."""
+    """
+    Reads a JSON Lines log file safely.
+    Entries from older schema versions are upgraded in memory unless migrate=False.
+    locked=False skips the file lock: logs are only ever replaced by rename, so
+    an unlocked reader still sees a complete old or new file.
+    Lines that are not valid JSON, or whose schema_version is a boolean, are
+    skipped with a warning, or raise CorruptLogError with strict=True (before
+    rewriting a log, say).
+    """
     entries = []
     if not log_file_path.is_file():
         log.debug(f"Log file does not exist: {log_file_path}")
         return entries
-    lock = FileLock(str(log_file_path))
+    lock = FileLock(str(log_file_path)) if locked else contextlib.nullcontext()
     try:
         with lock:  # Use context manager for acquire/release
             with open(log_file_path, "r", encoding="utf-8") as f:
@@ -334,14 +776,36 @@ def read_log_file(
                         continue
                     try:
                         entry = json.loads(line)
+                        if isinstance(entry, dict):
+                            decode_log_paths(entry)
+                            # Raises for a version that is not one, even unmigrated
+                            default_migrator.get_version(entry)
+                        if migrate and isinstance(entry, dict):
+                            entry = default_migrator.upgrade(entry)
                         entries.append(entry)
                     except json.JSONDecodeError as e:
+                        if strict:
+                            raise CorruptLogError(
+                                f"Invalid JSON on line {i + 1} of {log_file_path}: {e}",
+                                path=str(log_file_path),
+                                line=i + 1,
+                            ) from e
                         log.warning(
                             f"Invalid JSON on line {i + 1} in {log_file_path}: {e}"
                         )
                         log.warning(f"Problematic line: {line[:200]}...")
+                    except SchemaVersionError as e:
+                        if strict:
+                            raise CorruptLogError(
+                                f"{e} (line {i + 1} of {log_file_path})",
+                                path=str(log_file_path),
+                                line=i + 1,
+                            ) from e
+                        log.warning(f"Skipping line {i + 1} in {log_file_path}: {e}")
         log.debug(f"Successfully read {len(entries)} entries from {log_file_path}")
         return entries
+    except HistoryError:
+        raise
     except (IOError, TimeoutError) as e:
         log.error(f"Error reading log file {log_file_path}: {e}")
         raise HistoryError(f"Could not read log file: {log_file_path}") from e
@@ -350,6 +814,70 @@ def read_log_file(
         raise HistoryError(f"Unexpected error reading log file: {log_file_path}") from e
 
 
+# --- Write-Ahead Log ---
+# Every log write goes through the log's WAL (see mcp_history_format), which
+# `mcpdiff crash-recover` replays when a crash left it behind.
+
+
+def serialize_log_entries(entries: List[Dict[str, Any]]) -> bytes:
+    """The JSON Lines content of a log holding `entries`."""
+    return b"".join(
+        json.dumps(encode_log_paths(entry), separators=(",", ":")).encode("utf-8")
+        + b"\n"
+        for entry in entries
+    )
+
+
+def _commit_log(log_file_path: Path, data: bytes, temp_path: Path) -> None:
+    """
+    Replaces the log with `data`, which temp_path holds, through its WAL; the
+    caller holds the log's lock.
+    """
+    wal_path = write_wal(log_file_path, data)
+    try:
+        os.replace(temp_path, log_file_path)
+    except OSError:
+        # The log keeps its content, as the caller is told
+        wal_path.unlink(missing_ok=True)
+        raise
+    fsync_directory(log_file_path.parent)
+    wal_path.unlink()
+
+
+def find_wal_files(history_root: Path) -> List[Path]:
+    """The WALs in logs/, left behind by log writes a crash interrupted."""
+    logs_dir = history_root / LOGS_DIR
+    return find_log_files(logs_dir, WAL_SUFFIX)
+
+
+def recover_wal(wal_path: Path, lock_timeout: Optional[float] = None) -> str:
+    """
+    Deals with a WAL a crash left behind, under the locks of its conversation
+    and log, so not while the server or mcpdiff write it. Returns what was
+    done, as resolve_wal() in mcp_history_format tells.
+    """
+    log_file_path = history_format.get_wal_log_file(wal_path)
+    conv_lock = ConversationLock(
+        get_log_history_root(log_file_path), get_log_conversation_id(log_file_path)
+    )
+    conv_lock.acquire(lock_timeout)
+    lock = FileLock(str(log_file_path))
+    try:
+        lock.acquire(lock_timeout)
+    except BaseException:
+        conv_lock.release()
+        raise
+    try:
+        outcome = history_format.resolve_wal(wal_path, log_file_path)
+    finally:
+        lock.release()
+        conv_lock.release()
+    log.info(f"WAL {wal_path.name} of {log_file_path.name}: {outcome}")
+    if outcome == "replayed":
+        refresh_merkle_sidecars([log_file_path], lock_timeout)
+    return outcome
+
+
 def write_log_file(
     log_file_path: Path,
     entries: List[Dict[str, Any]],
@@ -367,14 +895,11 @@ def write_log_file(
     )
     lock = FileLock(str(log_file_path))
     try:
-        with lock:  # Use context manager
+        with lock, metrics.METRICS.time_log_write():
             log_file_path.parent.mkdir(parents=True, exist_ok=True)
-            with open(temp_path, "w", encoding="utf-8") as f:
-                for entry in entries:
-                    json.dump(entry, f, separators=(",", ":"))
-                    f.write("\n")
-            # Atomic rename/replace
-            os.replace(temp_path, log_file_path)
+            data = serialize_log_entries(entries)
+            write_synced(temp_path, data)
+            _commit_log(log_file_path, data, temp_path)
             log.debug(
                 f"Successfully wrote {len(entries)} entries to log file: {log_file_path}"
             )
@@ -385,6 +910,8 @@ def write_log_file(
                 os.remove(temp_path)
             except OSError:
                 pass
+        if isinstance(e, LockTimeoutError):
+            raise
         raise HistoryError(f"Could not write log file: {log_file_path}") from e
     except Exception as e:
         if temp_path.exists():
@@ -394,6 +921,237 @@ def write_log_file(
                 pass
         log.exception(f"Unexpected error writing log file {log_file_path}: {e}")
         raise HistoryError(f"Unexpected error writing log file: {log_file_path}") from e
+    # Outside the log lock: recomputing the root reads the log again
+    refresh_merkle_sidecars([log_file_path], lock_timeout)
+
+
+def write_log_files(
+    updates: Dict[Path, List[Dict[str, Any]]],
+    lock_timeout: Optional[float] = None,
+) -> Tuple[List[Path], Optional[str]]:
+    """
+    Writes several log files: every file is first written to a temp file, and
+    only when all of them succeeded are they renamed into place, each through
+    its WAL, in the order of `updates`. A failure while staging leaves every
+    log untouched; one while renaming leaves the logs before it replaced and
+    the others untouched. This is not one unit, so callers order `updates` such
+    that the logs are consistent after any of them.
+    Returns the logs that were replaced and the error that stopped the write, if any.
+    """
+    staged: List[Tuple[Path, Path, bytes]] = []
+
+    def discard_staged() -> None:
+        for _, temp_path, _ in staged:
+            try:
+                os.remove(temp_path)
+            except OSError:
+                pass
+
+    # 1. Stage
+    for log_file_path, entries in updates.items():
+        entries.sort(
+            key=lambda x: (
+                x.get("timestamp", 0),
+                x.get("tool_call_index", float("inf")),
+            )
+        )
+        temp_path = log_file_path.with_suffix(
+            log_file_path.suffix + ".tmp" + str(os.getpid())
+        )
+        data = b""
+        try:
+            data = serialize_log_entries(entries)
+            write_synced(temp_path, data)
+            staged.append((log_file_path, temp_path, data))
+        except (IOError, TypeError, ValueError) as e:
+            log.error(f"Error staging log file {log_file_path}: {e}")
+            staged.append((log_file_path, temp_path, data))  # Remove partial temp too
+            discard_staged()
+            return [], f"could not write {log_file_path.name}: {e}"
+
+    # 2. Commit
+    committed: List[Path] = []
+    for index, (log_file_path, temp_path, data) in enumerate(staged):
+        try:
+            lock = FileLock(str(log_file_path))
+            lock.acquire(lock_timeout)
+            try:
+                with metrics.METRICS.time_log_write():
+                    _commit_log(log_file_path, data, temp_path)
+            finally:
+                lock.release()
+            committed.append(log_file_path)
+        except (IOError, TimeoutError) as e:
+            log.error(f"Error replacing log file {log_file_path}: {e}")
+            staged = staged[index:]
+            discard_staged()
+            refresh_merkle_sidecars(committed, lock_timeout)
+            return committed, f"could not replace {log_file_path.name}: {e}"
+    log.debug(f"Wrote {len(committed)} log files: {[p.name for p in committed]}")
+    refresh_merkle_sidecars(committed, lock_timeout)
+    return committed, None
+
+
+def get_log_history_root(log_file_path: Path) -> Path:
+    """The history root of a log in logs/ or logs/<namespace>/."""
+    logs_dir = log_file_path.parent
+    if logs_dir.parent.name == LOGS_DIR:
+        logs_dir = logs_dir.parent
+    return logs_dir.parent
+
+
+def get_log_file_source(log_file_path: Path) -> str:
+    """The log_file_source of entries read from a log: its path below logs/."""
+    if log_file_path.parent.parent.name == LOGS_DIR:
+        return f"{log_file_path.parent.name}/{log_file_path.name}"
+    return log_file_path.name
+
+
+def find_log_files(logs_dir: Path, suffix: str = ".log") -> List[Path]:
+    """
+    The log files in logs_dir (or other files ending in `suffix`), including
+    those of namespaced conversations in logs/<namespace>/.
+    """
+    if not logs_dir.is_dir():
+        return []
+    return sorted(logs_dir.glob(f"*{suffix}")) + sorted(
+        p
+        for p in logs_dir.glob(f"*/*{suffix}")
+        if NAMESPACE_PATTERN.match(p.parent.name)
+    )
+
+
+def get_conversation_namespace(conv_id: str) -> str:
+    """The namespace of a conversation ID; "default" for IDs without one."""
+    namespace, sep, _ = conv_id.partition("/")
+    return namespace if sep else DEFAULT_NAMESPACE
+
+
+def is_valid_conversation_id(conv_id: str) -> bool:
+    """
+    True for conversation IDs that are safe as file names: no path separators,
+    except the one after a namespace.
+    """
+    namespace, sep, rest = conv_id.partition("/")
+    if sep and not NAMESPACE_PATTERN.match(namespace):
+        return False
+    name = rest if sep else conv_id
+    if name in ("", ".", ".."):
+        return False
+    return "/" not in name and "\\" not in name
+
+
+def qualify_conversation_ref(conv_ref: str, namespace: Optional[str]) -> str:
+    """
+    A conversation reference (-c) as the ID to look for in the configured
+    namespace: a bare ID or prefix gets the <namespace>/ prefix, one that
+    already names a namespace is used as is.
+    """
+    if namespace in (None, DEFAULT_NAMESPACE) or "/" in conv_ref:
+        return conv_ref
+    return f"{namespace}/{conv_ref}"
+
+
+def read_all_shards(
+    history_root: Path, conv_id: str, lock_timeout: Optional[float] = None
+) -> List[Dict[str, Any]]:
+    """Reads all log files (shards) of a conversation in chronological order."""
+    entries: List[Dict[str, Any]] = []
+    for log_file_path in get_conversation_log_files(history_root, conv_id):
+        shard_entries = read_log_file(log_file_path, lock_timeout=lock_timeout)
+        for entry in shard_entries:
+            entry["log_file_source"] = get_log_file_source(log_file_path)
+        entries.extend(shard_entries)
+    return entries
+
+
+def merkle_sidecar_path(history_root: Path, conv_id: str) -> Path:
+    return history_root / LOGS_DIR / f"{conv_id}{history_format.MERKLE_SUFFIX}"
+
+
+def compute_merkle_root(
+    history_root: Path,
+    conv_id: str,
+    algorithm: Optional[str] = None,
+    lock_timeout: Optional[float] = None,
+) -> Dict[str, Any]:
+    """
+    Computes the Merkle root over a conversation's entries as stored, across
+    all shards. Returns {algorithm, root, leaves, peaks}.
+    """
+    algorithm = algorithm or history_format.default_algorithm()
+    entries = []
+    for log_file_path in get_conversation_log_files(history_root, conv_id):
+        for entry in read_log_file(log_file_path, lock_timeout, migrate=False):
+            entries.append(encode_log_paths(entry))
+    peaks = history_format.merkle_peaks(entries, algorithm)
+    return history_format.merkle_sidecar_data(algorithm, peaks, len(entries))
+
+
+def read_merkle_sidecar(history_root: Path, conv_id: str) -> Optional[Dict[str, Any]]:
+    """The recorded root of a conversation, or None if there is no valid sidecar."""
+    sidecar = merkle_sidecar_path(history_root, conv_id)
+    if not sidecar.is_file():
+        return None
+    try:
+        with open(sidecar, "r", encoding="utf-8") as f:
+            data = json.load(f)
+        if isinstance(data, dict) and data.get("root") and data.get("algorithm"):
+            return data
+        log.warning(f"Ignoring invalid Merkle sidecar {sidecar}")
+    except (IOError, json.JSONDecodeError) as e:
+        log.warning(f"Ignoring unreadable Merkle sidecar {sidecar}: {e}")
+    return None
+
+
+def update_merkle_sidecar(
+    history_root: Path, conv_id: str, lock_timeout: Optional[float] = None
+) -> Dict[str, Any]:
+    """Recomputes a conversation's Merkle root and writes it to its sidecar."""
+    sidecar = merkle_sidecar_path(history_root, conv_id)
+    temp_path = sidecar.with_suffix(sidecar.suffix + ".tmp" + str(os.getpid()))
+    try:
+        with FileLock(str(sidecar)):
+            result = compute_merkle_root(
+                history_root, conv_id, lock_timeout=lock_timeout
+            )
+            result["updated"] = (
+                datetime.now(timezone.utc)
+                .isoformat(timespec="milliseconds")
+                .replace("+00:00", "Z")
+            )
+            with open(temp_path, "w", encoding="utf-8") as f:
+                json.dump(result, f, indent=2, sort_keys=True)
+                f.write("\n")
+            os.replace(temp_path, sidecar)
+        log.debug(f"Updated Merkle root of {conv_id}: {result['root']}")
+        return result
+    except (IOError, TimeoutError) as e:
+        if temp_path.exists():
+            try:
+                os.remove(temp_path)
+            except OSError:
+                pass
+        raise HistoryError(f"Could not write Merkle sidecar: {sidecar}") from e
+
+
+def refresh_merkle_sidecars(
+    log_file_paths: List[Path], lock_timeout: Optional[float] = None
+) -> None:
+    """
+    Updates the Merkle sidecars of the conversations the given log files belong
+    to. A failure only warns: the log write itself has already succeeded, and
+    `mcpdiff verify` reports the stale root.
+    """
+    conversations = {}
+    for log_file_path in log_file_paths:
+        history_root = get_log_history_root(log_file_path)
+        conversations[(history_root, get_log_conversation_id(log_file_path))] = None
+    for history_root, conv_id in conversations:
+        try:
+            update_merkle_sidecar(history_root, conv_id, lock_timeout)
+        except (HistoryError, ValueError) as e:
+            log.warning(f"Merkle root of {conv_id} not updated: {e}")
 
 
 def parse_timestamp(timestamp: Union[float, str]) -> float:
@@ -510,6 +1268,52 @@ def parse_time_filter(time_str: str) -> Optional[int]:
     return seconds
 
 
+def parse_time_point(time_str: str, now: Optional[float] = None) -> Optional[float]:
+    """
+    Parse a point in time into epoch seconds: an ISO 8601 date or time (UTC
+    unless it has an offset), or a duration like 2h or 3d1h meaning that long
+    before `now`. Returns None if it is neither.
+    """
+    try:
+        when = datetime.fromisoformat(time_str.strip().replace("Z", "+00:00"))
+        if when.tzinfo is None:
+            when = when.replace(tzinfo=timezone.utc)
+        return when.timestamp()
+    except ValueError:
+        if not re.fullmatch(r"(\s*\d+\s*[smhd])+\s*", time_str):
+            return None
+        seconds = parse_time_filter(time_str)
+        if seconds is None:
+            return None
+        return (time.time() if now is None else now) - seconds
+
+
+def format_size(num_bytes: int) -> str:
+    """Format a byte count for display (e.g., '1.5 KiB')."""
+    size = float(num_bytes)
+    for unit in ["B", "KiB", "MiB", "GiB"]:
+        if size < 1024 or unit == "GiB":
+            return f"{int(size)} {unit}" if unit == "B" else f"{size:.1f} {unit}"
+        size /= 1024
+    return f"{num_bytes} B"  # Unreachable
+
+
+def hex_dump(data: bytes, offset: int = 0, limit: int = 4096) -> str:
+    """
+    Lines of 16 bytes as `offset  hex  |ascii|`, numbered from `offset`, for at
+    most `limit` bytes; a last line counts the bytes left out.
+    """
+    lines = []
+    for pos in range(0, min(len(data), limit), 16):
+        chunk = data[pos : pos + 16]
+        hex_bytes = " ".join(f"{b:02x}" for b in chunk)
+        text = "".join(chr(b) if 32 <= b < 127 else "." for b in chunk)
+        lines.append(f"{offset + pos:08x}  {hex_bytes:<47}  |{text}|")
+    if len(data) > limit:
+        lines.append(f"... ({len(data) - limit} more bytes)")
+    return "\n".join(lines) + "\n" if lines else ""
+
+
 def generate_hex_timestamp() -> str:
     """Generate a timestamp as hexadecimal representation of the current Unix epoch time."""
     # Using UUID based on time ensures more uniqueness than just epoch hex
@@ -520,22 +1324,115 @@ def generate_hex_timestamp() -> str:
     return format(epoch_time_ns, "x")  # Hex of nanoseconds since epoch
 
 
-def print_diff_with_color(diff_content: Optional[str]) -> None:
-    """Print a diff with color highlighting."""
+def list_syntax_languages() -> Optional[List[Tuple[str, List[str]]]]:
+    """
+    (name, aliases) of each language Pygments can highlight, by name; any alias
+    can be given to show --language. None if Pygments is not installed.
+    """
+    try:
+        from pygments.lexers import get_all_lexers
+    except ImportError:
+        return None
+    languages = [
+        (name, list(aliases)) for name, aliases, _, _ in get_all_lexers() if aliases
+    ]
+    return sorted(languages, key=lambda language: language[0].lower())
+
+
+def check_syntax_language(language: str) -> None:
+    """
+    Raises a HistoryError if Pygments has no lexer with the alias `language`.
+    Without Pygments nothing is highlighted, so any name is accepted.
+    """
+    try:
+        from pygments.lexers import get_lexer_by_name
+        from pygments.util import ClassNotFound
+    except ImportError:
+        return
+    try:
+        get_lexer_by_name(language)
+    except ClassNotFound:
+        raise HistoryError(
+            f"Unknown language '{language}'. List the languages with: mcpdiff show --list-languages"
+        )
+
+
+def get_syntax_highlighter(
+    file_path: Optional[str],
+    theme: Optional[str] = None,
+    language: Optional[str] = None,
+    color: bool = True,
+) -> Optional[Callable[[str], str]]:
+    """
+    Returns a function that highlights a single line of source code for the terminal,
+    based on the file extension, or on `language`, a Pygments lexer alias, if
+    given. Returns None if Pygments is not installed, `color` is off, or no
+    lexer matches the file.
+    """
+    if not (file_path or language) or not color:
+        return None
+    try:
+        from pygments import highlight
+        from pygments.lexers import get_lexer_by_name, get_lexer_for_filename
+        from pygments.formatters import Terminal256Formatter
+        from pygments.util import ClassNotFound
+    except ImportError:
+        log.debug("Pygments not installed, skipping syntax highlighting.")
+        return None
+    try:
+        if language:
+            lexer = get_lexer_by_name(language, stripnl=False, ensurenl=False)
+        else:
+            lexer = get_lexer_for_filename(file_path, stripnl=False, ensurenl=False)
+        formatter = Terminal256Formatter(style=theme or "monokai")
+    except ClassNotFound as e:
+        log.debug(f"No syntax highlighting for {language or file_path}: {e}")
+        return None
+
+    def highlight_line(line: str) -> str:
+        return highlight(line, lexer, formatter).rstrip("\n")
+
+    return highlight_line
+
+
+def print_diff_with_color(
+    diff_content: Optional[str],
+    file_path: Optional[str] = None,
+    theme: Optional[str] = None,
+    language: Optional[str] = None,
+    color: bool = True,
+) -> None:
+    """
+    Print a diff with color highlighting.
+    If file_path (or language, which overrides its extension) is given and
+    Pygments is available, line content is also syntax-highlighted; the +/-
+    markers keep the diff colors. Nothing is colored if `color` is off.
+    """
+    colors = get_colors(color)
     if not diff_content:
-        print(f"{COLOR_YELLOW}No diff content available.{COLOR_RESET}")
+        print(f"{colors.yellow}No diff content available.{colors.reset}")
         return
 
+    highlight_line = get_syntax_highlighter(file_path, theme, language, color)
+
     for line in diff_content.splitlines():
         if line.startswith("+") and not line.startswith("+++"):
-            print(f"{COLOR_GREEN}{line}{COLOR_RESET}")
+            if highlight_line:
+                print(f"{colors.green}+{colors.reset}{highlight_line(line[1:])}")
+            else:
+                print(f"{colors.green}{line}{colors.reset}")
         elif line.startswith("-") and not line.startswith("---"):
-            print(f"{COLOR_RED}{line}{COLOR_RESET}")
+            if highlight_line:
+                print(f"{colors.red}-{colors.reset}{highlight_line(line[1:])}")
+            else:
+                print(f"{colors.red}{line}{colors.reset}")
         elif line.startswith("@@"):
-            print(f"{COLOR_CYAN}{line}{COLOR_RESET}")
+            print(f"{colors.cyan}{line}{colors.reset}")
         elif line.startswith(
             ("diff ", "--- ", "+++ ", "index ")
         ):  # Include index lines
-            print(f"{COLOR_BLUE}{line}{COLOR_RESET}")
+            print(f"{colors.blue}{line}{colors.reset}")
+        elif highlight_line and line.startswith(" "):
+            print(f" {highlight_line(line[1:])}")
         else:
             print(line)
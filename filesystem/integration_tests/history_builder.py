@@ -0,0 +1,460 @@
+#!/usr/bin/env python3
+"""
+Test support: builds edit histories by changing real files in a workspace and
+recording each change the way the server's track_edit_history decorator does
+(src/filesystem.py), so tests need not write logs, diffs and checkpoints by hand.
+
+    builder = HistoryBuilder(workspace)
+    builder.write("a.txt", "one\\n")            # create
+    edit_id = builder.edit("a.txt", "two\\n")   # edit_file_diff
+    builder.conversation("conv2").move("a.txt", "b.txt")
+
+It also has what tests that write logs by hand or run the CLI share:
+run_mcpdiff(), make_diff() and make_entry().
+"""
+
+import difflib
+import os
+import re
+import shutil
+import subprocess
+import sys
+import uuid
+from datetime import datetime, timedelta, timezone
+from pathlib import Path
+from typing import Any, Dict, List, Optional, Tuple, Union
+
+# The CLI modules import each other by bare name; the server's bsdiff generator
+# needs nothing of the server's dependencies
+sys.path.insert(0, str(Path(__file__).parent.parent / "cli"))
+sys.path.insert(0, str(Path(__file__).parent.parent / "src"))
+
+import mcpdiff_append as append
+import mcpdiff_config as config
+import mcpdiff_history as history
+import mcpdiff_ignore as ignore
+import mcpdiff_patch as patch
+import mcpdiff_utils as utils
+from mcp_bsdiff import generate_binary_patch
+
+LOG_SCHEMA_VERSION = 4  # As written by the server (src/mcp_edit_utils.py)
+PARTIAL_EDIT_MIN_SIZE = 1024 * 1024  # Likewise
+MCPDIFF = Path(__file__).parent.parent / "cli" / "mcpdiff.py"
+
+
+def generate_diff(before: List[str], after: List[str], path: str) -> str:
+    """Unified diff as the server's generate_diff() writes it."""
+
+    def ensure_nl(lines: List[str]) -> List[str]:
+        return [line if line.endswith("\n") else line + "\n" for line in lines]
+
+    return "".join(
+        difflib.unified_diff(
+            ensure_nl(before),
+            ensure_nl(after),
+            fromfile=f"a/{path}",
+            tofile=f"b/{path}",
+            lineterm="\n",
+        )
+    )
+
+
+def sanitize_path_for_filename(relative_path: str) -> str:
+    """Checkpoint file name of a path, as the server's sanitize_path_for_filename()."""
+    sanitized = relative_path.replace(os.sep, "_").replace(":", "_")
+    sanitized = sanitized.replace("\\", "_").replace("/", "_")
+    return re.sub(r"[^\w\-_\.]", "_", sanitized)
+
+
+def is_binary(data: bytes) -> bool:
+    """As the server's is_binary_content(): has NUL bytes."""
+    return b"\x00" in data
+
+
+def changed_byte_range(before: bytes, after: bytes) -> Tuple[int, int, bytes]:
+    """As the server's changed_byte_range(): the range that changed, new bytes."""
+    start = 0
+    limit = min(len(before), len(after))
+    while start < limit and before[start] == after[start]:
+        start += 1
+    suffix = 0
+    while suffix < limit - start and before[-1 - suffix] == after[-1 - suffix]:
+        suffix += 1
+    return start, len(before) - suffix, after[start : len(after) - suffix]
+
+
+def diff_encoding(diff_content: str) -> Optional[str]:
+    """As the server's encode_diff(): base64 if the diff is not valid UTF-8."""
+    try:
+        diff_content.encode("utf-8")
+    except UnicodeEncodeError:  # Undecodable bytes, read as surrogate escapes
+        return patch.BASE64_ENCODING
+    return None
+
+
+class HistoryBuilder:
+    """
+    Applies create/replace/edit/append/move/delete operations to files under
+    `workspace` and logs them as pending edits of the current conversation.
+    Each operation returns its edit ID. Timestamps start at `start` and advance
+    one second per operation, so the order of entries is deterministic.
+    """
+
+    def __init__(
+        self,
+        workspace: Path,
+        conversation_id: str = "conv1",
+        start: datetime = datetime(2025, 1, 1, tzinfo=timezone.utc),
+    ):
+        self.workspace = Path(workspace)
+        self.history_root = self.workspace / ".mcp" / utils.HISTORY_DIR_NAME
+        self.conversation_id = conversation_id
+        self.session_id: Optional[str] = None
+        # Edits of files this large are logged as partial edits; tests lower it
+        self.partial_min_size = PARTIAL_EDIT_MIN_SIZE
+        # conversation_id -> (parent_conversation_id, forked_at)
+        self._forks: Dict[str, Tuple[str, Optional[str]]] = {}
+        self._now = start
+        self._tool_call_indexes: Dict[str, int] = {}
+        (self.history_root / utils.LOGS_DIR).mkdir(parents=True, exist_ok=True)
+
+    def conversation(self, conversation_id: str) -> "HistoryBuilder":
+        """Logs the following operations in another conversation."""
+        self.conversation_id = conversation_id
+        return self
+
+    def fork(
+        self, conversation_id: str, parent: str, forked_at: Optional[str] = None
+    ) -> "HistoryBuilder":
+        """
+        Logs the following operations in `conversation_id`, recorded as forked
+        from `parent` at its edit `forked_at`.
+        """
+        self._forks[conversation_id] = (parent, forked_at)
+        return self.conversation(conversation_id)
+
+    def session(self, session_id: Optional[str]) -> "HistoryBuilder":
+        """Logs the following operations as made in a shell session (MCP_SESSION_ID)."""
+        self.session_id = session_id
+        return self
+
+    def log_file(self, conversation_id: Optional[str] = None) -> Path:
+        conv_id = conversation_id or self.conversation_id
+        return self.history_root / utils.LOGS_DIR / f"{conv_id}.log"
+
+    def path(self, relative_path: str) -> Path:
+        return self.workspace / relative_path
+
+    def read(self, relative_path: str) -> Optional[str]:
+        """Content of a workspace file, or None if it does not exist."""
+        path = self.path(relative_path)
+        return path.read_text(encoding="utf-8") if path.exists() else None
+
+    def entries(self) -> List[Dict[str, Any]]:
+        """All logged entries, read the way mcpdiff reads them."""
+        return history.find_all_entries(self.history_root)
+
+    def entry(self, edit_id: str) -> Dict[str, Any]:
+        return next(e for e in self.entries() if e.get("edit_id") == edit_id)
+
+    # --- Operations (named after the server's tools) ---
+
+    def write(
+        self, relative_path: str, content: Union[str, bytes], **options: Any
+    ) -> str:
+        """
+        write_file: a create, or a replace if the file exists. As the server
+        does, binary content is logged with a bsdiff patch, and other content
+        that is not valid UTF-8 with a base64-encoded unified diff.
+        """
+        operation = "replace" if self.path(relative_path).exists() else "create"
+
+        def apply() -> None:
+            self.path(relative_path).parent.mkdir(parents=True, exist_ok=True)
+            if isinstance(content, bytes):
+                self.path(relative_path).write_bytes(content)
+            else:
+                self.path(relative_path).write_text(content, encoding="utf-8")
+
+        return self._record(operation, "write_file", relative_path, apply, **options)
+
+    def edit(self, relative_path: str, content: str, **options: Any) -> str:
+        """edit_file_diff: changes an existing file to `content`."""
+        if not self.path(relative_path).exists():
+            raise FileNotFoundError(relative_path)
+
+        def apply() -> None:
+            self.path(relative_path).write_text(content, encoding="utf-8")
+
+        return self._record("edit", "edit_file_diff", relative_path, apply, **options)
+
+    def append(self, relative_path: str, content: str, **options: Any) -> str:
+        """append_to_file: an append, or a create if the file does not exist."""
+        operation = "append" if self.path(relative_path).exists() else "create"
+
+        def apply() -> None:
+            with open(self.path(relative_path), "a", encoding="utf-8") as f:
+                f.write(content)
+
+        return self._record(
+            operation, "append_to_file", relative_path, apply, **options
+        )
+
+    def move(self, source: str, destination: str, **options: Any) -> str:
+        """
+        move_file: renames `source` to `destination`, logged as a "rename"
+        within one directory and as a "move" to another one.
+        """
+
+        def apply() -> None:
+            self.path(destination).parent.mkdir(parents=True, exist_ok=True)
+            shutil.move(str(self.path(source)), str(self.path(destination)))
+
+        same_directory = self.path(source).parent == self.path(destination).parent
+        return self._record(
+            "rename" if same_directory else "move",
+            "move_file",
+            destination,
+            apply,
+            source=source,
+            **options,
+        )
+
+    def delete(self, relative_path: str, **options: Any) -> str:
+        """delete_a_file: removes an existing file."""
+        if not self.path(relative_path).exists():
+            raise FileNotFoundError(relative_path)
+        return self._record(
+            "delete",
+            "delete_a_file",
+            relative_path,
+            self.path(relative_path).unlink,
+            **options,
+        )
+
+    # --- Recording, following track_edit_history step by step ---
+
+    def _record(
+        self,
+        operation: str,
+        tool_name: str,
+        relative_path: str,
+        apply,
+        source: Optional[str] = None,
+        parent_edit_id: Optional[str] = None,
+    ) -> str:
+        conv_id = self.conversation_id
+        tool_call_index = self._tool_call_indexes.get(conv_id, -1) + 1
+        self._tool_call_indexes[conv_id] = tool_call_index
+        # With `namespace` configured, logged as <namespace>/<conv_id>
+        namespace = config.get_config_value(self.workspace, "namespace")
+        if namespace:
+            conv_id = f"{namespace}/{conv_id}"
+        edit_id = str(uuid.uuid4())
+        for name in (utils.DIFFS_DIR, utils.CHECKPOINTS_DIR):
+            (self.history_root / name / conv_id).mkdir(parents=True, exist_ok=True)
+        relative_diff_path = Path(utils.DIFFS_DIR) / conv_id / f"{edit_id}.diff"
+        diff_file_path = self.history_root / relative_diff_path
+
+        # Files listed in .mcp/ignore get no checkpoint and an empty diff
+        ignored = ignore.load_ignore_matcher(self.workspace).matches_entry(
+            {"file_path": relative_path, "source_path": source}
+        )
+
+        # State before the operation
+        path_before = self.path(source or relative_path)
+        path_after = self.path(relative_path)
+        hash_before = None
+        size_before = 0
+        content_before: Optional[List[str]] = []
+        bytes_before = b""
+        if path_before.exists():
+            hash_before = utils.calculate_hash(str(path_before))
+            size_before = path_before.stat().st_size
+            bytes_before = path_before.read_bytes()
+            with open(
+                path_before, "r", encoding="utf-8", errors="surrogateescape"
+            ) as f:
+                content_before = f.readlines()
+
+        # Checkpoint the first time the conversation touches the path
+        path_name = source or relative_path
+        checkpoint_name = sanitize_path_for_filename(path_name) + ".chkpt"
+        relative_checkpoint_path = (
+            Path(utils.CHECKPOINTS_DIR) / conv_id / checkpoint_name
+        )
+        seen_paths = {
+            e["file_path"]
+            for e in utils.read_all_shards(self.history_root, conv_id)
+            if "file_path" in e
+        }
+        checkpoint_created = relative_path not in seen_paths and not ignored
+        if checkpoint_created and path_before.exists():
+            shutil.copy2(path_before, self.history_root / relative_checkpoint_path)
+
+        apply()
+
+        content_after: Optional[List[str]] = None
+        bytes_after = b""
+        hash_after = None
+        if operation != "delete":
+            bytes_after = path_after.read_bytes()
+            with open(
+                path_after, "r", encoding="utf-8", errors="surrogateescape"
+            ) as f:
+                content_after = f.readlines()
+            hash_after = utils.calculate_hash(str(path_after))
+
+        diff_content = ""
+        encoding: Optional[str] = None
+        partial_range: Optional[Tuple[int, int]] = None
+        partial = (
+            not ignored
+            and operation in ("replace", "edit")
+            and size_before >= self.partial_min_size
+        )
+        binary = (
+            not ignored
+            and not partial
+            and operation in ("create", "replace", "edit")
+            and (is_binary(bytes_before) or is_binary(bytes_after))
+        )
+        if ignored:
+            if source is None:
+                diff_file_path.write_bytes(b"")
+        elif partial:
+            start, end, written = changed_byte_range(bytes_before, bytes_after)
+            partial_range = (start, end)
+            diff_file_path.write_bytes(written)
+        elif binary:
+            diff_file_path.write_bytes(generate_binary_patch(bytes_before, bytes_after))
+        elif operation == "append":
+            with open(path_after, "rb") as f:
+                f.seek(size_before)
+                appended = f.read()
+            diff_file_path.write_bytes(appended)
+            diff_content = appended.decode("utf-8", errors="replace")
+        elif content_before is not None and content_after is not None:
+            diff_content = generate_diff(content_before, content_after, relative_path)
+            if diff_content:
+                encoding = diff_encoding(diff_content)
+                diff_file_path.write_bytes(patch.encode_diff(diff_content, encoding))
+        has_diff = (
+            bool(diff_content)
+            or operation == "append"
+            or binary
+            or partial
+            or (ignored and source is None)
+        )
+        if operation in ("edit", "replace") and not has_diff:
+            # Edits and replaces always get a diff file, if an empty one
+            diff_file_path.write_text("", encoding="utf-8")
+            has_diff = True
+
+        timestamp = f"{self._now:%Y-%m-%dT%H%M%S.%f}"[:21] + "Z"
+        self._now += timedelta(seconds=1)
+        entry = {
+            "edit_id": edit_id,
+            "conversation_id": conv_id,
+            "tool_call_index": tool_call_index,
+            "timestamp": timestamp,
+            "operation": "partial" if partial else operation,
+            "file_path": relative_path,
+            "source_path": source,
+            "tool_name": tool_name,
+            "parent_edit_id": parent_edit_id,
+            "status": "pending",
+            "diff_file": str(relative_diff_path) if has_diff else None,
+            "checkpoint_file": str(relative_checkpoint_path)
+            if checkpoint_created
+            else None,
+            "hash_before": hash_before,
+            "hash_after": hash_after,
+            "schema_version": LOG_SCHEMA_VERSION,
+            "workspace_root_hash": utils.compute_workspace_root_hash(self.workspace),
+            "session_id": self.session_id,
+            "parent_conversation_id": self._forks.get(conv_id, (None, None))[0],
+            "forked_at": self._forks.get(conv_id, (None, None))[1],
+            "byte_range_start": partial_range[0] if partial_range else None,
+            "byte_range_end": partial_range[1] if partial_range else None,
+            "byte_size_before": size_before if hash_before is not None else None,
+            "byte_size_after": len(bytes_after) if hash_after is not None else None,
+            "conflict_markers": patch.find_conflict_markers(content_after) or None
+            if content_after and not binary
+            else None,
+        }
+        if ignored:
+            entry["ignored"] = True
+        if binary:
+            entry["file_encoding"] = patch.BSDIFF_ENCODING
+        elif encoding:
+            entry["file_encoding"] = encoding
+        # Through `mcpdiff log-append`'s writer, which checks the entry
+        append.append_log_entries(self.history_root, conv_id, [entry])
+        return edit_id
+
+
+def run_mcpdiff(
+    workspace: Optional[Path],
+    *args: str,
+    input: Optional[str] = None,
+    env: Optional[Dict[str, str]] = None,
+    check: bool = False,
+    cwd: Optional[Path] = None,
+) -> subprocess.CompletedProcess:
+    """
+    Runs the mcpdiff CLI on `workspace` (passed as -w, unless None) with the
+    output captured as text, `input` on stdin for its prompts. With `check`, a
+    non-zero exit fails the test, showing the output.
+    """
+    workspace_args = ["-w", str(workspace)] if workspace is not None else []
+    result = subprocess.run(
+        [sys.executable, str(MCPDIFF), *workspace_args, *args],
+        input=input,
+        capture_output=True,
+        text=True,
+        timeout=60,
+        env=env,
+        cwd=cwd,
+    )
+    if check and result.returncode != 0:
+        raise AssertionError(
+            f"mcpdiff {' '.join(args)} exited with {result.returncode}:\n"
+            f"{result.stdout}{result.stderr}"
+        )
+    return result
+
+
+def make_diff(before: str, after: str, name: str = "file.txt") -> str:
+    """Unified diff of `name` in the format the server records (LF line endings)."""
+    diff = "".join(
+        difflib.unified_diff(
+            before.splitlines(keepends=True),
+            after.splitlines(keepends=True),
+            f"a/{name}",
+            f"b/{name}",
+        )
+    )
+    if after and not after.endswith("\n"):
+        diff += "\n\\ No newline at end of file\n"
+    return diff
+
+
+def make_entry(
+    edit_id: str, conv_id: str = "conv1", index: int = 0, **fields: Any
+) -> Dict[str, Any]:
+    """
+    A log entry written by hand: a pending edit of f.txt, made at second
+    `index` with that tool_call_index, with `fields` added or replaced.
+    """
+    entry = {
+        "edit_id": edit_id,
+        "conversation_id": conv_id,
+        "tool_call_index": index,
+        "timestamp": f"2025-01-01T00:00:{index:02d}Z",
+        "operation": "edit",
+        "file_path": "f.txt",
+        "status": "pending",
+    }
+    entry.update(fields)
+    return entry
@@ -23,12 +23,218 @@ def import_test_classes():
         global \
             TestPathValidation, \
             TestAdvancedPathResolution, \
-            TestRealWorldPathScenarios
+            TestRealWorldPathScenarios, \
+            TestPathNormalization, \
+            TestPatchApplication, \
+            TestPatchFuzz, \
+            TestOmitWhitespaceChanges, \
+            TestWindowsWorkspace, \
+            TestNormalizeLogPath, \
+            TestMixedPathLog, \
+            TestNonUtf8Paths, \
+            TestApplyDiffWithBlame, \
+            TestAppendWithBlame, \
+            TestReplayFile, \
+            TestMerkleRoot, \
+            TestMerkleSidecar, \
+            TestAuditLog, \
+            TestPruneCheckpoints, \
+            TestCheckpointHashes, \
+            TestHistoryApi, \
+            TestLogIndex, \
+            TestDaemonQueries, \
+            TestWithoutDaemon, \
+            TestRejectMiddleEdit, \
+            TestRejectMove, \
+            TestAcceptThenReject, \
+            TestRepeatedAcceptReject, \
+            TestCorruptedLog, \
+            TestRejectWithFuzz, \
+            TestFinalVerification, \
+            TestInterleavedConversations, \
+            TestAcceptRejectByTime, \
+            TestWorkspaceRootHash, \
+            TestDiagnoseStructure, \
+            TestEditRangeFilter, \
+            TestSessionFilter, \
+            TestTimeRangeFilter, \
+            TestStatusCount, \
+            TestErrorKinds, \
+            TestLockTimeout, \
+            TestReconstructErrors, \
+            TestExternalModification, \
+            TestCorruptLogError, \
+            TestJsonError, \
+            TestPruneConversations, \
+            TestIgnoreMatcher, \
+            TestIgnoreFile, \
+            TestLogAppend, \
+            TestFormatLog, \
+            TestExpireOld, \
+            TestAutoExpire, \
+            TestReattach, \
+            TestAnnotate, \
+            TestBsdiffPatch, \
+            TestBinaryEdits, \
+            TestPlanFileReplay, \
+            TestPlanCommand, \
+            TestLocks, \
+            TestSchema, \
+            TestTop, \
+            TestWorkspaceDetect, \
+            TestShowSymbols, \
+            TestCompareConversations, \
+            TestSplit, \
+            TestInfo, \
+            TestConversationForks, \
+            TestPartialEdits, \
+            TestPreviewReject, \
+            TestAclRules, \
+            TestAclCommands, \
+            TestWorkspaceInit, \
+            TestWal, \
+            TestConflictMarkers, \
+            TestConflictCommands, \
+            TestSquashRejected, \
+            TestNamespaceHelpers, \
+            TestNamespacedHistory, \
+            TestSnapshot, \
+            TestGitStage, \
+            TestDiffSince, \
+            TestDiffSinceSnapshot, \
+            TestMetrics, \
+            TestMetricsEndpoint, \
+            TestShowLanguage, \
+            TestSyntaxHighlighter
         from integration_tests.test_path_validation import (
             TestPathValidation,
             TestAdvancedPathResolution,
             TestRealWorldPathScenarios,
         )
+        from integration_tests.test_mcpdiff_portability import (
+            TestPathNormalization,
+            TestPatchApplication,
+            TestPatchFuzz,
+            TestOmitWhitespaceChanges,
+            TestWindowsWorkspace,
+        )
+        from integration_tests.test_mcpdiff_log_paths import (
+            TestNormalizeLogPath,
+            TestMixedPathLog,
+            TestNonUtf8Paths,
+        )
+        from integration_tests.test_mcpdiff_replay import (
+            TestApplyDiffWithBlame,
+            TestAppendWithBlame,
+            TestReplayFile,
+        )
+        from integration_tests.test_mcpdiff_merkle import (
+            TestMerkleRoot,
+            TestMerkleSidecar,
+        )
+        from integration_tests.test_mcpdiff_audit import TestAuditLog
+        from integration_tests.test_mcpdiff_checkpoints import (
+            TestPruneCheckpoints,
+            TestCheckpointHashes,
+        )
+        from integration_tests.test_mcpdiff_api import TestHistoryApi
+        from integration_tests.test_mcpdiff_daemon import (
+            TestLogIndex,
+            TestDaemonQueries,
+            TestWithoutDaemon,
+        )
+        from integration_tests.test_mcpdiff_accept_reject import (
+            TestRejectMiddleEdit,
+            TestRejectMove,
+            TestAcceptThenReject,
+            TestRepeatedAcceptReject,
+            TestCorruptedLog,
+            TestRejectWithFuzz,
+            TestFinalVerification,
+            TestInterleavedConversations,
+            TestAcceptRejectByTime,
+        )
+        from integration_tests.test_mcpdiff_workspace_hash import (
+            TestWorkspaceRootHash,
+            TestDiagnoseStructure,
+        )
+        from integration_tests.test_mcpdiff_status import (
+            TestEditRangeFilter,
+            TestSessionFilter,
+            TestTimeRangeFilter,
+            TestStatusCount,
+        )
+        from integration_tests.test_mcpdiff_errors import (
+            TestErrorKinds,
+            TestLockTimeout,
+            TestReconstructErrors,
+            TestExternalModification,
+            TestCorruptLogError,
+            TestJsonError,
+        )
+        from integration_tests.test_mcpdiff_prune import TestPruneConversations
+        from integration_tests.test_mcpdiff_ignore import (
+            TestIgnoreMatcher,
+            TestIgnoreFile,
+        )
+        from integration_tests.test_mcpdiff_log_append import (
+            TestLogAppend,
+            TestFormatLog,
+        )
+        from integration_tests.test_mcpdiff_expire import (
+            TestExpireOld,
+            TestAutoExpire,
+        )
+        from integration_tests.test_mcpdiff_reattach import TestReattach
+        from integration_tests.test_mcpdiff_annotate import TestAnnotate
+        from integration_tests.test_mcpdiff_binary import (
+            TestBsdiffPatch,
+            TestBinaryEdits,
+        )
+        from integration_tests.test_mcpdiff_plan import (
+            TestPlanFileReplay,
+            TestPlanCommand,
+        )
+        from integration_tests.test_mcpdiff_locks import TestLocks
+        from integration_tests.test_mcpdiff_schema import TestSchema
+        from integration_tests.test_mcpdiff_top import TestTop
+        from integration_tests.test_mcpdiff_workspace import TestWorkspaceDetect
+        from integration_tests.test_mcpdiff_symbols import TestShowSymbols
+        from integration_tests.test_mcpdiff_compare import TestCompareConversations
+        from integration_tests.test_mcpdiff_split import TestSplit
+        from integration_tests.test_mcpdiff_info import TestInfo
+        from integration_tests.test_mcpdiff_forks import TestConversationForks
+        from integration_tests.test_mcpdiff_partial import TestPartialEdits
+        from integration_tests.test_mcpdiff_preview import TestPreviewReject
+        from integration_tests.test_mcpdiff_acl import (
+            TestAclRules,
+            TestAclCommands,
+            TestWorkspaceInit,
+        )
+        from integration_tests.test_mcpdiff_wal import TestWal
+        from integration_tests.test_mcpdiff_conflicts import (
+            TestConflictMarkers,
+            TestConflictCommands,
+        )
+        from integration_tests.test_mcpdiff_squash import TestSquashRejected
+        from integration_tests.test_mcpdiff_namespace import (
+            TestNamespaceHelpers,
+            TestNamespacedHistory,
+        )
+        from integration_tests.test_mcpdiff_snapshot import TestSnapshot
+        from integration_tests.test_mcpdiff_git import TestGitStage
+        from integration_tests.test_mcpdiff_diff_since import (
+            TestDiffSince,
+            TestDiffSinceSnapshot,
+        )
+        from integration_tests.test_mcpdiff_metrics import (
+            TestMetrics,
+            TestMetricsEndpoint,
+        )
+        from integration_tests.test_mcpdiff_show_language import (
+            TestShowLanguage,
+            TestSyntaxHighlighter,
+        )
 
         return True
     except Exception as e:
@@ -82,6 +288,170 @@ def add_tests_to_suite(test_suite):
     print("Adding TestRealWorldPathScenarios to test suite")
     test_suite.addTest(loader.loadTestsFromTestCase(TestRealWorldPathScenarios))
 
+    print("Adding mcpdiff portability tests to test suite")
+    test_suite.addTest(loader.loadTestsFromTestCase(TestPathNormalization))
+    test_suite.addTest(loader.loadTestsFromTestCase(TestPatchApplication))
+    test_suite.addTest(loader.loadTestsFromTestCase(TestPatchFuzz))
+    test_suite.addTest(loader.loadTestsFromTestCase(TestOmitWhitespaceChanges))
+    test_suite.addTest(loader.loadTestsFromTestCase(TestWindowsWorkspace))
+
+    print("Adding mcpdiff log path tests to test suite")
+    test_suite.addTest(loader.loadTestsFromTestCase(TestNormalizeLogPath))
+    test_suite.addTest(loader.loadTestsFromTestCase(TestMixedPathLog))
+    test_suite.addTest(loader.loadTestsFromTestCase(TestNonUtf8Paths))
+
+    print("Adding mcpdiff replay tests to test suite")
+    test_suite.addTest(loader.loadTestsFromTestCase(TestApplyDiffWithBlame))
+    test_suite.addTest(loader.loadTestsFromTestCase(TestAppendWithBlame))
+    test_suite.addTest(loader.loadTestsFromTestCase(TestReplayFile))
+
+    print("Adding mcpdiff Merkle root tests to test suite")
+    test_suite.addTest(loader.loadTestsFromTestCase(TestMerkleRoot))
+    test_suite.addTest(loader.loadTestsFromTestCase(TestMerkleSidecar))
+
+    print("Adding mcpdiff audit log tests to test suite")
+    test_suite.addTest(loader.loadTestsFromTestCase(TestAuditLog))
+
+    print("Adding mcpdiff checkpoint pruning tests to test suite")
+    test_suite.addTest(loader.loadTestsFromTestCase(TestPruneCheckpoints))
+    test_suite.addTest(loader.loadTestsFromTestCase(TestCheckpointHashes))
+
+    print("Adding mcpdiff history API tests to test suite")
+    test_suite.addTest(loader.loadTestsFromTestCase(TestHistoryApi))
+
+    print("Adding mcpdiff daemon tests to test suite")
+    test_suite.addTest(loader.loadTestsFromTestCase(TestLogIndex))
+    test_suite.addTest(loader.loadTestsFromTestCase(TestDaemonQueries))
+    test_suite.addTest(loader.loadTestsFromTestCase(TestWithoutDaemon))
+
+    print("Adding mcpdiff accept/reject tests to test suite")
+    test_suite.addTest(loader.loadTestsFromTestCase(TestRejectMiddleEdit))
+    test_suite.addTest(loader.loadTestsFromTestCase(TestRejectMove))
+    test_suite.addTest(loader.loadTestsFromTestCase(TestAcceptThenReject))
+    test_suite.addTest(loader.loadTestsFromTestCase(TestRepeatedAcceptReject))
+    test_suite.addTest(loader.loadTestsFromTestCase(TestCorruptedLog))
+    test_suite.addTest(loader.loadTestsFromTestCase(TestRejectWithFuzz))
+    test_suite.addTest(loader.loadTestsFromTestCase(TestFinalVerification))
+    test_suite.addTest(loader.loadTestsFromTestCase(TestInterleavedConversations))
+    test_suite.addTest(loader.loadTestsFromTestCase(TestAcceptRejectByTime))
+
+    print("Adding mcpdiff workspace structure hash tests to test suite")
+    test_suite.addTest(loader.loadTestsFromTestCase(TestWorkspaceRootHash))
+    test_suite.addTest(loader.loadTestsFromTestCase(TestDiagnoseStructure))
+
+    print("Adding mcpdiff status filter tests to test suite")
+    test_suite.addTest(loader.loadTestsFromTestCase(TestEditRangeFilter))
+    test_suite.addTest(loader.loadTestsFromTestCase(TestSessionFilter))
+    test_suite.addTest(loader.loadTestsFromTestCase(TestTimeRangeFilter))
+    test_suite.addTest(loader.loadTestsFromTestCase(TestStatusCount))
+
+    print("Adding mcpdiff error kind tests to test suite")
+    test_suite.addTest(loader.loadTestsFromTestCase(TestErrorKinds))
+    test_suite.addTest(loader.loadTestsFromTestCase(TestLockTimeout))
+    test_suite.addTest(loader.loadTestsFromTestCase(TestReconstructErrors))
+    test_suite.addTest(loader.loadTestsFromTestCase(TestExternalModification))
+    test_suite.addTest(loader.loadTestsFromTestCase(TestCorruptLogError))
+    test_suite.addTest(loader.loadTestsFromTestCase(TestJsonError))
+
+    print("Adding mcpdiff prune tests to test suite")
+    test_suite.addTest(loader.loadTestsFromTestCase(TestPruneConversations))
+
+    print("Adding mcpdiff ignore file tests to test suite")
+    test_suite.addTest(loader.loadTestsFromTestCase(TestIgnoreMatcher))
+    test_suite.addTest(loader.loadTestsFromTestCase(TestIgnoreFile))
+
+    print("Adding mcpdiff log-append tests to test suite")
+    test_suite.addTest(loader.loadTestsFromTestCase(TestLogAppend))
+    test_suite.addTest(loader.loadTestsFromTestCase(TestFormatLog))
+
+    print("Adding mcpdiff expire-old tests to test suite")
+    test_suite.addTest(loader.loadTestsFromTestCase(TestExpireOld))
+    test_suite.addTest(loader.loadTestsFromTestCase(TestAutoExpire))
+
+    print("Adding mcpdiff reattach tests to test suite")
+    test_suite.addTest(loader.loadTestsFromTestCase(TestReattach))
+
+    print("Adding mcpdiff annotate tests to test suite")
+    test_suite.addTest(loader.loadTestsFromTestCase(TestAnnotate))
+
+    print("Adding mcpdiff binary file tests to test suite")
+    test_suite.addTest(loader.loadTestsFromTestCase(TestBsdiffPatch))
+    test_suite.addTest(loader.loadTestsFromTestCase(TestBinaryEdits))
+
+    print("Adding Replay plan to test suite")
+    test_suite.addTest(loader.loadTestsFromTestCase(TestPlanFileReplay))
+    test_suite.addTest(loader.loadTestsFromTestCase(TestPlanCommand))
+
+    print("Adding Locks to test suite")
+    test_suite.addTest(loader.loadTestsFromTestCase(TestLocks))
+
+    print("Adding Schema to test suite")
+    test_suite.addTest(loader.loadTestsFromTestCase(TestSchema))
+
+    print("Adding Top to test suite")
+    test_suite.addTest(loader.loadTestsFromTestCase(TestTop))
+
+    print("Adding mcpdiff workspace detect to test suite")
+    test_suite.addTest(loader.loadTestsFromTestCase(TestWorkspaceDetect))
+
+    print("Adding mcpdiff show --symbols to test suite")
+    test_suite.addTest(loader.loadTestsFromTestCase(TestShowSymbols))
+
+    print("Adding mcpdiff compare-convs to test suite")
+    test_suite.addTest(loader.loadTestsFromTestCase(TestCompareConversations))
+
+    print("Adding split tests to test suite")
+    test_suite.addTest(loader.loadTestsFromTestCase(TestSplit))
+
+    print("Adding info tests to test suite")
+    test_suite.addTest(loader.loadTestsFromTestCase(TestInfo))
+
+    print("Adding mcpdiff fork tests to test suite")
+    test_suite.addTest(loader.loadTestsFromTestCase(TestConversationForks))
+
+    print("Adding partial edit tests to test suite")
+    test_suite.addTest(loader.loadTestsFromTestCase(TestPartialEdits))
+
+    print("Adding preview tests to test suite")
+    test_suite.addTest(loader.loadTestsFromTestCase(TestPreviewReject))
+
+    print("Adding ACL tests to test suite")
+    test_suite.addTest(loader.loadTestsFromTestCase(TestAclRules))
+    test_suite.addTest(loader.loadTestsFromTestCase(TestAclCommands))
+    test_suite.addTest(loader.loadTestsFromTestCase(TestWorkspaceInit))
+
+    print("Adding WAL to test suite")
+    test_suite.addTest(loader.loadTestsFromTestCase(TestWal))
+
+    print("Adding conflict marker tests to test suite")
+    test_suite.addTest(loader.loadTestsFromTestCase(TestConflictMarkers))
+    test_suite.addTest(loader.loadTestsFromTestCase(TestConflictCommands))
+
+    print("Adding mcpdiff squash-rejected tests to test suite")
+    test_suite.addTest(loader.loadTestsFromTestCase(TestSquashRejected))
+
+    print("Adding mcpdiff namespace tests to test suite")
+    test_suite.addTest(loader.loadTestsFromTestCase(TestNamespaceHelpers))
+    test_suite.addTest(loader.loadTestsFromTestCase(TestNamespacedHistory))
+
+    print("Adding Snapshot tests to test suite")
+    test_suite.addTest(loader.loadTestsFromTestCase(TestSnapshot))
+
+    print("Adding mcpdiff git staging tests to test suite")
+    test_suite.addTest(loader.loadTestsFromTestCase(TestGitStage))
+
+    print("Adding mcpdiff diff-since tests to test suite")
+    test_suite.addTest(loader.loadTestsFromTestCase(TestDiffSince))
+    test_suite.addTest(loader.loadTestsFromTestCase(TestDiffSinceSnapshot))
+
+    print("Adding metrics tests to test suite")
+    test_suite.addTest(loader.loadTestsFromTestCase(TestMetrics))
+    test_suite.addTest(loader.loadTestsFromTestCase(TestMetricsEndpoint))
+
+    print("Adding mcpdiff show --language to test suite")
+    test_suite.addTest(loader.loadTestsFromTestCase(TestShowLanguage))
+    test_suite.addTest(loader.loadTestsFromTestCase(TestSyntaxHighlighter))
+
 
 if __name__ == "__main__":
     # In script mode, set up any environment
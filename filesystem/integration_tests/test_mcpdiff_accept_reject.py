@@ -0,0 +1,481 @@
+#!/usr/bin/env python3
+"""
+End-to-end tests for `mcpdiff accept` and `mcpdiff reject`, run as a subprocess
+against histories recorded by HistoryBuilder:
+- Rejecting an edit in the middle of a file's history keeps the others;
+  --verbose prints each edit re-applied, with a ✓ or ✗
+- Rejecting a move puts the file back at its source; renames within a
+  directory are logged apart from moves to another one
+- An accepted edit can still be rejected
+- Accepting or rejecting edits again changes nothing and still succeeds
+- Unreadable log lines do not keep the rest of the history from working
+- --fuzz and --ignore-whitespace let diffs with stale context re-apply
+- A re-applied file that does not match the recorded hash is not written,
+  unless --no-verify
+- Rejecting one conversation keeps the edits another conversation made to the
+  same file in between
+- --before/--after accept or reject the edits of a conversation logged in a
+  time range, asking first when there are many
+"""
+
+import contextlib
+import functools
+import io
+import re
+import shutil
+import subprocess
+import sys
+import tempfile
+import unittest
+from unittest import mock
+from pathlib import Path
+
+# The CLI modules import each other by bare name; history_builder is found also
+# when run_tests.py imports this module as part of the package
+sys.path.insert(0, str(Path(__file__).parent.parent / "cli"))
+sys.path.insert(0, str(Path(__file__).parent))
+
+import mcpdiff_api as api
+import mcpdiff_history as history
+import mcpdiff_utils as utils
+from history_builder import HistoryBuilder, run_mcpdiff
+
+LINES = [f"line {i}\n" for i in range(1, 21)]
+
+
+class AcceptRejectTestCase(unittest.TestCase):
+    def setUp(self):
+        self.workspace = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        self.builder = HistoryBuilder(self.workspace)
+
+    def tearDown(self):
+        shutil.rmtree(self.workspace, ignore_errors=True)
+
+    def mcpdiff(
+        self, *args: str, confirm: bool = True
+    ) -> subprocess.CompletedProcess:
+        """Runs mcpdiff, answering any prompt about changed files with `confirm`."""
+        return run_mcpdiff(
+            self.workspace, *args, input="y\n" if confirm else "n\n", check=True
+        )
+
+    def status(self, edit_id: str) -> str:
+        return self.builder.entry(edit_id)["status"]
+
+    def edit_lines(self, path: str, changes: dict) -> list:
+        """Creates `path` with LINES, then edits one line per change."""
+        lines = list(LINES)
+        self.builder.write(path, "".join(lines))
+        edit_ids = []
+        for index, text in changes.items():
+            lines[index] = text
+            edit_ids.append(self.builder.edit(path, "".join(lines)))
+        return edit_ids
+
+
+class TestRejectMiddleEdit(AcceptRejectTestCase):
+    def test_other_edits_stay(self):
+        first, middle, last = self.edit_lines(
+            "a.txt", {1: "two\n", 9: "ten\n", 17: "eighteen\n"}
+        )
+        self.mcpdiff("reject", "-e", middle)
+
+        expected = list(LINES)
+        expected[1], expected[17] = "two\n", "eighteen\n"
+        self.assertEqual(self.builder.read("a.txt"), "".join(expected))
+        self.assertEqual(self.status(middle), "rejected")
+        self.assertEqual(self.status(first), "pending")
+        self.assertEqual(self.status(last), "pending")
+
+    def test_verbose(self):
+        first, middle, last = self.edit_lines(
+            "a.txt", {1: "two\n", 9: "ten\n", 17: "eighteen\n"}
+        )
+        create = self.builder.entries()[0]["edit_id"]
+        result = self.mcpdiff("reject", "-e", middle, "--verbose")
+        output = re.sub(r"\x1b\[\d+m", "", result.stdout)
+        self.assertEqual(
+            re.findall(r"  ([✓✗]) (\S+) (\w+) a.txt\n", output),
+            [("✓", create, "create"), ("✓", first, "edit"), ("✓", last, "edit")],
+        )
+
+    def test_verbose_is_per_replay(self):
+        self.edit_lines("a.txt", {1: "two\n"})
+
+        def reconstruct(options=None) -> str:
+            out = io.StringIO()
+            with contextlib.redirect_stdout(out):
+                history.reconstruct_file_from_history(
+                    "a.txt",
+                    self.builder.entries(),
+                    self.workspace,
+                    self.builder.history_root,
+                    options=options,
+                )
+            return out.getvalue()
+
+        self.assertIn("✓", reconstruct(history.ReplayOptions(show_reapplied=True)))
+        # Nothing left switched on for the next re-apply in the process
+        self.assertNotIn("✓", reconstruct())
+
+
+class TestRejectMove(AcceptRejectTestCase):
+    def test_file_returns_to_source(self):
+        self.builder.write("m.txt", "moved\n")
+        move_id = self.builder.move("m.txt", "sub/n.txt")
+        self.mcpdiff("reject", "-e", move_id)
+
+        self.assertEqual(self.builder.read("m.txt"), "moved\n")
+        self.assertIsNone(self.builder.read("sub/n.txt"))
+        self.assertEqual(self.status(move_id), "rejected")
+
+    def test_edits_before_the_move_stay(self):
+        self.builder.write("m.txt", "one\n")
+        self.builder.edit("m.txt", "two\n")
+        move_id = self.builder.move("m.txt", "n.txt")
+        self.mcpdiff("reject", "-e", move_id)
+        self.assertEqual(self.builder.read("m.txt"), "two\n")
+        self.assertIsNone(self.builder.read("n.txt"))
+
+    def test_rename_and_move_are_logged_apart(self):
+        self.builder.write("m.txt", "one\n")
+        rename_id = self.builder.move("m.txt", "n.txt")
+        move_id = self.builder.move("n.txt", "sub/n.txt")
+        self.assertEqual(self.builder.entry(rename_id)["operation"], "rename")
+        self.assertEqual(self.builder.entry(move_id)["operation"], "move")
+        # Without colors
+        status = re.sub(r"\x1b\[\d+m", "", self.mcpdiff("status").stdout)
+        self.assertIn("rename     pending   m.txt -> n.txt", status)
+        self.assertIn("move       pending   n.txt -> sub/n.txt", status)
+        self.assertIn("OPERATION: RENAME", self.mcpdiff("show", rename_id).stdout)
+
+        self.mcpdiff("reject", "-e", move_id)
+        self.assertEqual(self.builder.read("n.txt"), "one\n")
+        self.assertIsNone(self.builder.read("sub/n.txt"))
+
+    def test_apply_and_revert(self):
+        self.builder.write("m.txt", "one\n")
+        rename = self.builder.entry(self.builder.move("m.txt", "n.txt"))
+        move = self.builder.entry(self.builder.move("n.txt", "a/b/n.txt"))
+        root = self.builder.history_root
+        self.assertTrue(history.apply_or_revert_edit(move, self.workspace, root, True))
+        # A rename never creates directories
+        with mock.patch.object(history.shutil, "move") as move_mock:
+            self.assertTrue(
+                history.apply_or_revert_edit(rename, self.workspace, root, True)
+            )
+        move_mock.assert_not_called()
+        self.assertEqual(self.builder.read("m.txt"), "one\n")
+
+        shutil.rmtree(self.workspace / "a")
+        self.assertTrue(history.apply_or_revert_edit(rename, self.workspace, root))
+        # A move creates the directories it needs
+        self.assertTrue(history.apply_or_revert_edit(move, self.workspace, root))
+        self.assertEqual(self.builder.read("a/b/n.txt"), "one\n")
+
+
+class TestAcceptThenReject(AcceptRejectTestCase):
+    def test_reject_accepted_edit(self):
+        first, second = self.edit_lines("a.txt", {1: "two\n", 9: "ten\n"})
+        self.mcpdiff("accept", "-e", first)
+        self.assertEqual(self.status(first), "accepted")
+
+        self.mcpdiff("reject", "-e", first)
+        expected = list(LINES)
+        expected[9] = "ten\n"
+        self.assertEqual(self.builder.read("a.txt"), "".join(expected))
+        self.assertEqual(self.status(first), "rejected")
+        self.assertEqual(self.status(second), "pending")
+
+    def test_accept_after_reject(self):
+        """A re-applied file is not mistaken for an externally modified one."""
+        first, second = self.edit_lines("a.txt", {1: "two\n", 9: "ten\n"})
+        self.mcpdiff("reject", "-e", first)
+        contents = self.builder.read("a.txt")
+
+        result = self.mcpdiff("accept", "-e", second, confirm=False)
+        self.assertNotIn("external modifications", result.stdout)
+        self.assertEqual(self.status(second), "accepted")
+        self.assertEqual(self.builder.read("a.txt"), contents)
+
+
+class TestRepeatedAcceptReject(AcceptRejectTestCase):
+    """Scripts may accept a conversation after every turn."""
+
+    def setUp(self):
+        super().setUp()
+        self.first, self.second = self.edit_lines("a.txt", {1: "two\n", 9: "ten\n"})
+        self.log_file = self.builder.log_file()
+        self.audit_log = self.builder.history_root / "audit.log"
+
+    def assertUnchanged(self, *args: str) -> subprocess.CompletedProcess:
+        log_before = self.log_file.stat().st_mtime_ns, self.log_file.read_bytes()
+        audit_before = self.audit_log.read_bytes()
+        result = self.mcpdiff(*args)
+        self.assertEqual(
+            (self.log_file.stat().st_mtime_ns, self.log_file.read_bytes()), log_before
+        )
+        # Nothing changed, so nothing is audited
+        self.assertEqual(self.audit_log.read_bytes(), audit_before)
+        return result
+
+    def test_accept_twice(self):
+        self.mcpdiff("accept", "-e", self.first)
+        result = self.assertUnchanged("accept", "-e", self.first)
+        self.assertIn(f"Already accepted: {self.first}", result.stdout)
+
+        # Only the edit still pending is accepted
+        result = self.mcpdiff("accept", "-c", "conv1")
+        self.assertIn(f"Already accepted: {self.first}", result.stdout)
+        self.assertNotIn(f"Already accepted: {self.second}", result.stdout)
+        result = self.assertUnchanged("accept", "-c", "conv1")
+        self.assertIn(f"Already accepted: {self.second}", result.stdout)
+        self.assertIn("0 updated, 3 already accepted", result.stdout)
+
+    def test_reject_twice(self):
+        self.mcpdiff("reject", "-c", "conv1")
+        contents = self.builder.read("a.txt")
+        result = self.assertUnchanged("reject", "-c", "conv1")
+        self.assertIn(f"Already rejected: {self.first}", result.stdout)
+        result = self.assertUnchanged("reject", "-e", self.second)
+        self.assertIn(f"Already rejected: {self.second}", result.stdout)
+        self.assertEqual(self.builder.read("a.txt"), contents)
+
+
+class TestCorruptedLog(AcceptRejectTestCase):
+    def test_unreadable_lines_are_skipped(self):
+        first, second = self.edit_lines("a.txt", {1: "two\n", 9: "ten\n"})
+        log_file = self.builder.log_file()
+        lines = log_file.read_text(encoding="utf-8").splitlines(keepends=True)
+        lines.insert(1, '{"edit_id": "truncated", "conversation_\n')
+        lines.append("not json\n")
+        log_file.write_text("".join(lines), encoding="utf-8")
+
+        self.assertEqual(len(self.builder.entries()), 3)
+        self.mcpdiff("reject", "-e", second)
+
+        expected = list(LINES)
+        expected[1] = "two\n"
+        self.assertEqual(self.builder.read("a.txt"), "".join(expected))
+        self.assertEqual(self.status(first), "pending")
+        self.assertEqual(self.status(second), "rejected")
+
+
+class TestRejectWithFuzz(AcceptRejectTestCase):
+    def setUp(self):
+        super().setUp()
+        lines = list(LINES)
+        self.builder.write("a.txt", "".join(lines))
+        lines[1] = "two\n"
+        self.first = self.builder.edit("a.txt", "".join(lines))
+        # A change the history does not know about ends up in the next diff's
+        # context, so that diff no longer applies without the first edit
+        lines[14] = "line  15\n"
+        self.builder.path("a.txt").write_text("".join(lines), encoding="utf-8")
+        lines[17] = "eighteen\n"
+        self.second = self.builder.edit("a.txt", "".join(lines))
+
+    def test_exact_context_required_by_default(self):
+        result = run_mcpdiff(self.workspace, "reject", "-e", self.first, input="y\n")
+        self.assertIn("does not apply", result.stdout + result.stderr)
+        self.assertEqual(self.status(self.first), "pending")
+
+    def test_verbose_marks_the_failing_edit(self):
+        result = run_mcpdiff(
+            self.workspace, "reject", "-e", self.first, "--verbose", input="y\n"
+        )
+        output = re.sub(r"\x1b\[\d+m", "", result.stdout)
+        self.assertIn(f"  ✗ {self.second} edit a.txt\n", output)
+
+    def test_fuzz(self):
+        result = self.mcpdiff("reject", "-e", self.first, "--fuzz", "1")
+        self.assertIn("Hunk #1 applied with fuzz 1 at offset +0", result.stdout)
+        self.assertIn("differs from the recorded hash_after", result.stdout)
+        expected = list(LINES)
+        expected[17] = "eighteen\n"
+        self.assertEqual(self.builder.read("a.txt"), "".join(expected))
+        self.assertEqual(self.status(self.first), "rejected")
+
+    def test_ignore_whitespace(self):
+        result = self.mcpdiff("reject", "-e", self.first, "--ignore-whitespace")
+        self.assertIn("applied ignoring whitespace", result.stdout)
+        self.assertEqual(self.status(self.first), "rejected")
+
+
+class TestFinalVerification(AcceptRejectTestCase):
+    def setUp(self):
+        super().setUp()
+        first, self.middle, self.last = self.edit_lines(
+            "a.txt", {1: "two\n", 9: "ten\n", 17: "eighteen\n"}
+        )
+        # The middle diff still applies, but no longer to what was recorded
+        middle = self.builder.entry(self.middle)
+        diff_path = self.builder.history_root / middle["diff_file"]
+        diff = diff_path.read_text(encoding="utf-8")
+        diff_path.write_text(diff.replace("+ten", "+TEN"), encoding="utf-8")
+        self.before = self.builder.read("a.txt")
+
+    def test_mismatch_leaves_file_unchanged(self):
+        result = run_mcpdiff(self.workspace, "reject", "-e", self.last, input="y\n")
+        self.assertEqual(result.returncode, utils.VerificationError.exit_code)
+        output = result.stdout + result.stderr
+        self.assertIn(f"recorded by edit {self.middle}", output)
+        self.assertIn("--no-verify", output)
+        self.assertEqual(self.builder.read("a.txt"), self.before)
+        self.assertEqual(self.status(self.last), "pending")
+
+    def test_no_verify(self):
+        self.mcpdiff("reject", "-e", self.last, "--no-verify")
+        expected = list(LINES)
+        expected[1], expected[9] = "two\n", "TEN\n"
+        self.assertEqual(self.builder.read("a.txt"), "".join(expected))
+        self.assertEqual(self.status(self.last), "rejected")
+
+    def test_error_details(self):
+        result = history.reconstruct_file_from_history(
+            "a.txt",
+            self.builder.entries(),
+            self.workspace,
+            self.builder.history_root,
+        )
+        error = result["exception"]
+        self.assertIsInstance(error, utils.VerificationError)
+        self.assertEqual(error.edit_id, self.last)
+        self.assertEqual(
+            error.expected_hash, self.builder.entry(self.last)["hash_after"]
+        )
+        self.assertEqual(self.builder.read("a.txt"), self.before)
+
+
+class TestInterleavedConversations(AcceptRejectTestCase):
+    def setUp(self):
+        super().setUp()
+        # conv2's checkpoint of a.txt is taken after conv1's first edit
+        self.builder.path("a.txt").write_text("".join(LINES), encoding="utf-8")
+        self.lines = list(LINES)
+        self.a1 = self.change(1, "two\n")
+        self.builder.conversation("conv2")
+        self.b1 = self.change(9, "ten\n")
+        self.builder.conversation("conv1")
+        self.a2 = self.change(17, "eighteen\n")
+        self.builder.conversation("conv2")
+        self.b2 = self.change(12, "thirteen\n")
+
+    def change(self, index: int, text: str) -> str:
+        self.lines[index] = text
+        return self.builder.edit("a.txt", "".join(self.lines))
+
+    def expected(self, changes: dict) -> str:
+        lines = list(LINES)
+        for index, text in changes.items():
+            lines[index] = text
+        return "".join(lines)
+
+    def test_reject_conversation(self):
+        self.mcpdiff("accept", "-c", "conv2")
+        self.mcpdiff("reject", "-c", "conv1")
+        self.assertEqual(
+            self.builder.read("a.txt"), self.expected({9: "ten\n", 12: "thirteen\n"})
+        )
+        self.assertEqual(self.status(self.b1), "accepted")
+        self.assertEqual(self.status(self.a2), "rejected")
+
+    def test_reject_first_edit(self):
+        self.mcpdiff("reject", "-e", self.a1)
+        self.assertEqual(
+            self.builder.read("a.txt"),
+            self.expected({9: "ten\n", 17: "eighteen\n", 12: "thirteen\n"}),
+        )
+
+    def test_api_reapply_file_state(self):
+        for edit_id in (self.a1, self.a2):
+            history.update_entry_status(
+                self.builder.entry(edit_id), "rejected", self.builder.history_root
+            )
+        results = api.reapply_file_state(
+            self.workspace, self.builder.history_root, "a.txt"
+        )
+        self.assertIsNone(results["error"])
+        self.assertEqual(
+            self.builder.read("a.txt"), self.expected({9: "ten\n", 12: "thirteen\n"})
+        )
+
+        # Leaving conv1's log out replays conv2's edits from conv2's checkpoint,
+        # which has conv1's first edit in it, so the last recorded state is
+        # not reached
+        reapply = functools.partial(
+            api.reapply_file_state,
+            self.workspace,
+            self.builder.history_root,
+            "a.txt",
+            conv_ids=["conv2"],
+        )
+        results = reapply()
+        self.assertIsInstance(results["exception"], utils.VerificationError)
+        self.assertEqual(
+            self.builder.read("a.txt"), self.expected({9: "ten\n", 12: "thirteen\n"})
+        )
+        self.assertIsNone(reapply(verify=False)["error"])
+        self.assertEqual(
+            self.builder.read("a.txt"),
+            self.expected({1: "two\n", 9: "ten\n", 12: "thirteen\n"}),
+        )
+
+
+class TestAcceptRejectByTime(AcceptRejectTestCase):
+    def setUp(self):
+        super().setUp()
+        # One file per edit, logged a second apart from 2025-01-01T00:00:00Z
+        self.edit_ids = [
+            self.builder.write(f"f{i:02d}.txt", f"{i}\n") for i in range(25)
+        ]
+
+    def statuses(self) -> list:
+        return [self.status(edit_id) for edit_id in self.edit_ids]
+
+    def test_accept_before(self):
+        result = self.mcpdiff(
+            "accept", "-c", "conv1", "--before", "2025-01-01T00:00:03"
+        )
+        self.assertIn("3 edit(s) of conversation conv1 match", result.stdout)
+        for edit_id in self.edit_ids[:3]:
+            self.assertIn(edit_id, result.stdout)
+        self.assertNotIn(self.edit_ids[3], result.stdout)
+        self.assertEqual(self.statuses(), ["accepted"] * 3 + ["pending"] * 22)
+
+    def test_reject_range(self):
+        self.mcpdiff(
+            "reject",
+            "-c",
+            "conv1",
+            "--after",
+            "2025-01-01T00:00:10",
+            "--before",
+            "2025-01-01T00:00:12",
+        )
+        self.assertEqual(
+            self.statuses(), ["pending"] * 10 + ["rejected"] * 2 + ["pending"] * 13
+        )
+        self.assertEqual(self.builder.read("f10.txt"), "")
+        self.assertEqual(self.builder.read("f12.txt"), "12\n")
+
+    def test_many_edits_ask_first(self):
+        args = ("accept", "-c", "conv1", "--after", "2025-01-01")
+        result = self.mcpdiff(*args, confirm=False)
+        self.assertIn("Accept all 25?", result.stdout)
+        self.assertIn("Aborted.", result.stdout)
+        self.assertEqual(self.statuses(), ["pending"] * 25)
+
+        self.mcpdiff(*args, "--yes", confirm=False)
+        self.assertEqual(self.statuses(), ["accepted"] * 25)
+
+    def test_needs_conversation(self):
+        result = run_mcpdiff(
+            self.workspace, "accept", "-e", self.edit_ids[0], "--before", "1h"
+        )
+        self.assertEqual(result.returncode, utils.HistoryError.exit_code)
+        self.assertEqual(self.status(self.edit_ids[0]), "pending")
+
+
+if __name__ == "__main__":
+    unittest.main()
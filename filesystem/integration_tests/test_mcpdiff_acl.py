@@ -0,0 +1,155 @@
+#!/usr/bin/env python3
+"""
+Tests for .mcp/acl.toml, which says who may accept and reject each
+conversation's edits:
+- per-conversation lists, [default], "*", owners, and malformed files
+- `accept` and `reject` as $MCP_USER or $USER, which fail with
+  permission_denied before changing anything
+- `workspace init` writes a permissive ACL next to a new history
+"""
+
+import os
+import shutil
+import sys
+import tempfile
+import unittest
+from pathlib import Path
+
+# The CLI modules import each other by bare name; history_builder is found also
+# when run_tests.py imports this module as part of the package
+sys.path.insert(0, str(Path(__file__).parent.parent / "cli"))
+sys.path.insert(0, str(Path(__file__).parent))
+
+import mcpdiff_acl as acl
+from history_builder import HistoryBuilder, run_mcpdiff
+from mcpdiff_utils import HistoryError, PermissionDeniedError
+
+
+ACL = """
+[default]
+can_accept = ["*"]
+can_reject = ["admin"]
+
+[conversation."conv1"]
+owner = "alice"
+can_accept = ["alice", "bob"]
+can_reject = ["alice"]
+"""
+
+
+class TestAclRules(unittest.TestCase):
+    def setUp(self):
+        self.workspace = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+
+    def tearDown(self):
+        shutil.rmtree(self.workspace, ignore_errors=True)
+
+    def load(self, text: str):
+        acl.get_acl_path(self.workspace).parent.mkdir(exist_ok=True)
+        acl.get_acl_path(self.workspace).write_text(text)
+        return acl.load_acl(self.workspace)
+
+    def test_rules(self):
+        rules = self.load(ACL)
+        self.assertTrue(acl.is_allowed(rules, "accept", "conv1", "bob"))
+        self.assertFalse(acl.is_allowed(rules, "reject", "conv1", "bob"))
+        self.assertFalse(acl.is_allowed(rules, "accept", "conv1", None))
+        # Other conversations follow [default]
+        self.assertTrue(acl.is_allowed(rules, "accept", "conv2", "carol"))
+        self.assertFalse(acl.is_allowed(rules, "reject", "conv2", "alice"))
+        self.assertTrue(acl.is_allowed(rules, "reject", "conv2", "admin"))
+
+        # The owner needs no list entry, and a missing list allows everyone
+        rules = self.load('[conversation."c"]\nowner = "alice"\ncan_reject = []\n')
+        self.assertTrue(acl.is_allowed(rules, "reject", "c", "alice"))
+        self.assertFalse(acl.is_allowed(rules, "reject", "c", "bob"))
+        self.assertTrue(acl.is_allowed(rules, "accept", "c", "bob"))
+
+    def test_check_permission(self):
+        rules = self.load(ACL)
+        acl.check_permission(rules, "reject", ["conv1", None], "alice")
+        acl.check_permission(None, "reject", ["conv1"], "mallory")
+        with self.assertRaises(PermissionDeniedError) as raised:
+            acl.check_permission(rules, "reject", ["conv1", "conv2"], "alice")
+        self.assertEqual(raised.exception.exit_code, 12)
+        self.assertEqual(raised.exception.to_dict()["conversation_id"], "conv2")
+        self.assertEqual(raised.exception.to_dict()["user"], "alice")
+
+    def test_malformed(self):
+        self.assertIsNone(acl.load_acl(self.workspace))
+        for text in (
+            "[default\n",
+            "[default]\ncan_reject = 'alice'\n",
+            '[conversation."c"]\nowner = ["alice"]\n',
+            "conversation = 1\n",
+        ):
+            with self.assertRaises(HistoryError, msg=text):
+                self.load(text)
+
+
+class TestAclCommands(unittest.TestCase):
+    def setUp(self):
+        self.workspace = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        self.builder = HistoryBuilder(self.workspace)
+        self.edit = self.builder.write("a.txt", "one\n")
+        acl.get_acl_path(self.workspace).write_text(ACL)
+
+    def tearDown(self):
+        shutil.rmtree(self.workspace, ignore_errors=True)
+
+    def mcpdiff(self, user: str, *args: str, returncode: int = 0):
+        env = dict(os.environ, MCP_USER=user)
+        result = run_mcpdiff(self.workspace, *args, env=env)
+        self.assertEqual(result.returncode, returncode, result.stdout + result.stderr)
+        return result
+
+    def test_reject_denied(self):
+        result = self.mcpdiff("bob", "reject", "-c", "conv1", "-y", returncode=12)
+        self.assertIn("Permission denied: User bob may not reject", result.stderr)
+        self.assertEqual(self.builder.entry(self.edit)["status"], "pending")
+        self.assertEqual(self.builder.read("a.txt"), "one\n")
+
+        # Also for an edit named by its ID
+        result = self.mcpdiff("bob", "reject", "-e", self.edit[:8], returncode=12)
+        self.assertIn("conversation conv1", result.stderr)
+
+        self.mcpdiff("alice", "reject", "-c", "conv1", "-y")
+        self.assertEqual(self.builder.entry(self.edit)["status"], "rejected")
+
+    def test_accept(self):
+        self.mcpdiff("carol", "accept", "-c", "conv1", "-y", returncode=12)
+        self.mcpdiff("bob", "accept", "-e", self.edit[:8])
+        self.assertEqual(self.builder.entry(self.edit)["status"], "accepted")
+
+    def test_dependents_in_other_conversations(self):
+        child = self.builder.conversation("conv2").edit(
+            "a.txt", "two\n", parent_edit_id=self.edit
+        )
+        # Rejecting conv1 also rejects the child in conv2, which only admin may
+        result = self.mcpdiff("alice", "reject", "-c", "conv1", "-y", returncode=12)
+        self.assertIn("conversation conv2", result.stderr)
+        self.assertEqual(self.builder.entry(child)["status"], "pending")
+
+
+class TestWorkspaceInit(unittest.TestCase):
+    def setUp(self):
+        self.root = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+
+    def tearDown(self):
+        shutil.rmtree(self.root, ignore_errors=True)
+
+    def test_init(self):
+        result = run_mcpdiff(None, "workspace", "init", str(self.root), check=True)
+        self.assertTrue((self.root / ".mcp" / "edit_history" / "logs").is_dir())
+        rules = acl.load_acl(self.root)
+        self.assertTrue(acl.is_allowed(rules, "reject", "any", "anyone"))
+
+        # An existing ACL is kept
+        acl.get_acl_path(self.root).write_text(ACL)
+        result = run_mcpdiff(None, "workspace", "init", str(self.root))
+        self.assertIn("Kept existing", result.stdout)
+        self.assertEqual(acl.get_acl_path(self.root).read_text(), ACL)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,91 @@
+#!/usr/bin/env python3
+"""
+Tests for review notes:
+- `mcpdiff annotate` adds, replaces and removes the notes of an edit
+- `show` prints them after the diff
+- Notes do not change statuses or get in the way of accept and reject
+- Annotating an entry of an older schema version upgrades it
+"""
+
+import shutil
+import subprocess
+import sys
+import tempfile
+import unittest
+from pathlib import Path
+
+# The CLI modules import each other by bare name; history_builder is found also
+# when run_tests.py imports this module as part of the package
+sys.path.insert(0, str(Path(__file__).parent.parent / "cli"))
+sys.path.insert(0, str(Path(__file__).parent))
+
+import mcpdiff_api as api
+import mcpdiff_migration as migration
+import mcpdiff_utils as utils
+from history_builder import HistoryBuilder, run_mcpdiff
+
+
+class TestAnnotate(unittest.TestCase):
+    def setUp(self):
+        self.workspace = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        self.builder = HistoryBuilder(self.workspace)
+        self.builder.write("a.txt", "one\n")
+        self.edit_id = self.builder.edit("a.txt", "two\n")
+
+    def tearDown(self):
+        shutil.rmtree(self.workspace, ignore_errors=True)
+
+    def mcpdiff(self, *args: str) -> subprocess.CompletedProcess:
+        return run_mcpdiff(self.workspace, *args, input="y\n", check=True)
+
+    def notes(self):
+        return self.builder.entry(self.edit_id).get("review_notes")
+
+    def test_annotate_and_show(self):
+        self.mcpdiff("annotate", "-e", self.edit_id[:8], "--note", "Why two?")
+        self.assertEqual(self.notes(), "Why two?")
+        output = self.mcpdiff("show", self.edit_id).stdout
+        self.assertIn("Review notes:\n  Why two?", output)
+        self.assertLess(output.index("+two"), output.index("Why two?"))
+        self.assertIn("Why two?", self.mcpdiff("show", "conv1").stdout)
+        raw = self.mcpdiff("show", self.edit_id, "--format", "raw").stdout
+        self.assertNotIn("Why two?", raw)
+
+        self.mcpdiff("annotate", "-e", self.edit_id, "--note", "Fine after all")
+        self.assertEqual(self.notes(), "Fine after all")
+        self.mcpdiff("annotate", "-e", self.edit_id, "--note", "")
+        self.assertNotIn("review_notes", self.builder.entry(self.edit_id))
+
+    def test_status_is_unaffected(self):
+        self.mcpdiff("annotate", "-e", self.edit_id, "--note", "Looks wrong")
+        entry = self.builder.entry(self.edit_id)
+        self.assertEqual(entry["status"], "pending")
+        self.assertEqual(api.LogEntry.from_dict(entry).review_notes, "Looks wrong")
+
+        self.mcpdiff("reject", "-e", self.edit_id)
+        self.assertEqual(self.builder.read("a.txt"), "one\n")
+        entry = self.builder.entry(self.edit_id)
+        self.assertEqual(entry["status"], "rejected")
+        self.assertEqual(entry["review_notes"], "Looks wrong")
+
+    def test_older_entry_is_upgraded(self):
+        log_path = self.builder.history_root / "logs" / "conv1.log"
+        entries = utils.read_log_file(log_path, migrate=False)
+        for entry in entries:
+            entry["schema_version"] = 8
+        utils.write_log_file(log_path, entries)
+        self.assertNotIn("review_notes", migration.default_migrator.upgrade(entries[0]))
+
+        self.mcpdiff("annotate", "-e", self.edit_id, "--note", "Why two?")
+        raw = {e["edit_id"]: e for e in utils.read_log_file(log_path, migrate=False)}
+        self.assertEqual(
+            raw[self.edit_id]["schema_version"], migration.CURRENT_SCHEMA_VERSION
+        )
+        self.assertEqual(raw[self.edit_id]["review_notes"], "Why two?")
+        # Entries that were not annotated keep their version
+        other = next(e for e in raw.values() if e["edit_id"] != self.edit_id)
+        self.assertEqual(other["schema_version"], 8)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,231 @@
+#!/usr/bin/env python3
+"""
+Tests for the public history API in cli/mcpdiff_api.py, called directly
+rather than through the mcpdiff command line.
+"""
+
+import asyncio
+import hashlib
+import json
+import shutil
+import sys
+import tempfile
+import unittest
+from pathlib import Path
+
+# The CLI modules import each other by bare name; history_builder is found also
+# when run_tests.py imports this module as part of the package
+sys.path.insert(0, str(Path(__file__).parent.parent / "cli"))
+sys.path.insert(0, str(Path(__file__).parent))
+
+import mcpdiff_api as api
+import mcpdiff_utils as utils
+from history_builder import make_diff
+
+
+class TestHistoryApi(unittest.TestCase):
+    VERSIONS = ["1\n2\n3\n", "1\ntwo\n3\n", "1\ntwo\n3\n4\n"]
+
+    def setUp(self):
+        self.workspace = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        self.history_root = self.workspace / ".mcp" / utils.HISTORY_DIR_NAME
+        for name in (utils.LOGS_DIR, utils.DIFFS_DIR, utils.CHECKPOINTS_DIR):
+            (self.history_root / name / "conv1").mkdir(parents=True)
+        (self.workspace / "f.txt").write_text(self.VERSIONS[-1])
+
+        self.entries = []
+        for index in range(1, len(self.VERSIONS)):
+            before, after = self.VERSIONS[index - 1], self.VERSIONS[index]
+            checkpoint_rel = f"{utils.CHECKPOINTS_DIR}/conv1/f.txt.{index}.chkpt"
+            (self.history_root / checkpoint_rel).write_text(before)
+            diff_rel = f"conv1/e{index}.diff"
+            (self.history_root / utils.DIFFS_DIR / diff_rel).write_text(
+                make_diff(before, after, "f.txt")
+            )
+            self.entries.append(
+                {
+                    "edit_id": f"e{index}",
+                    "conversation_id": "conv1",
+                    "tool_call_index": index,
+                    "timestamp": f"2025-01-01T00:00:0{index}Z",
+                    "operation": "edit",
+                    "file_path": str(self.workspace / "f.txt"),  # Absolute, as logged
+                    "tool_name": "edit_file_diff",
+                    "status": "pending",
+                    "diff_file": diff_rel,
+                    "checkpoint_file": checkpoint_rel,
+                    "hash_before": hashlib.sha256(before.encode()).hexdigest(),
+                    "hash_after": hashlib.sha256(after.encode()).hexdigest(),
+                    "schema_version": 2,
+                    "ide_context": {"cursor_line": index},
+                }
+            )
+        # One entry per shard, to read the conversation across shards
+        for entry, name in zip(self.entries, ("conv1.log", "conv1_20250102.log")):
+            (self.history_root / utils.LOGS_DIR / name).write_text(
+                json.dumps(entry) + "\n"
+            )
+
+    def tearDown(self):
+        shutil.rmtree(self.workspace, ignore_errors=True)
+
+    def test_find_history_root(self):
+        (self.workspace / "sub").mkdir()
+        found = api.find_history_root(str(self.workspace / "sub"))
+        self.assertEqual(found.resolve(), self.history_root.resolve())
+
+    def test_find_workspace_and_history_roots(self):
+        (self.workspace / "sub").mkdir()
+        workspace_root, history_root = api.find_workspace_and_history_roots(
+            str(self.workspace / "sub")
+        )
+        self.assertEqual(workspace_root, self.workspace.resolve())
+        self.assertEqual(history_root, workspace_root / ".mcp" / "edit_history")
+
+        elsewhere = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        self.addCleanup(shutil.rmtree, elsewhere, ignore_errors=True)
+        with self.assertRaises(api.WorkspaceNotFoundError) as raised:
+            api.find_workspace_and_history_roots(str(elsewhere))
+        self.assertIsInstance(raised.exception, api.HistoryError)
+        self.assertEqual(raised.exception.to_dict()["kind"], "workspace_not_found")
+        self.assertEqual(raised.exception.start_path, str(elsewhere))
+        self.assertIsNone(api.find_history_root(str(elsewhere)))
+
+    def test_read_and_write_log_file(self):
+        log_file = self.history_root / utils.LOGS_DIR / "conv1.log"
+        [entry] = api.read_log_file(log_file)
+        self.assertEqual(entry.edit_id, "e1")
+        # As logged, not relative to the workspace
+        self.assertEqual(entry.file_path, str(self.workspace / "f.txt"))
+        self.assertEqual(api.read_log_file(log_file.with_name("none.log")), [])
+
+        entry.review_notes = "checked"
+        [later] = api.read_conversation(self.history_root, "conv1")[1:]
+        api.write_log_file(log_file, [later, entry])
+        # Sorted, and without the fields read_conversation() adds
+        stored = [json.loads(line) for line in log_file.read_text().splitlines()]
+        self.assertEqual([e["edit_id"] for e in stored], ["e1", "e2"])
+        self.assertEqual(stored[0]["review_notes"], "checked")
+        self.assertNotIn("log_file_source", stored[1])
+
+        log_file.write_text("{not json\n")
+        with self.assertRaises(api.CorruptLogError):
+            api.read_log_file(log_file)
+
+    def test_read_conversation(self):
+        entries = api.read_conversation(self.history_root, "conv1")
+        self.assertEqual([e.edit_id for e in entries], ["e1", "e2"])
+        first = entries[0]
+        self.assertIs(first.status, api.Status.PENDING)
+        self.assertIs(first.operation, api.Operation.EDIT)
+        self.assertEqual(first.file_path, "f.txt")
+        self.assertTrue(first.is_edit)
+        self.assertEqual(first.extra["ide_context"], {"cursor_line": 1})
+        self.assertEqual(first.extra["log_file_source"], "conv1.log")
+
+    def test_read_unknown_conversation(self):
+        with self.assertRaises(api.HistoryError):
+            api.read_conversation(self.history_root, "conv")
+
+    def test_log_entry_round_trip(self):
+        entry = dict(self.entries[0], operation="teleport", future_field=[1])
+        log_entry = api.LogEntry.from_dict(entry)
+        self.assertEqual(log_entry.operation, "teleport")
+        self.assertEqual(log_entry.to_dict(), entry)
+
+    def test_summarize_conversations(self):
+        (summary,) = api.summarize_conversations(self.history_root)
+        self.assertEqual(summary.conversation_id, "conv1")
+        self.assertEqual((summary.edits, summary.pending, summary.files), (2, 2, 1))
+        self.assertLess(summary.first_activity, summary.last_activity)
+
+    def test_acquire_lock_is_exclusive(self):
+        with api.acquire_lock_sync(self.history_root, "conv1"):
+            with self.assertRaises(TimeoutError):
+                api.acquire_lock_sync(self.history_root, "conv1", timeout=0.2)
+        api.acquire_lock_sync(self.history_root, "conv1", timeout=0.2).release()
+
+    def test_acquire_lock_async(self):
+        async def wait_for_release():
+            held = api.acquire_lock_sync(self.history_root, "conv1")
+            # Only runs if waiting leaves the event loop free
+            asyncio.get_running_loop().call_later(0.05, held.release)
+            lock = await api.acquire_lock(self.history_root, "conv1", timeout=5)
+            lock.release()
+
+        asyncio.run(wait_for_release())
+
+        async def cancelled():
+            with api.acquire_lock_sync(self.history_root, "conv1"):
+                with self.assertRaises(asyncio.TimeoutError):
+                    await asyncio.wait_for(
+                        api.acquire_lock(self.history_root, "conv1"), 0.1
+                    )
+                with self.assertRaises(TimeoutError):
+                    await api.acquire_lock(self.history_root, "conv1", timeout=0.1)
+
+        asyncio.run(cancelled())
+        # Neither attempt left the lock held
+        api.acquire_lock_sync(self.history_root, "conv1", timeout=0.2).release()
+
+    def test_calculate_hash(self):
+        self.assertEqual(
+            api.calculate_hash(self.workspace / "f.txt"), self.entries[-1]["hash_after"]
+        )
+        self.assertIsNone(api.calculate_hash(self.workspace / "missing.txt"))
+
+    def test_reapply_conversation_state(self):
+        """A clobbered file is rewritten to what it reconstructs to."""
+        api.reapply_conversation_state(self.workspace, self.history_root, "conv1")
+        expected = (self.workspace / "f.txt").read_text()
+        (self.workspace / "f.txt").write_text("clobbered\n")
+
+        results = api.reapply_conversation_state(
+            self.workspace, self.history_root, "conv1", file_path="f.txt"
+        )
+        self.assertEqual(list(results), ["f.txt"])
+        self.assertIsNone(results["f.txt"]["error"])
+        self.assertEqual((self.workspace / "f.txt").read_text(), expected)
+        self.assertEqual(
+            results["f.txt"]["hash"], api.calculate_hash(self.workspace / "f.txt")
+        )
+
+    def test_detect_conflicts(self):
+        diff = make_diff("a\nb\nc\nd\ne\n", "a\nB\nc\nd\nE\n")
+        self.assertEqual(api.detect_conflicts("a\nb\nc\nd\ne\n", diff), [])
+        # At an offset, the diff still applies
+        self.assertEqual(api.detect_conflicts("x\na\nb\nc\nd\ne\n", diff), [])
+
+        [conflict] = api.detect_conflicts("a\nb\nc\nd\nfive\n", diff)
+        self.assertIsInstance(conflict, api.ConflictRegion)
+        self.assertEqual(
+            (conflict.hunk, conflict.start_line, conflict.end_line), (1, 1, 5)
+        )
+        self.assertEqual(conflict.first_difference, 5)
+        self.assertEqual(conflict.actual_lines[4], "five")
+        self.assertEqual(
+            conflict.describe(),
+            "hunk #1 at lines 1-5: line 5 is `five`, the diff expects `e`",
+        )
+
+        # A file too short for the hunk
+        [conflict] = api.detect_conflicts("a\nb\n", diff)
+        self.assertEqual(conflict.first_difference, 3)
+        self.assertIn("the file ends before line 3", conflict.describe())
+
+        # Only failing hunks are reported, in lines of the content given
+        lines = [f"{i}\n" for i in range(1, 31)]
+        edited = list(lines)
+        edited[1:2] = ["2a\n", "2b\n"]
+        edited[25] = "25!\n"
+        diff = make_diff("".join(lines), "".join(edited))
+        lines[26] = "27?\n"
+        conflicts = api.detect_conflicts("".join(lines), diff)
+        self.assertEqual(
+            [(c.hunk, c.start_line, c.end_line, c.first_difference) for c in conflicts],
+            [(2, 22, 28, 27)],
+        )
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,130 @@
+#!/usr/bin/env python3
+"""
+Tests for the audit log behind `mcpdiff audit`:
+- Records written for status changes, with the invocation and outcome
+- The mcpdiff command line stored with the records a command makes, and none
+  with records made outside a command
+- Appends from concurrent processes staying whole lines
+- Filtering by time
+"""
+
+import json
+import multiprocessing
+import shutil
+import sys
+import tempfile
+import unittest
+from pathlib import Path
+
+# The CLI modules import each other by bare name; history_builder is found also
+# when run_tests.py imports this module as part of the package
+sys.path.insert(0, str(Path(__file__).parent.parent / "cli"))
+sys.path.insert(0, str(Path(__file__).parent))
+
+import mcpdiff_audit as audit
+import mcpdiff_history as history
+import mcpdiff_utils as utils
+from history_builder import HistoryBuilder, run_mcpdiff
+
+
+def append_records(history_root: Path, worker: int, count: int) -> None:
+    for index in range(count):
+        audit.record(history_root, "accepted", [f"w{worker}-{index}"], "success")
+
+
+class TestAuditLog(unittest.TestCase):
+    def setUp(self):
+        self.workspace = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        self.history_root = self.workspace / ".mcp" / utils.HISTORY_DIR_NAME
+        (self.history_root / utils.LOGS_DIR).mkdir(parents=True)
+
+    def tearDown(self):
+        shutil.rmtree(self.workspace, ignore_errors=True)
+
+    def test_status_change_is_recorded(self):
+        log_file = self.history_root / utils.LOGS_DIR / "conv1.log"
+        utils.write_log_file(
+            log_file,
+            [{"edit_id": "e1", "conversation_id": "conv1", "status": "pending"}],
+        )
+        entries = history.find_all_entries(self.history_root)
+        history.update_entries_status(
+            entries,
+            "accepted",
+            self.history_root,
+            invocation=audit.Invocation("accept", ["accept", "-e", "e1"]),
+        )
+
+        records = audit.read_audit_log(self.history_root)
+        self.assertEqual(len(records), 1)
+        record = records[0]
+        self.assertEqual(record["action"], "accepted")
+        self.assertEqual(record["edit_ids"], ["e1"])
+        self.assertEqual(record["outcome"], "success")
+        self.assertEqual(record["command"], "accept")
+        self.assertEqual(record["argv"], ["accept", "-e", "e1"])
+        self.assertTrue(record["user"])
+
+    def test_without_invocation(self):
+        """Changes made outside a command, by the daemon say, have no command."""
+        audit.record(self.history_root, "rejected", ["e1"], "success")
+        record = audit.read_audit_log(self.history_root)[0]
+        self.assertIsNone(record["command"])
+        self.assertEqual(record["argv"], [])
+
+    def test_cli_records_invocation(self):
+        builder = HistoryBuilder(self.workspace)
+        builder.write("a.txt", "one\n")
+        edit_id = builder.edit("a.txt", "two\n")
+        argv = ["-w", str(self.workspace), "reject", "-e", edit_id]
+        run_mcpdiff(None, *argv, input="y\n", check=True)
+
+        records = audit.read_audit_log(self.history_root)
+        self.assertEqual([r["action"] for r in records], ["rejected", "reapply"])
+        for record in records:
+            self.assertEqual(record["command"], "reject")
+            self.assertEqual(record["argv"], argv)
+
+    def test_unchanged_status_is_not_recorded(self):
+        log_file = self.history_root / utils.LOGS_DIR / "conv1.log"
+        utils.write_log_file(
+            log_file,
+            [{"edit_id": "e1", "conversation_id": "conv1", "status": "accepted"}],
+        )
+        entries = history.find_all_entries(self.history_root)
+        history.update_entries_status(entries, "accepted", self.history_root)
+        self.assertEqual(audit.read_audit_log(self.history_root), [])
+
+    def test_concurrent_appends(self):
+        workers = [
+            multiprocessing.Process(
+                target=append_records, args=(self.history_root, worker, 10)
+            )
+            for worker in range(4)
+        ]
+        for worker in workers:
+            worker.start()
+        for worker in workers:
+            worker.join()
+        lines = audit.get_audit_log_path(self.history_root).read_text().splitlines()
+        self.assertEqual(len(lines), 40)
+        ids = {json.loads(line)["edit_ids"][0] for line in lines}
+        self.assertEqual(len(ids), 40)
+
+    def test_since(self):
+        audit.record(self.history_root, "rejected", ["e1"], "failure")
+        path = audit.get_audit_log_path(self.history_root)
+        old = json.loads(path.read_text())
+        old["timestamp"] = "2020-01-01T00:00:00.000Z"
+        path.write_text(json.dumps(old) + "\n")
+        audit.record(self.history_root, "accepted", ["e2"], "success")
+
+        records = audit.read_audit_log(
+            self.history_root, since=utils.parse_timestamp("2021-01-01T00:00:00Z")
+        )
+        self.assertEqual([r["edit_ids"] for r in records], [["e2"]])
+        self.assertEqual(len(audit.read_audit_log(self.history_root)), 2)
+
+
+if __name__ == "__main__":
+    unittest.main()
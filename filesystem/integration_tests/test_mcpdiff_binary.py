@@ -0,0 +1,235 @@
+#!/usr/bin/env python3
+"""
+Tests for binary files, whose edits are stored as bsdiff patches:
+- Patches round-trip, and malformed ones or the wrong base content are refused
+- Rejecting binary edits re-applies the file from its patches
+- `show` and `du` recognize the patches
+- The server's patches apply with mcpdiff's, also the whole-content ones it
+  stores for large files
+- Text that is not valid UTF-8 gets a base64-encoded unified diff instead
+"""
+
+import base64
+import hashlib
+import json
+import random
+import shutil
+import subprocess
+import sys
+import tempfile
+import unittest
+from pathlib import Path
+
+# The CLI modules import each other by bare name; history_builder is found also
+# when run_tests.py imports this module as part of the package. The patches are
+# made by the server's generator, which needs none of its dependencies
+sys.path.insert(0, str(Path(__file__).parent.parent / "cli"))
+sys.path.insert(0, str(Path(__file__).parent.parent / "src"))
+sys.path.insert(0, str(Path(__file__).parent))
+
+import mcp_bsdiff as bsdiff
+import mcpdiff_api as api
+import mcpdiff_patch as patch
+import mcpdiff_utils as utils
+from history_builder import HistoryBuilder, run_mcpdiff
+
+try:
+    sys.path.insert(0, str(Path(__file__).parent.parent))
+    from src import mcp_edit_utils as server_utils
+except ImportError:  # The server needs filelock
+    server_utils = None
+
+
+IMAGE = b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR" + bytes(range(256)) * 8
+
+
+def edited(data: bytes, offset: int, replacement: bytes) -> bytes:
+    return data[:offset] + replacement + data[offset + len(replacement) :]
+
+
+class TestBsdiffPatch(unittest.TestCase):
+    def test_round_trip(self):
+        rng = random.Random(1)
+        cases = [(b"", b""), (b"", IMAGE), (IMAGE, b""), (IMAGE, IMAGE)]
+        cases.append((IMAGE, edited(IMAGE, 100, b"\xff" * 10) + b"\x00tail"))
+        for _ in range(50):
+            old = bytes(rng.randrange(4) for _ in range(rng.randrange(200)))
+            new = bytearray(old)
+            for _ in range(rng.randrange(5)):
+                i = rng.randrange(len(new) + 1)
+                new[i : i + rng.randrange(3)] = rng.randbytes(rng.randrange(4))
+            cases.append((old, bytes(new)))
+        for old, new in cases:
+            data = bsdiff.generate_bsdiff(old, new)
+            self.assertTrue(patch.is_bsdiff_patch(data))
+            self.assertEqual(patch.apply_bsdiff_patch(old, data), new)
+
+    def test_small_change_gives_small_patch(self):
+        new = edited(IMAGE, 1000, b"changed")
+        self.assertLess(len(bsdiff.generate_bsdiff(IMAGE, new)), 200)
+
+    def test_malformed_patches(self):
+        data = bsdiff.generate_bsdiff(IMAGE, edited(IMAGE, 5, b"x"))
+        for bad in (b"", b"--- a/f\n+++ b/f\n", data[:40], data[:32] + b"junk"):
+            with self.assertRaises(patch.PatchError):
+                patch.apply_bsdiff_patch(IMAGE, bad)
+
+    def test_wrong_base_content(self):
+        workspace = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        self.addCleanup(shutil.rmtree, workspace, ignore_errors=True)
+        target = workspace / "img.bin"
+        patch_path = workspace / "img.diff"
+        patch_path.write_bytes(bsdiff.generate_bsdiff(IMAGE, IMAGE[::-1]))
+        target.write_bytes(IMAGE[1:])
+        with self.assertRaises(patch.PatchError):
+            patch.apply_bsdiff_to_file(
+                target, patch_path, hashlib.sha256(IMAGE).hexdigest()
+            )
+        self.assertEqual(target.read_bytes(), IMAGE[1:])
+
+    def test_full_content_patches(self):
+        for old, new in [(b"", b""), (IMAGE, b""), (b"", IMAGE), (IMAGE, IMAGE[::-1])]:
+            data = bsdiff.generate_full_bsdiff(new)
+            self.assertTrue(patch.is_bsdiff_patch(data))
+            self.assertEqual(patch.apply_bsdiff_patch(old, data), new)
+
+    def test_large_files_stored_whole(self):
+        large = IMAGE * (bsdiff.BSDIFF_MAX_SIZE // len(IMAGE) + 1)
+        new = edited(large, 300, b"\x00large")
+        data = bsdiff.generate_binary_patch(large, new)
+        self.assertEqual(data, bsdiff.generate_full_bsdiff(new))
+        self.assertEqual(patch.apply_bsdiff_patch(large, data), new)
+        new = edited(IMAGE, 300, b"\x00small")
+        data = bsdiff.generate_binary_patch(IMAGE, new)
+        self.assertEqual(data, bsdiff.generate_bsdiff(IMAGE, new))
+
+    @unittest.skipIf(server_utils is None, "server dependencies not installed")
+    def test_server_patches_apply(self):
+        new = edited(IMAGE, 300, b"\x00server")
+        self.assertTrue(server_utils.is_binary_content(IMAGE))
+        self.assertFalse(server_utils.is_binary_content("ünïcode\n".encode()))
+        data = server_utils.generate_binary_patch(IMAGE, new)
+        self.assertEqual(patch.apply_bsdiff_patch(IMAGE, data), new)
+
+
+class TestBinaryEdits(unittest.TestCase):
+    def setUp(self):
+        self.workspace = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        self.builder = HistoryBuilder(self.workspace)
+        self.states = [IMAGE, edited(IMAGE, 64, b"one"), edited(IMAGE, 64, b"two")]
+        self.edits = [self.builder.write("img.bin", state) for state in self.states]
+
+    def tearDown(self):
+        shutil.rmtree(self.workspace, ignore_errors=True)
+
+    def mcpdiff(self, *args: str) -> subprocess.CompletedProcess:
+        return run_mcpdiff(self.workspace, *args, input="y\n")
+
+    def content(self) -> bytes:
+        return self.builder.path("img.bin").read_bytes()
+
+    def test_entries_have_patches(self):
+        for edit_id in self.edits:
+            entry = self.builder.entry(edit_id)
+            self.assertEqual(entry["file_encoding"], "bsdiff")
+            diff_path = self.builder.history_root / entry["diff_file"]
+            self.assertTrue(patch.is_bsdiff_patch(diff_path.read_bytes()))
+
+        result = self.mcpdiff("show", self.edits[1])
+        self.assertEqual(result.returncode, 0, result.stdout + result.stderr)
+        self.assertIn("Binary file changed (bsdiff patch", result.stdout)
+
+        result = self.mcpdiff("du", "--json")
+        totals = json.loads(result.stdout)["totals"]
+        self.assertGreater(totals["binary_diffs"], 0)
+        self.assertEqual(totals["binary_diffs"], totals["diffs"])
+
+    def test_reject_last_edits(self):
+        result = self.mcpdiff("reject", "-e", self.edits[2])
+        self.assertEqual(result.returncode, 0, result.stdout + result.stderr)
+        self.assertEqual(self.content(), self.states[1])
+        result = self.mcpdiff("reject", "-e", self.edits[1])
+        self.assertEqual(result.returncode, 0, result.stdout + result.stderr)
+        self.assertEqual(self.content(), self.states[0])
+
+    def test_reject_conversation(self):
+        result = self.mcpdiff("reject", "-c", "conv1")
+        self.assertEqual(result.returncode, 0, result.stdout + result.stderr)
+        # A rejected create leaves an empty file
+        self.assertEqual(self.content(), b"")
+
+    def test_reject_middle_edit_conflicts(self):
+        # Binary patches only apply to the exact content they were made for
+        result = self.mcpdiff("reject", "-e", self.edits[1])
+        self.assertEqual(result.returncode, utils.PatchConflictError.exit_code)
+        self.assertEqual(self.content(), self.states[2])
+        self.assertEqual(self.builder.entry(self.edits[1])["status"], "pending")
+
+
+class TestBase64Diffs(unittest.TestCase):
+    STATES = ["caf\xe9\n".encode("latin-1"), "caf\xe9 cr\xe8me\n".encode("latin-1")]
+
+    def setUp(self):
+        self.workspace = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        self.builder = HistoryBuilder(self.workspace)
+        self.edits = [self.builder.write("menu.txt", s) for s in self.STATES]
+
+    def tearDown(self):
+        shutil.rmtree(self.workspace, ignore_errors=True)
+
+    def mcpdiff(self, *args: str) -> subprocess.CompletedProcess:
+        return run_mcpdiff(self.workspace, *args, input="y\n")
+
+    def content(self) -> bytes:
+        return self.builder.path("menu.txt").read_bytes()
+
+    def test_entries_have_base64_diffs(self):
+        entry = self.builder.entry(self.edits[1])
+        self.assertEqual(entry["file_encoding"], "base64")
+        stored = (self.builder.history_root / entry["diff_file"]).read_bytes()
+        diff = base64.b64decode(stored, validate=True)
+        self.assertIn(b"-caf\xe9\n+caf\xe9 cr\xe8me\n", diff)
+
+        result = self.mcpdiff("show", self.edits[1])
+        self.assertEqual(result.returncode, 0, result.stdout + result.stderr)
+        self.assertIn("stored base64-encoded", result.stdout)
+        self.assertIn("+caf\ufffd cr\ufffdme", result.stdout)
+
+        # Raw output stays a diff; the warning goes to stderr
+        result = self.mcpdiff("show", self.edits[1], "--format", "raw")
+        self.assertTrue(result.stdout.startswith("--- a/menu.txt"), result.stdout)
+        self.assertIn("stored base64-encoded", result.stderr)
+
+    def test_reject_and_reapply(self):
+        result = self.mcpdiff("reject", "-e", self.edits[1])
+        self.assertEqual(result.returncode, 0, result.stdout + result.stderr)
+        self.assertEqual(self.content(), self.STATES[0])
+
+        self.builder.path("menu.txt").write_bytes(b"clobbered\n")
+        results = api.reapply_conversation_state(
+            self.workspace, self.builder.history_root, "conv1"
+        )
+        self.assertIsNone(results["menu.txt"]["error"])
+        self.assertEqual(self.content(), self.STATES[0])
+
+    def test_malformed_base64(self):
+        diff_path = self.workspace / "bad.diff"
+        diff_path.write_bytes(b"--- a/menu.txt\n")
+        with self.assertRaises(patch.PatchError):
+            patch.read_diff_file(diff_path, patch.BASE64_ENCODING)
+
+    @unittest.skipIf(server_utils is None, "server dependencies not installed")
+    def test_server_diffs_read_back(self):
+        self.assertFalse(server_utils.is_binary_content(self.STATES[0]))
+        lines = [s.decode("utf-8", "surrogateescape") for s in self.STATES]
+        diff = server_utils.generate_diff(lines[:1], lines[1:], "menu.txt", "menu.txt")
+        data, encoding = server_utils.encode_diff(diff)
+        self.assertEqual(encoding, patch.BASE64_ENCODING)
+        diff_path = self.workspace / "menu.diff"
+        diff_path.write_bytes(data)
+        self.assertEqual(patch.read_diff_file(diff_path, encoding), diff)
+        self.assertEqual(server_utils.encode_diff("caf\xe9\n"), ("café\n".encode(), None))
+
+
+if __name__ == "__main__":
+    unittest.main()
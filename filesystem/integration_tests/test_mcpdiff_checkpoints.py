@@ -0,0 +1,278 @@
+#!/usr/bin/env python3
+"""
+Tests for `mcpdiff prune-checkpoints`:
+- Only the N most recent checkpoints of a file are kept, and their references
+  are cleared from the log
+- The file can still be reconstructed after pruning
+- Nothing is deleted if a diff needed after the kept checkpoints is missing
+
+and for checkpoints that do not match their edit's hash_before: reported by
+re-applies and `mcpdiff verify`, and fixed with `mcpdiff rebaseline`, and for
+the history_depth setting: rejects prune the older checkpoints of a file,
+promoting a later one when a re-apply would need them.
+"""
+
+import hashlib
+import json
+import shutil
+import subprocess
+import sys
+import tempfile
+import unittest
+from pathlib import Path
+
+# The CLI modules import each other by bare name; history_builder is found also
+# when run_tests.py imports this module as part of the package
+sys.path.insert(0, str(Path(__file__).parent.parent / "cli"))
+sys.path.insert(0, str(Path(__file__).parent))
+
+import mcpdiff_api as api
+import mcpdiff_audit as audit
+import mcpdiff_config as config
+import mcpdiff_history as history
+import mcpdiff_utils as utils
+from history_builder import HistoryBuilder, make_diff, run_mcpdiff
+
+
+class TestPruneCheckpoints(unittest.TestCase):
+    # Each edit appends a line; edits 1, 2 and 3 each saved a checkpoint
+    VERSIONS = ["1\n", "1\n2\n", "1\n2\n3\n", "1\n2\n3\n4\n"]
+
+    def setUp(self):
+        self.workspace = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        self.history_root = self.workspace / ".mcp" / utils.HISTORY_DIR_NAME
+        for name in (utils.LOGS_DIR, utils.DIFFS_DIR, utils.CHECKPOINTS_DIR):
+            (self.history_root / name / "conv1").mkdir(parents=True)
+        (self.workspace / "f.txt").write_text(self.VERSIONS[-1])
+
+        entries = []
+        for index in range(1, len(self.VERSIONS)):
+            before, after = self.VERSIONS[index - 1], self.VERSIONS[index]
+            edit_id = f"e{index}"
+            checkpoint_rel = f"{utils.CHECKPOINTS_DIR}/conv1/f.txt.{index}.chkpt"
+            (self.history_root / checkpoint_rel).write_text(before)
+            diff_rel = f"conv1/{edit_id}.diff"
+            (self.history_root / utils.DIFFS_DIR / diff_rel).write_text(
+                make_diff(before, after, "f.txt")
+            )
+            entries.append(
+                {
+                    "edit_id": edit_id,
+                    "conversation_id": "conv1",
+                    "tool_call_index": index,
+                    "timestamp": f"2025-01-01T00:00:0{index}Z",
+                    "operation": "edit",
+                    "file_path": "f.txt",
+                    "tool_name": "edit_file_diff",
+                    "status": "accepted",
+                    "diff_file": diff_rel,
+                    "checkpoint_file": checkpoint_rel,
+                    "hash_before": hashlib.sha256(before.encode()).hexdigest(),
+                    "hash_after": hashlib.sha256(after.encode()).hexdigest(),
+                }
+            )
+        self.log_file = self.history_root / utils.LOGS_DIR / "conv1.log"
+        self.log_file.write_text("".join(json.dumps(e) + "\n" for e in entries))
+
+    def tearDown(self):
+        shutil.rmtree(self.workspace, ignore_errors=True)
+
+    def checkpoint_path(self, index: int) -> Path:
+        name = f"f.txt.{index}.chkpt"
+        return self.history_root / utils.CHECKPOINTS_DIR / "conv1" / name
+
+    def test_keeps_the_newest(self):
+        entries = history.find_all_entries(self.history_root)
+        plan = history.plan_checkpoint_pruning(self.history_root, entries, 1)
+        self.assertEqual([item["edit_ids"] for item in plan], [["e1"], ["e2"]])
+        history.prune_checkpoints(self.history_root, entries, plan)
+
+        self.assertFalse(self.checkpoint_path(1).exists())
+        self.assertFalse(self.checkpoint_path(2).exists())
+        self.assertTrue(self.checkpoint_path(3).exists())
+        logged = [json.loads(line) for line in self.log_file.read_text().splitlines()]
+        self.assertEqual(
+            [e["checkpoint_file"] is None for e in logged], [True, True, False]
+        )
+
+    def reconstruct(self) -> str:
+        (self.workspace / "f.txt").write_text("clobbered\n")
+        entries = history.find_all_entries(self.history_root)
+        result = history.reconstruct_file_from_history(
+            "f.txt", entries, self.workspace, self.history_root
+        )
+        self.assertIsNone(result["error"])
+        return (self.workspace / "f.txt").read_text()
+
+    def test_file_still_reconstructs(self):
+        """Pruning must not change what the file reconstructs to."""
+        before = self.reconstruct()
+        entries = history.find_all_entries(self.history_root)
+        plan = history.plan_checkpoint_pruning(self.history_root, entries, 1)
+        history.prune_checkpoints(self.history_root, entries, plan)
+        self.assertEqual(self.reconstruct(), before)
+
+    def test_missing_diff_prevents_pruning(self):
+        (self.history_root / utils.DIFFS_DIR / "conv1" / "e3.diff").unlink()
+        entries = history.find_all_entries(self.history_root)
+        with self.assertRaises(utils.HistoryError):
+            history.plan_checkpoint_pruning(self.history_root, entries, 1)
+        self.assertTrue(self.checkpoint_path(1).exists())
+
+    def test_nothing_to_prune(self):
+        entries = history.find_all_entries(self.history_root)
+        self.assertEqual(
+            history.plan_checkpoint_pruning(self.history_root, entries, 3), []
+        )
+        with self.assertRaises(utils.HistoryError):
+            history.plan_checkpoint_pruning(self.history_root, entries, 0)
+
+
+class TestCheckpointHashes(unittest.TestCase):
+    def setUp(self):
+        self.workspace = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        # The file existed before the conversation, so its edit is checkpointed
+        (self.workspace / "a.txt").write_text("base\n")
+        self.builder = HistoryBuilder(self.workspace)
+        self.edit = self.builder.edit("a.txt", "two\n")
+        self.checkpoint = self.builder.history_root / self.entry()["checkpoint_file"]
+        # As if the file changed between checkpointing and logging
+        self.checkpoint.write_text("crashed\n")
+
+    def tearDown(self):
+        shutil.rmtree(self.workspace, ignore_errors=True)
+
+    def entry(self) -> dict:
+        return self.builder.entry(self.edit)
+
+    def mcpdiff(self, *args: str, answer: str = "y") -> subprocess.CompletedProcess:
+        return run_mcpdiff(self.workspace, *args, input=f"{answer}\n")
+
+    def test_reject_reports_mismatch(self):
+        result = self.mcpdiff("reject", "-e", self.edit)
+        self.assertEqual(result.returncode, 0, result.stdout + result.stderr)
+        self.assertIn("not its hash_before", result.stdout)
+        self.assertIn(f"mcpdiff rebaseline --edit-id {self.edit}", result.stdout)
+        records = audit.read_audit_log(self.builder.history_root)
+        reapply = next(r for r in records if r["action"] == "reapply")
+        self.assertIn("not its hash_before", reapply["warnings"][0])
+
+    def test_verify(self):
+        result = self.mcpdiff("verify")
+        self.assertEqual(result.returncode, 1)
+        checkpoint_file = self.entry()["checkpoint_file"]
+        self.assertIn(f"CHECKPOINT Checkpoint {checkpoint_file}", result.stdout)
+
+        self.checkpoint.write_text("base\n")
+        result = self.mcpdiff("verify")
+        self.assertEqual(result.returncode, 0, result.stdout + result.stderr)
+
+    def test_rebaseline(self):
+        result = self.mcpdiff("rebaseline", "-e", self.edit[:8], answer="n")
+        self.assertIn("Aborted", result.stdout)
+        self.assertEqual(self.checkpoint.read_text(), "crashed\n")
+
+        old_hash = self.entry()["hash_before"]
+        result = self.mcpdiff("rebaseline", "-e", self.edit[:8])
+        self.assertEqual(result.returncode, 0, result.stdout + result.stderr)
+        self.assertEqual(self.checkpoint.read_text(), "two\n")
+        self.assertEqual(
+            self.entry()["hash_before"],
+            utils.calculate_hash(str(self.workspace / "a.txt")),
+        )
+        records = audit.read_audit_log(self.builder.history_root)
+        self.assertEqual(records[-1]["action"], "rebaseline")
+        self.assertEqual(records[-1]["edit_ids"], [self.edit])
+        self.assertEqual(records[-1]["old_hash_before"], old_hash)
+        # The log changed, and its Merkle root with it
+        result = self.mcpdiff("verify")
+        self.assertEqual(result.returncode, 0, result.stdout + result.stderr)
+
+    def test_rebaseline_without_checkpoint(self):
+        second = self.builder.edit("a.txt", "three\n")
+        self.assertIsNone(self.builder.entry(second)["checkpoint_file"])
+        result = self.mcpdiff("rebaseline", "-e", second, "--yes")
+        self.assertEqual(result.returncode, 0, result.stdout + result.stderr)
+        checkpoint_file = self.builder.entry(second)["checkpoint_file"]
+        self.assertEqual(Path(checkpoint_file).name, f"{second}.chkpt")
+        checkpoint = self.builder.history_root / checkpoint_file
+        self.assertEqual(checkpoint.read_text(), "three\n")
+
+
+class TestHistoryDepth(unittest.TestCase):
+    def setUp(self):
+        self.workspace = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        (self.workspace / "a.txt").write_text("base\n")
+        self.builder = HistoryBuilder(self.workspace)
+        self.edits = [
+            self.builder.edit("a.txt", content) for content in ("one\n", "two\n")
+        ]
+        self.last = self.builder.edit("a.txt", "three\n")
+        self.base = self.builder.history_root / self.checkpoint_file(self.edits[0])
+        config.set_config_value(self.workspace, "history_depth", "1")
+
+    def tearDown(self):
+        shutil.rmtree(self.workspace, ignore_errors=True)
+
+    def checkpoint_file(self, edit_id: str):
+        return self.builder.entry(edit_id)["checkpoint_file"]
+
+    def mcpdiff(self, *args: str) -> str:
+        return run_mcpdiff(self.workspace, *args, check=True).stdout
+
+    def snapshots(self) -> list:
+        return [
+            e["checkpoint_file"]
+            for e in self.builder.entries()
+            if e.get("operation") == "snapshot" and e.get("checkpoint_file")
+        ]
+
+    def test_invalid_depth(self):
+        with self.assertRaises(utils.HistoryError):
+            config.set_config_value(self.workspace, "history_depth", "0")
+
+    def test_keeps_checkpoints_pending_edits_need(self):
+        # Each reject snapshots the file; only the newest snapshot is kept, and
+        # the base checkpoint, since the pending edits may still be rejected
+        output = self.mcpdiff("reject", "-e", self.last)
+        self.assertIn("Kept 1 older checkpoint(s) of a.txt", output)
+        self.mcpdiff("reject", "-e", self.edits[1])
+        self.assertEqual(self.builder.read("a.txt"), "one\n")
+        self.assertEqual(len(self.snapshots()), 1)
+        self.assertTrue((self.builder.history_root / self.snapshots()[0]).is_file())
+        self.assertTrue(self.base.is_file())
+        snapshot_dir = self.builder.history_root / utils.CHECKPOINTS_DIR / "conv1"
+        self.assertEqual(len(list(snapshot_dir.glob("a.txt_*.chkpt"))), 1)
+
+    def test_promotes_after_accepted_edits(self):
+        self.mcpdiff("accept", "-e", self.edits[0])
+        self.mcpdiff("accept", "-e", self.edits[1])
+        output = self.mcpdiff("reject", "-e", self.last)
+        self.assertIn(f"Promoted a checkpoint for edit {self.edits[1][:8]}", output)
+        self.assertFalse(self.base.exists())
+        self.assertIsNone(self.checkpoint_file(self.edits[0]))
+        promoted = self.builder.history_root / self.checkpoint_file(self.edits[1])
+        self.assertEqual(promoted.read_text(), "one\n")
+        records = audit.read_audit_log(self.builder.history_root)
+        self.assertIn("promote", [r["action"] for r in records])
+
+        # The promoted checkpoint is enough to re-apply the file
+        (self.workspace / "a.txt").write_text("changed\n")
+        results = api.reapply_conversation_state(
+            self.workspace, self.builder.history_root, "conv1"
+        )
+        self.assertIsNone(results["a.txt"]["error"])
+        self.assertEqual(self.builder.read("a.txt"), "two\n")
+
+    def test_reapply_checks_checkpoint(self):
+        self.base.unlink()
+        results = api.reapply_conversation_state(
+            self.workspace, self.builder.history_root, "conv1"
+        )
+        error = results["a.txt"]["exception"]
+        self.assertIsInstance(error, utils.MissingCheckpointError)
+        self.assertEqual(self.builder.read("a.txt"), "three\n")
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,88 @@
+#!/usr/bin/env python3
+"""
+Tests for `mcpdiff compare-convs`:
+- Files changed by only one of two conversations, or by both
+- Whether both leave a shared file with the same content (hash_after)
+- Rejected edits and mcpdiff's own entries do not count
+"""
+
+import json
+import shutil
+import subprocess
+import sys
+import tempfile
+import unittest
+from pathlib import Path
+
+# The CLI modules import each other by bare name; history_builder is found also
+# when run_tests.py imports this module as part of the package
+sys.path.insert(0, str(Path(__file__).parent.parent / "cli"))
+sys.path.insert(0, str(Path(__file__).parent))
+
+import mcpdiff_history as history
+from history_builder import HistoryBuilder, run_mcpdiff
+
+
+class TestCompareConversations(unittest.TestCase):
+    def setUp(self):
+        self.workspace = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        builder = HistoryBuilder(self.workspace)
+        builder.write("a.txt", "a\n")
+        builder.write("shared.txt", "1\n")
+        builder.write("same.txt", "same\n")
+        builder.move("a.txt", "moved.txt")
+        builder.conversation("conv2")
+        self.shared = builder.edit("shared.txt", "2\n")
+        builder.write("same.txt", "same\n")
+        builder.write("b.txt", "b\n")
+        self.rejected = builder.write("dropped.txt", "x\n")
+        self.builder = builder
+
+    def tearDown(self):
+        shutil.rmtree(self.workspace, ignore_errors=True)
+
+    def mcpdiff(self, *args: str) -> subprocess.CompletedProcess:
+        return run_mcpdiff(self.workspace, *args, check=True)
+
+    def test_compare_conversations(self):
+        comparison = history.compare_conversations(
+            self.builder.entries(), "conv1", "conv2"
+        )
+        self.assertEqual(comparison["only_a"], ["a.txt", "moved.txt"])
+        self.assertEqual(comparison["only_b"], ["b.txt", "dropped.txt"])
+        both = {item["file_path"]: item for item in comparison["both"]}
+        self.assertEqual(list(both), ["same.txt", "shared.txt"])
+        self.assertTrue(both["same.txt"]["identical"])
+        self.assertFalse(both["shared.txt"]["identical"])
+        self.assertEqual(
+            both["shared.txt"]["hash_b"], self.builder.entry(self.shared)["hash_after"]
+        )
+
+    def test_rejected_edits_are_left_out(self):
+        self.mcpdiff("reject", "-e", self.rejected)
+        comparison = json.loads(
+            self.mcpdiff(
+                "compare-convs", "--conv-a", "conv1", "--conv-b", "conv2", "--json"
+            ).stdout
+        )
+        self.assertEqual(comparison["only_b"], ["b.txt"])
+
+    def test_output(self):
+        self.mcpdiff("tag", "-c", "conv2", "--name", "parallel")
+        output = self.mcpdiff(
+            "compare-convs", "--conv-a", "conv1", "--conv-b", "parallel"
+        ).stdout
+        self.assertIn("B: conv2", output)
+        self.assertIn("Only in A (2):", output)
+        self.assertRegex(output, r"same\.txt  \(\S*same final content")
+        self.assertRegex(output, r"shared\.txt  \(\S*different final content")
+
+        result = run_mcpdiff(
+            self.workspace, "compare-convs", "--conv-a", "conv1", "--conv-b", "conv9"
+        )
+        self.assertNotEqual(result.returncode, 0)
+        self.assertIn("No conversation found matching 'conv9'", result.stderr)
+
+
+if __name__ == "__main__":
+    unittest.main()
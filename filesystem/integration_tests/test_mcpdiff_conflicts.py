@@ -0,0 +1,152 @@
+#!/usr/bin/env python3
+"""
+Tests for the merge conflict markers edits leave in files (conflict_markers):
+- find_conflict_markers() and resolve_conflict_markers()
+- the blocks recorded on log entries, shown by `status` as [CONFLICT] and by
+  `diagnose` while the file still has them
+- `mcpdiff resolve-conflicts`, listing and interactive
+"""
+
+import json
+import os
+import shutil
+import sys
+import tempfile
+import unittest
+from pathlib import Path
+
+# The CLI modules import each other by bare name; history_builder is found also
+# when run_tests.py imports this module as part of the package
+sys.path.insert(0, str(Path(__file__).parent.parent / "cli"))
+sys.path.insert(0, str(Path(__file__).parent))
+
+import mcpdiff_patch as patch
+from history_builder import HistoryBuilder, run_mcpdiff
+
+
+CONFLICTED = """\
+fn main() {
+<<<<<<< HEAD
+    println!("ours");
+||||||| base
+    println!("base");
+=======
+    println!("theirs");
+>>>>>>> feature
+}
+"""
+
+
+class TestConflictMarkers(unittest.TestCase):
+    def test_find(self):
+        lines = patch.split_lines(CONFLICTED)
+        self.assertEqual(
+            patch.find_conflict_markers(lines), [{"start_line": 2, "end_line": 8}]
+        )
+        # A block needs all three markers, so a heading underline is none
+        self.assertEqual(
+            patch.find_conflict_markers(["Title\n", "=======\n", "text\n"]), []
+        )
+        self.assertEqual(
+            patch.find_conflict_markers(["<<<<<<< a\n", ">>>>>>> b\n"]), []
+        )
+        self.assertEqual(
+            patch.find_conflict_markers(["<<<<<<<<\n", "=======\n", ">>>>>>>\n"]), []
+        )
+
+    def test_resolve(self):
+        self.assertEqual(
+            patch.resolve_conflict_markers(CONFLICTED, "ours"),
+            'fn main() {\n    println!("ours");\n}\n',
+        )
+        self.assertEqual(
+            patch.resolve_conflict_markers(CONFLICTED, "theirs"),
+            'fn main() {\n    println!("theirs");\n}\n',
+        )
+
+
+class TestConflictCommands(unittest.TestCase):
+    def setUp(self):
+        self.workspace = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        self.builder = HistoryBuilder(self.workspace)
+        self.clean = self.builder.write("clean.rs", "fn main() {}\n")
+        self.edit = self.builder.write("main.rs", CONFLICTED)
+
+    def tearDown(self):
+        shutil.rmtree(self.workspace, ignore_errors=True)
+
+    def mcpdiff(self, *args: str, stdin: str = "", env=None):
+        return run_mcpdiff(
+            self.workspace, *args, input=stdin, env=env, check=True
+        ).stdout
+
+    def test_recorded(self):
+        self.assertEqual(
+            self.builder.entry(self.edit)["conflict_markers"],
+            [{"start_line": 2, "end_line": 8}],
+        )
+        self.assertIsNone(self.builder.entry(self.clean)["conflict_markers"])
+        lines = self.mcpdiff("status").splitlines()
+        self.assertIn("[CONFLICT]", next(l for l in lines if "main.rs" in l))
+        self.assertNotIn("[CONFLICT]", next(l for l in lines if "clean.rs" in l))
+
+    def test_diagnose(self):
+        output = self.mcpdiff("diagnose")
+        self.assertIn("(conflict markers at lines 2-8)", output)
+        self.assertIn("1 files have merge conflict markers", output)
+
+        # Once the file changed, the recorded blocks no longer say anything
+        self.builder.path("main.rs").write_text("fn main() {}\n")
+        self.assertNotIn("conflict markers", self.mcpdiff("diagnose"))
+
+    def test_list(self):
+        self.builder.conversation("conv2").write("lib.rs", CONFLICTED)
+        self.builder.path("lib.rs").write_text("fn lib() {}\n")
+        listed = json.loads(self.mcpdiff("resolve-conflicts", "--json"))
+        self.assertEqual(
+            [(item["file_path"], item["state"]) for item in listed],
+            [("lib.rs", "resolved"), ("main.rs", "unresolved")],
+        )
+        self.assertEqual(listed[1]["edit_id"], self.edit)
+        self.assertEqual(listed[1]["live"], [{"start_line": 2, "end_line": 8}])
+
+        listed = json.loads(self.mcpdiff("resolve-conflicts", "-c", "conv2", "--json"))
+        self.assertEqual([item["file_path"] for item in listed], ["lib.rs"])
+        output = self.mcpdiff("resolve-conflicts", "--list")
+        self.assertIn("1 of 2 files still have conflicts.", output)
+
+    def test_interactive(self):
+        output = self.mcpdiff("resolve-conflicts", stdin="x\nt\n")
+        self.assertIn("Invalid choice", output)
+        self.assertIn("Resolved: 1", output)
+        self.assertEqual(
+            self.builder.read("main.rs"), 'fn main() {\n    println!("theirs");\n}\n'
+        )
+        output = self.mcpdiff("resolve-conflicts")
+        self.assertIn("0 of 1 files still have conflicts.", output)
+        self.assertNotIn("Action?", output)
+
+    def test_editor(self):
+        # The "editor" keeps the first and last lines and reports its arguments
+        script = self.workspace / "editor.py"
+        script.write_text(
+            "import sys\n"
+            "path = sys.argv[-1]\n"
+            "lines = open(path).readlines()\n"
+            "open(path, 'w').writelines(lines[:1] + lines[-1:])\n"
+            "print('editing', *sys.argv[1:])\n"
+        )
+        env = dict(os.environ, VISUAL=f"{sys.executable} {script}")
+        output = self.mcpdiff("resolve-conflicts", stdin="e\n", env=env)
+        self.assertIn(f"editing +2 {self.workspace / 'main.rs'}", output)
+        self.assertIn("No conflicts left.", output)
+        self.assertEqual(self.builder.read("main.rs"), "fn main() {\n}\n")
+
+    def test_skip(self):
+        output = self.mcpdiff("resolve-conflicts", stdin="s\n")
+        self.assertIn("Skipped:  1", output)
+        self.assertEqual(self.builder.read("main.rs"), CONFLICTED)
+
+
+if __name__ == "__main__":
+    unittest.main()
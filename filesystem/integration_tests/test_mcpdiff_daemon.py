@@ -0,0 +1,136 @@
+#!/usr/bin/env python3
+"""
+Tests for `mcpdiff daemon`:
+- The in-memory index follows new, changed and removed log files
+- Queries over the socket return what find_all_entries() reads from disk, and
+  counts what status --count would
+- Without a daemon, clients get None and fall back to reading the logs
+"""
+
+import json
+import shutil
+import sys
+import tempfile
+import threading
+import time
+import unittest
+from pathlib import Path
+
+# The CLI modules import each other by bare name; history_builder is found also
+# when run_tests.py imports this module as part of the package
+sys.path.insert(0, str(Path(__file__).parent.parent / "cli"))
+sys.path.insert(0, str(Path(__file__).parent))
+
+import mcpdiff_daemon as daemon
+import mcpdiff_history as history
+import mcpdiff_utils as utils
+from history_builder import make_entry
+
+
+class DaemonTestCase(unittest.TestCase):
+    def setUp(self):
+        self.workspace = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        self.history_root = self.workspace / ".mcp" / utils.HISTORY_DIR_NAME
+        self.logs_dir = self.history_root / utils.LOGS_DIR
+        self.logs_dir.mkdir(parents=True)
+        self.write_log("conv1", [make_entry("e1", "conv1", 1)])
+
+    def tearDown(self):
+        shutil.rmtree(self.workspace, ignore_errors=True)
+
+    def write_log(self, conv_id: str, entries: list) -> None:
+        utils.write_log_file(self.logs_dir / f"{conv_id}.log", entries)
+
+
+class TestLogIndex(DaemonTestCase):
+    def test_follows_log_files(self):
+        index = daemon.LogIndex(self.history_root)
+        self.assertTrue(index.refresh())
+        self.assertEqual(list(index.by_edit_id), ["e1"])
+        self.assertFalse(index.refresh())  # Nothing changed
+
+        self.write_log("conv2", [make_entry("e2", "conv2", 2)])
+        self.write_log(
+            "conv1", [make_entry("e1", "conv1", 1), make_entry("e3", "conv1", 3)]
+        )
+        self.assertTrue(index.refresh())
+        self.assertEqual([e["edit_id"] for e in index.entries], ["e1", "e2", "e3"])
+        self.assertEqual(len(index.by_conversation["conv1"]), 2)
+
+        (self.logs_dir / "conv2.log").unlink()
+        self.assertTrue(index.refresh())
+        self.assertNotIn("e2", index.by_edit_id)
+        self.assertNotIn("conv2", index.by_conversation)
+
+
+@unittest.skipUnless(daemon.is_supported(), "needs fork() and Unix domain sockets")
+class TestDaemonQueries(DaemonTestCase):
+    def setUp(self):
+        super().setUp()
+        self.stop = threading.Event()
+        server = daemon.bind_socket(self.workspace)
+        self.thread = threading.Thread(
+            target=daemon.serve,
+            args=(server, self.history_root, 0.1, self.stop.is_set),
+            daemon=True,
+        )
+        self.thread.start()
+        self.addCleanup(server.close)
+
+    def tearDown(self):
+        self.stop.set()
+        self.thread.join(timeout=5)
+        super().tearDown()
+
+    def test_entries_match_the_logs(self):
+        self.write_log("conv2", [make_entry("e2", "conv2", 2)])
+        deadline = time.monotonic() + 5
+        while len(daemon.fetch_entries(self.workspace)) < 2:
+            self.assertLess(time.monotonic(), deadline, "change never indexed")
+            time.sleep(0.05)
+        self.assertEqual(
+            daemon.fetch_entries(self.workspace),
+            history.find_all_entries(self.history_root),
+        )
+
+    def test_lookup_by_id(self):
+        response = daemon.query(self.workspace, {"op": "entry", "edit_id": "e1"})
+        self.assertEqual(response["entry"]["conversation_id"], "conv1")
+        response = daemon.query(
+            self.workspace, {"op": "entries", "conversation_id": "conv9"}
+        )
+        self.assertEqual(response["entries"], [])
+        self.assertIsNone(daemon.query(self.workspace, {"op": "reboot"}))
+
+    def test_count(self):
+        self.write_log("conv2", [make_entry("e2", "conv2", 2)])
+        deadline = time.monotonic() + 5
+        while daemon.fetch_count(self.workspace, {}) < 2:
+            self.assertLess(time.monotonic(), deadline, "change never indexed")
+            time.sleep(0.05)
+        self.assertEqual(daemon.fetch_count(self.workspace, {"conv_id": "conv2"}), 1)
+        self.assertEqual(
+            daemon.fetch_count(self.workspace, {}, group_by="conversation"),
+            {"conv1": 1, "conv2": 1},
+        )
+        # Refused, so that the client counts itself and reports the error
+        self.assertIsNone(daemon.fetch_count(self.workspace, {"edit_id": "e1"}))
+        self.assertIsNone(daemon.fetch_count(self.workspace, {"since_edit_id": "e1"}))
+
+    def test_second_daemon_is_refused(self):
+        with self.assertRaises(utils.HistoryError):
+            daemon.bind_socket(self.workspace)
+
+
+class TestWithoutDaemon(DaemonTestCase):
+    def test_no_daemon(self):
+        self.assertIsNone(daemon.fetch_entries(self.workspace))
+        self.assertIsNone(daemon.stop(self.workspace, timeout=1))
+
+    def test_stale_socket_file(self):
+        daemon.get_socket_path(self.workspace).write_text(json.dumps({}))
+        self.assertIsNone(daemon.fetch_entries(self.workspace))
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,136 @@
+#!/usr/bin/env python3
+"""
+Tests for `mcpdiff diff-since`:
+- One diff per file edited after --since, from the file then to the file with
+  the edits accepted since applied; pending and rejected ones are left out
+- Created, deleted and moved files diff from or to nothing
+- --conv leaves out the edits of other conversations since then
+- --since-snapshot diffs from the contents the snapshot recorded
+"""
+
+import json
+import re
+import shutil
+import sys
+import tempfile
+import unittest
+from datetime import datetime, timedelta, timezone
+from pathlib import Path
+
+# The CLI modules import each other by bare name; history_builder is found also
+# when run_tests.py imports this module as part of the package
+sys.path.insert(0, str(Path(__file__).parent.parent / "cli"))
+sys.path.insert(0, str(Path(__file__).parent))
+
+from history_builder import HistoryBuilder, run_mcpdiff
+
+
+# Entries are logged a second apart from 2025-01-01T00:00:00Z
+SINCE = "2025-01-01T00:00:02Z"
+
+
+class TestDiffSince(unittest.TestCase):
+    def setUp(self):
+        self.workspace = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        self.builder = HistoryBuilder(self.workspace)
+        self.builder.write("a.txt", "one\ntwo\nthree\n")
+        self.builder.write("old.txt", "old\n")
+        self.builder.write("gone.txt", "gone\n")
+        self.mcpdiff("accept", "-c", "conv1")
+
+    def tearDown(self):
+        shutil.rmtree(self.workspace, ignore_errors=True)
+
+    def mcpdiff(self, *args: str, returncode: int = 0) -> str:
+        result = run_mcpdiff(self.workspace, *args, input="y\n")
+        self.assertEqual(result.returncode, returncode, result.stdout + result.stderr)
+        return result.stdout
+
+    def diffs(self, *args: str) -> dict:
+        output = self.mcpdiff("diff-since", *args, "--json")
+        return {d["file_path"]: d["diff"] for d in json.loads(output)}
+
+    def test_since_time(self):
+        self.builder.edit("a.txt", "one\nTWO\nthree\n")
+        self.builder.write("new.txt", "new\n")
+        self.builder.move("old.txt", "renamed.txt")
+        self.builder.delete("gone.txt")
+        self.mcpdiff("accept", "-c", "conv1")
+        pending = self.builder.edit("a.txt", "one\nTWO\nthree\nfour\n")
+
+        diffs = self.diffs("--since", SINCE)
+        self.assertEqual(
+            diffs["a.txt"],
+            "--- a/a.txt\n+++ b/a.txt\n@@ -1,3 +1,3 @@\n one\n-two\n+TWO\n three\n",
+        )
+        self.assertEqual(
+            diffs["new.txt"], "--- a/new.txt\n+++ b/new.txt\n@@ -0,0 +1 @@\n+new\n"
+        )
+        self.assertIn("-gone\n", diffs["gone.txt"])
+        self.assertIn("-old\n", diffs["old.txt"])
+        self.assertIn("+old\n", diffs["renamed.txt"])
+
+        output = re.sub(r"\x1b\[\d+m", "", self.mcpdiff("diff-since", "--since", SINCE))
+        self.assertIn("--- a.txt (2 edit(s)) ---", output)
+        self.assertIn("5 of 5 file(s) edited since then changed.", output)
+        # Accepted, the pending edit is part of the diff; rejected, it is not
+        self.mcpdiff("accept", "-e", pending)
+        self.assertIn("+four\n", self.diffs("--since", SINCE)["a.txt"])
+        self.mcpdiff("reject", "-e", pending)
+        self.assertNotIn("+four\n", self.diffs("--since", SINCE)["a.txt"])
+
+    def test_conv(self):
+        self.builder.edit("a.txt", "ONE\ntwo\nthree\n")
+        self.builder.conversation("conv2").edit("a.txt", "ONE\ntwo\nTHREE\n")
+        self.builder.write("b.txt", "bee\n")
+        self.mcpdiff("accept", "-c", "conv1")
+        self.mcpdiff("accept", "-c", "conv2")
+        diffs = self.diffs("--since", SINCE, "-c", "conv1")
+        self.assertEqual(list(diffs), ["a.txt"])
+        self.assertIn("-one\n+ONE\n two\n three\n", diffs["a.txt"])
+        diffs = self.diffs("--since", SINCE)
+        self.assertEqual(sorted(diffs), ["a.txt", "b.txt"])
+        self.assertIn("-three\n+THREE\n", diffs["a.txt"])
+
+    def test_nothing_since(self):
+        output = self.mcpdiff("diff-since", "--since", "2030-01-01T00:00:00+02:00")
+        self.assertIn("No edits found since then.", output)
+        self.mcpdiff("diff-since", "--since", "yesterday", returncode=1)
+
+
+class TestDiffSinceSnapshot(unittest.TestCase):
+    def setUp(self):
+        self.workspace = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        # Logged before the snapshot; the builder's clock ticks a second per entry
+        start = datetime.now(timezone.utc) - timedelta(minutes=1)
+        self.builder = HistoryBuilder(self.workspace, start=start)
+        self.builder.write("a.txt", "one\n")
+        self.builder.write("b.txt", "bee\n")
+
+    def tearDown(self):
+        shutil.rmtree(self.workspace, ignore_errors=True)
+
+    def mcpdiff(self, *args: str) -> str:
+        return run_mcpdiff(self.workspace, *args, input="y\n", check=True).stdout
+
+    def test_since_snapshot(self):
+        # Changed outside the history: only the snapshot has this content
+        (self.workspace / "a.txt").write_text("one\nlocal\n")
+        snapshot_id = json.loads(self.mcpdiff("snapshot", "--json"))["edit_id"]
+        later = HistoryBuilder(
+            self.workspace, "conv2", start=datetime.now(timezone.utc) + timedelta(1)
+        )
+        later.edit("a.txt", "two\n")
+        self.mcpdiff("accept", "-c", "conv2")
+        output = self.mcpdiff(
+            "diff-since", "--since-snapshot", snapshot_id[:8], "--json"
+        )
+        [diff] = json.loads(output)
+        self.assertEqual(
+            diff["diff"],
+            "--- a/a.txt\n+++ b/a.txt\n@@ -1,2 +1 @@\n-one\n-local\n+two\n",
+        )
+
+
+if __name__ == "__main__":
+    unittest.main()
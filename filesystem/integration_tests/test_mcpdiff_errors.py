@@ -0,0 +1,225 @@
+#!/usr/bin/env python3
+"""
+Tests for the kinds of HistoryError and the exit codes they map to:
+- A held conversation lock gives LockTimeoutError
+- Diffs that do not apply, missing diffs and missing checkpoints are told apart
+- Declining to overwrite an externally modified file gives ExternalModificationError
+- A strict read of a log with a broken line gives CorruptLogError, as does
+  one with a boolean schema_version
+- --json output carries the error kind
+"""
+
+import json
+import shutil
+import subprocess
+import sys
+import tempfile
+import unittest
+from pathlib import Path
+
+# The CLI modules import each other by bare name; history_builder is found also
+# when run_tests.py imports this module as part of the package
+sys.path.insert(0, str(Path(__file__).parent.parent / "cli"))
+sys.path.insert(0, str(Path(__file__).parent))
+
+import mcpdiff_history as history
+import mcpdiff_migration as migration
+import mcpdiff_utils as utils
+from history_builder import HistoryBuilder, run_mcpdiff
+
+LINES = [f"line {i}\n" for i in range(1, 21)]
+
+
+class ErrorTestCase(unittest.TestCase):
+    def setUp(self):
+        self.workspace = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        self.builder = HistoryBuilder(self.workspace)
+
+    def tearDown(self):
+        shutil.rmtree(self.workspace, ignore_errors=True)
+
+    def mcpdiff(
+        self, *args: str, confirm: bool = True
+    ) -> subprocess.CompletedProcess:
+        return run_mcpdiff(self.workspace, *args, input="y\n" if confirm else "n\n")
+
+    def reconstruct(self, file_path: str) -> dict:
+        return history.reconstruct_file_from_history(
+            file_path,
+            self.builder.entries(),
+            self.workspace,
+            self.builder.history_root,
+        )
+
+    def two_edits(self) -> tuple:
+        lines = list(LINES)
+        self.builder.write("a.txt", "".join(lines))
+        lines[1] = "two\n"
+        first = self.builder.edit("a.txt", "".join(lines))
+        lines[17] = "eighteen\n"
+        second = self.builder.edit("a.txt", "".join(lines))
+        return first, second
+
+
+class TestErrorKinds(unittest.TestCase):
+    def test_exit_codes_are_distinct(self):
+        kinds = [
+            utils.HistoryError,
+            utils.LockTimeoutError,
+            utils.MissingCheckpointError,
+            utils.MissingDiffError,
+            utils.PatchConflictError,
+            utils.ExternalModificationError,
+            utils.CorruptLogError,
+            utils.AmbiguousIDError,
+            utils.InvalidLogEntryError,
+            utils.VerificationError,
+        ]
+        codes = [kind.exit_code for kind in kinds]
+        self.assertEqual(len(set(codes)), len(codes))
+        self.assertNotIn(2, codes)  # Unexpected errors
+
+    def test_to_dict(self):
+        error = utils.PatchConflictError("no", file="a.txt", hunk=2, edit_id=None)
+        self.assertEqual(
+            error.to_dict(),
+            {"kind": "patch_conflict", "message": "no", "file": "a.txt", "hunk": 2},
+        )
+        self.assertEqual(error.hunk, 2)
+        self.assertIsInstance(utils.LockTimeoutError("x"), TimeoutError)
+
+
+class TestLockTimeout(ErrorTestCase):
+    def test_held_lock(self):
+        edit_id = self.builder.write("a.txt", "one\n")
+        with utils.ConversationLock(self.builder.history_root, "conv1"):
+            with self.assertRaises(utils.LockTimeoutError) as cm:
+                utils.ConversationLock(self.builder.history_root, "conv1").acquire(
+                    0.2
+                )
+            self.assertIn("conv1.log.lock", cm.exception.lock_path)
+
+            result = self.mcpdiff("--timeout", "0.5", "accept", "-e", edit_id)
+        self.assertEqual(result.returncode, utils.LockTimeoutError.exit_code)
+        self.assertEqual(self.builder.entry(edit_id)["status"], "pending")
+
+
+class TestReconstructErrors(ErrorTestCase):
+    def test_patch_conflict(self):
+        first, _ = self.two_edits()
+        # A change the history does not know about ends up in the third diff's
+        # context, so that diff only applies on top of it
+        content = self.builder.read("a.txt").replace("line 16", "line sixteen")
+        self.builder.path("a.txt").write_text(content, encoding="utf-8")
+        lines = content.splitlines(keepends=True)
+        lines[13] = "fourteen\n"
+        third = self.builder.edit("a.txt", "".join(lines))
+        history.update_entry_status(
+            self.builder.entry(first), "rejected", self.builder.history_root
+        )
+
+        result = self.reconstruct("a.txt")
+        error = result["exception"]
+        self.assertIsInstance(error, utils.PatchConflictError)
+        self.assertEqual((error.file, error.hunk, error.edit_id), ("a.txt", 1, third))
+        # Found before applying: the lines the hunk expected, and the first
+        # one that differs
+        self.assertIn("hunk #1 at lines 11-17: line 16 is `line 16`", str(error))
+        [conflict] = error.conflicts
+        self.assertEqual((conflict["start_line"], conflict["end_line"]), (11, 17))
+        self.assertEqual(conflict["first_difference"], 16)
+        self.assertEqual(conflict["expected_lines"][5], "line sixteen")
+        self.assertEqual(error.to_dict()["conflicts"], error.conflicts)
+
+    def test_missing_diff(self):
+        first, second = self.two_edits()
+        (self.builder.history_root / self.builder.entry(second)["diff_file"]).unlink()
+        result = self.mcpdiff("reject", "-e", first)
+        self.assertEqual(result.returncode, utils.MissingDiffError.exit_code)
+        self.assertIn("missing diff file", result.stderr)
+
+    def test_missing_checkpoint(self):
+        self.builder.path("a.txt").write_text("".join(LINES), encoding="utf-8")
+        lines = list(LINES)
+        lines[1] = "two\n"
+        edit_id = self.builder.edit("a.txt", "".join(lines))
+        checkpoint = self.builder.entry(edit_id)["checkpoint_file"]
+        (self.builder.history_root / checkpoint).unlink()
+
+        result = self.reconstruct("a.txt")
+        self.assertIsInstance(result["exception"], utils.MissingCheckpointError)
+        self.assertEqual(result["exception"].checkpoint_file, checkpoint)
+        result = self.mcpdiff("reject", "-e", edit_id)
+        self.assertEqual(result.returncode, utils.MissingCheckpointError.exit_code)
+
+
+class TestExternalModification(ErrorTestCase):
+    def test_declined_overwrite(self):
+        edit_id = self.builder.write("a.txt", "one\n")
+        self.builder.path("a.txt").write_text("changed\n", encoding="utf-8")
+        result = self.mcpdiff("accept", "-e", edit_id, confirm=False)
+        self.assertEqual(result.returncode, utils.ExternalModificationError.exit_code)
+        self.assertEqual(self.builder.read("a.txt"), "changed\n")
+
+
+class TestCorruptLogError(ErrorTestCase):
+    def test_strict_read(self):
+        self.builder.write("a.txt", "one\n")
+        log_file = self.builder.log_file()
+        with open(log_file, "a", encoding="utf-8") as f:
+            f.write("not json\n")
+
+        self.assertEqual(len(utils.read_log_file(log_file)), 1)
+        with self.assertRaises(utils.CorruptLogError) as cm:
+            utils.read_log_file(log_file, strict=True)
+        self.assertEqual((cm.exception.path, cm.exception.line), (str(log_file), 2))
+
+    def test_boolean_schema_version(self):
+        """true is no version, although Python would compare it equal to 1."""
+        edit_id = self.builder.write("a.txt", "one\n")
+        self.builder.edit("a.txt", "two\n")
+        log_file = self.builder.log_file()
+        entries = utils.read_log_file(log_file, migrate=False)
+        entries[0]["schema_version"] = True
+        log_file.write_text(
+            "".join(json.dumps(e) + "\n" for e in entries), encoding="utf-8"
+        )
+
+        with self.assertRaises(migration.SchemaVersionError):
+            migration.default_migrator.upgrade(entries[0])
+        self.assertNotIn(
+            edit_id, [e["edit_id"] for e in utils.read_log_file(log_file)]
+        )
+        with self.assertRaises(utils.CorruptLogError) as cm:
+            utils.read_log_file(log_file, migrate=False, strict=True)
+        self.assertEqual(cm.exception.line, 1)
+        content = log_file.read_text(encoding="utf-8")
+        result = self.mcpdiff("upgrade-schema")
+        self.assertEqual(result.returncode, utils.CorruptLogError.exit_code)
+        self.assertEqual(log_file.read_text(encoding="utf-8"), content)
+
+    def test_upgrade_schema_keeps_the_log(self):
+        self.builder.write("a.txt", "one\n")
+        log_file = self.builder.log_file()
+        with open(log_file, "a", encoding="utf-8") as f:
+            f.write("not json\n")
+        content = log_file.read_text(encoding="utf-8")
+        result = self.mcpdiff("upgrade-schema")
+        self.assertEqual(result.returncode, utils.CorruptLogError.exit_code)
+        self.assertEqual(log_file.read_text(encoding="utf-8"), content)
+
+
+class TestJsonError(ErrorTestCase):
+    def test_error_kind(self):
+        self.builder.write("a.txt", "one\n")
+        result = self.mcpdiff(
+            "status", "--json", "--conv", "conv1", "--since-edit-id", "nope"
+        )
+        self.assertEqual(result.returncode, utils.HistoryError.exit_code)
+        error = json.loads(result.stdout)["error"]
+        self.assertEqual(error["kind"], "history_error")
+        self.assertIn("nope", error["message"])
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,120 @@
+#!/usr/bin/env python3
+"""
+Tests for expiring old pending edits:
+- `mcpdiff expire-old --older-than` rejects pending edits older than the
+  duration in every conversation and re-applies their files, and reports them
+- Files changed outside the history are left alone
+- The daemon does the same on a timer when auto_expire_days is set
+"""
+
+import shutil
+import sys
+import tempfile
+import threading
+import unittest
+from datetime import datetime, timedelta, timezone
+from pathlib import Path
+
+# The CLI modules import each other by bare name; history_builder is found also
+# when run_tests.py imports this module as part of the package
+sys.path.insert(0, str(Path(__file__).parent.parent / "cli"))
+sys.path.insert(0, str(Path(__file__).parent))
+
+import mcpdiff_config as config
+import mcpdiff_daemon as daemon
+import mcpdiff_history as history
+import mcpdiff_utils as utils
+from history_builder import HistoryBuilder, run_mcpdiff
+
+
+class ExpireTestCase(unittest.TestCase):
+    def setUp(self):
+        self.workspace = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        # conv1 from 2025, conv2 from an hour ago
+        old = HistoryBuilder(self.workspace)
+        old.write("a.txt", "one\n")
+        history.update_entry_status(old.entries()[0], "accepted", old.history_root)
+        self.old_edit = old.edit("a.txt", "two\n")
+        recent = HistoryBuilder(
+            self.workspace,
+            conversation_id="conv2",
+            start=datetime.now(timezone.utc) - timedelta(hours=1),
+        )
+        self.recent_edit = recent.write("b.txt", "b\n")
+        self.builder = old
+
+    def tearDown(self):
+        shutil.rmtree(self.workspace, ignore_errors=True)
+
+    def status(self, edit_id: str) -> str:
+        return self.builder.entry(edit_id)["status"]
+
+
+class TestExpireOld(ExpireTestCase):
+    def test_expires_old_edits(self):
+        result = run_mcpdiff(self.workspace, "expire-old", "--older-than", "1d")
+        self.assertEqual(result.returncode, 0, result.stdout + result.stderr)
+        self.assertIn(self.old_edit[:8], result.stdout)
+        self.assertIn("Expired 1 pending edits older than 1d", result.stdout)
+        self.assertEqual(self.status(self.old_edit), "rejected")
+        self.assertEqual(self.builder.read("a.txt"), "one\n")
+        self.assertEqual(self.status(self.recent_edit), "pending")
+
+        run_mcpdiff(self.workspace, "expire-old", "--older-than", "30m")
+        self.assertEqual(self.status(self.recent_edit), "rejected")
+
+    def test_dry_run(self):
+        result = run_mcpdiff(
+            self.workspace, "expire-old", "--older-than", "1d", "--dry-run"
+        )
+        self.assertIn("Would expire 1 pending edits", result.stdout)
+        self.assertEqual(self.status(self.old_edit), "pending")
+
+    def test_modified_file_is_left_alone(self):
+        self.builder.path("a.txt").write_text("mine\n", encoding="utf-8")
+        result = run_mcpdiff(self.workspace, "expire-old", "--older-than", "1d")
+        self.assertEqual(result.returncode, utils.HistoryError.exit_code)
+        self.assertIn("skipped: modified outside the history", result.stdout)
+        self.assertEqual(self.status(self.old_edit), "pending")
+        self.assertEqual(self.builder.read("a.txt"), "mine\n")
+
+    def test_invalid_duration(self):
+        result = run_mcpdiff(self.workspace, "expire-old", "--older-than", "soon")
+        self.assertEqual(result.returncode, utils.HistoryError.exit_code)
+
+
+class TestAutoExpire(ExpireTestCase):
+    def test_setting(self):
+        self.assertEqual(daemon.expire_pending_edits(self.builder.history_root), 0)
+        with self.assertRaises(utils.HistoryError):
+            config.set_config_value(self.workspace, "auto_expire_days", "-1")
+        config.set_config_value(self.workspace, "auto_expire_days", "7")
+        self.assertEqual(daemon.expire_pending_edits(self.builder.history_root), 1)
+        self.assertEqual(self.status(self.old_edit), "rejected")
+        self.assertEqual(self.status(self.recent_edit), "pending")
+
+    @unittest.skipUnless(daemon.is_supported(), "needs fork() and Unix sockets")
+    def test_daemon_timer(self):
+        config.set_config_value(self.workspace, "auto_expire_days", "1")
+        server = daemon.bind_socket(self.workspace)
+        self.addCleanup(server.close)
+        stop = threading.Event()
+        thread = threading.Thread(
+            target=daemon.serve,
+            args=(server, self.builder.history_root, 0.1, stop.is_set),
+        )
+        thread.start()
+        try:
+            for _ in range(100):
+                if self.status(self.old_edit) == "rejected":
+                    break
+                stop.wait(0.1)
+        finally:
+            stop.set()
+            thread.join(timeout=10)
+        self.assertEqual(self.status(self.old_edit), "rejected")
+        self.assertEqual(self.builder.read("a.txt"), "one\n")
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,119 @@
+#!/usr/bin/env python3
+"""
+Tests for forked conversations (parent_conversation_id and forked_at):
+- The lineage of a conversation and the entries of its ancestors up to each fork
+- `mcpdiff conversations --tree`, as text and JSON
+- Re-applying a fork on its lineage with reapply_conversation_state()
+"""
+
+import json
+import shutil
+import sys
+import tempfile
+import unittest
+from pathlib import Path
+
+# The CLI modules import each other by bare name; history_builder is found
+# also when run_tests.py imports this module as part of the package
+sys.path.insert(0, str(Path(__file__).parent.parent / "cli"))
+sys.path.insert(0, str(Path(__file__).parent))
+
+from history_builder import HistoryBuilder, run_mcpdiff
+import mcpdiff_api as api
+import mcpdiff_history as history
+import mcpdiff_migration as migration
+
+
+class TestConversationForks(unittest.TestCase):
+    def setUp(self):
+        self.workspace = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        self.builder = builder = HistoryBuilder(self.workspace)
+        self.history_root = builder.history_root
+        self.e1 = builder.write("a.txt", "1\n")
+        self.e2 = builder.edit("a.txt", "1\n2\n")
+        self.e3 = builder.edit("a.txt", "1\n2\n3\n")
+        # conv2 continues from conv1's second edit
+        builder.path("a.txt").write_text("1\n2\n")
+        self.f1 = builder.fork("conv2", "conv1", self.e2).edit("a.txt", "1\n2\nf\n")
+        # conv3 continues from conv2's state when it was forked
+        self.g1 = builder.fork("conv3", "conv2").edit("a.txt", "1\n2\nf\ng\n")
+        self.f2 = builder.conversation("conv2").write("b.txt", "late\n")
+        builder.conversation("conv4").write("c.txt", "c\n")
+        self.entries = builder.entries()
+
+    def tearDown(self):
+        shutil.rmtree(self.workspace, ignore_errors=True)
+
+    def mcpdiff(self, *args: str) -> str:
+        return run_mcpdiff(self.workspace, *args, check=True).stdout
+
+    def test_lineage(self):
+        self.assertEqual(
+            history.conversation_lineage(self.entries, "conv3"),
+            [("conv2", None), ("conv1", self.e2)],
+        )
+        self.assertEqual(history.conversation_lineage(self.entries, "conv1"), [])
+        self.assertEqual(
+            [e["edit_id"] for e in history.lineage_entries(self.entries, "conv3")],
+            [self.e1, self.e2, self.f1, self.g1],
+        )
+
+    def test_parent_cycle(self):
+        entries = [
+            {"conversation_id": "x", "parent_conversation_id": "y"},
+            {"conversation_id": "y", "parent_conversation_id": "x"},
+            {"conversation_id": "z", "parent_conversation_id": "z"},
+        ]
+        self.assertEqual(history.conversation_lineage(entries, "x"), [("y", None)])
+        self.assertEqual(history.conversation_lineage(entries, "z"), [])
+        summaries = [{"conversation_id": c} for c in "xy"]
+        for summary, parent in zip(summaries, "yx"):
+            summary["parent_conversation_id"] = parent
+        self.assertEqual(
+            [(d, s["conversation_id"]) for d, s in history.conversation_tree(summaries)],
+            [(0, "x"), (1, "y")],
+        )
+
+    def test_conversations_tree(self):
+        conversations = json.loads(self.mcpdiff("conversations", "--tree", "--json"))
+        self.assertEqual(
+            [(c["depth"], c["conversation_id"]) for c in conversations],
+            [(0, "conv4"), (0, "conv1"), (1, "conv2"), (2, "conv3")],
+        )
+        self.assertEqual(
+            (conversations[2]["parent_conversation_id"], conversations[2]["forked_at"]),
+            ("conv1", self.e2),
+        )
+        lines = self.mcpdiff("convs", "--tree").splitlines()
+        self.assertTrue(any("└─ conv2" in line for line in lines))
+        self.assertTrue(any("   └─ conv3" in line for line in lines))
+
+        # Without --tree, lineage is only in the fields
+        flat = json.loads(self.mcpdiff("convs", "--json"))
+        self.assertNotIn("depth", flat[0])
+        self.assertIsNone(flat[0]["parent_conversation_id"])
+
+    def test_reapply_on_lineage(self):
+        self.builder.path("a.txt").write_text("clobbered\n")
+        results = api.reapply_conversation_state(
+            self.workspace, self.history_root, "conv3", lineage=True
+        )
+        self.assertIsNone(results["a.txt"]["error"])
+        self.assertEqual(self.builder.read("a.txt"), "1\n2\nf\ng\n")
+
+        api.reapply_file_state(
+            self.workspace, self.history_root, "a.txt", lineage_of="conv1"
+        )
+        self.assertEqual(self.builder.read("a.txt"), "1\n2\n3\n")
+
+    def test_older_entries(self):
+        entry = migration.default_migrator.upgrade(
+            {"edit_id": "e1", "schema_version": 4}
+        )
+        self.assertIsNone(entry["parent_conversation_id"])
+        self.assertIsNone(entry["forked_at"])
+        self.assertIsNone(api.LogEntry.from_dict(entry).parent_conversation_id)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,135 @@
+#!/usr/bin/env python3
+"""
+Tests for `mcpdiff accept --git-stage` and the config key auto_git_stage:
+- Accepted files git tracks are staged, sources of moves and renames included
+- Untracked files and workspaces outside a git repository are skipped quietly
+- A failing `git add` is warned about but does not fail the accept
+- Accepts run in one process stage only the files they accepted themselves
+"""
+
+import contextlib
+import io
+import shutil
+import subprocess
+import sys
+import tempfile
+import unittest
+from pathlib import Path
+from unittest import mock
+
+# The CLI modules import each other by bare name; history_builder is found also
+# when run_tests.py imports this module as part of the package
+sys.path.insert(0, str(Path(__file__).parent.parent / "cli"))
+sys.path.insert(0, str(Path(__file__).parent))
+
+import mcpdiff
+import mcpdiff_config as config
+import mcpdiff_utils as utils
+from history_builder import HistoryBuilder, run_mcpdiff
+
+
+@unittest.skipIf(shutil.which("git") is None, "git is not installed")
+class TestGitStage(unittest.TestCase):
+    def setUp(self):
+        self.workspace = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        self.builder = HistoryBuilder(self.workspace)
+        self.builder.write("a.txt", "one\n")
+        self.builder.write("b.txt", "bee\n")
+        self.git("init", "-q")
+        self.git("add", "a.txt", "b.txt")
+        self.git("-c", "user.name=t", "-c", "user.email=t@t", "commit", "-qm", "init")
+
+    def tearDown(self):
+        shutil.rmtree(self.workspace, ignore_errors=True)
+
+    def git(self, *args: str) -> str:
+        result = subprocess.run(
+            ["git", *args],
+            cwd=self.workspace,
+            capture_output=True,
+            text=True,
+            check=True,
+        )
+        return result.stdout
+
+    def staged(self):
+        return self.git("diff", "--cached", "--name-status").splitlines()
+
+    def mcpdiff(self, *args: str) -> subprocess.CompletedProcess:
+        return run_mcpdiff(self.workspace, *args, input="y\n")
+
+    def test_stage_accepted_files(self):
+        edit = self.builder.edit("a.txt", "two\n")
+        self.builder.edit("b.txt", "bee 2\n")
+        self.builder.write("new.txt", "new\n")
+        result = self.mcpdiff("accept", "-e", edit, "--git-stage")
+        self.assertEqual(result.returncode, 0, result.stdout + result.stderr)
+        self.assertIn("Staged 1 file(s)", result.stdout)
+        self.assertEqual(self.staged(), ["M\ta.txt"])
+        # new.txt is not tracked and stays so, without a warning
+        result = self.mcpdiff("accept", "-c", "conv1", "--git-stage")
+        self.assertEqual(result.returncode, 0, result.stdout + result.stderr)
+        self.assertEqual(self.staged(), ["M\ta.txt", "M\tb.txt"])
+        self.assertNotIn("WARNING", result.stderr)
+
+    def test_accepts_in_one_process(self):
+        first = self.builder.edit("a.txt", "two\n")
+        second = self.builder.edit("b.txt", "bee 2\n")
+        self.accept_in_process(first)
+        self.assertEqual(self.staged(), ["M\ta.txt"])
+        self.git("reset", "-q")
+        # a.txt, accepted by the first call, is not staged again
+        self.accept_in_process(second)
+        self.assertEqual(self.staged(), ["M\tb.txt"])
+
+    def accept_in_process(self, edit_id: str) -> None:
+        argv = ["mcpdiff", "-w", str(self.workspace), "accept", "-e", edit_id]
+        # Keep main() from setting the log level for the rest of the tests
+        with (
+            mock.patch.object(utils.log, "setLevel"),
+            mock.patch.object(sys, "argv", argv + ["--git-stage"]),
+            contextlib.redirect_stdout(io.StringIO()),
+            self.assertRaises(SystemExit) as exited,
+        ):
+            mcpdiff.main()
+        self.assertEqual(exited.exception.code, 0)
+
+    def test_config_and_moves(self):
+        self.builder.move("b.txt", "c.txt")
+        result = self.mcpdiff("accept", "-c", "conv1")
+        self.assertEqual(result.returncode, 0, result.stdout + result.stderr)
+        self.assertEqual(self.staged(), [])
+        self.git("reset", "-q")
+        self.builder.move("a.txt", "d.txt")
+        config.set_config_value(self.workspace, "auto_git_stage", "true")
+        result = self.mcpdiff("accept", "-c", "conv1")
+        self.assertEqual(result.returncode, 0, result.stdout + result.stderr)
+        # The deletion of the source is staged; d.txt is untracked
+        self.assertEqual(self.staged(), ["D\ta.txt"])
+
+    def test_failing_git_add(self):
+        edit = self.builder.edit("a.txt", "two\n")
+        (self.workspace / ".git" / "index.lock").write_text("")
+        result = self.mcpdiff("accept", "-e", edit, "--git-stage")
+        self.assertEqual(result.returncode, 0, result.stdout + result.stderr)
+        self.assertIn("Could not stage a.txt", result.stderr)
+        self.assertEqual(self.builder.entry(edit)["status"], "accepted")
+
+    def test_outside_git(self):
+        shutil.rmtree(self.workspace / ".git")
+        edit = self.builder.edit("a.txt", "two\n")
+        result = self.mcpdiff("accept", "-e", edit, "--git-stage")
+        self.assertEqual(result.returncode, 0, result.stdout + result.stderr)
+        self.assertNotIn("Staged", result.stdout)
+        self.assertNotIn("WARNING", result.stderr)
+
+    def test_config_value(self):
+        parse = config.CONFIG_KEYS["auto_git_stage"]["parse"]
+        self.assertTrue(parse("true"))
+        self.assertFalse(parse("off"))
+        with self.assertRaises(ValueError):
+            parse("maybe")
+
+
+if __name__ == "__main__":
+    unittest.main()
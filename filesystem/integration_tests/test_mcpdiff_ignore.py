@@ -0,0 +1,211 @@
+#!/usr/bin/env python3
+"""
+Tests for .mcp/ignore:
+- gitignore-style patterns: anchoring, `**`, directory-only rules and negation
+- `status` hides edits of ignored paths unless --no-ignore
+- `reject` leaves ignored files alone, with a notice, unless --no-ignore
+- Edits of ignored files have no checkpoint and an empty diff and are logged
+  as ignored, and re-applies leave the files as they are, by that flag rather
+  than the current .mcp/ignore
+- `ignore add` appends a pattern
+"""
+
+import json
+import shutil
+import subprocess
+import sys
+import tempfile
+import unittest
+from pathlib import Path
+
+# The CLI modules import each other by bare name; history_builder is found also
+# when run_tests.py imports this module as part of the package
+sys.path.insert(0, str(Path(__file__).parent.parent / "cli"))
+sys.path.insert(0, str(Path(__file__).parent))
+
+import mcpdiff_api as api
+import mcpdiff_ignore as ignore
+from history_builder import HistoryBuilder, run_mcpdiff
+
+
+class TestIgnoreMatcher(unittest.TestCase):
+    def assertIgnored(self, patterns: list, ignored: list, kept: list):
+        matcher = ignore.IgnoreMatcher(patterns)
+        for path in ignored:
+            self.assertTrue(matcher.matches(path), f"{patterns} should ignore {path}")
+        for path in kept:
+            self.assertFalse(matcher.matches(path), f"{patterns} should keep {path}")
+
+    def test_double_star_directory(self):
+        self.assertIgnored(
+            ["**/target/**"],
+            ["target/debug/app", "crates/core/target/x.rs"],
+            ["target", "targets/x", "src/target.rs"],
+        )
+
+    def test_anchored(self):
+        self.assertIgnored(["/Cargo.lock"], ["Cargo.lock"], ["sub/Cargo.lock"])
+        self.assertIgnored(
+            ["docs/*.md"], ["docs/a.md"], ["docs/sub/a.md", "x/docs/a.md"]
+        )
+        self.assertIgnored(
+            ["a/**/b.txt"], ["a/b.txt", "a/x/y/b.txt"], ["b.txt", "x/a/b.txt"]
+        )
+
+    def test_unanchored_name(self):
+        self.assertIgnored(
+            ["*.lock", "gen?.py"],
+            ["yarn.lock", "a/b/poetry.lock", "src/gen1.py"],
+            ["lock", "src/gen10.py", "yarn.lock.txt"],
+        )
+
+    def test_directory_only(self):
+        self.assertIgnored(["build/"], ["build/a", "src/build/b.o"], ["build"])
+
+    def test_negation(self):
+        self.assertIgnored(
+            ["*.gen.py", "!keep.gen.py"], ["a/b.gen.py"], ["a/keep.gen.py"]
+        )
+        # Last match wins
+        self.assertIgnored(["!a.txt", "*.txt"], ["a.txt"], [])
+        # Nothing under an ignored directory can be re-included
+        self.assertIgnored(["out/", "!out/keep.txt"], ["out/keep.txt"], [])
+        self.assertIgnored(["out/*", "!out/keep.txt"], ["out/x"], ["out/keep.txt"])
+
+    def test_comments_escapes_and_classes(self):
+        self.assertIgnored(
+            ["# comment", "", "\\#hash", "\\!bang", "file[0-9].txt", "x[!a].c"],
+            ["#hash", "!bang", "file3.txt", "xb.c"],
+            ["# comment", "bang", "filea.txt", "xa.c"],
+        )
+
+    def test_windows_separators(self):
+        self.assertIgnored(["**/target/**"], ["a\\target\\b.rs"], [])
+
+    def test_moves(self):
+        matcher = ignore.IgnoreMatcher(["*.lock"])
+        self.assertTrue(
+            matcher.matches_entry({"file_path": "a.txt", "source_path": "a.lock"})
+        )
+
+
+class TestIgnoreFile(unittest.TestCase):
+    def setUp(self):
+        self.workspace = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        self.builder = HistoryBuilder(self.workspace)
+        self.builder.write("Cargo.lock", "v1\n")
+        self.lock_edit = self.builder.edit("Cargo.lock", "v2\n")
+        self.builder.write("src/main.rs", "fn main() {}\n")
+        ignore.get_ignore_path(self.workspace).write_text(
+            "# generated\n/Cargo.lock\n", encoding="utf-8"
+        )
+
+    def tearDown(self):
+        shutil.rmtree(self.workspace, ignore_errors=True)
+
+    def mcpdiff(self, *args: str) -> subprocess.CompletedProcess:
+        return run_mcpdiff(self.workspace, *args, input="y\n", check=True)
+
+    def status_paths(self, *args: str) -> set:
+        entries = json.loads(self.mcpdiff("status", "--json", *args).stdout)
+        return {e["file_path"] for e in entries}
+
+    def test_status(self):
+        self.assertEqual(self.status_paths(), {"src/main.rs"})
+        self.assertEqual(
+            self.status_paths("--no-ignore"), {"Cargo.lock", "src/main.rs"}
+        )
+        result = self.mcpdiff("status")
+        self.assertIn("2 entries of paths in .mcp/ignore hidden", result.stdout)
+
+    def test_reject_conversation(self):
+        result = self.mcpdiff("reject", "-c", "conv1")
+        self.assertIn("Skipping files listed in .mcp/ignore", result.stdout)
+        self.assertIn("2 skipped: ignored", result.stdout)
+        self.assertEqual(self.builder.read("Cargo.lock"), "v2\n")
+        statuses = {
+            e["file_path"]: e["status"]
+            for e in self.builder.entries()
+            if e["tool_name"] != "mcpdiff"
+        }
+        self.assertEqual(statuses, {"Cargo.lock": "pending", "src/main.rs": "rejected"})
+
+    def test_reject_edit(self):
+        result = self.mcpdiff("reject", "-e", self.lock_edit)
+        self.assertIn("is listed in .mcp/ignore", result.stdout)
+        self.assertEqual(self.builder.entry(self.lock_edit)["status"], "pending")
+
+        self.mcpdiff("reject", "-e", self.lock_edit, "--no-ignore")
+        self.assertEqual(self.builder.read("Cargo.lock"), "v1\n")
+        self.assertEqual(self.builder.entry(self.lock_edit)["status"], "rejected")
+
+
+class TestIgnoredEdits(unittest.TestCase):
+    def setUp(self):
+        self.workspace = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        self.builder = HistoryBuilder(self.workspace)
+        ignore.get_ignore_path(self.workspace).write_text("target/\n", encoding="utf-8")
+        self.builder.write("target/out.txt", "v1\n")
+        self.output_edit = self.builder.edit("target/out.txt", "v2\n")
+        self.main_edit = self.builder.write("src/main.rs", "fn main() {}\n")
+
+    def tearDown(self):
+        shutil.rmtree(self.workspace, ignore_errors=True)
+
+    def mcpdiff(self, *args: str) -> str:
+        return run_mcpdiff(self.workspace, *args, input="y\n", check=True).stdout
+
+    def test_entries(self):
+        for entry in self.builder.entries():
+            if entry["file_path"] != "target/out.txt":
+                continue
+            self.assertIsNone(entry["checkpoint_file"])
+            self.assertTrue(entry["ignored"])
+            diff_path = self.builder.history_root / entry["diff_file"]
+            self.assertEqual(diff_path.read_bytes(), b"")
+        self.assertIsNotNone(self.builder.entry(self.main_edit)["checkpoint_file"])
+        self.assertFalse(self.builder.entry(self.main_edit)["ignored"])
+
+    def test_reapply(self):
+        self.builder.path("target/out.txt").write_text("rebuilt\n")
+        self.builder.path("src/main.rs").write_text("clobbered\n")
+        results = api.reapply_conversation_state(
+            self.workspace, self.builder.history_root, "conv1"
+        )
+        self.assertTrue(results["target/out.txt"]["ignored"])
+        self.assertIsNone(results["target/out.txt"]["error"])
+        self.assertEqual(self.builder.read("target/out.txt"), "rebuilt\n")
+        self.assertIsNone(results["src/main.rs"]["error"])
+        self.assertEqual(self.builder.read("src/main.rs"), "fn main() {}\n")
+
+    def test_accept_and_reject(self):
+        # The pattern is gone, but the edits were logged without their content
+        ignore.get_ignore_path(self.workspace).unlink()
+        self.builder.path("target/out.txt").write_text("rebuilt\n")
+        self.mcpdiff("accept", "-c", "conv1")
+        self.assertEqual(self.builder.read("target/out.txt"), "rebuilt\n")
+
+        self.mcpdiff("reject", "-c", "conv1")
+        self.assertEqual(self.builder.read("target/out.txt"), "rebuilt\n")
+        self.assertEqual(self.builder.entry(self.output_edit)["status"], "rejected")
+        # Rejected creates leave empty files
+        self.assertEqual(self.builder.read("src/main.rs"), "")
+
+    def test_add(self):
+        output = self.mcpdiff("ignore", "add", "*.lock")
+        self.assertIn("Added *.lock", output)
+        self.assertIn("already in", self.mcpdiff("ignore", "add", "*.lock"))
+        self.assertEqual(
+            ignore.get_ignore_path(self.workspace).read_text(), "target/\n*.lock\n"
+        )
+        matcher = ignore.load_ignore_matcher(self.workspace)
+        self.assertTrue(matcher.matches("Cargo.lock"))
+
+        # The file is created if there is none
+        ignore.get_ignore_path(self.workspace).unlink()
+        self.mcpdiff("ignore", "add", "dist/")
+        self.assertEqual(ignore.get_ignore_path(self.workspace).read_text(), "dist/\n")
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,92 @@
+#!/usr/bin/env python3
+"""
+Tests for `mcpdiff info`:
+- The workspace and history roots, and the settings in effect
+- The number of log files, entries per status, diff and checkpoint sizes and
+  the newest entry's timestamp
+- Outside any workspace, an error saying how to find one
+"""
+
+import json
+import shutil
+import subprocess
+import sys
+import tempfile
+import unittest
+from pathlib import Path
+
+# The CLI modules import each other by bare name; history_builder is found also
+# when run_tests.py imports this module as part of the package
+sys.path.insert(0, str(Path(__file__).parent.parent / "cli"))
+sys.path.insert(0, str(Path(__file__).parent))
+
+from history_builder import HistoryBuilder, run_mcpdiff
+
+
+class TestInfo(unittest.TestCase):
+    def setUp(self):
+        self.workspace = Path(tempfile.mkdtemp(prefix="mcpdiff_test_")).resolve()
+        builder = HistoryBuilder(self.workspace)
+        builder.write("a.txt", "a\n")
+        builder.edit("a.txt", "b\n")
+        builder.conversation("conv2").write("b.txt", "b\n")
+        self.rejected = builder.edit("b.txt", "c\n")
+        self.builder = builder
+
+    def tearDown(self):
+        shutil.rmtree(self.workspace, ignore_errors=True)
+
+    def mcpdiff(self, *args: str, cwd: Path = None) -> subprocess.CompletedProcess:
+        return run_mcpdiff(None, *args, cwd=cwd or self.workspace)
+
+    def test_json(self):
+        self.assertEqual(self.mcpdiff("reject", "-e", self.rejected).returncode, 0)
+        self.assertEqual(
+            self.mcpdiff("config", "set", "syntax_theme", "dracula").returncode, 0
+        )
+        result = self.mcpdiff("info", "--json")
+        self.assertEqual(result.returncode, 0, result.stderr)
+        info = json.loads(result.stdout)
+        self.assertEqual(info["workspace_root"], str(self.workspace))
+        self.assertEqual(info["history_root"], str(self.builder.history_root))
+        self.assertEqual(
+            info["config_file"], str(self.workspace / ".mcp" / "config.json")
+        )
+        self.assertEqual(info["config"]["syntax_theme"], "dracula")
+        self.assertIsNone(info["config"]["auto_expire_days"])
+        self.assertEqual(info["log_files"], 2)
+        # The reject logged a snapshot and a revert
+        self.assertEqual(
+            info["entries_by_status"], {"pending": 3, "rejected": 1, "done": 2}
+        )
+        self.assertEqual(info["entries"], 6)
+        self.assertGreater(info["diffs_bytes"], 0)
+        self.assertGreater(info["checkpoints_bytes"], 0)
+        entries = self.builder.entries()
+        self.assertEqual(
+            info["latest_timestamp"], max(e["timestamp"] for e in entries)
+        )
+
+    def test_output(self):
+        result = self.mcpdiff("info", cwd=self.workspace / ".mcp")
+        self.assertEqual(result.returncode, 0, result.stderr)
+        lines = result.stdout.splitlines()
+        self.assertEqual(lines[0], f"Workspace:    {self.workspace}")
+        self.assertIn("Config:       none, all defaults", lines)
+        self.assertIn("  syntax_theme            monokai (default)", lines)
+        self.assertIn("Entries:      4 (4 pending)", lines)
+        self.assertIn("Last entry:   2025-01-01 00:00:03", lines)
+
+    def test_no_workspace(self):
+        elsewhere = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        self.addCleanup(shutil.rmtree, elsewhere, ignore_errors=True)
+        result = self.mcpdiff("info", "--json", cwd=elsewhere)
+        self.assertEqual(result.returncode, 1)
+        self.assertIn("Could not find workspace root", result.stderr)
+        self.assertIn("mcpdiff workspace detect", result.stderr)
+        error = json.loads(result.stdout)["error"]
+        self.assertIn("Could not find workspace root", error["message"])
+
+
+if __name__ == "__main__":
+    unittest.main()
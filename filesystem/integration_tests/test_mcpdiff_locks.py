@@ -0,0 +1,102 @@
+#!/usr/bin/env python3
+"""
+Tests for where file locks live:
+- The server locks workspace files in .mcp/edit_history/locks/, under names
+  that differ for different paths
+- `mcpdiff clean-locks` removes unheld locks there, and the <file>.lock files
+  older servers left next to tracked files once confirmed
+"""
+
+import shutil
+import subprocess
+import sys
+import tempfile
+import unittest
+from pathlib import Path
+
+# The CLI modules import each other by bare name; history_builder is found also
+# when run_tests.py imports this module as part of the package
+sys.path.insert(0, str(Path(__file__).parent.parent / "cli"))
+sys.path.insert(0, str(Path(__file__).parent))
+
+import mcpdiff_utils as utils
+from history_builder import HistoryBuilder, run_mcpdiff
+
+try:
+    sys.path.insert(0, str(Path(__file__).parent.parent))
+    from src import mcp_edit_utils as server_utils
+except ImportError:  # The server needs filelock
+    server_utils = None
+
+
+class TestLocks(unittest.TestCase):
+    def setUp(self):
+        self.workspace = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        self.builder = HistoryBuilder(self.workspace)
+        self.builder.write("a.txt", "a\n")
+        self.builder.write("src/b.txt", "b\n")
+        self.builder.write("Cargo.lock", "# generated\n")
+
+    def tearDown(self):
+        shutil.rmtree(self.workspace, ignore_errors=True)
+
+    def mcpdiff(self, *args: str, answer: str = "y") -> subprocess.CompletedProcess:
+        return run_mcpdiff(self.workspace, *args, input=f"{answer}\n", check=True)
+
+    @unittest.skipIf(server_utils is None, "server dependencies not installed")
+    def test_server_lock_paths(self):
+        history_root = self.builder.history_root
+        paths = [
+            Path(server_utils.get_target_lock_path(history_root, self.workspace / p))
+            for p in ("a.txt", "src/a.txt", "src/b.txt")
+        ]
+        self.assertEqual(len(set(paths)), 3)
+        for path in paths:
+            self.assertEqual(path.parent, history_root / server_utils.LOCKS_DIR)
+        self.assertTrue(paths[0].name.endswith("_a.txt"))
+
+        lock = server_utils.acquire_lock(str(paths[0]))
+        self.assertFalse(self.builder.path("a.txt.lock").exists())
+        server_utils.release_lock(lock)
+
+    def test_clean_locks(self):
+        strays = [self.builder.path("a.txt.lock"), self.builder.path("src/b.txt.lock")]
+        for path in strays:
+            path.write_bytes(b"")
+        # Not a lock of ours: the file next to it is not tracked, or it has content
+        self.builder.path("c.txt.lock").write_bytes(b"")
+        self.builder.path("Cargo.lock.lock").write_text("data")
+        locks_dir = self.builder.history_root / utils.LOCKS_DIR
+        locks_dir.mkdir()
+        (locks_dir / "0123abcd_a.txt.lock").write_bytes(b"")
+
+        result = self.mcpdiff("clean-locks", answer="n")
+        self.assertIn("Cleaned up 1 stale lock(s)", result.stdout)
+        self.assertIn("Found 2 lock file(s)", result.stdout)
+        self.assertIn("src/b.txt.lock", result.stdout)
+        self.assertFalse(any(locks_dir.iterdir()))
+        self.assertTrue(all(path.exists() for path in strays))
+
+        result = self.mcpdiff("cleanup")
+        self.assertIn("Removed 2 stray lock file(s)", result.stdout)
+        self.assertFalse(any(path.exists() for path in strays))
+        self.assertTrue(self.builder.path("c.txt.lock").exists())
+        self.assertTrue(self.builder.path("Cargo.lock.lock").exists())
+        self.assertEqual(self.builder.read("Cargo.lock"), "# generated\n")
+
+    def test_held_lock_is_kept(self):
+        stray = self.builder.path("a.txt.lock")
+        stray.write_bytes(b"")
+        with open(stray, "a") as handle:
+            utils.lock_file_handle(handle)
+            result = self.mcpdiff("clean-locks", "--force")
+            utils.unlock_file_handle(handle)
+        self.assertNotIn("Removed", result.stdout)
+        self.assertTrue(stray.exists())
+
+        self.mcpdiff("clean-locks", "--force")
+        self.assertFalse(stray.exists())
+
+
+if __name__ == "__main__":
+    unittest.main()
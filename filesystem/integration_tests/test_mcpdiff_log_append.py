@@ -0,0 +1,218 @@
+#!/usr/bin/env python3
+"""
+Tests for `mcpdiff log-append`, the validating writer for log entries:
+- Invalid entries are refused with a pointer to the offending field, and a
+  batch with one invalid entry writes nothing
+- Missing tool_call_index values continue the conversation's numbering, also
+  with several processes appending at once
+- Entries without file_encoding, given or from before schema version 10, get
+  "text"
+- Entries go to the current shard when logs are sharded
+- `mcpdiff format-log` pretty-prints a log and reports entries that fail the
+  same checks
+"""
+
+import json
+import shutil
+import subprocess
+import sys
+import tempfile
+import unittest
+from pathlib import Path
+
+# The CLI modules import each other by bare name; history_builder is found also
+# when run_tests.py imports this module as part of the package
+sys.path.insert(0, str(Path(__file__).parent.parent / "cli"))
+sys.path.insert(0, str(Path(__file__).parent))
+
+import mcpdiff_append as append
+import mcpdiff_config as config
+import mcpdiff_migration as migration
+import mcpdiff_utils as utils
+from history_builder import MCPDIFF, HistoryBuilder, run_mcpdiff
+
+
+def make_entry(edit_id: str, **fields) -> dict:
+    """An entry as given to log-append, which fills in the rest."""
+    entry = {
+        "edit_id": edit_id,
+        "timestamp": "2025-01-01T000000.000Z",
+        "operation": "edit",
+        "file_path": "a.txt",
+        "status": "pending",
+        "tool_name": "edit_file_diff",
+    }
+    entry.update(fields)
+    return entry
+
+
+class TestLogAppend(unittest.TestCase):
+    def setUp(self):
+        self.workspace = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        self.builder = HistoryBuilder(self.workspace)
+        self.history_root = self.builder.history_root
+
+    def tearDown(self):
+        shutil.rmtree(self.workspace, ignore_errors=True)
+
+    def mcpdiff(self, stdin: str, *args: str) -> subprocess.CompletedProcess:
+        return run_mcpdiff(
+            self.workspace, "log-append", "--conv", "conv1", *args, input=stdin
+        )
+
+    def assertInvalid(self, entries: list, pointer: str):
+        with self.assertRaises(utils.InvalidLogEntryError) as cm:
+            append.append_log_entries(self.history_root, "conv1", entries)
+        self.assertEqual(cm.exception.pointer, pointer)
+
+    def test_invalid_entries(self):
+        for fields, pointer in [
+            ({"status": "done!"}, "/0/status"),
+            ({"operation": "copy"}, "/0/operation"),
+            ({"tool_call_index": "3"}, "/0/tool_call_index"),
+            ({"tool_call_index": True}, "/0/tool_call_index"),
+            ({"timestamp": "yesterday"}, "/0/timestamp"),
+            ({"conversation_id": "conv2"}, "/0/conversation_id"),
+            ({"operation": "move"}, "/0/source_path"),
+            ({"operation": "partial"}, "/0/byte_range_start"),
+            (
+                {"operation": "partial", "byte_range_start": 5, "byte_range_end": 2},
+                "/0/byte_range_end",
+            ),
+            (
+                {"conflict_markers": [{"start_line": 3, "end_line": 1}]},
+                "/0/conflict_markers",
+            ),
+            ({"diff_file": "../x.diff"}, "/0/diff_file"),
+            ({"schema_version": 99}, "/0/schema_version"),
+        ]:
+            self.assertInvalid([make_entry("e1", **fields)], pointer)
+        entry = make_entry("e1")
+        del entry["file_path"]
+        self.assertInvalid([make_entry("e0"), entry], "/1/file_path")
+        self.assertInvalid([make_entry("e1"), make_entry("e1")], "/1/edit_id")
+        self.assertInvalid(["e1"], "/0")
+        self.assertEqual(self.builder.entries(), [])
+
+    def test_indexes_continue(self):
+        self.builder.write("a.txt", "one\n")
+        self.builder.edit("a.txt", "two\n")
+        written = append.append_log_entries(
+            self.history_root,
+            "conv1",
+            [make_entry("e1"), make_entry("e2", tool_call_index=7), make_entry("e3")],
+        )
+        self.assertEqual([e["tool_call_index"] for e in written], [2, 7, 8])
+        entry = self.builder.entry("e1")
+        self.assertEqual(entry["schema_version"], 11)
+        self.assertIsNone(entry["session_id"])
+        self.assertInvalid([make_entry("e2")], "/0/edit_id")
+
+    def test_file_encoding_default(self):
+        written = append.append_log_entries(
+            self.history_root, "conv1", [make_entry("e1", schema_version=9)]
+        )
+        self.assertEqual(written[0]["file_encoding"], "text")
+        entry = migration.default_migrator.upgrade(make_entry("e2", schema_version=9))
+        self.assertEqual(entry["file_encoding"], "text")
+
+        written = append.append_log_entries(
+            self.history_root, "conv1", [make_entry("e3", file_encoding="base64")]
+        )
+        self.assertEqual(written[0]["file_encoding"], "base64")
+        written = append.append_log_entries(
+            self.history_root, "conv1", [make_entry("e4")]
+        )
+        self.assertEqual(written[0]["file_encoding"], "text")
+
+    def test_cli(self):
+        stdin = "\n".join(json.dumps(make_entry(f"e{i}")) for i in range(3))
+        result = self.mcpdiff(stdin, "--json")
+        self.assertEqual(result.returncode, 0, result.stderr)
+        self.assertEqual(
+            [e["tool_call_index"] for e in json.loads(result.stdout)], [0, 1, 2]
+        )
+
+        result = self.mcpdiff('[{"edit_id": "e9"}]', "--json")
+        self.assertEqual(result.returncode, utils.InvalidLogEntryError.exit_code)
+        error = json.loads(result.stdout)["error"]
+        self.assertEqual(
+            (error["kind"], error["pointer"]), ("invalid_entry", "/0/timestamp")
+        )
+        result = self.mcpdiff("{not json")
+        self.assertEqual(result.returncode, utils.InvalidLogEntryError.exit_code)
+
+    def test_concurrent_appends(self):
+        processes = [
+            subprocess.Popen(
+                [sys.executable, str(MCPDIFF), "-w", str(self.workspace)]
+                + ["log-append", "--conv", "conv1"],
+                stdin=subprocess.PIPE,
+                stdout=subprocess.DEVNULL,
+                stderr=subprocess.DEVNULL,
+                text=True,
+            )
+            for _ in range(4)
+        ]
+        for n, process in enumerate(processes):
+            process.communicate(
+                "\n".join(json.dumps(make_entry(f"p{n}e{i}")) for i in range(5)),
+                timeout=60,
+            )
+            self.assertEqual(process.returncode, 0)
+        indexes = sorted(e["tool_call_index"] for e in self.builder.entries())
+        self.assertEqual(indexes, list(range(20)))
+
+    def test_sharded_log(self):
+        config.set_config_value(self.workspace, "log_shard_interval", "month")
+        written = append.append_log_entries(
+            self.history_root, "conv1", [make_entry("e1")]
+        )
+        self.assertRegex(written[0]["log_file_source"], r"^conv1_\d{6}\.log$")
+        self.assertFalse(self.builder.log_file().exists())
+
+
+class TestFormatLog(unittest.TestCase):
+    def setUp(self):
+        self.workspace = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        self.builder = HistoryBuilder(self.workspace)
+        self.builder.write("a.txt", "one\n")
+        self.builder.edit("a.txt", "two\n")
+
+    def tearDown(self):
+        shutil.rmtree(self.workspace, ignore_errors=True)
+
+    def mcpdiff(self, *args: str) -> subprocess.CompletedProcess:
+        return run_mcpdiff(self.workspace, "format-log", "--conv", "conv1", *args)
+
+    def test_pretty_print(self):
+        result = self.mcpdiff("--output", "-")
+        self.assertEqual(result.returncode, 0, result.stderr)
+        blocks = result.stdout.split("\n\n")
+        self.assertEqual(
+            [json.loads(block) for block in blocks],
+            utils.read_log_file(self.builder.log_file(), migrate=False),
+        )
+        self.assertIn('\n  "operation": "create",\n', blocks[0])
+
+        output = self.workspace / "conv1.txt"
+        result = self.mcpdiff("-o", str(output))
+        self.assertEqual(result.returncode, 0, result.stderr)
+        self.assertEqual(output.read_text(encoding="utf-8"), self.mcpdiff().stdout)
+
+        result = self.mcpdiff("-o", str(self.builder.log_file()))
+        self.assertEqual(result.returncode, utils.HistoryError.exit_code)
+
+    def test_invalid_entries(self):
+        log_file = self.builder.log_file()
+        with open(log_file, "a", encoding="utf-8") as f:
+            f.write(json.dumps(make_entry("e9", status="maybe")) + "\n{not json\n")
+        result = self.mcpdiff()
+        self.assertEqual(result.returncode, utils.CorruptLogError.exit_code)
+        self.assertEqual(len(result.stdout.split("\n\n")), 3)
+        self.assertIn("conv1.log:3: status: 'maybe' is not one of", result.stderr)
+        self.assertIn("conv1.log:4: not JSON", result.stderr)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,289 @@
+#!/usr/bin/env python3
+"""
+Tests that mcpdiff treats logged paths as workspace-relative:
+- Absolute and relative `file_path` values normalize to the same form
+- A log mixing both styles for one file is filtered and reconstructed as one file
+- Paths that are not valid UTF-8 round-trip through the log exactly
+"""
+
+import base64
+import difflib
+import hashlib
+import json
+import os
+import shutil
+import sys
+import tempfile
+import unittest
+from pathlib import Path
+
+# The CLI modules import each other by bare name
+sys.path.insert(0, str(Path(__file__).parent.parent / "cli"))
+
+import mcpdiff_history as history
+import mcpdiff_utils as utils
+
+VERSIONS = ["a\nb\nc\n", "a\nB\nc\n", "a\nB\nc\nd\n"]
+
+
+def sha(content: str) -> str:
+    return hashlib.sha256(content.encode()).hexdigest()
+
+
+class TestNormalizeLogPath(unittest.TestCase):
+    """Test the canonical form of paths read from logs."""
+
+    def setUp(self):
+        self.base = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        self.workspace = self.base / "ws"
+        self.workspace.mkdir()
+
+    def tearDown(self):
+        shutil.rmtree(self.base, ignore_errors=True)
+
+    def test_absolute_and_relative_agree(self):
+        expected = "src/lib.rs"
+        for path in (
+            "src/lib.rs",
+            "./src/lib.rs",
+            "src\\lib.rs",
+            "src/../src/lib.rs",
+            str(self.workspace / "src" / "lib.rs"),
+            str(self.workspace) + "/src/./lib.rs",
+        ):
+            with self.subTest(path=path):
+                self.assertEqual(
+                    utils.normalize_log_path(path, self.workspace), expected
+                )
+
+    def test_workspace_root_itself(self):
+        self.assertEqual(
+            utils.normalize_log_path(str(self.workspace), self.workspace), ""
+        )
+
+    def test_path_outside_workspace_stays_absolute(self):
+        """A sibling directory sharing the workspace's name prefix is outside it."""
+        outside = str(self.base / "ws2" / "f.txt")
+        self.assertEqual(
+            utils.normalize_log_path(outside, self.workspace),
+            outside.replace("\\", "/"),
+        )
+
+    def test_empty_path_is_unchanged(self):
+        self.assertIsNone(utils.normalize_log_path(None, self.workspace))
+        self.assertEqual(utils.normalize_log_path("", self.workspace), "")
+
+
+class TestMixedPathLog(unittest.TestCase):
+    """A conversation log whose entries use absolute and relative paths."""
+
+    def setUp(self):
+        self.workspace = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        self.history_root = self.workspace / ".mcp" / utils.HISTORY_DIR_NAME
+        for name in (utils.LOGS_DIR, utils.DIFFS_DIR, utils.CHECKPOINTS_DIR):
+            (self.history_root / name / "conv1").mkdir(parents=True)
+        self.target = self.workspace / "src" / "f.txt"
+        self.target.parent.mkdir()
+        self.target.write_text(VERSIONS[-1])
+
+        checkpoint_rel = f"{utils.CHECKPOINTS_DIR}/conv1/f.txt.chkpt"
+        (self.history_root / checkpoint_rel).write_text(VERSIONS[0])
+        # The first entry marks the checkpointed state, then one edit is logged
+        # with an absolute path and one with a relative path
+        entries = [
+            self._entry("e0", 0, "src/f.txt", VERSIONS[0], VERSIONS[0]),
+            self._entry("e1", 1, str(self.target), VERSIONS[0], VERSIONS[1]),
+            self._entry("e2", 2, "./src/f.txt", VERSIONS[1], VERSIONS[2]),
+        ]
+        entries[0]["checkpoint_file"] = checkpoint_rel
+        log_file = self.history_root / utils.LOGS_DIR / "conv1.log"
+        log_file.write_text("".join(json.dumps(e) + "\n" for e in entries))
+
+    def tearDown(self):
+        shutil.rmtree(self.workspace, ignore_errors=True)
+
+    def _entry(self, edit_id, index, file_path, before, after):
+        diff_rel = f"conv1/{edit_id}.diff"
+        diff = "".join(
+            difflib.unified_diff(
+                before.splitlines(keepends=True),
+                after.splitlines(keepends=True),
+                "a/src/f.txt",
+                "b/src/f.txt",
+            )
+        )
+        (self.history_root / utils.DIFFS_DIR / diff_rel).write_text(diff)
+        return {
+            "edit_id": edit_id,
+            "conversation_id": "conv1",
+            "tool_call_index": index,
+            "timestamp": f"2025-01-01T00:00:0{index}Z",
+            "operation": "edit",
+            "file_path": file_path,
+            "status": "pending",
+            "diff_file": diff_rel,
+            "checkpoint_file": None,
+            "hash_before": sha(before),
+            "hash_after": sha(after),
+        }
+
+    def test_entries_are_read_relative(self):
+        entries = history.find_all_entries(self.history_root)
+        self.assertEqual([e["file_path"] for e in entries], ["src/f.txt"] * 3)
+
+    def test_log_file_is_not_rewritten(self):
+        history.find_all_entries(self.history_root)
+        log_file = self.history_root / utils.LOGS_DIR / "conv1.log"
+        lines = log_file.read_text().splitlines()
+        logged = [json.loads(line)["file_path"] for line in lines]
+        self.assertEqual(logged[1], str(self.target))
+
+    def test_file_filter_matches_both_styles(self):
+        entries = history.find_all_entries(self.history_root)
+        filtered = history.filter_entries(entries, file_path="src/f.txt", limit=None)
+        self.assertEqual({e["edit_id"] for e in filtered}, {"e0", "e1", "e2"})
+
+    def test_relevant_history_includes_both_styles(self):
+        entries = history.find_all_entries(self.history_root)
+        relevant = history.get_relevant_history_for_file("src/f.txt", entries)
+        self.assertEqual([e["edit_id"] for e in relevant], ["e0", "e1", "e2"])
+
+    def test_reconstruct_applies_both_styles(self):
+        entries = history.find_all_entries(self.history_root)
+        result = history.reconstruct_file_from_history(
+            "src/f.txt", entries, self.workspace, self.history_root
+        )
+        self.assertIsNone(result["error"])
+        self.assertEqual(result["hash"], sha(VERSIONS[-1]))
+
+    def test_new_log_entries_are_relative(self):
+        log_file = self.history_root / utils.LOGS_DIR / "conv1.log"
+        entries, _ = history.read_new_log_entries(log_file, None)
+        self.assertEqual({e["file_path"] for e in entries}, {"src/f.txt"})
+
+
+@unittest.skipUnless(os.name == "posix", "Non-UTF-8 file names are POSIX-only")
+class TestNonUtf8Paths(unittest.TestCase):
+    """File names that are legal bytes on Unix but not valid UTF-8."""
+
+    RAW_NAME = b"caf\xe9.txt"
+
+    def setUp(self):
+        self.workspace = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        self.history_root = self.workspace / ".mcp" / utils.HISTORY_DIR_NAME
+        for name in (utils.LOGS_DIR, utils.DIFFS_DIR, utils.CHECKPOINTS_DIR):
+            (self.history_root / name / "conv1").mkdir(parents=True)
+        self.name = os.fsdecode(self.RAW_NAME)
+        self.target = self.workspace / self.name
+        self.log_file = self.history_root / utils.LOGS_DIR / "conv1.log"
+
+    def tearDown(self):
+        shutil.rmtree(self.workspace, ignore_errors=True)
+
+    def _write_history(self, status="pending"):
+        checkpoint_rel = f"{utils.CHECKPOINTS_DIR}/conv1/cafe.txt.chkpt"
+        (self.history_root / checkpoint_rel).write_text(VERSIONS[0])
+        entries = []
+        for index in range(1, len(VERSIONS)):
+            diff_rel = f"conv1/e{index}.diff"
+            diff = "".join(
+                difflib.unified_diff(
+                    VERSIONS[index - 1].splitlines(keepends=True),
+                    VERSIONS[index].splitlines(keepends=True),
+                    f"a/{self.name}",
+                    f"b/{self.name}",
+                )
+            )
+            (self.history_root / utils.DIFFS_DIR / diff_rel).write_text(
+                diff, encoding="utf-8", errors="surrogateescape"
+            )
+            entries.append(
+                {
+                    "edit_id": f"e{index}",
+                    "conversation_id": "conv1",
+                    "tool_call_index": index,
+                    "timestamp": f"2025-01-01T00:00:0{index}Z",
+                    "operation": "edit",
+                    "file_path": self.name,
+                    "status": status,
+                    "diff_file": diff_rel,
+                    "checkpoint_file": None,
+                    "hash_before": sha(VERSIONS[index - 1]),
+                    "hash_after": sha(VERSIONS[index]),
+                }
+            )
+        # e0 marks the checkpointed state with an empty diff
+        (self.history_root / utils.DIFFS_DIR / "conv1" / "e0.diff").write_text("")
+        entries.insert(0, dict(entries[0], edit_id="e0", tool_call_index=0))
+        entries[0].update(
+            diff_file="conv1/e0.diff",
+            timestamp="2025-01-01T00:00:00Z",
+            checkpoint_file=checkpoint_rel,
+            hash_after=sha(VERSIONS[0]),
+        )
+        utils.write_log_file(self.log_file, entries)
+
+    def test_log_stores_bytes_fallback(self):
+        """The log is valid UTF-8 JSON with the exact bytes in file_path_bytes."""
+        self._write_history()
+        raw = self.log_file.read_bytes().decode("utf-8")
+        entry = json.loads(raw.splitlines()[0])
+        self.assertEqual(entry["file_path"], "caf\ufffd.txt")
+        self.assertEqual(base64.b64decode(entry["file_path_bytes"]), self.RAW_NAME)
+
+    def test_read_restores_exact_path(self):
+        self._write_history()
+        entries = history.find_all_entries(self.history_root)
+        self.assertEqual(
+            {os.fsencode(e["file_path"]) for e in entries}, {self.RAW_NAME}
+        )
+        self.assertNotIn("file_path_bytes", entries[0])
+
+    def test_status_displays_lossy_path(self):
+        self._write_history()
+        entry = history.find_all_entries(self.history_root)[0]
+        summary = history.format_entry_summary(entry)
+        self.assertIn("caf\ufffd.txt", summary)
+        summary.encode("utf-8")  # Printable without surrogate escapes
+
+    def test_show_reads_diff(self):
+        self._write_history()
+        entry = history.find_all_entries(self.history_root)[1]
+        diff = history.get_diff_for_entry(entry, self.history_root)
+        self.assertIn("+B", diff)
+
+    def test_reconstruct_uses_exact_path(self):
+        self._write_history()
+        self.target.write_text(VERSIONS[-1])
+        entries = history.find_all_entries(self.history_root)
+        result = history.reconstruct_file_from_history(
+            entries[0]["file_path"], entries, self.workspace, self.history_root
+        )
+        self.assertIsNone(result["error"])
+        self.assertEqual(result["hash"], sha(VERSIONS[-1]))
+
+    def test_revert_touches_only_the_original_file(self):
+        self._write_history()
+        self.target.write_text(VERSIONS[-1])
+        entry = history.find_all_entries(self.history_root)[-1]
+        self.assertTrue(
+            history.apply_or_revert_edit(
+                entry, self.workspace, self.history_root, is_revert=True
+            )
+        )
+        self.assertEqual(self.target.read_text(), VERSIONS[1])
+        names = {os.fsencode(p.name) for p in self.workspace.iterdir()}
+        self.assertEqual(names, {b".mcp", self.RAW_NAME})
+
+    def test_status_update_keeps_bytes(self):
+        """Rewriting the log (as accept/reject do) keeps the exact path."""
+        self._write_history()
+        entries = history.find_all_entries(self.history_root)
+        history.update_entry_status(entries[1], "accepted", self.history_root)
+        reread = history.find_all_entries(self.history_root)
+        self.assertEqual(reread[1]["status"], "accepted")
+        self.assertEqual(os.fsencode(reread[1]["file_path"]), self.RAW_NAME)
+
+
+if __name__ == "__main__":
+    unittest.main()
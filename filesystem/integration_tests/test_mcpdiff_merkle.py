@@ -0,0 +1,181 @@
+#!/usr/bin/env python3
+"""
+Tests for the Merkle roots behind `mcpdiff verify`:
+- Root computation: order sensitivity, odd leaf counts, canonical JSON
+- Peaks: appending leaves to them gives the root of the whole tree
+- The logs/<conv_id>.merkle sidecar kept current by log writes
+- Tamper detection, and the server computing the same roots as mcpdiff, also
+  when it only adds the entries it appends
+"""
+
+import shutil
+import sys
+import tempfile
+import unittest
+from pathlib import Path
+
+# The CLI modules import each other by bare name; history_builder is found also
+# when run_tests.py imports this module as part of the package
+sys.path.insert(0, str(Path(__file__).parent.parent / "cli"))
+sys.path.insert(0, str(Path(__file__).parent.parent / "src"))
+sys.path.insert(0, str(Path(__file__).parent))
+
+import mcp_history_format as history_format
+import mcpdiff_utils as utils
+from history_builder import make_entry
+
+try:
+    sys.path.insert(0, str(Path(__file__).parent.parent))
+    from src import mcp_edit_utils as server_utils
+except ImportError:  # The server needs filelock
+    server_utils = None
+
+
+class TestMerkleRoot(unittest.TestCase):
+    ALGORITHM = history_format.FALLBACK_ALGORITHM
+
+    def test_root_depends_on_order(self):
+        entries = [make_entry(f"e{i}", index=i) for i in (1, 2)]
+        self.assertNotEqual(
+            history_format.merkle_root(entries, self.ALGORITHM),
+            history_format.merkle_root(entries[::-1], self.ALGORITHM),
+        )
+
+    def test_key_order_does_not_matter(self):
+        entry = make_entry("e1", index=1)
+        reordered = dict(reversed(list(entry.items())))
+        self.assertEqual(
+            history_format.merkle_root([entry], self.ALGORITHM),
+            history_format.merkle_root([reordered], self.ALGORITHM),
+        )
+
+    def test_odd_leaf_is_carried_up(self):
+        entries = [make_entry(f"e{i}", index=i) for i in (1, 2, 3)]
+        a, b, c = (history_format.leaf_hash(e, self.ALGORITHM) for e in entries)
+        node = history_format.NODE_PREFIX
+        ab = history_format.merkle_hash(node + a + b, self.ALGORITHM)
+        expected = history_format.merkle_hash(node + ab + c, self.ALGORITHM)
+        self.assertEqual(
+            history_format.merkle_root(entries, self.ALGORITHM), expected.hex()
+        )
+
+    def test_single_leaf_is_not_an_inner_node(self):
+        """A leaf is hashed with its own prefix, so one entry's root is its leaf."""
+        entry = make_entry("e1", index=1)
+        self.assertEqual(
+            history_format.merkle_root([entry], self.ALGORITHM),
+            history_format.leaf_hash(entry, self.ALGORITHM).hex(),
+        )
+        self.assertNotEqual(
+            history_format.leaf_hash(entry, self.ALGORITHM),
+            history_format.merkle_hash(
+                history_format.canonical_entry_json(entry), self.ALGORITHM
+            ),
+        )
+
+    def test_unknown_algorithm(self):
+        with self.assertRaises(ValueError):
+            history_format.merkle_root([make_entry("e1", index=1)], "md5")
+
+    def test_added_leaves_give_the_same_root(self):
+        entries = [make_entry(f"e{i}", index=i) for i in range(13)]
+        leaves = [history_format.leaf_hash(e, self.ALGORITHM) for e in entries]
+        for count in range(len(entries)):
+            peaks = history_format.merkle_peaks(entries[:count], self.ALGORITHM)
+            peaks = history_format.add_merkle_leaves(
+                peaks, count, leaves[count:], self.ALGORITHM
+            )
+            self.assertEqual(
+                history_format.merkle_root_of_peaks(peaks, self.ALGORITHM),
+                history_format.merkle_root(entries, self.ALGORITHM),
+            )
+
+    def test_sidecar_peaks_must_fit_the_root(self):
+        entries = [make_entry(f"e{i}", index=i) for i in (1, 2, 3)]
+        peaks = history_format.merkle_peaks(entries, self.ALGORITHM)
+        sidecar = history_format.merkle_sidecar_data(self.ALGORITHM, peaks, 3)
+        self.assertEqual(
+            history_format.read_sidecar_peaks(sidecar, self.ALGORITHM), peaks
+        )
+        self.assertIsNone(
+            history_format.read_sidecar_peaks(dict(sidecar, leaves=4), self.ALGORITHM)
+        )
+        self.assertIsNone(
+            history_format.read_sidecar_peaks(dict(sidecar, root="00"), self.ALGORITHM)
+        )
+
+
+class TestMerkleSidecar(unittest.TestCase):
+    def setUp(self):
+        self.workspace = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        self.history_root = self.workspace / ".mcp" / utils.HISTORY_DIR_NAME
+        self.logs_dir = self.history_root / utils.LOGS_DIR
+        self.logs_dir.mkdir(parents=True)
+        self.log_file = self.logs_dir / "conv1.log"
+
+    def tearDown(self):
+        shutil.rmtree(self.workspace, ignore_errors=True)
+
+    def test_write_updates_sidecar(self):
+        entries = [make_entry(f"e{i}", index=i) for i in (1, 2)]
+        utils.write_log_file(self.log_file, entries)
+        sidecar = utils.read_merkle_sidecar(self.history_root, "conv1")
+        self.assertIsNotNone(sidecar)
+        self.assertEqual(sidecar["leaves"], 2)
+        self.assertEqual(
+            sidecar["root"], history_format.merkle_root(entries, sidecar["algorithm"])
+        )
+
+        entries[0]["status"] = "accepted"
+        utils.write_log_file(self.log_file, entries)
+        updated = utils.read_merkle_sidecar(self.history_root, "conv1")
+        self.assertNotEqual(updated["root"], sidecar["root"])
+
+    def test_root_covers_all_shards(self):
+        utils.write_log_file(self.log_file, [make_entry("e1", index=1)])
+        shard = self.logs_dir / "conv1_20250102.log"
+        utils.write_log_files({shard: [make_entry("e2", index=2)]})
+        sidecar = utils.read_merkle_sidecar(self.history_root, "conv1")
+        self.assertEqual(sidecar["leaves"], 2)
+
+    def test_tampering_changes_root(self):
+        entries = [make_entry(f"e{i}", index=i) for i in (1, 2)]
+        utils.write_log_file(self.log_file, entries)
+        recorded = utils.read_merkle_sidecar(self.history_root, "conv1")
+        content = self.log_file.read_text().replace('"pending"', '"accepted"', 1)
+        self.log_file.write_text(content)
+        actual = utils.compute_merkle_root(
+            self.history_root, "conv1", recorded["algorithm"]
+        )
+        self.assertNotEqual(actual["root"], recorded["root"])
+
+    def test_invalid_sidecar_is_ignored(self):
+        utils.merkle_sidecar_path(self.history_root, "conv1").write_text("{not json")
+        with self.assertLogs("mcpdiff", level="WARNING"):
+            self.assertIsNone(utils.read_merkle_sidecar(self.history_root, "conv1"))
+
+    @unittest.skipIf(server_utils is None, "server dependencies not installed")
+    def test_server_computes_the_same_root(self):
+        entries = [make_entry(f"e{i}", index=i) for i in (1, 2)]
+        utils.write_log_file(self.log_file, entries)
+        recorded = utils.read_merkle_sidecar(self.history_root, "conv1")
+        server = server_utils.compute_merkle_root(self.history_root, "conv1")
+        self.assertEqual(server["algorithm"], recorded["algorithm"])
+        self.assertEqual(server["root"], recorded["root"])
+
+    @unittest.skipIf(server_utils is None, "server dependencies not installed")
+    def test_server_adds_appended_entries(self):
+        entries = [make_entry(f"e{i}", index=i) for i in (1, 2)]
+        utils.write_log_file(self.log_file, entries)
+        entries.append(make_entry("e3", index=3))
+        server_utils.write_log_file(self.log_file, entries, appended=1)
+        sidecar = utils.read_merkle_sidecar(self.history_root, "conv1")
+        actual = utils.compute_merkle_root(
+            self.history_root, "conv1", sidecar["algorithm"]
+        )
+        self.assertEqual(sidecar["leaves"], 3)
+        self.assertEqual(sidecar["root"], actual["root"])
+
+
+if __name__ == "__main__":
+    unittest.main()
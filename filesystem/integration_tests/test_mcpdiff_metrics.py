@@ -0,0 +1,190 @@
+#!/usr/bin/env python3
+"""
+Tests for operation metrics and `mcpdiff daemon start --metrics PORT`:
+- Counters and the log write histogram, merged across processes and rendered
+  in the Prometheus text format
+- accept and reject runs report their counts to a running daemon, which
+  serves the sums at /metrics without authentication
+- Lock contention is counted when a lock has to be waited for
+"""
+
+import shutil
+import subprocess
+import sys
+import tempfile
+import threading
+import unittest
+import urllib.error
+import urllib.request
+from pathlib import Path
+
+# The CLI modules import each other by bare name; history_builder is found also
+# when run_tests.py imports this module as part of the package
+sys.path.insert(0, str(Path(__file__).parent.parent / "cli"))
+sys.path.insert(0, str(Path(__file__).parent))
+
+import mcpdiff_daemon as daemon
+import mcpdiff_metrics as metrics
+import mcpdiff_utils as utils
+from history_builder import HistoryBuilder, run_mcpdiff
+
+
+def parse_metrics(text: str) -> dict:
+    """{name: value} of the samples of a Prometheus text exposition."""
+    samples = {}
+    for line in text.splitlines():
+        if line and not line.startswith("#"):
+            name, value = line.rsplit(" ", 1)
+            samples[name] = float(value)
+    return samples
+
+
+class TestMetrics(unittest.TestCase):
+    def test_render(self):
+        counted = metrics.Metrics()
+        counted.increment("accepts_total", 3)
+        counted.observe_log_write(0.002)
+        counted.observe_log_write(10.0)
+        with self.assertRaises(KeyError):
+            counted.increment("edits_total")
+        text = counted.render()
+        self.assertIn("# TYPE mcpdiff_accepts_total counter\n", text)
+        self.assertIn("# TYPE mcpdiff_log_write_duration_seconds histogram\n", text)
+        samples = parse_metrics(text)
+        self.assertEqual(samples["mcpdiff_accepts_total"], 3)
+        self.assertEqual(samples["mcpdiff_rejects_total"], 0)
+        bucket = "mcpdiff_log_write_duration_seconds_bucket"
+        self.assertEqual(samples[f'{bucket}{{le="0.001"}}'], 0)
+        self.assertEqual(samples[f'{bucket}{{le="0.005"}}'], 1)  # Cumulative
+        self.assertEqual(samples[f'{bucket}{{le="2.5"}}'], 1)
+        self.assertEqual(samples[f'{bucket}{{le="+Inf"}}'], 2)
+        self.assertEqual(samples["mcpdiff_log_write_duration_seconds_count"], 2)
+        self.assertAlmostEqual(
+            samples["mcpdiff_log_write_duration_seconds_sum"], 10.002
+        )
+
+    def test_merge(self):
+        total = metrics.Metrics()
+        self.assertTrue(total.is_empty())
+        counted = metrics.Metrics()
+        counted.increment("rejects_total")
+        counted.increment("reapply_failures_total", 2)
+        with counted.time_log_write():
+            pass
+        total.merge(counted.to_dict())
+        total.merge(counted.to_dict())
+        self.assertEqual(total.counters["rejects_total"], 2)
+        self.assertEqual(total.counters["reapply_failures_total"], 4)
+        self.assertEqual(sum(total.log_write_buckets), 2)
+        for malformed in (
+            {"counters": {"edits_total": 1}},
+            {"counters": {"accepts_total": "many"}},
+            {"log_write_duration_seconds": {"buckets": [1]}},
+            {"counters": []},
+        ):
+            with self.assertRaises(ValueError):
+                total.merge(malformed)
+        self.assertEqual(total.counters["rejects_total"], 2)
+
+    def test_lock_contention(self):
+        workspace = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        self.addCleanup(shutil.rmtree, workspace, True)
+        before = metrics.METRICS.counters["lock_contentions_total"]
+        holder = utils.ConversationLock(workspace, "conv1")
+        holder.acquire()
+        with self.assertRaises(TimeoutError):
+            utils.ConversationLock(workspace, "conv1").acquire(timeout=0.1)
+        holder.release()
+        with utils.ConversationLock(workspace, "conv1"):
+            pass  # Free: not counted
+        self.assertEqual(
+            metrics.METRICS.counters["lock_contentions_total"], before + 1
+        )
+
+
+@unittest.skipUnless(daemon.is_supported(), "needs fork() and Unix domain sockets")
+class TestMetricsEndpoint(unittest.TestCase):
+    def setUp(self):
+        self.workspace = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        self.builder = HistoryBuilder(self.workspace)
+        self.builder.write("a.txt", "one\n")
+        self.builder.write("b.txt", "bee\n")
+        # The daemon counts in this process; start from nothing
+        self.addCleanup(setattr, metrics, "METRICS", metrics.METRICS)
+        metrics.METRICS = metrics.Metrics()
+        self.stop = threading.Event()
+        server = daemon.bind_socket(self.workspace)
+        metrics_server = daemon.bind_metrics_socket(0)
+        self.url = daemon.get_metrics_url(metrics_server.getsockname()[1])
+        self.thread = threading.Thread(
+            target=daemon.serve,
+            args=(server, self.builder.history_root, 0.1, self.stop.is_set),
+            kwargs={"metrics_server": metrics_server},
+            daemon=True,
+        )
+        self.thread.start()
+        self.addCleanup(server.close)
+        self.addCleanup(metrics_server.close)
+
+    def tearDown(self):
+        self.stop.set()
+        self.thread.join(timeout=5)
+        shutil.rmtree(self.workspace, ignore_errors=True)
+
+    def mcpdiff(self, *args: str) -> subprocess.CompletedProcess:
+        return run_mcpdiff(self.workspace, *args, input="y\n", check=True)
+
+    def fetch(self, url: str) -> str:
+        with urllib.request.urlopen(url, timeout=5) as response:
+            self.assertEqual(
+                response.headers["Content-Type"], daemon.METRICS_CONTENT_TYPE
+            )
+            return response.read().decode("utf-8")
+
+    def test_counts_of_runs(self):
+        samples = parse_metrics(self.fetch(self.url))
+        self.assertEqual(samples["mcpdiff_accepts_total"], 0)
+
+        edit = self.builder.edit("a.txt", "two\n")
+        self.mcpdiff("accept", "-e", edit)
+        self.mcpdiff("reject", "-c", "conv1")  # The accepted edit as well
+        samples = parse_metrics(self.fetch(self.url))
+        self.assertEqual(samples["mcpdiff_accepts_total"], 1)
+        self.assertEqual(samples["mcpdiff_rejects_total"], 3)
+        self.assertGreaterEqual(samples["mcpdiff_reapply_successes_total"], 2)
+        self.assertEqual(samples["mcpdiff_reapply_failures_total"], 0)
+        self.assertGreaterEqual(
+            samples["mcpdiff_log_write_duration_seconds_count"], 2
+        )
+        result = self.mcpdiff("daemon", "status")
+        self.assertIn(f"Metrics: {self.url}", result.stdout)
+
+    def test_other_paths(self):
+        with self.assertRaises(urllib.error.HTTPError) as caught:
+            urllib.request.urlopen(self.url.replace("/metrics", "/"), timeout=5)
+        self.assertEqual(caught.exception.code, 404)
+        caught.exception.close()
+        request = urllib.request.Request(self.url, data=b"", method="POST")
+        with self.assertRaises(urllib.error.HTTPError) as caught:
+            urllib.request.urlopen(request, timeout=5)
+        self.assertEqual(caught.exception.code, 405)
+        caught.exception.close()
+        # Malformed reports are refused and change nothing
+        self.assertIsNone(
+            daemon.query(
+                self.workspace, {"op": "metrics", "metrics": {"counters": "x"}}
+            )
+        )
+        counted = metrics.Metrics()
+        counted.increment("lock_contentions_total", 5)
+        self.assertTrue(daemon.report_metrics(self.workspace, counted))
+        samples = parse_metrics(self.fetch(self.url))
+        self.assertEqual(samples["mcpdiff_lock_contentions_total"], 5)
+
+    def test_port_in_use(self):
+        with self.assertRaises(utils.HistoryError):
+            daemon.bind_metrics_socket(int(self.url.rsplit(":", 1)[1].split("/")[0]))
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,145 @@
+#!/usr/bin/env python3
+"""
+Tests for conversation namespaces (config key `namespace`):
+- Conversations logged with a namespace are <namespace>/<conv_id>, their logs,
+  diffs and checkpoints one directory further down
+- IDs given with --conv are looked for in the configured namespace
+- `conversations --namespace`, with unprefixed conversations in "default"
+- find_workspace_and_history_roots() names the namespace
+"""
+
+import json
+import shutil
+import sys
+import tempfile
+import unittest
+from pathlib import Path
+
+# The CLI modules import each other by bare name; history_builder is found also
+# when run_tests.py imports this module as part of the package
+sys.path.insert(0, str(Path(__file__).parent.parent / "cli"))
+sys.path.insert(0, str(Path(__file__).parent))
+
+import mcpdiff_api as api
+import mcpdiff_config as config
+import mcpdiff_utils as utils
+from history_builder import HistoryBuilder, run_mcpdiff
+
+
+class TestNamespaceHelpers(unittest.TestCase):
+    def test_conversation_ids(self):
+        logs_dir = Path("/ws/.mcp/edit_history/logs")
+        for path, conv_id, source in (
+            (logs_dir / "abc.log", "abc", "abc.log"),
+            (logs_dir / "abc_20250101.log", "abc", "abc_20250101.log"),
+            (logs_dir / "web" / "abc.log", "web/abc", "web/abc.log"),
+            (logs_dir / "web" / "abc_2025-W01.log", "web/abc", "web/abc_2025-W01.log"),
+        ):
+            self.assertEqual(utils.get_log_conversation_id(path), conv_id)
+            self.assertEqual(utils.get_log_file_source(path), source)
+            self.assertEqual(utils.get_log_history_root(path), logs_dir.parent)
+        self.assertEqual(utils.get_conversation_namespace("web/abc"), "web")
+        self.assertEqual(utils.get_conversation_namespace("abc"), "default")
+
+    def test_valid_ids(self):
+        for conv_id in ("abc", "web/abc", "web.v2/abc_1"):
+            self.assertTrue(utils.is_valid_conversation_id(conv_id), conv_id)
+        for conv_id in ("", "a/b/c", "web/", "web/..", "../abc", "a\\b", "."):
+            self.assertFalse(utils.is_valid_conversation_id(conv_id), conv_id)
+
+    def test_qualify(self):
+        self.assertEqual(utils.qualify_conversation_ref("abc", "web"), "web/abc")
+        self.assertEqual(utils.qualify_conversation_ref("api/abc", "web"), "api/abc")
+        self.assertEqual(utils.qualify_conversation_ref("abc", None), "abc")
+        self.assertEqual(utils.qualify_conversation_ref("abc", "default"), "abc")
+
+    def test_config(self):
+        parse = config.CONFIG_KEYS["namespace"]["parse"]
+        self.assertEqual(parse("web-app"), "web-app")
+        self.assertIsNone(parse("none"))
+        for value in ("a/b", "default", "web app", ""):
+            with self.assertRaises(ValueError):
+                parse(value)
+
+
+class TestNamespacedHistory(unittest.TestCase):
+    def setUp(self):
+        self.workspace = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        self.builder = HistoryBuilder(self.workspace)
+        # Logged before a namespace was configured
+        self.old = self.builder.conversation("conv1").write("old.txt", "old\n")
+        self.mcpdiff("config", "set", "namespace", "web")
+        self.builder.write("a.txt", "one\n")
+        self.edit = self.builder.edit("a.txt", "two\n")
+
+    def tearDown(self):
+        shutil.rmtree(self.workspace, ignore_errors=True)
+
+    def mcpdiff(self, *args: str) -> str:
+        return run_mcpdiff(self.workspace, *args, input="y\n", check=True).stdout
+
+    def test_layout(self):
+        entry = self.builder.entry(self.edit)
+        self.assertEqual(entry["conversation_id"], "web/conv1")
+        self.assertTrue(entry["diff_file"].startswith("diffs/web/conv1/"))
+        history_root = self.builder.history_root
+        self.assertTrue((history_root / "logs" / "web" / "conv1.log").is_file())
+        self.assertEqual(entry["log_file_source"], "web/conv1.log")
+        self.assertEqual(self.builder.entry(self.old)["conversation_id"], "conv1")
+
+    def test_conv_resolves_in_namespace(self):
+        self.mcpdiff("reject", "-c", "conv1")
+        self.assertEqual(self.builder.read("a.txt"), "")
+        self.assertEqual(self.builder.entry(self.edit)["status"], "rejected")
+        # The conversation without a namespace is untouched
+        self.assertEqual(self.builder.entry(self.old)["status"], "pending")
+        self.assertEqual(self.builder.read("old.txt"), "old\n")
+        self.assertIn("OK         web/conv1", self.mcpdiff("verify"))
+
+    def test_conversations(self):
+        listed = json.loads(self.mcpdiff("conversations", "--json"))
+        self.assertEqual(
+            sorted((c["conversation_id"], c["namespace"]) for c in listed),
+            [("conv1", "default"), ("web/conv1", "web")],
+        )
+        listed = json.loads(
+            self.mcpdiff("conversations", "--namespace", "default", "--json")
+        )
+        self.assertEqual([c["conversation_id"] for c in listed], ["conv1"])
+        listed = json.loads(
+            self.mcpdiff("conversations", "--namespace", "web", "--json")
+        )
+        self.assertEqual([c["conversation_id"] for c in listed], ["web/conv1"])
+
+    def test_roots(self):
+        roots = api.find_workspace_and_history_roots(str(self.workspace))
+        self.assertEqual(roots.namespace, "web")
+        workspace_root, history_root = roots
+        self.assertEqual(history_root, self.builder.history_root)
+        config.unset_config_value(workspace_root, "namespace")
+        self.assertEqual(roots.namespace, "default")
+
+    def test_log_append(self):
+        entry = {
+            "edit_id": "e1",
+            "operation": "edit",
+            "file_path": "a.txt",
+            "status": "pending",
+            "timestamp": "2025-01-01T00:00:00Z",
+        }
+        result = run_mcpdiff(
+            self.workspace,
+            "log-append",
+            "--conv",
+            "conv2",
+            input=json.dumps(entry),
+            check=True,
+        )
+        self.assertEqual(self.builder.entry("e1")["conversation_id"], "web/conv2")
+        self.assertTrue(
+            (self.builder.history_root / "logs" / "web" / "conv2.log").is_file()
+        )
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,113 @@
+#!/usr/bin/env python3
+"""
+Tests for partial edits, which store only the bytes written over the range of
+a large file that changed:
+- Entries record the byte range, and their diff file the new bytes
+- Rejecting and re-applying splice the bytes in; offsets only fit the content
+  the edit was made on
+- `show` prints the range, and a hex dump for bytes that are not text
+- `blame` attributes the lines the range touched to the edit
+"""
+
+import shutil
+import subprocess
+import sys
+import tempfile
+import unittest
+from pathlib import Path
+
+# The CLI modules import each other by bare name; history_builder is found also
+# when run_tests.py imports this module as part of the package
+sys.path.insert(0, str(Path(__file__).parent.parent / "cli"))
+sys.path.insert(0, str(Path(__file__).parent))
+
+import mcpdiff_api as api
+import mcpdiff_patch as patch
+import mcpdiff_replay as replay
+import mcpdiff_utils as utils
+from history_builder import HistoryBuilder, run_mcpdiff
+
+
+BASE = "".join(f"line {i}\n" for i in range(20))
+
+
+class TestPartialEdits(unittest.TestCase):
+    def setUp(self):
+        self.workspace = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        self.builder = builder = HistoryBuilder(self.workspace)
+        builder.partial_min_size = 64
+        self.states = [
+            BASE,
+            BASE.replace("line 5\n", "line five\n"),
+            BASE.replace("line 5\n", "line five\n").replace("line 12", "line 12!"),
+        ]
+        self.edits = [builder.write("big.txt", self.states[0])]
+        self.edits += [builder.edit("big.txt", state) for state in self.states[1:]]
+
+    def tearDown(self):
+        shutil.rmtree(self.workspace, ignore_errors=True)
+
+    def mcpdiff(self, *args: str) -> subprocess.CompletedProcess:
+        return run_mcpdiff(self.workspace, *args, input="y\n")
+
+    def test_entries(self):
+        self.assertEqual(self.builder.entry(self.edits[0])["operation"], "create")
+        entry = api.LogEntry.from_dict(self.builder.entry(self.edits[1]))
+        self.assertEqual(entry.operation, api.Operation.PARTIAL)
+        start = BASE.index("5\n")
+        self.assertEqual(
+            (entry.byte_range_start, entry.byte_range_end), (start, start + 1)
+        )
+        diff_path = self.builder.history_root / entry.diff_file
+        self.assertEqual(diff_path.read_bytes(), b"five")
+
+    def test_reject_and_reapply(self):
+        result = self.mcpdiff("reject", "-e", self.edits[2])
+        self.assertEqual(result.returncode, 0, result.stdout + result.stderr)
+        self.assertEqual(self.builder.read("big.txt"), self.states[1])
+
+        self.builder.path("big.txt").write_text("clobbered\n")
+        results = api.reapply_conversation_state(
+            self.workspace, self.builder.history_root, "conv1"
+        )
+        self.assertIsNone(results["big.txt"]["error"])
+        self.assertEqual(self.builder.read("big.txt"), self.states[1])
+
+    def test_reject_middle_edit_conflicts(self):
+        # The last edit's offsets only fit the content with the middle one
+        result = self.mcpdiff("reject", "-e", self.edits[1])
+        self.assertEqual(result.returncode, utils.PatchConflictError.exit_code)
+        self.assertEqual(self.builder.read("big.txt"), self.states[2])
+
+    def test_show(self):
+        result = self.mcpdiff("show", self.edits[1])
+        self.assertEqual(result.returncode, 0, result.stdout + result.stderr)
+        start = BASE.index("5\n")
+        self.assertIn(f"Bytes {start}-{start + 1} replaced with 4 bytes", result.stdout)
+        self.assertIn("+five", result.stdout)
+
+        content = self.builder.path("big.txt").read_bytes()
+        binary = content.replace(b"line 0", b"\x00\xff\x01")
+        edit_id = self.builder.write("big.txt", binary)
+        result = self.mcpdiff("show", edit_id)
+        self.assertEqual(result.returncode, 0, result.stdout + result.stderr)
+        self.assertIn("00000000  00 ff 01", result.stdout)
+
+    def test_blame(self):
+        lines = replay.replay_file(
+            "big.txt", self.builder.entries(), self.builder.history_root
+        )
+        self.assertEqual(replay.render(lines), self.states[2])
+        blamed = {i: line.edit_id for i, line in enumerate(lines)}
+        self.assertEqual(blamed[5], self.edits[1])
+        self.assertEqual(blamed[12], self.edits[2])
+        self.assertEqual(blamed[11], self.edits[0])
+
+    def test_splice_out_of_range(self):
+        with self.assertRaises(patch.PatchError):
+            patch.splice_bytes(b"abc", 2, 4, b"x")
+        self.assertEqual(patch.splice_bytes(b"abc", 1, 2, b"xy"), b"axyc")
+
+
+if __name__ == "__main__":
+    unittest.main()
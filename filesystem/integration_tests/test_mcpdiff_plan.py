@@ -0,0 +1,233 @@
+#!/usr/bin/env python3
+"""
+Tests for replay plans, which say how accept and reject re-apply a file:
+- plan_file_replay() on hand-written histories (moves, rejected creates,
+  missing checkpoints and diffs), without touching the filesystem
+- `mcpdiff plan` prints the plan as a table or JSON and changes nothing
+"""
+
+import json
+import shutil
+import subprocess
+import sys
+import tempfile
+import unittest
+from pathlib import Path
+from typing import Any, Dict, Optional
+
+# The CLI modules import each other by bare name; history_builder is found also
+# when run_tests.py imports this module as part of the package
+sys.path.insert(0, str(Path(__file__).parent.parent / "cli"))
+sys.path.insert(0, str(Path(__file__).parent))
+
+import mcpdiff_history as history
+from history_builder import HistoryBuilder, run_mcpdiff
+
+
+HISTORY_ROOT = Path("/history")
+
+
+def entry(
+    index: int,
+    operation: str,
+    file_path: str,
+    status: str = "pending",
+    source_path: Optional[str] = None,
+    checkpoint: bool = False,
+    **fields: Any,
+) -> Dict[str, Any]:
+    edit_id = f"edit{index}"
+    result = {
+        "edit_id": edit_id,
+        "conversation_id": "conv1",
+        "tool_call_index": index,
+        "timestamp": f"2026-01-01T00:00:{index:02d}Z",
+        "operation": operation,
+        "file_path": file_path,
+        "source_path": source_path,
+        "tool_name": "edit_file",
+        "status": status,
+        "diff_file": f"diffs/conv1/{edit_id}.diff",
+        "checkpoint_file": f"checkpoints/conv1/{edit_id}.chkpt" if checkpoint else None,
+        "hash_after": f"hash{index}",
+    }
+    result.update(fields)
+    return result
+
+
+class TestPlanFileReplay(unittest.TestCase):
+    def setUp(self):
+        # Every diff and checkpoint exists unless listed here
+        self.missing = set()
+
+    def plan(self, file_path: str, entries, **options) -> history.ReplayPlan:
+        return history.plan_file_replay(
+            file_path,
+            entries,
+            HISTORY_ROOT,
+            exists=lambda path: path.name not in self.missing,
+            **options,
+        )
+
+    def actions(self, plan: history.ReplayPlan):
+        return [(step.edit_id, step.action) for step in plan.steps]
+
+    def test_create_and_edits(self):
+        entries = [
+            entry(1, "create", "a.txt"),
+            entry(2, "edit", "a.txt", status="accepted"),
+            entry(3, "edit", "a.txt", status="rejected"),
+            entry(4, "edit", "b.txt"),
+        ]
+        plan = self.plan("a.txt", entries)
+        self.assertEqual(plan.start, history.PLAN_START_EMPTY)
+        self.assertEqual(plan.start_edit_id, "edit1")
+        self.assertEqual(
+            self.actions(plan),
+            [("edit1", "apply"), ("edit2", "apply"), ("edit3", "skip")],
+        )
+        self.assertEqual(plan.verified_step().edit_id, "edit2")
+
+        plan = self.plan("a.txt", entries, apply_only_accepted=True)
+        self.assertEqual(self.actions(plan)[0], ("edit1", "skip"))
+        self.assertIsNone(plan.verified_step())
+
+    def test_rejected_create(self):
+        entries = [
+            entry(1, "create", "a.txt", status="rejected"),
+            entry(2, "edit", "a.txt"),
+        ]
+        plan = self.plan("a.txt", entries)
+        self.assertEqual(plan.start, history.PLAN_START_EMPTY)
+        self.assertEqual(self.actions(plan), [("edit1", "skip"), ("edit2", "apply")])
+        # The edit was made to the created content, so the result is not it
+        self.assertIsNone(plan.verified_step())
+        self.assertIsNone(plan.to_dict()["expected_hash"])
+
+    def test_checkpoint_after_rejected_edit_is_passed_over(self):
+        entries = [
+            entry(1, "edit", "a.txt", status="accepted", checkpoint=True),
+            entry(2, "edit", "a.txt", status="rejected", checkpoint=True),
+            entry(3, "edit", "a.txt", checkpoint=True),
+        ]
+        plan = self.plan("a.txt", entries)
+        self.assertEqual(plan.start, history.PLAN_START_CHECKPOINT)
+        self.assertEqual(plan.start_edit_id, "edit2")
+        self.assertEqual(
+            plan.to_dict(HISTORY_ROOT)["checkpoint_file"],
+            "checkpoints/conv1/edit2.chkpt",
+        )
+        self.assertEqual(self.actions(plan), [("edit2", "skip"), ("edit3", "apply")])
+
+        # Rejecting edit1 as well leaves only its checkpoint
+        entries[0]["status"] = "rejected"
+        plan = self.plan("a.txt", entries)
+        self.assertEqual(plan.start_edit_id, "edit1")
+        self.assertEqual(len(plan.steps), 3)
+
+    def test_missing_checkpoint(self):
+        entries = [
+            entry(1, "edit", "a.txt", checkpoint=True),
+            entry(2, "edit", "a.txt"),
+        ]
+        self.missing.add("edit1.chkpt")
+        plan = self.plan("a.txt", entries)
+        self.assertEqual(plan.start, history.PLAN_START_MISSING_CHECKPOINT)
+        self.assertEqual(plan.missing_checkpoint, "checkpoints/conv1/edit1.chkpt")
+
+        entries[0]["checkpoint_file"] = None
+        self.assertEqual(self.plan("a.txt", entries).start, history.PLAN_START_UNKNOWN)
+
+    def test_missing_diff(self):
+        entries = [entry(1, "create", "a.txt"), entry(2, "edit", "a.txt")]
+        self.missing.add("edit2.diff")
+        steps = self.plan("a.txt", entries).to_dict(HISTORY_ROOT)["steps"]
+        self.assertEqual(steps[0]["diff_file"], "diffs/conv1/edit1.diff")
+        self.assertFalse(steps[0]["diff_missing"])
+        self.assertIsNone(steps[1]["diff_file"])
+        self.assertTrue(steps[1]["diff_missing"])
+
+    def test_moves(self):
+        entries = [
+            entry(1, "create", "a.txt", status="accepted"),
+            entry(2, "move", "b.txt", source_path="a.txt", status="rejected"),
+            entry(3, "edit", "b.txt"),
+        ]
+        # The move away is skipped, so a.txt keeps its content
+        plan = self.plan("a.txt", entries)
+        self.assertEqual(self.actions(plan), [("edit1", "apply"), ("edit2", "skip")])
+
+        # The content never arrived at b.txt, which is cleared
+        plan = self.plan("b.txt", entries)
+        self.assertEqual(plan.start, history.PLAN_START_UNKNOWN)
+        self.assertEqual(self.actions(plan), [("edit2", "clear"), ("edit3", "apply")])
+
+        entries[1]["status"] = "pending"
+        self.assertEqual(
+            self.actions(self.plan("b.txt", entries)),
+            [("edit2", "apply"), ("edit3", "apply")],
+        )
+
+    def test_rejected_parent(self):
+        entries = [
+            entry(1, "create", "a.txt", status="rejected"),
+            entry(2, "edit", "b.txt", parent_edit_id="edit1"),
+        ]
+        plan = self.plan("b.txt", entries)
+        self.assertEqual(plan.steps[0].rejected_parent, "edit1")
+        self.assertEqual(plan.to_dict()["steps"][0]["rejected_parent"], "edit1")
+
+    def test_no_history(self):
+        plan = self.plan("c.txt", [entry(1, "create", "a.txt")])
+        self.assertEqual(plan.start, history.PLAN_START_NO_HISTORY)
+        self.assertEqual(plan.steps, [])
+
+
+class TestPlanCommand(unittest.TestCase):
+    def setUp(self):
+        self.workspace = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        self.builder = HistoryBuilder(self.workspace)
+        self.create = self.builder.write("a.txt", "one\n")
+        self.edit = self.builder.edit("a.txt", "two\n")
+        self.builder.write("b.txt", "b\n")
+
+    def tearDown(self):
+        shutil.rmtree(self.workspace, ignore_errors=True)
+
+    def mcpdiff(self, *args: str) -> subprocess.CompletedProcess:
+        return run_mcpdiff(self.workspace, *args, check=True)
+
+    def test_table(self):
+        before = self.builder.entries()
+        output = self.mcpdiff("plan", "-c", "conv1").stdout
+        self.assertIn("Re-applying a.txt", output)
+        self.assertIn("Re-applying b.txt", output)
+        self.assertIn(f"created by edit {self.create[:8]}", output)
+        self.assertIn(self.edit[:8], output)
+        self.assertIn(f"as recorded by edit {self.edit[:8]}", output)
+        # Planning changes nothing
+        self.assertEqual(self.builder.entries(), before)
+        self.assertEqual(self.builder.read("a.txt"), "two\n")
+
+    def test_json_for_one_file(self):
+        output = self.mcpdiff("plan", "-c", "conv1", "-f", "a.txt", "--json").stdout
+        plans = json.loads(output)
+        self.assertEqual([plan["file_path"] for plan in plans], ["a.txt"])
+        self.assertEqual(plans[0]["start"], history.PLAN_START_EMPTY)
+        steps = plans[0]["steps"]
+        self.assertEqual([step["edit_id"] for step in steps], [self.create, self.edit])
+        diff_file = self.builder.entry(self.edit)["diff_file"]
+        self.assertEqual(steps[1]["diff_file"], diff_file)
+        self.assertEqual(plans[0]["expected_hash"], steps[1]["hash_after"])
+
+        output = self.mcpdiff(
+            "plan", "-c", "conv1", "-f", "a.txt", "--json", "--accepted"
+        ).stdout
+        self.assertEqual(
+            [step["action"] for step in json.loads(output)[0]["steps"]],
+            ["skip", "skip"],
+        )
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,309 @@
+#!/usr/bin/env python3
+"""
+Tests for the platform-independent parts of the mcpdiff CLI:
+- Path normalization used when matching --file against logged paths
+- Pure-Python unified diff application (offsets, CRLF files, missing newlines)
+- Hiding whitespace-only changes when showing a diff
+- End-to-end apply/revert and locking on Windows (skipped elsewhere)
+"""
+
+import json
+import os
+import shutil
+import sys
+import tempfile
+import unittest
+from pathlib import Path
+
+# The CLI modules import each other by bare name; history_builder is found also
+# when run_tests.py imports this module as part of the package
+sys.path.insert(0, str(Path(__file__).parent.parent / "cli"))
+sys.path.insert(0, str(Path(__file__).parent))
+
+import mcpdiff_history as history
+import mcpdiff_utils as utils
+import mcpdiff_patch as patch
+from mcpdiff_patch import PatchError, apply_diff_to_file, apply_unified_diff
+from history_builder import make_diff
+
+
+class TestPathNormalization(unittest.TestCase):
+    """Test the path helpers used to compare paths across platforms."""
+
+    def test_strip_long_path_prefix(self):
+        """The \\\\?\\ prefix is removed, including the UNC form."""
+        self.assertEqual(
+            utils.strip_long_path_prefix("\\\\?\\C:\\work\\src\\main.rs"),
+            "C:\\work\\src\\main.rs",
+        )
+        self.assertEqual(
+            utils.strip_long_path_prefix("\\\\?\\UNC\\server\\share\\f.txt"),
+            "\\\\server\\share\\f.txt",
+        )
+        self.assertEqual(utils.strip_long_path_prefix("/home/u/f.txt"), "/home/u/f.txt")
+
+    def test_normalize_separators_and_case(self):
+        """Separators become '/', and case is folded only when requested."""
+        self.assertEqual(
+            utils.normalize_path_for_compare(".\\Src\\Lib.rs", case_insensitive=True),
+            "src/lib.rs",
+        )
+        self.assertEqual(
+            utils.normalize_path_for_compare("./Src/Lib.rs/", case_insensitive=False),
+            "Src/Lib.rs",
+        )
+        self.assertEqual(
+            utils.normalize_path_for_compare("/", case_insensitive=False), "/"
+        )
+
+    def test_path_matches_filter(self):
+        """--file matches regardless of separator style, and of case if requested."""
+        self.assertTrue(utils.path_matches_filter("src/lib.rs", "src\\lib.rs"))
+        self.assertTrue(
+            utils.path_matches_filter("src/Lib.rs", "SRC\\lib", case_insensitive=True)
+        )
+        self.assertFalse(
+            utils.path_matches_filter("src/Lib.rs", "SRC\\lib", case_insensitive=False)
+        )
+
+    def test_is_path_within_directory_requires_separator(self):
+        """A sibling directory sharing a name prefix is not inside the directory."""
+        base = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        try:
+            (base / "ws").mkdir()
+            (base / "ws2").mkdir()
+            self.assertTrue(utils.is_path_within_directory(base / "ws" / "f", base / "ws"))
+            self.assertTrue(utils.is_path_within_directory(base / "ws", base / "ws"))
+            self.assertFalse(utils.is_path_within_directory(base / "ws2", base / "ws"))
+        finally:
+            shutil.rmtree(base)
+
+
+class TestPatchApplication(unittest.TestCase):
+    """Test applying the server's unified diffs without external tools."""
+
+    BEFORE = "a\nb\nc\nd\ne\nf\ng\n"
+    AFTER = "a\nB\nc\nd\ne\nf\ng\nh\n"
+
+    def test_apply_and_reverse(self):
+        diff = make_diff(self.BEFORE, self.AFTER)
+        self.assertEqual(apply_unified_diff(self.BEFORE, diff), self.AFTER)
+        self.assertEqual(
+            apply_unified_diff(self.AFTER, diff, reverse=True), self.BEFORE
+        )
+
+    def test_hunk_offset(self):
+        """Hunks apply when lines were inserted above them."""
+        diff = make_diff(self.BEFORE, self.AFTER)
+        self.assertEqual(
+            apply_unified_diff("x\ny\n" + self.BEFORE, diff), "x\ny\n" + self.AFTER
+        )
+
+    def test_crlf_file_keeps_its_line_endings(self):
+        """An LF diff applies to a CRLF file, and added lines use CRLF."""
+        diff = make_diff(self.BEFORE, self.AFTER)
+        crlf_before = self.BEFORE.replace("\n", "\r\n")
+        crlf_after = self.AFTER.replace("\n", "\r\n")
+        self.assertEqual(apply_unified_diff(crlf_before, diff), crlf_after)
+        self.assertEqual(
+            apply_unified_diff(crlf_after, diff, reverse=True), crlf_before
+        )
+
+    def test_crlf_diff_on_lf_file(self):
+        """A diff recorded with CRLF lines applies to an LF file."""
+        diff = make_diff(self.BEFORE, self.AFTER).replace("\n", "\r\n")
+        self.assertEqual(apply_unified_diff(self.BEFORE, diff), self.AFTER)
+
+    def test_no_newline_at_end_of_file(self):
+        diff = make_diff("", "x\ny")
+        self.assertEqual(apply_unified_diff("", diff), "x\ny")
+        self.assertEqual(apply_unified_diff("x\ny", diff, reverse=True), "")
+
+    def test_mismatched_context_fails(self):
+        diff = make_diff(self.BEFORE, self.AFTER)
+        with self.assertRaises(PatchError):
+            apply_unified_diff("something else\n", diff)
+
+    def test_apply_diff_to_file_leaves_target_on_failure(self):
+        base = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        try:
+            target = base / "file.txt"
+            diff_path = base / "edit.diff"
+            diff_path.write_text(make_diff(self.BEFORE, self.AFTER))
+            target.write_bytes(b"unrelated\r\n")
+            with self.assertRaises(PatchError):
+                apply_diff_to_file(target, diff_path)
+            self.assertEqual(target.read_bytes(), b"unrelated\r\n")
+        finally:
+            shutil.rmtree(base)
+
+
+class TestPatchFuzz(unittest.TestCase):
+    """Test loose context matching (reject --fuzz / --ignore-whitespace)."""
+
+    BEFORE = "".join(f"line {i}\n" for i in range(1, 21))
+    AFTER = BEFORE.replace("line 10\n", "ten\n")
+
+    def test_stale_context_needs_fuzz(self):
+        diff = make_diff(self.BEFORE, self.AFTER)
+        stale = "x\n" + self.BEFORE.replace("line 7\n", "line seven\n")
+        with self.assertRaises(PatchError):
+            patch.apply_unified_diff_reporting(stale, diff)
+        result, messages = patch.apply_unified_diff_reporting(stale, diff, fuzz=1)
+        self.assertEqual(
+            result, "x\n" + self.AFTER.replace("line 7\n", "line seven\n")
+        )
+        self.assertEqual(messages, ["Hunk #1 applied with fuzz 1 at offset +1"])
+
+    def test_fuzz_only_drops_outer_context(self):
+        diff = make_diff(self.BEFORE, self.AFTER)
+        stale = self.BEFORE.replace("line 8\n", "line eight\n")
+        with self.assertRaises(PatchError):
+            patch.apply_unified_diff_reporting(stale, diff, fuzz=1)
+        _, messages = patch.apply_unified_diff_reporting(stale, diff, fuzz=2)
+        self.assertEqual(messages, ["Hunk #1 applied with fuzz 2 at offset +0"])
+
+    def test_ignore_whitespace(self):
+        diff = make_diff(self.BEFORE, self.AFTER)
+        spaced = self.BEFORE.replace("line 9\n", "line  9 \n")
+        with self.assertRaises(PatchError):
+            patch.apply_unified_diff_reporting(spaced, diff)
+        result, messages = patch.apply_unified_diff_reporting(
+            spaced, diff, ignore_whitespace=True
+        )
+        # Context lines keep the file's own whitespace
+        self.assertEqual(result, self.AFTER.replace("line 9\n", "line  9 \n"))
+        self.assertEqual(messages, ["Hunk #1 applied ignoring whitespace at offset +0"])
+
+    def test_exact_match_is_not_reported(self):
+        diff = make_diff(self.BEFORE, self.AFTER)
+        _, messages = patch.apply_unified_diff_reporting(
+            "x\n" + self.BEFORE, diff, fuzz=3, ignore_whitespace=True
+        )
+        self.assertEqual(messages, [])
+
+    def test_fuzz_applies_to_files(self):
+        base = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        try:
+            target = base / "file.txt"
+            diff_path = base / "edit.diff"
+            diff_path.write_text(make_diff(self.BEFORE, self.AFTER))
+            stale = self.BEFORE.replace("line 13\n", "line 13!\n")
+            target.write_text(stale)
+            self.assertEqual(
+                apply_diff_to_file(target, diff_path, fuzz=1),
+                ["Hunk #1 applied with fuzz 1 at offset +0"],
+            )
+            self.assertIn("ten\n", target.read_text())
+            # Nothing carries over to later calls
+            target.write_text(stale)
+            with self.assertRaises(PatchError):
+                apply_diff_to_file(target, diff_path)
+        finally:
+            shutil.rmtree(base)
+
+
+class TestOmitWhitespaceChanges(unittest.TestCase):
+    """Test the display filter behind show --ignore-whitespace."""
+
+    BEFORE = "def f():\n    x = 1\n    y = 2\n    return x\n"
+
+    def test_reindented_lines_become_context(self):
+        after = "def f():\n  x = 1  \n    y = 3\n    return x\n"
+        shown = patch.omit_whitespace_changes(make_diff(self.BEFORE, after))
+        self.assertEqual(
+            shown.splitlines()[2:],
+            [
+                "@@ -1,4 +1,4 @@ (whitespace changes omitted)",
+                " def f():",
+                "   x = 1  ",
+                "-    y = 2",
+                "+    y = 3",
+                "     return x",
+            ],
+        )
+
+    def test_whitespace_only_hunk_keeps_its_header(self):
+        after = self.BEFORE.replace("    return", "\treturn")
+        shown = patch.omit_whitespace_changes(make_diff(self.BEFORE, after))
+        self.assertEqual(
+            shown.splitlines()[2:], ["@@ -1,4 +1,4 @@ (whitespace changes omitted)"]
+        )
+
+    def test_other_diffs_unchanged(self):
+        diff = make_diff(self.BEFORE, self.BEFORE.replace("y = 2", "y = 3"))
+        self.assertEqual(patch.omit_whitespace_changes(diff), diff)
+        move = "OPERATION: MOVE\nSource: a\nDestination: b"
+        self.assertEqual(patch.omit_whitespace_changes(move), move)
+
+
+@unittest.skipUnless(os.name == "nt", "Windows-only")
+class TestWindowsWorkspace(unittest.TestCase):
+    """End-to-end checks of the CLI's history operations on Windows."""
+
+    def setUp(self):
+        self.workspace = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        self.history_root = self.workspace / ".mcp" / utils.HISTORY_DIR_NAME
+        for name in (utils.LOGS_DIR, utils.DIFFS_DIR, utils.CHECKPOINTS_DIR):
+            (self.history_root / name / "conv1").mkdir(parents=True)
+        self.target = self.workspace / "Src" / "Main.txt"
+        self.target.parent.mkdir()
+        self.diff_rel = "conv1/edit1.diff"
+        (self.history_root / utils.DIFFS_DIR / self.diff_rel).write_text(
+            make_diff("one\ntwo\n", "one\n2\n", "Src/Main.txt")
+        )
+        self.entry = {
+            "edit_id": "edit1",
+            "conversation_id": "conv1",
+            "operation": "edit",
+            "file_path": "Src\\Main.txt",
+            "diff_file": self.diff_rel,
+        }
+
+    def tearDown(self):
+        shutil.rmtree(self.workspace, ignore_errors=True)
+
+    def test_apply_and_revert_crlf_file(self):
+        self.target.write_bytes(b"one\r\ntwo\r\n")
+        self.assertTrue(
+            history.apply_or_revert_edit(self.entry, self.workspace, self.history_root)
+        )
+        self.assertEqual(self.target.read_bytes(), b"one\r\n2\r\n")
+        self.assertTrue(
+            history.apply_or_revert_edit(
+                self.entry, self.workspace, self.history_root, is_revert=True
+            )
+        )
+        self.assertEqual(self.target.read_bytes(), b"one\r\ntwo\r\n")
+
+    def test_file_filter_ignores_case_and_separators(self):
+        entries = history.filter_entries([self.entry], file_path="src/main.TXT")
+        self.assertEqual(len(entries), 1)
+
+    def test_workspace_root_without_long_path_prefix(self):
+        root = history.find_workspace_root("\\\\?\\" + str(self.target.parent))
+        self.assertIsNotNone(root)
+        self.assertFalse(str(root).startswith("\\\\?\\"))
+        self.assertEqual(
+            history.get_relative_path(self.target, root).replace("\\", "/"),
+            "Src/Main.txt",
+        )
+
+    def test_locks_exclude_each_other(self):
+        lock_path = str(self.history_root / utils.LOGS_DIR / "conv1.log")
+        with utils.FileLock(lock_path):
+            with self.assertRaises(TimeoutError):
+                utils.FileLock(lock_path).acquire(timeout=0.3)
+        with utils.ConversationLock(self.history_root, "conv1"):
+            with self.assertRaises(TimeoutError):
+                utils.ConversationLock(self.history_root, "conv1").acquire(timeout=0.3)
+
+    def test_log_round_trip(self):
+        log_file = self.history_root / utils.LOGS_DIR / "conv1.log"
+        log_file.write_text(json.dumps(self.entry) + "\n")
+        entries = utils.read_all_shards(self.history_root, "conv1")
+        self.assertEqual([e["edit_id"] for e in entries], ["edit1"])
+
+
+if __name__ == "__main__":
+    unittest.main()
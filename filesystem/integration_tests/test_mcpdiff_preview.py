@@ -0,0 +1,103 @@
+#!/usr/bin/env python3
+"""
+Tests for `mcpdiff preview reject`, which re-applies a conversation's files as
+reject would, in a staging directory under .mcp/preview:
+- the delta against the working tree, as text and JSON, with --file
+- the workspace and the history are left alone, and the staging directory is
+  removed unless --keep-staged
+- the staged files are what reject then writes
+"""
+
+import json
+import shutil
+import subprocess
+import sys
+import tempfile
+import unittest
+from pathlib import Path
+
+sys.path.insert(0, str(Path(__file__).parent.parent / "cli"))
+sys.path.insert(0, str(Path(__file__).parent))
+
+from history_builder import HistoryBuilder, run_mcpdiff
+
+
+class TestPreviewReject(unittest.TestCase):
+    def setUp(self):
+        self.workspace = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        self.builder = HistoryBuilder(self.workspace)
+        self.builder.write("a.txt", "one\n")
+        self.builder.conversation("conv2")
+        self.edit = self.builder.edit("a.txt", "two\n")
+        self.builder.write("b.txt", "b\n")
+
+    def tearDown(self):
+        shutil.rmtree(self.workspace, ignore_errors=True)
+
+    def mcpdiff(self, *args: str, returncode: int = 0) -> subprocess.CompletedProcess:
+        result = run_mcpdiff(self.workspace, *args)
+        self.assertEqual(result.returncode, returncode, result.stdout + result.stderr)
+        return result
+
+    def preview_json(self, *args: str):
+        output = self.mcpdiff("preview", "reject", "-c", "conv2", "--json", *args)
+        return {p["file_path"]: p for p in json.loads(output.stdout)}
+
+    def test_delta_against_working_tree(self):
+        before = self.builder.entries()
+        output = self.mcpdiff("preview", "reject", "-c", "conv2").stdout
+        self.assertIn("--- a.txt would change ---", output)
+        self.assertIn("-two", output)
+        self.assertIn("+one", output)
+        # A rejected create leaves the file empty
+        self.assertIn("--- b.txt would change ---", output)
+        self.assertIn("-b\n", output.replace("\x1b[0m", ""))
+        self.assertIn("would change 2 of 2 file(s)", output)
+
+        # Nothing changes, and nothing is left staged
+        self.assertEqual(self.builder.entries(), before)
+        self.assertEqual(self.builder.read("a.txt"), "two\n")
+        self.assertEqual(self.builder.read("b.txt"), "b\n")
+        self.assertFalse((self.workspace / ".mcp" / "preview").exists())
+
+    def test_json_for_one_file(self):
+        previews = self.preview_json("-f", "a.txt")
+        self.assertEqual(list(previews), ["a.txt"])
+        self.assertEqual(previews["a.txt"]["state"], "modified")
+        self.assertIn("-two\n+one\n", previews["a.txt"]["diff"])
+        self.assertIsNone(previews["a.txt"]["error"])
+        self.assertIsNone(previews["a.txt"]["staged_path"])
+
+        # Edits already rejected leave nothing to preview
+        self.mcpdiff("reject", "-c", "conv2", "-y")
+        output = self.mcpdiff("preview", "reject", "-c", "conv2").stdout
+        self.assertIn("No pending/accepted edits found to reject", output)
+
+    def test_keep_staged_matches_reject(self):
+        previews = self.preview_json("--keep-staged")
+        self.assertEqual(previews["b.txt"]["state"], "modified")
+        staged = Path(previews["a.txt"]["staged_path"])
+        self.assertEqual(staged.parent.parent, self.workspace / ".mcp" / "preview")
+        self.assertEqual(staged.read_text(), "one\n")
+        self.assertEqual((staged.parent / "b.txt").read_text(), "")
+
+        self.mcpdiff("reject", "-c", "conv2", "-y")
+        self.assertEqual(self.builder.read("a.txt"), staged.read_text())
+        self.assertEqual(self.builder.read("b.txt"), "")
+
+    def test_replay_failure(self):
+        diff_file = self.builder.entry(self.edit)["diff_file"]
+        (self.builder.history_root / diff_file).write_text(
+            "--- a/a.txt\n+++ b/a.txt\n@@ -1 +1 @@\n-zero\n+two\n"
+        )
+        self.builder.conversation("conv3").edit("a.txt", "three\n")
+        result = self.mcpdiff(
+            "preview", "reject", "-c", "conv2", "-f", "a.txt", returncode=6
+        )
+        self.assertIn("a.txt: cannot be re-applied", result.stdout)
+        self.assertEqual(self.builder.read("a.txt"), "three\n")
+        self.assertFalse((self.workspace / ".mcp" / "preview").exists())
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,106 @@
+#!/usr/bin/env python3
+"""
+Tests for `mcpdiff prune --keep-last N`:
+- Conversations older than the N most recent lose their diffs and checkpoints,
+  while their log entries are kept and marked archived
+- --keep-artifacts-for-pending spares conversations with pending edits
+- --dry-run deletes nothing, and `verify` still passes after pruning
+- Archived edits can no longer be accepted or rejected
+"""
+
+import shutil
+import subprocess
+import sys
+import tempfile
+import unittest
+from pathlib import Path
+
+# The CLI modules import each other by bare name; history_builder is found also
+# when run_tests.py imports this module as part of the package
+sys.path.insert(0, str(Path(__file__).parent.parent / "cli"))
+sys.path.insert(0, str(Path(__file__).parent))
+
+import mcpdiff_history as history
+import mcpdiff_utils as utils
+from history_builder import HistoryBuilder, run_mcpdiff
+
+
+class TestPruneConversations(unittest.TestCase):
+    def setUp(self):
+        self.workspace = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        self.builder = HistoryBuilder(self.workspace)
+        self.edits = {}
+        for conv_id in ("conv1", "conv2", "conv3"):
+            self.builder.conversation(conv_id)
+            self.builder.write(f"{conv_id}.txt", "one\n")
+            self.edits[conv_id] = self.builder.edit(f"{conv_id}.txt", "two\n")
+        for conv_id in ("conv1", "conv3"):
+            for entry in self.builder.entries():
+                if entry["conversation_id"] == conv_id:
+                    history.update_entry_status(
+                        entry, "accepted", self.builder.history_root
+                    )
+
+    def tearDown(self):
+        shutil.rmtree(self.workspace, ignore_errors=True)
+
+    def mcpdiff(self, *args: str) -> subprocess.CompletedProcess:
+        return run_mcpdiff(self.workspace, *args, input="y\n")
+
+    def artifacts(self, conv_id: str) -> list:
+        return [
+            path
+            for name in (utils.DIFFS_DIR, utils.CHECKPOINTS_DIR)
+            for path in (self.builder.history_root / name / conv_id).glob("*")
+        ]
+
+    def test_keeps_the_newest(self):
+        self.assertTrue(self.artifacts("conv1"))
+        result = self.mcpdiff("prune", "--keep-last", "1")
+        self.assertEqual(result.returncode, 0, result.stdout + result.stderr)
+        self.assertIn(
+            "Pruned the diffs and checkpoints of 2 conversations", result.stdout
+        )
+
+        self.assertEqual(self.artifacts("conv1") + self.artifacts("conv2"), [])
+        self.assertTrue(self.artifacts("conv3"))
+        for entry in self.builder.entries():
+            archived = entry["conversation_id"] != "conv3"
+            self.assertEqual(entry.get("archived", False), archived)
+            if archived:
+                self.assertIsNone(entry["diff_file"])
+                self.assertIsNone(entry["checkpoint_file"])
+        self.assertEqual(len(self.builder.entries()), 6)  # Logs are kept
+        self.assertEqual(self.mcpdiff("verify").returncode, 0)
+
+    def test_keep_artifacts_for_pending(self):
+        plan = history.plan_conversation_pruning(
+            self.builder.history_root,
+            self.builder.entries(),
+            1,
+            keep_artifacts_for_pending=True,
+        )
+        self.assertEqual([item["conversation_id"] for item in plan], ["conv1"])
+        self.assertEqual(
+            plan[0]["bytes"], sum(p.stat().st_size for p in self.artifacts("conv1"))
+        )
+
+    def test_dry_run(self):
+        result = self.mcpdiff("prune", "--keep-last", "0", "--dry-run")
+        self.assertIn(
+            "Would prune the diffs and checkpoints of 3 conversations", result.stdout
+        )
+        self.assertTrue(all(self.artifacts(c) for c in ("conv1", "conv2", "conv3")))
+        self.assertFalse(any(e.get("archived") for e in self.builder.entries()))
+
+    def test_archived_edit_is_not_rejected(self):
+        self.mcpdiff("prune", "--keep-last", "1")
+        result = self.mcpdiff("reject", "-e", self.edits["conv2"])
+        self.assertEqual(result.returncode, utils.HistoryError.exit_code)
+        self.assertIn("is archived", result.stdout)
+        self.assertEqual(self.builder.entry(self.edits["conv2"])["status"], "pending")
+        self.assertEqual(self.builder.read("conv2.txt"), "two\n")
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,132 @@
+#!/usr/bin/env python3
+"""
+Tests for `mcpdiff reattach`, which moves edits of a restarted session back
+to the conversation they belong to:
+- All edits, or a single one, move with their tool_call_index renumbered
+- Moved edits keep their diffs and can be rejected with their new conversation
+- Bad references change nothing
+- A failed write to the destination changes nothing, and one to the source is
+  finished by running reattach again
+"""
+
+import shutil
+import subprocess
+import sys
+import tempfile
+import unittest
+from pathlib import Path
+from unittest import mock
+
+# The CLI modules import each other by bare name; history_builder is found also
+# when run_tests.py imports this module as part of the package
+sys.path.insert(0, str(Path(__file__).parent.parent / "cli"))
+sys.path.insert(0, str(Path(__file__).parent))
+
+import mcpdiff_audit as audit
+import mcpdiff_history as history
+import mcpdiff_utils as utils
+from history_builder import HistoryBuilder, run_mcpdiff
+
+
+class TestReattach(unittest.TestCase):
+    def setUp(self):
+        self.workspace = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        # The session crashed after two edits and went on as conv2
+        self.builder = HistoryBuilder(self.workspace)
+        self.builder.write("a.txt", "one\n")
+        self.builder.edit("a.txt", "two\n")
+        self.builder.conversation("conv2")
+        self.b_create = self.builder.write("b.txt", "b1\n")
+        self.b_edit = self.builder.edit("b.txt", "b2\n")
+
+    def tearDown(self):
+        shutil.rmtree(self.workspace, ignore_errors=True)
+
+    def mcpdiff(self, *args: str) -> subprocess.CompletedProcess:
+        return run_mcpdiff(self.workspace, *args, input="y\n")
+
+    def indexes(self, conv_id: str) -> dict:
+        return {
+            e["edit_id"]: e["tool_call_index"]
+            for e in self.builder.entries()
+            if e["conversation_id"] == conv_id
+        }
+
+    def test_reattach_all(self):
+        result = self.mcpdiff("reattach", "--from-conv", "conv2", "--to-conv", "conv1")
+        self.assertEqual(result.returncode, 0, result.stdout + result.stderr)
+        self.assertIn("Reattached 2 entries from conv2 to conv1", result.stdout)
+        self.assertEqual(self.indexes("conv2"), {})
+        indexes = self.indexes("conv1")
+        self.assertEqual((indexes[self.b_create], indexes[self.b_edit]), (2, 3))
+        self.assertEqual(utils.read_log_file(self.builder.log_file("conv2")), [])
+
+        self.assertIn("+b2", self.mcpdiff("show", self.b_edit).stdout)
+        result = self.mcpdiff("reject", "-c", "conv1")
+        self.assertEqual(result.returncode, 0, result.stdout + result.stderr)
+        # Rejected creates leave empty files
+        self.assertEqual(self.builder.read("a.txt"), "")
+        self.assertEqual(self.builder.read("b.txt"), "")
+
+        records = audit.read_audit_log(self.builder.history_root)
+        self.assertEqual(records[0]["action"], "reattach")
+        self.assertEqual(records[0]["to_conversation"], "conv1")
+
+    def test_reattach_one_edit(self):
+        result = self.mcpdiff(
+            "reattach", "--from-conv", "conv2", "--to-conv", "conv1", "-e", self.b_edit
+        )
+        self.assertEqual(result.returncode, 0, result.stdout + result.stderr)
+        self.assertEqual(self.indexes("conv2"), {self.b_create: 0})
+        self.assertEqual(self.indexes("conv1")[self.b_edit], 2)
+
+    def test_bad_references(self):
+        before = self.builder.entries()
+        for args, error in [
+            (("--from-conv", "conv1", "--to-conv", "conv1"), utils.HistoryError),
+            (("--from-conv", "conv2", "--to-conv", "conv9"), utils.HistoryError),
+            (("--from-conv", "conv", "--to-conv", "conv1"), utils.AmbiguousIDError),
+            (
+                ("--from-conv", "conv1", "--to-conv", "conv2", "-e", self.b_edit),
+                utils.HistoryError,
+            ),
+        ]:
+            result = self.mcpdiff("reattach", *args)
+            self.assertEqual(result.returncode, error.exit_code, args)
+        self.assertEqual(self.builder.entries(), before)
+
+    def reattach_failing_on(self, conv_id: str) -> None:
+        """Reattaches conv2 in-process with the commit of conv_id's log failing."""
+        failing = self.builder.log_file(conv_id)
+        commit = utils._commit_log
+
+        def commit_log(log_file_path, data, temp_path):
+            if log_file_path == failing:
+                raise OSError("disk full")
+            commit(log_file_path, data, temp_path)
+
+        with mock.patch.object(utils, "_commit_log", side_effect=commit_log):
+            with self.assertRaises(utils.HistoryError):
+                history.reattach_entries(self.builder.history_root, "conv2", "conv1")
+
+    def test_destination_write_fails(self):
+        before = self.builder.entries()
+        self.reattach_failing_on("conv1")
+        self.assertEqual(self.builder.entries(), before)
+
+    def test_source_write_fails(self):
+        self.reattach_failing_on("conv2")
+        # The edits are in both conversations until reattach runs again
+        self.assertEqual(len(self.indexes("conv1")), 4)
+        self.assertEqual(len(self.indexes("conv2")), 2)
+
+        result = self.mcpdiff("reattach", "--from-conv", "conv2", "--to-conv", "conv1")
+        self.assertEqual(result.returncode, 0, result.stdout + result.stderr)
+        self.assertEqual(self.indexes("conv2"), {})
+        indexes = self.indexes("conv1")
+        self.assertEqual(len(indexes), 4)
+        self.assertEqual((indexes[self.b_create], indexes[self.b_edit]), (2, 3))
+
+
+if __name__ == "__main__":
+    unittest.main()
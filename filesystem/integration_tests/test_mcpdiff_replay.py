@@ -0,0 +1,221 @@
+#!/usr/bin/env python3
+"""
+Tests for the line-level replay behind `mcpdiff blame`:
+- Attribution of added, changed and context lines when applying diffs
+- Appends, including to a file without a final newline
+- Replaying a logged history, with baseline lines from the original checkpoint
+"""
+
+import hashlib
+import json
+import shutil
+import sys
+import tempfile
+import unittest
+from pathlib import Path
+
+# The CLI modules import each other by bare name; history_builder is found also
+# when run_tests.py imports this module as part of the package
+sys.path.insert(0, str(Path(__file__).parent.parent / "cli"))
+sys.path.insert(0, str(Path(__file__).parent))
+
+import mcpdiff_history as history
+import mcpdiff_replay as replay
+import mcpdiff_utils as utils
+from mcpdiff_patch import PatchError
+from history_builder import make_diff
+
+
+def edit_ids(lines):
+    return [line.edit_id for line in lines]
+
+
+class TestApplyDiffWithBlame(unittest.TestCase):
+    """Hunk-level bookkeeping on synthetic diffs."""
+
+    E1 = {"edit_id": "e1"}
+    E2 = {"edit_id": "e2"}
+
+    def test_baseline_lines(self):
+        lines = replay.lines_from_content("a\nb\n")
+        self.assertEqual(edit_ids(lines), [replay.BASELINE, replay.BASELINE])
+
+    def test_changed_and_added_lines_take_the_edit(self):
+        before = "a\nb\nc\n"
+        after = "a\nB\nc\nd\n"
+        lines = replay.apply_diff_with_blame(
+            replay.lines_from_content(before), make_diff(before, after), self.E1
+        )
+        self.assertEqual(replay.render(lines), after)
+        self.assertEqual(
+            edit_ids(lines), [replay.BASELINE, "e1", replay.BASELINE, "e1"]
+        )
+
+    def test_later_edit_overrides_only_its_lines(self):
+        v0, v1, v2 = "a\nb\nc\n", "a\nB\nC\n", "a\nB\nC2\n"
+        lines = replay.lines_from_content(v0)
+        lines = replay.apply_diff_with_blame(lines, make_diff(v0, v1), self.E1)
+        lines = replay.apply_diff_with_blame(lines, make_diff(v1, v2), self.E2)
+        self.assertEqual(replay.render(lines), v2)
+        self.assertEqual(edit_ids(lines), [replay.BASELINE, "e1", "e2"])
+
+    def test_deleted_lines_disappear(self):
+        before, after = "a\nb\nc\n", "a\nc\n"
+        lines = replay.apply_diff_with_blame(
+            replay.lines_from_content(before), make_diff(before, after), self.E1
+        )
+        self.assertEqual(replay.render(lines), after)
+        self.assertEqual(edit_ids(lines), [replay.BASELINE, replay.BASELINE])
+
+    def test_hunk_offset_keeps_attribution(self):
+        """Lines inserted above a hunk shift it without losing origins."""
+        before, after = "a\nb\nc\n", "a\nB\nc\n"
+        lines = replay.lines_from_content("x\n", self.E2) + replay.lines_from_content(
+            before
+        )
+        lines = replay.apply_diff_with_blame(lines, make_diff(before, after), self.E1)
+        self.assertEqual(replay.render(lines), "x\n" + after)
+        self.assertEqual(
+            edit_ids(lines), ["e2", replay.BASELINE, "e1", replay.BASELINE]
+        )
+
+    def test_create_from_empty(self):
+        lines = replay.apply_diff_with_blame([], make_diff("", "x\ny"), self.E1)
+        self.assertEqual(replay.render(lines), "x\ny")
+        self.assertEqual(edit_ids(lines), ["e1", "e1"])
+
+    def test_mismatched_context_fails(self):
+        with self.assertRaises(PatchError):
+            replay.apply_diff_with_blame(
+                replay.lines_from_content("other\n"),
+                make_diff("a\nb\n", "a\nB\n"),
+                self.E1,
+            )
+
+    def test_input_lines_are_not_modified(self):
+        lines = replay.lines_from_content("a\nb")
+        diff = (
+            "--- a/file.txt\n+++ b/file.txt\n@@ -1,2 +1,3 @@\n a\n-b\n"
+            "\\ No newline at end of file\n+b\n+c\n"
+        )
+        result = replay.apply_diff_with_blame(lines, diff, self.E1)
+        self.assertEqual(replay.render(result), "a\nb\nc\n")
+        self.assertEqual(replay.render(lines), "a\nb")
+
+
+class TestAppendWithBlame(unittest.TestCase):
+    E1 = {"edit_id": "e1"}
+
+    def test_append_new_lines(self):
+        lines = replay.append_with_blame(
+            replay.lines_from_content("a\n"), "b\nc\n", self.E1
+        )
+        self.assertEqual(replay.render(lines), "a\nb\nc\n")
+        self.assertEqual(edit_ids(lines), [replay.BASELINE, "e1", "e1"])
+
+    def test_append_completes_unterminated_line(self):
+        lines = replay.append_with_blame(
+            replay.lines_from_content("a\nb"), "c\nd\n", self.E1
+        )
+        self.assertEqual(replay.render(lines), "a\nbc\nd\n")
+        self.assertEqual(edit_ids(lines), [replay.BASELINE, "e1", "e1"])
+
+
+class TestReplayFile(unittest.TestCase):
+    """Replaying a logged history from its original checkpoint."""
+
+    BASE = "1\n2\n3\n4\n5\n6\n7\n8\n"
+    # e1 changes line 2, e2 adds line 9 (far enough apart that their hunks do
+    # not share context), e3 appends line 10
+    VERSIONS = [
+        BASE,
+        BASE.replace("2\n", "two\n"),
+        BASE.replace("2\n", "two\n") + "9\n",
+        BASE.replace("2\n", "two\n") + "9\n10\n",
+    ]
+
+    def setUp(self):
+        self.workspace = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        self.history_root = self.workspace / ".mcp" / utils.HISTORY_DIR_NAME
+        for name in (utils.LOGS_DIR, utils.DIFFS_DIR, utils.CHECKPOINTS_DIR):
+            (self.history_root / name / "conv1").mkdir(parents=True)
+        checkpoint_rel = f"{utils.CHECKPOINTS_DIR}/conv1/f.txt.chkpt"
+        (self.history_root / checkpoint_rel).write_text(self.VERSIONS[0])
+
+        entries = []
+        for index in range(1, len(self.VERSIONS)):
+            before, after = self.VERSIONS[index - 1], self.VERSIONS[index]
+            edit_id = f"e{index}"
+            diff_rel = f"conv1/{edit_id}.diff"
+            entry = {
+                "edit_id": edit_id,
+                "conversation_id": "conv1",
+                "tool_call_index": index,
+                "timestamp": f"2025-01-01T00:00:0{index}Z",
+                "operation": "edit",
+                "file_path": "f.txt",
+                "tool_name": "edit_file_diff",
+                "status": "pending",
+                "diff_file": diff_rel,
+                "checkpoint_file": checkpoint_rel if index == 1 else None,
+                "hash_before": hashlib.sha256(before.encode()).hexdigest(),
+                "hash_after": hashlib.sha256(after.encode()).hexdigest(),
+            }
+            if index == 3:
+                # The last edit appends, storing only the appended bytes
+                entry.update(operation="append", tool_name="append_to_file")
+                (self.history_root / utils.DIFFS_DIR / diff_rel).write_text("10\n")
+            else:
+                (self.history_root / utils.DIFFS_DIR / diff_rel).write_text(
+                    make_diff(before, after)
+                )
+            entries.append(entry)
+        self.log_file = self.history_root / utils.LOGS_DIR / "conv1.log"
+        self.write_entries(entries)
+
+    def tearDown(self):
+        shutil.rmtree(self.workspace, ignore_errors=True)
+
+    def write_entries(self, entries):
+        self.log_file.write_text("".join(json.dumps(e) + "\n" for e in entries))
+
+    def test_replay_attributes_each_line(self):
+        """The checkpointed entry is replayed: its checkpoint is the state before it."""
+        entries = history.find_all_entries(self.history_root)
+        lines = replay.replay_file("f.txt", entries, self.history_root)
+        self.assertEqual(replay.render(lines), self.VERSIONS[-1])
+        self.assertEqual(
+            edit_ids(lines),
+            [replay.BASELINE, "e1"] + [replay.BASELINE] * 6 + ["e2", "e3"],
+        )
+        self.assertEqual(lines[1].origin["timestamp"], "2025-01-01T00:00:01Z")
+
+    def test_rejected_edit_is_skipped(self):
+        entries = history.find_all_entries(self.history_root)
+        for entry in entries:
+            entry.pop("log_file_source", None)
+            if entry["edit_id"] == "e1":
+                entry["status"] = "rejected"
+        self.write_entries(entries)
+        entries = history.find_all_entries(self.history_root)
+        # e2's hunk still finds its context; the replay warns about e2's hash
+        with self.assertLogs("mcpdiff", level="WARNING"):
+            lines = replay.replay_file("f.txt", entries, self.history_root)
+        self.assertEqual(replay.render(lines), self.BASE + "9\n10\n")
+        self.assertEqual(edit_ids(lines)[:2], [replay.BASELINE, replay.BASELINE])
+
+    def test_accepted_only(self):
+        entries = history.find_all_entries(self.history_root)
+        lines = replay.replay_file(
+            "f.txt", entries, self.history_root, apply_only_accepted=True
+        )
+        self.assertEqual(replay.render(lines), self.VERSIONS[0])
+
+    def test_unknown_file(self):
+        entries = history.find_all_entries(self.history_root)
+        with self.assertRaises(utils.HistoryError):
+            replay.replay_file("other.txt", entries, self.history_root)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,126 @@
+#!/usr/bin/env python3
+"""
+Tests for `mcpdiff schema`, the JSON Schema of log entries:
+- The schema covers the fields, required fields and enums mcpdiff reads
+- Entries written by a history accept under it
+- The command prints each type as JSON, also outside a workspace
+"""
+
+import json
+import re
+import shutil
+import sys
+import tempfile
+import unittest
+from pathlib import Path
+from typing import Any, Dict, List
+
+# The CLI modules import each other by bare name; history_builder is found also
+# when run_tests.py imports this module as part of the package
+sys.path.insert(0, str(Path(__file__).parent.parent / "cli"))
+sys.path.insert(0, str(Path(__file__).parent))
+
+import mcpdiff_api as api
+import mcpdiff_append as append
+import mcpdiff_schema as schema
+import mcpdiff_utils as utils
+from history_builder import HistoryBuilder, run_mcpdiff
+
+
+_PYTHON_TYPES = {
+    "string": str,
+    "integer": int,
+    "object": dict,
+    "array": list,
+    "boolean": bool,
+    "null": type(None),
+}
+
+
+def schema_errors(value: Dict[str, Any], entry_schema: Dict[str, Any]) -> List[str]:
+    """The draft-07 keywords the LogEntry schema uses, checked by hand."""
+    errors = [f"missing {n}" for n in entry_schema["required"] if n not in value]
+    for name, prop in entry_schema["properties"].items():
+        if name not in value:
+            continue
+        if "$ref" in prop:
+            prop = entry_schema["definitions"][prop["$ref"].rsplit("/", 1)[1]]
+        types = prop["type"] if isinstance(prop["type"], list) else [prop["type"]]
+        field = value[name]
+        # bool is an int in Python, not in JSON
+        if not any(
+            isinstance(field, _PYTHON_TYPES[t])
+            and not (t == "integer" and isinstance(field, bool))
+            for t in types
+        ):
+            errors.append(f"{name}: {field!r} is not {types}")
+        elif "enum" in prop and field not in prop["enum"]:
+            errors.append(f"{name}: {field!r} not in enum")
+        elif "pattern" in prop and not re.search(prop["pattern"], field):
+            errors.append(f"{name}: {field!r} does not match pattern")
+    return errors
+
+
+class TestSchema(unittest.TestCase):
+    def setUp(self):
+        self.workspace = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+
+    def tearDown(self):
+        shutil.rmtree(self.workspace, ignore_errors=True)
+
+    def test_log_entry_fields(self):
+        entry_schema = schema.get_schema("LogEntry")
+        self.assertEqual(entry_schema["$schema"], schema.DRAFT_07)
+        self.assertEqual(set(entry_schema["properties"]), set(append._FIELD_TYPES))
+        self.assertEqual(
+            set(entry_schema["required"]), set(api.LogEntry._REQUIRED_FIELDS)
+        )
+        for name, prop in entry_schema["properties"].items():
+            self.assertTrue(prop["description"], name)
+        self.assertEqual(
+            entry_schema["properties"]["source_path"]["type"], ["string", "null"]
+        )
+        self.assertEqual(
+            entry_schema["properties"]["status"]["$ref"], "#/definitions/Status"
+        )
+
+    def test_enums(self):
+        for type_name, enum in (("Status", api.Status), ("Operation", api.Operation)):
+            enum_schema = schema.get_schema(type_name)
+            self.assertEqual(enum_schema["enum"], [member.value for member in enum])
+            self.assertEqual(
+                len(enum_schema["enumDescriptions"]), len(enum_schema["enum"])
+            )
+        with self.assertRaises(ValueError):
+            schema.get_schema("Tag")
+
+    def test_written_entries_are_valid(self):
+        builder = HistoryBuilder(self.workspace)
+        create = builder.write("a.txt", "one\n")
+        builder.edit("a.txt", "two\n", parent_edit_id=create)
+        builder.move("a.txt", "b.txt")
+        builder.delete("b.txt")
+        builder.write("c.bin", b"\x00\xff")
+
+        entry_schema = schema.get_schema("LogEntry")
+        log_files = sorted((builder.history_root / utils.LOGS_DIR).glob("*.log"))
+        entries = [e for p in log_files for e in utils.read_log_file(p, migrate=False)]
+        self.assertEqual(len(entries), 5)
+        for entry in entries:
+            self.assertEqual(schema_errors(entry, entry_schema), [], entry)
+
+        broken = dict(entries[0], status="maybe", tool_call_index="1")
+        del broken["edit_id"]
+        self.assertEqual(len(schema_errors(broken, entry_schema)), 3)
+
+    def test_command(self):
+        for type_name in schema.SCHEMA_TYPES:
+            # No workspace is needed
+            result = run_mcpdiff(
+                None, "schema", "--type", type_name, cwd=self.workspace, check=True
+            )
+            self.assertEqual(json.loads(result.stdout), schema.get_schema(type_name))
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,121 @@
+#!/usr/bin/env python3
+"""
+Tests for `mcpdiff show --language LANG` and `show --list-languages`:
+- --language highlights a diff as LANG whatever the file's extension, and
+  leaves the log entry as it was
+- Unknown languages are refused, naming --list-languages
+- --list-languages lists the Pygments languages and aliases, also outside a
+  workspace, and fails without Pygments
+- Printing without color leaves color on for later output in the process
+"""
+
+import contextlib
+import importlib.util
+import io
+import shutil
+import subprocess
+import sys
+import tempfile
+import unittest
+from pathlib import Path
+
+# The CLI modules import each other by bare name; history_builder is found also
+# when run_tests.py imports this module as part of the package
+sys.path.insert(0, str(Path(__file__).parent.parent / "cli"))
+sys.path.insert(0, str(Path(__file__).parent))
+
+import mcpdiff_history as history
+import mcpdiff_utils as utils
+from history_builder import HistoryBuilder, run_mcpdiff
+
+
+HAS_PYGMENTS = importlib.util.find_spec("pygments") is not None
+
+
+class TestShowLanguage(unittest.TestCase):
+    def setUp(self):
+        self.workspace = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        self.builder = HistoryBuilder(self.workspace)
+        self.edit_id = self.builder.write("page.mako", "def f():\n    return 1\n")
+
+    def tearDown(self):
+        shutil.rmtree(self.workspace, ignore_errors=True)
+
+    def mcpdiff(
+        self, *args: str, workspace: bool = True
+    ) -> subprocess.CompletedProcess:
+        prefix = ["-w", str(self.workspace)] if workspace else []
+        return run_mcpdiff(None, *prefix, *args, cwd=tempfile.gettempdir())
+
+    def test_display_only(self):
+        entry = dict(self.builder.entry(self.edit_id))
+        result = self.mcpdiff("show", self.edit_id, "--language", "python")
+        self.assertEqual(result.returncode, 0, result.stderr)
+        self.assertIn("+    return 1", result.stdout)
+        self.assertEqual(self.builder.entry(self.edit_id), entry)
+
+    def test_needs_identifier(self):
+        result = self.mcpdiff("show")
+        self.assertNotEqual(result.returncode, 0)
+        self.assertIn("needs an edit or conversation ID", result.stderr)
+
+    @unittest.skipUnless(HAS_PYGMENTS, "needs Pygments")
+    def test_unknown_language(self):
+        result = self.mcpdiff("show", self.edit_id, "--language", "no-such-lang")
+        self.assertNotEqual(result.returncode, 0)
+        self.assertIn("Unknown language 'no-such-lang'", result.stderr)
+        self.assertIn("--list-languages", result.stderr)
+
+    @unittest.skipUnless(HAS_PYGMENTS, "needs Pygments")
+    def test_list_languages(self):
+        result = self.mcpdiff("show", "--list-languages", workspace=False)
+        self.assertEqual(result.returncode, 0, result.stderr)
+        lines = result.stdout.splitlines()
+        python = next(line for line in lines if line.startswith("Python: "))
+        self.assertIn("python", python.split(": ", 1)[1].split(", "))
+        self.assertEqual(
+            [name for name, _ in utils.list_syntax_languages()],
+            [line.split(": ", 1)[0] for line in lines],
+        )
+
+    @unittest.skipIf(HAS_PYGMENTS, "Pygments is installed")
+    def test_list_languages_without_pygments(self):
+        self.assertIsNone(utils.list_syntax_languages())
+        result = self.mcpdiff("show", "--list-languages", workspace=False)
+        self.assertNotEqual(result.returncode, 0)
+        self.assertIn("Pygments is not installed", result.stderr)
+
+
+@unittest.skipUnless(HAS_PYGMENTS, "needs Pygments")
+class TestSyntaxHighlighter(unittest.TestCase):
+    def test_language_overrides_extension(self):
+        self.assertIsNone(utils.get_syntax_highlighter("page.unknown-extension"))
+        highlight = utils.get_syntax_highlighter("page.unknown-extension", None, "py")
+        self.assertIsNotNone(highlight)
+        self.assertIn("\x1b[", highlight("return 1"))
+        self.assertIsNone(utils.get_syntax_highlighter("a.py", None, "no-such-lang"))
+        utils.check_syntax_language("Python")  # Aliases match in any case
+        with self.assertRaises(utils.HistoryError):
+            utils.check_syntax_language("no-such-lang")
+
+
+class TestColor(unittest.TestCase):
+    def test_color_is_per_call(self):
+        def print_diff(**kwargs) -> str:
+            out = io.StringIO()
+            with contextlib.redirect_stdout(out):
+                utils.print_diff_with_color("@@ -1 +1 @@\n-a\n+b\n", "a.py", **kwargs)
+            return out.getvalue()
+
+        self.assertEqual(print_diff(color=False), "@@ -1 +1 @@\n-a\n+b\n")
+        self.assertIsNone(utils.get_syntax_highlighter("a.py", color=False))
+        self.assertIn(f"{utils.COLOR_CYAN}@@ -1 +1 @@", print_diff())
+
+    def test_entry_summary(self):
+        entry = {"edit_id": "e1", "operation": "edit", "status": "pending"}
+        self.assertNotIn("\x1b[", history.format_entry_summary(entry, color=False))
+        self.assertIn(utils.COLOR_YELLOW, history.format_entry_summary(entry))
+
+
+if __name__ == "__main__":
+    unittest.main()
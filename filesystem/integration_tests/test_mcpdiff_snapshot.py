@@ -0,0 +1,147 @@
+#!/usr/bin/env python3
+"""
+Tests for `mcpdiff snapshot` and `mcpdiff restore-snapshot`:
+- A snapshot checkpoints every tracked file, records absent ones, leaves out
+  paths in .mcp/ignore and is no conversation
+- Restoring writes the files back, deletes those created since, and is audited
+- A restore that fails part way, or finds a checkpoint missing, changes nothing
+"""
+
+import json
+import os
+import shutil
+import sys
+import tempfile
+import unittest
+from datetime import datetime, timezone
+from pathlib import Path
+from unittest import mock
+
+# The CLI modules import each other by bare name; history_builder is found also
+# when run_tests.py imports this module as part of the package
+sys.path.insert(0, str(Path(__file__).parent.parent / "cli"))
+sys.path.insert(0, str(Path(__file__).parent))
+
+import mcpdiff_audit as audit
+import mcpdiff_snapshot as snapshots
+import mcpdiff_utils as utils
+from history_builder import HistoryBuilder, run_mcpdiff
+
+
+class TestSnapshot(unittest.TestCase):
+    def setUp(self):
+        self.workspace = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        # Entries logged after a snapshot must be timestamped after it; the
+        # builder's clock ticks a second per entry, ahead of the real one
+        self.builder = HistoryBuilder(self.workspace, start=datetime.now(timezone.utc))
+        self.history_root = self.builder.history_root
+        self.builder.write("a.txt", "one\n")
+        self.builder.write("src/b.txt", "bee\n")
+        self.builder.write("gone.txt", "gone\n")
+        self.builder.delete("gone.txt")
+
+    def tearDown(self):
+        shutil.rmtree(self.workspace, ignore_errors=True)
+
+    def mcpdiff(self, *args: str, stdin: str = "y\n") -> str:
+        return run_mcpdiff(self.workspace, *args, input=stdin, check=True).stdout
+
+    def snapshot(self) -> dict:
+        return json.loads(self.mcpdiff("snapshot", "-m", "before", "--json"))
+
+    def test_snapshot(self):
+        (self.workspace / ".mcp" / "ignore").write_text("src/\n")
+        entry = self.snapshot()
+        self.assertEqual(
+            (entry["operation"], entry["message"], entry["conversation_id"]),
+            ("snapshot", "before", "snapshots"),
+        )
+        files = {f["file_path"]: f for f in entry["files"]}
+        self.assertEqual(sorted(files), ["a.txt", "gone.txt"])
+        self.assertIsNone(files["gone.txt"]["checkpoint_file"])
+        checkpoint = self.history_root / files["a.txt"]["checkpoint_file"]
+        self.assertEqual(checkpoint.read_text(), "one\n")
+        self.assertEqual(files["a.txt"]["hash"], utils.calculate_hash(str(checkpoint)))
+
+        [listed] = snapshots.read_snapshots(self.history_root)
+        self.assertEqual(listed["edit_id"], entry["edit_id"])
+        self.assertIn("before", self.mcpdiff("snapshot", "--list"))
+        # Neither a conversation nor an edit
+        conversations = json.loads(self.mcpdiff("conversations", "--json"))
+        self.assertEqual([c["conversation_id"] for c in conversations], ["conv1"])
+        self.assertNotIn(entry["edit_id"][:8], self.mcpdiff("status"))
+
+    def test_restore(self):
+        snapshot_id = self.snapshot()["edit_id"]
+        self.builder.edit("a.txt", "two\n")
+        self.builder.delete("src/b.txt")
+        self.builder.write("gone.txt", "back\n")
+        self.builder.write("new.txt", "new\n")
+        output = self.mcpdiff("restore-snapshot", "-s", snapshot_id[:8])
+        self.assertIn("2 written, 2 deleted", output)
+        self.assertEqual(self.builder.read("a.txt"), "one\n")
+        self.assertEqual(self.builder.read("src/b.txt"), "bee\n")
+        self.assertIsNone(self.builder.read("gone.txt"))
+        self.assertIsNone(self.builder.read("new.txt"))
+        record = audit.read_audit_log(self.history_root)[-1]
+        self.assertEqual(
+            (record["action"], record["snapshot_id"]),
+            ("restore-snapshot", snapshot_id),
+        )
+        self.assertEqual(record["deleted"], ["gone.txt", "new.txt"])
+        output = self.mcpdiff("restore-snapshot", "-s", snapshot_id)
+        self.assertIn("already match", output)
+
+    def test_needs_confirmation(self):
+        snapshot_id = self.snapshot()["edit_id"]
+        self.builder.edit("a.txt", "two\n")
+        output = self.mcpdiff("restore-snapshot", "-s", snapshot_id, stdin="n\n")
+        self.assertIn("Aborted.", output)
+        self.assertEqual(self.builder.read("a.txt"), "two\n")
+
+    def test_failed_restore_changes_nothing(self):
+        snapshot_id = self.snapshot()["edit_id"]
+        self.builder.edit("a.txt", "two\n")
+        self.builder.edit("src/b.txt", "bee 2\n")
+        real_replace = os.replace
+        failed = []
+
+        # Fails renaming the staged src/b.txt in, but not putting it back
+        def replace(src, dst):
+            if Path(dst) == self.workspace / "src" / "b.txt" and not failed:
+                failed.append(dst)
+                raise OSError("disk full")
+            return real_replace(src, dst)
+
+        with mock.patch.object(snapshots.os, "replace", side_effect=replace):
+            with self.assertRaises(utils.HistoryError):
+                snapshots.restore_snapshot(
+                    self.workspace,
+                    self.history_root,
+                    snapshot_id,
+                    self.builder.entries(),
+                )
+        self.assertEqual(self.builder.read("a.txt"), "two\n")
+        self.assertEqual(self.builder.read("src/b.txt"), "bee 2\n")
+        self.assertEqual(
+            sorted(p.name for p in (self.workspace / "src").iterdir()), ["b.txt"]
+        )
+
+    def test_missing_checkpoint(self):
+        entry = self.snapshot()
+        self.builder.edit("a.txt", "two\n")
+        self.builder.edit("src/b.txt", "bee 2\n")
+        checkpoint = next(f for f in entry["files"] if f["file_path"] == "src/b.txt")
+        (self.history_root / checkpoint["checkpoint_file"]).unlink()
+        with self.assertRaises(utils.MissingCheckpointError):
+            snapshots.restore_snapshot(
+                self.workspace,
+                self.history_root,
+                entry["edit_id"],
+                self.builder.entries(),
+            )
+        self.assertEqual(self.builder.read("a.txt"), "two\n")
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,215 @@
+#!/usr/bin/env python3
+"""
+Tests for `mcpdiff split`:
+- A Rust edit becomes one child edit per top-level item its hunks change, plus
+  one for the hunks outside any item, logged after it in order
+- The children's diffs chain from the edit's hash_before to its hash_after
+- Re-applies use the children instead of the edit, so they can be accepted and
+  rejected one by one; rejecting the edit rejects them all
+- Edits that are not pending, not Rust, or change a single item are refused
+"""
+
+import shutil
+import sys
+import tempfile
+import unittest
+from pathlib import Path
+
+# The CLI modules import each other by bare name; history_builder is found also
+# when run_tests.py imports this module as part of the package
+sys.path.insert(0, str(Path(__file__).parent.parent / "cli"))
+sys.path.insert(0, str(Path(__file__).parent))
+
+import mcpdiff_audit as audit
+import mcpdiff_history as history
+import mcpdiff_split as split
+from history_builder import HistoryBuilder, run_mcpdiff
+from mcpdiff_utils import HistoryError
+
+
+SOURCE = """// Version 1
+//
+// Values used by
+// the tests, and
+// nothing else.
+//
+
+const LIMIT: u32 = 10;
+
+fn alpha() -> u32 {
+    let value = 1;
+    value + LIMIT
+}
+
+fn untouched() -> u32 {
+    let a = 2;
+    let b = 3;
+    a * b
+}
+
+fn beta() -> u32 {
+    let value = 4;
+    value
+}
+
+struct Point {
+    x: i32,
+}
+
+impl Point {
+    fn get(&self) -> i32 {
+        self.x
+    }
+}
+"""
+
+# Changes the leading comment, alpha, beta and a method of Point, in hunks of
+# their own
+EDITED = (
+    SOURCE.replace("Version 1", "Version 2")
+    .replace("value = 1;", "value = 10;")
+    .replace("value = 4;", "value = 40;")
+    .replace("self.x\n", "self.x * 2\n")
+)
+
+
+class TestSplit(unittest.TestCase):
+    def setUp(self):
+        self.workspace = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        self.builder = HistoryBuilder(self.workspace)
+        self.builder.write("src/lib.rs", SOURCE)
+        self.edit_id = self.builder.edit("src/lib.rs", EDITED)
+
+    def tearDown(self):
+        shutil.rmtree(self.workspace, ignore_errors=True)
+
+    def split(self) -> list:
+        result = run_mcpdiff(self.workspace, "split", "-e", self.edit_id[:8])
+        self.assertEqual(result.returncode, 0, result.stdout + result.stderr)
+        return self.builder.entry(self.edit_id)["split_into"]
+
+    def test_children(self):
+        child_ids = self.split()
+        self.assertEqual(len(child_ids), 4)
+        parent = self.builder.entry(self.edit_id)
+        entries = self.builder.entries()
+        # Logged right after the edit, in order
+        position = [e["edit_id"] for e in entries].index(self.edit_id)
+        self.assertEqual([e["edit_id"] for e in entries[position + 1 :]], child_ids)
+        children = [self.builder.entry(child_id) for child_id in child_ids]
+        self.assertEqual(children[0]["hash_before"], parent["hash_before"])
+        self.assertEqual(children[-1]["hash_after"], parent["hash_after"])
+        for before, after in zip(children, children[1:]):
+            self.assertEqual(after["hash_before"], before["hash_after"])
+        self.assertEqual(children[0]["checkpoint_file"], parent["checkpoint_file"])
+        for child in children:
+            self.assertEqual(child["parent_edit_id"], self.edit_id)
+            self.assertEqual(child["status"], "pending")
+            self.assertEqual(child["timestamp"], parent["timestamp"])
+            self.assertEqual(child["tool_call_index"], parent["tool_call_index"])
+            self.assertEqual(child["diff_file"], f"diffs/conv1/{child['edit_id']}.diff")
+        self.assertTrue(all(c["checkpoint_file"] is None for c in children[1:]))
+
+        # Each child's diff changes its item only
+        alpha_diff = (self.builder.history_root / children[1]["diff_file"]).read_text()
+        self.assertIn("+    let value = 10;", alpha_diff)
+        self.assertNotIn("40", alpha_diff)
+
+        # The edit's place in the plan is taken by its children
+        plan = history.plan_file_replay(
+            "src/lib.rs", entries, self.builder.history_root
+        )
+        self.assertEqual(
+            [step.action for step in plan.steps],
+            ["apply", "split", "apply", "apply", "apply", "apply"],
+        )
+        self.assertEqual(plan.verified_step().edit_id, child_ids[-1])
+
+    def test_output(self):
+        result = run_mcpdiff(self.workspace, "split", "-e", self.edit_id)
+        self.assertEqual(result.returncode, 0, result.stderr)
+        self.assertIn(f"Split edit {self.edit_id} into 4 edits:", result.stdout)
+        labels = [line.split("  ")[-1] for line in result.stdout.splitlines()[1:]]
+        self.assertEqual(labels, ["(other)", "fn alpha", "fn beta", "impl Point"])
+        [record] = [
+            r
+            for r in audit.read_audit_log(self.builder.history_root)
+            if r["action"] == "split"
+        ]
+        self.assertEqual(record["edit_ids"], [self.edit_id])
+        self.assertEqual(
+            record["children"], self.builder.entry(self.edit_id)["split_into"]
+        )
+
+    def test_reject_one_child(self):
+        # A later edit of the same file has to apply on top of the children
+        later = EDITED.replace("let b = 3;", "let b = 30;")
+        self.builder.edit("src/lib.rs", later)
+        other, alpha, beta, point = self.split()
+
+        result = run_mcpdiff(self.workspace, "reject", "-e", alpha)
+        self.assertEqual(result.returncode, 0, result.stdout + result.stderr)
+        self.assertEqual(
+            self.builder.read("src/lib.rs"),
+            later.replace("value = 10;", "value = 1;"),
+        )
+        result = run_mcpdiff(self.workspace, "accept", "-e", beta)
+        self.assertEqual(result.returncode, 0, result.stdout + result.stderr)
+        self.assertEqual(self.builder.entry(beta)["status"], "accepted")
+        self.assertEqual(self.builder.entry(other)["status"], "pending")
+
+        # Rejecting the edit itself rejects what is left of it
+        result = run_mcpdiff(self.workspace, "reject", "-e", self.edit_id)
+        self.assertEqual(result.returncode, 0, result.stdout + result.stderr)
+        for child_id in (other, alpha, beta, point):
+            self.assertEqual(self.builder.entry(child_id)["status"], "rejected")
+        self.assertEqual(
+            self.builder.read("src/lib.rs"),
+            SOURCE.replace("let b = 3;", "let b = 30;"),
+        )
+
+    def test_group_hunks(self):
+        entry = self.builder.entry(self.edit_id)
+        planned = split.plan_split(
+            entry, self.builder.entries(), self.builder.history_root
+        )
+        self.assertEqual(
+            [child["label"] for child in planned],
+            [split.OTHER_HUNKS, "fn alpha", "fn beta", "impl Point"],
+        )
+        # Nothing is written before split_entry()
+        self.assertNotIn("split_into", self.builder.entry(self.edit_id))
+
+    def test_refused(self):
+        # A single item
+        one_item = self.builder.edit(
+            "src/lib.rs", EDITED.replace("value + LIMIT", "value - LIMIT")
+        )
+        # Not Rust
+        self.builder.write("notes.txt", "one\n")
+        text_edit = self.builder.edit("notes.txt", "two\n")
+        cases = {
+            one_item: "only changes fn alpha; nothing to split",
+            text_edit: "items are only known in .rs files",
+        }
+        for edit_id, message in cases.items():
+            result = run_mcpdiff(self.workspace, "split", "-e", edit_id)
+            self.assertEqual(result.returncode, HistoryError.exit_code)
+            self.assertIn(message, result.stderr)
+
+        self.split()
+        result = run_mcpdiff(self.workspace, "split", "-e", self.edit_id)
+        self.assertEqual(result.returncode, HistoryError.exit_code)
+        self.assertIn("is already split", result.stderr)
+
+        run_mcpdiff(self.workspace, "accept", "-e", one_item, check=True)
+        with self.assertRaisesRegex(HistoryError, "Only pending edits"):
+            split.plan_split(
+                self.builder.entry(one_item),
+                self.builder.entries(),
+                self.builder.history_root,
+            )
+
+
+if __name__ == "__main__":
+    unittest.main()
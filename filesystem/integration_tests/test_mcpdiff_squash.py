@@ -0,0 +1,187 @@
+#!/usr/bin/env python3
+"""
+Tests for `mcpdiff squash-rejected`:
+- Rejected edits leave the log, and their diffs and checkpoints the disk
+- A removed edit's checkpoint moves to the conversation's next edit of the
+  file, which can still be rejected afterwards, also in a later shard and
+  when writing the shard the edit is removed from fails
+- Edits named as parent_edit_id, or that a later checkpoint may contain, are
+  kept with a warning
+- Nothing changes without --confirm
+"""
+
+import shutil
+import sys
+import tempfile
+import unittest
+from pathlib import Path
+from unittest import mock
+
+# The CLI modules import each other by bare name; history_builder is found also
+# when run_tests.py imports this module as part of the package
+sys.path.insert(0, str(Path(__file__).parent.parent / "cli"))
+sys.path.insert(0, str(Path(__file__).parent))
+
+import mcpdiff_audit as audit
+import mcpdiff_history as history
+import mcpdiff_utils as utils
+from history_builder import HistoryBuilder, run_mcpdiff
+
+
+# Far enough apart that edits of the first and last lines apply independently
+ORIGINAL = "".join(f"line {i}\n" for i in range(1, 21))
+
+
+class TestSquashRejected(unittest.TestCase):
+    def setUp(self):
+        self.workspace = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        self.builder = HistoryBuilder(self.workspace)
+        self.builder.path("a.txt").write_text(ORIGINAL)
+        self.first = self.builder.edit("a.txt", ORIGINAL.replace("line 1\n", "first\n"))
+        self.second = self.builder.edit(
+            "a.txt", self.builder.read("a.txt").replace("line 20\n", "second\n")
+        )
+
+    def tearDown(self):
+        shutil.rmtree(self.workspace, ignore_errors=True)
+
+    def mcpdiff(self, *args: str) -> str:
+        return run_mcpdiff(self.workspace, *args, input="y\n", check=True).stdout
+
+    def edit_ids(self) -> list:
+        return [
+            e["edit_id"] for e in self.builder.entries() if e["tool_name"] != "mcpdiff"
+        ]
+
+    def test_removes_rejected_edits(self):
+        self.mcpdiff("reject", "-e", self.second)
+        diff_file = self.builder.history_root / self.builder.entry(self.second)[
+            "diff_file"
+        ]
+        self.assertTrue(diff_file.is_file())
+        output = self.mcpdiff("squash-rejected", "-c", "conv1", "--confirm")
+        self.assertIn("Removed 1 rejected edit(s) from conversation conv1", output)
+        self.assertIn("freed", output)
+        self.assertEqual(self.edit_ids(), [self.first])
+        self.assertFalse(diff_file.exists())
+        self.assertEqual(
+            self.builder.read("a.txt"), ORIGINAL.replace("line 1\n", "first\n")
+        )
+        records = audit.read_audit_log(self.builder.history_root)
+        self.assertEqual(
+            (records[-1]["action"], records[-1]["edit_ids"]), ("squash", [self.second])
+        )
+
+        # Once the edit holding it goes, so does the checkpoint
+        self.mcpdiff("reject", "-e", self.first)
+        checkpoint = self.builder.history_root / self.builder.entry(self.first)[
+            "checkpoint_file"
+        ]
+        self.mcpdiff("squash-rejected", "-c", "conv1", "--confirm")
+        self.assertEqual(self.edit_ids(), [])
+        self.assertFalse(checkpoint.exists())
+        self.assertEqual(self.builder.read("a.txt"), ORIGINAL)
+        self.mcpdiff("verify")
+
+    def test_moves_checkpoint(self):
+        self.mcpdiff("reject", "-e", self.first)
+        checkpoint_file = self.builder.entry(self.first)["checkpoint_file"]
+        self.mcpdiff("squash-rejected", "-c", "conv1", "--confirm")
+        self.assertEqual(self.edit_ids(), [self.second])
+        self.assertEqual(
+            self.builder.entry(self.second)["checkpoint_file"], checkpoint_file
+        )
+        self.assertTrue((self.builder.history_root / checkpoint_file).is_file())
+
+        # The next edit re-applies from it
+        self.mcpdiff("reject", "-e", self.second)
+        self.assertEqual(self.builder.read("a.txt"), ORIGINAL)
+
+    def test_moves_checkpoint_to_later_shard(self):
+        history.update_entry_status(
+            self.builder.entry(self.first), "rejected", self.builder.history_root
+        )
+        checkpoint_file = self.builder.entry(self.first)["checkpoint_file"]
+        log_file = self.builder.log_file()
+        shard = log_file.with_name("conv1_20250102.log")
+        first_line, second_line = log_file.read_text().splitlines(keepends=True)
+        log_file.write_text(first_line)
+        shard.write_text(second_line)
+
+        # The shard that gains the checkpoint is written first
+        commit = utils._commit_log
+
+        def commit_log(log_file_path, data, temp_path):
+            if log_file_path == log_file:
+                raise OSError("disk full")
+            commit(log_file_path, data, temp_path)
+
+        all_entries = self.builder.entries()
+        plan = history.plan_rejected_squash(
+            self.builder.history_root, all_entries, "conv1"
+        )
+        with mock.patch.object(utils, "_commit_log", side_effect=commit_log):
+            with self.assertRaises(utils.HistoryError):
+                history.squash_rejected(self.builder.history_root, all_entries, plan)
+        self.assertEqual(self.edit_ids(), [self.first, self.second])
+        self.assertEqual(
+            self.builder.entry(self.second)["checkpoint_file"], checkpoint_file
+        )
+
+        # Running it again finishes the squash
+        self.mcpdiff("squash-rejected", "-c", "conv1", "--confirm")
+        self.assertEqual(self.edit_ids(), [self.second])
+        self.assertEqual(log_file.read_text(), "")
+        self.assertTrue((self.builder.history_root / checkpoint_file).is_file())
+
+    def test_keeps_parents(self):
+        child = self.builder.edit(
+            "a.txt",
+            self.builder.read("a.txt").replace("line 10\n", "child\n"),
+            parent_edit_id=self.second,
+        )
+        for edit_id in (self.first, self.second):
+            history.update_entry_status(
+                self.builder.entry(edit_id), "rejected", self.builder.history_root
+            )
+        checkpoint_file = self.builder.entry(self.first)["checkpoint_file"]
+        output = self.mcpdiff("squash-rejected", "-c", "conv1", "--confirm")
+        self.assertIn(
+            f"keeping edit {self.second[:8]} (a.txt): a non-rejected edit names it as its parent",
+            output,
+        )
+        self.assertEqual(self.edit_ids(), [self.second, child])
+        # A rejected edit can hold the checkpoint, as it does not change it
+        self.assertEqual(
+            self.builder.entry(self.second)["checkpoint_file"], checkpoint_file
+        )
+
+    def test_keeps_edits_later_checkpoints_may_contain(self):
+        history.update_entry_status(
+            self.builder.entry(self.second), "rejected", self.builder.history_root
+        )
+        self.builder.conversation("conv2")
+        self.builder.edit("a.txt", ORIGINAL)
+        output = self.mcpdiff("squash-rejected", "-c", "conv1", "--confirm")
+        self.assertIn("a later checkpoint of a.txt may contain its change", output)
+        self.assertIn(self.second, self.edit_ids())
+
+    def test_needs_confirm(self):
+        self.mcpdiff("reject", "-e", self.second)
+        plan = history.plan_rejected_squash(
+            self.builder.history_root, self.builder.entries(), "conv1"
+        )
+        [item] = plan["remove"]
+        self.assertEqual(item["entry"]["edit_id"], self.second)
+        self.assertEqual(
+            item["bytes"],
+            sum((self.builder.history_root / f).stat().st_size for f in item["files"]),
+        )
+        output = self.mcpdiff("squash-rejected", "-c", "conv1")
+        self.assertIn("Would remove 1 rejected edit(s)", output)
+        self.assertIn("--confirm", output)
+        self.assertIn(self.second, self.edit_ids())
+
+
+if __name__ == "__main__":
+    unittest.main()
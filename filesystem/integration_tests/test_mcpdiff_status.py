@@ -0,0 +1,271 @@
+#!/usr/bin/env python3
+"""
+Tests for the entry filters behind `mcpdiff status`:
+- --since-edit-id/--until-edit-id ranges within a conversation
+- --session, which also covers `mcpdiff conversations`
+- --before/--after time ranges, which accept and reject select by too
+- --count and --group-by, and the order of -n 0
+- Conversation tags labelling the listing, passed in rather than kept globally
+"""
+
+import json
+import shutil
+import sys
+import tempfile
+import unittest
+from pathlib import Path
+
+# The CLI modules import each other by bare name; history_builder is found
+# also when run_tests.py imports this module as part of the package
+sys.path.insert(0, str(Path(__file__).parent.parent / "cli"))
+sys.path.insert(0, str(Path(__file__).parent))
+
+from history_builder import HistoryBuilder, run_mcpdiff
+import mcpdiff_history as history
+import mcpdiff_migration as migration
+import mcpdiff_utils as utils
+from mcpdiff_utils import HistoryError
+
+
+class TestEditRangeFilter(unittest.TestCase):
+    def setUp(self):
+        self.workspace = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        builder = HistoryBuilder(self.workspace)
+        self.ids = [builder.write("a.txt", "0\n")]
+        for i in range(1, 5):
+            self.ids.append(builder.edit("a.txt", f"{i}\n"))
+        # Interleaved edits of another conversation
+        self.other = builder.conversation("conv2").write("b.txt", "b\n")
+        builder.conversation("conv1")
+        self.ids.append(builder.write("a.txt", "5\n"))
+        self.entries = builder.entries()
+
+    def tearDown(self):
+        shutil.rmtree(self.workspace, ignore_errors=True)
+
+    def edit_ids(self, **filters) -> list:
+        entries = history.filter_entries(self.entries, limit=None, **filters)
+        return [e["edit_id"] for e in entries]
+
+    def test_range(self):
+        self.assertEqual(
+            self.edit_ids(
+                conv_id="conv1", since_edit_id=self.ids[1], until_edit_id=self.ids[4]
+            ),
+            self.ids[2:4],
+        )
+        self.assertEqual(
+            self.edit_ids(conv_id="conv1", since_edit_id=self.ids[3][:8]),
+            self.ids[4:],
+        )
+        self.assertEqual(
+            self.edit_ids(conv_id="conv", until_edit_id=self.ids[2]), self.ids[:2]
+        )
+
+    def test_snapshots_are_left_out(self):
+        snapshot = dict(
+            self.entries[0], edit_id="snap", tool_call_index=-1, tool_name="mcpdiff"
+        )
+        entries = self.entries + [snapshot]
+        filtered = history.filter_entries(
+            entries, conv_id="conv1", until_edit_id=self.ids[1], limit=None
+        )
+        self.assertEqual([e["edit_id"] for e in filtered], self.ids[:1])
+
+    def test_requires_conversation(self):
+        with self.assertRaises(HistoryError):
+            self.edit_ids(since_edit_id=self.ids[1])
+
+    def test_edit_not_in_conversation(self):
+        with self.assertRaisesRegex(HistoryError, "no edit"):
+            self.edit_ids(conv_id="conv1", since_edit_id=self.other)
+        with self.assertRaisesRegex(HistoryError, "no edit"):
+            self.edit_ids(conv_id="conv1", until_edit_id="ffffffff")
+
+
+class TestSessionFilter(unittest.TestCase):
+    def setUp(self):
+        self.workspace = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        builder = HistoryBuilder(self.workspace)
+        builder.session("tty1").write("a.txt", "a\n")
+        builder.conversation("conv2").session("tty2").write("b.txt", "b\n")
+        builder.conversation("conv3").session(None).write("c.txt", "c\n")
+        self.entries = builder.entries()
+
+    def tearDown(self):
+        shutil.rmtree(self.workspace, ignore_errors=True)
+
+    def mcpdiff(self, *args: str) -> list:
+        return json.loads(run_mcpdiff(self.workspace, *args, check=True).stdout)
+
+    def test_filter_entries(self):
+        filtered = history.filter_entries(self.entries, session_id="tty2", limit=None)
+        self.assertEqual([e["file_path"] for e in filtered], ["b.txt"])
+        self.assertEqual(
+            history.filter_entries(self.entries, session_id="tty", limit=None), []
+        )
+
+    def test_status_and_conversations(self):
+        entries = self.mcpdiff("status", "--json", "--session", "tty1")
+        self.assertEqual([e["conversation_id"] for e in entries], ["conv1"])
+        conversations = self.mcpdiff("conversations", "--json", "--session", "tty2")
+        self.assertEqual([c["conversation_id"] for c in conversations], ["conv2"])
+
+    def test_older_entries(self):
+        entry = migration.default_migrator.upgrade(
+            {"edit_id": "e1", "schema_version": 3}
+        )
+        self.assertIsNone(entry["session_id"])
+        self.assertEqual(entry["schema_version"], migration.CURRENT_SCHEMA_VERSION)
+
+
+class TestTimeRangeFilter(unittest.TestCase):
+    def setUp(self):
+        self.workspace = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        # Logged at 00:00:00 to 00:00:04 on 2025-01-01, UTC
+        builder = HistoryBuilder(self.workspace)
+        self.ids = [builder.write("a.txt", "0\n")]
+        for i in range(1, 5):
+            self.ids.append(builder.edit("a.txt", f"{i}\n"))
+        self.entries = builder.entries()
+
+    def tearDown(self):
+        shutil.rmtree(self.workspace, ignore_errors=True)
+
+    def test_parse_time_point(self):
+        self.assertEqual(utils.parse_time_point("2025-01-01"), 1735689600)
+        self.assertEqual(utils.parse_time_point("2025-01-01T01:00Z"), 1735693200)
+        self.assertEqual(utils.parse_time_point("2025-01-01T02:00+01:00"), 1735693200)
+        self.assertEqual(utils.parse_time_point("1h30m", now=10000), 4600)
+        for invalid in ("lunch", "", "2h and then some", "2025-13-01"):
+            self.assertIsNone(utils.parse_time_point(invalid), invalid)
+
+    def test_filter_entries(self):
+        def edit_ids(**filters):
+            entries = history.filter_entries(self.entries, limit=None, **filters)
+            return [e["edit_id"] for e in entries]
+
+        at = utils.parse_time_point
+        self.assertEqual(edit_ids(before=at("2025-01-01T00:00:02")), self.ids[:2])
+        self.assertEqual(edit_ids(after=at("2025-01-01T00:00:02")), self.ids[2:])
+        self.assertEqual(
+            edit_ids(after=at("2025-01-01T00:00:01"), before=at("2025-01-01T00:00:03")),
+            self.ids[1:3],
+        )
+
+    def test_status(self):
+        result = run_mcpdiff(
+            self.workspace,
+            "status",
+            "--json",
+            "--after",
+            "2025-01-01T00:00:03",
+            check=True,
+        )
+        edit_ids = [e["edit_id"] for e in json.loads(result.stdout)]
+        self.assertEqual(sorted(edit_ids), sorted(self.ids[3:]))
+
+        result = run_mcpdiff(self.workspace, "status", "--before", "after lunch")
+        self.assertEqual(result.returncode, HistoryError.exit_code)
+        self.assertIn("Invalid --before value", result.stderr)
+
+
+class TestStatusCount(unittest.TestCase):
+    def setUp(self):
+        # Logged a second apart from 2025-01-01T00:00:00Z
+        self.workspace = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        builder = HistoryBuilder(self.workspace)
+        self.ids = [builder.write("a.txt", "0\n")]
+        self.ids.append(builder.edit("a.txt", "1\n"))
+        self.ids.append(builder.write("b.txt", "b\n"))
+        builder.conversation("conv2")
+        self.ids.append(builder.edit("a.txt", "2\n"))
+        self.ids.append(builder.delete("b.txt"))
+
+    def tearDown(self):
+        shutil.rmtree(self.workspace, ignore_errors=True)
+
+    def output(self, *args: str) -> str:
+        result = run_mcpdiff(self.workspace, *args)
+        self.assertEqual(result.returncode, 0, result.stdout + result.stderr)
+        return result.stdout
+
+    def test_count_matches_listing(self):
+        self.output("accept", "-e", self.ids[0])
+        for filters in (
+            [],
+            ["--conv", "conv1"],
+            ["--file", "b.txt"],
+            ["--status", "pending"],
+            ["--status", "accepted"],
+            ["--op", "edit"],
+            ["--after", "2025-01-01T00:00:02"],
+            ["--before", "2025-01-01T00:00:02", "--file", "a.txt"],
+            ["--conv", "conv1", "--since-edit-id", self.ids[0]],
+            ["--file", "nothing"],
+        ):
+            listed = json.loads(self.output("status", "-n", "0", "--json", *filters))
+            self.assertEqual(
+                self.output("status", "--count", *filters), f"{len(listed)}\n"
+            )
+            self.assertEqual(
+                json.loads(self.output("status", "--count", "--json", *filters)),
+                {"count": len(listed)},
+            )
+
+    def test_group_by(self):
+        self.assertEqual(
+            json.loads(
+                self.output("status", "--count", "--group-by", "file", "--json")
+            ),
+            {"count": 5, "groups": {"a.txt": 3, "b.txt": 2}},
+        )
+        output = self.output(
+            "status", "--count", "--group-by", "operation", "--conv", "conv2"
+        )
+        self.assertEqual(output.splitlines(), ["     1  delete", "     1  edit"])
+
+        result = run_mcpdiff(self.workspace, "status", "--group-by", "file")
+        self.assertEqual(result.returncode, HistoryError.exit_code)
+        self.assertIn("--group-by needs --count", result.stderr)
+
+    def test_all_entries_newest_first(self):
+        listed = json.loads(self.output("status", "-n", "0", "--json"))
+        self.assertEqual([e["edit_id"] for e in listed], self.ids[::-1])
+        listed = json.loads(self.output("status", "-n", "2", "--json"))
+        self.assertEqual([e["edit_id"] for e in listed], self.ids[:2:-1])
+
+    def test_count_filtered_entries(self):
+        entries = history.find_all_entries(self.workspace / ".mcp" / "edit_history")
+        self.assertEqual(history.count_filtered_entries(entries, op_type="edit"), 2)
+        self.assertEqual(
+            history.count_filtered_entries(entries, "conversation"),
+            {"conv1": 3, "conv2": 2},
+        )
+        with self.assertRaises(HistoryError):
+            history.count_filtered_entries(entries, since_edit_id=self.ids[0])
+
+
+class TestConversationTags(unittest.TestCase):
+    def setUp(self):
+        self.workspace = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        self.builder = HistoryBuilder(self.workspace)
+        self.builder.write("a.txt", "a\n")
+        self.entry = self.builder.entries()[0]
+
+    def tearDown(self):
+        shutil.rmtree(self.workspace, ignore_errors=True)
+
+    def test_format_entry_summary(self):
+        tagged = history.format_entry_summary(self.entry, tags={"conv1": "dev"})
+        self.assertIn("conv1 [dev]", tagged)
+        self.assertNotIn("[dev]", history.format_entry_summary(self.entry))
+
+    def test_status_lists_tags(self):
+        history.save_tags(self.builder.history_root, {"dev": "conv1"})
+        result = run_mcpdiff(self.workspace, "status", check=True)
+        self.assertIn("conv1 [dev]", result.stdout)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,128 @@
+#!/usr/bin/env python3
+"""
+Tests for `mcpdiff show --symbols`:
+- Hunk headers of Rust files name the innermost fn, struct or impl they change
+- The content before an edit is replayed from the checkpoint and prior diffs
+- Other files, and content that cannot be replayed, keep plain headers
+"""
+
+import shutil
+import sys
+import tempfile
+import unittest
+from pathlib import Path
+
+# The CLI modules import each other by bare name; history_builder is found also
+# when run_tests.py imports this module as part of the package
+sys.path.insert(0, str(Path(__file__).parent.parent / "cli"))
+sys.path.insert(0, str(Path(__file__).parent))
+
+import mcpdiff_replay as replay
+import mcpdiff_symbols as symbols
+from history_builder import HistoryBuilder, run_mcpdiff
+from mcpdiff_utils import HistoryError
+
+
+SOURCE = """struct Point {
+    x: i32,
+}
+
+impl Point {
+    fn new() -> Self {
+        Point { x: 1 }
+    }
+
+    fn get(&self) -> i32 {
+        self.x
+    }
+}
+
+fn main() {
+    let p = Point::new();
+}
+"""
+
+
+class TestShowSymbols(unittest.TestCase):
+    def setUp(self):
+        self.workspace = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        self.builder = HistoryBuilder(self.workspace)
+        self.create = self.builder.write("src/main.rs", SOURCE)
+
+    def tearDown(self):
+        shutil.rmtree(self.workspace, ignore_errors=True)
+
+    def hunk_headers(self, identifier: str, *args: str) -> list:
+        result = run_mcpdiff(
+            self.workspace, "show", identifier, "--format", "raw", *args, check=True
+        )
+        return [line for line in result.stdout.splitlines() if line.startswith("@@")]
+
+    def test_hunks_name_their_symbol(self):
+        content = SOURCE.replace("self.x\n", "self.x + 1\n")
+        edit_get = self.builder.edit("src/main.rs", content)
+        content = content.replace("    x: i32,\n", "    x: i64,\n")
+        edit_struct = self.builder.edit("src/main.rs", content)
+
+        self.assertEqual(
+            self.hunk_headers(edit_get, "--symbols"),
+            ["@@ -8,7 +8,7 @@ fn Point::get (src/main.rs)"],
+        )
+        self.assertEqual(
+            self.hunk_headers(edit_struct, "--symbols"),
+            ["@@ -1,5 +1,5 @@ struct Point (src/main.rs)"],
+        )
+        # Only with --symbols
+        self.assertEqual(self.hunk_headers(edit_get), ["@@ -8,7 +8,7 @@"])
+
+    def test_insertion(self):
+        content = SOURCE.replace(
+            "    let p = Point::new();\n", "    let p = Point::new();\n    p.get();\n"
+        )
+        edit_id = self.builder.edit("src/main.rs", content)
+        self.assertEqual(
+            self.hunk_headers(edit_id, "--symbols"),
+            ["@@ -14,4 +14,5 @@ fn main (src/main.rs)"],
+        )
+
+    def test_plain_headers(self):
+        # A new file has no symbols before the edit
+        self.assertEqual(
+            self.hunk_headers(self.create, "--symbols"), ["@@ -0,0 +1,17 @@"]
+        )
+        self.builder.write("notes.txt", "fn main() {\n}\n")
+        edit_id = self.builder.edit("notes.txt", "fn main() {\n    go();\n}\n")
+        self.assertEqual(self.hunk_headers(edit_id, "--symbols"), ["@@ -1,2 +1,3 @@"])
+
+    def test_content_before(self):
+        edit_id = self.builder.edit("src/main.rs", SOURCE.replace("1", "2"))
+        entries = self.builder.entries()
+        self.assertEqual(
+            replay.content_before(
+                self.builder.entry(edit_id), entries, self.builder.history_root
+            ),
+            SOURCE,
+        )
+        self.assertEqual(
+            replay.content_before(
+                self.builder.entry(self.create), entries, self.builder.history_root
+            ),
+            "",
+        )
+        # An edit it does not follow from
+        entry = dict(self.builder.entry(edit_id), hash_before="0" * 64)
+        with self.assertRaises(HistoryError):
+            replay.content_before(entry, entries, self.builder.history_root)
+
+    def test_annotate_hunk_headers(self):
+        diff = "@@ -11 +11 @@\n-        self.x\n+        0\n"
+        self.assertEqual(
+            symbols.annotate_hunk_headers(diff, SOURCE, "lib.rs"),
+            "@@ -11 +11 @@ fn Point::get (lib.rs)\n-        self.x\n+        0\n",
+        )
+        self.assertEqual(symbols.annotate_hunk_headers(diff, SOURCE, "lib.py"), diff)
+        self.assertEqual(symbols.annotate_hunk_headers(diff, "", "lib.rs"), diff)
+
+
+if __name__ == "__main__":
+    unittest.main()
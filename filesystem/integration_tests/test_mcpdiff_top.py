@@ -0,0 +1,146 @@
+#!/usr/bin/env python3
+"""
+Tests for `mcpdiff top-files` and `mcpdiff top-conversations`:
+- Edits are counted per file or conversation, in total and per status,
+  without mcpdiff's own snapshot and revert entries
+- --limit, and --since/--until time ranges
+- byte growth per file or conversation, and ranking by it with --by bytes
+"""
+
+import json
+import shutil
+import subprocess
+import sys
+import tempfile
+import unittest
+from pathlib import Path
+
+# The CLI modules import each other by bare name; history_builder is found also
+# when run_tests.py imports this module as part of the package
+sys.path.insert(0, str(Path(__file__).parent.parent / "cli"))
+sys.path.insert(0, str(Path(__file__).parent))
+
+import mcpdiff_utils as utils
+from history_builder import HistoryBuilder, run_mcpdiff
+
+
+class TestTop(unittest.TestCase):
+    def setUp(self):
+        # Logged a second apart from 2025-01-01T00:00:00Z
+        self.workspace = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        self.builder = builder = HistoryBuilder(self.workspace)
+        self.create_a = builder.write("a.txt", "1\n")
+        builder.edit("a.txt", "2\n")
+        self.edit_a = builder.edit("a.txt", "3\n")
+        builder.write("b.txt", "b\n")
+        builder.conversation("conv2")
+        builder.edit("b.txt", "b2\n")
+        self.create_c = builder.write("c.txt", "c\n")
+
+    def tearDown(self):
+        shutil.rmtree(self.workspace, ignore_errors=True)
+
+    def mcpdiff(self, *args: str) -> subprocess.CompletedProcess:
+        return run_mcpdiff(self.workspace, *args, check=True)
+
+    def ranking(self, *args: str) -> list:
+        return json.loads(self.mcpdiff(*args, "--json").stdout)
+
+    def test_top_files(self):
+        self.mcpdiff("accept", "-e", self.create_a)
+        # Writes snapshot and revert entries for a.txt, which are not counted
+        self.mcpdiff("reject", "-e", self.edit_a)
+        self.assertEqual(
+            self.ranking("top-files"),
+            [
+                {
+                    "file_path": "a.txt",
+                    "total_edits": 3,
+                    "pending": 1,
+                    "accepted": 1,
+                    "rejected": 1,
+                    "byte_growth": 2,
+                },
+                {
+                    "file_path": "b.txt",
+                    "total_edits": 2,
+                    "pending": 2,
+                    "accepted": 0,
+                    "rejected": 0,
+                    "byte_growth": 3,
+                },
+                {
+                    "file_path": "c.txt",
+                    "total_edits": 1,
+                    "pending": 1,
+                    "accepted": 0,
+                    "rejected": 0,
+                    "byte_growth": 2,
+                },
+            ],
+        )
+
+        output = self.mcpdiff("top-files", "--limit", "2").stdout
+        self.assertIn("b.txt", output)
+        self.assertNotIn("c.txt", output)
+
+    def test_top_conversations(self):
+        rows = self.ranking("top-conversations")
+        self.assertEqual(
+            [(row["conversation_id"], row["total_edits"]) for row in rows],
+            [("conv1", 4), ("conv2", 2)],
+        )
+
+        self.mcpdiff("tag", "-c", "conv2", "--name", "refactor")
+        self.assertIn("conv2 [refactor]", self.mcpdiff("top-conversations").stdout)
+
+    def test_byte_growth(self):
+        self.builder.write("big.bin", "x" * 5000)
+        self.builder.delete("c.txt")
+        rows = self.ranking("top-files", "--by", "bytes")
+        self.assertEqual(
+            [(row["file_path"], row["byte_growth"]) for row in rows],
+            [("big.bin", 5000), ("b.txt", 3), ("a.txt", 2), ("c.txt", 0)],
+        )
+        rows = self.ranking("top-conversations", "--by", "bytes")
+        self.assertEqual(
+            [(row["conversation_id"], row["byte_growth"]) for row in rows],
+            [("conv2", 5001), ("conv1", 4)],
+        )
+
+        # Per file over one conversation
+        rows = self.ranking("top-files", "-c", "conv2", "--by", "bytes")
+        self.assertEqual(
+            [(row["file_path"], row["byte_growth"]) for row in rows],
+            [("big.bin", 5000), ("b.txt", 1), ("c.txt", 0)],
+        )
+        self.assertIn("+4.9 KiB", self.mcpdiff("top-files", "--by", "bytes").stdout)
+
+        # Entries logged before sizes were recorded count as no growth
+        entry = self.builder.entry(self.create_a)
+        self.assertIsNone(entry["byte_size_before"])
+        self.assertEqual(entry["byte_size_after"], 2)
+        log_file = self.builder.log_file("conv1")
+        entries = utils.read_log_file(log_file)
+        del entries[0]["byte_size_before"], entries[0]["byte_size_after"]
+        utils.write_log_file(log_file, entries)
+        rows = self.ranking("top-files", "--by", "bytes")
+        growth = {row["file_path"]: row["byte_growth"] for row in rows}
+        self.assertEqual(growth["a.txt"], 0)
+
+    def test_time_range(self):
+        # The edits of conv2 were logged at 00:00:04 and 00:00:05
+        rows = self.ranking("top-conversations", "--since", "2025-01-01T00:00:04")
+        self.assertEqual([row["conversation_id"] for row in rows], ["conv2"])
+        rows = self.ranking("top-files", "--until", "2025-01-01T00:00:02")
+        self.assertEqual(
+            [(row["file_path"], row["total_edits"]) for row in rows], [("a.txt", 2)]
+        )
+        self.assertEqual(self.ranking("top-files", "--since", "2026-01-01"), [])
+        self.assertIn(
+            "No edits found", self.mcpdiff("top-files", "--until", "2024-12-31").stdout
+        )
+
+
+if __name__ == "__main__":
+    unittest.main()
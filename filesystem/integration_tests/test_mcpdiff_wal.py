@@ -0,0 +1,180 @@
+#!/usr/bin/env python3
+"""
+Tests for the write-ahead logs (logs/<conv_id>.wal) of log writes:
+- Writes go through the WAL and leave none behind
+- `mcpdiff crash-recover` replays a WAL whose log is missing, torn or still
+  has the content the write replaces, also when the write removes entries,
+  and removes WALs that are already applied, incomplete or stale, whatever the
+  files' modification times
+- The server writes WALs mcpdiff can read and replay
+- The server resolves a WAL left in a conversation before it logs an edit
+  there, and never writes its own WAL over one
+"""
+
+import json
+import os
+import shutil
+import sys
+import tempfile
+import unittest
+from pathlib import Path
+from unittest import mock
+
+# The CLI modules import each other by bare name; history_builder is found also
+# when run_tests.py imports this module as part of the package
+sys.path.insert(0, str(Path(__file__).parent.parent / "cli"))
+sys.path.insert(0, str(Path(__file__).parent.parent / "src"))
+sys.path.insert(0, str(Path(__file__).parent))
+
+import mcp_history_format as history_format
+import mcpdiff_utils as utils
+from history_builder import HistoryBuilder, run_mcpdiff
+
+try:
+    sys.path.insert(0, str(Path(__file__).parent.parent))
+    from src import mcp_edit_utils as server_utils
+except ImportError:  # The server needs filelock
+    server_utils = None
+
+
+class TestWal(unittest.TestCase):
+    def setUp(self):
+        self.workspace = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        self.builder = HistoryBuilder(self.workspace)
+        self.edit = self.builder.write("a.txt", "one\n")
+        self.log_file = self.builder.log_file()
+        self.wal = history_format.get_wal_path(self.log_file)
+
+    def tearDown(self):
+        shutil.rmtree(self.workspace, ignore_errors=True)
+
+    def recover(self) -> list:
+        result = run_mcpdiff(self.workspace, "crash-recover", "--json", check=True)
+        return [(r["wal_file"], r["outcome"]) for r in json.loads(result.stdout)]
+
+    def crash_before_replace(self, status: str) -> bytes:
+        """The WAL of a status change whose log was never replaced."""
+        entries = utils.read_log_file(self.log_file)
+        entries[0]["status"] = status
+        data = utils.serialize_log_entries(entries)
+        history_format.write_wal(self.log_file, data)
+        self.touch_log_after_wal()
+        return data
+
+    def touch_log_after_wal(self):
+        """Makes the log look newer than the WAL, which must not matter."""
+        stat = self.wal.stat()
+        os.utime(self.log_file, (stat.st_atime + 10, stat.st_mtime + 10))
+
+    def test_writes_leave_no_wal(self):
+        self.assertEqual(self.wal.name, "conv1.wal")
+        self.builder.edit("a.txt", "two\n")
+        self.assertEqual(utils.find_wal_files(self.builder.history_root), [])
+        self.assertEqual(self.recover(), [])
+
+    def test_replay(self):
+        data = self.crash_before_replace("accepted")
+        self.assertEqual(self.builder.entry(self.edit)["status"], "pending")
+        self.assertEqual(self.recover(), [("conv1.wal", "replayed")])
+        self.assertEqual(self.log_file.read_bytes(), data)
+        self.assertEqual(self.builder.entry(self.edit)["status"], "accepted")
+        self.assertFalse(self.wal.exists())
+
+    def test_torn_or_missing_log(self):
+        # A rename that was not atomic left half a log
+        data = self.crash_before_replace("rejected")
+        with open(self.log_file, "r+b") as f:
+            f.truncate(10)
+        self.touch_log_after_wal()
+        self.assertEqual(self.recover(), [("conv1.wal", "replayed")])
+        self.assertEqual(self.log_file.read_bytes(), data)
+
+        self.crash_before_replace("accepted")
+        self.log_file.unlink()
+        self.assertEqual(self.recover(), [("conv1.wal", "replayed")])
+        self.assertEqual(self.builder.entry(self.edit)["status"], "accepted")
+
+    def test_removed_wals(self):
+        before = self.log_file.read_bytes()
+        # The crash came while the WAL was written
+        data = self.crash_before_replace("accepted")
+        self.wal.write_bytes(self.wal.read_bytes()[:-5])
+        self.assertEqual(self.recover(), [("conv1.wal", "discarded")])
+        self.assertEqual(self.log_file.read_bytes(), before)
+
+        # ... or after the log was replaced
+        history_format.write_wal(self.log_file, before)
+        self.assertEqual(self.recover(), [("conv1.wal", "applied")])
+
+        # A log written after the WAL's write reached it wins even when it
+        # looks older
+        self.builder.edit("a.txt", "two\n")
+        after = self.log_file.read_bytes()
+        self.log_file.write_bytes(before)
+        history_format.write_wal(self.log_file, before)
+        self.log_file.write_bytes(after)
+        stat = self.wal.stat()
+        os.utime(self.log_file, (stat.st_atime - 10, stat.st_mtime - 10))
+        self.assertEqual(self.recover(), [("conv1.wal", "stale")])
+        self.assertEqual(self.log_file.read_bytes(), after)
+        self.assertEqual(utils.find_wal_files(self.builder.history_root), [])
+
+    def test_replay_removal(self):
+        # A squash or reattach that removes an entry from the log
+        self.builder.edit("a.txt", "two\n")
+        entries = utils.read_log_file(self.log_file, migrate=False)
+        self.assertEqual(len(entries), 2)
+        data = utils.serialize_log_entries(entries[1:])
+        history_format.write_wal(self.log_file, data)
+        self.touch_log_after_wal()
+        self.assertEqual(self.recover(), [("conv1.wal", "replayed")])
+        self.assertEqual(self.log_file.read_bytes(), data)
+
+    def test_human_output(self):
+        self.crash_before_replace("accepted")
+        result = run_mcpdiff(self.workspace, "crash-recover")
+        self.assertIn("conv1.wal: ", result.stdout)
+        self.assertIn("Recovered 1 WAL file(s), 1 replayed.", result.stdout)
+
+    @unittest.skipIf(server_utils is None, "the server's dependencies are missing")
+    def test_server_wal(self):
+        before = self.log_file.read_bytes()
+        entries = utils.read_log_file(self.log_file, migrate=False)
+        entries[0]["status"] = "accepted"
+        server_utils.write_log_file(self.log_file, entries)
+        self.assertFalse(self.wal.exists())
+        self.log_file.write_bytes(before)
+
+        # The server process dies before it replaces the log
+        with mock.patch.object(server_utils.os, "replace", side_effect=SystemExit):
+            with self.assertRaises(SystemExit):
+                server_utils.write_log_file(self.log_file, entries)
+        self.assertEqual(self.log_file.read_bytes(), before)
+        data = history_format.read_wal(self.wal)
+        self.assertEqual(data, utils.serialize_log_entries(entries))
+        self.touch_log_after_wal()
+        self.assertEqual(self.recover(), [("conv1.wal", "replayed")])
+        self.assertEqual(self.builder.entry(self.edit)["status"], "accepted")
+
+    @unittest.skipIf(server_utils is None, "the server's dependencies are missing")
+    def test_server_resolves_orphaned_wal(self):
+        data = self.crash_before_replace("accepted")
+        entries = utils.read_log_file(self.log_file, migrate=False)
+        with self.assertRaises(server_utils.HistoryError):
+            server_utils.write_log_file(self.log_file, entries)
+        self.assertEqual(history_format.read_wal(self.wal), data)
+
+        with self.assertLogs("mcp_history_utils", level="WARNING"):
+            server_utils.recover_wals(self.builder.history_root, "conv1")
+        self.assertFalse(self.wal.exists())
+        self.assertEqual(self.log_file.read_bytes(), data)
+        self.assertEqual(self.builder.entry(self.edit)["status"], "accepted")
+        sidecar = utils.read_merkle_sidecar(self.builder.history_root, "conv1")
+        actual = utils.compute_merkle_root(
+            self.builder.history_root, "conv1", sidecar["algorithm"]
+        )
+        self.assertEqual(sidecar["root"], actual["root"])
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,98 @@
+#!/usr/bin/env python3
+"""
+Tests for `mcpdiff workspace detect`:
+- Workspaces are found at any depth below ROOT, and ROOT itself
+- .git, node_modules and the .mcp directories themselves are not searched
+- --json adds the number of conversations and pending edits of each
+"""
+
+import json
+import shutil
+import subprocess
+import sys
+import tempfile
+import unittest
+from pathlib import Path
+
+# The CLI modules import each other by bare name; history_builder is found also
+# when run_tests.py imports this module as part of the package
+sys.path.insert(0, str(Path(__file__).parent.parent / "cli"))
+sys.path.insert(0, str(Path(__file__).parent))
+
+import mcpdiff_history as history
+from history_builder import HistoryBuilder, run_mcpdiff
+from mcpdiff_utils import HistoryError
+
+
+class TestWorkspaceDetect(unittest.TestCase):
+    def setUp(self):
+        self.root = Path(tempfile.mkdtemp(prefix="mcpdiff_test_")).resolve()
+        builder = HistoryBuilder(self.root / "services" / "api")
+        builder.write("a.txt", "a\n")
+        builder.edit("a.txt", "b\n")
+        builder.conversation("conv2").write("b.txt", "b\n")
+        HistoryBuilder(self.root / "web").write("index.html", "<p>\n")
+        (self.root / "docs").mkdir()
+        # Copies of a workspace that are not workspaces of their own
+        for skipped in (".git", "node_modules/pkg"):
+            HistoryBuilder(self.root / skipped).write("x.txt", "x\n")
+
+    def tearDown(self):
+        shutil.rmtree(self.root, ignore_errors=True)
+
+    def mcpdiff(self, *args: str, cwd: Path) -> subprocess.CompletedProcess:
+        return run_mcpdiff(None, *args, cwd=cwd)
+
+    def test_find_workspace_roots(self):
+        self.assertEqual(
+            history.find_workspace_roots(self.root),
+            [self.root / "services" / "api", self.root / "web"],
+        )
+        self.assertEqual(
+            history.find_workspace_roots(self.root / "web"), [self.root / "web"]
+        )
+        self.assertEqual(history.find_workspace_roots(self.root / "docs"), [])
+
+    def test_detect(self):
+        # ROOT defaults to the current directory, which need not be a workspace
+        result = self.mcpdiff("workspace", "detect", cwd=self.root)
+        self.assertEqual(result.returncode, 0, result.stderr)
+        self.assertEqual(
+            result.stdout.splitlines(),
+            [str(self.root / "services" / "api"), str(self.root / "web")],
+        )
+
+        result = self.mcpdiff("workspace", "detect", "web", "--json", cwd=self.root)
+        self.assertEqual(result.returncode, 0, result.stderr)
+        self.assertEqual(
+            json.loads(result.stdout),
+            [
+                {
+                    "path": str(self.root / "web"),
+                    "conversation_count": 1,
+                    "pending_count": 1,
+                }
+            ],
+        )
+
+    def test_counts(self):
+        result = self.mcpdiff(
+            "workspace", "detect", str(self.root / "services"), "--json", cwd=self.root
+        )
+        self.assertEqual(result.returncode, 0, result.stderr)
+        [summary] = json.loads(result.stdout)
+        self.assertEqual(summary["conversation_count"], 2)
+        self.assertEqual(summary["pending_count"], 3)
+
+        api = self.root / "services" / "api"
+        self.assertEqual(self.mcpdiff("accept", "-c", "conv2", cwd=api).returncode, 0)
+        self.assertEqual(history.summarize_workspace(api)["pending_count"], 2)
+
+    def test_not_a_directory(self):
+        result = self.mcpdiff("workspace", "detect", "missing", cwd=self.root)
+        self.assertEqual(result.returncode, HistoryError.exit_code)
+        self.assertIn("Not a directory", result.stderr)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,123 @@
+#!/usr/bin/env python3
+"""
+Tests for the workspace_root_hash log field:
+- The hash covers file names only, not contents, the history itself or
+  files listed in .mcp/ignore
+- The server's hash, kept up to date with its edits, matches a fresh walk
+- `diagnose` notices files added, removed or renamed since an edit was recorded
+"""
+
+import shutil
+import sys
+import tempfile
+import unittest
+from pathlib import Path
+
+# The CLI modules import each other by bare name; history_builder is found
+# also when run_tests.py imports this module as part of the package
+sys.path.insert(0, str(Path(__file__).parent.parent / "cli"))
+sys.path.insert(0, str(Path(__file__).parent))
+
+from history_builder import HistoryBuilder
+import mcpdiff_history as history
+import mcpdiff_migration as migration
+import mcpdiff_utils as utils
+
+try:
+    sys.path.insert(0, str(Path(__file__).parent.parent))
+    from src import mcp_edit_utils as server_utils
+except ImportError:  # The server needs filelock
+    server_utils = None
+
+
+class WorkspaceHashTestCase(unittest.TestCase):
+    def setUp(self):
+        self.workspace = Path(tempfile.mkdtemp(prefix="mcpdiff_test_"))
+        self.builder = HistoryBuilder(self.workspace)
+
+    def tearDown(self):
+        shutil.rmtree(self.workspace, ignore_errors=True)
+
+
+class TestWorkspaceRootHash(WorkspaceHashTestCase):
+    def test_names_only(self):
+        (self.workspace / "src").mkdir()
+        (self.workspace / "src" / "a.txt").write_text("one\n")
+        before = utils.compute_workspace_root_hash(self.workspace)
+        self.assertRegex(before, r"^(blake3|blake2b-256):[0-9a-f]{64}$")
+
+        (self.workspace / "src" / "a.txt").write_text("two\n")
+        (self.workspace / ".git").mkdir()
+        (self.workspace / ".git" / "HEAD").write_text("ref\n")
+        (self.workspace / "src" / ".mcp").mkdir()
+        self.assertEqual(utils.compute_workspace_root_hash(self.workspace), before)
+
+        (self.workspace / "src" / "a.txt").rename(self.workspace / "src" / "b.txt")
+        self.assertNotEqual(utils.compute_workspace_root_hash(self.workspace), before)
+
+    def test_ignored_files(self):
+        (self.workspace / ".mcp" / "ignore").write_text("build/\n*.log\n")
+        (self.workspace / "a.txt").write_text("one\n")
+        before = utils.compute_workspace_root_hash(self.workspace)
+
+        (self.workspace / "build").mkdir()
+        (self.workspace / "build" / "out.bin").write_bytes(b"\0")
+        (self.workspace / "run.log").write_text("")
+        self.assertEqual(utils.compute_workspace_root_hash(self.workspace), before)
+
+    @unittest.skipIf(server_utils is None, "server dependencies not installed")
+    def test_server_keeps_its_files_current(self):
+        (self.workspace / "a.txt").write_text("one\n")
+        server_utils.load_workspace_files(self.workspace)
+        (self.workspace / "a.txt").rename(self.workspace / "b.txt")
+        (self.workspace / "c.txt").write_text("")
+        self.assertEqual(
+            server_utils.workspace_root_hash(
+                self.workspace, [Path("b.txt"), Path("a.txt"), Path("c.txt")]
+            ),
+            utils.compute_workspace_root_hash(self.workspace),
+        )
+
+    def test_computed_once_per_command(self):
+        algorithm = utils.compute_workspace_root_hash(self.workspace).split(":")[0]
+        hashes = utils.WorkspaceRootHashes(self.workspace)
+        first = hashes.get(algorithm)
+        (self.workspace / "new.txt").write_text("")
+        self.assertEqual(hashes.get(algorithm), first)
+        self.assertNotEqual(
+            utils.WorkspaceRootHashes(self.workspace).get(algorithm), first
+        )
+
+    def test_unknown_algorithm(self):
+        with self.assertRaises(ValueError):
+            utils.compute_workspace_root_hash(self.workspace, "md5")
+
+
+class TestDiagnoseStructure(WorkspaceHashTestCase):
+    def test_structure_changed(self):
+        self.builder.write("a.txt", "one\n")
+        edit_id = self.builder.edit("a.txt", "two\n")
+        entries = self.builder.entries()
+        result = history.diagnose_file("a.txt", entries, self.workspace)
+        self.assertEqual(result["state"], "ok")
+        self.assertFalse(result["structure_changed"])
+
+        (self.workspace / "b.txt").write_text("untracked\n")
+        result = history.diagnose_file("a.txt", entries, self.workspace)
+        self.assertEqual(result["edit_id"], edit_id)
+        self.assertEqual(result["state"], "ok")
+        self.assertTrue(result["structure_changed"])
+
+    def test_entries_without_hash(self):
+        entry = migration.default_migrator.upgrade(
+            {"edit_id": "e1", "schema_version": 2}
+        )
+        self.assertIsNone(entry["workspace_root_hash"])
+        hashes = utils.WorkspaceRootHashes(self.workspace)
+        self.assertIsNone(history.workspace_structure_changed(entry, hashes))
+        entry["workspace_root_hash"] = "sha1:00"
+        self.assertIsNone(history.workspace_structure_changed(entry, hashes))
+
+
+if __name__ == "__main__":
+    unittest.main()
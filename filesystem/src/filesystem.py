@@ -25,12 +25,25 @@ try:
         get_metadata,
         get_history_root,
         sanitize_path_for_filename,
+        get_target_lock_path,
         acquire_lock,
         release_lock,
         calculate_hash,
+        load_workspace_files,
+        workspace_root_hash,
         generate_diff,
+        generate_binary_patch,
+        changed_byte_range,
+        encode_diff,
+        find_conflict_markers,
+        is_binary_content,
+        is_ignored_path,
+        recover_wals,
         read_log_file,
+        read_all_shards,
         write_log_file,
+        get_log_file_path,
+        get_namespaced_conversation_id,
         HistoryError,
         log,
         get_next_tool_call_index,
@@ -38,6 +51,12 @@ try:
         LOGS_DIR,
         DIFFS_DIR,
         CHECKPOINTS_DIR,
+        LOG_SCHEMA_VERSION,
+        SESSION_ID_ENV_VAR,
+        BSDIFF_ENCODING,
+        TEXT_ENCODING,
+        PARTIAL_EDIT_MIN_SIZE,
+        MOVE_OPERATIONS,
     )
 except ImportError:
     # This branch is for when running as a module
@@ -48,12 +67,25 @@ except ImportError:
         get_metadata,
         get_history_root,
         sanitize_path_for_filename,
+        get_target_lock_path,
         acquire_lock,
         release_lock,
         calculate_hash,
+        load_workspace_files,
+        workspace_root_hash,
         generate_diff,
+        generate_binary_patch,
+        changed_byte_range,
+        encode_diff,
+        find_conflict_markers,
+        is_binary_content,
+        is_ignored_path,
+        recover_wals,
         read_log_file,
+        read_all_shards,
         write_log_file,
+        get_log_file_path,
+        get_namespaced_conversation_id,
         HistoryError,
         log,
         get_next_tool_call_index,
@@ -61,6 +93,12 @@ except ImportError:
         LOGS_DIR,
         DIFFS_DIR,
         CHECKPOINTS_DIR,
+        LOG_SCHEMA_VERSION,
+        SESSION_ID_ENV_VAR,
+        BSDIFF_ENCODING,
+        TEXT_ENCODING,
+        PARTIAL_EDIT_MIN_SIZE,
+        MOVE_OPERATIONS,
     )
 
 try:
@@ -303,6 +341,64 @@ def _get_or_create_conversation_id(ctx: Optional[Context] = None) -> str:
         return _current_conversation_id
 
 
+def _get_ide_context(ctx: Optional[Context] = None) -> Optional[Dict[str, Any]]:
+    """
+    Get the optional IDE context (cursor position, visible range) sent by the client.
+    Clients pass it as `ide_context` in the request `_meta`. Its contents are not
+    validated so that each IDE integration can store whatever it finds relevant.
+    """
+    try:
+        meta = ctx.request_context.meta if ctx else None
+    except (AttributeError, ValueError):
+        return None
+    ide_context = getattr(meta, "ide_context", None) if meta else None
+    return ide_context if isinstance(ide_context, dict) else None
+
+
+def _get_parent_edit_id(ctx: Optional[Context] = None) -> Optional[str]:
+    """
+    Get the edit this tool call depends on, if the client declared one as
+    `parent_edit_id` in the request `_meta`. mcpdiff rejects an edit's children
+    along with it, and won't accept a child of a rejected edit without --force.
+    """
+    try:
+        meta = ctx.request_context.meta if ctx else None
+    except (AttributeError, ValueError):
+        return None
+    parent_edit_id = getattr(meta, "parent_edit_id", None) if meta else None
+    if isinstance(parent_edit_id, str) and parent_edit_id:
+        return parent_edit_id
+    return None
+
+
+def _get_fork(ctx: Optional[Context] = None) -> Tuple[Optional[str], Optional[str]]:
+    """
+    Get the conversation this one was forked from and the edit it was forked at,
+    if the client declared them as `parent_conversation_id` and `forked_at` in
+    the request `_meta`. `mcpdiff conversations --tree` shows the lineage.
+    """
+    try:
+        meta = ctx.request_context.meta if ctx else None
+    except (AttributeError, ValueError):
+        return None, None
+    values = []
+    for key in ("parent_conversation_id", "forked_at"):
+        value = getattr(meta, key, None) if meta else None
+        values.append(value if isinstance(value, str) and value else None)
+    parent_conversation_id, forked_at = values
+    # A fork point without the conversation it belongs to means nothing
+    return parent_conversation_id, forked_at if parent_conversation_id else None
+
+
+def _get_session_id() -> Optional[str]:
+    """
+    Get the shell session the server runs in, from the MCP_SESSION_ID environment
+    variable, so that `mcpdiff status --session` can group the conversations of
+    one terminal session.
+    """
+    return os.environ.get(SESSION_ID_ENV_VAR) or None
+
+
 def _finish_edit() -> str:
     """
     End the current conversation and return its ID.
@@ -393,9 +489,16 @@ def track_edit_history(func: Callable) -> Callable:
             return f"Error: Missing required arguments for {func.__name__}."
 
         # Get or create conversation ID
+        ide_context: Optional[Dict[str, Any]] = None
+        parent_edit_id: Optional[str] = None
+        parent_conversation_id: Optional[str] = None
+        forked_at: Optional[str] = None
         if "ctx" in bound_args.arguments:
             ctx = bound_args.arguments["ctx"]
             conversation_id = _get_or_create_conversation_id(ctx)
+            ide_context = _get_ide_context(ctx)
+            parent_edit_id = _get_parent_edit_id(ctx)
+            parent_conversation_id, forked_at = _get_fork(ctx)
         else:
             conversation_id = _get_or_create_conversation_id()
         current_index = get_next_tool_call_index(conversation_id)
@@ -456,7 +559,9 @@ def track_edit_history(func: Callable) -> Callable:
         file_existed_before = validated_path.exists()  # Check before locks
 
         if tool_name == "move_file":
-            operation = "move"
+            # A rename stays in the source's directory; a move may need new ones
+            same_directory = validated_source_path.parent == validated_path.parent
+            operation = "rename" if same_directory else "move"
         elif tool_name == "delete_a_file":
             operation = "delete"
             if not file_existed_before:
@@ -464,6 +569,8 @@ def track_edit_history(func: Callable) -> Callable:
                 return f"Error: File to delete not found at {file_path_str}"
         elif tool_name == "write_file":
             operation = "create" if not file_existed_before else "replace"
+        elif tool_name == "append_to_file":
+            operation = "append" if file_existed_before else "create"
         elif tool_name in [
             "edit_file_diff",
             "replace_lines_in_file",
@@ -483,12 +590,15 @@ def track_edit_history(func: Callable) -> Callable:
 
         # --- Initialize Variables ---
         workspace_root = history_root.parent.parent
+        conversation_id = get_namespaced_conversation_id(
+            workspace_root, conversation_id
+        )
         edit_id = str(uuid.uuid4())
-        log_file_path = history_root / LOGS_DIR / f"{conversation_id}.log"
+        log_file_path = get_log_file_path(history_root, conversation_id)
         diff_dir = history_root / DIFFS_DIR / conversation_id
-        diff_dir.mkdir(exist_ok=True)
+        diff_dir.mkdir(parents=True, exist_ok=True)
         checkpoint_dir = history_root / CHECKPOINTS_DIR / conversation_id
-        checkpoint_dir.mkdir(exist_ok=True)
+        checkpoint_dir.mkdir(parents=True, exist_ok=True)
         relative_diff_path = Path(DIFFS_DIR) / conversation_id / f"{edit_id}.diff"
         diff_file_path = history_root / relative_diff_path
 
@@ -499,8 +609,14 @@ def track_edit_history(func: Callable) -> Callable:
             if validated_source_path
             else None
         )
+        # Files listed in .mcp/ignore get no checkpoint and an empty diff
+        ignored = is_ignored_path(workspace_root, str(relative_file_path)) or (
+            relative_source_path is not None
+            and is_ignored_path(workspace_root, str(relative_source_path))
+        )
 
         content_before: Optional[List[str]] = None
+        bytes_before = b""
         hash_before: Optional[str] = None
         checkpoint_created = False
         relative_checkpoint_path: Optional[Path] = None
@@ -508,16 +624,33 @@ def track_edit_history(func: Callable) -> Callable:
         source_file_lock = None
         log_file_lock = None
 
+        # Walked now, if at all, not while the locks below hold other edits up
+        load_workspace_files(workspace_root)
+
         try:
             # --- Acquire Locks ---
-            target_file_lock = acquire_lock(str(validated_path))
+            # File locks live in the history directory (locks/), not next to
+            # the files
+            target_file_lock = acquire_lock(
+                get_target_lock_path(history_root, validated_path)
+            )
             if validated_source_path:
-                source_file_lock = acquire_lock(str(validated_source_path))
-            log_file_lock = acquire_lock(str(log_file_path))
+                source_file_lock = acquire_lock(
+                    get_target_lock_path(history_root, validated_source_path)
+                )
+            # Conversation-level lock (logs/<conv_id>.log.lock), shared by all
+            # log shards and taken by mcpdiff while it accepts/rejects edits
+            log_file_lock = acquire_lock(
+                str(history_root / LOGS_DIR / f"{conversation_id}.log")
+            )
+            # A write that crashed may have left a WAL with the log's content
+            recover_wals(history_root, conversation_id)
 
             # --- Read State Before Operation ---
             path_to_read_before = (
-                validated_source_path if operation == "move" else validated_path
+                validated_source_path
+                if operation in MOVE_OPERATIONS
+                else validated_path
             )
             file_existed_before_locked = path_to_read_before.exists()
             if operation in ["delete", "edit"] and not file_existed_before_locked:
@@ -525,13 +658,20 @@ def track_edit_history(func: Callable) -> Callable:
                     f"File vanished before {operation}: {path_to_read_before.name}"
                 )
 
+            size_before = 0
             if file_existed_before_locked:
                 hash_before = calculate_hash(str(path_to_read_before))
+                size_before = path_to_read_before.stat().st_size
                 try:
+                    # Undecodable bytes are kept for the diff (see encode_diff())
                     with open(
-                        path_to_read_before, "r", encoding="utf-8", errors="ignore"
+                        path_to_read_before,
+                        "r",
+                        encoding="utf-8",
+                        errors="surrogateescape",
                     ) as f:
                         content_before = f.readlines()
+                    bytes_before = path_to_read_before.read_bytes()
                 except IOError:
                     content_before = None
             else:
@@ -548,12 +688,15 @@ def track_edit_history(func: Callable) -> Callable:
                 Path(CHECKPOINTS_DIR) / conversation_id / sanitized_chkpt_fname
             )
             current_log_entries = read_log_file(log_file_path)
+            # Checkpoint once per conversation, so look at all of its shards
             seen_paths = set(
-                e["file_path"] for e in current_log_entries if "file_path" in e
+                e["file_path"]
+                for e in read_all_shards(history_root, conversation_id)
+                if "file_path" in e
             )
 
             # Only create checkpoint if this is the first time we're seeing this path
-            if str(relative_file_path) not in seen_paths:
+            if str(relative_file_path) not in seen_paths and not ignored:
                 checkpoint_created = True
                 if file_existed_before_locked:
                     try:
@@ -571,13 +714,15 @@ def track_edit_history(func: Callable) -> Callable:
 
             # --- Read State After Operation ---
             content_after: Optional[List[str]] = None
+            bytes_after = b""
             hash_after: Optional[str] = None
             if operation != "delete":
                 try:
                     with open(
-                        validated_path, "r", encoding="utf-8", errors="ignore"
+                        validated_path, "r", encoding="utf-8", errors="surrogateescape"
                     ) as f:
                         content_after = f.readlines()
+                    bytes_after = validated_path.read_bytes()
                     hash_after = calculate_hash(str(validated_path))
                 except IOError as e:
                     log.error(f"Failed to read file after operation: {e}")
@@ -586,7 +731,37 @@ def track_edit_history(func: Callable) -> Callable:
 
             # --- Generate Diff ---
             diff_content = ""  # Initialize with empty string to avoid None case
-            if content_before is not None and content_after is not None:
+            diff_encoding: Optional[str] = None
+            appended = b""
+            # Binary files get a bsdiff patch instead of a unified diff
+            binary_patch: Optional[bytes] = None
+            # Large files only store the bytes written over the range that changed
+            partial_range: Optional[Tuple[int, int]] = None
+            written = b""
+            if ignored:
+                if operation not in MOVE_OPERATIONS:
+                    diff_file_path.write_bytes(b"")
+            elif (
+                operation in ["replace", "edit"]
+                and size_before >= PARTIAL_EDIT_MIN_SIZE
+                and hash_after is not None
+            ):
+                start, end, written = changed_byte_range(bytes_before, bytes_after)
+                partial_range = (start, end)
+                diff_file_path.write_bytes(written)
+            elif operation in ["create", "replace", "edit"] and (
+                is_binary_content(bytes_before) or is_binary_content(bytes_after)
+            ):
+                binary_patch = generate_binary_patch(bytes_before, bytes_after)
+                diff_file_path.write_bytes(binary_patch)
+            elif operation == "append":
+                # Store only the appended bytes; mcpdiff re-applies them verbatim
+                with open(validated_path, "rb") as f:
+                    f.seek(size_before)
+                    appended = f.read()
+                diff_file_path.write_bytes(appended)
+                diff_content = appended.decode("utf-8", errors="replace")
+            elif content_before is not None and content_after is not None:
                 try:
                     diff_content = generate_diff(
                         content_before,
@@ -595,7 +770,10 @@ def track_edit_history(func: Callable) -> Callable:
                         str(relative_file_path),
                     )
                     if diff_content:
-                        diff_file_path.write_text(diff_content)
+                        # Files (or paths in the headers) that are not valid
+                        # UTF-8 get a base64-encoded diff
+                        diff_bytes, diff_encoding = encode_diff(diff_content)
+                        diff_file_path.write_bytes(diff_bytes)
                 except Exception as e:
                     log.error(f"Failed to generate diff: {e}")
                     raise HistoryError(f"Failed to generate diff: {e}")
@@ -609,23 +787,59 @@ def track_edit_history(func: Callable) -> Callable:
                     :21
                 ]
                 + "Z",
-                "operation": operation,
+                "operation": "partial" if partial_range else operation,
                 "file_path": str(relative_file_path),
                 "source_path": str(relative_source_path)
                 if relative_source_path
                 else None,
                 "tool_name": tool_name,
+                "parent_edit_id": parent_edit_id,
                 "status": "pending",
-                "diff_file": str(relative_diff_path) if diff_content else None,
+                "diff_file": str(relative_diff_path)
+                if (ignored and operation not in MOVE_OPERATIONS)
+                or diff_content
+                or operation == "append"
+                or binary_patch is not None
+                or partial_range is not None
+                else None,
                 "checkpoint_file": str(relative_checkpoint_path)
                 if checkpoint_created
                 else None,
                 "hash_before": hash_before,
                 "hash_after": hash_after,
+                "schema_version": LOG_SCHEMA_VERSION,
+                "workspace_root_hash": workspace_root_hash(
+                    workspace_root,
+                    [relative_file_path]
+                    + ([relative_source_path] if relative_source_path else []),
+                ),
+                "session_id": _get_session_id(),
+                "parent_conversation_id": parent_conversation_id,
+                "forked_at": forked_at,
+                "byte_range_start": partial_range[0] if partial_range else None,
+                "byte_range_end": partial_range[1] if partial_range else None,
+                "byte_size_before": size_before if file_existed_before_locked else None,
+                "byte_size_after": len(bytes_after) if hash_after is not None else None,
+                "conflict_markers": find_conflict_markers(content_after) or None
+                if content_after and binary_patch is None
+                else None,
+                "ignored": ignored,
             }
+            if ide_context:
+                log_entry["ide_context"] = ide_context
+            if binary_patch is not None:
+                log_entry["file_encoding"] = BSDIFF_ENCODING
+            else:
+                log_entry["file_encoding"] = diff_encoding or TEXT_ENCODING
 
             # For edit and replace operations, always ensure there's a diff file
-            if (operation == "edit" or operation == "replace") and not diff_content:
+            if (
+                operation in ["edit", "replace"]
+                and not ignored
+                and not diff_content
+                and binary_patch is None
+                and partial_range is None
+            ):
                 # Create an empty diff for the edit or replace operation
                 empty_diff = generate_diff(
                     content_before or [],
@@ -633,15 +847,31 @@ def track_edit_history(func: Callable) -> Callable:
                     str(relative_file_path),
                     str(relative_file_path),
                 )
-                diff_file_path.write_text(empty_diff)
+                diff_file_path.write_text(
+                    empty_diff, encoding="utf-8", errors="surrogateescape"
+                )
                 log_entry["diff_file"] = str(relative_diff_path)
 
             current_log_entries.append(log_entry)
-            write_log_file(log_file_path, current_log_entries)
+            write_log_file(log_file_path, current_log_entries, appended=1)
 
             # Modify the result to include the diff if it's small enough
             # (only for operations that modify files)
+            if operation == "append" and not ignored:
+                return f"{result}\nAppended {len(appended)} bytes."
+            if partial_range is not None:
+                start, end = partial_range
+                return (
+                    f"{result}\nBytes {start}-{end} replaced with {len(written)} bytes."
+                )
+            if binary_patch is not None:
+                return f"{result}\nBinary file changed (bsdiff patch, {len(binary_patch)} bytes)."
             if operation in ["edit", "replace", "create", "delete"] and diff_content:
+                if diff_encoding:
+                    # Undecodable bytes cannot be sent back as text
+                    diff_content = diff_content.encode(
+                        "utf-8", errors="surrogateescape"
+                    ).decode("utf-8", errors="replace")
                 # Count the number of lines in the diff
                 diff_lines = diff_content.count("\n")
                 if diff_lines < 200:  # Check if the diff is less than 200 lines
@@ -1866,6 +2096,24 @@ def write_file(ctx: Context, path: str, content: str) -> str:
         return f"Error writing file: {str(e)}"
 
 
+@mcp.tool()
+@track_edit_history
+def append_to_file(ctx: Context, path: str, content: str) -> str:
+    """Append content to the end of a file, creating it if it does not exist. Nothing is added between the existing content and `content`, so include a leading newline if needed. Use this for logs and generated output instead of rewriting the whole file."""
+    try:
+        resolved_path = _resolve_path(path)
+        validated_path = validate_path(resolved_path, WORKING_DIRECTORY)
+        if os.path.isdir(validated_path):
+            return f"Error: Path {path} is a directory."
+        Path(validated_path).parent.mkdir(parents=True, exist_ok=True)
+        # newline="" keeps the content's line endings as given
+        with open(validated_path, "a", encoding="utf-8", newline="") as f:
+            f.write(content)
+        return f"Successfully appended to {path}"
+    except Exception as e:
+        return f"Error appending to file: {str(e)}"
+
+
 @mcp.tool()
 @track_edit_history
 def edit_file_diff(
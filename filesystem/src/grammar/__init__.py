@@ -0,0 +1,30 @@
+"""
+Source code parsers: `regex_parser` and `token_parser` find code elements for
+the server's tools, and `symbols` provides parse_symbols(), the public API for
+extracting a symbol tree, find_tests() and render_outline().
+"""
+
+from .symbols import (
+    Attribute,
+    ColumnUnit,
+    ElidedBody,
+    FnSignature,
+    GenericKind,
+    GenericParam,
+    IssueKind,
+    Language,
+    Outline,
+    OutlineOptions,
+    Param,
+    ParseIssue,
+    ParseResult,
+    Shape,
+    Span,
+    Symbol,
+    SymbolKind,
+    TestItem,
+    WherePredicate,
+    find_tests,
+    parse_symbols,
+    render_outline,
+)
@@ -0,0 +1,100 @@
+"""
+Symbol extraction: the items of a source file as a tree of symbols with their
+kind, name, visibility, span, doc comment and attributes.
+
+    from grammar import Language, parse_symbols
+
+    result = parse_symbols(source, Language.RUST)
+    for symbol in result.walk():
+        print(symbol.kind.value, symbol.name, symbol.span.start_line)
+    for issue in result.issues:
+        print(issue.kind.value, issue.message)
+
+Parsing does not fail on invalid source: the symbols that can be recognized
+are returned along with the problems found (ParseIssue). Spans have both byte
+offsets and lines and columns; pass columns=ColumnUnit.UTF16 for LSP columns.
+
+find_tests(source, language) lists the test functions and test modules of a
+source (TestItem), and render_outline(source, options) renders its skeleton,
+with bodies elided to `{ ... }` (Outline).
+"""
+
+from typing import Union
+
+from .analysis import find_tests
+from .model import (
+    Attribute,
+    ColumnUnit,
+    FnSignature,
+    GenericKind,
+    GenericParam,
+    IssueKind,
+    Language,
+    Param,
+    ParseIssue,
+    ParseResult,
+    Shape,
+    Span,
+    Symbol,
+    SymbolKind,
+    TestItem,
+    WherePredicate,
+)
+from .outline import ElidedBody, Outline, OutlineOptions, render_outline
+from .rust_parser import parse_rust
+
+__all__ = [
+    "Attribute",
+    "ColumnUnit",
+    "ElidedBody",
+    "FnSignature",
+    "GenericKind",
+    "GenericParam",
+    "IssueKind",
+    "Language",
+    "Outline",
+    "OutlineOptions",
+    "Param",
+    "ParseIssue",
+    "ParseResult",
+    "Shape",
+    "Span",
+    "Symbol",
+    "SymbolKind",
+    "TestItem",
+    "WherePredicate",
+    "find_tests",
+    "parse_symbols",
+    "render_outline",
+]
+
+
+def parse_symbols(
+    source: str,
+    language: Union[Language, str],
+    include_nested: bool = False,
+    columns: Union[ColumnUnit, str] = ColumnUnit.CHARACTERS,
+) -> ParseResult:
+    """
+    The symbol tree of a source, with the issues found parsing it.
+
+    Args:
+        source: The source code
+        language: A Language, or its value (`"rust"`)
+        include_nested: Also list the items declared in function bodies (nested
+            functions, structs, impls) as children of the function
+        columns: What the columns of spans count, a ColumnUnit or its value:
+            characters, or UTF-16 code units (`"utf16"`) as LSP positions do
+
+    Returns:
+        The top-level symbols, each with its nested symbols as children, and the
+        parse issues in source order.
+
+    Raises:
+        ValueError: If the language or column unit is not supported.
+    """
+    language = Language(language)
+    columns = ColumnUnit(columns)
+    if language == Language.RUST:
+        return parse_rust(source, include_nested, columns)
+    raise ValueError(f"Unsupported language: {language.value}")
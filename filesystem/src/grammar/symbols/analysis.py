@@ -0,0 +1,89 @@
+"""
+Analyses built on the symbol tree: find_tests() lists the tests of a source,
+for tools that offer to list or run them.
+"""
+
+from typing import List, Optional, Union
+
+from .model import Attribute, ColumnUnit, Language, Symbol, SymbolKind, TestItem
+from .rust_parser import parse_rust
+
+# Test attributes besides the `<crate>::test` ones (`tokio::test`,
+# `async_std::test`)
+_TEST_ATTRIBUTES = {"test", "rstest"}
+
+
+def _test_attribute(attributes: List[Attribute]) -> Optional[str]:
+    """The name of the first test attribute among `attributes`, if any."""
+    for attribute in attributes:
+        if attribute.name in _TEST_ATTRIBUTES or attribute.name.endswith("::test"):
+            return attribute.name
+    return None
+
+
+def _is_cfg_test(attribute: Attribute) -> bool:
+    return (
+        attribute.name == "cfg"
+        and "".join((attribute.args_raw or "").split()) == "(test)"
+    )
+
+
+def _collect(
+    symbols: List[Symbol], prefix: str, in_test_module: bool, tests: List[TestItem]
+) -> None:
+    for symbol in symbols:
+        path = f"{prefix}{symbol.name}"
+        # `#[cfg(test)] mod tests;` is a test module too, in a file of its own
+        if symbol.kind in (SymbolKind.MODULE, SymbolKind.MODULE_DECL):
+            is_test_module = in_test_module or any(
+                _is_cfg_test(attribute) for attribute in symbol.attributes
+            )
+            if is_test_module:
+                tests.append(
+                    TestItem(SymbolKind.MODULE, symbol.name, path, symbol.span)
+                )
+            _collect(symbol.children, f"{path}::", is_test_module, tests)
+        elif symbol.kind == SymbolKind.FUNCTION:
+            attribute = _test_attribute(symbol.attributes)
+            if attribute is not None:
+                tests.append(
+                    TestItem(
+                        SymbolKind.FUNCTION,
+                        symbol.name,
+                        path,
+                        symbol.span,
+                        attribute=attribute,
+                        is_async=symbol.is_async,
+                        ignored=any(a.name == "ignore" for a in symbol.attributes),
+                    )
+                )
+
+
+def find_tests(
+    source: str,
+    language: Union[Language, str] = Language.RUST,
+    columns: Union[ColumnUnit, str] = ColumnUnit.CHARACTERS,
+) -> List[TestItem]:
+    """
+    The test functions and test modules of a source, in source order.
+
+    Args:
+        source: The source code
+        language: A Language, or its value (`"rust"`)
+        columns: What the columns of the spans count, as for parse_symbols()
+
+    Returns:
+        A TestItem for each function with a test attribute and each module
+        gated by `#[cfg(test)]` or nested in such a module. Tests are found
+        also in sources with parse issues, as far as their symbols are.
+
+    Raises:
+        ValueError: If the language or column unit is not supported.
+    """
+    language = Language(language)
+    columns = ColumnUnit(columns)
+    if language != Language.RUST:
+        raise ValueError(f"Unsupported language: {language.value}")
+    tests: List[TestItem] = []
+    _collect(parse_rust(source, columns=columns).symbols, "", False, tests)
+    return tests
@@ -0,0 +1,530 @@
+"""
+Data model of the symbol-extraction API: languages, symbol kinds, spans,
+symbols, parse issues and the result of a parse.
+"""
+
+from dataclasses import dataclass, field
+from enum import Enum
+from pathlib import PurePath
+from typing import Any, Dict, Iterator, List, Optional
+
+
+class Language(Enum):
+    """Languages parse_symbols() understands."""
+
+    RUST = "rust"
+
+    @classmethod
+    def for_path(cls, path: str) -> Optional["Language"]:
+        """The language of a file, by its extension, or None if unsupported."""
+        return _EXTENSIONS.get(PurePath(path).suffix.lower())
+
+
+_EXTENSIONS = {".rs": Language.RUST}
+
+
+class SymbolKind(Enum):
+    """Kinds of symbols that are extracted."""
+
+    FUNCTION = "function"
+    STRUCT = "struct"
+    UNION = "union"
+    ENUM = "enum"
+    TRAIT = "trait"
+    IMPL = "impl"
+    MODULE = "module"
+    # `mod utils;`, a module whose items are in a file of its own
+    MODULE_DECL = "module_decl"
+    CONST = "const"
+    STATIC = "static"
+    TYPE_ALIAS = "type_alias"
+    # A macro_rules! definition
+    MACRO = "macro"
+    # `extern "C" { ... }`, whose foreign functions and statics are its children
+    EXTERN_BLOCK = "extern_block"
+    # A variant of an enum, and a field of an enum variant or struct
+    ENUM_VARIANT = "enum_variant"
+    FIELD = "field"
+
+
+class Shape(Enum):
+    """How the fields of an enum variant or struct are declared."""
+
+    # `Empty`, `struct Marker;`, without fields
+    UNIT = "unit"
+    # `Text(String)`, `struct Color(u8, u8, u8);`, with fields named by index
+    TUPLE = "tuple"
+    # `Click { x: i64, y: i64 }`
+    STRUCT = "struct"
+
+
+class IssueKind(Enum):
+    """Kinds of problems found in the source while parsing it."""
+
+    # An opening (, [ or { without its closing delimiter
+    UNCLOSED_DELIMITER = "unclosed_delimiter"
+    # A closing delimiter without a matching opening one
+    UNEXPECTED_DELIMITER = "unexpected_delimiter"
+    UNTERMINATED_STRING = "unterminated_string"
+    UNTERMINATED_CHAR = "unterminated_char"
+    UNTERMINATED_COMMENT = "unterminated_comment"
+    # Tokens where an item was expected, e.g. a statement at module level
+    UNEXPECTED_TOKEN = "unexpected_token"
+    # An item keyword not followed by a usable name
+    EXPECTED_NAME = "expected_name"
+    # An item whose header is not followed by its body or `;`
+    INCOMPLETE_ITEM = "incomplete_item"
+    # Bounds ending in `+`, or a where predicate without bounds (`T: Clone +`)
+    INCOMPLETE_BOUNDS = "incomplete_bounds"
+
+
+class ColumnUnit(Enum):
+    """What the columns of spans count."""
+
+    # Characters (code points): `é` and `🦀` are one column each
+    CHARACTERS = "characters"
+    # UTF-16 code units, as LSP positions count by default: `🦀` is two
+    UTF16 = "utf16"
+
+
+@dataclass(frozen=True)
+class Span:
+    """
+    A range of the source. Byte offsets index the UTF-8 encoded source; lines
+    and columns are 1-based, columns counting characters, or UTF-16 code units
+    if parsed with ColumnUnit.UTF16. Lines end at `\n`, so a CRLF source has
+    the same lines as its LF version. The end is exclusive: end_byte is the
+    offset just past the range, and (end_line, end_col) the position just past
+    its last character.
+    """
+
+    start_byte: int
+    end_byte: int
+    start_line: int
+    start_col: int
+    end_line: int
+    end_col: int
+
+    def to_dict(self) -> Dict[str, int]:
+        return {
+            "start_byte": self.start_byte,
+            "end_byte": self.end_byte,
+            "start_line": self.start_line,
+            "start_col": self.start_col,
+            "end_line": self.end_line,
+            "end_col": self.end_col,
+        }
+
+
+@dataclass
+class Attribute:
+    """
+    An attribute, outer (`#[...]`) or inner (`#![...]`). name is its path with
+    whitespace removed (`derive`, `tokio::test`); args_raw is the source after
+    the path as written, with its delimiters or `=` (`(Debug, Clone)`,
+    `= "text"`), or None for a bare attribute such as `#[test]`. The span runs
+    from the `#` to the closing `]`.
+    """
+
+    name: str
+    args_raw: Optional[str]
+    span: Span
+    inner: bool = False
+
+    def to_dict(self) -> Dict[str, Any]:
+        return {
+            "name": self.name,
+            "args_raw": self.args_raw,
+            "inner": self.inner,
+            "span": self.span.to_dict(),
+        }
+
+
+@dataclass
+class Import:
+    """
+    A path a `use` declaration imports, one per name of its groups:
+    `use std::{fs, io::{self, Read}};` imports `std::fs`, `std::io` and
+    `std::io::Read`. path_segments are the names of the path (`self` in a group
+    standing for the path before it), with an empty first segment for a path
+    starting with `::`; alias is the name after `as`, `_` included. A glob
+    import (`std::cmp::*`) has the path of the module. is_pub is set for
+    re-exports, `pub use` with any visibility, and the span is that of the
+    whole declaration.
+    """
+
+    path_segments: List[str]
+    alias: Optional[str]
+    is_glob: bool
+    is_pub: bool
+    span: Span
+
+    @property
+    def path(self) -> str:
+        """The path as written: `std::io::Read`, `std::cmp::*`."""
+        return "::".join(self.path_segments + (["*"] if self.is_glob else []))
+
+    def to_dict(self) -> Dict[str, Any]:
+        return {
+            "path_segments": list(self.path_segments),
+            "alias": self.alias,
+            "is_glob": self.is_glob,
+            "is_pub": self.is_pub,
+            "span": self.span.to_dict(),
+        }
+
+
+class GenericKind(Enum):
+    """Kinds of generic parameters."""
+
+    LIFETIME = "lifetime"
+    TYPE = "type"
+    CONST = "const"
+
+
+@dataclass
+class GenericParam:
+    """
+    A generic parameter: `'a: 'b`, `T: Clone = String`, `const N: usize = 3`.
+    name is the lifetime with its `'`, or the identifier; bounds are the bounds
+    after the `:` of a lifetime or type parameter, const_type the type of a
+    const parameter and default the value after `=`, each as written with
+    whitespace collapsed, or None.
+    """
+
+    kind: GenericKind
+    name: str
+    bounds: Optional[str] = None
+    const_type: Optional[str] = None
+    default: Optional[str] = None
+
+    def to_dict(self) -> Dict[str, Any]:
+        return {
+            "kind": self.kind.value,
+            "name": self.name,
+            "bounds": self.bounds,
+            "const_type": self.const_type,
+            "default": self.default,
+        }
+
+
+@dataclass
+class Param:
+    """
+    A parameter of a function other than its receiver. pattern (`x`,
+    `mut buf`, `(a, b)`, `_`) and param_type are as written, whitespace
+    collapsed; param_type is None if it is missing.
+    """
+
+    pattern: str
+    param_type: Optional[str]
+    span: Span
+
+    def to_dict(self) -> Dict[str, Any]:
+        return {
+            "pattern": self.pattern,
+            "param_type": self.param_type,
+            "span": self.span.to_dict(),
+        }
+
+
+@dataclass
+class WherePredicate:
+    """
+    A predicate of a where clause: the bounded type or lifetime (`T`, `'a`,
+    `for<'b> F`) and its bounds (`Clone + Debug`), as written with whitespace
+    collapsed, without a trailing `+`; bounds are None if missing.
+    """
+
+    bounded: str
+    bounds: Optional[str]
+
+    def to_dict(self) -> Dict[str, Any]:
+        return {"bounded": self.bounded, "bounds": self.bounds}
+
+
+@dataclass
+class FnSignature:
+    """
+    The signature of a function. abi is the ABI of an `extern` qualifier
+    without quotes (`C`, `system`), `C` for a bare `extern` as in Rust, or None.
+    generics are the parameters of its generic parameter list. receiver is
+    the `self` parameter as written, whitespace collapsed (`&self`,
+    `&'a mut self`, `mut self`, `self: Pin<&mut Self>`), or None, and params
+    are the other parameters. return_type is the type after `->`, or None.
+    where_clause is the clause as written from `where` up to the body or `;`,
+    and where_predicates its predicates.
+
+    A signature whose parameter list is not closed ends its parameters at a
+    `->`, `{` or `;`; what is missing is reported as issues of the parse.
+    """
+
+    is_async: bool = False
+    is_unsafe: bool = False
+    is_const: bool = False
+    abi: Optional[str] = None
+    generics: List[GenericParam] = field(default_factory=list)
+    receiver: Optional[str] = None
+    params: List[Param] = field(default_factory=list)
+    return_type: Optional[str] = None
+    where_clause: Optional[str] = None
+    where_predicates: List[WherePredicate] = field(default_factory=list)
+
+    def to_dict(self) -> Dict[str, Any]:
+        return {
+            "is_async": self.is_async,
+            "is_unsafe": self.is_unsafe,
+            "is_const": self.is_const,
+            "abi": self.abi,
+            "generics": [param.to_dict() for param in self.generics],
+            "receiver": self.receiver,
+            "params": [param.to_dict() for param in self.params],
+            "return_type": self.return_type,
+            "where_clause": self.where_clause,
+            "where_predicates": [p.to_dict() for p in self.where_predicates],
+        }
+
+
+# Kinds of symbols that declare generic parameters, after their name or `impl`
+_GENERIC_KINDS = {
+    SymbolKind.STRUCT,
+    SymbolKind.UNION,
+    SymbolKind.ENUM,
+    SymbolKind.TRAIT,
+    SymbolKind.TYPE_ALIAS,
+    SymbolKind.FUNCTION,
+    SymbolKind.IMPL,
+}
+
+
+# Symbols with fields, which have a shape
+_SHAPED_KINDS = {SymbolKind.ENUM_VARIANT, SymbolKind.STRUCT, SymbolKind.UNION}
+
+
+@dataclass
+class Symbol:
+    """
+    An item of the source. The span covers the item from its first attribute or
+    visibility modifier to its closing `}` or `;`; doc comments are not part of
+    it. doc_comment is the text of the outer doc comments before the item, then
+    of the inner ones in the body of a module, trait or impl, without markers
+    or common indentation. Visibility is the modifier as written (`pub`,
+    `pub(crate)`), or None for private items. Methods and associated items are
+    children of their impl or trait, the items of an inline module children
+    of the module, and modules declared in the body of a function children of
+    the function. `mod utils;` is a MODULE_DECL, without children.
+
+    qualified_name is the name prefixed with those of the symbols it is in,
+    joined by `::` (`visualization::plot_data`, `Shape::Circle::radius`). An
+    impl stands for its type (`geometry::Point`), or for a trait impl for the
+    type as the trait (`<geometry::Point as Display>`), so its methods are
+    `geometry::Point::new` and `<geometry::Point as Display>::fmt`. Functions
+    qualify the modules declared in them, though Rust paths cannot name those.
+
+    An impl is named by its header, `Point<T>` or `Display for Point<T>`;
+    impl_type is the implemented type without generic arguments (`Point`) and
+    impl_trait the trait as written (`Display`), None for inherent impls. Both
+    are None for other symbols.
+
+    attributes are the outer attributes of the item, then for a module, trait
+    or impl the inner ones in its body. derives lists the traits of its
+    `#[derive(...)]` attributes as written, whitespace collapsed (`Debug`,
+    `serde::Serialize`).
+
+    is_async is whether a function is declared `async fn`; it is False for
+    other symbols. signature is the FnSignature of a function, None for other
+    symbols.
+
+    The variants of an enum are its children, with the shape of their fields,
+    and the fields of a variant or struct its children in turn: named by their
+    name, or by their index (`0`, `1`) in a tuple variant or struct, with
+    field_type the type as written, whitespace collapsed (None if it is
+    missing). A discriminant (`= 1`) is part of the span of its variant. shape
+    is None for symbols other than variants, structs and unions, field_type for
+    symbols other than fields.
+
+    generics is the generic parameter list after the name of a struct, union,
+    enum, trait, type alias or function, or after the `impl` of an impl, as
+    written with its `<>` (`<'a, T, S = Vec<T>>`), or None if it has none;
+    generic_params are its parameters. where_clause is the where clause of
+    these symbols as written, from `where` up to the body or `;`, and
+    where_predicates its predicates. A function has the same parameters and
+    predicates in its signature.
+
+    has_default_body is whether a function of a trait has a body (a default
+    implementation) rather than ending at `;`; it is None for other symbols
+    and for trait functions missing both. declared_type is the type of a const
+    or static, and the type a type alias stands for (the default of an
+    associated type); default_value is the value of a const or static. bounds
+    are the bounds of an associated type (`Clone + Debug`). All are as written,
+    whitespace collapsed, and None if missing. is_mutable is whether a static
+    is `static mut`, None for other symbols.
+
+    An extern block is named by its header (`extern "C"`) and has the ABI in
+    abi (`C` for a bare `extern`, None for other symbols); its foreign
+    functions, whose signatures carry the same ABI, and statics are its
+    children. A union has its fields as children, like a struct.
+
+    body_span is the span of the body of a function, module, trait, impl,
+    extern block, enum, struct or union from its `{` to its `}`, and of a
+    macro_rules! definition from its opening to its closing delimiter; an
+    unclosed body ends where the item does. It is None for items without a
+    body, such as `mod utils;`, tuple structs and trait functions ending at `;`.
+    """
+
+    kind: SymbolKind
+    name: str
+    visibility: Optional[str]
+    span: Span
+    doc_comment: Optional[str] = None
+    children: List["Symbol"] = field(default_factory=list)
+    qualified_name: Optional[str] = None
+    impl_type: Optional[str] = None
+    impl_trait: Optional[str] = None
+    attributes: List[Attribute] = field(default_factory=list)
+    derives: List[str] = field(default_factory=list)
+    is_async: bool = False
+    shape: Optional[Shape] = None
+    field_type: Optional[str] = None
+    generics: Optional[str] = None
+    generic_params: List[GenericParam] = field(default_factory=list)
+    where_clause: Optional[str] = None
+    where_predicates: List[WherePredicate] = field(default_factory=list)
+    has_default_body: Optional[bool] = None
+    declared_type: Optional[str] = None
+    default_value: Optional[str] = None
+    bounds: Optional[str] = None
+    signature: Optional[FnSignature] = None
+    is_mutable: Optional[bool] = None
+    abi: Optional[str] = None
+    body_span: Optional[Span] = None
+
+    def walk(self) -> Iterator["Symbol"]:
+        """This symbol and all its descendants, depth first in source order."""
+        yield self
+        for child in self.children:
+            yield from child.walk()
+
+    def to_dict(self) -> Dict[str, Any]:
+        result = {
+            "kind": self.kind.value,
+            "name": self.name,
+            "qualified_name": self.qualified_name,
+            "visibility": self.visibility,
+            "span": self.span.to_dict(),
+            "body_span": self.body_span.to_dict() if self.body_span else None,
+            "doc_comment": self.doc_comment,
+            "attributes": [attribute.to_dict() for attribute in self.attributes],
+            "derives": list(self.derives),
+            "children": [child.to_dict() for child in self.children],
+        }
+        if self.kind in _GENERIC_KINDS:
+            result["generics"] = self.generics
+            result["generic_params"] = [p.to_dict() for p in self.generic_params]
+            result["where_clause"] = self.where_clause
+            result["where_predicates"] = [
+                p.to_dict() for p in self.where_predicates
+            ]
+        if self.kind == SymbolKind.FUNCTION:
+            result["is_async"] = self.is_async
+            result["has_default_body"] = self.has_default_body
+            result["signature"] = self.signature.to_dict() if self.signature else None
+        if self.kind in (SymbolKind.CONST, SymbolKind.STATIC):
+            result["declared_type"] = self.declared_type
+            result["default_value"] = self.default_value
+        if self.kind == SymbolKind.STATIC:
+            result["is_mutable"] = self.is_mutable
+        if self.kind == SymbolKind.EXTERN_BLOCK:
+            result["abi"] = self.abi
+        if self.kind == SymbolKind.TYPE_ALIAS:
+            result["declared_type"] = self.declared_type
+            result["bounds"] = self.bounds
+        if self.kind in _SHAPED_KINDS:
+            result["shape"] = self.shape.value if self.shape else None
+        if self.kind == SymbolKind.FIELD:
+            result["field_type"] = self.field_type
+        if self.kind == SymbolKind.IMPL:
+            result["impl_type"] = self.impl_type
+            result["impl_trait"] = self.impl_trait
+        return result
+
+
+@dataclass
+class TestItem:
+    """
+    A test found by find_tests(): a function with a test attribute (`#[test]`,
+    `#[tokio::test]`, `#[rstest]`, ...), or a module gated by `#[cfg(test)]` or
+    nested in one. path is its name qualified by the modules it is in
+    (`tests::parsing::empty_input`), as `cargo test` filters take it. attribute
+    is the test attribute of a function as written (`tokio::test`), None for a
+    module; is_async and ignored (`#[ignore]`) are always False for modules.
+    """
+
+    kind: SymbolKind
+    name: str
+    path: str
+    span: Span
+    attribute: Optional[str] = None
+    is_async: bool = False
+    ignored: bool = False
+
+    def to_dict(self) -> Dict[str, Any]:
+        return {
+            "kind": self.kind.value,
+            "name": self.name,
+            "path": self.path,
+            "span": self.span.to_dict(),
+            "attribute": self.attribute,
+            "is_async": self.is_async,
+            "ignored": self.ignored,
+        }
+
+
+@dataclass
+class ParseIssue:
+    """A problem in the source; parsing continues after it."""
+
+    kind: IssueKind
+    message: str
+    span: Span
+
+    def to_dict(self) -> Dict[str, Any]:
+        return {
+            "kind": self.kind.value,
+            "message": self.message,
+            "span": self.span.to_dict(),
+        }
+
+
+@dataclass
+class ParseResult:
+    """
+    The top-level symbols of a source, and the issues found in it in source
+    order. Symbols are extracted also from sources with issues; what could not
+    be parsed is left out. doc_comment is the text of the inner doc comments of
+    the source itself (`//!` in a crate root), or None, and attributes are its
+    inner attributes (`#![allow(dead_code)]`). imports are the paths of the
+    `use` declarations of the source and of its modules, in source order.
+    """
+
+    language: Language
+    symbols: List[Symbol] = field(default_factory=list)
+    issues: List[ParseIssue] = field(default_factory=list)
+    doc_comment: Optional[str] = None
+    attributes: List[Attribute] = field(default_factory=list)
+    imports: List[Import] = field(default_factory=list)
+
+    def walk(self) -> Iterator[Symbol]:
+        """All symbols, depth first in source order."""
+        for symbol in self.symbols:
+            yield from symbol.walk()
+
+    def to_dict(self) -> Dict[str, Any]:
+        return {
+            "language": self.language.value,
+            "doc_comment": self.doc_comment,
+            "attributes": [attribute.to_dict() for attribute in self.attributes],
+            "imports": [item.to_dict() for item in self.imports],
+            "symbols": [symbol.to_dict() for symbol in self.symbols],
+            "issues": [issue.to_dict() for issue in self.issues],
+        }
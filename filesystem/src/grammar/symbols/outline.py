@@ -0,0 +1,257 @@
+"""
+Outlines built on the symbol tree: render_outline() renders a source as a
+skeleton of its declarations, with the bodies of functions (and of items
+deeper than a maximum depth) elided to `{ ... }`, for tools that show the
+structure of a file without its code.
+"""
+
+import re
+from dataclasses import dataclass, field
+from typing import Any, Dict, List, Optional, Union
+
+from .model import Language, ParseResult, Span, Symbol, SymbolKind
+from .rust_parser import parse_rust
+
+# Symbols whose children are rendered in their body, up to the maximum depth
+_CONTAINERS = {
+    SymbolKind.MODULE,
+    SymbolKind.TRAIT,
+    SymbolKind.IMPL,
+    SymbolKind.EXTERN_BLOCK,
+    SymbolKind.ENUM,
+    SymbolKind.STRUCT,
+    SymbolKind.UNION,
+}
+# Children listed with a `,` after them rather than as items
+_LISTED = {SymbolKind.ENUM_VARIANT, SymbolKind.FIELD}
+_CLOSERS = {"{": "}", "(": ")", "[": "]"}
+_INDENT = "    "
+_BLANK_LINE = re.compile(r"\n[ \t\r]*\n")
+
+
+@dataclass
+class OutlineOptions:
+    """
+    What render_outline() keeps: doc comments and attributes (outer and inner
+    ones), and the symbols up to max_depth levels of nesting, 0 being the
+    top-level items. The bodies of the items at max_depth are elided like
+    those of functions; None renders all levels.
+    """
+
+    keep_doc_comments: bool = True
+    keep_attributes: bool = True
+    max_depth: Optional[int] = None
+
+
+@dataclass
+class ElidedBody:
+    """
+    A body render_outline() elided: the symbol it belongs to, the 1-based
+    line of the outline its `{ ... }` is on, and its span in the source, whose
+    byte offsets slice the UTF-8 encoded source.
+    """
+
+    qualified_name: str
+    kind: SymbolKind
+    line: int
+    span: Span
+
+    def to_dict(self) -> Dict[str, Any]:
+        return {
+            "qualified_name": self.qualified_name,
+            "kind": self.kind.value,
+            "line": self.line,
+            "span": self.span.to_dict(),
+        }
+
+
+@dataclass
+class Outline:
+    """The text of an outline, and the bodies elided from it in text order."""
+
+    text: str
+    bodies: List[ElidedBody] = field(default_factory=list)
+
+    def body_at(self, line: int) -> Optional[ElidedBody]:
+        """The body elided on a line of the outline, if any."""
+        return next((body for body in self.bodies if body.line == line), None)
+
+    def to_dict(self) -> Dict[str, Any]:
+        return {"text": self.text, "bodies": [b.to_dict() for b in self.bodies]}
+
+
+class _Renderer:
+    def __init__(self, source: str, result: ParseResult, options: OutlineOptions):
+        self.data = source.encode("utf-8")
+        self.result = result
+        self.options = options
+        self.lines: List[str] = []
+        self.bodies: List[ElidedBody] = []
+        # Each `use` declaration once, though it has an Import per path
+        spans = {item.span.start_byte: item.span for item in result.imports}
+        self.uses = sorted(spans.values(), key=lambda span: span.start_byte)
+
+    def render(self) -> Outline:
+        if self.options.keep_doc_comments and self.result.doc_comment:
+            self._doc_lines(self.result.doc_comment, "", "//!")
+        if self.options.keep_attributes:
+            for attribute in self.result.attributes:
+                self.lines.append(self._text(attribute.span))
+        if self.lines:
+            self.lines.append("")
+        self._items(self.result.symbols, 0, len(self.data), "", 0)
+        return Outline("\n".join(self.lines) + "\n", self.bodies)
+
+    # --- Source text ---
+
+    def _slice(self, start: int, end: int) -> str:
+        return self.data[start:end].decode("utf-8")
+
+    def _text(self, span: Span) -> str:
+        return self._slice(span.start_byte, span.end_byte)
+
+    def _line_indent(self, offset: int) -> Optional[str]:
+        """The indentation of the line at a byte offset, if only it precedes it."""
+        line_start = self.data.rfind(b"\n", 0, offset) + 1
+        before = self._slice(line_start, offset)
+        return None if before.strip() else before
+
+    def _start(self, symbol: Symbol) -> int:
+        """Where the symbol's text starts: at its attributes, or after them."""
+        outer = [a for a in symbol.attributes if not a.inner]
+        if self.options.keep_attributes or not outer:
+            return symbol.span.start_byte
+        end = outer[-1].span.end_byte
+        while end < symbol.span.end_byte and self.data[end : end + 1].isspace():
+            end += 1
+        return end
+
+    # --- Rendering ---
+
+    def _emit(self, text: str, indent: str) -> None:
+        """Adds text at an indentation; lines after the first are as written."""
+        lines = text.split("\n")
+        self.lines.append(indent + lines[0])
+        self.lines.extend(line.rstrip() for line in lines[1:])
+
+    def _doc_lines(self, doc: str, indent: str, marker: str) -> None:
+        for line in doc.split("\n"):
+            self.lines.append(f"{indent}{marker} {line}".rstrip())
+
+    def _items(
+        self, symbols: List[Symbol], start: int, end: int, indent: str, depth: int
+    ) -> None:
+        """The symbols, and the `use` declarations among them, from start to end."""
+        uses = [
+            span
+            for span in self.uses
+            if start <= span.start_byte < end
+            and not any(_contains(s.span, span.start_byte) for s in symbols)
+        ]
+        items: List[Union[Symbol, Span]] = sorted(symbols + uses, key=_start_byte)
+        previous_end: Optional[int] = None
+        for item in items:
+            span = item.span if isinstance(item, Symbol) else item
+            if previous_end is not None and _BLANK_LINE.search(
+                self._slice(previous_end, span.start_byte)
+            ):
+                self.lines.append("")
+            if isinstance(item, Symbol):
+                self._symbol(item, indent, depth)
+            else:
+                self._emit(self._text(item), self._indent(item.start_byte, indent))
+            previous_end = span.end_byte
+
+    def _indent(self, offset: int, fallback: str) -> str:
+        """The indentation of an item first on its line, else `fallback`."""
+        indent = self._line_indent(offset)
+        return fallback if indent is None else indent
+
+    def _symbol(self, symbol: Symbol, parent_indent: str, depth: int) -> None:
+        start = self._start(symbol)
+        indent = self._indent(symbol.span.start_byte, parent_indent)
+        if self.options.keep_doc_comments and symbol.doc_comment:
+            self._doc_lines(symbol.doc_comment, indent, "///")
+        body = symbol.body_span
+        if body is None or symbol.kind in _LISTED:
+            text = self._slice(start, symbol.span.end_byte)
+            self._emit(text + ("," if symbol.kind in _LISTED else ""), indent)
+            return
+        header = self._slice(start, body.start_byte).rstrip()
+        # A `{` first on its line stays there, as after a multi-line where clause
+        brace_indent = self._line_indent(body.start_byte)
+        separator = "\n" + brace_indent if brace_indent is not None else " "
+        max_depth = self.options.max_depth
+        if symbol.kind not in _CONTAINERS or (
+            max_depth is not None and depth >= max_depth
+        ):
+            self._elided(symbol, header + separator, indent)
+            return
+        child_indent = indent + _INDENT
+        inner = [a for a in symbol.attributes if a.inner]
+        if not symbol.children and not (inner and self.options.keep_attributes):
+            if not any(_contains(body, span.start_byte) for span in self.uses):
+                self._emit(header + separator + "{}", indent)
+                return
+        self._emit(header + separator + "{", indent)
+        if self.options.keep_attributes:
+            for attribute in inner:
+                self._emit(self._text(attribute.span), child_indent)
+        self._items(
+            symbol.children, body.start_byte, body.end_byte, child_indent, depth + 1
+        )
+        self.lines.append(indent + "}")
+
+    def _elided(self, symbol: Symbol, header: str, indent: str) -> None:
+        body = symbol.body_span
+        opener = self._slice(body.start_byte, body.start_byte + 1)
+        closer = _CLOSERS.get(opener, "}")
+        # What follows the body, as the `;` of macro_rules! m ( ... );
+        tail = self._slice(body.end_byte, symbol.span.end_byte)
+        self._emit(f"{header}{opener} ... {closer}{tail}", indent)
+        self.bodies.append(
+            ElidedBody(
+                symbol.qualified_name or symbol.name,
+                symbol.kind,
+                len(self.lines),
+                body,
+            )
+        )
+
+
+def _contains(span: Span, offset: int) -> bool:
+    return span.start_byte <= offset < span.end_byte
+
+
+def _start_byte(item: Union[Symbol, Span]) -> int:
+    return (item.span if isinstance(item, Symbol) else item).start_byte
+
+
+def render_outline(
+    source: str,
+    options: Optional[OutlineOptions] = None,
+    language: Union[Language, str] = Language.RUST,
+) -> Outline:
+    """
+    A skeleton of a source: its `use` declarations and the declarations of its
+    items as written, with doc comments, attributes and nesting as `options`
+    say, and the bodies of functions and macros elided to `{ ... }`.
+
+    Args:
+        source: The source code
+        options: What to keep; by default doc comments, attributes and all
+            levels of nesting
+        language: A Language, or its value (`"rust"`)
+
+    Returns:
+        The outline's text, and the span in the source of each body elided
+        from it, by the line of the outline it is on. Sources with parse
+        issues are outlined as far as their symbols are.
+
+    Raises:
+        ValueError: If the language is not supported.
+    """
+    language = Language(language)
+    if language != Language.RUST:
+        raise ValueError(f"Unsupported language: {language.value}")
+    return _Renderer(source, parse_rust(source), options or OutlineOptions()).render()
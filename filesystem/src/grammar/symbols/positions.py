@@ -0,0 +1,76 @@
+"""
+Conversion of character offsets into a source string to spans.
+"""
+
+import re
+from bisect import bisect_right
+from itertools import accumulate
+from typing import List, Optional, Tuple
+
+from .model import ColumnUnit, Span
+
+
+def _utf8_length(char: str) -> int:
+    code = ord(char)
+    if code < 0x80:
+        return 1
+    if code < 0x800:
+        return 2
+    if code < 0x10000:
+        return 3
+    return 4
+
+
+class LineIndex:
+    """
+    Maps character offsets into a source to byte offsets, lines and columns.
+    line_col() counts characters; the columns of spans count `columns`.
+    """
+
+    def __init__(self, source: str, columns: ColumnUnit = ColumnUnit.CHARACTERS):
+        self.source = source
+        # A \r\n ends a line at its \n like a plain \n does
+        self.line_starts: List[int] = [0] + [
+            m.end() for m in re.finditer("\n", source)
+        ]
+        self._byte_offsets: Optional[List[int]] = None
+        if not source.isascii():
+            self._byte_offsets = list(
+                accumulate((_utf8_length(c) for c in source), initial=0)
+            )
+        # Only characters outside the BMP take two UTF-16 code units
+        self._utf16_offsets: Optional[List[int]] = None
+        if columns == ColumnUnit.UTF16 and any(ord(c) > 0xFFFF for c in source):
+            widths = (2 if ord(c) > 0xFFFF else 1 for c in source)
+            self._utf16_offsets = list(accumulate(widths, initial=0))
+
+    def byte_offset(self, offset: int) -> int:
+        if self._byte_offsets is None:
+            return offset
+        return self._byte_offsets[offset]
+
+    def line_col(self, offset: int) -> Tuple[int, int]:
+        """The 1-based line and column of a character offset."""
+        line = bisect_right(self.line_starts, offset)
+        return line, offset - self.line_starts[line - 1] + 1
+
+    def _span_position(self, offset: int) -> Tuple[int, int]:
+        line, column = self.line_col(offset)
+        if self._utf16_offsets is not None:
+            line_start = self.line_starts[line - 1]
+            units = self._utf16_offsets
+            column = units[offset] - units[line_start] + 1
+        return line, column
+
+    def span(self, start: int, end: int) -> Span:
+        """The span of the characters from `start` up to, not including, `end`."""
+        start_line, start_col = self._span_position(start)
+        end_line, end_col = self._span_position(end)
+        return Span(
+            self.byte_offset(start),
+            self.byte_offset(end),
+            start_line,
+            start_col,
+            end_line,
+            end_col,
+        )
@@ -0,0 +1,245 @@
+"""
+Lexer for Rust source, as far as symbol extraction needs it: identifiers,
+lifetimes, literals, single-character punctuation and doc comments. Plain
+comments and whitespace are dropped. Lexing never fails; problems such as an
+unterminated string are reported as issues.
+
+A string, char literal or block comment that is never terminated ends at the
+end of the line it starts on, and lexing goes on with the next line, so the
+rest of the file is not taken for its content. An unterminated raw string,
+whose content may be anything, ends with the file.
+"""
+
+import re
+from dataclasses import dataclass
+from enum import Enum
+from typing import List, Tuple
+
+from .model import IssueKind, ParseIssue
+from .positions import LineIndex
+
+
+class TokenKind(Enum):
+    IDENT = "ident"
+    LIFETIME = "lifetime"
+    # String, char and number literals
+    LITERAL = "literal"
+    PUNCT = "punct"
+    # `///` and `/** */`, documenting the item after them
+    OUTER_DOC = "outer_doc"
+    # `//!` and `/*! */`, documenting the item they are in
+    INNER_DOC = "inner_doc"
+
+
+@dataclass
+class Token:
+    kind: TokenKind
+    text: str
+    # Character offsets into the source
+    start: int
+    end: int
+
+    def is_punct(self, chars: str) -> bool:
+        """Whether the token is one of the punctuation characters."""
+        return self.kind == TokenKind.PUNCT and self.text in chars
+
+    def is_ident(self, *names: str) -> bool:
+        """Whether the token is an identifier, one of `names` if given."""
+        return self.kind == TokenKind.IDENT and (not names or self.text in names)
+
+
+_WHITESPACE = re.compile(r"\s+")
+# Letters, digits and underscores, not starting with a digit
+_IDENT = re.compile(r"[^\W\d]\w*")
+# A dot belongs to a number only if a digit follows, so `0..n` is a range
+_NUMBER = re.compile(r"\d\w*(?:\.\d\w*)?")
+_CHAR_ESCAPE = re.compile(r"'\\(?:x[0-9a-fA-F]{2}|u\{[0-9a-fA-F_]*\}|.)'")
+# Prefixes of string and byte/char literals: b"..", c"..", b'.'
+_STRING_PREFIXES = {"b", "c"}
+# Prefixes of raw strings, r"..", r#".."#, br".." and cr"..", and their opening
+# hashes and quote
+_RAW_STRING_PREFIXES = {"r", "br", "cr"}
+_RAW_STRING_START = re.compile(r'#*"')
+# Raw identifiers, r#type, are never keywords
+_RAW_IDENT = re.compile(r"r#[^\W\d]\w*")
+
+
+class RustLexer:
+    def __init__(self, source: str, index: LineIndex):
+        self.source = source
+        self.index = index
+        self.tokens: List[Token] = []
+        self.issues: List[ParseIssue] = []
+
+    def tokenize(self) -> Tuple[List[Token], List[ParseIssue]]:
+        source = self.source
+        pos = 0
+        while pos < len(source):
+            char = source[pos]
+            if char.isspace():
+                pos = _WHITESPACE.match(source, pos).end()
+            elif source.startswith("//", pos):
+                pos = self._line_comment(pos)
+            elif source.startswith("/*", pos):
+                pos = self._block_comment(pos)
+            elif char == '"':
+                pos = self._string(pos, pos)
+            elif char == "'":
+                pos = self._quote(pos)
+            elif char.isdigit():
+                end = _NUMBER.match(source, pos).end()
+                self._add(TokenKind.LITERAL, pos, end)
+                pos = end
+            elif _IDENT.match(source, pos):
+                end = _IDENT.match(source, pos).end()
+                prefix = source[pos:end]
+                raw_ident = _RAW_IDENT.match(source, pos)
+                if prefix in _STRING_PREFIXES and source.startswith('"', end):
+                    pos = self._string(pos, end)
+                elif prefix in _RAW_STRING_PREFIXES and _RAW_STRING_START.match(
+                    source, end
+                ):
+                    pos = self._raw_string(pos, end)
+                elif prefix == "r" and raw_ident:
+                    pos = self._add(TokenKind.IDENT, pos, raw_ident.end())
+                elif prefix == "b" and source.startswith("'", end):
+                    pos = self._char(pos, end) or self._unterminated_char(pos)
+                else:
+                    pos = self._add(TokenKind.IDENT, pos, end)
+            else:
+                pos = self._add(TokenKind.PUNCT, pos, pos + 1)
+        return self.tokens, self.issues
+
+    def _add(self, kind: TokenKind, start: int, end: int) -> int:
+        self.tokens.append(Token(kind, self.source[start:end], start, end))
+        return end
+
+    def _issue(self, kind: IssueKind, message: str, start: int, end: int) -> None:
+        self.issues.append(ParseIssue(kind, message, self.index.span(start, end)))
+
+    def _line_end(self, pos: int) -> int:
+        """The offset of the end of the line `pos` is on, before its newline."""
+        end = self.source.find("\n", pos)
+        if end == -1:
+            return len(self.source)
+        return end - 1 if self.source[end - 1] == "\r" else end
+
+    def _unterminated(
+        self, kind: IssueKind, message: str, token: TokenKind, start: int
+    ) -> int:
+        """Reports the literal or comment at `start` and ends it at the line end."""
+        end = self._line_end(start)
+        self._issue(kind, message, start, end)
+        return self._add(token, start, end)
+
+    def _line_comment(self, pos: int) -> int:
+        end = self._line_end(pos)
+        text = self.source[pos:end]
+        if text.startswith("///") and not text.startswith("////"):
+            self._add(TokenKind.OUTER_DOC, pos, pos + len(text))
+        elif text.startswith("//!"):
+            self._add(TokenKind.INNER_DOC, pos, pos + len(text))
+        return end
+
+    def _block_comment(self, pos: int) -> int:
+        # Block comments nest
+        depth = 0
+        scan = pos
+        while scan < len(self.source):
+            if self.source.startswith("/*", scan):
+                depth += 1
+                scan += 2
+            elif self.source.startswith("*/", scan):
+                depth -= 1
+                scan += 2
+                if depth == 0:
+                    break
+            else:
+                scan += 1
+        else:
+            scan = self._line_end(pos)
+            self._issue(
+                IssueKind.UNTERMINATED_COMMENT,
+                "Block comment is not terminated",
+                pos,
+                scan,
+            )
+        text = self.source[pos:scan]
+        # `/**/` and `/*** ... */` are no doc comments
+        if text.startswith("/**") and not text.startswith(("/**/", "/***")):
+            self._add(TokenKind.OUTER_DOC, pos, scan)
+        elif text.startswith("/*!"):
+            self._add(TokenKind.INNER_DOC, pos, scan)
+        return scan
+
+    def _string(self, start: int, quote: int) -> int:
+        """A string literal starting at `start`, with its opening quote at `quote`."""
+        scan = quote + 1
+        while scan < len(self.source):
+            char = self.source[scan]
+            if char == "\\":
+                scan += 2
+            elif char == '"':
+                return self._add(TokenKind.LITERAL, start, scan + 1)
+            else:
+                scan += 1
+        return self._unterminated(
+            IssueKind.UNTERMINATED_STRING,
+            "String literal is not terminated",
+            TokenKind.LITERAL,
+            start,
+        )
+
+    def _raw_string(self, start: int, hashes: int) -> int:
+        """
+        A raw string starting at `start`, with its opening hashes at `hashes`:
+        it ends at the first quote followed by as many hashes.
+        """
+        opening = _RAW_STRING_START.match(self.source, hashes)
+        closing = '"' + "#" * (opening.end() - 1 - hashes)
+        end = self.source.find(closing, opening.end())
+        if end != -1:
+            return self._add(TokenKind.LITERAL, start, end + len(closing))
+        self._issue(
+            IssueKind.UNTERMINATED_STRING,
+            "Raw string literal is not terminated",
+            start,
+            self._line_end(start),
+        )
+        return self._add(TokenKind.LITERAL, start, len(self.source))
+
+    def _char(self, start: int, quote: int) -> int:
+        """
+        A char literal starting at `start`, with its opening quote at `quote`;
+        0 if there is none.
+        """
+        escaped = _CHAR_ESCAPE.match(self.source, quote)
+        if escaped:
+            return self._add(TokenKind.LITERAL, start, escaped.end())
+        if self.source.startswith("'", quote + 2) and self.source[quote + 1] != "\n":
+            return self._add(TokenKind.LITERAL, start, quote + 3)
+        return 0
+
+    def _unterminated_char(self, start: int) -> int:
+        return self._unterminated(
+            IssueKind.UNTERMINATED_CHAR,
+            "Char literal is not terminated",
+            TokenKind.LITERAL,
+            start,
+        )
+
+    def _quote(self, pos: int) -> int:
+        # 'x' and '\n' are chars, 'a in &'a str a lifetime or a loop label
+        end = self._char(pos, pos)
+        if end:
+            return end
+        name = _IDENT.match(self.source, pos + 1)
+        if name:
+            return self._add(TokenKind.LIFETIME, pos, name.end())
+        # Neither a char nor a lifetime, e.g. '\n or '( without the closing quote
+        return self._unterminated_char(pos)
+
+
+def tokenize(source: str, index: LineIndex) -> Tuple[List[Token], List[ParseIssue]]:
+    """The tokens of a Rust source, and the issues found lexing it."""
+    return RustLexer(source, index).tokenize()
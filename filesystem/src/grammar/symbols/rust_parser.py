@@ -0,0 +1,1594 @@
+"""
+Extraction of the items of a Rust source as a symbol tree.
+
+The parser reads items only: the headers of functions, structs, enums and the
+like are skipped up to their body, and bodies other than those of modules,
+traits, impls and extern blocks are skipped as balanced delimiter groups,
+function bodies
+but for the modules declared at the start of a statement. Enum bodies are read
+as far as their variants and the fields of those, struct bodies as far as their
+fields, whose types are kept as text, as are generic parameters and the types,
+values and bounds of consts, statics and type aliases. Function headers are
+read into signatures, with parameter and return types as text. What is not an
+item where an item is expected is skipped up to the next item and reported as
+an issue, as are unbalanced delimiters.
+
+An item start (an item keyword, `pub`, an attribute or a doc comment) first on
+its line and indented no deeper than the innermost item still open is taken to
+start an item at that item's level or above: the groups and bodies still open
+before it are reported as unclosed and end there, so a missing `}` or `)` does
+not hide the items after it. At the top level that is an item start at the
+start of a line, unindented. Likewise the fields of a variant or struct that
+are not closed end at a name first on its line and indented no deeper than the
+variant or struct, which starts the next variant.
+
+Outer doc comments (`///`, `/** */`) belong to the item after them, also past
+attributes and blank lines; inner ones (`//!`, `/*! */`) to the enclosing
+module, trait or impl, or to the crate at the top level.
+"""
+
+from typing import List, Optional, Tuple
+
+from .model import (
+    Attribute,
+    ColumnUnit,
+    FnSignature,
+    GenericKind,
+    GenericParam,
+    Import,
+    IssueKind,
+    Language,
+    Param,
+    ParseIssue,
+    ParseResult,
+    Shape,
+    Symbol,
+    SymbolKind,
+    Span,
+    WherePredicate,
+)
+from .positions import LineIndex
+from .rust_lexer import Token, TokenKind, tokenize
+
+_ITEM_KEYWORDS = {
+    "fn": SymbolKind.FUNCTION,
+    "struct": SymbolKind.STRUCT,
+    "enum": SymbolKind.ENUM,
+    "trait": SymbolKind.TRAIT,
+    "impl": SymbolKind.IMPL,
+    "mod": SymbolKind.MODULE,
+    "const": SymbolKind.CONST,
+    "static": SymbolKind.STATIC,
+    "type": SymbolKind.TYPE_ALIAS,
+}
+
+# Items whose body holds items of their own (extern blocks too, see
+# _extern_block())
+_CONTAINERS = {SymbolKind.TRAIT, SymbolKind.IMPL, SymbolKind.MODULE}
+
+# Items that end at a `;` rather than at a body
+_DECLARATIONS = {SymbolKind.CONST, SymbolKind.STATIC, SymbolKind.TYPE_ALIAS}
+
+# Tokens an item can start with, where skipping stops after an unexpected token
+_ITEM_STARTS = set(_ITEM_KEYWORDS) | {
+    "pub",
+    "use",
+    "extern",
+    "async",
+    "unsafe",
+    "macro_rules",
+}
+
+# Keywords that cannot name an item
+_RESERVED = set(
+    """
+    as async await break const continue crate dyn else enum extern false fn for
+    if impl in let loop match mod move mut pub ref return self Self static
+    struct super trait true type unsafe use where while
+    """.split()
+)
+
+# A `|` where an expression starts opens the parameter list of a closure
+_CLOSING = {"(": ")", "[": "]", "{": "}", "|": "|"}
+
+# Keywords that start a statement. One first on its line, indented no deeper
+# than the line a `(`, `[` or closure parameter list was opened on, cannot be
+# inside it, and ends it (see _close_before_statement())
+_STATEMENT_STARTS = {"let", "if", "for", "while", "loop", "match", "return"}
+
+
+class RustSymbolParser:
+    def __init__(
+        self,
+        source: str,
+        include_nested: bool = False,
+        columns: ColumnUnit = ColumnUnit.CHARACTERS,
+    ):
+        self.source = source
+        # Whether function bodies yield the items declared in them, not only
+        # their modules
+        self.include_nested = include_nested
+        self.index = LineIndex(source, columns)
+        self.tokens, self.issues = tokenize(source, self.index)
+        self.pos = 0
+        # Indentation (1-based column) of the lines the open items start on
+        self.open_indents: List[int] = []
+        # Kinds of the open items, innermost last
+        self.open_kinds: List[SymbolKind] = []
+        self.imports: List[Import] = []
+
+    def parse(self) -> ParseResult:
+        symbols, inner_docs, inner_attributes = self._items(opener=None)
+        _qualify(symbols, "")
+        issues = sorted(self.issues, key=lambda issue: issue.span.start_byte)
+        return ParseResult(
+            Language.RUST,
+            symbols,
+            issues,
+            _doc_comment(inner_docs),
+            inner_attributes,
+            self.imports,
+        )
+
+    # --- Token access ---
+
+    def _peek(self, ahead: int = 0) -> Optional[Token]:
+        position = self.pos + ahead
+        return self.tokens[position] if position < len(self.tokens) else None
+
+    def _last(self) -> Token:
+        """The token consumed last."""
+        return self.tokens[self.pos - 1]
+
+    def _span(self, first: Token, last: Token) -> Span:
+        return self.index.span(first.start, last.end)
+
+    def _issue(self, kind: IssueKind, message: str, first: Token, last=None) -> None:
+        self.issues.append(ParseIssue(kind, message, self._span(first, last or first)))
+
+    def _line_column(self, token: Token) -> Optional[int]:
+        """The column of a token that is first on its line, else None."""
+        line, column = self.index.line_col(token.start)
+        line_start = self.index.line_starts[line - 1]
+        if self.source[line_start : token.start].strip():
+            return None
+        return column
+
+    def _indent(self, token: Token) -> int:
+        """The indentation of the token's line, as the column of its first token."""
+        line = self.index.line_col(token.start)[0]
+        before = self.source[self.index.line_starts[line - 1] : token.start]
+        return len(before) - len(before.lstrip()) + 1
+
+    def _ends_open_items(self, token: Token) -> bool:
+        """
+        Whether the token starts an item that cannot be inside the innermost
+        open item: it is first on its line, indented no deeper than that item.
+        """
+        column = self._line_column(token)
+        if column is None or column > (self.open_indents or [1])[-1]:
+            return False
+        return (
+            token.kind == TokenKind.OUTER_DOC
+            or token.is_punct("#")
+            or (token.is_ident() and token.text in _ITEM_STARTS)
+        )
+
+    # --- Items ---
+
+    def _items(
+        self, opener: Optional[Token]
+    ) -> Tuple[List[Symbol], List[Token], List[Attribute]]:
+        """
+        The items up to the `}` closing `opener`, which is left unconsumed, or
+        up to the end of the source if `opener` is None, and the inner doc
+        comments and inner attributes among them.
+        """
+        symbols: List[Symbol] = []
+        inner_docs: List[Token] = []
+        inner_attributes: List[Attribute] = []
+        docs: List[Token] = []
+        attributes: List[Attribute] = []
+        first_attribute: Optional[Token] = None
+        while self._peek() is not None:
+            token = self._peek()
+            if opener is not None and self._ends_open_items(token):
+                # Before its doc comments are taken, which belong to that item
+                break
+            elif token.kind == TokenKind.OUTER_DOC:
+                docs.append(token)
+                self.pos += 1
+            elif token.kind == TokenKind.INNER_DOC:
+                inner_docs.append(token)
+                self.pos += 1
+            elif token.is_punct("}") and opener is not None:
+                return symbols, inner_docs, inner_attributes
+            elif token.is_punct(")]}"):
+                self._issue(
+                    IssueKind.UNEXPECTED_DELIMITER,
+                    f"Unexpected closing delimiter `{token.text}`",
+                    token,
+                )
+                self.pos += 1
+            elif token.is_punct("#"):
+                following = self._peek(1)
+                inner = following is not None and following.is_punct("!")
+                attribute = self._attribute()
+                if inner:
+                    if attribute is not None:
+                        inner_attributes.append(attribute)
+                    continue
+                if attribute is not None:
+                    attributes.append(attribute)
+                if first_attribute is None:
+                    first_attribute = token
+            else:
+                symbol = self._item(first_attribute or token, docs, attributes)
+                if symbol is not None:
+                    symbols.append(symbol)
+                docs = []
+                attributes = []
+                first_attribute = None
+        if opener is not None:
+            self._issue(
+                IssueKind.UNCLOSED_DELIMITER,
+                f"Unclosed delimiter `{opener.text}`",
+                opener,
+            )
+        return symbols, inner_docs, inner_attributes
+
+    def _attribute(self) -> Optional[Attribute]:
+        """
+        The attribute at the current `#`, consumed with its `!` and `[...]`;
+        None if no `[` follows. Its arguments may span lines and nest groups.
+        """
+        start_token = self._peek()
+        self.pos += 1
+        inner = self._peek() is not None and self._peek().is_punct("!")
+        if inner:
+            self.pos += 1
+        if self._peek() is None or not self._peek().is_punct("["):
+            return None
+        start = self.pos + 1
+        self._skip_group()
+        closed = self.pos - 1 >= start and self._last().is_punct("]")
+        end = self.pos - 1 if closed else self.pos
+        # The path: names and the colons of `::` between them
+        path_end = start
+        while path_end < end and (
+            self.tokens[path_end].is_ident() or self.tokens[path_end].is_punct(":")
+        ):
+            path_end += 1
+        name = "".join(self._text(start, path_end).split())
+        args_raw = None
+        if path_end < end:
+            args_raw = self.source[
+                self.tokens[path_end].start : self.tokens[end - 1].end
+            ]
+        return Attribute(
+            name, args_raw, self._span(start_token, self._last()), inner=inner
+        )
+
+    def _item(
+        self, first: Token, docs: List[Token], attributes: List[Attribute]
+    ) -> Optional[Symbol]:
+        """
+        The item at the current token, which `first` (the item's first
+        attribute, or the current token) starts. Returns None for items that are
+        not extracted, such as `use` declarations and macro invocations, and for
+        tokens that are no item.
+        """
+        visibility = self._visibility()
+        qualifiers = FnSignature()
+        while True:
+            token = self._peek()
+            following = self._peek(1)
+            if token is None:
+                break
+            if token.is_ident("async", "unsafe"):
+                if token.text == "async":
+                    qualifiers.is_async = True
+                else:
+                    qualifiers.is_unsafe = True
+                self.pos += 1
+            elif token.is_ident("default") and following and following.is_ident():
+                self.pos += 1
+            elif token.is_ident("auto") and following and following.is_ident("trait"):
+                self.pos += 1
+            elif token.is_ident("const") and following and following.is_ident(
+                "fn", "unsafe", "async", "extern"
+            ):
+                qualifiers.is_const = True
+                self.pos += 1
+            elif token.is_ident("safe") and following and following.is_ident(
+                "fn", "static"
+            ):
+                # In an `unsafe extern` block
+                self.pos += 1
+            elif token.is_ident("extern"):
+                if following and following.is_ident("crate"):
+                    self._skip_to_semicolon()
+                    return None
+                self.pos += 1
+                # The ABI, as in extern "C" fn; Rust's default is C
+                qualifiers.abi = "C"
+                if self._peek() is not None and self._peek().kind == TokenKind.LITERAL:
+                    qualifiers.abi = self._peek().text.strip('"')
+                    self.pos += 1
+                if self._peek() is not None and self._peek().is_punct("{"):
+                    return self._extern_block(
+                        first, token, qualifiers.abi, visibility, docs, attributes
+                    )
+            else:
+                break
+
+        token = self._peek()
+        if token is None:
+            self._issue(
+                IssueKind.UNEXPECTED_TOKEN, "Expected an item", first, self._last()
+            )
+            return None
+        following = self._peek(1)
+        kind = _ITEM_KEYWORDS.get(token.text) if token.is_ident() else None
+        # union is a keyword only before the name of a union
+        if token.is_ident("union") and following is not None and following.is_ident():
+            kind = SymbolKind.UNION
+        if kind is not None:
+            self.open_indents.append(self._indent(first))
+            self.open_kinds.append(kind)
+            symbol = self._keyword_item(kind, first, visibility, docs, attributes)
+            self.open_indents.pop()
+            self.open_kinds.pop()
+            if symbol is not None and kind == SymbolKind.FUNCTION:
+                symbol.is_async = qualifiers.is_async
+                signature = symbol.signature
+                signature.is_async = qualifiers.is_async
+                signature.is_unsafe = qualifiers.is_unsafe
+                signature.is_const = qualifiers.is_const
+                signature.abi = qualifiers.abi
+            return symbol
+        if token.is_ident("use"):
+            self._use(first, visibility)
+            return None
+        if token.is_ident("macro_rules") and following and following.is_punct("!"):
+            self.open_indents.append(self._indent(first))
+            symbol = self._macro_rules(first, visibility, docs, attributes)
+            self.open_indents.pop()
+            return symbol
+        if token.is_ident() and following is not None and following.is_punct("!"):
+            self._skip_macro()
+            return None
+        self._skip_non_item()
+        return None
+
+    def _extern_block(
+        self,
+        first: Token,
+        keyword: Token,
+        abi: str,
+        visibility: Optional[str],
+        docs: List[Token],
+        attributes: List[Attribute],
+    ) -> Symbol:
+        """
+        The extern block whose `{` is the current token, after the `extern`
+        keyword and ABI: its foreign functions and statics are its children,
+        the functions with the block's ABI.
+        """
+        opener = self._peek()
+        name = " ".join(self.source[keyword.start : self._last().end].split())
+        self.open_indents.append(self._indent(first))
+        self.open_kinds.append(SymbolKind.EXTERN_BLOCK)
+        self.pos += 1
+        children, inner_docs, inner_attributes = self._items(opener=opener)
+        if self._peek() is not None and self._peek().is_punct("}"):
+            self.pos += 1
+        self.open_indents.pop()
+        self.open_kinds.pop()
+        for child in children:
+            if child.signature is not None:
+                child.signature.abi = abi
+        return Symbol(
+            SymbolKind.EXTERN_BLOCK,
+            name,
+            visibility,
+            self._span(first, self._last()),
+            _doc_comment(docs + inner_docs),
+            children,
+            attributes=attributes + inner_attributes,
+            abi=abi,
+            body_span=self._span(opener, self._last()),
+        )
+
+    def _visibility(self) -> Optional[str]:
+        """The visibility modifier at the current token, consumed, if any."""
+        token = self._peek()
+        if token is None or not token.is_ident("pub"):
+            return None
+        self.pos += 1
+        following = self._peek()
+        if following is not None and following.is_punct("("):
+            self._skip_group()
+            return "".join(self.source[token.start : self._last().end].split())
+        return "pub"
+
+    def _keyword_item(
+        self,
+        kind: SymbolKind,
+        first: Token,
+        visibility: Optional[str],
+        docs: List[Token],
+        attributes: List[Attribute],
+    ) -> Optional[Symbol]:
+        keyword = self._peek()
+        self.pos += 1
+        impl_type = impl_trait = None
+        mutable = False
+        generics: Optional[str] = None
+        generic_params: List[GenericParam] = []
+        if kind == SymbolKind.IMPL:
+            generics, generic_params = self._generics()
+            name, impl_type, impl_trait = self._impl_header()
+        else:
+            mutable = self._peek() is not None and self._peek().is_ident("mut")
+            if kind == SymbolKind.STATIC and mutable:
+                self.pos += 1
+            name = self._name(keyword)
+            if name is not None:
+                generics, generic_params = self._generics()
+        if name is None:
+            return None
+        signature = None
+        if kind == SymbolKind.FUNCTION:
+            signature = self._signature(generic_params)
+
+        children: List[Symbol] = []
+        inner_docs: List[Token] = []
+        inner_attributes: List[Attribute] = []
+        shape = None
+        declared_type = default_value = bounds = None
+        token = self._peek()
+        if kind == SymbolKind.STRUCT and token is not None and token.is_punct("("):
+            shape = Shape.TUPLE
+            children = self._fields(token, self.open_indents[-1])
+        if kind == SymbolKind.TYPE_ALIAS and token is not None and token.is_punct(":"):
+            bounds = self._declared("=;", where=True)
+        if kind == SymbolKind.TYPE_ALIAS:
+            token = self._peek()
+            if token is not None and token.is_punct("="):
+                declared_type = self._declared(";", where=True)
+        elif kind in _DECLARATIONS and token is not None and token.is_punct(":"):
+            declared_type = self._declared("=;")
+            token = self._peek()
+            if token is not None and token.is_punct("="):
+                default_value = self._declared(";")
+        token = self._peek()
+        if kind == SymbolKind.TRAIT and token is not None and token.is_punct(":"):
+            # The supertraits, before the where clause
+            self._declared("{;", where=True)
+        if signature is not None:
+            where_clause = signature.where_clause
+            where_predicates = signature.where_predicates
+        else:
+            where_clause, where_predicates = self._where_clause()
+        if kind in _DECLARATIONS or shape is not None:
+            end = self._skip_header(";")
+        else:
+            end = self._skip_header("{;")
+        has_default_body = None
+        in_trait = len(self.open_kinds) > 1 and self.open_kinds[-2] == SymbolKind.TRAIT
+        if kind == SymbolKind.FUNCTION and in_trait and end is not None:
+            has_default_body = end.is_punct("{")
+        if end is None:
+            self._issue(
+                IssueKind.INCOMPLETE_ITEM,
+                f"Expected the body of {keyword.text} {name}",
+                keyword,
+                self._last(),
+            )
+        elif end.is_punct("{") and kind in _CONTAINERS:
+            self.pos += 1
+            children, inner_docs, inner_attributes = self._items(opener=end)
+            if self._peek() is not None and self._peek().is_punct("}"):
+                self.pos += 1
+        elif end.is_punct("{") and kind == SymbolKind.ENUM:
+            self.pos += 1
+            children = self._variants(end)
+        elif end.is_punct("{") and kind in (SymbolKind.STRUCT, SymbolKind.UNION):
+            shape = Shape.STRUCT
+            children = self._fields(end, self.open_indents[-1])
+        elif end.is_punct("{") and kind == SymbolKind.FUNCTION:
+            children = self._function_body()
+        elif end.is_punct("{"):
+            self._skip_group()
+        else:
+            self.pos += 1
+            if kind == SymbolKind.STRUCT and shape is None:
+                shape = Shape.UNIT
+            elif kind == SymbolKind.MODULE:
+                kind = SymbolKind.MODULE_DECL
+        body_span = None
+        if end is not None and end.is_punct("{"):
+            body_span = self._span(end, self._last())
+        return Symbol(
+            kind,
+            name,
+            visibility,
+            self._span(first, self._last()),
+            _doc_comment(docs + inner_docs),
+            children,
+            impl_type=impl_type,
+            impl_trait=impl_trait,
+            attributes=attributes + inner_attributes,
+            derives=_derives(attributes),
+            shape=shape,
+            generics=generics,
+            generic_params=generic_params,
+            where_clause=where_clause,
+            where_predicates=where_predicates,
+            has_default_body=has_default_body,
+            declared_type=declared_type,
+            default_value=default_value,
+            bounds=bounds,
+            signature=signature,
+            is_mutable=mutable if kind == SymbolKind.STATIC else None,
+            body_span=body_span,
+        )
+
+    # --- Variants and fields ---
+
+    def _ends_variant(self, token: Token, indent: int) -> bool:
+        """
+        Whether the token starts the next variant, as in a variant whose fields
+        are not closed: a name, attribute or doc comment first on its line and
+        indented no deeper than the variant.
+        """
+        column = self._line_column(token)
+        if column is None or column > indent:
+            return False
+        return (
+            token.is_ident()
+            or token.is_punct("#")
+            or token.kind == TokenKind.OUTER_DOC
+        )
+
+    def _variants(self, opener: Token) -> List[Symbol]:
+        """The variants of an enum, up to and including the `}` closing `opener`."""
+        variants: List[Symbol] = []
+        docs: List[Token] = []
+        attributes: List[Attribute] = []
+        first_attribute: Optional[Token] = None
+        while self._peek() is not None:
+            token = self._peek()
+            if self._ends_open_items(token):
+                break
+            if token.is_punct("}"):
+                self.pos += 1
+                return variants
+            if token.kind == TokenKind.OUTER_DOC:
+                docs.append(token)
+                self.pos += 1
+            elif token.is_punct("#"):
+                attribute = self._attribute()
+                if attribute is not None:
+                    attributes.append(attribute)
+                if first_attribute is None:
+                    first_attribute = token
+            elif token.is_ident():
+                variants.append(
+                    self._variant(first_attribute or token, docs, attributes)
+                )
+                docs = []
+                attributes = []
+                first_attribute = None
+            elif token.is_punct(","):
+                self.pos += 1
+            else:
+                self._issue(
+                    IssueKind.UNEXPECTED_TOKEN,
+                    f"Expected a variant, found `{token.text}`",
+                    token,
+                )
+                if token.is_punct("(["):
+                    self._skip_group()
+                else:
+                    self.pos += 1
+        self._issue(
+            IssueKind.UNCLOSED_DELIMITER, f"Unclosed delimiter `{opener.text}`", opener
+        )
+        return variants
+
+    def _variant(
+        self, first: Token, docs: List[Token], attributes: List[Attribute]
+    ) -> Symbol:
+        """The variant at the current name, up to its `,` or the enum's `}`."""
+        indent = self._indent(first)
+        name = self._peek().text
+        self.pos += 1
+        shape = Shape.UNIT
+        fields: List[Symbol] = []
+        token = self._peek()
+        if token is not None and token.is_punct("("):
+            shape = Shape.TUPLE
+            fields = self._fields(token, indent)
+        elif token is not None and token.is_punct("{"):
+            shape = Shape.STRUCT
+            fields = self._fields(token, indent)
+        token = self._peek()
+        if token is not None and token.is_punct("="):
+            # The discriminant
+            self.pos += 1
+            self._skip_field_type(indent, "}")
+        return Symbol(
+            SymbolKind.ENUM_VARIANT,
+            name,
+            None,
+            self._span(first, self._last()),
+            _doc_comment(docs),
+            fields,
+            attributes=attributes,
+            shape=shape,
+        )
+
+    def _fields(self, opener: Token, indent: int) -> List[Symbol]:
+        """
+        The fields in the `(...)` or `{...}` at `opener`, consumed with its
+        closing delimiter. Fields not closed end where the next variant starts,
+        or a name indented no deeper than `indent`, that of the variant or
+        struct.
+        """
+        closing = _CLOSING[opener.text]
+        named = opener.is_punct("{")
+        self.pos += 1
+        fields: List[Symbol] = []
+        docs: List[Token] = []
+        attributes: List[Attribute] = []
+        first_attribute: Optional[Token] = None
+        while self._peek() is not None:
+            token = self._peek()
+            if self._ends_open_items(token) or self._ends_variant(token, indent):
+                break
+            if token.is_punct(closing):
+                self.pos += 1
+                return fields
+            if token.is_punct(")]}"):
+                # Closes an enclosing group, as the `}` of the enum
+                break
+            if token.kind == TokenKind.OUTER_DOC:
+                docs.append(token)
+                self.pos += 1
+            elif token.is_punct("#"):
+                attribute = self._attribute()
+                if attribute is not None:
+                    attributes.append(attribute)
+                if first_attribute is None:
+                    first_attribute = token
+            elif token.is_punct(","):
+                self.pos += 1
+            else:
+                field = self._field(
+                    first_attribute or token, named, len(fields), indent, closing
+                )
+                if field is not None:
+                    field.doc_comment = _doc_comment(docs)
+                    field.attributes = attributes
+                    fields.append(field)
+                docs = []
+                attributes = []
+                first_attribute = None
+        self._issue(
+            IssueKind.UNCLOSED_DELIMITER, f"Unclosed delimiter `{opener.text}`", opener
+        )
+        return fields
+
+    def _field(
+        self, first: Token, named: bool, index: int, indent: int, closing: str
+    ) -> Optional[Symbol]:
+        """
+        The field at the current token, up to its `,` or `closing`. None for a
+        named field without its name and `:`, which is skipped and reported.
+        """
+        visibility = self._visibility()
+        name = str(index)
+        if named:
+            token = self._peek()
+            following = self._peek(1)
+            if (
+                token is None
+                or not token.is_ident()
+                or following is None
+                or not following.is_punct(":")
+            ):
+                self._issue(
+                    IssueKind.UNEXPECTED_TOKEN,
+                    "Expected a field name and `:`",
+                    first,
+                    token or self._last(),
+                )
+                self._skip_field_type(indent, closing)
+                return None
+            name = token.text
+            self.pos += 2
+        start = self.pos
+        self._skip_field_type(indent, closing)
+        field_type = self._text(start, self.pos) or None
+        if field_type is None:
+            self._issue(
+                IssueKind.INCOMPLETE_ITEM,
+                f"Expected the type of field {name}",
+                first,
+                self._last(),
+            )
+        return Symbol(
+            SymbolKind.FIELD,
+            name,
+            visibility,
+            self._span(first, self._last()),
+            field_type=field_type,
+        )
+
+    def _skip_field_type(self, indent: int, closing: str) -> None:
+        """
+        Consumes a type or expression up to a `,` outside any group or `<...>`,
+        or up to `closing`; both are left unconsumed.
+        """
+        depth = 0
+        while self._peek() is not None:
+            token = self._peek()
+            if self._ends_open_items(token) or self._ends_variant(token, indent):
+                return
+            if token.is_punct(closing) or token.is_punct(")]}"):
+                return
+            if token.is_punct(",") and depth == 0:
+                return
+            if token.is_punct("([{"):
+                self._skip_group()
+                continue
+            if token.is_punct("<"):
+                depth += 1
+            elif token.is_punct(">") and not self._last().is_punct("-") and depth:
+                depth -= 1
+            self.pos += 1
+
+    def _macro_rules(
+        self,
+        first: Token,
+        visibility: Optional[str],
+        docs: List[Token],
+        attributes: List[Attribute],
+    ) -> Optional[Symbol]:
+        """
+        A macro_rules! definition. Its body is skipped as a token tree: the
+        matchers and transcribers in it are no items, whatever their `$(...)*`
+        fragments and delimiters look like.
+        """
+        keyword = self._peek()
+        self.pos += 2
+        name = self._name(keyword)
+        if name is None:
+            return None
+        body = self._peek()
+        body_span = None
+        if body is None or not body.is_punct("([{"):
+            self._issue(
+                IssueKind.INCOMPLETE_ITEM,
+                f"Expected the body of macro_rules! {name}",
+                keyword,
+                self._last(),
+            )
+        else:
+            self._skip_group()
+            body_span = self._span(body, self._last())
+            # macro_rules! m ( ... ); and [ ... ]; end at the `;`
+            following = self._peek()
+            if not body.is_punct("{") and following and following.is_punct(";"):
+                self.pos += 1
+        return Symbol(
+            SymbolKind.MACRO,
+            name,
+            visibility,
+            self._span(first, self._last()),
+            _doc_comment(docs),
+            attributes=attributes,
+            derives=_derives(attributes),
+            body_span=body_span,
+        )
+
+    def _use(self, first: Token, visibility: Optional[str]) -> None:
+        """
+        A use declaration, skipped like other items up to its `;`; the paths of
+        its tree are added to the imports, as far as they are complete.
+        """
+        start = self.pos + 1
+        self._skip_to_semicolon()
+        end = self.pos - 1 if self._last().is_punct(";") else self.pos
+        imports: List[Tuple[List[str], Optional[str], bool]] = []
+        _use_tree(self.tokens[start:end], 0, [], imports)
+        span = self._span(first, self._last())
+        for segments, alias, is_glob in imports:
+            self.imports.append(
+                Import(segments, alias, is_glob, visibility is not None, span)
+            )
+
+    def _name(self, keyword: Token) -> Optional[str]:
+        """The name after an item keyword, consumed; None if there is none."""
+        token = self._peek()
+        if token is None or not token.is_ident():
+            found = f"`{token.text}`" if token is not None else "the end of the file"
+            self._issue(
+                IssueKind.EXPECTED_NAME,
+                f"Expected a name after `{keyword.text}`, found {found}",
+                keyword,
+            )
+            return None
+        self.pos += 1
+        if token.text in _RESERVED:
+            # Kept as the name, so the item is still listed
+            self._issue(
+                IssueKind.EXPECTED_NAME,
+                f"Expected a name after `{keyword.text}`, found keyword "
+                f"`{token.text}`",
+                token,
+            )
+        return token.text
+
+    def _impl_header(self) -> Tuple[str, str, Optional[str]]:
+        """
+        The name, implemented type and trait of an impl, consumed after its
+        generic parameters up to its where clause or body. The name is the
+        header as written with whitespace collapsed: `Point<T>`, `Display for
+        Point<T>`. The type is without its generic arguments (`Point`), the
+        trait as written (`From<u32>`), or None for an inherent impl.
+        """
+        start = self.pos
+        depth = 0
+        split = None
+        while self._peek() is not None:
+            token = self._peek()
+            if token.is_punct("{;}") or token.is_ident("where"):
+                break
+            if self._ends_open_items(token):
+                break
+            if token.is_punct("(["):
+                self._skip_group()
+                continue
+            following = self._peek(1)
+            if token.is_punct("<"):
+                depth += 1
+            elif token.is_punct(">") and not self._last().is_punct("-"):
+                depth -= 1
+            # Not the for<'a> of a higher-ranked bound
+            elif token.is_ident("for") and depth == 0 and split is None:
+                if following is None or not following.is_punct("<"):
+                    split = self.pos
+            self.pos += 1
+        name = self._text(start, self.pos)
+        if split is None:
+            return name, self._type_name(start, self.pos), None
+        return name, self._type_name(split + 1, self.pos), self._text(start, split)
+
+    def _text(self, start: int, end: int) -> str:
+        """The source of tokens start to end (exclusive), whitespace collapsed."""
+        if end <= start:
+            return ""
+        first, last = self.tokens[start], self.tokens[end - 1]
+        return " ".join(self.source[first.start : last.end].split())
+
+    def _type_name(self, start: int, end: int) -> str:
+        """The type in tokens start to end, without the generic arguments after it."""
+        for position in range(start + 1, end):
+            token, before = self.tokens[position], self.tokens[position - 1]
+            if token.is_punct("<") and before.is_ident():
+                return self._text(start, position)
+        return self._text(start, end)
+
+    # --- Skipping ---
+
+    def _skip_group(self) -> None:
+        """
+        Consumes the delimiter group opened at the current token. A closing
+        delimiter that matches an enclosing opener closes the openers after
+        it, which are reported as unclosed; one that matches none is reported
+        and ignored.
+        """
+        openers = [self._peek()]
+        self.pos += 1
+        while openers and self._peek() is not None:
+            if self._ends_open_items(self._peek()):
+                break
+            self._close_before_statement(openers)
+            if openers:
+                self._group_token(openers)
+        self._unclosed(openers)
+
+    def _group_token(self, openers: List[Token]) -> None:
+        """Consumes a token inside the groups `openers` opened, innermost last."""
+        token = self._peek()
+        opens_closure = self._opens_closure()
+        self.pos += 1
+        if token.is_punct("|") and openers and openers[-1].is_punct("|"):
+            del openers[-1]
+        elif token.is_punct("([{") or opens_closure:
+            openers.append(token)
+        elif token.is_punct(")]}"):
+            matching = [
+                i
+                for i, opener in enumerate(openers)
+                if _CLOSING[opener.text] == token.text
+            ]
+            if not matching:
+                self._issue(
+                    IssueKind.UNEXPECTED_DELIMITER,
+                    f"Unexpected closing delimiter `{token.text}`",
+                    token,
+                )
+                return
+            self._unclosed(openers[matching[-1] + 1 :])
+            del openers[matching[-1] :]
+
+    def _unclosed(self, openers: List[Token]) -> None:
+        for opener in openers:
+            self._issue(
+                IssueKind.UNCLOSED_DELIMITER,
+                f"Unclosed delimiter `{opener.text}`",
+                opener,
+            )
+
+    def _opens_closure(self) -> bool:
+        """
+        Whether the current token is a `|` opening the parameter list of a
+        closure: one where an expression starts, after `=`, `=>`, an opening
+        delimiter, `,`, `;`, `:`, `move`, `async` or `return`. Elsewhere a `|`
+        is an operator or separates patterns.
+        """
+        token = self._peek()
+        if token is None or not token.is_punct("|"):
+            return False
+        if self.pos == 0:
+            return True
+        previous = self._last()
+        if previous.is_punct(">") and self.pos >= 2:
+            arrow = self.tokens[self.pos - 2]
+            return arrow.is_punct("=") and arrow.end == previous.start
+        return previous.is_punct("=([{,;:") or previous.is_ident(
+            "move", "async", "return"
+        )
+
+    def _starts_statement(self, token: Token) -> bool:
+        """Whether the token is a statement keyword first on its line."""
+        return (
+            token.is_ident()
+            and token.text in _STATEMENT_STARTS
+            and self._line_column(token) is not None
+        )
+
+    def _close_before_statement(self, openers: List[Token]) -> None:
+        """
+        Ends the `(`, `[` and closure parameter lists innermost in `openers`
+        that the current token cannot be inside: a statement keyword first on
+        its line, indented no deeper than the lines they were opened on. They
+        are reported as unclosed, so that scanning resumes at the statement.
+        """
+        token = self._peek()
+        if not self._starts_statement(token):
+            return
+        column = self._line_column(token)
+        end = len(openers)
+        while (
+            end
+            and openers[end - 1].is_punct("([|")
+            and self._indent(openers[end - 1]) >= column
+        ):
+            end -= 1
+        self._unclosed(openers[end:])
+        del openers[end:]
+
+    def _function_body(self) -> List[Symbol]:
+        """
+        Consumes the body of a function at the current `{` as _skip_group()
+        does, and returns the modules declared in it, or with include_nested
+        all items: those at the start of a statement, with their attributes
+        and doc comments. Closures are no items.
+        """
+        openers = [self._peek()]
+        self.pos += 1
+        items: List[Symbol] = []
+        docs: List[Token] = []
+        attributes: List[Attribute] = []
+        first_attribute: Optional[Token] = None
+        while openers and self._peek() is not None:
+            token = self._peek()
+            if self._ends_open_items(token):
+                break
+            self._close_before_statement(openers)
+            last = self._last()
+            statement_start = last.is_punct("{;}]") or last.kind == TokenKind.OUTER_DOC
+            following = self._peek(1)
+            if statement_start and token.kind == TokenKind.OUTER_DOC:
+                docs.append(token)
+                self.pos += 1
+            elif statement_start and token.is_punct("#") and following is not None:
+                if not following.is_punct("["):
+                    self._group_token(openers)
+                    continue
+                attribute = self._attribute()
+                if attribute is not None:
+                    attributes.append(attribute)
+                if first_attribute is None:
+                    first_attribute = token
+            elif statement_start and (
+                self._at_module() or self.include_nested and self._at_nested_item()
+            ):
+                symbol = self._item(first_attribute or token, docs, attributes)
+                if symbol is not None:
+                    items.append(symbol)
+                docs = []
+                attributes = []
+                first_attribute = None
+            else:
+                docs = []
+                attributes = []
+                first_attribute = None
+                self._group_token(openers)
+        self._unclosed(openers)
+        return items
+
+    def _visibility_length(self) -> int:
+        """The number of tokens of the visibility at the current token."""
+        ahead = 0
+        token = self._peek()
+        if token is not None and token.is_ident("pub"):
+            ahead = 1
+            if self._peek(1) is not None and self._peek(1).is_punct("("):
+                while self._peek(ahead) is not None and not (
+                    self._peek(ahead).is_punct(")")
+                ):
+                    ahead += 1
+                ahead += 1
+        return ahead
+
+    def _at_module(self) -> bool:
+        """Whether a `mod` item, possibly with a visibility, starts here."""
+        ahead = self._visibility_length()
+        keyword, name = self._peek(ahead), self._peek(ahead + 1)
+        return (
+            keyword is not None
+            and keyword.is_ident("mod")
+            and name is not None
+            and name.is_ident()
+        )
+
+    def _at_nested_item(self) -> bool:
+        """
+        Whether an item starts here, in a function body: an item keyword and
+        the item's name, or `impl`, possibly after a visibility and function
+        qualifiers. `unsafe {` and `async move {` blocks, `const {` blocks and
+        `use` declarations are no items.
+        """
+        ahead = self._visibility_length()
+        while True:
+            keyword, name = self._peek(ahead), self._peek(ahead + 1)
+            if keyword is None or name is None:
+                return False
+            if keyword.is_ident("async", "unsafe") or (
+                keyword.is_ident("const")
+                and name.is_ident("fn", "unsafe", "async", "extern")
+            ):
+                ahead += 1
+            elif keyword.is_ident("extern"):
+                ahead += 2 if name.kind == TokenKind.LITERAL else 1
+            else:
+                break
+        if keyword.is_ident("impl"):
+            return True
+        return (
+            keyword.is_ident("union", *_ITEM_KEYWORDS)
+            and name.is_ident()
+            and name.text not in _RESERVED - {"mut"}
+        )
+
+    def _skip_header(self, stops: str) -> Optional[Token]:
+        """
+        Consumes tokens up to one of the `stops` punctuation characters outside
+        any group, which is returned unconsumed. Returns None if a `}` closing
+        an enclosing block or the end of the source comes first.
+        """
+        while self._peek() is not None:
+            token = self._peek()
+            if token.is_punct(stops):
+                return token
+            if token.is_punct("}") or self._ends_open_items(token):
+                return None
+            if token.is_punct("([{"):
+                self._skip_group()
+            elif token.is_punct(")]"):
+                self._issue(
+                    IssueKind.UNEXPECTED_DELIMITER,
+                    f"Unexpected closing delimiter `{token.text}`",
+                    token,
+                )
+                self.pos += 1
+            else:
+                self.pos += 1
+        return None
+
+    # --- Function signatures ---
+
+    def _signature(self, generics: List[GenericParam]) -> FnSignature:
+        """
+        The signature of the function at the current token, past its name and
+        its generic parameters, `generics`. Consumes the parameter list, return
+        type and where clause, up to the body or `;`. The qualifiers before
+        `fn` are left to the caller.
+        """
+        signature = FnSignature(generics=generics)
+        token = self._peek()
+        if token is not None and token.is_punct("("):
+            opener = self._span(token, token)
+            issues = len(self.issues)
+            start = self.pos + 1
+            self._skip_group()
+            unclosed = any(
+                issue.kind == IssueKind.UNCLOSED_DELIMITER and issue.span == opener
+                for issue in self.issues[issues:]
+            )
+            end = self.pos
+            if unclosed:
+                # The parameters end where the rest of the signature starts
+                end = next(
+                    (
+                        i
+                        for i in range(start, self.pos)
+                        if self.tokens[i].is_punct("{;")
+                        or self.tokens[i].is_punct("-")
+                        and i + 1 < self.pos
+                        and self.tokens[i + 1].is_punct(">")
+                    ),
+                    self.pos,
+                )
+            else:
+                end -= 1
+            signature.receiver, signature.params = self._params(start, end)
+        token, following = self._peek(), self._peek(1)
+        if (
+            token is not None
+            and following is not None
+            and token.is_punct("-")
+            and following.is_punct(">")
+        ):
+            self.pos += 1
+            signature.return_type = self._declared("{;", where=True)
+        signature.where_clause, signature.where_predicates = self._where_clause()
+        return signature
+
+    def _where_clause(self) -> Tuple[Optional[str], List[WherePredicate]]:
+        """
+        The where clause at the current token as written, and its predicates,
+        consuming it up to the body or `;`; (None, []) if there is none. The
+        clause may span lines, and a `{` in `<...>` or in a group, such as a
+        const argument (`Buf<{ N + 1 }>`), does not end it. A clause left
+        incomplete ends where the next item starts.
+        """
+        token = self._peek()
+        if token is None or not token.is_ident("where"):
+            return None, []
+        start = self.pos
+        self._declared("{;")
+        if self.pos == start + 1:
+            return None, []
+        return (
+            self.source[token.start : self._last().end],
+            self._where_predicates(start + 1, self.pos),
+        )
+
+    def _where_predicates(self, start: int, end: int) -> List[WherePredicate]:
+        """
+        The predicates of a where clause in tokens start to end, after `where`.
+        A predicate without bounds is reported, and so is a trailing `+`.
+        """
+        predicates: List[WherePredicate] = []
+        for a, b in self._split(start, end):
+            colon = self._find(a, b, ":")
+            bounded = self._text(a, b if colon is None else colon)
+            bounds = None if colon is None else self._bounds(colon + 1, b)
+            if bounds is None:
+                self._issue(
+                    IssueKind.INCOMPLETE_BOUNDS,
+                    f"Expected bounds for `{bounded}`",
+                    self.tokens[a],
+                    self.tokens[b - 1],
+                )
+            predicates.append(WherePredicate(bounded, bounds))
+        return predicates
+
+    def _bounds(self, start: int, end: int) -> Optional[str]:
+        """
+        The bounds in tokens start to end, whitespace collapsed, or None if
+        there are none. A trailing `+` is reported and left out.
+        """
+        if end > start and self.tokens[end - 1].is_punct("+"):
+            self._issue(
+                IssueKind.INCOMPLETE_BOUNDS,
+                "Expected a bound after `+`",
+                self.tokens[end - 1],
+            )
+            end -= 1
+        return self._text(start, end) or None
+
+    def _params(self, start: int, end: int) -> Tuple[Optional[str], List[Param]]:
+        """The receiver and the other parameters in tokens start to end."""
+        receiver = None
+        params: List[Param] = []
+        for index, (a, b) in enumerate(self._split(start, end)):
+            # Attributes of the parameter
+            while a + 1 < b and self.tokens[a].is_punct("#"):
+                depth = 0
+                for a in range(a + 1, b):
+                    if self.tokens[a].is_punct("["):
+                        depth += 1
+                    elif self.tokens[a].is_punct("]"):
+                        depth -= 1
+                        if depth == 0:
+                            break
+                a += 1
+            if a >= b:
+                continue
+            colon = self._find(a, b, ":")
+            pattern_end = b if colon is None else colon
+            if index == 0 and self.tokens[pattern_end - 1].is_ident("self"):
+                receiver = self._text(a, b)
+                continue
+            params.append(
+                Param(
+                    self._text(a, pattern_end),
+                    None if colon is None else self._text(colon + 1, b) or None,
+                    self._span(self.tokens[a], self.tokens[b - 1]),
+                )
+            )
+        return receiver, params
+
+    def _generics(self) -> Tuple[Optional[str], List[GenericParam]]:
+        """
+        The generic parameter list at the current token as written, with its
+        `<>`, and its parameters, consuming it; (None, []) if there is none. A
+        list that is not closed ends before the body or `;` of the item.
+        """
+        token = self._peek()
+        if token is None or not token.is_punct("<"):
+            return None, []
+        start = self.pos
+        self._skip_angle_brackets()
+        closed = self._last().is_punct(">") and self.pos - 1 > start
+        return (
+            self.source[token.start : self._last().end],
+            self._generic_params(start + 1, self.pos - closed),
+        )
+
+    def _generic_params(self, start: int, end: int) -> List[GenericParam]:
+        """The generic parameters in tokens start to end, inside the `<...>`."""
+        params: List[GenericParam] = []
+        for a, b in self._split(start, end):
+            first = self.tokens[a]
+            equals = self._find(a, b, "=")
+            head_end = b if equals is None else equals
+            if first.kind == TokenKind.LIFETIME:
+                param = GenericParam(GenericKind.LIFETIME, first.text)
+            elif first.is_ident("const") and a + 1 < head_end:
+                a += 1
+                param = GenericParam(GenericKind.CONST, self.tokens[a].text)
+            else:
+                param = GenericParam(GenericKind.TYPE, first.text)
+            colon = self._find(a + 1, head_end, ":")
+            if colon is not None and param.kind == GenericKind.CONST:
+                param.const_type = self._text(colon + 1, head_end) or None
+            elif colon is not None:
+                param.bounds = self._bounds(colon + 1, head_end)
+            if equals is not None:
+                param.default = self._text(equals + 1, b) or None
+            params.append(param)
+        return params
+
+    def _split(
+        self, start: int, end: int, separator: str = ","
+    ) -> List[Tuple[int, int]]:
+        """
+        The ranges of tokens start to end between the `separator` punctuation
+        outside any group or `<...>`, leaving out empty ones.
+        """
+        ranges: List[Tuple[int, int]] = []
+        depth = 0
+        part_start = start
+        for i in range(start, end):
+            token = self.tokens[i]
+            if token.is_punct(separator) and depth == 0:
+                if i > part_start:
+                    ranges.append((part_start, i))
+                part_start = i + 1
+            elif token.is_punct("([{<"):
+                depth += 1
+            elif token.is_punct(")]}") or (
+                token.is_punct(">") and not self.tokens[i - 1].is_punct("-")
+            ):
+                depth = max(depth - 1, 0)
+        if end > part_start:
+            ranges.append((part_start, end))
+        return ranges
+
+    def _find(self, start: int, end: int, char: str) -> Optional[int]:
+        """
+        The index of the first `char` punctuation in tokens start to end outside
+        any group or `<...>`, a `:` not being part of a `::`; None if none.
+        """
+        depth = 0
+        for i in range(start, end):
+            token = self.tokens[i]
+            if token.is_punct(char) and depth == 0:
+                if char == ":" and (
+                    self.tokens[i + 1].is_punct(":")
+                    and self.tokens[i + 1].start == token.end
+                    or self.tokens[i - 1].is_punct(":")
+                    and self.tokens[i - 1].end == token.start
+                ):
+                    continue
+                return i
+            if token.is_punct("([{<"):
+                depth += 1
+            elif token.is_punct(")]}") or (
+                token.is_punct(">") and not self.tokens[i - 1].is_punct("-")
+            ):
+                depth = max(depth - 1, 0)
+        return None
+
+    def _declared(self, stops: str, where: bool = False) -> Optional[str]:
+        """
+        The type, bounds or expression after the current token, which is
+        consumed, up to one of the `stops` punctuation characters outside any
+        group or `<...>`, or up to `where` if `where` is set; the stop is left
+        unconsumed. Whitespace is collapsed; None if there is nothing.
+        """
+        self.pos += 1
+        start = self.pos
+        depth = 0
+        while self._peek() is not None:
+            token = self._peek()
+            if token.is_punct("}") or self._ends_open_items(token):
+                break
+            if depth == 0 and (
+                token.is_punct(stops) or (where and token.is_ident("where"))
+            ):
+                break
+            if token.is_punct("([{"):
+                self._skip_group()
+                continue
+            if token.is_punct("<"):
+                depth += 1
+            elif token.is_punct(">") and not self._last().is_punct("-") and depth:
+                depth -= 1
+            self.pos += 1
+        return self._text(start, self.pos) or None
+
+    def _skip_angle_brackets(self) -> None:
+        """
+        Consumes the <...> at the current token, which may nest. A `{` ends it
+        unless it opens a block after `=`, the default of a const parameter
+        (`const N: usize = { 2 * M }`).
+        """
+        depth = 0
+        while self._peek() is not None:
+            token = self._peek()
+            if token.is_punct("{") and depth and self._last().is_punct("="):
+                self._skip_group()
+                continue
+            if token.is_punct("{;}"):
+                return
+            if token.is_punct("(["):
+                self._skip_group()
+                continue
+            self.pos += 1
+            if token.is_punct("<"):
+                depth += 1
+            # The > of -> in Fn() -> T closes nothing
+            elif token.is_punct(">") and not self.tokens[self.pos - 2].is_punct("-"):
+                depth -= 1
+                if depth == 0:
+                    return
+
+    def _skip_to_semicolon(self) -> None:
+        """Consumes the item at the current token up to and including its `;`."""
+        self.pos += 1
+        if self._skip_header(";") is not None:
+            self.pos += 1
+
+    def _skip_macro(self) -> None:
+        """Consumes a macro invocation."""
+        self.pos += 2
+        token = self._peek()
+        if token is not None and token.is_punct("([{"):
+            self._skip_group()
+            token = self._peek()
+        if token is not None and token.is_punct(";"):
+            self.pos += 1
+
+    def _skip_non_item(self) -> None:
+        """
+        Consumes the tokens from the current one on up to where an item may
+        start, and reports them.
+        """
+        start = self.pos
+        first = self._peek()
+        while self._peek() is not None:
+            token = self._peek()
+            if self.pos > start and (
+                token.is_punct("#}")
+                or token.kind == TokenKind.OUTER_DOC
+                or (token.is_ident() and token.text in _ITEM_STARTS)
+                or self._starts_statement(token)
+            ):
+                break
+            if token.is_punct("([{") or self._opens_closure():
+                self._skip_group()
+            else:
+                self.pos += 1
+            if token.is_punct(";"):
+                break
+        self._issue(
+            IssueKind.UNEXPECTED_TOKEN,
+            f"Expected an item, found `{first.text}`",
+            first,
+            self._last(),
+        )
+
+
+def _use_tree(
+    tokens: List[Token],
+    position: int,
+    prefix: List[str],
+    imports: List[Tuple[List[str], Optional[str], bool]],
+) -> int:
+    """
+    Adds the (segments, alias, is_glob) of the paths of the use tree at
+    `tokens[position]` below `prefix` to `imports`, and returns the position
+    after the tree. What is not a use tree ends it.
+    """
+    segments = list(prefix)
+
+    def at_path_separator(i: int) -> bool:
+        return (
+            i + 1 < len(tokens)
+            and tokens[i].is_punct(":")
+            and tokens[i + 1].is_punct(":")
+        )
+
+    if at_path_separator(position):
+        position += 2
+        if not segments:
+            segments.append("")
+    while position < len(tokens):
+        token = tokens[position]
+        if token.is_punct("*"):
+            imports.append((segments, None, True))
+            return position + 1
+        if token.is_punct("{"):
+            position += 1
+            while position < len(tokens) and not tokens[position].is_punct("}"):
+                found = len(imports)
+                position = _use_tree(tokens, position, segments, imports)
+                if position < len(tokens) and tokens[position].is_punct(","):
+                    position += 1
+                elif position < len(tokens) and not tokens[position].is_punct("}"):
+                    # Not a use tree, as in a group that is never closed
+                    del imports[found:]
+                    return position
+            return position + 1
+        if not token.is_ident():
+            return position
+        segments.append(token.text)
+        position += 1
+        if at_path_separator(position):
+            position += 2
+            continue
+        alias = None
+        if position < len(tokens) and tokens[position].is_ident("as"):
+            position += 1
+            if position < len(tokens) and tokens[position].is_ident():
+                alias = tokens[position].text
+                position += 1
+        # `io::{self}` imports io itself
+        if segments[-1] == "self" and len(segments) > 1:
+            segments.pop()
+        imports.append((segments, alias, False))
+        return position
+    return position
+
+
+def _derives(attributes: List[Attribute]) -> List[str]:
+    """The traits the `#[derive(...)]` attributes among `attributes` list."""
+    derives: List[str] = []
+    for attribute in attributes:
+        args = (attribute.args_raw or "").strip()
+        if attribute.name != "derive" or not args.startswith("("):
+            continue
+        args = args[1:-1] if args.endswith(")") else args[1:]
+        # Commas inside <...> (generic arguments) do not separate traits
+        depth = 0
+        current: List[str] = []
+        for char in args + ",":
+            if char == "," and depth == 0:
+                name = " ".join("".join(current).split())
+                if name:
+                    derives.append(name)
+                current = []
+                continue
+            if char in "<([":
+                depth += 1
+            elif char in ">)]":
+                depth -= 1
+            current.append(char)
+    return derives
+
+
+def _qualify(symbols: List[Symbol], prefix: str) -> None:
+    """Sets the qualified_name of the symbols and their descendants."""
+    for symbol in symbols:
+        if symbol.kind == SymbolKind.IMPL:
+            path = prefix + (symbol.impl_type or symbol.name)
+            if symbol.impl_trait is not None:
+                path = f"<{path} as {symbol.impl_trait}>"
+        else:
+            path = prefix + symbol.name
+        symbol.qualified_name = path
+        # Foreign items are named like the items around the extern block
+        if symbol.kind == SymbolKind.EXTERN_BLOCK:
+            _qualify(symbol.children, prefix)
+        else:
+            _qualify(symbol.children, path + "::")
+
+
+def _doc_comment(docs: List[Token]) -> Optional[str]:
+    """
+    The text of doc comments, as rustdoc reads it: without their markers and
+    with the indentation common to their non-blank lines removed, so that
+    Markdown indentation (nested lists, code blocks) is kept.
+    """
+    if not docs:
+        return None
+    lines: List[str] = []
+    for doc in docs:
+        lines.extend(_doc_lines(doc.text))
+    indents = [len(line) - len(line.lstrip()) for line in lines if line.strip()]
+    common = min(indents, default=0)
+    return "\n".join(line[common:] for line in lines)
+
+
+def _doc_lines(text: str) -> List[str]:
+    """The lines of one doc comment, without its markers."""
+    if text.startswith(("///", "//!")):
+        return [text[3:].rstrip("\r")]
+    body = text[3:-2] if text.endswith("*/") and len(text) >= 5 else text[3:]
+    lines = [line.rstrip("\r") for line in body.split("\n")]
+    lines[-1] = lines[-1].rstrip()
+    # The lines of /** */ usually start with a ` * `, all but a first one with
+    # text right after the /**
+    starred = [i for i in range(1, len(lines)) if lines[i].strip()]
+    if starred and all(lines[i].lstrip().startswith("*") for i in starred):
+        for i in starred:
+            lines[i] = lines[i].lstrip()[1:]
+    # Blank first and last lines are the ones of the markers
+    if lines and not lines[0].strip():
+        lines = lines[1:]
+    if lines and not lines[-1].strip():
+        lines = lines[:-1]
+    return lines
+
+
+def parse_rust(
+    source: str,
+    include_nested: bool = False,
+    columns: ColumnUnit = ColumnUnit.CHARACTERS,
+) -> ParseResult:
+    """
+    The symbols of a Rust source, and the issues found parsing it. With
+    `include_nested`, the items declared in function bodies are the
+    functions' children; `columns` is what the columns of spans count.
+    """
+    return RustSymbolParser(source, include_nested, columns).parse()
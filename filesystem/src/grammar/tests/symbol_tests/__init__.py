@@ -0,0 +1,4 @@
+"""
+Tests of the symbol-extraction API (grammar.symbols) against the test and
+validation data.
+"""
@@ -0,0 +1,2248 @@
+#!/usr/bin/env python3
+"""
+Tests for parse_symbols() on Rust sources:
+- The kind, name, visibility, span and doc comment of each kind of item
+- Methods as children of their impl or trait, items as children of their module
+- Nested, declared and function-local modules, and qualified names
+- macro_rules! definitions, whose bodies are skipped as token trees
+- Enum variants, their shape and fields, also of truncated variants
+- Struct fields, their visibility and types, and generic parameter lists
+- Generic parameters of items and impls: lifetimes, const parameters,
+  defaults, and bounds with nested `<>` and higher-ranked `for<'b>`
+- Where clauses of all items, across lines and with braces in const
+  arguments, and incomplete ones recovering at the next item
+- Extern blocks with their foreign functions and statics, unions, and the
+  mutability of statics
+- Function signatures: qualifiers, generics, receivers, parameters, return
+  types and where clauses, also of unclosed parameter lists
+- Byte offsets and line/column positions of spans with multi-byte characters
+  before and inside symbols, in characters and UTF-16 code units, and CRLF
+  sources counting lines like LF
+- Outer and inner attributes, and the traits of derive lists
+- find_tests(): test functions and #[cfg(test)] modules, nested ones too
+- The paths of use declarations, with nested groups, renames, globs, re-exports
+- Closures, turbofish and match arms in function bodies, and the items nested
+  in them with include_nested
+- Partial results and issues on the erroneous and incomplete validation files
+- render_outline(): the outlines of the fixtures against their .expected.outline
+  snapshots, its options, and the body spans of the elided bodies
+"""
+
+import sys
+import unittest
+from pathlib import Path
+
+# grammar is imported as a top-level package, as the server does
+sys.path.insert(0, str(Path(__file__).resolve().parents[3]))
+
+from grammar import (
+    ColumnUnit,
+    GenericKind,
+    IssueKind,
+    Language,
+    OutlineOptions,
+    Shape,
+    SymbolKind,
+    find_tests,
+    parse_symbols,
+    render_outline,
+)
+
+TESTS_DIR = Path(__file__).resolve().parent.parent
+TEST_DATA = TESTS_DIR / "test_data" / "rs"
+VALIDATION_DATA = TESTS_DIR / "validation_data" / "rs"
+
+
+def parse_file(path: Path):
+    return parse_symbols(path.read_text(encoding="utf-8"), Language.RUST)
+
+
+def outline(symbols):
+    """(kind, name) of each symbol, with its children's outline if it has any."""
+    return [
+        (s.kind.value, s.name, outline(s.children))
+        if s.children
+        else (s.kind.value, s.name)
+        for s in symbols
+    ]
+
+
+class TestRustSymbols(unittest.TestCase):
+    def test_item_kinds(self):
+        source = """pub(crate) mod shapes {
+    pub struct Point { x: i32 }
+    enum Side { Left, Right }
+    pub trait Area { fn area(&self) -> f64; }
+    impl Area for Point {
+        fn area(&self) -> f64 { 0.0 }
+    }
+}
+const LIMIT: usize = { 1 + 2 };
+static mut COUNTER: u32 = 0;
+pub type Map<K> = std::collections::HashMap<K, u32>;
+pub async unsafe fn run() {}
+"""
+        result = parse_symbols(source, "rust")
+        self.assertEqual(result.issues, [])
+        self.assertEqual(
+            outline(result.symbols),
+            [
+                (
+                    "module",
+                    "shapes",
+                    [
+                        ("struct", "Point", [("field", "x")]),
+                        (
+                            "enum",
+                            "Side",
+                            [("enum_variant", "Left"), ("enum_variant", "Right")],
+                        ),
+                        ("trait", "Area", [("function", "area")]),
+                        ("impl", "Area for Point", [("function", "area")]),
+                    ],
+                ),
+                ("const", "LIMIT"),
+                ("static", "COUNTER"),
+                ("type_alias", "Map"),
+                ("function", "run"),
+            ],
+        )
+        self.assertEqual(
+            [s.visibility for s in result.walk()],
+            ["pub(crate)", "pub", None, None, None, None, "pub", None, None, None]
+            + [None, None, "pub", "pub"],
+        )
+        self.assertTrue(result.symbols[-1].is_async)
+        self.assertFalse(result.symbols[0].children[2].children[0].is_async)
+
+    def test_spans(self):
+        source = "// é\n#[inline]\npub fn f() {\n}\nstruct S;"
+        function, struct = parse_symbols(source, Language.RUST).symbols
+        # The attribute starts the item
+        span = function.span
+        self.assertEqual((span.start_line, span.start_col), (2, 1))
+        self.assertEqual((span.end_line, span.end_col), (4, 2))
+        # é is two bytes
+        self.assertEqual(span.start_byte, len("// é\n") + 1)
+        encoded = source.encode("utf-8")
+        self.assertEqual(
+            encoded[span.start_byte : span.end_byte].decode(),
+            "#[inline]\npub fn f() {\n}",
+        )
+        self.assertEqual(
+            encoded[struct.span.start_byte : struct.span.end_byte], b"struct S;"
+        )
+
+        # CRLF line endings count lines like LF
+        crlf = parse_symbols(source.replace("\n", "\r\n"), Language.RUST).symbols
+        self.assertEqual(crlf[1].span.start_line, struct.span.start_line)
+
+    def test_multibyte_spans(self):
+        source = (
+            "// 🦀 é\n"
+            'const NAME: &str = "😀 é";\n'
+            'fn größe() -> u8 { let _ = "🦀"; 1 }\n'
+            "struct Ünï { 𝒳: u8, b: u8 }\n"
+            'fn broken() { let s = "🦀\n'
+            "}\n"
+        )
+
+        def spans(result):
+            return [s.span for s in result.walk()] + [i.span for i in result.issues]
+
+        def positions(result):
+            return [
+                (s.start_line, s.start_col, s.end_line, s.end_col)
+                for s in spans(result)
+            ]
+
+        def check_columns(source, result, width):
+            """Each span's lines and columns, counted from its bytes with `width`."""
+            encoded = source.encode("utf-8")
+            for span in spans(result):
+                for byte, line, col in (
+                    (span.start_byte, span.start_line, span.start_col),
+                    (span.end_byte, span.end_line, span.end_col),
+                ):
+                    before = encoded[:byte].decode("utf-8")
+                    self.assertEqual(before.count("\n") + 1, line)
+                    self.assertEqual(width(before.rsplit("\n", 1)[-1]) + 1, col)
+
+        def utf16(text):
+            return len(text.encode("utf-16-le")) // 2
+
+        chars = parse_symbols(source, Language.RUST)
+        units = parse_symbols(source, Language.RUST, columns=ColumnUnit.UTF16)
+        check_columns(source, chars, len)
+        check_columns(source, units, utf16)
+        self.assertEqual(
+            [s.name for s in chars.walk()],
+            ["NAME", "größe", "Ünï", "𝒳", "b", "broken"],
+        )
+        name, function, struct, wide, narrow, broken = chars.walk()
+        self.assertEqual(
+            source.encode("utf-8")[function.span.start_byte : function.span.end_byte],
+            'fn größe() -> u8 { let _ = "🦀"; 1 }'.encode("utf-8"),
+        )
+        # 😀 and 𝒳 are one character, two UTF-16 code units; é one of each
+        self.assertEqual((name.span.end_col, narrow.span.start_col), (26, 21))
+        name, function, struct, wide, narrow, broken = units.walk()
+        self.assertEqual((name.span.end_col, narrow.span.start_col), (27, 22))
+        self.assertEqual((wide.span.start_col, wide.span.end_col), (14, 20))
+        [issue] = units.issues
+        self.assertEqual(issue.kind, IssueKind.UNTERMINATED_STRING)
+        self.assertEqual((issue.span.start_col, issue.span.end_col), (23, 26))
+        # Byte offsets do not depend on the column unit
+        self.assertEqual(
+            [s.span.start_byte for s in chars.walk()],
+            [s.span.start_byte for s in units.walk()],
+        )
+        [test] = find_tests('#[test]\nfn t() { "🦀" }', columns="utf16")
+        self.assertEqual(test.span.end_col, len('fn t() { "🦀" }') + 2)
+
+        # CRLF line endings give the same lines and columns as LF
+        crlf_source = source.replace("\n", "\r\n")
+        for columns in ColumnUnit:
+            lf = parse_symbols(source, Language.RUST, columns=columns)
+            crlf = parse_symbols(crlf_source, Language.RUST, columns=columns)
+            self.assertEqual(positions(crlf), positions(lf))
+        check_columns(crlf_source, parse_symbols(crlf_source, Language.RUST), len)
+
+        with self.assertRaises(ValueError):
+            parse_symbols(source, Language.RUST, columns="bytes")
+
+    def test_doc_comments(self):
+        [function] = parse_file(TEST_DATA / "test_rust_parser_2.rs").symbols
+        self.assertEqual(function.name, "add")
+        # Past the #[inline] attribute
+        self.assertEqual(
+            function.doc_comment,
+            "Adds two numbers.\n\n# Arguments\n* `a` - The first number.\n"
+            "* `b` - The second number.\n\n# Returns\nThe sum of `a` and `b`.",
+        )
+        [trait] = parse_file(TEST_DATA / "test_rust_parser_5.rs").symbols
+        self.assertEqual(
+            [(s.name, s.doc_comment) for s in trait.walk()],
+            [
+                ("Summary", "A summary trait."),
+                ("author_summary", "Get the author summary."),
+                ("summarize", "Get the full summary."),
+            ],
+        )
+        [enum] = parse_file(TEST_DATA / "test_rust_parser_4.rs").symbols[:1]
+        self.assertEqual(enum.doc_comment, "An enum representing web events.")
+
+        result = parse_file(TEST_DATA / "test_rust_parser_13.rs")
+        self.assertEqual(
+            result.doc_comment,
+            "Crate documentation for the data analysis toolkit.\n"
+            "Contains modules for loading, processing, and visualizing data.",
+        )
+        docs = {s.name: s.doc_comment for s in result.walk() if s.doc_comment}
+        self.assertEqual(docs["DEFAULT_THRESHOLD"], "Global configuration constant")
+        self.assertEqual(
+            docs["process_data"],
+            "Processes loaded data.\nTODO: Implement actual processing logic.",
+        )
+
+        [module, *_] = parse_file(TEST_DATA / "rust_simple.rs").symbols
+        self.assertEqual(
+            module.doc_comment,
+            "A simple Rust program demonstrating language features\n"
+            "with some edge cases for parser testing",
+        )
+        self.assertIsNone(module.children[0].doc_comment)
+
+    def test_doc_comment_text(self):
+        source = """/**
+ * Lists:
+ *   - nested
+ *
+ *       indented code
+ */
+
+#[derive(Debug)]
+struct S;
+
+/** One line */
+mod m {
+    //! Inner docs of m,
+    //!     then code.
+    /*! And a block. */
+    fn f() {}
+}
+
+///Unspaced
+/// spaced
+fn g() {}
+"""
+        result = parse_symbols(source, Language.RUST)
+        self.assertEqual(result.issues, [])
+        self.assertIsNone(result.doc_comment)
+        struct, module, function = result.symbols
+        # Blank lines and attributes between the docs and the item are skipped
+        self.assertEqual(
+            struct.doc_comment, "Lists:\n  - nested\n\n      indented code"
+        )
+        self.assertEqual(
+            module.doc_comment,
+            "One line\nInner docs of m,\n    then code.\nAnd a block.",
+        )
+        self.assertIsNone(module.children[0].doc_comment)
+        self.assertEqual(function.doc_comment, "Unspaced\n spaced")
+
+    def test_lifetimes_and_chars(self):
+        source = """fn f<'a>(s: &'a str) -> char { let q = '\\''; '{' }
+fn g() -> &'static str { "}" }
+"""
+        result = parse_symbols(source, Language.RUST)
+        self.assertEqual(result.issues, [])
+        self.assertEqual(
+            outline(result.symbols), [("function", "f"), ("function", "g")]
+        )
+
+        result = parse_file(TEST_DATA / "test_rust_edge_cases_3.rs")
+        self.assertEqual(result.issues, [])
+        self.assertIn(
+            ("impl", "Clone for Ref<'a, T>", [("function", "clone")]),
+            outline(result.symbols),
+        )
+
+    def test_lifetime_spans(self):
+        # A lifetime lexed as a char literal swallows what follows it and shifts
+        # the end of the item
+        result = parse_file(TEST_DATA / "test_rust_edge_cases_3.rs")
+        self.assertEqual(result.issues, [])
+        self.assertEqual(
+            [(s.name, s.span.start_line, s.span.end_line) for s in result.walk()],
+            [
+                ("Ref", 2, 4),
+                ("reference", 3, 3),
+                ("RefPair", 7, 10),
+                ("ref1", 8, 8),
+                ("ref2", 9, 9),
+                ("longest", 13, 15),
+                ("StrSplit", 18, 21),
+                ("remainder", 19, 19),
+                ("delimiter", 20, 20),
+                ("StrSplit<'a, 'b>", 23, 46),
+                ("new", 24, 29),
+                ("next_token", 32, 45),
+                ("Wrapper", 49, 51),
+                ("value", 50, 50),
+                ("HELLO", 54, 54),
+                ("StaticRef", 56, 58),
+                ("data", 57, 57),
+                ("Matcher", 61, 63),
+                ("matches", 62, 62),
+                ("match_all", 65, 70),
+                ("Clone for Ref<'a, T>", 73, 79),
+                ("clone", 74, 78),
+                ("Slice", 82, 86),
+                ("start", 83, 83),
+                ("end", 84, 84),
+                ("phantom", 85, 85),
+                ("returns_str_slice", 89, 91),
+            ],
+        )
+        returns = result.symbols[-1]
+        self.assertEqual(
+            returns.signature.return_type, "impl Iterator<Item = &'a str> + 'a"
+        )
+        self.assertEqual((returns.span.end_line, returns.span.end_col), (91, 2))
+
+        # Lifetimes next to `,`, `>`, `:` and `+` without spaces
+        source = """fn a<'a,'b:'a>(x:&'a str)->impl Iterator<Item=&'a str>+'a{ x.lines() }
+struct P<'a>(&'a str,Foo<'a>);
+fn b() { 'o: loop { break 'o; } let c = 'a'; }
+"""
+        result = parse_symbols(source, Language.RUST)
+        self.assertEqual(result.issues, [])
+        a, p, b = result.symbols
+        self.assertEqual(
+            [(g.name, g.bounds) for g in a.generic_params],
+            [("'a", None), ("'b", "'a")],
+        )
+        self.assertEqual(a.signature.return_type, "impl Iterator<Item=&'a str>+'a")
+        self.assertEqual([f.name for f in p.children], ["0", "1"])
+        self.assertEqual(
+            [(s.span.start_line, s.span.end_col) for s in (a, p, b)],
+            [(1, 71), (2, 31), (3, 47)],
+        )
+
+    def test_raw_strings(self):
+        # The control file has plain strings where the fixture has raw ones
+        result = parse_file(TEST_DATA / "test_rust_raw_strings.rs")
+        control = parse_file(TEST_DATA / "test_rust_raw_strings_control.rs")
+        self.assertEqual(result.issues, [])
+        self.assertEqual(outline(result.symbols), outline(control.symbols))
+        self.assertEqual(
+            [(s.name, s.span.start_line, s.span.end_line) for s in result.walk()],
+            [(s.name, s.span.start_line, s.span.end_line) for s in control.walk()],
+        )
+        self.assertIn(("function", "r#type"), outline(result.symbols[3].children))
+
+        # An unterminated raw string may hold anything, so it ends with the file
+        source = """fn r#fn() {}
+const Q: &[u8] = br##"no "# end
+fn hidden() {}
+"""
+        result = parse_symbols(source, Language.RUST)
+        self.assertEqual(
+            outline(result.symbols), [("function", "r#fn"), ("const", "Q")]
+        )
+        self.assertEqual(
+            [(i.kind, i.span.start_line, i.span.start_col) for i in result.issues],
+            [
+                # The const's `;` is in the string
+                (IssueKind.INCOMPLETE_ITEM, 2, 1),
+                (IssueKind.UNTERMINATED_STRING, 2, 18),
+            ],
+        )
+
+    def test_attributes(self):
+        result = parse_file(TEST_DATA / "test_rust_edge_cases_6.rs")
+        attributes = {
+            (s.name, s.span.start_line): [(a.name, a.args_raw) for a in s.attributes]
+            for s in result.walk()
+            if s.attributes
+        }
+        self.assertEqual(
+            attributes,
+            {
+                ("Point", 2): [
+                    ("derive", "(Debug, Clone, PartialEq)"),
+                    ("repr", "(C)"),
+                ],
+                ("optimized_function", 10): [("inline", "(always)")],
+                ("linux_only", 16): [("cfg", '(target_os = "linux")')],
+                ("linux_only", 21): [("cfg", '(not(target_os = "linux"))')],
+                ("advanced_features", 27): [("cfg", '(feature = "advanced")')],
+            },
+        )
+        self.assertEqual(result.symbols[0].derives, ["Debug", "Clone", "PartialEq"])
+        self.assertEqual(result.attributes, [])
+
+        source = """#![allow(dead_code)]
+#![cfg_attr(test, feature(custom_test_frameworks))]
+#[derive(Debug, serde::Serialize)]
+#[derive(
+    Default,
+    PartialEq,
+)]
+#[doc = "A [bracket]."]
+struct S;
+
+#[cfg(test)]
+mod tests {
+    #![allow(unused)]
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn runs() {}
+
+    #[ test ]
+    fn plain() {}
+}
+"""
+        result = parse_symbols(source, Language.RUST)
+        self.assertEqual(result.issues, [])
+        # Inner attributes of the file
+        self.assertEqual(
+            [(a.name, a.args_raw, a.inner) for a in result.attributes],
+            [
+                ("allow", "(dead_code)", True),
+                ("cfg_attr", "(test, feature(custom_test_frameworks))", True),
+            ],
+        )
+        struct, module = result.symbols
+        self.assertEqual(
+            [(a.name, a.args_raw) for a in struct.attributes],
+            [
+                ("derive", "(Debug, serde::Serialize)"),
+                ("derive", "(\n    Default,\n    PartialEq,\n)"),
+                ("doc", '= "A [bracket]."'),
+            ],
+        )
+        self.assertEqual(
+            struct.derives, ["Debug", "serde::Serialize", "Default", "PartialEq"]
+        )
+        span = struct.attributes[1].span
+        self.assertEqual(
+            (span.start_line, span.start_col, span.end_line, span.end_col),
+            (4, 1, 7, 3),
+        )
+        # The inner attributes of a module follow its outer ones
+        self.assertEqual(
+            [(a.name, a.inner) for a in module.attributes],
+            [("cfg", False), ("allow", True)],
+        )
+        runs, plain = module.children
+        self.assertEqual(
+            [(a.name, a.args_raw) for a in runs.attributes + plain.attributes],
+            [("tokio::test", '(flavor = "multi_thread")'), ("test", None)],
+        )
+        self.assertEqual(runs.attributes[0].to_dict()["span"]["start_line"], 15)
+
+    def test_imports(self):
+        result = parse_file(TEST_DATA / "test_rust_parser_13.rs")
+        self.assertEqual(
+            [(i.path, i.span.start_line) for i in result.imports],
+            [
+                ("std::collections::HashMap", 9),
+                ("std::path::Path", 10),
+                ("std::path::PathBuf", 10),
+                # use std::{fs, io::{self, Read}};
+                ("std::fs", 11),
+                ("std::io", 11),
+                ("std::io::Read", 11),
+                # In mod data_source
+                ("super::AnalysisError", 93),
+            ],
+        )
+        nested = result.imports[3]
+        self.assertEqual(nested.path_segments, ["std", "fs"])
+        self.assertEqual(
+            (nested.span.start_col, nested.span.end_col),
+            (1, len("use std::{fs, io::{self, Read}};") + 1),
+        )
+
+        result = parse_file(TEST_DATA / "test_rust_parser_8.rs")
+        self.assertEqual(
+            [(i.path, i.alias, i.is_glob, i.is_pub) for i in result.imports],
+            [
+                ("std::collections::HashMap", None, False, False),
+                ("std::fmt", None, False, False),
+                ("std::fmt::Result", None, False, False),
+                ("std::io::Result", "IoResult", False, False),
+                ("crate::kinds::PrimaryColor", None, False, True),
+                ("std::cmp::*", None, True, False),
+            ],
+        )
+
+        source = """pub(crate) use ::core::{
+    fmt::{self as f, Write as _},
+    ops::*,
+    {cell::Cell},
+};
+use a::{b c, d};
+"""
+        result = parse_symbols(source, Language.RUST)
+        self.assertEqual(
+            [(i.path_segments, i.alias, i.is_glob, i.is_pub) for i in result.imports],
+            [
+                (["", "core", "fmt"], "f", False, True),
+                (["", "core", "fmt", "Write"], "_", False, True),
+                (["", "core", "ops"], None, True, True),
+                (["", "core", "cell", "Cell"], None, False, True),
+            ],
+        )
+        self.assertEqual(result.imports[0].to_dict()["span"]["end_line"], 5)
+
+        # The group that is never closed ends at println!
+        result = parse_file(VALIDATION_DATA / "validator_incomplete_syntax.rs")
+        self.assertEqual(
+            [i.path for i in result.imports], ["std::collections::HashMap"]
+        )
+
+    def test_modules(self):
+        source = """mod net {
+    pub mod http {
+        pub(crate) mod client {
+            pub fn get() {}
+            enum Method { Get, Post { body: String } }
+        }
+    }
+    impl<T> Point<T> { fn new() {} }
+    impl fmt::Display for Point { fn fmt(&self) {} }
+}
+mod utils;
+#[path = "io_impl.rs"]
+pub mod io;
+fn main() {
+    let x = 1;
+    /// Helpers of main
+    mod local {
+        pub fn helper() {}
+    }
+    if x > 0 { pub(crate) mod in_block; }
+    let y = [x; 2]; y[0];
+}
+"""
+        result = parse_symbols(source, Language.RUST)
+        self.assertEqual(result.issues, [])
+        self.assertEqual(
+            [(s.kind.value, s.qualified_name, s.visibility) for s in result.walk()],
+            [
+                ("module", "net", None),
+                ("module", "net::http", "pub"),
+                ("module", "net::http::client", "pub(crate)"),
+                ("function", "net::http::client::get", "pub"),
+                ("enum", "net::http::client::Method", None),
+                ("enum_variant", "net::http::client::Method::Get", None),
+                ("enum_variant", "net::http::client::Method::Post", None),
+                ("field", "net::http::client::Method::Post::body", None),
+                ("impl", "net::Point", None),
+                ("function", "net::Point::new", None),
+                ("impl", "<net::Point as fmt::Display>", None),
+                ("function", "<net::Point as fmt::Display>::fmt", None),
+                ("module_decl", "utils", None),
+                ("module_decl", "io", "pub"),
+                ("function", "main", None),
+                ("module", "main::local", None),
+                ("function", "main::local::helper", "pub"),
+                ("module_decl", "main::in_block", "pub(crate)"),
+            ],
+        )
+        declaration = result.symbols[2]
+        self.assertEqual(declaration.children, [])
+        self.assertEqual(
+            (declaration.attributes[0].name, declaration.attributes[0].args_raw),
+            ("path", '= "io_impl.rs"'),
+        )
+        local = result.symbols[3].children[0]
+        self.assertEqual(local.doc_comment, "Helpers of main")
+        self.assertEqual((local.span.start_line, local.span.end_line), (17, 19))
+
+        # A module inside an unclosed function body ends with it
+        result = parse_symbols("fn f() {\n    mod m {\n        fn g() {}\n", "rust")
+        self.assertEqual(
+            [s.qualified_name for s in result.walk()], ["f", "f::m", "f::m::g"]
+        )
+        self.assertEqual(
+            [issue.kind for issue in result.issues],
+            [IssueKind.UNCLOSED_DELIMITER, IssueKind.UNCLOSED_DELIMITER],
+        )
+
+        result = parse_file(TEST_DATA / "rust_simple.rs")
+        names = [s.qualified_name for s in result.walk()]
+        self.assertIn("utils::add", names)
+        self.assertIn("<Container as Describable>::describe", names)
+        result = parse_file(TEST_DATA / "test_rust_parser_13.rs")
+        self.assertIn(
+            "visualization::plot_data", [s.qualified_name for s in result.walk()]
+        )
+
+        # A declared test module is a test module
+        tests = find_tests("#[cfg(test)]\nmod tests;\n")
+        self.assertEqual(
+            [(t.kind, t.path) for t in tests], [(SymbolKind.MODULE, "tests")]
+        )
+
+    def test_find_tests(self):
+        source = """fn helper() {}
+
+#[test]
+fn top_level() {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn runs_async() {}
+
+    #[test]
+    #[ignore = "slow"]
+    fn slow() {}
+
+    #[rstest]
+    #[case(1)]
+    fn cases(#[case] n: u32) {}
+
+    fn not_a_test() {}
+
+    mod parsing {
+        #[async_std::test]
+        async fn nested() {}
+    }
+}
+
+#[cfg(not(test))]
+mod real {
+    #[my_crate :: test]
+    fn custom() {}
+}
+"""
+        tests = find_tests(source)
+        self.assertEqual(
+            [
+                (t.kind.value, t.path, t.attribute, t.is_async, t.ignored)
+                for t in tests
+            ],
+            [
+                ("function", "top_level", "test", False, False),
+                ("module", "tests", None, False, False),
+                ("function", "tests::runs_async", "tokio::test", True, False),
+                ("function", "tests::slow", "test", False, True),
+                ("function", "tests::cases", "rstest", False, False),
+                ("module", "tests::parsing", None, False, False),
+                ("function", "tests::parsing::nested", "async_std::test", True, False),
+                ("function", "real::custom", "my_crate::test", False, False),
+            ],
+        )
+        # Spans are those of the symbols, from the first attribute
+        module = tests[1]
+        self.assertEqual(module.name, "tests")
+        self.assertEqual((module.span.start_line, module.span.end_line), (6, 27))
+        self.assertEqual((tests[2].span.start_line, tests[2].span.end_line), (10, 11))
+        self.assertEqual(tests[3].to_dict()["span"]["start_line"], 13)
+        self.assertEqual(find_tests("fn main() {}", "rust"), [])
+        with self.assertRaises(ValueError):
+            find_tests("", "cobol")
+
+    def test_macro_rules(self):
+        result = parse_file(TEST_DATA / "rust_complex.rs")
+        nested_vec, accessors, processor = result.symbols[:3]
+        self.assertEqual(
+            [
+                (s.kind, s.name, [a.name for a in s.attributes])
+                for s in (nested_vec, accessors)
+            ],
+            [
+                (SymbolKind.MACRO, "nested_vec", []),
+                (SymbolKind.MACRO, "generate_accessors", ["macro_export"]),
+            ],
+        )
+        # From the attribute to the closing brace
+        self.assertEqual(
+            [(s.span.start_line, s.span.end_line) for s in (nested_vec, accessors)],
+            [(23, 42), (45, 62)],
+        )
+        self.assertEqual((accessors.span.start_col, accessors.span.end_col), (1, 2))
+        # The fn and impl in the transcriber are no items of the file
+        self.assertEqual(
+            outline([processor]),
+            [
+                (
+                    "trait",
+                    "DataProcessor",
+                    [
+                        ("type_alias", "Output"),
+                        ("type_alias", "Error"),
+                        ("function", "process"),
+                        ("function", "process_all"),
+                    ],
+                )
+            ],
+        )
+        self.assertEqual([i for i in result.issues if i.span.start_line < 91], [])
+
+        source = """mod m {
+    /// Pairs.
+    macro_rules! pairs ( $( ($a:expr, $b:expr) ),* ] => { [$(($a, $b)),*] } );
+    macro_rules! unbalanced {
+        ({ $($t:tt)* }) => { fn ok() {} };
+    }
+    fn after() {}
+}
+macro_rules! truncated
+fn last() {}
+"""
+        result = parse_symbols(source, Language.RUST)
+        self.assertEqual(
+            outline(result.symbols),
+            [
+                (
+                    "module",
+                    "m",
+                    [
+                        ("macro", "pairs"),
+                        ("macro", "unbalanced"),
+                        ("function", "after"),
+                    ],
+                ),
+                ("macro", "truncated"),
+                ("function", "last"),
+            ],
+        )
+        pairs = result.symbols[0].children[0]
+        self.assertEqual(pairs.doc_comment, "Pairs.")
+        self.assertEqual((pairs.span.start_line, pairs.span.end_col), (3, 79))
+        self.assertEqual(
+            [(i.kind, i.span.start_line) for i in result.issues],
+            [
+                # The `]` of the matcher closes nothing
+                (IssueKind.UNEXPECTED_DELIMITER, 3),
+                (IssueKind.INCOMPLETE_ITEM, 9),
+            ],
+        )
+
+    def test_enum_variants(self):
+        def variants(enum):
+            return [
+                (v.name, v.shape, [(f.name, f.field_type) for f in v.children])
+                for v in enum.children
+            ]
+
+        web_event = parse_file(TEST_DATA / "test_rust_parser_4.rs").symbols[0]
+        self.assertEqual(
+            variants(web_event),
+            [
+                ("PageLoad", Shape.UNIT, []),
+                ("KeyPress", Shape.TUPLE, [("0", "char")]),
+                ("Click", Shape.STRUCT, [("x", "i64"), ("y", "i64")]),
+            ],
+        )
+        self.assertEqual(
+            [v.kind for v in web_event.children], [SymbolKind.ENUM_VARIANT] * 3
+        )
+        self.assertEqual(
+            [(v.span.start_col, v.span.end_col) for v in web_event.children],
+            [(5, 13), (5, 19), (5, 29)],
+        )
+
+        message = next(
+            s
+            for s in parse_file(TEST_DATA / "rust_complex.rs").symbols
+            if s.name == "Message"
+        )
+        self.assertEqual(
+            variants(message),
+            [
+                ("Text", Shape.TUPLE, [("0", "String")]),
+                ("Binary", Shape.TUPLE, [("0", "Vec<u8>")]),
+                (
+                    "Structured",
+                    Shape.STRUCT,
+                    [
+                        ("id", "u64"),
+                        ("timestamp", "u64"),
+                        ("data", "T"),
+                        ("tags", "Vec<String>"),
+                    ],
+                ),
+                ("Empty", Shape.UNIT, []),
+            ],
+        )
+        structured = message.children[2]
+        self.assertEqual(
+            (structured.span.start_line, structured.span.end_line), (106, 111)
+        )
+        self.assertEqual(
+            [(f.span.start_line, f.kind) for f in structured.children],
+            [(line, SymbolKind.FIELD) for line in range(107, 111)],
+        )
+
+        # The truncated variants are kept, each with its unclosed delimiter
+        result = parse_file(VALIDATION_DATA / "validator_incomplete_syntax.rs")
+        status = result.symbols[1]
+        self.assertEqual(
+            variants(status),
+            [
+                ("Active", Shape.UNIT, []),
+                ("Pending", Shape.UNIT, []),
+                ("Completed", Shape.TUPLE, []),
+                ("Failed", Shape.STRUCT, []),
+            ],
+        )
+        self.assertEqual(
+            [
+                (issue.kind, issue.span.start_line, issue.span.start_col)
+                for issue in result.issues
+                if 10 <= issue.span.start_line <= 15
+            ],
+            [
+                (IssueKind.UNCLOSED_DELIMITER, 10, 13),
+                (IssueKind.UNCLOSED_DELIMITER, 13, 14),
+                (IssueKind.UNCLOSED_DELIMITER, 15, 12),
+            ],
+        )
+
+        source = """enum E<T> {
+    /// The first.
+    #[default]
+    A = 1 << 2,
+    B(#[allow(unused)] pub(crate) HashMap<String, Vec<T>>, fn(u8) -> u8),
+    C {
+        /// A field.
+        #[serde(rename = "x")]
+        pub x: Option<T>,
+        y:,
+    },
+}
+"""
+        result = parse_symbols(source, Language.RUST)
+        a, b, c = result.symbols[0].children
+        self.assertEqual(
+            (a.doc_comment, [x.name for x in a.attributes]), ("The first.", ["default"])
+        )
+        # From the attribute to the end of the discriminant
+        self.assertEqual(
+            (a.span.start_line, a.span.end_line, a.span.end_col), (3, 4, 16)
+        )
+        self.assertEqual(
+            [(f.name, f.visibility, f.field_type) for f in b.children],
+            [
+                ("0", "pub(crate)", "HashMap<String, Vec<T>>"),
+                ("1", None, "fn(u8) -> u8"),
+            ],
+        )
+        self.assertEqual([x.name for x in b.children[0].attributes], ["allow"])
+        x, y = c.children
+        self.assertEqual(
+            (x.doc_comment, x.visibility, x.field_type, x.span.start_line),
+            ("A field.", "pub", "Option<T>", 8),
+        )
+        self.assertIsNone(y.field_type)
+        self.assertEqual(
+            [(i.kind, i.span.start_line) for i in result.issues],
+            [(IssueKind.INCOMPLETE_ITEM, 10)],
+        )
+        self.assertEqual(
+            (a.to_dict()["shape"], y.to_dict()["field_type"]), ("unit", None)
+        )
+        self.assertNotIn("shape", result.symbols[0].to_dict())
+
+    def test_struct_fields(self):
+        def fields(struct):
+            return [(f.name, f.visibility, f.field_type) for f in struct.children]
+
+        point, unit, color, generic = parse_file(
+            TEST_DATA / "test_rust_parser_3.rs"
+        ).symbols
+        self.assertEqual(
+            [(s.name, s.shape, s.generics) for s in (point, unit, color, generic)],
+            [
+                ("Point", Shape.STRUCT, None),
+                ("Unit", Shape.UNIT, None),
+                ("Color", Shape.TUPLE, None),
+                ("GenericPoint", Shape.STRUCT, "<T>"),
+            ],
+        )
+        self.assertEqual(fields(point), [("x", None, "f64"), ("y", None, "f64")])
+        self.assertEqual(fields(color), [(str(i), None, "u8") for i in range(3)])
+        self.assertEqual(unit.children, [])
+        self.assertEqual(
+            [(f.kind, f.span.start_line, f.span.start_col) for f in point.children],
+            [(SymbolKind.FIELD, 2, 5), (SymbolKind.FIELD, 3, 5)],
+        )
+
+        repository = next(
+            s
+            for s in parse_file(TEST_DATA / "rust_complex.rs").symbols
+            if s.name == "Repository" and s.kind == SymbolKind.STRUCT
+        )
+        self.assertEqual(repository.generics, "<'a, T, E, S = Vec<T>>")
+        self.assertEqual(
+            fields(repository),
+            [
+                ("name", None, "&'a str"),
+                ("data", None, "S"),
+                ("error_handler", None, "Box<dyn Fn(E) -> String + 'a>"),
+                ("_phantom", None, "PhantomData<E>"),
+            ],
+        )
+
+        source = """pub struct Config<'a> {
+    /// The name.
+    #[serde(default)]
+    pub name: &'a str,
+    pub(crate) limit: usize,
+    let value: i32,
+    flags: Vec<u8>,
+}
+pub struct Wrapper<T>(pub T, pub(super) Box<T>) where T: Clone;
+"""
+        result = parse_symbols(source, Language.RUST)
+        config, wrapper = result.symbols
+        self.assertEqual(
+            fields(config),
+            [
+                ("name", "pub", "&'a str"),
+                ("limit", "pub(crate)", "usize"),
+                ("flags", None, "Vec<u8>"),
+            ],
+        )
+        name = config.children[0]
+        self.assertEqual(
+            (name.doc_comment, [a.name for a in name.attributes]),
+            ("The name.", ["serde"]),
+        )
+        self.assertEqual((name.span.start_line, name.span.end_line), (3, 4))
+        self.assertEqual(
+            [(i.kind, i.span.start_line) for i in result.issues],
+            [(IssueKind.UNEXPECTED_TOKEN, 6)],
+        )
+        # Up to the `;` after the where clause
+        self.assertEqual(
+            (wrapper.shape, wrapper.generics, wrapper.span.end_col),
+            (Shape.TUPLE, "<T>", 64),
+        )
+        self.assertEqual(
+            fields(wrapper), [("0", "pub", "T"), ("1", "pub(super)", "Box<T>")]
+        )
+        self.assertEqual(
+            (config.to_dict()["shape"], config.to_dict()["generics"]),
+            ("struct", "<'a>"),
+        )
+
+    def test_generic_params(self):
+        def params(symbol):
+            return [
+                (g.kind, g.name, g.bounds, g.const_type, g.default)
+                for g in symbol.generic_params
+            ]
+
+        symbols = parse_file(TEST_DATA / "test_rust_edge_cases_6.rs").symbols
+        array = next(s for s in symbols if s.name == "Array")
+        self.assertEqual(array.generics, "<T, const N: usize>")
+        self.assertEqual(
+            params(array),
+            [
+                (GenericKind.TYPE, "T", None, None, None),
+                (GenericKind.CONST, "N", None, "usize", None),
+            ],
+        )
+        default = next(s for s in symbols if s.name == "Default for Array<T, N>")
+        self.assertEqual(
+            (default.impl_type, default.impl_trait, default.generics),
+            ("Array", "Default", "<T: Default + Copy, const N: usize>"),
+        )
+        self.assertEqual(
+            params(default),
+            [
+                (GenericKind.TYPE, "T", "Default + Copy", None, None),
+                (GenericKind.CONST, "N", None, "usize", None),
+            ],
+        )
+        self.assertEqual([c.name for c in default.children], ["default"])
+
+        symbols = parse_file(TEST_DATA / "rust_complex.rs").symbols
+        repository = next(s for s in symbols if s.name == "Repository")
+        self.assertEqual(
+            params(repository),
+            [
+                (GenericKind.LIFETIME, "'a", None, None, None),
+                (GenericKind.TYPE, "T", None, None, None),
+                (GenericKind.TYPE, "E", None, None, None),
+                (GenericKind.TYPE, "S", None, None, "Vec<T>"),
+            ],
+        )
+        cache = next(s for s in symbols if s.name == "Cache<K, V>")
+        self.assertEqual(
+            [(g.name, g.bounds) for g in cache.generic_params],
+            [("K", "Eq + std::hash::Hash + Clone"), ("V", "Clone")],
+        )
+
+        symbols = parse_file(TEST_DATA / "test_rust_edge_cases_3.rs").symbols
+        static_ref = next(s for s in symbols if s.name == "StaticRef")
+        self.assertEqual(
+            params(static_ref), [(GenericKind.TYPE, "T", "'static", None, None)]
+        )
+        match_all = next(s for s in symbols if s.name == "match_all")
+        self.assertEqual(match_all.generic_params, match_all.signature.generics)
+        self.assertEqual([g.name for g in match_all.generic_params], ["'a", "T", "M"])
+
+        # Higher-ranked bounds, `->` and nested `<>` in bounds, block defaults
+        source = """fn apply<
+    'a,
+    F: for<'b> Fn(&'b str) -> &'b str + 'a,
+    const M: usize = { 2 * 3 },
+>(f: F) {}
+trait Source<T: ?Sized + Iterator<Item = Vec<u8>>> {}
+impl<'a, 'b: 'a, M> Matcher<&'a str> for Wrap<'b, M> where M: for<'c> Matcher<&'c str> {}
+struct Grid<T, const W: usize = 4, S = Vec<Vec<T>>>(S);
+"""
+        result = parse_symbols(source, Language.RUST)
+        self.assertEqual(result.issues, [])
+        apply, source_trait, matcher, grid = result.symbols
+        self.assertEqual(
+            params(apply),
+            [
+                (GenericKind.LIFETIME, "'a", None, None, None),
+                (GenericKind.TYPE, "F", "for<'b> Fn(&'b str) -> &'b str + 'a")
+                + (None, None),
+                (GenericKind.CONST, "M", None, "usize", "{ 2 * 3 }"),
+            ],
+        )
+        self.assertEqual([p.pattern for p in apply.signature.params], ["f"])
+        self.assertEqual(
+            params(source_trait),
+            [(GenericKind.TYPE, "T", "?Sized + Iterator<Item = Vec<u8>>", None, None)],
+        )
+        self.assertEqual(
+            (matcher.name, params(matcher)[1]),
+            (
+                "Matcher<&'a str> for Wrap<'b, M>",
+                (GenericKind.LIFETIME, "'b", "'a", None, None),
+            ),
+        )
+        self.assertEqual(
+            [(g.name, g.default) for g in grid.generic_params],
+            [("T", None), ("W", "4"), ("S", "Vec<Vec<T>>")],
+        )
+        self.assertEqual(grid.shape, Shape.TUPLE)
+        self.assertEqual(
+            grid.to_dict()["generic_params"][1],
+            {
+                "kind": "const",
+                "name": "W",
+                "bounds": None,
+                "const_type": "usize",
+                "default": "4",
+            },
+        )
+
+    def test_where_clauses(self):
+        def predicates(symbol):
+            return [(p.bounded, p.bounds) for p in symbol.where_predicates]
+
+        repository = next(
+            s
+            for s in parse_file(TEST_DATA / "rust_complex.rs").symbols
+            if s.name == "Repository"
+        )
+        self.assertEqual(
+            predicates(repository),
+            [("T", "Clone + 'a"), ("E", "Display"), ("S", "AsRef<[T]> + Default")],
+        )
+        self.assertEqual(repository.where_clause.splitlines()[0], "where")
+        self.assertEqual(len(repository.children), 4)
+
+        # Braces in const arguments and closures do not open the body
+        source = """fn pad<const N: usize>(buf: Buf<N>) -> Buf<{ N + 1 }>
+where
+    Buf<{ N + 1 }>: Default,
+    [(); { let f = |x: usize| x + 1; f(N) }]: Sized,
+{
+    mod inner {}
+}
+trait Store: Clone + Send
+where
+    Self: Sized,
+{
+    fn get(&self);
+}
+pub struct Pair<T>(T, T)
+where
+    T: Copy;
+"""
+        result = parse_symbols(source, Language.RUST)
+        self.assertEqual(result.issues, [])
+        pad, store, pair = result.symbols
+        self.assertEqual(
+            predicates(pad),
+            [
+                ("Buf<{ N + 1 }>", "Default"),
+                ("[(); { let f = |x: usize| x + 1; f(N) }]", "Sized"),
+            ],
+        )
+        self.assertEqual(pad.signature.where_predicates, pad.where_predicates)
+        self.assertEqual(pad.signature.return_type, "Buf<{ N + 1 }>")
+        self.assertEqual(
+            [(s.name, s.span.start_line, s.span.end_line) for s in result.walk()],
+            [
+                ("pad", 1, 7),
+                ("inner", 6, 6),
+                ("Store", 8, 13),
+                ("get", 12, 12),
+                ("Pair", 14, 16),
+                ("0", 14, 14),
+                ("1", 14, 14),
+            ],
+        )
+        self.assertEqual(
+            (store.where_clause, predicates(store)),
+            ("where\n    Self: Sized,", [("Self", "Sized")]),
+        )
+        self.assertEqual(predicates(pair), [("T", "Copy")])
+        self.assertEqual(
+            pair.to_dict()["where_predicates"], [{"bounded": "T", "bounds": "Copy"}]
+        )
+
+        # A trailing `+` and missing bounds are reported, and the items go on
+        source = """impl<T> Pair<T> where T: Copy + {
+    fn first(&self) {}
+}
+enum Side<T> where T {
+    Left,
+}
+"""
+        result = parse_symbols(source, Language.RUST)
+        pair, side = result.symbols
+        self.assertEqual(
+            (predicates(pair), predicates(side)), ([("T", "Copy")], [("T", None)])
+        )
+        self.assertEqual([c.name for c in pair.children], ["first"])
+        self.assertEqual([c.name for c in side.children], ["Left"])
+        self.assertEqual(
+            [(i.kind, i.span.start_line, i.span.start_col) for i in result.issues],
+            [
+                (IssueKind.INCOMPLETE_BOUNDS, 1, 31),
+                (IssueKind.INCOMPLETE_BOUNDS, 4, 20),
+            ],
+        )
+
+    def test_incomplete_where_clause(self):
+        result = parse_file(VALIDATION_DATA / "validator_incomplete_syntax.rs")
+        names = [s.name for s in result.symbols]
+        generic_function, fetch_data = result.symbols[
+            names.index("generic_function") :
+        ]
+        # `T: Clone +` and a comment, then the next function
+        self.assertEqual(
+            (generic_function.span.start_line, generic_function.span.end_line),
+            (89, 91),
+        )
+        self.assertEqual(generic_function.where_clause, "where \n    T: Clone +")
+        self.assertEqual(
+            [(p.bounded, p.bounds) for p in generic_function.where_predicates],
+            [("T", "Clone")],
+        )
+        self.assertEqual(
+            [
+                (i.kind, i.span.start_line, i.span.start_col)
+                for i in result.issues
+                if 89 <= i.span.start_line <= 93
+            ],
+            [
+                (IssueKind.INCOMPLETE_ITEM, 89, 1),
+                (IssueKind.INCOMPLETE_BOUNDS, 91, 14),
+            ],
+        )
+        # The function after it is parsed as before
+        self.assertEqual(
+            (fetch_data.name, fetch_data.is_async, fetch_data.span.start_line),
+            ("fetch_data", True, 95),
+        )
+        self.assertEqual(fetch_data.signature.params[0].pattern, "url")
+        self.assertEqual(fetch_data.signature.return_type, "Result<String, Error>")
+
+    def test_trait_items(self):
+        def trait(path, name):
+            return next(
+                s
+                for s in parse_file(path).symbols
+                if s.kind == SymbolKind.TRAIT and s.name == name
+            )
+
+        def items(trait):
+            return [
+                (c.kind.value, c.name, c.has_default_body, c.declared_type, c.bounds)
+                for c in trait.children
+            ]
+
+        summary = trait(TEST_DATA / "test_rust_parser_5.rs", "Summary")
+        self.assertEqual(
+            items(summary),
+            [
+                ("function", "author_summary", False, None, None),
+                ("function", "summarize", True, None, None),
+            ],
+        )
+        self.assertEqual(
+            [(c.span.start_line, c.span.end_line) for c in summary.children],
+            [(4, 4), (7, 9)],
+        )
+
+        # A where clause between the header and the body
+        processor = trait(TEST_DATA / "rust_complex.rs", "DataProcessor")
+        self.assertEqual(processor.generics, "<T>")
+        self.assertEqual((processor.span.start_line, processor.span.end_line), (65, 80))
+        self.assertEqual(
+            items(processor),
+            [
+                ("type_alias", "Output", None, None, "Debug"),
+                ("type_alias", "Error", None, "String", "Display + Debug"),
+                ("function", "process", False, None, None),
+                ("function", "process_all", True, None, None),
+            ],
+        )
+        self.assertEqual(
+            [(c.span.start_line, c.span.end_line) for c in processor.children],
+            [(69, 69), (70, 70), (72, 72), (75, 79)],
+        )
+        # The bounds of a generic associated type are in its where clause
+        self.assertEqual(
+            items(trait(TEST_DATA / "rust_complex.rs", "AsyncProcessor")),
+            [
+                ("type_alias", "Input", None, None, None),
+                ("type_alias", "Output", None, None, None),
+                ("function", "process_async", False, None, None),
+            ],
+        )
+
+        processor = trait(
+            VALIDATION_DATA / "validator_complex_features.rs", "DataProcessor"
+        )
+        self.assertEqual(
+            (processor.visibility, processor.span.start_line, processor.span.end_line),
+            ("pub", 17, 26),
+        )
+        self.assertEqual(
+            items(processor),
+            [
+                ("type_alias", "Output", None, None, "Clone + Debug + Send"),
+                ("type_alias", "Error", None, None, "Debug + Display"),
+                ("function", "process", False, None, None),
+                ("function", "validate", False, None, None),
+            ],
+        )
+        path = VALIDATION_DATA / "validator_complex_features.rs"
+        self.assertEqual(
+            items(trait(path, "AsyncProcessor"))[2:],
+            [
+                ("type_alias", "Error", None, None, "Send + Debug + Display"),
+                ("function", "process", False, None, None),
+                ("function", "process_with_retry", True, None, None),
+            ],
+        )
+
+        physics = trait(TEST_DATA / "test_rust_edge_cases_2.rs", "Physics")
+        gravity = physics.children[0]
+        self.assertEqual(
+            (gravity.kind, gravity.name, gravity.declared_type, gravity.default_value),
+            (SymbolKind.CONST, "GRAVITY", "f64", "9.81"),
+        )
+        self.assertEqual(gravity.to_dict()["declared_type"], "f64")
+        self.assertEqual(
+            items(physics)[1:], [("function", "calculate_force", False, None, None)]
+        )
+        self.assertEqual(
+            items(trait(TEST_DATA / "test_rust_edge_cases_2.rs", "Printable")),
+            [("function", "print", True, None, None)],
+        )
+        self.assertEqual(
+            items(trait(TEST_DATA / "test_rust_edge_cases_2.rs", "Iterator")),
+            [
+                ("type_alias", "Item", None, None, None),
+                ("function", "next", False, None, None),
+                ("function", "map", True, None, None),
+            ],
+        )
+        self.assertEqual(
+            items(trait(TEST_DATA / "rust_simple.rs", "Describable")),
+            [
+                ("function", "describe", False, None, None),
+                ("function", "summary", True, None, None),
+            ],
+        )
+        self.assertEqual(
+            items(trait(TEST_DATA / "test_rust_edge_cases_6.rs", "Container")),
+            [
+                ("type_alias", "Item", None, None, None),
+                ("function", "get", False, None, None),
+                ("function", "insert", False, None, None),
+            ],
+        )
+        self.assertEqual(
+            items(trait(TEST_DATA / "test_rust_parser_13.rs", "DataLoader"))[:2],
+            [
+                ("type_alias", "Item", None, None, None),
+                ("function", "load", False, None, None),
+            ],
+        )
+        # Bodies left open still count as default implementations
+        self.assertEqual(
+            items(trait(TEST_DATA / "test_rust_edge_cases_7.rs", "MyTrait")),
+            [
+                ("function", "required_method", False, None, None),
+                ("function", "optional_method", True, None, None),
+            ],
+        )
+        path = VALIDATION_DATA / "validator_incomplete_syntax.rs"
+        self.assertEqual(
+            items(trait(path, "DataProcessor")),
+            [
+                ("function", "process", False, None, None),
+                ("function", "validate", None, None, None),
+            ],
+        )
+
+        # Unclosed generics and a mangled method end the trait at the method,
+        # with the enum after it intact; `T:` lacks its bounds
+        result = parse_file(TEST_DATA / "rust_complex.rs")
+        names = [s.name for s in result.symbols]
+        incomplete = result.symbols[names.index("IncompleteTrait")]
+        self.assertEqual(
+            (incomplete.span.start_line, incomplete.span.end_line), (91, 95)
+        )
+        self.assertEqual(items(incomplete), [("function", "sync", None, None, None)])
+        self.assertEqual(
+            [
+                (i.kind, i.span.start_line)
+                for i in result.issues
+                if 91 <= i.span.start_line <= 95
+            ],
+            [
+                (IssueKind.INCOMPLETE_BOUNDS, 93),
+                (IssueKind.UNCLOSED_DELIMITER, 94),
+                (IssueKind.INCOMPLETE_ITEM, 95),
+                (IssueKind.UNCLOSED_DELIMITER, 95),
+                (IssueKind.UNCLOSED_DELIMITER, 95),
+            ],
+        )
+        message = result.symbols[names.index("IncompleteTrait") + 1]
+        self.assertEqual(
+            (message.kind, message.name, message.span.start_line),
+            (SymbolKind.ENUM, "Message", 102),
+        )
+
+        source = """trait Limits {
+    const MAX: usize;
+    const NAMES: [&str; 2] = ["a", "b"];
+    type Map<K>: Iterator<Item = (K, u8)> = HashMap<K, u8> where K: Hash;
+    fn check<T>(&self, value: T) -> bool
+    where
+        T: Into<usize>,
+    {
+        value.into() <= Self::MAX
+    }
+}
+impl Limits for () {
+    fn check<T>(&self, value: T) -> bool;
+}
+static mut COUNT: u32 = 0;
+"""
+        limits, impl, count = parse_symbols(source, Language.RUST).symbols
+        constant, names, alias, check = limits.children
+        self.assertEqual(
+            [(c.declared_type, c.default_value) for c in (constant, names)],
+            [("usize", None), ("[&str; 2]", '["a", "b"]')],
+        )
+        self.assertEqual(
+            (alias.bounds, alias.declared_type),
+            ("Iterator<Item = (K, u8)>", "HashMap<K, u8>"),
+        )
+        self.assertEqual(
+            (check.has_default_body, check.span.start_line, check.span.end_line),
+            (True, 5, 10),
+        )
+        # Only the functions of traits have it
+        self.assertIsNone(impl.children[0].has_default_body)
+        self.assertEqual((count.declared_type, count.default_value), ("u32", "0"))
+        self.assertEqual(
+            {key: alias.to_dict()[key] for key in ("bounds", "declared_type")},
+            {"bounds": "Iterator<Item = (K, u8)>", "declared_type": "HashMap<K, u8>"},
+        )
+
+    def test_fn_signatures(self):
+        def params(function):
+            return [(p.pattern, p.param_type) for p in function.signature.params]
+
+        source = """struct Buffer;
+impl Buffer {
+    pub const unsafe fn new<'a, T: Clone + Send = String, const N: usize>(
+        (a, b): (u8, u8),
+        mut data: Vec<T>,
+        #[allow(unused)] _: [u8; N],
+        map: impl Fn(u8) -> u8,
+    ) -> Result<Self, Box<dyn Error + 'a>> {
+        todo!()
+    }
+    async unsafe fn read(&mut self) {}
+    fn take(self) -> u8 { 0 }
+    fn consume(mut self, n: usize) {}
+    fn borrow<'a>(&'a mut self) -> &'a [u8] { &[] }
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()>;
+    fn size(&self) -> usize where Self: Sized { 0 }
+}
+extern "system" fn callback(code: i32) {}
+extern fn plain() {}
+"""
+        impl = parse_symbols(source, Language.RUST).symbols[1]
+        new, read, take, consume, borrow, poll, size = impl.children
+        signature = new.signature
+        self.assertEqual(
+            (signature.is_const, signature.is_unsafe, signature.is_async),
+            (True, True, False),
+        )
+        self.assertEqual(
+            [
+                (g.kind, g.name, g.bounds, g.const_type, g.default)
+                for g in signature.generics
+            ],
+            [
+                (GenericKind.LIFETIME, "'a", None, None, None),
+                (GenericKind.TYPE, "T", "Clone + Send", None, "String"),
+                (GenericKind.CONST, "N", None, "usize", None),
+            ],
+        )
+        self.assertIsNone(signature.receiver)
+        self.assertEqual(
+            params(new),
+            [
+                ("(a, b)", "(u8, u8)"),
+                ("mut data", "Vec<T>"),
+                ("_", "[u8; N]"),
+                ("map", "impl Fn(u8) -> u8"),
+            ],
+        )
+        self.assertEqual(
+            [(p.span.start_line, p.span.start_col) for p in signature.params],
+            [(4, 9), (5, 9), (6, 26), (7, 9)],
+        )
+        self.assertEqual(signature.return_type, "Result<Self, Box<dyn Error + 'a>>")
+        self.assertEqual((new.span.start_line, new.span.end_line), (3, 10))
+
+        self.assertEqual(
+            (read.is_async, read.signature.is_async, read.signature.is_unsafe),
+            (True, True, True),
+        )
+        self.assertEqual(
+            [
+                (f.signature.receiver, params(f), f.signature.return_type)
+                for f in (read, take, consume, borrow, poll, size)
+            ],
+            [
+                ("&mut self", [], None),
+                ("self", [], "u8"),
+                ("mut self", [("n", "usize")], None),
+                ("&'a mut self", [], "&'a [u8]"),
+                ("self: Pin<&mut Self>", [("cx", "&mut Context<'_>")], "Poll<()>"),
+                ("&self", [], "usize"),
+            ],
+        )
+        self.assertEqual(size.signature.where_clause, "where Self: Sized")
+        callback, plain = parse_symbols(source, Language.RUST).symbols[2:]
+        self.assertEqual(
+            (callback.signature.abi, plain.signature.abi, size.signature.abi),
+            ("system", "C", None),
+        )
+
+        # Multi-line where clauses, as written and as predicates
+        symbols = parse_file(VALIDATION_DATA / "validator_complex_features.rs").symbols
+        analyze = next(s for s in symbols if s.name == "analyze_result")
+        self.assertEqual(
+            analyze.signature.where_clause, "where\n    T: Debug,\n    E: Debug,"
+        )
+        self.assertEqual(
+            [(p.bounded, p.bounds) for p in analyze.signature.where_predicates],
+            [("T", "Debug"), ("E", "Debug")],
+        )
+        self.assertEqual([g.name for g in analyze.signature.generics], ["T", "E"])
+        self.assertEqual(params(analyze), [("result", "&ProcessingResult<T, E>")])
+        self.assertEqual(analyze.signature.return_type, "&'static str")
+        processor = next(s for s in symbols if s.name == "AsyncProcessor")
+        retry = processor.children[-1]
+        self.assertEqual(
+            (retry.name, retry.signature.is_async, retry.signature.receiver),
+            ("process_with_retry", True, "&self"),
+        )
+        self.assertEqual(
+            [(p.bounded, p.bounds) for p in retry.signature.where_predicates],
+            [("Self::Input", "Clone")],
+        )
+        memory_copy = next(s for s in symbols if s.name == "memory_copy")
+        self.assertTrue(memory_copy.signature.is_unsafe)
+        self.assertEqual(memory_copy.signature.generics[0].bounds, "Copy")
+
+        symbols = parse_file(TEST_DATA / "test_rust_edge_cases_3.rs").symbols
+        match_all = next(s for s in symbols if s.name == "match_all")
+        self.assertEqual(
+            [(p.bounded, p.bounds) for p in match_all.signature.where_predicates],
+            [("M", "for<'b> Matcher<&'b T>")],
+        )
+
+        # Unclosed parameter lists keep the parameters before the next item
+        result = parse_file(VALIDATION_DATA / "validator_incomplete_syntax.rs")
+        process_data = result.symbols[2]
+        self.assertEqual(process_data.name, "process_data")
+        self.assertEqual(params(process_data), [("data", "Vec<u32>")])
+        self.assertIsNone(process_data.signature.return_type)
+        self.assertIn(
+            (IssueKind.UNCLOSED_DELIMITER, 19, 16),
+            [(i.kind, i.span.start_line, i.span.start_col) for i in result.issues],
+        )
+        processor = next(s for s in result.symbols if s.name == "DataProcessor")
+        validate = processor.children[1]
+        self.assertEqual(
+            (validate.signature.receiver, params(validate)),
+            ("&self", [("input", "&str")]),
+        )
+        result = parse_file(TEST_DATA / "test_rust_edge_cases_7.rs")
+        self.assertEqual(params(result.symbols[0]), [("x", "i32")])
+        # A signature whose list runs into its body stops at the `->`
+        result = parse_file(TEST_DATA / "rust_complex.rs")
+        incomplete = next(s for s in result.symbols if s.name == "IncompleteTrait")
+        [sync] = incomplete.children
+        self.assertEqual(
+            (sync.signature.params, sync.signature.return_type), ([], None)
+        )
+
+    def test_clean_fixtures(self):
+        for path in (
+            TEST_DATA / "rust_simple.rs",
+            VALIDATION_DATA / "validator_complex_features.rs",
+        ):
+            with self.subTest(path=path.name):
+                result = parse_file(path)
+                self.assertEqual(result.issues, [])
+                self.assertTrue(result.symbols)
+
+        names = [s.name for s in parse_file(TEST_DATA / "rust_simple.rs").walk()]
+        self.assertEqual(
+            names,
+            ["utils", "add", "check_value", "Container", "name", "value"]
+            + ["Describable", "describe", "summary", "Container<'a, T>", "new"]
+            + ["get_value", "Describable for Container<'a, T>", "describe", "main"],
+        )
+
+    def test_erroneous_code(self):
+        result = parse_file(VALIDATION_DATA / "validator_erroneous_code.rs")
+        names = [s.name for s in result.symbols]
+        self.assertEqual(len(names), 27)
+        self.assertEqual(
+            names[:3], ["missing_semicolon", "mutability_error", "type_mismatch"]
+        )
+        self.assertEqual(names[-1], "multiple_errors")
+        self.assertIn("let", names)
+        self.assertEqual(
+            [(issue.kind, issue.span.start_line) for issue in result.issues],
+            [
+                # The ; after struct ExtraToken { .. }
+                (IssueKind.UNEXPECTED_TOKEN, 33),
+                # vec![1, 2, 3);
+                (IssueKind.UNCLOSED_DELIMITER, 37),
+                (IssueKind.UNEXPECTED_DELIMITER, 37),
+                # let value: i32, in struct InvalidField
+                (IssueKind.UNEXPECTED_TOKEN, 55),
+                # fn let()
+                (IssueKind.EXPECTED_NAME, 126),
+            ],
+        )
+        # The mismatched bracket does not swallow the function after it
+        function = result.symbols[names.index("mismatched_brackets")]
+        self.assertEqual(function.span.end_line, 38)
+
+    def test_incomplete_syntax(self):
+        result = parse_file(VALIDATION_DATA / "validator_incomplete_syntax.rs")
+        # Each unclosed item ends at the next item indented no deeper than it
+        self.assertEqual(
+            outline(result.symbols),
+            [
+                (
+                    "struct",
+                    "IncompleteStruct",
+                    [("field", "name"), ("field", "value")],
+                ),
+                (
+                    "enum",
+                    "Status",
+                    [
+                        ("enum_variant", "Active"),
+                        ("enum_variant", "Pending"),
+                        ("enum_variant", "Completed"),
+                        ("enum_variant", "Failed"),
+                    ],
+                ),
+                ("function", "process_data"),
+                ("function", "incomplete_match"),
+                (
+                    "impl",
+                    "IncompleteStruct",
+                    [("function", "new"), ("function", "get_value")],
+                ),
+                (
+                    "trait",
+                    "DataProcessor",
+                    [("function", "process"), ("function", "validate")],
+                ),
+                # After the unterminated string on line 82
+                ("function", "with_lifetime"),
+                ("function", "generic_function"),
+                ("function", "fetch_data"),
+            ],
+        )
+        struct = result.symbols[0]
+        self.assertEqual(struct.kind, SymbolKind.STRUCT)
+        self.assertEqual((struct.span.start_line, struct.span.end_line), (4, 6))
+        kinds = {issue.kind for issue in result.issues}
+        self.assertIn(IssueKind.UNCLOSED_DELIMITER, kinds)
+        # The string ends with its line
+        string = next(
+            i for i in result.issues if i.kind == IssueKind.UNTERMINATED_STRING
+        )
+        self.assertEqual(
+            (string.span.start_line, string.span.start_col, string.span.end_line),
+            (82, 15, 82),
+        )
+        first = result.issues[0]
+        self.assertEqual((first.span.start_line, first.span.start_col), (4, 25))
+        # fn new's body and the struct literal in it end before fn get_value
+        impl = result.symbols[4]
+        self.assertEqual(
+            [(s.span.start_line, s.span.end_line) for s in impl.children],
+            [(32, 35), (38, 38)],
+        )
+        self.assertEqual(
+            [
+                (issue.span.start_line, issue.span.start_col)
+                for issue in result.issues
+                if issue.kind == IssueKind.UNCLOSED_DELIMITER
+                and 31 <= issue.span.start_line <= 38
+            ],
+            [(31, 23), (32, 32), (33, 14), (38, 17)],
+        )
+
+    def test_unclosed_bodies(self):
+        result = parse_file(TEST_DATA / "test_rust_parser_13.rs")
+        # fn load and the impl it is in are never closed
+        self.assertEqual(
+            outline(result.symbols),
+            [
+                ("module_decl", "utils"),
+                ("module_decl", "data_source"),
+                ("const", "DEFAULT_THRESHOLD"),
+                ("static", "LOGGER_INITIALIZED"),
+                (
+                    "enum",
+                    "AnalysisError",
+                    [
+                        ("enum_variant", "Io", [("field", "0")]),
+                        ("enum_variant", "ParseError", [("field", "0")]),
+                        ("enum_variant", "CalculationError", [("field", "details")]),
+                    ],
+                ),
+                (
+                    "trait",
+                    "DataLoader",
+                    [
+                        ("type_alias", "Item"),
+                        ("function", "load"),
+                        ("function", "supports_extension"),
+                    ],
+                ),
+                (
+                    "struct",
+                    "CsvLoader",
+                    [("field", "delimiter"), ("field", "has_headers")],
+                ),
+                (
+                    "impl",
+                    "DataLoader for CsvLoader",
+                    [("type_alias", "Item"), ("function", "load")],
+                ),
+                ("function", "process_data"),
+                (
+                    "module",
+                    "visualization",
+                    [
+                        ("function", "plot_data"),
+                        ("struct", "PlotOptions", [("field", "title")]),
+                    ],
+                ),
+                ("function", "main"),
+            ],
+        )
+        impl = result.symbols[7]
+        self.assertEqual((impl.span.start_line, impl.span.end_line), (48, 68))
+        self.assertIn(
+            (IssueKind.UNCLOSED_DELIMITER, 48),
+            [(issue.kind, issue.span.start_line) for issue in result.issues],
+        )
+
+        source = """mod outer {
+    impl A {
+        fn a(&self) {
+            if x {
+        fn b(&self) {}
+    }
+    fn c() {}
+}
+struct S;
+"""
+        result = parse_symbols(source, Language.RUST)
+        self.assertEqual(
+            outline(result.symbols),
+            [
+                (
+                    "module",
+                    "outer",
+                    [
+                        ("impl", "A", [("function", "a"), ("function", "b")]),
+                        ("function", "c"),
+                    ],
+                ),
+                ("struct", "S"),
+            ],
+        )
+        self.assertEqual(
+            [(i.kind, i.span.start_line, i.span.start_col) for i in result.issues],
+            [
+                (IssueKind.UNCLOSED_DELIMITER, 3, 21),
+                (IssueKind.UNCLOSED_DELIMITER, 4, 18),
+            ],
+        )
+
+    def test_closures_and_nested_items(self):
+        source = """fn outer(items: &[u32]) -> u32 {
+    let add = |x: u32, y: u32| { x + y };
+    let none = || 0;
+    let owned = move |a| a.len();
+    let both = a || b;
+    let v = items.iter().map(|&x| x as u64).collect::<Vec<_>>();
+    match v {
+        Some(1 | 2) => { 1 }
+        _ => |z| { z },
+    };
+    fn inner() {}
+    struct Local { a: u32 }
+    impl Local { fn get(&self) -> u32 { self.a } }
+    mod m {}
+    add(1, 2)
+}
+struct After;
+"""
+        result = parse_symbols(source, Language.RUST)
+        self.assertEqual(result.issues, [])
+        # Modules declared in functions are always listed, other items on request
+        self.assertEqual(
+            outline(result.symbols),
+            [("function", "outer", [("module", "m")]), ("struct", "After")],
+        )
+        self.assertEqual(
+            [(s.span.start_line, s.span.end_line) for s in result.symbols],
+            [(1, 16), (17, 17)],
+        )
+        result = parse_symbols(source, Language.RUST, include_nested=True)
+        self.assertEqual(result.issues, [])
+        self.assertEqual(
+            outline(result.symbols),
+            [
+                (
+                    "function",
+                    "outer",
+                    [
+                        ("function", "inner"),
+                        ("struct", "Local", [("field", "a")]),
+                        ("impl", "Local", [("function", "get")]),
+                        ("module", "m"),
+                    ],
+                ),
+                ("struct", "After"),
+            ],
+        )
+        self.assertEqual(
+            [s.qualified_name for s in result.symbols[0].children],
+            ["outer::inner", "outer::Local", "outer::Local", "outer::m"],
+        )
+
+        # A closure whose `|` is never closed ends before the next statement
+        source = """fn f() {
+    let c = items.map(|x: u32, y: u32
+    let d = 1;
+    g(d)
+}
+fn h() {}
+"""
+        result = parse_symbols(source, Language.RUST)
+        self.assertEqual(
+            [(s.name, s.span.start_line, s.span.end_line) for s in result.symbols],
+            [("f", 1, 5), ("h", 6, 6)],
+        )
+        self.assertEqual(
+            [(i.kind, i.span.start_line, i.span.start_col) for i in result.issues],
+            [
+                (IssueKind.UNCLOSED_DELIMITER, 2, 22),
+                (IssueKind.UNCLOSED_DELIMITER, 2, 23),
+            ],
+        )
+        result = parse_file(VALIDATION_DATA / "validator_incomplete_syntax.rs")
+        self.assertEqual(
+            [
+                (i.span.start_line, i.span.start_col, i.message)
+                for i in result.issues
+                if 70 <= i.span.start_line <= 80
+            ],
+            [
+                (73, 15, "Unclosed delimiter `|`"),
+                (77, 18, "Unclosed delimiter `[`"),
+            ],
+        )
+
+    def test_unterminated_literals(self):
+        # Each ends at the end of its line; the next line is code again
+        source = """fn a() { let s = "no end; }
+fn b() { let c = '\\n; }
+/* no end
+fn c() { let b = b'; }
+fn d() {}
+"""
+        result = parse_symbols(source, Language.RUST)
+        self.assertEqual(
+            outline(result.symbols),
+            [("function", name) for name in "abcd"],
+        )
+        self.assertEqual(
+            [
+                (i.kind, i.span.start_line, i.span.start_col, i.span.end_col)
+                for i in result.issues
+                if i.kind != IssueKind.UNCLOSED_DELIMITER
+            ],
+            [
+                (IssueKind.UNTERMINATED_STRING, 1, 18, 28),
+                (IssueKind.UNTERMINATED_CHAR, 2, 18, 24),
+                (IssueKind.UNTERMINATED_COMMENT, 3, 1, 10),
+                (IssueKind.UNTERMINATED_CHAR, 4, 18, 23),
+            ],
+        )
+        self.assertEqual([s.span.end_line for s in result.symbols], [1, 2, 4, 5])
+
+        # Strings spanning lines are only cut when they never end
+        source = 'const S: &str = "one\ntwo";\nfn f() {}\n'
+        result = parse_symbols(source, Language.RUST)
+        self.assertEqual(result.issues, [])
+        self.assertEqual(outline(result.symbols), [("const", "S"), ("function", "f")])
+
+    def test_extern_blocks_and_unions(self):
+        result = parse_file(TEST_DATA / "test_rust_edge_cases_5.rs")
+        self.assertEqual(result.issues, [])
+        symbols = {s.name: s for s in result.symbols}
+        block = symbols['extern "C"']
+        self.assertEqual((block.kind, block.abi), (SymbolKind.EXTERN_BLOCK, "C"))
+        self.assertEqual(
+            [(f.kind, f.qualified_name, f.signature.abi) for f in block.children],
+            [(SymbolKind.FUNCTION, "abs", "C"), (SymbolKind.FUNCTION, "sqrt", "C")],
+        )
+        self.assertEqual(block.children[0].signature.return_type, "i32")
+        # The foreign functions end at their `;`, the block at its `}`
+        self.assertEqual((block.span.start_line, block.span.end_line), (47, 50))
+        self.assertEqual(symbols["use_c_functions"].span.start_line, 52)
+        union = symbols["IntOrFloat"]
+        self.assertEqual((union.kind, union.shape), (SymbolKind.UNION, Shape.STRUCT))
+        self.assertEqual(
+            [(f.name, f.field_type) for f in union.children],
+            [("int_val", "i32"), ("float_val", "f32")],
+        )
+        counter = symbols["COUNTER"]
+        self.assertEqual((counter.kind, counter.is_mutable), (SymbolKind.STATIC, True))
+
+        result = parse_file(TEST_DATA / "test_rust_parser_13.rs")
+        threshold, logger = result.symbols[2:4]
+        self.assertEqual(
+            (threshold.kind, threshold.declared_type, threshold.is_mutable),
+            (SymbolKind.CONST, "f64", None),
+        )
+        self.assertEqual(
+            (logger.kind, logger.name, logger.is_mutable),
+            (SymbolKind.STATIC, "LOGGER_INITIALIZED", False),
+        )
+
+        source = """unsafe extern "system" {
+    /// The last error
+    pub safe static errno: i32;
+    static mut ENVIRON: *const *const u8;
+    safe fn getpid() -> u32;
+    fn variadic(format: *const u8, ...);
+}
+extern {
+    fn bare();
+}
+#[repr(C)]
+pub union Bits<T: Copy> { value: T, raw: [u8; 4] }
+fn union() { let union = 1; }
+"""
+        result = parse_symbols(source, Language.RUST)
+        self.assertEqual(result.issues, [])
+        self.assertEqual(
+            outline(result.symbols),
+            [
+                (
+                    "extern_block",
+                    'extern "system"',
+                    [
+                        ("static", "errno"),
+                        ("static", "ENVIRON"),
+                        ("function", "getpid"),
+                        ("function", "variadic"),
+                    ],
+                ),
+                ("extern_block", "extern", [("function", "bare")]),
+                ("union", "Bits", [("field", "value"), ("field", "raw")]),
+                ("function", "union"),
+            ],
+        )
+        system, bare, bits = result.symbols[:3]
+        errno, environ, getpid, _ = system.children
+        self.assertEqual(
+            (errno.visibility, errno.doc_comment), ("pub", "The last error")
+        )
+        self.assertEqual([errno.is_mutable, environ.is_mutable], [False, True])
+        self.assertEqual(environ.declared_type, "*const *const u8")
+        self.assertEqual(getpid.signature.abi, "system")
+        self.assertEqual((bare.abi, bare.children[0].signature.abi), ("C", "C"))
+        self.assertEqual(
+            (bits.visibility, bits.generics, bits.attributes[0].name),
+            ("pub", "<T: Copy>", "repr"),
+        )
+        self.assertEqual(system.to_dict()["abi"], "system")
+        self.assertTrue(environ.to_dict()["is_mutable"])
+        self.assertEqual(bits.to_dict()["shape"], "struct")
+
+    def test_impls(self):
+        impls = [
+            (s.span.start_line, s.name, s.impl_type, s.impl_trait)
+            for s in parse_file(TEST_DATA / "rust_complex.rs").walk()
+            if s.kind == SymbolKind.IMPL
+        ]
+        # Every impl of the file, also after the unclosed trait at line 90
+        lines = (TEST_DATA / "rust_complex.rs").read_text().splitlines()
+        self.assertEqual(
+            [line for line, *_ in impls],
+            [n for n, line in enumerate(lines, 1) if line.startswith("impl")],
+        )
+        self.assertIn((116, "Message<T>", "Message", None), impls)
+        self.assertIn((159, "Repository<'a, T, E, S>", "Repository", None), impls)
+        self.assertIn(
+            (
+                208,
+                "DataProcessor<T> for DataManager<T>",
+                "DataManager",
+                "DataProcessor<T>",
+            ),
+            impls,
+        )
+        self.assertIn((233, "Drop for DataManager<T>", "DataManager", "Drop"), impls)
+        self.assertIn((264, "StringExt for &str", "&str", "StringExt"), impls)
+        self.assertIn((547, "StateMachine<Running>", "StateMachine", None), impls)
+
+        result = parse_file(TEST_DATA / "rust_complex.rs")
+        [repository] = [s for s in result.symbols if s.span.start_line == 159]
+        # The where clause is not part of the name
+        self.assertEqual(
+            [child.name for child in repository.children],
+            ["new", "add", "find", "transform"],
+        )
+
+    def test_unsafe_and_path_impls(self):
+        impls = {
+            s.name: (s.impl_type, s.impl_trait)
+            for s in parse_file(TEST_DATA / "test_rust_edge_cases_2.rs").walk()
+            if s.kind == SymbolKind.IMPL
+        }
+        # unsafe impl
+        self.assertEqual(
+            impls["Send for ThreadSafeCounter"], ("ThreadSafeCounter", "Send")
+        )
+        self.assertEqual(
+            impls["Sync for ThreadSafeCounter"], ("ThreadSafeCounter", "Sync")
+        )
+        self.assertEqual(
+            impls["std::fmt::Display for MyType"], ("MyType", "std::fmt::Display")
+        )
+
+        source = """impl<F> Handler for F where F: for<'a> Fn(&'a str) -> u32 {}
+unsafe impl<T: Send> !Sync for Wrapper<T> {}
+impl<T> Trait for Vec<Box<dyn Fn() -> T>> {}
+"""
+        result = parse_symbols(source, Language.RUST)
+        self.assertEqual(result.issues, [])
+        self.assertEqual(
+            [(s.name, s.impl_type, s.impl_trait) for s in result.symbols],
+            [
+                ("Handler for F", "F", "Handler"),
+                ("!Sync for Wrapper<T>", "Wrapper", "!Sync"),
+                ("Trait for Vec<Box<dyn Fn() -> T>>", "Vec", "Trait"),
+            ],
+        )
+        self.assertEqual(result.symbols[0].to_dict()["impl_trait"], "Handler")
+
+    def test_outline_snapshots(self):
+        for path in (
+            TEST_DATA / "rust_complex.rs",
+            VALIDATION_DATA / "validator_complex_features.rs",
+        ):
+            with self.subTest(path=path.name):
+                source = path.read_text(encoding="utf-8")
+                expected = path.with_suffix(".expected.outline")
+                result = render_outline(source)
+                self.assertEqual(result.text, expected.read_text(encoding="utf-8"))
+                # Each elided body is `{ ... }` on its line, and its span in
+                # the source a whole body
+                data = source.encode("utf-8")
+                lines = result.text.splitlines()
+                for body in result.bodies:
+                    self.assertIn(" ... ", lines[body.line - 1])
+                    text = data[body.span.start_byte : body.span.end_byte]
+                    self.assertEqual(text[:1] + text[-1:], b"{}")
+                    self.assertIs(result.body_at(body.line), body)
+
+    def test_outline_options(self):
+        source = (
+            "//! Crate docs\n"
+            "\n"
+            "/// Größe in Bytes, \"✓\"\n"
+            "#[inline]\n"
+            "pub fn größe(x: &str) -> usize {\n"
+            "    x.len()\n"
+            "}\n"
+            "\n"
+            "mod inner {\n"
+            "    #![allow(dead_code)]\n"
+            "    use std::fmt;\n"
+            "\n"
+            "    /// Kept\n"
+            "    pub struct Point { x: i32, y: i32 }\n"
+            "\n"
+            "    impl Point {\n"
+            "        fn origin() -> Self { Point { x: 0, y: 0 } }\n"
+            "    }\n"
+            "}\n"
+        )
+        result = render_outline(source)
+        self.assertEqual(
+            result.text,
+            "//! Crate docs\n"
+            "\n"
+            "/// Größe in Bytes, \"✓\"\n"
+            "#[inline]\n"
+            "pub fn größe(x: &str) -> usize { ... }\n"
+            "\n"
+            "mod inner {\n"
+            "    #![allow(dead_code)]\n"
+            "    use std::fmt;\n"
+            "\n"
+            "    /// Kept\n"
+            "    pub struct Point {\n"
+            "        x: i32,\n"
+            "        y: i32,\n"
+            "    }\n"
+            "\n"
+            "    impl Point {\n"
+            "        fn origin() -> Self { ... }\n"
+            "    }\n"
+            "}\n",
+        )
+        data = source.encode("utf-8")
+        self.assertEqual(
+            [(b.qualified_name, b.kind, b.line) for b in result.bodies],
+            [
+                ("größe", SymbolKind.FUNCTION, 5),
+                ("inner::Point::origin", SymbolKind.FUNCTION, 18),
+            ],
+        )
+        [first, second] = [
+            data[b.span.start_byte : b.span.end_byte] for b in result.bodies
+        ]
+        self.assertEqual(first, b"{\n    x.len()\n}")
+        self.assertEqual(second, b"{ Point { x: 0, y: 0 } }")
+        self.assertIsNone(result.body_at(1))
+
+        bare = render_outline(
+            source, OutlineOptions(keep_doc_comments=False, keep_attributes=False)
+        )
+        self.assertTrue(
+            bare.text.startswith("pub fn größe(x: &str) -> usize { ... }\n")
+        )
+        self.assertNotIn("//", bare.text)
+        self.assertNotIn("#", bare.text)
+        self.assertEqual(bare.body_at(1).qualified_name, "größe")
+
+        top = render_outline(source, OutlineOptions(max_depth=0))
+        self.assertTrue(top.text.endswith("\nmod inner { ... }\n"))
+        self.assertEqual(top.body_at(7).qualified_name, "inner")
+        self.assertEqual(top.body_at(7).kind, SymbolKind.MODULE)
+        nested = render_outline(source, OutlineOptions(max_depth=1))
+        self.assertIn("    pub struct Point { ... }\n", nested.text)
+        self.assertIn("    impl Point { ... }\n", nested.text)
+        self.assertEqual(
+            [b.kind for b in nested.bodies],
+            [SymbolKind.FUNCTION, SymbolKind.STRUCT, SymbolKind.IMPL],
+        )
+
+        with self.assertRaises(ValueError):
+            render_outline("", language="cobol")
+
+    def test_body_spans(self):
+        source = (
+            "fn f() -> u8 { 1 }\n"
+            "struct Unit;\n"
+            "trait T\n"
+            "where\n"
+            "    Self: Sized,\n"
+            "{\n"
+            "    fn g(&self);\n"
+            "}\n"
+            "macro_rules! m { () => {}; }\n"
+            'extern "C" { fn h(); }\n'
+        )
+        symbols = parse_symbols(source, Language.RUST).symbols
+        bodies = {
+            s.name: s.body_span
+            and source[s.body_span.start_byte : s.body_span.end_byte]
+            for s in symbols
+        }
+        self.assertEqual(
+            bodies,
+            {
+                "f": "{ 1 }",
+                "Unit": None,
+                "T": "{\n    fn g(&self);\n}",
+                "m": "{ () => {}; }",
+                'extern "C"': "{ fn h(); }",
+            },
+        )
+        self.assertIsNone(symbols[2].children[0].body_span)
+        trait_body = symbols[2].body_span
+        self.assertEqual((trait_body.start_line, trait_body.start_col), (6, 1))
+        self.assertEqual((trait_body.end_line, trait_body.end_col), (8, 2))
+
+    def test_to_dict(self):
+        result = parse_symbols("/// Doc\nfn f() {}", Language.RUST)
+        self.assertEqual(
+            result.to_dict(),
+            {
+                "language": "rust",
+                "doc_comment": None,
+                "attributes": [],
+                "imports": [],
+                "symbols": [
+                    {
+                        "kind": "function",
+                        "name": "f",
+                        "qualified_name": "f",
+                        "visibility": None,
+                        "span": {
+                            "start_byte": 8,
+                            "end_byte": 17,
+                            "start_line": 2,
+                            "start_col": 1,
+                            "end_line": 2,
+                            "end_col": 10,
+                        },
+                        "body_span": {
+                            "start_byte": 15,
+                            "end_byte": 17,
+                            "start_line": 2,
+                            "start_col": 8,
+                            "end_line": 2,
+                            "end_col": 10,
+                        },
+                        "doc_comment": "Doc",
+                        "attributes": [],
+                        "derives": [],
+                        "children": [],
+                        "generics": None,
+                        "generic_params": [],
+                        "where_clause": None,
+                        "where_predicates": [],
+                        "is_async": False,
+                        "has_default_body": None,
+                        "signature": {
+                            "is_async": False,
+                            "is_unsafe": False,
+                            "is_const": False,
+                            "abi": None,
+                            "generics": [],
+                            "receiver": None,
+                            "params": [],
+                            "return_type": None,
+                            "where_clause": None,
+                            "where_predicates": [],
+                        },
+                    }
+                ],
+                "issues": [],
+            },
+        )
+
+    def test_languages(self):
+        self.assertEqual(Language.for_path("src/main.rs"), Language.RUST)
+        self.assertIsNone(Language.for_path("main.py"))
+        with self.assertRaises(ValueError):
+            parse_symbols("", "cobol")
+
+
+if __name__ == "__main__":
+    unittest.main()
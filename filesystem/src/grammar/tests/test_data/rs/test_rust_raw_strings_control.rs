@@ -0,0 +1,43 @@
+// Raw strings, byte strings and raw identifiers full of braces and quotes
+use regex::Regex;
+
+// A regex with quotes, braces and a backslash before the closing quote
+fn email_pattern() -> Regex {
+    Regex::new("pattern").unwrap()
+}
+
+// A JSON template spanning lines, with `"#` inside the string
+fn render_template(name: &str) -> String {
+    let template = "
+    name
+    tags
+    note
+";
+    template.replace("name", name)
+}
+
+/// Byte strings and raw byte strings
+pub struct Patterns {
+    open: &'static [u8],
+    close: &'static [u8],
+}
+
+impl Patterns {
+    pub fn new() -> Self {
+        Patterns { open: b"open", close: b"close" }
+    }
+
+    fn r#type(&self) -> &str {
+        let r#match = c"match";
+        "patterns"
+    }
+}
+
+// A raw string with no hashes ends at the first quote
+fn windows_path() -> &'static str {
+    "path"
+}
+
+fn after_raw_strings() -> u32 {
+    42
+}
@@ -0,0 +1,200 @@
+# mcp_bsdiff.py
+
+"""
+BSDIFF40 patches (the format of Colin Percival's bsdiff and of the bsdiff4
+package), the diffs the server stores for binary files (file_encoding
+"bsdiff"): a header with the compressed lengths of the control and diff blocks
+and the new size, then the three bz2-compressed blocks.
+
+This is the only generator of these patches: the server logs them through
+mcp_edit_utils, and mcpdiff, which applies them (cli/mcpdiff_patch.py), has
+its tests make them here. It needs nothing beyond the standard library.
+"""
+
+import bz2
+from typing import List, Tuple
+
+BSDIFF_MAGIC = b"BSDIFF40"
+# Content larger than this (before or after the edit) gets a patch that holds
+# the whole new content: generate_bsdiff() is pure Python and would keep the
+# tool call, and the locks it holds while logging, waiting for seconds
+BSDIFF_MAX_SIZE = 64 * 1024
+
+
+def generate_binary_patch(before: bytes, after: bytes) -> bytes:
+    """
+    The patch stored for an edit of a binary file: a generate_bsdiff() one, or
+    above BSDIFF_MAX_SIZE a generate_full_bsdiff() one.
+    """
+    if max(len(before), len(after)) > BSDIFF_MAX_SIZE:
+        return generate_full_bsdiff(after)
+    return generate_bsdiff(before, after)
+
+
+def _offtout(value: int) -> bytes:
+    """bsdiff's 8-byte sign-magnitude little-endian integer."""
+    encoded = bytearray(abs(value).to_bytes(8, "little"))
+    if value < 0:
+        encoded[7] |= 0x80
+    return bytes(encoded)
+
+
+def _suffix_array(data: bytes) -> List[int]:
+    """Start offsets of the suffixes of `data` in sorted order (prefix doubling)."""
+    n = len(data)
+    rank = list(data)
+    order = sorted(range(n), key=rank.__getitem__)
+    k = 1
+    while n > 1:
+
+        def key(i: int) -> Tuple[int, int]:
+            return rank[i], rank[i + k] if i + k < n else -1
+
+        order.sort(key=key)
+        new_rank = [0] * n
+        for j in range(1, n):
+            new_rank[order[j]] = new_rank[order[j - 1]] + (
+                key(order[j - 1]) != key(order[j])
+            )
+        rank = new_rank
+        if rank[order[-1]] == n - 1:
+            break
+        k *= 2
+    return order
+
+
+def _match_length(old: bytes, old_pos: int, new: bytes, new_pos: int) -> int:
+    length = 0
+    limit = min(len(old) - old_pos, len(new) - new_pos)
+    while length < limit and old[old_pos + length] == new[new_pos + length]:
+        length += 1
+    return length
+
+
+def _search(
+    order: List[int], old: bytes, new: bytes, new_pos: int
+) -> Tuple[int, int]:
+    """Longest match of new[new_pos:] among the suffixes of `old`: (pos, length)."""
+    start, end = 0, len(order) - 1
+    while end - start >= 2:
+        middle = start + (end - start) // 2
+        pos = order[middle]
+        length = min(len(old) - pos, len(new) - new_pos)
+        if old[pos : pos + length] < new[new_pos : new_pos + length]:
+            start = middle
+        else:
+            end = middle
+    best = (0, 0)
+    for pos in {order[start], order[end]}:
+        length = _match_length(old, pos, new, new_pos)
+        if length > best[1]:
+            best = (pos, length)
+    return best
+
+
+def generate_bsdiff(old: bytes, new: bytes) -> bytes:
+    """
+    A BSDIFF40 patch that turns `old` into `new`, with the old bytes it can
+    reuse found through a suffix array. Pure Python: slow for large files
+    (about a second for 100 KB), see generate_full_bsdiff().
+    """
+    order = _suffix_array(old) if old else []
+    control = bytearray()
+    diff = bytearray()
+    extra = bytearray()
+    scan = length = pos = 0
+    last_scan = last_pos = last_offset = 0
+    while scan < len(new):
+        old_score = 0
+        scan += length
+        scsc = scan
+        while scan < len(new):
+            pos, length = _search(order, old, new, scan) if order else (0, 0)
+            while scsc < scan + length:
+                if (
+                    scsc + last_offset < len(old)
+                    and old[scsc + last_offset] == new[scsc]
+                ):
+                    old_score += 1
+                scsc += 1
+            if (length == old_score and length != 0) or length > old_score + 8:
+                break
+            if scan + last_offset < len(old) and old[scan + last_offset] == new[scan]:
+                old_score -= 1
+            scan += 1
+
+        if length == old_score and scan != len(new):
+            continue
+
+        # Extend the previous match forwards and this one backwards
+        score = best = length_forward = i = 0
+        while last_scan + i < scan and last_pos + i < len(old):
+            if old[last_pos + i] == new[last_scan + i]:
+                score += 1
+            i += 1
+            if score * 2 - i > best * 2 - length_forward:
+                best = score
+                length_forward = i
+        length_back = 0
+        if scan < len(new):
+            score = best = 0
+            i = 1
+            while scan >= last_scan + i and pos >= i:
+                if old[pos - i] == new[scan - i]:
+                    score += 1
+                if score * 2 - i > best * 2 - length_back:
+                    best = score
+                    length_back = i
+                i += 1
+        if last_scan + length_forward > scan - length_back:
+            overlap = (last_scan + length_forward) - (scan - length_back)
+            score = best = split = 0
+            for i in range(overlap):
+                forward = last_scan + length_forward - overlap + i
+                if new[forward] == old[last_pos + length_forward - overlap + i]:
+                    score += 1
+                if new[scan - length_back + i] == old[pos - length_back + i]:
+                    score -= 1
+                if score > best:
+                    best = score
+                    split = i + 1
+            length_forward += split - overlap
+            length_back -= split
+
+        diff.extend(
+            (new[last_scan + i] - old[last_pos + i]) & 0xFF
+            for i in range(length_forward)
+        )
+        extra.extend(new[last_scan + length_forward : scan - length_back])
+        control.extend(_offtout(length_forward))
+        control.extend(_offtout((scan - length_back) - (last_scan + length_forward)))
+        control.extend(_offtout((pos - length_back) - (last_pos + length_forward)))
+        last_scan = scan - length_back
+        last_pos = pos - length_back
+        last_offset = pos - scan
+
+    return _patch(bytes(control), bytes(diff), bytes(extra), len(new))
+
+
+def generate_full_bsdiff(new: bytes) -> bytes:
+    """
+    A BSDIFF40 patch that writes `new` whole, without reusing old bytes: a
+    single control triple copying it all as extra bytes. Takes linear time,
+    for files too large for generate_bsdiff().
+    """
+    control = _offtout(0) + _offtout(len(new)) + _offtout(0)
+    return _patch(control, b"", new, len(new))
+
+
+def _patch(control: bytes, diff: bytes, extra: bytes, new_size: int) -> bytes:
+    control_block = bz2.compress(control)
+    diff_block = bz2.compress(diff)
+    return (
+        BSDIFF_MAGIC
+        + _offtout(len(control_block))
+        + _offtout(len(diff_block))
+        + _offtout(new_size)
+        + control_block
+        + diff_block
+        + bz2.compress(extra)
+    )
@@ -1,5 +1,7 @@
 # mcp_edit_utils.py
 
+import base64
+import glob
 import os
 import re
 import hashlib
@@ -9,16 +11,59 @@ import subprocess
 import difflib
 import filelock
 import threading
+import time
 from pathlib import Path
 from datetime import datetime, timezone
-from typing import Optional, List, Dict, Any
+from typing import Optional, List, Dict, Any, Set, Tuple
+
+try:
+    from .mcp_bsdiff import BSDIFF_MAX_SIZE, generate_binary_patch
+    from . import mcp_history_format as history_format
+    from .mcp_history_format import (
+        LOGS_DIR,
+        MERKLE_SUFFIX,
+        WAL_SUFFIX,
+        fsync_directory,
+        get_conversation_log_files,
+        get_log_conversation_id,
+        get_wal_path,
+        load_ignore_matcher,
+        write_synced,
+        write_wal,
+    )
+except ImportError:  # Imported as a top-level module, with src/ on sys.path
+    from mcp_bsdiff import BSDIFF_MAX_SIZE, generate_binary_patch
+    import mcp_history_format as history_format
+    from mcp_history_format import (
+        LOGS_DIR,
+        MERKLE_SUFFIX,
+        WAL_SUFFIX,
+        fsync_directory,
+        get_conversation_log_files,
+        get_log_conversation_id,
+        get_wal_path,
+        load_ignore_matcher,
+        write_synced,
+        write_wal,
+    )
 
 # --- Configuration Constants ---
 HISTORY_DIR_NAME = ".mcp/edit_history"
-LOGS_DIR = "logs"
 DIFFS_DIR = "diffs"
 CHECKPOINTS_DIR = "checkpoints"
+# Locks on workspace files, kept out of the working tree
+LOCKS_DIR = "locks"
 LOCK_TIMEOUT = 10  # seconds for file locks
+LOG_SCHEMA_VERSION = 11  # Must match CURRENT_SCHEMA_VERSION in cli/mcpdiff_migration.py
+CONFIG_FILE_NAME = "config.json"  # Workspace settings in .mcp/, shared with mcpdiff
+SESSION_ID_ENV_VAR = "MCP_SESSION_ID"  # Recorded as session_id with each edit
+# With `namespace` configured, conversation IDs are <namespace>/<conv_id> and
+# their logs, diffs and checkpoints live one directory further down
+NAMESPACE_PATTERN = re.compile(r"^[A-Za-z0-9][A-Za-z0-9._-]*$")
+DEFAULT_NAMESPACE = "default"
+# move_file logs a "rename" within one directory and a "move" to another one;
+# shared with mcpdiff
+MOVE_OPERATIONS = ("move", "rename")
 
 # --- Logging Setup ---
 logging.basicConfig(
@@ -393,6 +438,67 @@ def get_history_root(path: str) -> Optional[Path]:
     return None
 
 
+def load_workspace_config(workspace_root: Path) -> Dict[str, Any]:
+    """Reads .mcp/config.json (managed by `mcpdiff config`). {} if unavailable."""
+    config_path = workspace_root / ".mcp" / CONFIG_FILE_NAME
+    if not config_path.is_file():
+        return {}
+    try:
+        with open(config_path, "r", encoding="utf-8") as f:
+            config = json.load(f)
+        return config if isinstance(config, dict) else {}
+    except (IOError, json.JSONDecodeError) as e:
+        log.warning(f"Ignoring unreadable config file {config_path}: {e}")
+        return {}
+
+
+# --- Ignore File ---
+# Files matching a pattern in .mcp/ignore get no checkpoint and an empty diff.
+
+
+def is_ignored_path(workspace_root: Path, relative_path: str) -> bool:
+    """Whether the file at a workspace-relative path is listed in .mcp/ignore."""
+    return load_ignore_matcher(workspace_root).matches(relative_path)
+
+
+def get_log_file_path(
+    history_root: Path, conversation_id: str, now: Optional[datetime] = None
+) -> Path:
+    """
+    Returns the log file new entries of a conversation should be written to.
+    With `log_shard_interval` configured this is logs/<conv_id>_<shard>.log,
+    otherwise logs/<conv_id>.log.
+    """
+    workspace_root = history_root.parent.parent
+    interval = load_workspace_config(workspace_root).get("log_shard_interval")
+    suffix = history_format.get_log_shard_suffix(
+        interval, now or datetime.now(timezone.utc)
+    )
+    if interval and suffix is None:
+        log.warning(
+            f"Unknown log_shard_interval '{interval}', expected one of {history_format.LOG_SHARD_INTERVALS}. Not sharding."
+        )
+    name = f"{conversation_id}_{suffix}.log" if suffix else f"{conversation_id}.log"
+    return history_root / LOGS_DIR / name
+
+
+def get_namespaced_conversation_id(workspace_root: Path, conversation_id: str) -> str:
+    """
+    The ID a conversation is logged under in this workspace: with `namespace`
+    configured (see `mcpdiff config`) <namespace>/<conversation_id>, so that
+    sub-projects sharing one .mcp/ root keep their conversations apart.
+    """
+    namespace = load_workspace_config(workspace_root).get("namespace")
+    if not namespace:
+        return conversation_id
+    if not isinstance(namespace, str) or not NAMESPACE_PATTERN.match(namespace):
+        log.warning(f"Ignoring invalid namespace in config: {namespace!r}")
+        return conversation_id
+    if namespace == DEFAULT_NAMESPACE:
+        return conversation_id
+    return f"{namespace}/{conversation_id}"
+
+
 def sanitize_path_for_filename(abs_path: str, workspace_root: Path) -> str:
     """Creates a safe filename from an absolute path relative to the workspace."""
     try:
@@ -416,7 +522,19 @@ def sanitize_path_for_filename(abs_path: str, workspace_root: Path) -> str:
         log.warning(
             f"Path {abs_path} not relative to workspace {workspace_root}. Using hash."
         )
-        return hashlib.sha256(abs_path.encode()).hexdigest()
+        return hashlib.sha256(os.fsencode(abs_path)).hexdigest()
+
+
+def get_target_lock_path(history_root: Path, target_path: Path) -> str:
+    """
+    The path to lock (see acquire_lock()) while changing a workspace file:
+    locks/<hash>_<name>, keyed by the SHA-256 of the canonical path, with the
+    file name for whoever looks at the lock directory.
+    """
+    canonical = os.path.normcase(os.path.realpath(target_path))
+    digest = hashlib.sha256(os.fsencode(canonical)).hexdigest()[:32]
+    name = re.sub(r"[^\w\-.]", "_", Path(canonical).name)[:64]
+    return str(history_root / LOCKS_DIR / f"{digest}_{name}")
 
 
 def acquire_lock(lock_path: str) -> filelock.FileLock:
@@ -509,6 +627,96 @@ def generate_diff(
     return "".join(diff_iter)
 
 
+# --- Partial Edits ---
+# Edits of files at least this large are logged as "partial" operations: the
+# range of bytes that changed and the bytes written over it, not a diff
+PARTIAL_EDIT_MIN_SIZE = 1024 * 1024
+
+
+def changed_byte_range(before: bytes, after: bytes) -> Tuple[int, int, bytes]:
+    """
+    The smallest range start..end of `before` that `after` differs in, and the
+    bytes `after` has in its place.
+    """
+    limit = min(len(before), len(after))
+    start = 0
+    while start < limit and before[start] == after[start]:
+        start += 1
+    # The common suffix may not overlap the common prefix
+    suffix = 0
+    while (
+        suffix < limit - start
+        and before[len(before) - 1 - suffix] == after[len(after) - 1 - suffix]
+    ):
+        suffix += 1
+    return start, len(before) - suffix, after[start : len(after) - suffix]
+
+
+# file_encoding of entries whose diff is stored as is: a unified diff, or the
+# bytes an append added
+TEXT_ENCODING = "text"
+# file_encoding of entries whose unified diff is stored base64-encoded
+BASE64_ENCODING = "base64"
+
+
+def encode_diff(diff_content: str) -> Tuple[bytes, Optional[str]]:
+    """
+    The bytes to store for a unified diff and the entry's file_encoding: the
+    diff as UTF-8 (None, for TEXT_ENCODING), or base64-encoded if it carries
+    bytes that are not valid UTF-8 (read as surrogate escapes), which JSON and
+    UTF-8 tools cannot pass through.
+    """
+    data = diff_content.encode("utf-8", errors="surrogateescape")
+    try:
+        data.decode("utf-8")
+    except UnicodeDecodeError:
+        return base64.b64encode(data), BASE64_ENCODING
+    return data, None
+
+
+# Lines of git's merge conflict markers: `<<<<<<< ours`, `|||||||` (diff3),
+# `=======` and `>>>>>>> theirs`
+_CONFLICT_MARKER_PATTERN = re.compile(r"^(<{7}|\|{7}|={7}|>{7})(?:[ \t]|\r?\n?$)")
+
+
+def find_conflict_markers(lines: List[str]) -> List[Dict[str, int]]:
+    """
+    The conflict blocks left among the lines of a file, as the entry's
+    `conflict_markers`: {"start_line", "end_line"}, 1-based and inclusive.
+    Only blocks with `<<<<<<<`, `=======` and `>>>>>>>` count (as in mcpdiff).
+    """
+    blocks: List[Dict[str, int]] = []
+    start: Optional[int] = None
+    separated = False
+    for number, line in enumerate(lines, 1):
+        match = _CONFLICT_MARKER_PATTERN.match(line)
+        if not match:
+            continue
+        marker = match.group(1)[0]
+        if marker == "<":
+            start, separated = number, False
+        elif marker == "=" and start is not None:
+            separated = True
+        elif marker == ">" and start is not None and separated:
+            blocks.append({"start_line": start, "end_line": number})
+            start = None
+    return blocks
+
+
+# --- Binary Diffs (bsdiff) ---
+# file_encoding of entries whose diff is a bsdiff patch, made by
+# generate_binary_patch() (src/mcp_bsdiff.py)
+BSDIFF_ENCODING = "bsdiff"
+
+
+def is_binary_content(data: bytes) -> bool:
+    """
+    Content with NUL bytes gets a bsdiff patch; other text that is not valid
+    UTF-8 gets a base64-encoded unified diff (see encode_diff()).
+    """
+    return b"\x00" in data
+
+
 def apply_patch(
     diff_content: str, target_file: str, workspace_root: Path, reverse: bool = False
 ) -> bool:
@@ -562,6 +770,37 @@ def apply_patch(
         return False
 
 
+# Paths whose bytes are not valid UTF-8 are logged with a lossy `<field>` and
+# the exact bytes, base64 encoded, in `<field>_bytes` (see DIFFSPEC.md)
+LOG_PATH_FIELDS = ("file_path", "source_path")
+
+
+def encode_log_paths(entry: Dict[str, Any]) -> Dict[str, Any]:
+    """Returns a copy of `entry` with non-UTF-8 paths in the `_bytes` form."""
+    encoded = dict(entry)
+    for field in LOG_PATH_FIELDS:
+        path = encoded.get(field)
+        encoded.pop(f"{field}_bytes", None)
+        if not isinstance(path, str):
+            continue
+        try:
+            path.encode("utf-8")
+        except UnicodeEncodeError:
+            raw = os.fsencode(path)
+            encoded[field] = raw.decode("utf-8", errors="replace")
+            encoded[f"{field}_bytes"] = base64.b64encode(raw).decode("ascii")
+    return encoded
+
+
+def decode_log_paths(entry: Dict[str, Any]) -> Dict[str, Any]:
+    """Restores exact paths from `_bytes` fields in place and returns `entry`."""
+    for field in LOG_PATH_FIELDS:
+        raw = entry.pop(f"{field}_bytes", None)
+        if raw:
+            entry[field] = os.fsdecode(base64.b64decode(raw))
+    return entry
+
+
 def read_log_file(log_file_path: Path) -> List[Dict[str, Any]]:
     """Reads a JSON Lines log file safely."""
     entries = []
@@ -572,7 +811,7 @@ def read_log_file(log_file_path: Path) -> List[Dict[str, Any]]:
             for i, line in enumerate(f):
                 if line.strip():
                     try:
-                        entries.append(json.loads(line))
+                        entries.append(decode_log_paths(json.loads(line)))
                     except json.JSONDecodeError:
                         log.warning(
                             f"Skipping invalid JSON line {i + 1} in {log_file_path}: {line.strip()}"
@@ -584,27 +823,246 @@ def read_log_file(log_file_path: Path) -> List[Dict[str, Any]]:
         raise HistoryError(f"Could not read log file: {log_file_path}") from e
 
 
-def write_log_file(log_file_path: Path, entries: List[Dict[str, Any]]):
-    """Writes a list of entries to a JSON Lines log file atomically."""
+def read_all_shards(history_root: Path, conversation_id: str) -> List[Dict[str, Any]]:
+    """Reads all log files (shards) of a conversation in chronological order."""
+    entries: List[Dict[str, Any]] = []
+    for log_file_path in get_conversation_log_files(history_root, conversation_id):
+        entries.extend(read_log_file(log_file_path))
+    return entries
+
+
+# --- Log Writes ---
+# Logs are replaced through their write-ahead log (see mcp_history_format),
+# which `mcpdiff crash-recover` replays when a crash left it behind.
+
+
+def recover_wals(history_root: Path, conversation_id: str):
+    """
+    Resolves the WALs a crashed write, by the server or mcpdiff, left in a
+    conversation's logs, as `mcpdiff crash-recover` would, so that the server
+    neither reads a log without its WAL nor writes its own WAL over one. The
+    caller holds the conversation's lock, which every writer of its logs holds.
+    """
+    logs_dir = history_root / LOGS_DIR
+    replayed = False
+    for wal_path in sorted(
+        logs_dir.glob(f"{glob.escape(conversation_id)}*{WAL_SUFFIX}")
+    ):
+        log_file_path = history_format.get_wal_log_file(wal_path)
+        if get_log_conversation_id(log_file_path) != conversation_id:
+            continue
+        try:
+            outcome = history_format.resolve_wal(wal_path, log_file_path)
+        except OSError as e:
+            raise HistoryError(f"Could not recover WAL {wal_path.name}: {e}") from e
+        log.warning(f"WAL {wal_path.name} of {log_file_path.name}: {outcome}")
+        replayed = replayed or outcome == "replayed"
+    if replayed:
+        update_merkle_sidecar(history_root, conversation_id)
+
+
+def write_log_file(
+    log_file_path: Path, entries: List[Dict[str, Any]], appended: int = 0
+):
+    """
+    Writes a list of entries to a JSON Lines log file through its write-ahead
+    log, <conv_id>.wal, so a crash cannot lose the write. `appended` is the
+    number of entries at the end of `entries` that are new: the conversation's
+    Merkle root only needs their leaves added. A WAL already there is never
+    overwritten: recover_wals() resolves it first.
+    """
     temp_path = log_file_path.with_suffix(log_file_path.suffix + ".tmp")
+    wal_path = get_wal_path(log_file_path)
+    if wal_path.exists():
+        raise HistoryError(
+            f"Log file {log_file_path} has a WAL left by an interrupted write; "
+            "run `mcpdiff crash-recover`"
+        )
     try:
         log_file_path.parent.mkdir(parents=True, exist_ok=True)  # Ensure dir exists
-        with open(temp_path, "w", encoding="utf-8") as f:
-            for entry in entries:
-                json.dump(entry, f, separators=(",", ":"))  # More compact
-                f.write("\n")
-        # Atomic rename/replace
+        data = b"".join(
+            json.dumps(encode_log_paths(entry), separators=(",", ":")).encode("utf-8")
+            + b"\n"
+            for entry in entries
+        )
+        write_synced(temp_path, data)
+        write_wal(log_file_path, data)
         os.replace(temp_path, log_file_path)
+        fsync_directory(log_file_path.parent)
+        wal_path.unlink()
     except IOError as e:
         log.error(f"Error writing log file {log_file_path}: {e}")
         if temp_path.exists():
             os.remove(temp_path)  # Clean up temp file
+        # The log keeps its content, so the write must not be replayed
+        wal_path.unlink(missing_ok=True)
         raise HistoryError(f"Could not write log file: {log_file_path}") from e
     except Exception as e:
         if temp_path.exists():
             os.remove(temp_path)
+        wal_path.unlink(missing_ok=True)
         log.exception(f"Unexpected error writing log file {log_file_path}: {e}")
         raise HistoryError(f"Unexpected error writing log file: {log_file_path}") from e
+    # Logs live in <history_root>/logs/, or logs/<namespace>/
+    history_root = log_file_path.parents[
+        2 if log_file_path.parent.parent.name == LOGS_DIR else 1
+    ]
+    conversation_id = get_log_conversation_id(log_file_path)
+    # Leaves are in log order, so only entries appended to the last log extend it
+    log_files = get_conversation_log_files(history_root, conversation_id)
+    if appended and log_files and log_files[-1] == log_file_path:
+        update_merkle_sidecar(history_root, conversation_id, entries[-appended:])
+    else:
+        update_merkle_sidecar(history_root, conversation_id)
+
+
+# --- Conversation Merkle Roots ---
+# Computed as mcpdiff does, by mcp_history_format; see DIFFSPEC.md.
+
+
+def compute_merkle_root(history_root: Path, conversation_id: str) -> Dict[str, Any]:
+    """Merkle root over a conversation's stored entries, across all shards."""
+    algorithm = history_format.default_algorithm()
+    entries = [
+        encode_log_paths(entry)
+        for log_file_path in get_conversation_log_files(history_root, conversation_id)
+        for entry in read_log_file(log_file_path)
+    ]
+    peaks = history_format.merkle_peaks(entries, algorithm)
+    return history_format.merkle_sidecar_data(algorithm, peaks, len(entries))
+
+
+def _extend_merkle_root(
+    sidecar: Path, appended: List[Dict[str, Any]]
+) -> Optional[Dict[str, Any]]:
+    """
+    The sidecar's root with the leaves of `appended` added, or None if it has
+    no peaks to add them to (it is missing, or was written by an older server).
+    """
+    algorithm = history_format.default_algorithm()
+    try:
+        with open(sidecar, "r", encoding="utf-8") as f:
+            recorded = json.load(f)
+    except (IOError, json.JSONDecodeError):
+        return None
+    if not isinstance(recorded, dict):
+        return None
+    peaks = history_format.read_sidecar_peaks(recorded, algorithm)
+    if peaks is None:
+        return None
+    leaves = [
+        history_format.leaf_hash(encode_log_paths(entry), algorithm)
+        for entry in appended
+    ]
+    peaks = history_format.add_merkle_leaves(
+        peaks, recorded["leaves"], leaves, algorithm
+    )
+    return history_format.merkle_sidecar_data(
+        algorithm, peaks, recorded["leaves"] + len(leaves)
+    )
+
+
+def update_merkle_sidecar(
+    history_root: Path,
+    conversation_id: str,
+    appended: Optional[List[Dict[str, Any]]] = None,
+):
+    """
+    Brings a conversation's Merkle root in its sidecar up to date: entries
+    `appended` to its last log only have their leaves hashed, anything else
+    has the root recomputed from all shards. Failures are only logged: the log
+    itself has been written, and mcpdiff verify reports the stale root.
+    """
+    sidecar = history_root / LOGS_DIR / f"{conversation_id}{MERKLE_SUFFIX}"
+    temp_path = sidecar.with_suffix(sidecar.suffix + ".tmp")
+    lock = None
+    try:
+        lock = acquire_lock(str(sidecar))
+        result = _extend_merkle_root(sidecar, appended) if appended else None
+        if result is None:
+            result = compute_merkle_root(history_root, conversation_id)
+        result["updated"] = (
+            datetime.now(timezone.utc)
+            .isoformat(timespec="milliseconds")
+            .replace("+00:00", "Z")
+        )
+        with open(temp_path, "w", encoding="utf-8") as f:
+            json.dump(result, f, indent=2, sort_keys=True)
+            f.write("\n")
+        os.replace(temp_path, sidecar)
+    except (IOError, TimeoutError, HistoryError) as e:
+        log.warning(f"Could not update Merkle root of {conversation_id}: {e}")
+        if temp_path.exists():
+            os.remove(temp_path)
+    finally:
+        release_lock(lock)
+
+
+# --- Workspace Structure Hash ---
+# The files workspace_root_hash covers, per workspace: walked once in a while,
+# before an edit takes its locks, and kept up to date with the edits logged in
+# between, so that logging an edit never walks the workspace. The walk is
+# redone when .mcp/ignore changes, and after WORKSPACE_FILES_MAX_AGE seconds to
+# take in files changed outside the server.
+WORKSPACE_FILES_MAX_AGE = 60
+# (workspace root, mtime of .mcp/ignore) -> (time of the walk, paths)
+_workspace_files: Dict[Tuple[str, float], Tuple[float, Set[bytes]]] = {}
+_workspace_files_lock = threading.Lock()
+
+
+def _workspace_files_key(workspace_root: Path) -> Tuple[str, float]:
+    try:
+        ignore_mtime = history_format.get_ignore_path(workspace_root).stat().st_mtime
+    except OSError:
+        ignore_mtime = -1.0
+    return str(workspace_root), ignore_mtime
+
+
+def load_workspace_files(workspace_root: Path):
+    """
+    Walks the workspace for workspace_root_hash(), unless it was walked
+    recently. Call it before taking an edit's locks: the walk takes as long as
+    the workspace is large.
+    """
+    key = _workspace_files_key(workspace_root)
+    with _workspace_files_lock:
+        cached = _workspace_files.get(key)
+        if cached and time.monotonic() - cached[0] < WORKSPACE_FILES_MAX_AGE:
+            return
+    walked_at = time.monotonic()
+    paths = history_format.list_workspace_files(workspace_root)
+    with _workspace_files_lock:
+        for stale in [k for k in _workspace_files if k[0] == key[0]]:
+            del _workspace_files[stale]
+        _workspace_files[key] = (walked_at, paths)
+
+
+def workspace_root_hash(workspace_root: Path, changed_paths: List[Path]) -> str:
+    """
+    The workspace_root_hash after an edit, whose relative `changed_paths` are
+    looked up again in the files load_workspace_files() found. A workspace it
+    has not walked, or not since .mcp/ignore changed, is walked now.
+    """
+    key = _workspace_files_key(workspace_root)
+    with _workspace_files_lock:
+        cached = _workspace_files.get(key)
+    if cached is None:
+        load_workspace_files(workspace_root)
+        with _workspace_files_lock:
+            cached = _workspace_files[key]
+    ignore = load_ignore_matcher(workspace_root)
+    with _workspace_files_lock:
+        paths = cached[1]
+        for changed_path in changed_paths:
+            path = Path(changed_path).as_posix()
+            if ignore.matches(path):
+                continue
+            full_path = workspace_root / changed_path
+            if os.path.lexists(full_path) and not full_path.is_dir():
+                paths.add(os.fsencode(path))
+            else:
+                paths.discard(os.fsencode(path))
+        return history_format.hash_workspace_files(paths)
 
 
 # --- Global Counter and Lock for Tool Call Index ---
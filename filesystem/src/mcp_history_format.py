@@ -0,0 +1,555 @@
+# mcp_history_format.py
+
+"""
+The parts of the edit history format (DIFFSPEC.md) that both the server,
+through mcp_edit_utils, and mcpdiff (cli/) compute: the names of sharded logs,
+.mcp/ignore patterns, conversation Merkle roots, the workspace structure hash
+and write-ahead logs. Both sides import this module, so they cannot drift
+apart.
+
+It needs nothing beyond the standard library (blake3 is optional) and takes no
+locks: each side holds its own around the calls that need them.
+"""
+
+import glob
+import hashlib
+import json
+import logging
+import os
+import re
+from datetime import datetime
+from pathlib import Path
+from typing import Any, Dict, Iterable, List, Optional, Set, Tuple
+
+try:
+    import blake3
+except ImportError:  # Optional; Merkle roots fall back to BLAKE2b
+    blake3 = None
+
+log = logging.getLogger("mcp_history_format")
+
+# --- Log Files ---
+LOGS_DIR = "logs"
+LOG_SHARD_INTERVALS = ("day", "week", "month")
+# <conv_id>_<shard>.log where shard is YYYYMMDD (day), YYYY-Www (week) or YYYYMM (month)
+LOG_SHARD_PATTERN = re.compile(r"^(?P<conv>.+)_(?P<shard>\d{8}|\d{4}-W\d{2}|\d{6})$")
+
+
+def get_log_shard_suffix(interval: Optional[str], now: datetime) -> Optional[str]:
+    """The shard suffix of a log written at `now`; None if not sharding."""
+    if interval == "day":
+        return now.strftime("%Y%m%d")
+    if interval == "week":
+        iso_year, iso_week, _ = now.isocalendar()
+        return f"{iso_year:04d}-W{iso_week:02d}"
+    if interval == "month":
+        return now.strftime("%Y%m")
+    return None
+
+
+def get_log_conversation_id(log_file_path: Path) -> str:
+    """Returns the conversation ID a (possibly sharded) log file belongs to."""
+    match = LOG_SHARD_PATTERN.match(log_file_path.stem)
+    conv_id = match.group("conv") if match else log_file_path.stem
+    if log_file_path.parent.parent.name == LOGS_DIR:
+        return f"{log_file_path.parent.name}/{conv_id}"
+    return conv_id
+
+
+def get_conversation_log_files(history_root: Path, conv_id: str) -> List[Path]:
+    """Returns the unsharded log and all shards of a conversation, oldest first."""
+    logs_dir = history_root / LOGS_DIR
+    if not logs_dir.is_dir():
+        return []
+    files = [
+        p
+        for p in logs_dir.glob(f"{glob.escape(conv_id)}*.log")
+        if get_log_conversation_id(p) == conv_id
+    ]
+    # The unsharded log predates any shards; shard suffixes sort chronologically
+    return sorted(files, key=lambda p: (p.stem != conv_id, p.stem))
+
+
+# --- Ignore File ---
+# <workspace>/.mcp/ignore lists paths, with gitignore-style patterns, that the
+# server logs without checkpoints or diffs and mcpdiff leaves alone.
+IGNORE_FILE_NAME = "ignore"
+
+
+def get_ignore_path(workspace_root: Path) -> Path:
+    return workspace_root / ".mcp" / IGNORE_FILE_NAME
+
+
+def _translate(pattern: str) -> str:
+    """Regex source for a gitignore glob, without anchoring or directory rules."""
+    parts: List[str] = []
+    i = 0
+    while i < len(pattern):
+        c = pattern[i]
+        if pattern.startswith("**/", i) and (i == 0 or pattern[i - 1] == "/"):
+            parts.append("(?:.*/)?")
+            i += 3
+            continue
+        if pattern.startswith("**", i) and i + 2 == len(pattern):
+            parts.append(".*")
+            i += 2
+            continue
+        if c == "*":
+            parts.append("[^/]*")
+        elif c == "?":
+            parts.append("[^/]")
+        elif c == "\\" and i + 1 < len(pattern):
+            i += 1
+            parts.append(re.escape(pattern[i]))
+        elif c == "[":
+            end = pattern.find("]", i + 2)
+            if end == -1:
+                parts.append(re.escape(c))
+            else:
+                members = pattern[i + 1 : end]
+                if members.startswith("!"):
+                    members = "^" + members[1:]
+                parts.append("[" + members.replace("\\", "\\\\") + "]")
+                i = end
+        else:
+            parts.append(re.escape(c))
+        i += 1
+    return "".join(parts)
+
+
+class IgnoreRule:
+    """One line of an ignore file, compiled."""
+
+    def __init__(self, pattern: str):
+        self.pattern = pattern
+        self.negated = pattern.startswith("!")
+        if self.negated:
+            pattern = pattern[1:]
+        elif pattern.startswith("\\!") or pattern.startswith("\\#"):
+            pattern = pattern[1:]
+        # A trailing slash only matches directories
+        self.directory_only = pattern.endswith("/")
+        pattern = pattern.rstrip("/")
+        # A slash anywhere else anchors the pattern at the workspace root;
+        # without one it matches a name at any depth
+        anchored = "/" in pattern
+        pattern = pattern.lstrip("/")
+        prefix = "" if anchored or pattern.startswith("**/") else "(?:.*/)?"
+        self.regex = re.compile(prefix + _translate(pattern) + r"\Z", re.DOTALL)
+
+    def matches(self, path: str, is_dir: bool) -> bool:
+        if self.directory_only and not is_dir:
+            return False
+        return self.regex.match(path) is not None
+
+    def __repr__(self) -> str:
+        return f"IgnoreRule({self.pattern!r})"
+
+
+class IgnoreMatcher:
+    """
+    Decides whether a workspace-relative path is ignored, following gitignore:
+    the last matching rule wins, `!pattern` re-includes a path, and nothing
+    under an ignored directory can be re-included.
+    """
+
+    def __init__(self, lines: List[str]):
+        self.rules: List[IgnoreRule] = []
+        for line in lines:
+            line = line.rstrip("\r\n")
+            if not line.endswith("\\ "):
+                line = line.rstrip(" ")
+            if not line or line.startswith("#"):
+                continue
+            self.rules.append(IgnoreRule(line))
+
+    def __bool__(self) -> bool:
+        return bool(self.rules)
+
+    def _last_match(self, path: str, is_dir: bool) -> bool:
+        ignored = False
+        for rule in self.rules:
+            if rule.negated == ignored and rule.matches(path, is_dir):
+                ignored = not rule.negated
+        return ignored
+
+    def matches(self, path: Optional[str]) -> bool:
+        """Whether the file at `path` (relative, either separator) is ignored."""
+        if not path or not self.rules:
+            return False
+        parts = [p for p in str(path).replace("\\", "/").split("/") if p]
+        for depth in range(1, len(parts)):
+            if self._last_match("/".join(parts[:depth]), is_dir=True):
+                return True
+        return self._last_match("/".join(parts), is_dir=False)
+
+    def matches_in_walk(self, path: str, is_dir: bool) -> bool:
+        """
+        Whether `path` ("/"-separated) is ignored, given that no directory
+        above it is, as in a walk that skips ignored directories.
+        """
+        return bool(self.rules) and self._last_match(path, is_dir)
+
+    def matches_entry(self, entry: Dict[str, str]) -> bool:
+        """Whether an edit touches an ignored path (either end of a move)."""
+        return self.matches(entry.get("file_path")) or self.matches(
+            entry.get("source_path")
+        )
+
+
+# One matcher per ignore file, compiled the first time it is needed and again
+# whenever the file changes
+_matchers: Dict[Tuple[str, float], IgnoreMatcher] = {}
+
+
+def load_ignore_matcher(workspace_root: Path) -> IgnoreMatcher:
+    """The workspace's ignore rules; a missing or unreadable file ignores nothing."""
+    ignore_path = get_ignore_path(workspace_root)
+    try:
+        key = (str(ignore_path), ignore_path.stat().st_mtime)
+    except OSError:
+        return IgnoreMatcher([])
+    if key not in _matchers:
+        try:
+            lines = ignore_path.read_text(encoding="utf-8").splitlines()
+        except (OSError, UnicodeDecodeError) as e:
+            log.warning(f"Ignoring unreadable ignore file {ignore_path}: {e}")
+            lines = []
+        _matchers[key] = IgnoreMatcher(lines)
+    return _matchers[key]
+
+
+# --- Conversation Merkle Trees ---
+# A conversation's log entries, in log order, are the leaves of a Merkle tree
+# whose root is kept in logs/<conv_id>.merkle. Leaves and inner nodes are
+# hashed with distinct prefixes so that a leaf cannot pose as an inner node
+# (as in RFC 6962). A node without a sibling is carried up unchanged, which
+# makes the root a fold of the roots of the perfect subtrees ("peaks") the
+# leaves split into, largest first: the sidecar keeps them, so a writer that
+# appends entries only hashes the new ones.
+
+MERKLE_SUFFIX = ".merkle"
+LEAF_PREFIX = b"\x00"
+NODE_PREFIX = b"\x01"
+# Used when the blake3 package is not installed. The sidecar records which
+# algorithm produced its root, and roots are only compared within one.
+FALLBACK_ALGORITHM = "blake2b-256"
+MERKLE_ALGORITHMS = ("blake3", FALLBACK_ALGORITHM)
+
+
+def default_algorithm() -> str:
+    return "blake3" if blake3 is not None else FALLBACK_ALGORITHM
+
+
+def merkle_hash(data: bytes, algorithm: str) -> bytes:
+    if algorithm == "blake3":
+        if blake3 is None:
+            raise ValueError(
+                "This Merkle root needs the blake3 package (pip install blake3)."
+            )
+        return blake3.blake3(data).digest()
+    if algorithm == FALLBACK_ALGORITHM:
+        return hashlib.blake2b(data, digest_size=32).digest()
+    raise ValueError(f"Unknown Merkle hash algorithm: {algorithm}")
+
+
+def canonical_entry_json(entry: Dict[str, Any]) -> bytes:
+    """
+    The bytes a log entry is hashed as: keys sorted, no whitespace, UTF-8.
+    `entry` must be in its stored form (non-UTF-8 paths encoded, no fields
+    added in memory such as log_file_source).
+    """
+    return json.dumps(
+        entry, sort_keys=True, separators=(",", ":"), ensure_ascii=False
+    ).encode("utf-8")
+
+
+def leaf_hash(entry: Dict[str, Any], algorithm: str) -> bytes:
+    return merkle_hash(LEAF_PREFIX + canonical_entry_json(entry), algorithm)
+
+
+def add_merkle_leaves(
+    peaks: List[bytes], leaves: int, new_leaves: Iterable[bytes], algorithm: str
+) -> List[bytes]:
+    """
+    The peaks of a tree of `leaves` leaves once `new_leaves` are appended: like
+    a binary counter, each leaf merges the peaks of equal size it completes.
+    """
+    peaks = list(peaks)
+    for leaf in new_leaves:
+        peaks.append(leaf)
+        carry = leaves
+        while carry & 1:
+            right = peaks.pop()
+            peaks[-1] = merkle_hash(NODE_PREFIX + peaks[-1] + right, algorithm)
+            carry >>= 1
+        leaves += 1
+    return peaks
+
+
+def merkle_root_of_peaks(peaks: List[bytes], algorithm: str) -> str:
+    """Hex root hash of a tree with these peaks. No peaks hash as b""."""
+    if not peaks:
+        return merkle_hash(b"", algorithm).hex()
+    root = peaks[-1]
+    for peak in reversed(peaks[:-1]):
+        root = merkle_hash(NODE_PREFIX + peak + root, algorithm)
+    return root.hex()
+
+
+def merkle_peaks(entries: List[Dict[str, Any]], algorithm: str) -> List[bytes]:
+    """The peaks of the tree over `entries` in order."""
+    return add_merkle_leaves(
+        [], 0, (leaf_hash(entry, algorithm) for entry in entries), algorithm
+    )
+
+
+def merkle_root(entries: List[Dict[str, Any]], algorithm: str) -> str:
+    """Hex root hash over `entries` in order. An empty list hashes as b""."""
+    return merkle_root_of_peaks(merkle_peaks(entries, algorithm), algorithm)
+
+
+def merkle_sidecar_data(
+    algorithm: str, peaks: List[bytes], leaves: int
+) -> Dict[str, Any]:
+    """The content of a sidecar, but for its `updated` time."""
+    return {
+        "algorithm": algorithm,
+        "root": merkle_root_of_peaks(peaks, algorithm),
+        "leaves": leaves,
+        "peaks": [peak.hex() for peak in peaks],
+    }
+
+
+def read_sidecar_peaks(
+    sidecar: Dict[str, Any], algorithm: str
+) -> Optional[List[bytes]]:
+    """
+    The peaks a sidecar records for its `leaves`, or None if it has none (as
+    sidecars written before they were recorded) or they do not fit them.
+    """
+    leaves = sidecar.get("leaves")
+    peaks = sidecar.get("peaks")
+    if (
+        sidecar.get("algorithm") != algorithm
+        or not isinstance(leaves, int)
+        or isinstance(leaves, bool)
+        or not isinstance(peaks, list)
+        or len(peaks) != bin(leaves).count("1")
+    ):
+        return None
+    try:
+        decoded = [bytes.fromhex(peak) for peak in peaks]
+    except (TypeError, ValueError):
+        return None
+    if merkle_root_of_peaks(decoded, algorithm) != sidecar.get("root"):
+        return None
+    return decoded
+
+
+# --- Workspace Structure Hash ---
+# workspace_root_hash: the sorted relative paths of all files (names only,
+# "/"-separated, as bytes) joined by newlines, hashed like Merkle roots and
+# stored as "<algorithm>:<hex>". Files listed in .mcp/ignore are left out, so
+# generated files and build output neither change it nor slow down the walk.
+# Directories left out, wherever they occur
+WORKSPACE_HASH_EXCLUDED_DIRS = (".git", ".mcp")
+
+
+def list_workspace_files(workspace_root: Path) -> Set[bytes]:
+    """The relative paths of the files workspace_root_hash covers, as bytes."""
+    ignore = load_ignore_matcher(workspace_root)
+    paths = set()
+    for dir_path, dir_names, file_names in os.walk(workspace_root):
+        relative_dir = Path(dir_path).relative_to(workspace_root)
+        dir_names[:] = [
+            d
+            for d in dir_names
+            if d not in WORKSPACE_HASH_EXCLUDED_DIRS
+            and not ignore.matches_in_walk((relative_dir / d).as_posix(), True)
+        ]
+        for name in file_names:
+            path = (relative_dir / name).as_posix()
+            if not ignore.matches_in_walk(path, False):
+                paths.add(os.fsencode(path))
+    return paths
+
+
+def hash_workspace_files(
+    paths: Iterable[bytes], algorithm: Optional[str] = None
+) -> str:
+    """The workspace_root_hash of a workspace with files at `paths`."""
+    algorithm = algorithm or default_algorithm()
+    digest = merkle_hash(b"\n".join(sorted(paths)), algorithm)
+    return f"{algorithm}:{digest.hex()}"
+
+
+def compute_workspace_root_hash(
+    workspace_root: Path, algorithm: Optional[str] = None
+) -> str:
+    """
+    Structural fingerprint of the workspace, logged as workspace_root_hash.
+    Raises ValueError if `algorithm` is not available.
+    """
+    return hash_workspace_files(list_workspace_files(workspace_root), algorithm)
+
+
+# --- Write-Ahead Log ---
+# A log write first stores the new content of the log in <log stem>.wal next to
+# it and syncs that to disk, then replaces the log and deletes the WAL. Renames
+# are not atomic on every platform and file system, so a crash can leave a torn
+# log, but never without the WAL that restores it. The first line of a WAL is a
+# header naming the log, with the SHA-256 of the content after it, so a WAL the
+# crash tore while it was written is recognized; the log was not touched yet
+# then. The header also has the SHA-256 of the log before the write (null if
+# there was none), which tells whether the write reached the log.
+WAL_SUFFIX = ".wal"
+WAL_VERSION = 1
+
+
+def get_wal_path(log_file_path: Path) -> Path:
+    """The write-ahead log of a log file: <conv_id>.wal for <conv_id>.log."""
+    return log_file_path.with_suffix(WAL_SUFFIX)
+
+
+def fsync_directory(directory: Path) -> None:
+    """Syncs the entries of a directory (new and renamed files) to disk."""
+    try:
+        fd = os.open(directory, os.O_RDONLY)
+    except OSError:  # Directories cannot be opened on Windows
+        return
+    try:
+        os.fsync(fd)
+    except OSError:
+        pass
+    finally:
+        os.close(fd)
+
+
+def write_synced(path: Path, data: bytes) -> None:
+    with open(path, "wb") as f:
+        f.write(data)
+        f.flush()
+        os.fsync(f.fileno())
+
+
+def write_wal(log_file_path: Path, data: bytes) -> Path:
+    """Stores `data`, the next content of the log, in its WAL, synced to disk."""
+    header = {
+        "wal_version": WAL_VERSION,
+        "log_file": log_file_path.name,
+        "size": len(data),
+        "sha256": hashlib.sha256(data).hexdigest(),
+        "base_sha256": (
+            hashlib.sha256(log_file_path.read_bytes()).hexdigest()
+            if log_file_path.is_file()
+            else None
+        ),
+    }
+    wal_path = get_wal_path(log_file_path)
+    write_synced(wal_path, json.dumps(header).encode("utf-8") + b"\n" + data)
+    fsync_directory(wal_path.parent)
+    return wal_path
+
+
+def _read_wal(wal_path: Path) -> Tuple[Dict[str, Any], Optional[bytes]]:
+    """The header and content of a WAL; None for content that is damaged."""
+    try:
+        header_line, _, data = wal_path.read_bytes().partition(b"\n")
+        header = json.loads(header_line)
+    except (OSError, ValueError):
+        return {}, None
+    if (
+        not isinstance(header, dict)
+        or header.get("wal_version") != WAL_VERSION
+        or header.get("size") != len(data)
+        or header.get("sha256") != hashlib.sha256(data).hexdigest()
+    ):
+        return {}, None
+    return header, data
+
+
+def read_wal(wal_path: Path) -> Optional[bytes]:
+    """
+    The log content a WAL holds, or None if it is incomplete or damaged, as
+    when a crash interrupted writing it.
+    """
+    return _read_wal(wal_path)[1]
+
+
+def get_wal_log_file(wal_path: Path) -> Path:
+    """The log a WAL was written for, as its header names it."""
+    header, _ = _read_wal(wal_path)
+    log_name = header.get("log_file") or wal_path.with_suffix(".log").name
+    return wal_path.parent / Path(log_name).name
+
+
+def _log_entry_keys(data: bytes) -> Optional[Set[str]]:
+    """
+    The entries of a log's content, each as canonical JSON, or None if a line
+    is not a JSON object (a torn log).
+    """
+    keys = set()
+    for line in data.splitlines():
+        if not line.strip():
+            continue
+        try:
+            entry = json.loads(line)
+        except ValueError:
+            return None
+        if not isinstance(entry, dict):
+            return None
+        keys.add(json.dumps(entry, sort_keys=True))
+    return keys
+
+
+def _is_newer_log(
+    log_file_path: Path, wal_data: bytes, header: Dict[str, Any]
+) -> bool:
+    """
+    Whether the log was written after the WAL's write reached it: it parses and
+    is neither the content the WAL replaces nor the one it holds. WALs without
+    base_sha256 go by entries instead: the log has every entry the WAL has.
+    """
+    if not log_file_path.is_file():
+        return False
+    log_data = log_file_path.read_bytes()
+    log_keys = _log_entry_keys(log_data)
+    if log_keys is None:
+        return False
+    if "base_sha256" in header:
+        return hashlib.sha256(log_data).hexdigest() != header["base_sha256"]
+    wal_keys = _log_entry_keys(wal_data)
+    return wal_keys is not None and wal_keys <= log_keys
+
+
+def resolve_wal(wal_path: Path, log_file_path: Path) -> str:
+    """
+    Deals with a WAL a crash left behind; the caller holds the lock of the
+    log's conversation, so that no write is in flight. Returns what was done:
+    - "replayed": the log was missing or torn, or still had the content the
+      WAL's write replaces, and now holds the WAL's content
+    - "applied": the log already held it, only the WAL was left
+    - "discarded": the WAL was torn, so its write never reached the log
+    - "stale": the log was written after the WAL's write reached it
+    The decision goes by content only: modification times say nothing about
+    the order of writes once a clock is changed or a file is copied. The WAL
+    is deleted in every case.
+    """
+    header, data = _read_wal(wal_path)
+    if data is None:
+        outcome = "discarded"
+    elif log_file_path.is_file() and log_file_path.read_bytes() == data:
+        outcome = "applied"
+    elif _is_newer_log(log_file_path, data, header):
+        outcome = "stale"
+    else:
+        temp_path = log_file_path.with_suffix(
+            log_file_path.suffix + ".tmp" + str(os.getpid())
+        )
+        write_synced(temp_path, data)
+        os.replace(temp_path, log_file_path)
+        fsync_directory(log_file_path.parent)
+        outcome = "replayed"
+    wal_path.unlink()
+    return outcome